@@ -90,4 +90,7 @@ $SED_INPLACE "s/  \"version\": *\"[^\"]*\",/  \"version\": \"$new_version\",/" "
 # Update src-tauri/Cargo.toml
 $SED_INPLACE "s/^version = \"[^\"]*\"/version = \"$new_version\"/" "$escaped_script_dir/src-tauri/Cargo.toml"
 
+# Update src-tauri/engine/Cargo.toml
+$SED_INPLACE "s/^version = \"[^\"]*\"/version = \"$new_version\"/" "$escaped_script_dir/src-tauri/engine/Cargo.toml"
+
 echo "Version updated successfully to $new_version!"
@@ -0,0 +1,200 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+/// How a text file was stored on disk, so it can be written back the same
+/// way
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct TextFormat {
+    /// an encoding_rs label
+    pub encoding: String,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+}
+
+impl Default for TextFormat {
+    fn default() -> Self {
+        TextFormat {
+            encoding: encoding_rs::UTF_8.name().to_owned(),
+            bom: false,
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+/// Bytes read at a time by `read_lines`, the first of which tell the
+/// encoding
+const CHUNK: usize = 64 * 1024;
+
+pub struct Decoded {
+    /// always uses `\n` line endings
+    pub text: String,
+    pub format: TextFormat,
+    /// some bytes were invalid in the encoding and got replaced with U+FFFD
+    pub lossy: bool,
+}
+
+/// A BOM always wins; otherwise ask chardetng
+pub fn detect(buf: &[u8]) -> &'static encoding_rs::Encoding {
+    if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(buf) {
+        return encoding;
+    }
+    let mut det = chardetng::EncodingDetector::new();
+    det.feed(buf, true);
+    det.guess(None, true)
+}
+
+/// Decodes to UTF-8, detecting the encoding if none is given
+pub fn decode(buf: &[u8], encoding: Option<&'static encoding_rs::Encoding>) -> Decoded {
+    let encoding = encoding.unwrap_or_else(|| detect(buf));
+    let (cow, actual, lossy) = encoding.decode(buf);
+    if lossy {
+        log::warn!("decoding as {} was lossy", actual.name());
+    }
+    // whichever is more common wins, so a few stray CRs don't matter
+    let crlf = cow.matches("\r\n").count();
+    let line_ending = if crlf > cow.matches('\n').count() - crlf {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    };
+    Decoded {
+        text: cow.replace("\r\n", "\n"),
+        format: TextFormat {
+            encoding: actual.name().to_owned(),
+            bom: encoding_rs::Encoding::for_bom(buf).is_some(),
+            line_ending,
+        },
+        lossy,
+    }
+}
+
+/// The reverse of `decode`. Returns the bytes and whether any character
+/// was unrepresentable in the target encoding.
+pub fn encode(text: &str, format: &TextFormat) -> Result<(Vec<u8>, bool), String> {
+    let encoding = encoding_rs::Encoding::for_label(format.encoding.as_bytes())
+        .ok_or(format!("invalid encoding: {}", format.encoding))?;
+    let text = match format.line_ending {
+        LineEnding::Lf => text.to_owned(),
+        LineEnding::CrLf => text.replace('\n', "\r\n"),
+    };
+
+    let mut buf = Vec::new();
+    // encoding_rs only decodes UTF-16, so do it by hand
+    if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE {
+        let le = encoding == encoding_rs::UTF_16LE;
+        let units = (format.bom.then_some(0xfeff_u16)).into_iter().chain(text.encode_utf16());
+        for unit in units {
+            buf.extend(if le { unit.to_le_bytes() } else { unit.to_be_bytes() });
+        }
+        return Ok((buf, false));
+    }
+
+    if format.bom && encoding == encoding_rs::UTF_8 {
+        buf.extend([0xef, 0xbb, 0xbf]);
+    }
+    let (cow, _, lossy) = encoding.encode(&text);
+    if lossy {
+        log::warn!("encoding as {} was lossy", encoding.name());
+    }
+    buf.extend_from_slice(&cow);
+    Ok((buf, lossy))
+}
+
+fn for_label(encoding: Option<&str>) -> Result<Option<&'static encoding_rs::Encoding>, String> {
+    match encoding {
+        Some(label) => Some(
+            encoding_rs::Encoding::for_label(label.as_bytes())
+            .ok_or(format!("invalid encoding: {label}"))).transpose(),
+        None => Ok(None)
+    }
+}
+
+/// Reads the file at `path` and decodes it as `encoding`, an encoding_rs
+/// label, or as detected
+pub fn read(path: &Path, encoding: Option<&str>) -> Result<Decoded, String> {
+    let encoding = for_label(encoding)?;
+    let buf = fs::read(path).map_err(|e| e.to_string())?;
+    Ok(decode(buf.as_slice(), encoding))
+}
+
+/// Reads as much of `file` as fits in `buf`, short only at its end
+fn fill(file: &mut File, buf: &mut [u8]) -> Result<usize, String> {
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..]).map_err(|e| e.to_string())? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+/// Like `read`, but a chunk at a time, for files too large to want whole:
+/// each line is given to `line` without its ending, along with how much
+/// of the file has been read. Only the first chunk is looked at to detect
+/// the encoding. Returns how the file was stored and whether decoding was
+/// lossy, as in `Decoded`.
+pub fn read_lines(
+    path: &Path, encoding: Option<&str>, mut line: impl FnMut(&str, f64)
+) -> Result<(TextFormat, bool), String> {
+    let encoding = for_label(encoding)?;
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len().max(1);
+    let mut buf = vec![0; CHUNK];
+    let mut len = fill(&mut file, &mut buf)?;
+    let bom = encoding_rs::Encoding::for_bom(&buf[..len]).is_some();
+    let mut decoder = encoding.unwrap_or_else(|| detect(&buf[..len])).new_decoder();
+
+    let mut text = String::new();
+    let (mut read, mut lossy, mut lf, mut crlf) = (0, false, 0, 0);
+    loop {
+        let last = len < CHUNK;
+        read += len;
+        text.reserve(decoder.max_utf8_buffer_length(len).unwrap_or(len * 3));
+        let (_, _, replaced) = decoder.decode_to_string(&buf[..len], &mut text, last);
+        lossy |= replaced;
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = read as f64 / size as f64;
+        let mut start = 0;
+        while let Some(end) = text[start..].find('\n') {
+            let end = start + end;
+            lf += 1;
+            let x = &text[start..end];
+            let x = x.strip_suffix('\r').inspect(|_| crlf += 1).unwrap_or(x);
+            line(x, fraction);
+            start = end + 1;
+        }
+        if last {
+            if start < text.len() {
+                line(&text[start..], fraction);
+            }
+            break;
+        }
+        text.drain(..start);
+        len = fill(&mut file, &mut buf)?;
+    }
+
+    if lossy {
+        log::warn!("decoding as {} was lossy", decoder.encoding().name());
+    }
+    // as in `decode`
+    let line_ending = if crlf > lf - crlf { LineEnding::CrLf } else { LineEnding::Lf };
+    let format = TextFormat {
+        encoding: decoder.encoding().name().to_owned(),
+        bom,
+        line_ending,
+    };
+    Ok((format, lossy))
+}
@@ -0,0 +1,194 @@
+//! The ways into the engine that most tools need, for using it without the
+//! app: a bot that times subtitles to the audio, say, or a service that
+//! runs QC on uploads. An `Engine` is had once ffmpeg is set up, and its
+//! methods open media and run the analyses that the app's commands do,
+//! without the app's ids, channels or sandbox. Reading, editing and
+//! writing subtitle files needs no ffmpeg, so those are functions of
+//! `Engine` called without one; the app's commands for them go through
+//! these too. Documents are plain values, changed by `Edit`s as in the app;
+//! the modules underneath are public too, for all the rest, like
+//! `subtitle::qc` and `subtitle::query`, which take a document as it is.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::encoding::{self, TextFormat};
+use crate::media::backend::{self, BackendKind, MediaBackend};
+use crate::media::conform::{self, ConformReport};
+use crate::media::drift::{self, DriftReport};
+use crate::media::internal::MediaError;
+use crate::media::units::Seconds;
+use crate::media::{audio, audio_class};
+use crate::subtitle::document::{Document, ParseIssue, SubtitleFormat};
+use crate::subtitle::edit::Edit;
+use crate::subtitle::lead::{self, LeadConfig};
+use crate::subtitle::parse::{self, Detection, Unparsed};
+use crate::subtitle::retime::{self, Retime};
+use crate::subtitle::{ass, convert, microdvd, sami};
+
+#[derive(Debug)]
+pub enum EngineError {
+    Media(MediaError),
+    /// a subtitle file that couldn't be read as any format; see `parse`
+    Unparsed(Unparsed),
+    /// writing MicroDVD, which counts frames, for a document without a
+    /// framerate
+    FramerateRequired,
+    /// an edit that couldn't be applied, after `applied` that were
+    Edit { applied: usize, reason: String },
+    /// reading or writing a file, or an encoding not known
+    Io(String),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Media(e) => write!(f, "{e}"),
+            EngineError::Unparsed(Unparsed::FramerateRequired)
+                | EngineError::FramerateRequired => write!(f, "framerate required"),
+            EngineError::Unparsed(Unparsed::UnknownFormat) => write!(f, "unknown format"),
+            EngineError::Edit { applied, reason } =>
+                write!(f, "edit {applied}: {reason}"),
+            EngineError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<MediaError> for EngineError {
+    fn from(e: MediaError) -> Self {
+        EngineError::Media(e)
+    }
+}
+
+/// A subtitle file as read by `Engine::open_subtitle`
+pub struct Opened {
+    /// with the text format it was stored in, for writing it back the same
+    /// way
+    pub document: Document,
+    pub issues: Vec<ParseIssue>,
+    pub detection: Detection,
+    /// some bytes were invalid in the encoding and got replaced with U+FFFD
+    pub lossy_decoding: bool,
+}
+
+/// Proof that ffmpeg was set up; see the module's doc. Holds nothing, so it
+/// can be made wherever it's wanted.
+#[derive(Clone, Copy, Debug)]
+pub struct Engine {
+    _ready: (),
+}
+
+impl Engine {
+    /// Sets up ffmpeg, the first time in the process
+    pub fn new() -> Result<Engine, EngineError> {
+        static INIT: OnceLock<Result<(), ffmpeg::Error>> = OnceLock::new();
+        match *INIT.get_or_init(ffmpeg::init) {
+            Ok(()) => Ok(Engine { _ready: () }),
+            Err(e) => Err(EngineError::Media(MediaError::FFMpegError {
+                func: "ffmpeg::init".to_owned(), e, line: line!()
+            })),
+        }
+    }
+
+    /// Reads the subtitle file at `path`, as `encoding`, an encoding_rs
+    /// label, or as detected; `framerate` is for MicroDVD files and
+    /// `progress` is called as ASS files are parsed, see `parse::LineParser`
+    pub fn open_subtitle(
+        path: &Path, encoding: Option<&str>, framerate: Option<f64>,
+        progress: impl FnMut(f64),
+    ) -> Result<Opened, EngineError> {
+        let mut parser = parse::LineParser::new(framerate, progress);
+        let (format, lossy) = encoding::read_lines(path, encoding, |line, fraction| {
+            parser.feed_line(line, fraction);
+        }).map_err(EngineError::Io)?;
+        let (result, detection) = parser.finish().map_err(EngineError::Unparsed)?;
+        let mut document = result.document;
+        document.text_format = format;
+        Ok(Opened { document, issues: result.issues, detection, lossy_decoding: lossy })
+    }
+
+    /// The document as text in its own format
+    pub fn write_subtitle(document: &Document) -> Result<String, EngineError> {
+        Ok(match document.format {
+            SubtitleFormat::Ass => ass::write(document),
+            SubtitleFormat::Sami => sami::write(document),
+            SubtitleFormat::MicroDvd => {
+                let framerate = document.framerate.ok_or(EngineError::FramerateRequired)?;
+                microdvd::write(document, framerate)
+            }
+            SubtitleFormat::Srt => convert::write_srt(document),
+        })
+    }
+
+    /// Writes the document to `path` in its own format, stored as
+    /// `text_format` or as it was read. Returns whether some characters
+    /// couldn't be represented in the encoding.
+    pub fn save_subtitle(
+        document: &Document, path: &Path, text_format: Option<&TextFormat>,
+    ) -> Result<bool, EngineError> {
+        let text = Self::write_subtitle(document)?;
+        let (buf, lossy) = encoding::encode(&text, text_format.unwrap_or(&document.text_format))
+            .map_err(EngineError::Io)?;
+        fs::write(path, buf).map_err(|e| EngineError::Io(e.to_string()))?;
+        Ok(lossy)
+    }
+
+    /// Applies `edits` in order, stopping at the first that fails
+    pub fn apply(document: &mut Document, edits: &[Edit]) -> Result<(), EngineError> {
+        for (applied, edit) in edits.iter().enumerate() {
+            document.apply(edit).map_err(|reason| EngineError::Edit { applied, reason })?;
+        }
+        Ok(())
+    }
+
+    /// Moves and stretches every event by `retime`; see `retime::suggest`
+    pub fn retime(document: &mut Document, retime: Retime) -> Result<(), EngineError> {
+        let edits = retime::suggest(document, retime);
+        Self::apply(document, &edits)
+    }
+
+    /// Opens the media file at `path` for decoding, with no players yet;
+    /// see `MediaBackend`
+    pub fn open_media(&self, path: &Path) -> Result<Box<dyn MediaBackend>, EngineError> {
+        Ok(backend::open(BackendKind::Ffmpeg, path)?)
+    }
+
+    /// Stretches of speech in audio stream `stream` of the file at `path`,
+    /// or its default one; see `audio_class`. `progress` is called with the
+    /// fraction read so far, and returns `false` to give up.
+    pub fn speech(
+        &self, path: &Path, stream: Option<usize>, progress: impl FnMut(f64) -> bool,
+    ) -> Result<Vec<(Seconds, Seconds)>, EngineError> {
+        let waveform = audio::classified_waveform(path, stream, audio_class::FRAME_RATE, progress)?;
+        #[allow(clippy::cast_precision_loss)]
+        let speech = audio_class::speech_segments(&waveform.classes, waveform.start_time,
+            waveform.sample_per_second as f64, audio_class::SPEECH_BRIDGE);
+        Ok(speech)
+    }
+
+    /// Edits giving the events of `document` lead-in and lead-out from
+    /// where speech starts and stops in the default audio of the file at
+    /// `media`, as `suggest_lead` does in the app; see `lead::suggest`
+    pub fn suggest_lead(
+        &self, document: &Document, media: &Path, cuts: &[Seconds], config: &LeadConfig,
+    ) -> Result<Vec<Edit>, EngineError> {
+        let speech = self.speech(media, None, |_| true)?;
+        Ok(lead::suggest(document, &speech, cuts, config))
+    }
+
+    /// Whether the default audio of the file at `path` drifts against its
+    /// video; see `drift::measure`
+    pub fn measure_drift(&self, path: &Path) -> Result<DriftReport, EngineError> {
+        Ok(drift::measure(path, None, None, |_| true)?)
+    }
+
+    /// How times in the file at `old` map to the same moments in `new`,
+    /// another release of it; see `conform::compare`
+    pub fn compare_releases(&self, old: &Path, new: &Path) -> Result<ConformReport, EngineError> {
+        Ok(conform::compare(old, None, new, |_| true)?)
+    }
+}
@@ -0,0 +1,17 @@
+//! What Subtle does with media and subtitles, apart from the app around it:
+//! decoding and analysing audio and video with ffmpeg, and reading,
+//! editing, checking and writing subtitle documents. The app's tauri
+//! commands are built on it, adding ids, channels and the sandbox; other
+//! tools can embed it too, starting from `Engine`.
+
+#![allow(clippy::needless_pass_by_value)]
+#![allow(clippy::used_underscore_binding)]
+
+extern crate ffmpeg_next as ffmpeg;
+
+pub mod encoding;
+pub mod engine;
+pub mod media;
+pub mod subtitle;
+
+pub use engine::{Engine, EngineError};
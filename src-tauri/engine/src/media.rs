@@ -0,0 +1,41 @@
+pub mod internal;
+pub mod accel;
+pub mod frame;
+pub mod demux;
+pub mod units;
+pub mod audio;
+pub mod video;
+pub mod subpicture;
+pub mod still;
+pub mod compare;
+pub mod session;
+pub mod backend;
+pub mod group;
+pub mod symphonia_backend;
+pub mod mux;
+pub mod verify;
+pub mod availability;
+pub mod capabilities;
+pub mod delta;
+pub mod surface;
+pub mod images;
+pub mod test_media;
+pub mod selfcheck;
+pub mod waveform;
+pub mod seek_index;
+pub mod audio_class;
+pub mod record;
+pub mod monitor;
+pub mod timecode;
+pub mod damage;
+pub mod drift;
+pub mod prefetch;
+pub mod color;
+pub mod conform;
+
+mod aggregation_tree;
+mod loudness;
+mod disjoint_interval_set;
+
+#[cfg(test)]
+mod tests;
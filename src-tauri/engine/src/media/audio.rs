@@ -0,0 +1,962 @@
+use std::collections::VecDeque;
+
+use enum_dispatch::enum_dispatch;
+use ffmpeg::{codec, error::EAGAIN, format, software::{self, resampling}, ChannelLayout, Rational};
+use getset::{CopyGetters, Getters};
+use log::{debug, warn};
+use num_traits::ToPrimitive;
+
+use crate::media::{aggregation_tree::AggregationTree, audio_class::{self, AudioClass}, damage::Damage, demux, frame, internal::{check, MediaError}, loudness::LoudnessMeter, units};
+
+#[derive(Getters, CopyGetters)]
+pub struct Decoder {
+    inner: codec::decoder::Audio,
+
+    #[getset(get = "pub")]
+    stream_info: demux::StreamInfo,
+
+    /// will be inaccurate in case of VFR
+    #[getset(get_copy = "pub")]
+    sample_rate: u32,
+
+    /// number of samples
+    #[getset(get_copy = "pub")]
+    estimated_length: usize,
+
+    /// since the decoder was opened
+    #[getset(get = "pub")]
+    damage: Damage,
+}
+
+impl Decoder {
+    #[tracing::instrument(name = "audio::open", skip_all)]
+    pub fn create(
+        demuxer: &demux::Demuxer, index: Option<usize>
+    ) -> Result<Decoder, MediaError> {
+        let (stream_info, stream) = match index {
+            Some(i) => demuxer.get_stream_from_index(i),
+            None => demuxer.get_stream_from_kind(demux::StreamKind::Audio)
+        }?;
+
+        // create decoder
+        let codecxt = check!(codec::Context::from_parameters(stream.parameters()))?;
+        let mut codec = check!(codecxt.decoder().audio())?;
+        check!(codec.set_parameters(stream.parameters()))?;
+        codec.set_packet_time_base(stream.time_base());
+
+        let timebase = codec.time_base();
+        if timebase != Rational(1, codec.rate().try_into().unwrap()) {
+            warn!("create_audio_base: time_base = {timebase} but rate = {}", codec.rate());
+        }
+
+        let estimated_length = 
+            units::Timestamp::from_seconds(demuxer.duration(), timebase)
+            .0.to_usize().unwrap();
+
+        debug!(
+            "create_audio_base: [{}] len={}, decoder_tb={}, stream_tb={}, format={}, layout=0x{:x}",
+            stream_info.index(),
+            estimated_length,
+            timebase,
+            stream.time_base(),
+            codec.format().name(),
+            codec.channel_layout().bits()
+        );
+
+        Ok(Decoder {
+            stream_info,
+            estimated_length,
+            sample_rate: codec.rate(),
+            inner: codec,
+            damage: Damage::default(),
+        })
+    }
+
+    pub fn flush(&mut self) {
+        self.inner.flush();
+        self.stream_info.byte_pos_can_update = true;
+        self.stream_info.byte_pos = -1;
+    }
+
+    #[tracing::instrument(name = "audio::feed", skip_all)]
+    pub fn feed(&mut self, packet: &demux::Packet) -> Result<(), MediaError> {
+        if self.stream_info.byte_pos_can_update {
+            self.stream_info.byte_pos = packet.position();
+        }
+        match self.inner.send_packet(packet) {
+            Ok(()) => Ok(()),
+            Err(ffmpeg_next::Error::Other { errno: EAGAIN }) => {
+                warn!("audio::Decoder::feed: some frames haven't been read! flushing.");
+                self.flush();
+                // resend packet
+                self.feed(packet)
+            }
+            // damaged in the file; decoding goes on with the next
+            Err(ffmpeg_next::Error::InvalidData) => {
+                let time = units::Timestamp(packet.pts().or(packet.dts()).unwrap_or(0))
+                    .to_seconds(self.stream_info.timebase());
+                debug!("audio::Decoder::feed: [{}] invalid packet at {time}", self.stream_info.index());
+                self.damage.record_error(time);
+                Ok(())
+            }
+            send_packet_error => check!(send_packet_error),
+        }
+    }
+
+    #[tracing::instrument(name = "audio::receive", skip_all)]
+    pub fn try_receive(&mut self) -> Result<Option<frame::Audio>, MediaError> {
+        let mut decoded = frame::AudioData::empty();
+        let mut byte_pos: isize = -1;
+        match self.inner.receive_frame(&mut decoded) {
+            Ok(()) => {
+                byte_pos = self.stream_info.byte_pos;
+                self.stream_info.byte_pos_can_update = true;
+            },
+            Err(ffmpeg_next::Error::Other { errno: EAGAIN }) => {
+                return Ok(None);
+            }
+            receive_frame_error => check!(receive_frame_error)?,
+        }
+
+        let time = units::Timestamp(
+            decoded
+            .pts()
+            .ok_or(MediaError::InternalError(
+                "decoded frame has no pts".to_owned(),
+            ))?
+        ).to_seconds(self.stream_info.timebase());
+        if decoded.flags().contains(ffmpeg_next::frame::Flags::CORRUPT) {
+            self.damage.record_corrupt(time);
+        }
+
+        Ok(Some(frame::Audio {
+            meta: frame::FrameMetadata {
+                time, byte_pos,
+                pkt_pos: decoded.packet().position,
+            },
+            decoded,
+        }))
+    }
+}
+
+#[enum_dispatch]
+pub enum AudioSinkKind {
+    Player,
+    Sampler,
+    SilenceDetector
+}
+
+#[enum_dispatch(AudioSinkKind)]
+pub trait AudioSink {
+    fn clear(&mut self);
+    fn is_empty(&self) -> bool;
+    fn process(&mut self, frame: frame::Audio) -> Result<(), MediaError>;
+}
+
+/// The most makeup gain, either way, that auto gain applies
+const MAX_GAIN_DB: f64 = 20.0;
+/// How fast auto gain follows the measurement, so it doesn't pump
+const GAIN_SLEW_DB_PER_SECOND: f64 = 3.0;
+
+/// Makeup gain for monitoring: brings the loudness measured so far towards a
+/// target. It only touches what is played, never the analysis paths.
+struct AutoGain {
+    target_lufs: f64,
+    meter: LoudnessMeter,
+    gain_db: f64,
+}
+
+impl AutoGain {
+    #[allow(clippy::cast_possible_truncation)]
+    fn apply(&mut self, samples: &mut [f32], sample_rate: u32) {
+        self.meter.feed(samples);
+        let Some(measured) = self.meter.integrated() else { return };
+
+        let wanted = (self.target_lufs - measured).clamp(-MAX_GAIN_DB, MAX_GAIN_DB);
+        #[allow(clippy::cast_precision_loss)]
+        let max_step = GAIN_SLEW_DB_PER_SECOND * samples.len() as f64 / f64::from(sample_rate);
+        let from = self.gain_db;
+        self.gain_db += (wanted - from).clamp(-max_step, max_step);
+
+        // ramp across the frame to avoid zipper noise
+        #[allow(clippy::cast_precision_loss)]
+        let length = samples.len().max(1) as f64;
+        for (i, x) in samples.iter_mut().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let db = from + (self.gain_db - from) * (i as f64 / length);
+            let gain = 10f64.powf(db / 20.0) as f32;
+            *x = (*x * gain).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+pub struct Player {
+    resampler: resampling::Context,
+    sample_rate: u32,
+    auto_gain: Option<AutoGain>,
+    frames: VecDeque<frame::Audio>
+}
+
+impl AudioSink for Player {
+    fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    #[tracing::instrument(name = "audio::resample", skip_all)]
+    fn process(&mut self, mut frame: frame::Audio) -> Result<(), MediaError> {
+        let mut processed = frame::AudioData::empty();
+        check!(self.resampler.run(&frame.decoded, &mut processed))?;
+        if let Some(auto_gain) = &mut self.auto_gain {
+            auto_gain.apply(processed.plane_mut::<f32>(0), self.sample_rate);
+        }
+        frame.decoded = processed;
+        self.frames.push_back(frame);
+        Ok(())
+    }
+}
+
+impl Player {
+    pub fn create(decoder: &Decoder) -> Result<Self, MediaError> {
+        // Same target as original: F32 packed, MONO, keep source rate
+        let resampler = check!(software::resampler(
+            (
+                decoder.inner.format(),
+                decoder.inner.channel_layout(),
+                decoder.sample_rate()
+            ),
+            (
+                format::Sample::F32(format::sample::Type::Packed),
+                ChannelLayout::MONO,
+                decoder.sample_rate()
+            )
+        ))?;
+        Ok(Self { 
+            resampler,
+            sample_rate: decoder.sample_rate(),
+            auto_gain: None,
+            frames: VecDeque::new()
+        })
+    }
+
+    pub fn get_delta(&mut self) -> VecDeque<frame::Audio> {
+        std::mem::take(&mut self.frames)
+    }
+
+    /// The loudness auto gain aims for, if it is on
+    pub fn auto_gain(&self) -> Option<f64> {
+        self.auto_gain.as_ref().map(|x| x.target_lufs)
+    }
+
+    /// `None` turns auto gain off. The loudness measured so far is kept when
+    /// only the target changes.
+    pub fn set_auto_gain(&mut self, target_lufs: Option<f64>) {
+        match (target_lufs, &mut self.auto_gain) {
+            (None, _) => self.auto_gain = None,
+            (Some(target), Some(x)) => x.target_lufs = target,
+            (Some(target), None) => self.auto_gain = Some(AutoGain {
+                target_lufs: target,
+                meter: LoudnessMeter::new(self.sample_rate),
+                gain_db: 0.0,
+            }),
+        }
+    }
+}
+
+pub struct Sampler {
+    resampler: resampling::Context,
+    intensities: Intensities,
+}
+
+/// Peak levels on a grid of `per_second` values from `start_time`, filled
+/// in from mono samples as they come, in whatever order seeking brings
+/// them. Kept apart from `Sampler` for backends that decode without ffmpeg.
+pub struct Intensities {
+    start_time: units::Seconds,
+    /// values per second; need not be whole
+    per_second: f64,
+    intensities: AggregationTree<f32, fn(f32, f32) -> f32>,
+    /// where the last frame ended, to continue from
+    next_time: Option<f64>,
+
+    data: Option<SamplerDeltaData>,
+}
+
+#[derive(Clone, serde::Serialize, Debug, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+#[ts(rename = "AudioSamplerDeltaData")]
+pub struct SamplerDeltaData {
+    pub start_index: usize,
+    pub start_time: units::Seconds,
+    pub end_time: units::Seconds,
+    pub intensity: Vec<f32>,
+}
+
+impl Intensities {
+    pub fn new(start_time: units::Seconds, duration: units::Seconds, per_second: f64) -> Self {
+        // capacity: ceil(duration_seconds * per_second)
+        let capacity = (duration.0 * per_second).ceil().to_usize().unwrap_or(0);
+        let intensities = AggregationTree::new(capacity, f32::max as fn(f32, f32) -> f32, f32::NAN);
+
+        debug!("audio::Intensities::new: capacity={capacity}, per_second={per_second}, start_time={start_time}");
+
+        Self {
+            start_time,
+            per_second,
+            intensities,
+            next_time: None,
+            data: None,
+        }
+    }
+
+    pub fn get_delta(&mut self) -> Option<SamplerDeltaData> {
+        std::mem::take(&mut self.data)
+    }
+
+    pub fn clear(&mut self) {
+        self.data = None;
+        self.next_time = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_none()
+    }
+
+    /// `data` is mono, at `rate`, and starts at `time`
+    pub fn add(&mut self, time: f64, rate: f64, data: &[f32]) {
+        // Timestamps are rounded to the stream's timebase, so a frame that
+        // follows on from the last may seem to start a sample early or late.
+        // Counting on from where the last one ended keeps values from being
+        // split or doubled at frame boundaries.
+        let start = match self.next_time {
+            Some(x) if (time - x).abs() < 1.5 / rate => x,
+            _ => time,
+        };
+        self.next_time = Some(start + data.len().to_f64().unwrap() / rate);
+        let index_at = |i: usize| {
+            (self.per_second * (start - self.start_time.0 + i.to_f64().unwrap() / rate)).floor()
+        };
+
+        let start_index_signed = index_at(0).to_isize().unwrap();
+        if start_index_signed < 0 {
+            // ignore out-of-bound data
+            return;
+        }
+
+        let start_index = start_index_signed.to_usize().unwrap();
+        if start_index > self.intensities.length {
+            // ignore out-of-bound data
+            return;
+        }
+
+        // Lazily init delta container
+        if self.data.is_none() {
+            self.data = Some(SamplerDeltaData {
+                start_index,
+                start_time: units::Seconds(start),
+                end_time: units::Seconds(start),
+                intensity: Vec::new(),
+            });
+        }
+
+        let sd = self.data.as_mut().unwrap();
+        let mut index = start_index;
+        let mut sum = self.intensities.at(index);
+
+        for (i, sample) in data.iter().enumerate() {
+            let new_index = index_at(i).to_usize().unwrap();
+
+            if new_index != index {
+                self.intensities.set(&[sum], index);
+
+                let expected_next = sd.start_index + sd.intensity.len();
+                if expected_next > index {
+                    // warn!("audio::Sampler::process: {expected_next} > {index}; revising");
+                    sd.intensity[index - sd.start_index] = sum;
+                } else {
+                    if expected_next < index {
+                        // warn!("audio::Sampler::process: {expected_next} < {index}; filling with 0s");
+                    }
+                    while sd.start_index + sd.intensity.len() < index {
+                        sd.intensity.push(0.0);
+                    }
+                    sd.intensity.push(sum);
+                    sd.end_time = units::Seconds(start + i.to_f64().unwrap() / rate);
+                }
+                index = new_index;
+                if index >= self.intensities.length {
+                    return;
+                }
+                sum = self.intensities.at(index);
+            }
+
+            sum = sum.max(sample.abs());
+        }
+
+        self.intensities.set(&[sum], index);
+    }
+}
+
+impl Sampler {
+    pub fn create(decoder: &Decoder, per_second: f64) -> Result<Self, MediaError> {
+        let resampler = check!(software::resampler(
+            (
+                decoder.inner.format(),
+                decoder.inner.channel_layout(),
+                decoder.sample_rate()
+            ),
+            (
+                format::Sample::F32(format::sample::Type::Packed),
+                ChannelLayout::MONO,
+                decoder.sample_rate()
+            )
+        ))?;
+
+        let duration = decoder.estimated_length().to_f64().unwrap()
+            / f64::from(decoder.sample_rate());
+        let start_time = decoder.stream_info().start_time_seconds();
+        let intensities = Intensities::new(start_time, units::Seconds(duration), per_second);
+        Ok(Self { resampler, intensities })
+    }
+
+    pub fn get_delta(&mut self) -> Option<SamplerDeltaData> {
+        self.intensities.get_delta()
+    }
+}
+
+impl AudioSink for Sampler {
+    fn clear(&mut self) {
+        self.intensities.clear();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.intensities.is_empty()
+    }
+
+    fn process(&mut self, frame: frame::Audio) -> Result<(), MediaError> {
+        let mut processed = frame::AudioData::empty();
+        check!(self.resampler.run(&frame.decoded, &mut processed))?;
+        self.intensities.add(frame.meta.time.0, processed.rate().into(), processed.plane(0));
+        Ok(())
+    }
+}
+/// Peak levels of one stream, on a time grid given by the caller, such as
+/// the one `intensity_pair` shares between two
+struct PeakTrack {
+    decoder: Decoder,
+    resampler: resampling::Context,
+    peaks: Vec<f32>,
+    /// time of the first sample decoded
+    first_time: Option<f64>,
+    /// fed the same mono samples, if the audio is to be labelled as well
+    classifier: Option<audio_class::Classifier>,
+}
+
+impl PeakTrack {
+    fn create(demuxer: &demux::Demuxer, index: Option<usize>) -> Result<Self, MediaError> {
+        let decoder = Decoder::create(demuxer, index)?;
+        let resampler = check!(software::resampler(
+            (
+                decoder.inner.format(),
+                decoder.inner.channel_layout(),
+                decoder.sample_rate()
+            ),
+            (
+                format::Sample::F32(format::sample::Type::Packed),
+                ChannelLayout::MONO,
+                decoder.sample_rate()
+            )
+        ))?;
+        Ok(Self { decoder, resampler, peaks: Vec::new(), first_time: None, classifier: None })
+    }
+
+    /// `origin` is the time of the first value
+    fn add(&mut self, frame: &frame::Audio, origin: f64, sample_per_second: f64) -> Result<(), MediaError> {
+        let mut processed = frame::AudioData::empty();
+        check!(self.resampler.run(&frame.decoded, &mut processed))?;
+        let rate = f64::from(processed.rate());
+        let data: &[f32] = processed.plane(0);
+        self.first_time.get_or_insert(frame.meta.time.0);
+        if let Some(classifier) = &mut self.classifier {
+            classifier.add(data);
+        }
+        for (i, sample) in data.iter().enumerate() {
+            let time = frame.meta.time.0 + i.to_f64().unwrap() / rate - origin;
+            let Some(index) = (time * sample_per_second).to_usize() else { continue };
+            if index >= self.peaks.len() {
+                self.peaks.resize(index + 1, 0.0);
+            }
+            self.peaks[index] = self.peaks[index].max(sample.abs());
+        }
+        Ok(())
+    }
+
+    /// A label for each of `peaks`, which count from `origin`; the
+    /// classifier's count from the first sample decoded, which may be
+    /// before or after it
+    fn classes(&mut self, origin: f64, sample_per_second: f64) -> Vec<AudioClass> {
+        let length = self.peaks.len();
+        let Some(classifier) = self.classifier.take() else {
+            return vec![AudioClass::Silence; length];
+        };
+        let shift = self.first_time.map_or(0.0, |t| ((t - origin) * sample_per_second).round());
+        let mut classes = if shift >= 0.0 {
+            let offset = shift.to_usize().unwrap_or(0).min(length);
+            let mut classes = vec![AudioClass::Silence; offset];
+            classes.extend(classifier.finish(sample_per_second, length - offset));
+            classes
+        } else {
+            let skip = (-shift).to_usize().unwrap_or(0);
+            let mut classes = classifier.finish(sample_per_second, length + skip);
+            classes.drain(..skip);
+            classes
+        };
+        classes.resize(length, AudioClass::Silence);
+        classes
+    }
+}
+
+#[derive(Clone, serde::Serialize, Debug, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct IntensityPair {
+    /// time of the first value of both
+    pub start_time: units::Seconds,
+    pub sample_per_second: usize,
+    pub a: Vec<f32>,
+    pub b: Vec<f32>,
+}
+
+/// Peak intensity of two audio streams of the file at `path` on a common
+/// time grid, decoding both in a single read of the file; for laying a dub
+/// over its original. `progress` gets the fraction done now and then, and
+/// returns `false` to give up.
+pub fn intensity_pair(
+    path: &std::path::Path, streams: (usize, usize), sample_per_second: usize,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<IntensityPair, MediaError> {
+    let mut demuxer = demux::Demuxer::open(path)?;
+    let mut a = PeakTrack::create(&demuxer, Some(streams.0))?;
+    let mut b = PeakTrack::create(&demuxer, Some(streams.1))?;
+    let start_time = a.decoder.stream_info().start_time_seconds().0
+        .min(b.decoder.stream_info().start_time_seconds().0);
+    let per_second = sample_per_second.to_f64().unwrap();
+    let expected = demuxer.duration().0 * per_second;
+
+    let mut reported = 0.0;
+    while let Some((i, packet)) = demuxer.next_packet() {
+        for track in [&mut a, &mut b] {
+            if track.decoder.stream_info().index() != i {
+                continue;
+            }
+            track.decoder.feed(&packet)?;
+            while let Some(frame) = track.decoder.try_receive()? {
+                track.add(&frame, start_time, per_second)?;
+            }
+        }
+        let done = a.peaks.len().min(b.peaks.len()).to_f64().unwrap() / expected;
+        if done - reported >= 0.01 {
+            reported = done;
+            if !progress(done.min(1.0)) {
+                return Err(MediaError::Cancelled);
+            }
+        }
+    }
+    for track in [&mut a, &mut b] {
+        check!(track.decoder.inner.send_eof())?;
+        // ends in an EOF error once everything is out
+        while let Ok(Some(frame)) = track.decoder.try_receive() {
+            track.add(&frame, start_time, per_second)?;
+        }
+    }
+
+    let length = a.peaks.len().max(b.peaks.len());
+    a.peaks.resize(length, 0.0);
+    b.peaks.resize(length, 0.0);
+    progress(1.0);
+    Ok(IntensityPair {
+        start_time: units::Seconds(start_time),
+        sample_per_second,
+        a: a.peaks,
+        b: b.peaks,
+    })
+}
+
+#[derive(Clone, serde::Serialize, Debug, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ClassifiedWaveform {
+    /// time of the first value
+    pub start_time: units::Seconds,
+    pub sample_per_second: usize,
+    pub peaks: Vec<f32>,
+    /// what each stretch of `peaks` sounds like, one for each
+    pub classes: Vec<AudioClass>,
+}
+
+/// Peak intensity of an audio stream of the file at `path`, each value
+/// labelled as speech, music or silence; for tinting the timeline and
+/// jumping between dialogue. `progress` works as in `intensity_pair`.
+pub fn classified_waveform(
+    path: &std::path::Path, stream: Option<usize>, sample_per_second: usize,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<ClassifiedWaveform, MediaError> {
+    let mut demuxer = demux::Demuxer::open(path)?;
+    let mut track = PeakTrack::create(&demuxer, stream)?;
+    track.classifier = Some(audio_class::Classifier::new(track.decoder.sample_rate()));
+    let start_time = track.decoder.stream_info().start_time_seconds().0;
+    let index = track.decoder.stream_info().index();
+    let per_second = sample_per_second.max(1).to_f64().unwrap();
+    let expected = demuxer.duration().0 * per_second;
+
+    let mut reported = 0.0;
+    while let Some((i, packet)) = demuxer.next_packet() {
+        if i != index {
+            continue;
+        }
+        track.decoder.feed(&packet)?;
+        while let Some(frame) = track.decoder.try_receive()? {
+            track.add(&frame, start_time, per_second)?;
+        }
+        let done = track.peaks.len().to_f64().unwrap() / expected;
+        if done - reported >= 0.01 {
+            reported = done;
+            if !progress(done.min(1.0)) {
+                return Err(MediaError::Cancelled);
+            }
+        }
+    }
+    check!(track.decoder.inner.send_eof())?;
+    // ends in an EOF error once everything is out
+    while let Ok(Some(frame)) = track.decoder.try_receive() {
+        track.add(&frame, start_time, per_second)?;
+    }
+
+    let classes = track.classes(start_time, per_second);
+    progress(1.0);
+    Ok(ClassifiedWaveform {
+        start_time: units::Seconds(start_time),
+        sample_per_second: sample_per_second.max(1),
+        peaks: track.peaks,
+        classes,
+    })
+}
+
+/// `classified_waveform` of just `from` to `to`, sought to rather than read
+/// up to, so that it's quick enough to ask for while dragging. The noise
+/// floor is that of the stretch rather than the whole file, which makes the
+/// labels a little less sure.
+pub fn classified_window(
+    path: &std::path::Path, stream: Option<usize>, (from, to): (units::Seconds, units::Seconds),
+    sample_per_second: usize,
+) -> Result<ClassifiedWaveform, MediaError> {
+    let mut demuxer = demux::Demuxer::open(path)?;
+    let mut track = PeakTrack::create(&demuxer, stream)?;
+    track.classifier = Some(audio_class::Classifier::new(track.decoder.sample_rate()));
+    let index = track.decoder.stream_info().index();
+    let per_second = sample_per_second.max(1).to_f64().unwrap();
+    // lands on or before `from`; what comes before is left out of the peaks
+    demuxer.seek_stream(from, track.decoder.stream_info())?;
+
+    'read: while let Some((i, packet)) = demuxer.next_packet() {
+        if i != index {
+            continue;
+        }
+        track.decoder.feed(&packet)?;
+        while let Some(frame) = track.decoder.try_receive()? {
+            let past = frame.meta.time.0 >= to.0;
+            track.add(&frame, from.0, per_second)?;
+            if past {
+                break 'read;
+            }
+        }
+    }
+
+    let length = ((to.0 - from.0) * per_second).ceil().to_usize().unwrap_or(0);
+    track.peaks.resize(length, 0.0);
+    let classes = track.classes(from.0, per_second);
+    Ok(ClassifiedWaveform {
+        start_time: from,
+        sample_per_second: sample_per_second.max(1),
+        peaks: track.peaks,
+        classes,
+    })
+}
+
+/// Lowest and highest sample of every `samples_per_pixel` samples, mixed
+/// down to mono, counting from the first sample decoded
+struct MinMaxTrack {
+    resampler: resampling::Context,
+    samples_per_pixel: usize,
+    count: usize,
+    peaks: Vec<(f32, f32)>,
+}
+
+impl MinMaxTrack {
+    fn add(&mut self, frame: &frame::Audio) -> Result<(), MediaError> {
+        let mut processed = frame::AudioData::empty();
+        check!(self.resampler.run(&frame.decoded, &mut processed))?;
+        let data: &[f32] = processed.plane(0);
+        for &sample in data {
+            if self.count % self.samples_per_pixel == 0 {
+                self.peaks.push((sample, sample));
+            }
+            let last = self.peaks.last_mut().unwrap();
+            *last = (last.0.min(sample), last.1.max(sample));
+            self.count += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Minimum and maximum per `samples_per_pixel` samples of an audio stream
+/// of the file at `path`, and the sample rate they were counted at; for
+/// waveform export. `progress` works as in `intensity_pair`.
+pub fn min_max_peaks(
+    path: &std::path::Path, stream: Option<usize>, samples_per_pixel: usize,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<(u32, Vec<(f32, f32)>), MediaError> {
+    let mut demuxer = demux::Demuxer::open(path)?;
+    let mut decoder = Decoder::create(&demuxer, stream)?;
+    let resampler = check!(software::resampler(
+        (
+            decoder.inner.format(),
+            decoder.inner.channel_layout(),
+            decoder.sample_rate()
+        ),
+        (
+            format::Sample::F32(format::sample::Type::Packed),
+            ChannelLayout::MONO,
+            decoder.sample_rate()
+        )
+    ))?;
+    let mut track = MinMaxTrack {
+        resampler,
+        samples_per_pixel: samples_per_pixel.max(1),
+        count: 0,
+        peaks: Vec::new(),
+    };
+    let index = decoder.stream_info().index();
+    let expected = decoder.estimated_length().max(1).to_f64().unwrap();
+
+    let mut reported = 0.0;
+    while let Some((i, packet)) = demuxer.next_packet() {
+        if i != index {
+            continue;
+        }
+        decoder.feed(&packet)?;
+        while let Some(frame) = decoder.try_receive()? {
+            track.add(&frame)?;
+        }
+        let done = track.count.to_f64().unwrap() / expected;
+        if done - reported >= 0.01 {
+            reported = done;
+            if !progress(done.min(1.0)) {
+                return Err(MediaError::Cancelled);
+            }
+        }
+    }
+    check!(decoder.inner.send_eof())?;
+    // ends in an EOF error once everything is out
+    while let Ok(Some(frame)) = decoder.try_receive() {
+        track.add(&frame)?;
+    }
+    progress(1.0);
+    Ok((decoder.sample_rate(), track.peaks))
+}
+
+/// Decodes an audio stream of the file at `path`, or the default one, to
+/// stereo at `sample_rate`, handing it to `consume` along with
+/// the time of its first sample as it comes. `progress` works as in
+/// `intensity_pair`.
+pub fn decode_stereo(
+    path: &std::path::Path, stream: Option<usize>, sample_rate: u32,
+    consume: impl FnMut(f64, &[(f32, f32)]) -> Result<(), MediaError>,
+    progress: impl FnMut(f64) -> bool,
+) -> Result<(), MediaError> {
+    decode_stereo_within(path, stream, sample_rate, None, consume, progress)
+}
+
+/// `decode_stereo` of just `from` to `to`, sought to rather than read up
+/// to; it starts at or before `from`, and ends once past `to`
+pub fn decode_stereo_window(
+    path: &std::path::Path, stream: Option<usize>, sample_rate: u32,
+    window: (units::Seconds, units::Seconds),
+    consume: impl FnMut(f64, &[(f32, f32)]) -> Result<(), MediaError>,
+) -> Result<(), MediaError> {
+    decode_stereo_within(path, stream, sample_rate, Some(window), consume, |_| true)
+}
+
+fn decode_stereo_within(
+    path: &std::path::Path, stream: Option<usize>, sample_rate: u32,
+    window: Option<(units::Seconds, units::Seconds)>,
+    mut consume: impl FnMut(f64, &[(f32, f32)]) -> Result<(), MediaError>,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<(), MediaError> {
+    let mut demuxer = demux::Demuxer::open(path)?;
+    let mut decoder = Decoder::create(&demuxer, stream)?;
+    let mut resampler = check!(software::resampler(
+        (
+            decoder.inner.format(),
+            decoder.inner.channel_layout(),
+            decoder.sample_rate()
+        ),
+        (
+            format::Sample::F32(format::sample::Type::Packed),
+            ChannelLayout::STEREO,
+            sample_rate
+        )
+    ))?;
+    let index = decoder.stream_info().index();
+    let duration = demuxer.duration().0;
+    if let Some((from, _)) = window {
+        demuxer.seek_stream(from, decoder.stream_info())?;
+    }
+
+    let mut handle = |frame: &frame::Audio| -> Result<(), MediaError> {
+        let mut processed = frame::AudioData::empty();
+        check!(resampler.run(&frame.decoded, &mut processed))?;
+        consume(frame.meta.time.0, processed.plane(0))
+    };
+    let mut reported = 0.0;
+    'read: while let Some((i, packet)) = demuxer.next_packet() {
+        if i != index {
+            continue;
+        }
+        decoder.feed(&packet)?;
+        while let Some(frame) = decoder.try_receive()? {
+            handle(&frame)?;
+            if window.is_some_and(|(_, to)| frame.meta.time.0 >= to.0) {
+                break 'read;
+            }
+            let done = frame.meta.time.0 / duration;
+            if done - reported >= 0.01 {
+                reported = done;
+                if !progress(done.min(1.0)) {
+                    return Err(MediaError::Cancelled);
+                }
+            }
+        }
+    }
+    check!(decoder.inner.send_eof())?;
+    // ends in an EOF error once everything is out
+    while let Ok(Some(frame)) = decoder.try_receive() {
+        handle(&frame)?;
+    }
+    progress(1.0);
+    Ok(())
+}
+
+/// Peak level, about -50 dBFS, under which audio counts as silent
+const SILENCE_LEVEL: f32 = 0.003;
+/// Length of the windows whose peaks are compared against `SILENCE_LEVEL`,
+/// so that zero crossings don't count as silence
+const SILENCE_WINDOW: f64 = 0.02;
+
+/// Finds the stretches where the audio stays below `SILENCE_LEVEL`
+pub struct SilenceDetector {
+    resampler: resampling::Context,
+    window_length: usize,
+    min_duration: f64,
+
+    /// peak and sample count of the window being filled
+    window: (f32, usize),
+    silent_since: Option<units::Seconds>,
+    intervals: Vec<(units::Seconds, units::Seconds)>,
+    last_time: Option<units::Seconds>,
+}
+
+impl SilenceDetector {
+    pub fn create(decoder: &Decoder, min_duration: units::Seconds) -> Result<Self, MediaError> {
+        let resampler = check!(software::resampler(
+            (
+                decoder.inner.format(),
+                decoder.inner.channel_layout(),
+                decoder.sample_rate()
+            ),
+            (
+                format::Sample::F32(format::sample::Type::Packed),
+                ChannelLayout::MONO,
+                decoder.sample_rate()
+            )
+        ))?;
+        let window_length = (f64::from(decoder.sample_rate()) * SILENCE_WINDOW)
+            .to_usize().unwrap().max(1);
+        Ok(Self {
+            resampler,
+            window_length,
+            min_duration: min_duration.0,
+            window: (0.0, 0),
+            silent_since: None,
+            intervals: Vec::new(),
+            last_time: None,
+        })
+    }
+
+    pub fn last_time(&self) -> Option<units::Seconds> {
+        self.last_time
+    }
+
+    fn end_silence(&mut self, time: units::Seconds) {
+        if let Some(start) = self.silent_since.take()
+            && time.0 - start.0 >= self.min_duration
+        {
+            self.intervals.push((start, time));
+        }
+    }
+
+    /// Takes the silences found so far, including one still ongoing
+    pub fn get_delta(&mut self) -> Vec<(units::Seconds, units::Seconds)> {
+        if let Some(time) = self.last_time {
+            let ongoing = self.silent_since;
+            self.end_silence(time);
+            self.silent_since = ongoing;
+        }
+        std::mem::take(&mut self.intervals)
+    }
+}
+
+impl AudioSink for SilenceDetector {
+    fn clear(&mut self) {
+        self.window = (0.0, 0);
+        self.silent_since = None;
+        self.intervals.clear();
+        self.last_time = None;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.last_time.is_none()
+    }
+
+    fn process(&mut self, frame: frame::Audio) -> Result<(), MediaError> {
+        let mut processed = frame::AudioData::empty();
+        check!(self.resampler.run(&frame.decoded, &mut processed))?;
+        let rate: f64 = processed.rate().into();
+
+        for (i, sample) in processed.plane::<f32>(0).iter().enumerate() {
+            let (peak, count) = &mut self.window;
+            *peak = peak.max(sample.abs());
+            *count += 1;
+            if *count < self.window_length {
+                continue;
+            }
+            let silent = *peak < SILENCE_LEVEL;
+            self.window = (0.0, 0);
+
+            let time = units::Seconds(frame.meta.time.0 + i.to_f64().unwrap() / rate);
+            match (silent, self.silent_since) {
+                (true, None) => self.silent_since =
+                    Some(units::Seconds(time.0 - SILENCE_WINDOW)),
+                (false, Some(_)) =>
+                    self.end_silence(units::Seconds(time.0 - SILENCE_WINDOW)),
+                _ => (),
+            }
+            self.last_time = Some(time);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+//! Files that are still being written, typically by a torrent client. Such
+//! clients preallocate the whole file and fill pieces in any order, and the
+//! unwritten parts read as zeros; so a block that is zero throughout is
+//! taken as missing. A finished file that really contains such a block
+//! would never count as complete, but media files practically never do.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::media::{demux, internal::MediaError, units::{self, Seconds}};
+
+const BLOCK_SIZE: usize = 16 * 1024;
+/// How often rechecking is allowed when playback runs into missing data
+const RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How many missing blocks a refresh reads again at most, 64 MiB, so that a file that is mostly missing doesn't hold up
+/// playback for as long as reading all of it would; later refreshes go on
+/// where one stopped
+const REFRESH_BLOCKS: usize = 4096;
+/// Of those, how many are always the first missing ones, where playback is
+/// waiting
+const FRONTIER_BLOCKS: usize = 256;
+
+pub struct Availability {
+    path: PathBuf,
+    length: u64,
+    /// one per block of the file as it was last checked
+    present: Vec<bool>,
+    last_check: Instant,
+    /// the block the next refresh goes on from, past the frontier
+    cursor: usize,
+
+    /// reads ahead through the available prefix to learn how far in time it
+    /// goes; `None` until the file can be opened at all
+    probe: Option<demux::Demuxer>,
+    timebases: Vec<units::Rational>,
+    /// the probe read a packet beyond the prefix, and with it zeros where
+    /// data is yet to come; it reads again from `playable_until` next time
+    probe_stalled: bool,
+    playable_until: Seconds,
+}
+
+struct BlockReader {
+    file: File,
+    buffer: Vec<u8>,
+    /// where the file is at, to seek only when skipping
+    position: Option<u64>,
+}
+
+impl BlockReader {
+    /// Whether block `i` has anything but zeros in it
+    fn is_written(&mut self, i: usize) -> io::Result<bool> {
+        let offset = (i * BLOCK_SIZE) as u64;
+        if self.position != Some(offset) {
+            self.file.seek(SeekFrom::Start(offset))?;
+        }
+        let read = read_block(&mut self.file, &mut self.buffer)?;
+        self.position = Some(offset + read as u64);
+        Ok(self.buffer[..read].iter().any(|&x| x != 0))
+    }
+}
+
+impl Availability {
+    pub fn scan(path: &Path) -> io::Result<Self> {
+        let mut result = Self {
+            path: path.to_owned(),
+            length: 0,
+            present: Vec::new(),
+            last_check: Instant::now(),
+            cursor: 0,
+            probe: None,
+            timebases: Vec::new(),
+            probe_stalled: false,
+            playable_until: Seconds(0.0),
+        };
+        // the whole file, once
+        result.refresh_within(usize::MAX)?;
+        Ok(result)
+    }
+
+    /// Reads blocks that were missing again, and any the file grew by: the
+    /// first missing ones, then as many more as `REFRESH_BLOCKS` allows
+    pub fn refresh(&mut self) -> io::Result<()> {
+        self.refresh_within(REFRESH_BLOCKS)
+    }
+
+    fn refresh_within(&mut self, budget: usize) -> io::Result<()> {
+        self.last_check = Instant::now();
+        let file = File::open(&self.path)?;
+        self.length = file.metadata()?.len();
+        let blocks = usize::try_from(self.length.div_ceil(BLOCK_SIZE as u64)).unwrap();
+        self.present.resize(blocks, false);
+
+        let Some(frontier) = self.present.iter().position(|&x| !x) else {
+            self.advance_probe();
+            return Ok(());
+        };
+        let mut reader = BlockReader { file, buffer: vec![0; BLOCK_SIZE], position: None };
+        let mut read = 0;
+        let mut i = frontier;
+        while i < blocks && i < frontier + FRONTIER_BLOCKS {
+            if !self.present[i] {
+                self.present[i] = reader.is_written(i)?;
+                read += 1;
+            }
+            i += 1;
+        }
+        // round the rest of the file, from where the last refresh stopped
+        let start = self.cursor.clamp(i, blocks);
+        self.cursor = 0;
+        for j in (start..blocks).chain(i..start) {
+            if read >= budget {
+                self.cursor = j;
+                break;
+            }
+            if !self.present[j] {
+                self.present[j] = reader.is_written(j)?;
+                read += 1;
+            }
+        }
+        self.advance_probe();
+        Ok(())
+    }
+
+    /// Refreshes unless that was done less than `RECHECK_INTERVAL` ago;
+    /// returns whether it did
+    pub fn refresh_if_due(&mut self) -> io::Result<bool> {
+        if self.last_check.elapsed() < RECHECK_INTERVAL {
+            return Ok(false);
+        }
+        self.refresh()?;
+        Ok(true)
+    }
+
+    /// Length of the part at the start of the file that has been written
+    pub fn contiguous_bytes(&self) -> u64 {
+        let blocks = self.present.iter().take_while(|&&x| x).count();
+        ((blocks * BLOCK_SIZE) as u64).min(self.length)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.present.iter().all(|&x| x)
+    }
+
+    pub fn playable_until(&self) -> Seconds {
+        self.playable_until
+    }
+
+    /// Whether the `size` bytes of a packet at byte `position` have all
+    /// been written
+    pub fn covers(&self, position: isize, size: usize) -> bool {
+        if self.is_complete() {
+            return true;
+        }
+        let Ok(start) = usize::try_from(position) else { return false };
+        let end = start + size.max(1);
+        let blocks = start / BLOCK_SIZE..end.div_ceil(BLOCK_SIZE);
+        end as u64 <= self.length
+            && self.present.get(blocks).is_some_and(|x| x.iter().all(|&x| x))
+    }
+
+    fn advance_probe(&mut self) {
+        if self.probe.is_none() {
+            // headers may not be there yet
+            let Ok(probe) = demux::Demuxer::open(&self.path) else { return };
+            self.timebases = (0..probe.describe_streams().len())
+                .map(|i| probe.get_stream_from_index(i)
+                    .map_or(units::DEFAULT_TIMEBASE, |(x, _)| x.timebase()))
+                .collect();
+            self.probe = Some(probe);
+        }
+        let limit = self.contiguous_bytes();
+        let probe = self.probe.as_mut().unwrap();
+        if self.present.iter().all(|&x| x) {
+            self.probe_stalled = false;
+            self.playable_until = probe.duration();
+            return;
+        }
+        if self.probe_stalled {
+            // what was read as zeros is read again
+            probe.discard_buffer();
+            if let Err(e) = probe.seek(self.playable_until) {
+                log::warn!("availability: cannot go back to {}: {e}", self.playable_until);
+                return;
+            }
+            self.probe_stalled = false;
+        }
+        while let Some((index, packet)) = probe.next_packet() {
+            let end = u64::try_from(packet.position()).ok()
+                .map(|x| x + packet.size() as u64);
+            if end.is_none_or(|x| x > limit) {
+                self.probe_stalled = true;
+                break;
+            }
+            if let Some(pts) = packet.pts()
+                && let Some(&timebase) = self.timebases.get(index)
+            {
+                let time = units::Timestamp(pts).to_seconds(timebase);
+                if time > self.playable_until {
+                    self.playable_until = time;
+                }
+            }
+        }
+    }
+
+    /// The error to report for a position past what is available
+    pub fn unavailable(&self) -> MediaError {
+        MediaError::DataNotYetAvailable { playable_until: self.playable_until }
+    }
+}
+
+/// Fills as much of `buffer` as the file has left
+fn read_block(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
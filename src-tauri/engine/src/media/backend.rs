@@ -0,0 +1,264 @@
+//! What the command layer needs of an open file, whatever decodes it.
+//! Playing and sampling audio, seeking and describing the file go through
+//! `MediaBackend`; everything else, such as video, subpictures and the
+//! analysis sinks, is only done by ffmpeg and reached through
+//! `MediaBackend::session_mut`.
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::{audio::{self, AudioSink, AudioSinkKind}, damage::StreamDamage, demux, frame, group::MediaGroup, internal::MediaError, session::{Session, Snapshot}, symphonia_backend::SymphoniaBackend, units, video::{VideoSink, VideoSinkKind}};
+
+/// Which backend to open a file with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum BackendKind {
+    #[default]
+    Ffmpeg,
+    /// audio only, in pure Rust; for when ffmpeg is missing or broken
+    Symphonia,
+}
+
+pub struct MediaStatus {
+    pub audio_index: Option<usize>,
+    pub video_index: Option<usize>,
+    pub audio_only: bool,
+    pub duration: units::Seconds,
+    pub streams: Vec<demux::StreamDescription>,
+    /// as the file gives it; see `Demuxer::start_timecode`
+    pub start_timecode: Option<String>,
+}
+
+pub struct AudioStatus {
+    pub index: usize,
+    /// number of samples, estimated
+    pub length: usize,
+    pub start_time: units::Seconds,
+    pub sample_rate: u32,
+}
+
+pub struct VideoStatus {
+    pub index: usize,
+    pub framerate: f64,
+    pub is_vfr: bool,
+    pub start_time: units::Seconds,
+    pub sample_aspect_ratio: f64,
+    pub size: (u32, u32),
+}
+
+/// Mono samples for the audio player, at the stream's own rate
+#[derive(Clone)]
+pub struct AudioBlock {
+    pub time: units::Seconds,
+    pub pkt_pos: i64,
+    pub samples: Vec<f32>,
+}
+
+pub trait MediaBackend: Send {
+    fn path(&self) -> &Path;
+    fn status(&self) -> MediaStatus;
+    fn snapshot(&self) -> Snapshot;
+
+    fn seek(&mut self, time: units::Seconds) -> Result<(), MediaError>;
+    fn open_audio_player(&mut self, index: Option<usize>) -> Result<AudioStatus, MediaError>;
+    fn open_audio_sampler(
+        &mut self, index: Option<usize>, per_second: f64
+    ) -> Result<AudioStatus, MediaError>;
+
+    /// `MediaError::NoVideo` for backends that only decode audio
+    fn open_video_player(
+        &mut self, _index: Option<usize>, _accel: bool
+    ) -> Result<VideoStatus, MediaError> {
+        Err(MediaError::NoVideo)
+    }
+    /// Sets the size the video player scales to; `Ok(false)` without one
+    fn set_video_size(&mut self, _size: (u32, u32)) -> Result<bool, MediaError> {
+        Ok(false)
+    }
+
+    /// Decodes for about `budget`, and past it until some sink has something
+    /// to give; `Ok(false)` at the end of the file
+    fn decode(&mut self, budget: Duration) -> Result<bool, MediaError>;
+    /// What the audio player has got since last time
+    fn take_audio(&mut self) -> VecDeque<AudioBlock>;
+    /// What the audio sampler, or a sampler tap on the player, has got
+    /// since last time
+    fn take_audio_samples(&mut self) -> Option<audio::SamplerDeltaData>;
+    /// What the video player has got since last time, unless it draws to
+    /// a surface
+    fn take_video(&mut self) -> VecDeque<frame::Video> {
+        VecDeque::new()
+    }
+    /// The damage decoding has come across in the streams being decoded,
+    /// for those with some; see `damage`
+    fn damage(&self) -> Vec<StreamDamage> {
+        Vec::new()
+    }
+
+    /// The ffmpeg session underneath, if this is one
+    fn session_mut(&mut self) -> Option<&mut Session> {
+        None
+    }
+
+    /// Whether it plays several files as one, so that times in `path`
+    /// aren't its own; see `group`
+    fn is_group(&self) -> bool {
+        false
+    }
+}
+
+pub fn open(kind: BackendKind, path: &Path) -> Result<Box<dyn MediaBackend>, MediaError> {
+    match kind {
+        BackendKind::Ffmpeg => Ok(Box::new(Session::create(path)?)),
+        BackendKind::Symphonia => Ok(Box::new(SymphoniaBackend::create(path)?)),
+    }
+}
+
+/// Reopens a snapshot with the backend that took it; see `Session::restore`
+pub fn restore(snapshot: &Snapshot) -> Result<Box<dyn MediaBackend>, MediaError> {
+    if !snapshot.members.is_empty() {
+        return Ok(Box::new(MediaGroup::restore(snapshot)?));
+    }
+    match snapshot.backend {
+        BackendKind::Ffmpeg => Ok(Box::new(Session::restore(snapshot)?)),
+        BackendKind::Symphonia => Ok(Box::new(SymphoniaBackend::restore(snapshot)?)),
+    }
+}
+
+fn audio_status(decoder: &audio::Decoder) -> AudioStatus {
+    AudioStatus {
+        index: decoder.stream_info().index(),
+        length: decoder.estimated_length(),
+        start_time: decoder.stream_info().start_time_seconds(),
+        sample_rate: decoder.sample_rate(),
+    }
+}
+
+impl MediaBackend for Session {
+    fn path(&self) -> &Path {
+        Session::path(self)
+    }
+
+    fn status(&self) -> MediaStatus {
+        MediaStatus {
+            audio_index: self.audio().map(|(d, _)| d.stream_info().index()),
+            video_index: self.video().map(|(d, _)| d.stream_info().index()),
+            audio_only: self.is_audio_only(),
+            duration: self.demuxer().duration(),
+            streams: self.demuxer().describe_streams(),
+            start_timecode: self.demuxer().start_timecode(),
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Session::snapshot(self)
+    }
+
+    fn damage(&self) -> Vec<StreamDamage> {
+        let audio = self.audio().map(|(d, _)| (d.stream_info().index(), d.damage()));
+        let video = self.video().map(|(d, _)| (d.stream_info().index(), d.damage()));
+        audio.into_iter().chain(video)
+            .filter(|(_, x)| !x.is_empty())
+            .map(|(index, x)| x.report(index))
+            .collect()
+    }
+
+    fn seek(&mut self, time: units::Seconds) -> Result<(), MediaError> {
+        Session::seek(self, time)
+    }
+
+    fn open_audio_player(&mut self, index: Option<usize>) -> Result<AudioStatus, MediaError> {
+        Session::open_audio_player(self, index)?;
+        Ok(audio_status(&self.audio().unwrap().0))
+    }
+
+    fn open_audio_sampler(
+        &mut self, index: Option<usize>, per_second: f64
+    ) -> Result<AudioStatus, MediaError> {
+        Session::open_audio_sampler(self, index, per_second)?;
+        Ok(audio_status(&self.audio().unwrap().0))
+    }
+
+    fn open_video_player(
+        &mut self, index: Option<usize>, accel: bool
+    ) -> Result<VideoStatus, MediaError> {
+        Session::open_video_player(self, index, accel)?;
+        let d = &self.video().unwrap().0;
+        Ok(VideoStatus {
+            index: d.stream_info().index(),
+            framerate: d.framerate().into(),
+            is_vfr: d.is_vfr(),
+            start_time: d.stream_info().start_time_seconds(),
+            sample_aspect_ratio: d.sample_aspect_ratio().into(),
+            size: d.original_size(),
+        })
+    }
+
+    fn set_video_size(&mut self, size: (u32, u32)) -> Result<bool, MediaError> {
+        let Some((_, VideoSinkKind::Player(p))) = self.video_mut() else { return Ok(false) };
+        p.set_output_size(size)?;
+        Ok(true)
+    }
+
+    fn decode(&mut self, budget: Duration) -> Result<bool, MediaError> {
+        let start_time = Instant::now();
+        loop {
+            self.try_process()?;
+            if start_time.elapsed() >= budget
+                && (self.audio().is_none_or(|(_, s)| !s.is_empty())
+                || self.video().is_none_or(|(_, s)| !s.is_empty()))
+            {
+                return Ok(true);
+            }
+            if !self.try_feed()? {
+                return Ok(false);
+            }
+        }
+    }
+
+    fn take_audio(&mut self) -> VecDeque<AudioBlock> {
+        let Some((_, AudioSinkKind::Player(p))) = self.audio_mut() else {
+            return VecDeque::new();
+        };
+        p.get_delta().into_iter()
+            .map(|x| AudioBlock {
+                time: x.meta.time,
+                pkt_pos: x.meta.pkt_pos,
+                samples: x.decoded.plane::<f32>(0).to_vec(),
+            })
+            .collect()
+    }
+
+    fn take_audio_samples(&mut self) -> Option<audio::SamplerDeltaData> {
+        match self.audio_mut() {
+            Some((_, AudioSinkKind::Sampler(s))) => s.get_delta(),
+            // through a tap; what the player has queued is left for playback
+            Some((_, AudioSinkKind::Player(_))) => {
+                self.audio_taps_mut().iter_mut().find_map(|x| match x {
+                    AudioSinkKind::Sampler(s) => s.get_delta(),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn take_video(&mut self) -> VecDeque<frame::Video> {
+        // with a surface, `present_surface` takes them instead
+        if self.has_surface() {
+            return VecDeque::new();
+        }
+        match self.video_mut() {
+            Some((_, VideoSinkKind::Player(p))) => p.get_delta(),
+            _ => VecDeque::new(),
+        }
+    }
+
+    fn session_mut(&mut self) -> Option<&mut Session> {
+        Some(self)
+    }
+}
@@ -0,0 +1,104 @@
+//! What the linked ffmpeg can actually do. Builds differ a lot, especially
+//! those from package managers: without libass there is no hardsub export,
+//! without zimg no HDR tone mapping, and so on. The frontend greys out what
+//! is missing instead of letting a job fail halfway through.
+
+use std::ffi::CStr;
+use std::ptr::null;
+use std::sync::OnceLock;
+
+use ffmpeg::{codec, encoder, filter};
+use log::info;
+use serde::Serialize;
+
+use crate::media::accel::HardwareDecoder;
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Capabilities {
+    pub version: String,
+    /// burning ASS subtitles into the picture, with libass's `subtitles`
+    /// filter
+    pub hardsub: bool,
+    /// HDR to SDR, with `zscale` and `tonemap` or with `libplacebo`
+    pub hdr_tone_mapping: bool,
+    pub h264_encoding: bool,
+    pub hevc_encoding: bool,
+    pub png_export: bool,
+    pub jpeg_export: bool,
+    /// the timecode in generated test clips, with `drawtext`
+    pub burnt_in_timecode: bool,
+    pub image_sequences: bool,
+    pub matroska_muxing: bool,
+    pub mp4_muxing: bool,
+    pub hardware_decoders: Vec<String>,
+    /// every component looked for and not found, such as `filter:subtitles`
+    /// or `encoder:hevc`, for bug reports
+    pub missing: Vec<String>,
+}
+
+struct Probe {
+    missing: Vec<String>,
+}
+
+impl Probe {
+    fn found(&mut self, kind: &str, name: &str, present: bool) -> bool {
+        if !present {
+            self.missing.push(format!("{kind}:{name}"));
+        }
+        present
+    }
+
+    fn filter(&mut self, name: &str) -> bool {
+        self.found("filter", name, filter::find(name).is_some())
+    }
+
+    fn encoder(&mut self, id: codec::Id) -> bool {
+        self.found("encoder", &format!("{id:?}").to_lowercase(), encoder::find(id).is_some())
+    }
+
+    fn demuxer(&mut self, name: &CStr) -> bool {
+        let format = unsafe { ffmpeg_sys_next::av_find_input_format(name.as_ptr()) };
+        self.found("demuxer", &name.to_string_lossy(), !format.is_null())
+    }
+
+    fn muxer(&mut self, name: &CStr) -> bool {
+        let format = unsafe { ffmpeg_sys_next::av_guess_format(name.as_ptr(), null(), null()) };
+        self.found("muxer", &name.to_string_lossy(), !format.is_null())
+    }
+}
+
+fn detect() -> Capabilities {
+    let mut probe = Probe { missing: vec![] };
+    let version = unsafe { CStr::from_ptr(ffmpeg::sys::av_version_info()) };
+    let h264_encoding = probe.encoder(codec::Id::H264);
+    let hevc_encoding = probe.encoder(codec::Id::HEVC);
+    let subtitles = probe.filter("subtitles");
+    let zscale = probe.filter("zscale");
+    let tonemap = probe.filter("tonemap");
+    let libplacebo = probe.filter("libplacebo");
+    let capabilities = Capabilities {
+        version: version.to_string_lossy().into_owned(),
+        hardsub: subtitles && (h264_encoding || hevc_encoding),
+        hdr_tone_mapping: (zscale && tonemap) || libplacebo,
+        h264_encoding,
+        hevc_encoding,
+        png_export: probe.encoder(codec::Id::PNG),
+        jpeg_export: probe.encoder(codec::Id::MJPEG),
+        burnt_in_timecode: probe.filter("drawtext"),
+        image_sequences: probe.demuxer(c"image2"),
+        matroska_muxing: probe.muxer(c"matroska"),
+        mp4_muxing: probe.muxer(c"mp4"),
+        hardware_decoders: HardwareDecoder::available_types(),
+        missing: probe.missing,
+    };
+    info!("capabilities: missing {:?}", capabilities.missing);
+    capabilities
+}
+
+/// Probed on first use; ffmpeg must have been initialized by then
+pub fn get() -> &'static Capabilities {
+    static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+    CAPABILITIES.get_or_init(detect)
+}
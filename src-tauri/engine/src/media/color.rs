@@ -0,0 +1,689 @@
+//! Colour management of the preview. swscale reads YUV by the BT.601
+//! matrix unless told otherwise, and the RGB it gives is shown as if it
+//! were sRGB, which on a wide-gamut display, like those of many laptops,
+//! stretches every colour to the display's wider primaries. Media players
+//! convert for the display instead, by the colour profile the system has
+//! for it; managed, the video player does the same: it reads YUV by the
+//! matrix and range the file gives, and converts from the file's primaries
+//! to the display's after scaling. The source is taken to be in the sRGB
+//! curve, as the preview has always been shown, so that an sRGB display
+//! with a BT.709 file is left as it was.
+
+use std::ffi::c_int;
+
+use ffmpeg::color::{Primaries, Range, Space};
+use ffmpeg::software::scaling;
+use ffmpeg_sys_next as ffi;
+use serde::{Deserialize, Serialize};
+
+use crate::media::frame::VideoData;
+
+/// Steps of linear light in the table back to 8 bits
+const ENCODE_STEPS: usize = 1 << 14;
+/// ICC profiles have their colorants adapted to this white
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+const D65: (f64, f64) = (0.3127, 0.3290);
+const BRADFORD: [[f64; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+/// The sRGB curve, as `Trc::Parametric` has it
+const SRGB_CURVE: [f64; 7] = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045, 0.0, 0.0];
+
+type Matrix = [[f64; 3]; 3];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn apply(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| (0..3).map(|k| m[i][k] * v[k]).sum())
+}
+
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let cofactor = |i: usize, j: usize| {
+        let (a, b) = ((i + 1) % 3, (i + 2) % 3);
+        let (c, d) = ((j + 1) % 3, (j + 2) % 3);
+        m[a][c] * m[b][d] - m[a][d] * m[b][c]
+    };
+    let determinant: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    Some(std::array::from_fn(|i| std::array::from_fn(|j| cofactor(j, i) / determinant)))
+}
+
+fn xyz((x, y): (f64, f64)) -> [f64; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+/// From linear RGB on `primaries` to XYZ, white being `white`
+fn rgb_to_xyz(primaries: [(f64, f64); 3], white: (f64, f64)) -> Option<Matrix> {
+    let columns = primaries.map(xyz);
+    let p: Matrix = std::array::from_fn(|i| std::array::from_fn(|j| columns[j][i]));
+    let scale = apply(&invert(&p)?, xyz(white));
+    Some(std::array::from_fn(|i| std::array::from_fn(|j| p[i][j] * scale[j])))
+}
+
+/// From XYZ under the white `from` to XYZ under D50
+fn adapt_to_d50(from: [f64; 3]) -> Option<Matrix> {
+    let (a, b) = (apply(&BRADFORD, from), apply(&BRADFORD, D50));
+    let scale: Matrix = std::array::from_fn(|i| std::array::from_fn(|j|
+        if i == j { b[i] / a[i] } else { 0.0 }));
+    Some(multiply(&invert(&BRADFORD)?, &multiply(&scale, &BRADFORD)))
+}
+
+/// The primaries and white of `primaries`; what isn't known is taken as
+/// BT.709
+fn chromaticities(primaries: Primaries) -> ([(f64, f64); 3], (f64, f64)) {
+    const C: (f64, f64) = (0.310, 0.316);
+    match primaries {
+        Primaries::BT470M => ([(0.67, 0.33), (0.21, 0.71), (0.14, 0.08)], C),
+        Primaries::BT470BG => ([(0.64, 0.33), (0.29, 0.60), (0.15, 0.06)], D65),
+        Primaries::SMPTE170M | Primaries::SMPTE240M =>
+            ([(0.630, 0.340), (0.310, 0.595), (0.155, 0.070)], D65),
+        Primaries::Film => ([(0.681, 0.319), (0.243, 0.692), (0.145, 0.049)], C),
+        Primaries::BT2020 => ([(0.708, 0.292), (0.170, 0.797), (0.131, 0.046)], D65),
+        Primaries::SMPTE431 =>
+            ([(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)], (0.314, 0.351)),
+        Primaries::SMPTE432 => ([(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)], D65),
+        _ => ([(0.640, 0.330), (0.300, 0.600), (0.150, 0.060)], D65),
+    }
+}
+
+/// A tone curve of an ICC profile, from encoded values to linear light
+#[derive(Clone, Debug)]
+enum Trc {
+    /// `(a * x + b) ^ g + e` from `d` on, `c * x + f` below, as
+    /// `[g, a, b, c, d, e, f]`
+    Parametric([f64; 7]),
+    /// evenly spaced over 0 to 1
+    Table(Vec<f64>),
+}
+
+impl Trc {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    fn eval(&self, x: f64) -> f64 {
+        match self {
+            Trc::Parametric([g, a, b, c, d, e, f]) => if x >= *d {
+                (a * x + b).max(0.0).powf(*g) + e
+            } else {
+                c * x + f
+            },
+            Trc::Table(table) => {
+                let position = x.clamp(0.0, 1.0) * (table.len() - 1) as f64;
+                let i = position.floor() as usize;
+                let j = (i + 1).min(table.len() - 1);
+                table[i] + (table[j] - table[i]) * (position - i as f64)
+            }
+        }
+    }
+
+    /// The encoded value giving `y`; the curve is taken to rise
+    fn invert(&self, y: f64) -> f64 {
+        let (mut low, mut high) = (0.0, 1.0);
+        for _ in 0..24 {
+            let middle = f64::midpoint(low, high);
+            if self.eval(middle) < y { low = middle } else { high = middle }
+        }
+        f64::midpoint(low, high)
+    }
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn index(data: &[u8], at: usize) -> Option<usize> {
+    usize::try_from(be_u32(data, at)?).ok()
+}
+
+#[allow(clippy::cast_possible_wrap)]
+fn s15_fixed16(data: &[u8], at: usize) -> Option<f64> {
+    Some(f64::from(be_u32(data, at)? as i32) / 65536.0)
+}
+
+/// The element of the profile `data` under `signature`
+fn tag<'a>(data: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    // no more entries than there is room for, whatever the count says
+    let count = index(data, 128)?.min(data.len().saturating_sub(132) / 12);
+    (0..count).find_map(|i| {
+        let entry = 132 + i * 12;
+        if data.get(entry..entry + 4)? != signature {
+            return None;
+        }
+        let (offset, size) = (index(data, entry + 4)?, index(data, entry + 8)?);
+        data.get(offset..offset.checked_add(size)?)
+    })
+}
+
+fn parse_xyz(data: &[u8]) -> Option<[f64; 3]> {
+    if data.get(0..4)? != b"XYZ " {
+        return None;
+    }
+    Some([s15_fixed16(data, 8)?, s15_fixed16(data, 12)?, s15_fixed16(data, 16)?])
+}
+
+fn parse_trc(data: &[u8]) -> Option<Trc> {
+    match data.get(0..4)? {
+        b"curv" => match index(data, 8)? {
+            0 => Some(Trc::Parametric([1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0])),
+            1 => {
+                let gamma = f64::from(be_u16(data, 12)?) / 256.0;
+                Some(Trc::Parametric([gamma, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]))
+            }
+            n => (0..n)
+                .map(|i| Some(f64::from(be_u16(data, 12 + i * 2)?) / 65535.0))
+                .collect::<Option<_>>()
+                .map(Trc::Table),
+        },
+        b"para" => {
+            let kind = usize::from(be_u16(data, 8)?);
+            let count = *[1, 3, 4, 5, 7].get(kind)?;
+            let p: Vec<f64> = (0..count)
+                .map(|i| s15_fixed16(data, 12 + i * 4))
+                .collect::<Option<_>>()?;
+            Some(Trc::Parametric(match kind {
+                0 => [p[0], 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                1 => [p[0], p[1], p[2], 0.0, -p[2] / p[1], 0.0, 0.0],
+                2 => [p[0], p[1], p[2], 0.0, -p[2] / p[1], p[3], p[3]],
+                3 => [p[0], p[1], p[2], p[3], p[4], 0.0, 0.0],
+                _ => [p[0], p[1], p[2], p[3], p[4], p[5], p[6]],
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn parse_description(data: &[u8]) -> Option<String> {
+    let tag = tag(data, b"desc")?;
+    match tag.get(0..4)? {
+        b"desc" => {
+            let text = tag.get(12..12 + index(tag, 8)?)?;
+            Some(String::from_utf8_lossy(text).trim_end_matches('\0').to_owned())
+        }
+        // the first of its translations
+        b"mluc" if index(tag, 8)? > 0 => {
+            let (length, offset) = (index(tag, 20)?, index(tag, 24)?);
+            let units: Vec<u16> = tag.get(offset..offset + length)?
+                .chunks_exact(2)
+                .map(|x| u16::from_be_bytes([x[0], x[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    }
+}
+
+/// A display, by a point on it in the desktop's physical pixels and its
+/// scale factor, as the window system gives them
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export)]
+pub struct DisplayAt {
+    pub x: i32,
+    pub y: i32,
+    pub scale: f64,
+}
+
+/// What a matrix-and-curves ICC profile, as display profiles are, says of
+/// the display
+#[derive(Clone, Debug)]
+pub struct DisplayProfile {
+    /// `None` only for `srgb`
+    pub description: Option<String>,
+    /// which display it was read for; `None` for the main one
+    pub at: Option<DisplayAt>,
+    /// from the display's linear RGB to XYZ under D50
+    colorants: Matrix,
+    trc: [Trc; 3],
+}
+
+impl DisplayProfile {
+    /// What is taken without a profile
+    pub fn srgb() -> Self {
+        let (primaries, white) = chromaticities(Primaries::BT709);
+        let colorants = rgb_to_xyz(primaries, white)
+            .zip(adapt_to_d50(xyz(white)))
+            .map(|(m, adapt)| multiply(&adapt, &m))
+            .unwrap();
+        let trc = Trc::Parametric(SRGB_CURVE);
+        DisplayProfile {
+            description: None, at: None, colorants, trc: [trc.clone(), trc.clone(), trc],
+        }
+    }
+
+    /// Reads an ICC profile; only RGB ones with colorants and curves will do
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.get(36..40)? != b"acsp" || data.get(16..20)? != b"RGB " {
+            return None;
+        }
+        let [r, g, b] = [b"rXYZ", b"gXYZ", b"bXYZ"].map(|x| tag(data, x).and_then(parse_xyz));
+        let [r, g, b] = [r?, g?, b?];
+        let [rt, gt, bt] = [b"rTRC", b"gTRC", b"bTRC"].map(|x| tag(data, x).and_then(parse_trc));
+        Some(DisplayProfile {
+            description: Some(parse_description(data).unwrap_or_else(|| "unnamed".to_owned())),
+            at: None,
+            colorants: std::array::from_fn(|i| [r[i], g[i], b[i]]),
+            trc: [rt?, gt?, bt?],
+        })
+    }
+}
+
+/// The profile the system has for the display `at`, or the main one, if it
+/// can be had
+pub fn display_profile(at: Option<DisplayAt>) -> Option<DisplayProfile> {
+    let data = query_profile(at)?;
+    let profile = DisplayProfile::parse(&data);
+    if profile.is_none() {
+        log::debug!("display profile: not a matrix profile, {} bytes", data.len());
+    }
+    profile.map(|x| DisplayProfile { at, ..x })
+}
+
+/// Quartz places displays in points, the physical pixels of each divided by
+/// its own scale
+#[cfg(target_os = "macos")]
+fn query_profile(at: Option<DisplayAt>) -> Option<Vec<u8>> {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    unsafe extern "C" {
+        fn CGMainDisplayID() -> u32;
+        fn CGGetDisplaysWithPoint(
+            point: CGPoint, max: u32, displays: *mut u32, count: *mut u32,
+        ) -> i32;
+        fn CGDisplayCopyColorSpace(display: u32) -> *const c_void;
+        fn CGColorSpaceCopyICCData(space: *const c_void) -> *const c_void;
+    }
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        fn CFDataGetLength(data: *const c_void) -> isize;
+        fn CFDataGetBytePtr(data: *const c_void) -> *const u8;
+        fn CFRelease(object: *const c_void);
+    }
+
+    unsafe {
+        let display = at.and_then(|at| {
+            let point = CGPoint { x: f64::from(at.x) / at.scale, y: f64::from(at.y) / at.scale };
+            let (mut display, mut count) = (0, 0);
+            let error = CGGetDisplaysWithPoint(point, 1, &raw mut display, &raw mut count);
+            (error == 0 && count > 0).then_some(display)
+        });
+        let space = CGDisplayCopyColorSpace(display.unwrap_or_else(|| CGMainDisplayID()));
+        if space.is_null() {
+            return None;
+        }
+        let data = CGColorSpaceCopyICCData(space);
+        CFRelease(space);
+        if data.is_null() {
+            return None;
+        }
+        let length = usize::try_from(CFDataGetLength(data)).unwrap_or(0);
+        let bytes = std::slice::from_raw_parts(CFDataGetBytePtr(data), length).to_vec();
+        CFRelease(data);
+        Some(bytes)
+    }
+}
+
+/// By the device context of the monitor at the point, or of the whole
+/// screen, which is the primary monitor's
+#[cfg(windows)]
+fn query_profile(at: Option<DisplayAt>) -> Option<Vec<u8>> {
+    use std::ffi::c_void;
+    use std::os::windows::ffi::OsStringExt;
+
+    #[repr(C)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+    #[repr(C)]
+    struct MonitorInfoEx {
+        size: u32,
+        monitor: [i32; 4],
+        work: [i32; 4],
+        flags: u32,
+        device: [u16; 32],
+    }
+    const MONITOR_DEFAULTTOPRIMARY: u32 = 1;
+
+    #[link(name = "user32")]
+    unsafe extern "system" {
+        fn GetDC(window: *mut c_void) -> *mut c_void;
+        fn ReleaseDC(window: *mut c_void, dc: *mut c_void) -> i32;
+        fn MonitorFromPoint(point: Point, flags: u32) -> *mut c_void;
+        fn GetMonitorInfoW(monitor: *mut c_void, info: *mut MonitorInfoEx) -> i32;
+    }
+    #[link(name = "gdi32")]
+    unsafe extern "system" {
+        fn CreateDCW(
+            driver: *const u16, device: *const u16, port: *const u16, mode: *const c_void,
+        ) -> *mut c_void;
+        fn DeleteDC(dc: *mut c_void) -> i32;
+        fn GetICMProfileW(dc: *mut c_void, length: *mut u32, name: *mut u16) -> i32;
+    }
+
+    let mut name = [0u16; 260];
+    let mut length = u32::try_from(name.len()).unwrap();
+    let found = unsafe {
+        let monitor = at.map(|at| MonitorFromPoint(Point { x: at.x, y: at.y },
+            MONITOR_DEFAULTTOPRIMARY));
+        let mut info = MonitorInfoEx {
+            size: u32::try_from(std::mem::size_of::<MonitorInfoEx>()).unwrap(),
+            monitor: [0; 4], work: [0; 4], flags: 0, device: [0; 32],
+        };
+        let device = monitor
+            .filter(|x| !x.is_null() && GetMonitorInfoW(*x, &raw mut info) != 0)
+            .map(|_| CreateDCW(info.device.as_ptr(), info.device.as_ptr(),
+                std::ptr::null(), std::ptr::null()))
+            .filter(|dc| !dc.is_null());
+        let dc = device.unwrap_or_else(|| GetDC(std::ptr::null_mut()));
+        let found = GetICMProfileW(dc, &raw mut length, name.as_mut_ptr());
+        if device.is_some() {
+            DeleteDC(dc);
+        } else {
+            ReleaseDC(std::ptr::null_mut(), dc);
+        }
+        found
+    };
+    if found == 0 {
+        return None;
+    }
+    let end = name.iter().position(|&x| x == 0).unwrap_or(name.len());
+    let path = std::ffi::OsString::from_wide(&name[..end]);
+    std::fs::read(&path).inspect_err(|e| log::debug!("display profile: {e}")).ok()
+}
+
+/// The index of the monitor that has `(x, y)` in a listing of `xrandr
+/// --listmonitors`, lines like ` 1: +HDMI-1 2560/597x1440/336+1920+0  HDMI-1`
+#[cfg(all(unix, not(target_os = "macos")))]
+fn monitor_at(listing: &str, (x, y): (i32, i32)) -> Option<usize> {
+    listing.lines().find_map(|line| {
+        let (index, rest) = line.trim().split_once(':')?;
+        let geometry = rest.split_whitespace().nth(1)?;
+        let mut parts = geometry.split('+');
+        let (width, height) = parts.next()?.split_once('x')?;
+        let size = |x: &str| x.split('/').next()?.parse::<i32>().ok();
+        let (width, height) = (size(width)?, size(height)?);
+        let left: i32 = parts.next()?.parse().ok()?;
+        let top: i32 = parts.next()?.parse().ok()?;
+        ((left..left + width).contains(&x) && (top..top + height).contains(&y))
+            .then(|| index.parse().ok())?
+    })
+}
+
+/// From the `_ICC_PROFILE` of the X root window, where colour managers put
+/// it, or `_ICC_PROFILE_n` for the `n`th monitor after the first; there is
+/// none to be had this way under Wayland
+#[cfg(all(unix, not(target_os = "macos")))]
+fn query_profile(at: Option<DisplayAt>) -> Option<Vec<u8>> {
+    let monitor = at.and_then(|at| {
+        let output = std::process::Command::new("xrandr")
+            .arg("--listmonitors")
+            .output()
+            .inspect_err(|e| log::debug!("xrandr: {e}"))
+            .ok()?;
+        monitor_at(&String::from_utf8_lossy(&output.stdout), (at.x, at.y))
+    });
+    let atom = match monitor {
+        None | Some(0) => "_ICC_PROFILE".to_owned(),
+        Some(n) => format!("_ICC_PROFILE_{n}"),
+    };
+    let output = std::process::Command::new("xprop")
+        .args(["-root", "-notype", &atom])
+        .output()
+        .inspect_err(|e| log::debug!("xprop: {e}"))
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (_, values) = text.split_once('=')?;
+    values.split(',').map(|x| x.trim().parse::<u8>().ok()).collect()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn query_profile(_at: Option<DisplayAt>) -> Option<Vec<u8>> {
+    None
+}
+
+/// What decides how a frame's colours are read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceColor {
+    pub primaries: Primaries,
+    pub space: Space,
+    pub range: Range,
+}
+
+impl SourceColor {
+    /// As `frame` gives it, guessing what it doesn't by its height, as
+    /// players do
+    pub fn of(frame: &VideoData) -> Self {
+        let height = frame.height();
+        let primaries = match frame.color_primaries() {
+            Primaries::Unspecified | Primaries::Reserved | Primaries::Reserved0 =>
+                match height {
+                    720.. => Primaries::BT709,
+                    576 => Primaries::BT470BG,
+                    _ => Primaries::SMPTE170M,
+                },
+            x => x,
+        };
+        let space = match frame.color_space() {
+            Space::Unspecified | Space::Reserved =>
+                if height >= 720 { Space::BT709 } else { Space::BT470BG },
+            x => x,
+        };
+        let range = match frame.color_range() {
+            Range::Unspecified => Range::MPEG,
+            x => x,
+        };
+        SourceColor { primaries, space, range }
+    }
+}
+
+/// Has `scaler` read YUV by the matrix and range of `source`; it has no
+/// effect on RGB input
+pub fn set_colorspace(scaler: &mut scaling::Context, source: SourceColor) {
+    let full = c_int::from(source.range == Range::JPEG);
+    unsafe {
+        let coefficients = ffi::sws_getCoefficients(ffi::AVColorSpace::from(source.space) as c_int);
+        // the output table and range don't matter for RGB
+        ffi::sws_setColorspaceDetails(
+            scaler.as_mut_ptr(), coefficients, full, coefficients, 1, 0, 1 << 16, 1 << 16);
+    }
+}
+
+/// From RGBA on a file's primaries to RGBA on a display's
+pub struct Transform {
+    /// from 8 bits to linear light
+    decode: [f32; 256],
+    matrix: [[f32; 3]; 3],
+    /// from linear light, in `ENCODE_STEPS`, to 8 bits; by channel
+    encode: [Vec<u8>; 3],
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+fn step(v: f32) -> usize {
+    (v.clamp(0.0, 1.0) * (ENCODE_STEPS - 1) as f32).round() as usize
+}
+
+impl Transform {
+    /// `None` if it would change nothing
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    pub fn new(primaries: Primaries, display: &DisplayProfile) -> Option<Self> {
+        let (points, white) = chromaticities(primaries);
+        let to_pcs = multiply(&adapt_to_d50(xyz(white))?, &rgb_to_xyz(points, white)?);
+        let matrix = multiply(&invert(&display.colorants)?, &to_pcs);
+
+        let srgb = Trc::Parametric(SRGB_CURVE);
+        let decode = std::array::from_fn(|i| srgb.eval(i as f64 / 255.0) as f32);
+        let encode = display.trc.clone().map(|trc| (0..ENCODE_STEPS)
+            .map(|i| (trc.invert(i as f64 / (ENCODE_STEPS - 1) as f64) * 255.0).round() as u8)
+            .collect::<Vec<u8>>());
+        let transform = Transform {
+            decode,
+            matrix: matrix.map(|row| row.map(|x| x as f32)),
+            encode,
+        };
+
+        let near_identity = (0..3).all(|i| (0..3).all(|j|
+            (matrix[i][j] - if i == j { 1.0 } else { 0.0 }).abs() < 1e-3));
+        let same_curve = transform.encode.iter().all(|encode| (0..=255u8)
+            .all(|v| encode[step(transform.decode[usize::from(v)])].abs_diff(v) <= 1));
+        (!(near_identity && same_curve)).then_some(transform)
+    }
+
+    /// Converts `frame`, which is RGBA, in place
+    pub fn apply(&self, frame: &mut VideoData) {
+        let width = usize::try_from(frame.width()).unwrap();
+        let height = usize::try_from(frame.height()).unwrap();
+        let stride = frame.stride(0);
+        let m = &self.matrix;
+        for row in frame.data_mut(0).chunks_mut(stride).take(height) {
+            for pixel in row[..width * 4].chunks_exact_mut(4) {
+                let rgb = [0, 1, 2].map(|i| self.decode[usize::from(pixel[i])]);
+                for (i, out) in pixel[..3].iter_mut().enumerate() {
+                    let v = m[i][0] * rgb[0] + m[i][1] * rgb[1] + m[i][2] * rgb[2];
+                    *out = self.encode[i][step(v)];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn fixed(v: f64) -> [u8; 4] {
+        ((v * 65536.0).round() as i32 as u32).to_be_bytes()
+    }
+
+    /// A profile with `tags`, laid out as ICC has it
+    fn profile(space: &[u8; 4], tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0; 128];
+        data[16..20].copy_from_slice(space);
+        data[36..40].copy_from_slice(b"acsp");
+        data.extend(u32::try_from(tags.len()).unwrap().to_be_bytes());
+        let mut offset = 132 + tags.len() * 12;
+        for (signature, element) in tags {
+            data.extend(*signature);
+            data.extend(u32::try_from(offset).unwrap().to_be_bytes());
+            data.extend(u32::try_from(element.len()).unwrap().to_be_bytes());
+            offset += element.len();
+        }
+        for (_, element) in tags {
+            data.extend(element);
+        }
+        data
+    }
+
+    fn xyz_tag(v: [f64; 3]) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        tag.extend(v.iter().flat_map(|x| fixed(*x)));
+        tag
+    }
+
+    fn para_tag(p: &[f64]) -> Vec<u8> {
+        let mut tag = b"para\0\0\0\0\0\x03\0\0".to_vec();
+        tag.extend(p.iter().flat_map(|x| fixed(*x)));
+        tag
+    }
+
+    fn mluc_tag(text: &str) -> Vec<u8> {
+        let units: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let mut tag = b"mluc\0\0\0\0".to_vec();
+        for x in [1, 12] {
+            tag.extend(u32::to_be_bytes(x));
+        }
+        tag.extend(b"enUS");
+        tag.extend(u32::try_from(units.len()).unwrap().to_be_bytes());
+        tag.extend(28u32.to_be_bytes());
+        tag.extend(units);
+        tag
+    }
+
+    /// sRGB, as a display profile would give it
+    fn srgb_profile() -> Vec<u8> {
+        let m = DisplayProfile::srgb().colorants;
+        let column = |j: usize| xyz_tag([m[0][j], m[1][j], m[2][j]]);
+        let curve = para_tag(&SRGB_CURVE[..5]);
+        profile(b"RGB ", &[
+            (b"desc", mluc_tag("Test sRGB")),
+            (b"rXYZ", column(0)), (b"gXYZ", column(1)), (b"bXYZ", column(2)),
+            (b"rTRC", curve.clone()), (b"gTRC", curve.clone()), (b"bTRC", curve),
+        ])
+    }
+
+    #[test]
+    fn reads_a_matrix_profile() {
+        let parsed = DisplayProfile::parse(&srgb_profile()).unwrap();
+        assert_eq!(parsed.description.as_deref(), Some("Test sRGB"));
+        let srgb = DisplayProfile::srgb();
+        for (a, b) in parsed.colorants.iter().flatten().zip(srgb.colorants.iter().flatten()) {
+            assert!((a - b).abs() < 1e-4, "{a} against {b}");
+        }
+        // nothing to do for BT.709 on it, something for a wider gamut
+        assert!(Transform::new(Primaries::BT709, &parsed).is_none());
+        assert!(Transform::new(Primaries::BT2020, &parsed).is_some());
+    }
+
+    #[test]
+    fn reads_curves() {
+        let Some(Trc::Parametric(p)) = parse_trc(&para_tag(&SRGB_CURVE[..5])) else { panic!() };
+        assert!(p.iter().zip(SRGB_CURVE).all(|(a, b)| (a - b).abs() < 1e-4));
+
+        // a gamma of 2.2 in u8Fixed8
+        let gamma = parse_trc(b"curv\0\0\0\0\0\0\0\x01\x02\x33").unwrap();
+        assert!((gamma.eval(0.5) - 0.5f64.powf(2.2)).abs() < 1e-3);
+
+        let table = parse_trc(b"curv\0\0\0\0\0\0\0\x03\0\0\x80\0\xff\xff").unwrap();
+        assert!((table.eval(0.25) - 0.25).abs() < 1e-3);
+        assert!((table.invert(0.75) - 0.75).abs() < 1e-3);
+    }
+
+    #[test]
+    fn refuses_what_isnt_one() {
+        let data = srgb_profile();
+        assert!(DisplayProfile::parse(&data[..200]).is_none());
+        let mut gray = data.clone();
+        gray[16..20].copy_from_slice(b"GRAY");
+        assert!(DisplayProfile::parse(&gray).is_none());
+        let mut no_blue = data;
+        no_blue.truncate(no_blue.len() - 1);
+        assert!(DisplayProfile::parse(&no_blue).is_none());
+    }
+
+    #[test]
+    fn tag_count_is_bounded_by_the_data() {
+        let mut data = srgb_profile();
+        data[128..132].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(tag(&data, b"rXYZ").is_some());
+        assert!(tag(&data, b"wtpt").is_none());
+        assert!(tag(&data[..140], b"rXYZ").is_none());
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    #[test]
+    fn finds_the_monitor_at_a_point() {
+        let listing = "Monitors: 2\n \
+            0: +*eDP-1 1920/344x1080/194+0+0  eDP-1\n \
+            1: +HDMI-1 2560/597x1440/336+1920+0  HDMI-1\n";
+        assert_eq!(monitor_at(listing, (960, 540)), Some(0));
+        assert_eq!(monitor_at(listing, (1920, 100)), Some(1));
+        assert_eq!(monitor_at(listing, (1000, 1200)), None);
+    }
+}
@@ -0,0 +1,123 @@
+//! Checking a re-export against an older one: frames of a hardsub export
+//! next to the same frames of the source with the current script burnt in
+//! by libass, as the export would have done, compared block by block. If
+//! only the intended signs changed, only their blocks differ.
+
+use std::path::Path;
+
+use ffmpeg::{filter, format};
+use serde::Serialize;
+
+use crate::media::{frame, internal::{check, MediaError}, units::Seconds};
+
+/// Side of the square blocks compared, in pixels
+const BLOCK: usize = 8;
+/// Mean difference over a block, out of 255, above which it has changed;
+/// the noise of lossy encoding stays well below
+const BLOCK_THRESHOLD: f64 = 10.0;
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct FrameDiff {
+    pub time: Seconds,
+    /// of all pixels, from 0 to 1
+    pub mean_difference: f64,
+    /// fraction of the blocks that changed
+    pub changed: f64,
+    /// `(x, y, width, height)` around the blocks that changed, in pixels of
+    /// the export; absent if none did
+    pub bounds: Option<(u32, u32, u32, u32)>,
+}
+
+/// Burns an ASS script into RGBA frames with the `subtitles` filter
+pub struct Burner {
+    graph: filter::Graph,
+}
+
+impl Burner {
+    /// For frames of `size`. libass reads the script itself, so it has to
+    /// be in a file, whose path can't have a quote in it.
+    pub fn create(size: (u32, u32), script: &Path) -> Result<Self, MediaError> {
+        let path = script.to_string_lossy().replace('\\', "/");
+        if path.contains('\'') {
+            return Err(MediaError::InternalError(format!("unusable script path: {path}")));
+        }
+        let find = |name: &str| filter::find(name).ok_or(
+            MediaError::InternalError(format!("filter not found: {name}")));
+        let mut graph = filter::Graph::new();
+        check!(graph.add(&find("buffer")?, "in", &format!(
+            "video_size={}x{}:pix_fmt=rgba:time_base=1/1000:pixel_aspect=1/1",
+            size.0, size.1)))?;
+        check!(graph.add(&find("buffersink")?, "out", ""))?;
+        check!(check!(check!(graph.output("in", 0))?.input("out", 0))?.parse(&format!(
+            "subtitles=filename='{}',format=rgba", path.replace(':', "\\:"))))?;
+        check!(graph.validate())?;
+        Ok(Self { graph })
+    }
+
+    pub fn burn(
+        &mut self, picture: &frame::VideoData, time: Seconds,
+    ) -> Result<frame::VideoData, MediaError> {
+        let mut input = picture.clone();
+        #[allow(clippy::cast_possible_truncation)]
+        input.set_pts(Some((time.0 * 1000.0).round() as i64));
+        check!(self.graph.get("in").unwrap().source().add(&input))?;
+        let mut output = frame::VideoData::empty();
+        check!(self.graph.get("out").unwrap().sink().frame(&mut output))?;
+        Ok(output)
+    }
+}
+
+/// Compares two RGBA frames of the same size
+pub fn compare(a: &frame::VideoData, b: &frame::VideoData, time: Seconds) -> FrameDiff {
+    debug_assert_eq!(a.format(), format::Pixel::RGBA);
+    let (width, height) = (a.width() as usize, a.height() as usize);
+    let (data_a, stride_a, data_b, stride_b) = (a.data(0), a.stride(0), b.data(0), b.stride(0));
+    let (columns, rows) = (width.div_ceil(BLOCK), height.div_ceil(BLOCK));
+
+    let mut total = 0u64;
+    let mut blocks = vec![0u64; columns * rows];
+    for y in 0..height {
+        let row_a = &data_a[y * stride_a..][..width * 4];
+        let row_b = &data_b[y * stride_b..][..width * 4];
+        for x in 0..width {
+            let difference: u64 = (0..3)
+                .map(|c| u64::from(row_a[x * 4 + c].abs_diff(row_b[x * 4 + c])))
+                .sum();
+            total += difference;
+            blocks[y / BLOCK * columns + x / BLOCK] += difference;
+        }
+    }
+
+    let mut changed = 0u64;
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for (i, &sum) in blocks.iter().enumerate() {
+        let (column, row) = (i % columns, i / columns);
+        let pixels = (BLOCK.min(width - column * BLOCK) * BLOCK.min(height - row * BLOCK)) * 3;
+        if fraction(sum, pixels) <= BLOCK_THRESHOLD {
+            continue;
+        }
+        changed += 1;
+        let (x0, y0) = (column * BLOCK, row * BLOCK);
+        let (x1, y1) = ((x0 + BLOCK).min(width), (y0 + BLOCK).min(height));
+        bounds = Some(match bounds {
+            None => (x0, y0, x1, y1),
+            Some((left, top, right, bottom)) =>
+                (left.min(x0), top.min(y0), right.max(x1), bottom.max(y1)),
+        });
+    }
+    let to_u32 = |x: usize| u32::try_from(x).unwrap();
+    FrameDiff {
+        time,
+        mean_difference: fraction(total, width * height * 3) / 255.0,
+        changed: fraction(changed, blocks.len()),
+        bounds: bounds.map(|(x0, y0, x1, y1)|
+            (to_u32(x0), to_u32(y0), to_u32(x1 - x0), to_u32(y1 - y0))),
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn fraction(part: u64, whole: usize) -> f64 {
+    part as f64 / whole.max(1) as f64
+}
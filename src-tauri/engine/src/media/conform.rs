@@ -0,0 +1,235 @@
+//! Moving a project over to another release of its media, as when a better
+//! encode turns up, or a PAL transfer, or a cut with a longer logo at the
+//! start. The audio of the two is compared by its loudness over time, which
+//! survives re-encoding and remixing well enough, to find how times in the
+//! old file map to the new: an offset, and a stretch where one runs at
+//! another speed. The stretches tried are those of the usual rate
+//! conversions, that of the two lengths and that of the two framerates;
+//! for each, the offset that lines the loudness up best is searched for
+//! coarsely, then finely around the best of all.
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::media::demux::{self, StreamKind};
+use crate::media::internal::MediaError;
+use crate::media::units::Seconds;
+use crate::media::{audio, audio_class, drift};
+use crate::subtitle::retime::Retime;
+
+/// Loudness values per second compared
+const RATE: usize = audio_class::FRAME_RATE;
+/// Values averaged into one for the coarse search
+const COARSE: usize = 10;
+/// How far apart the two may start, either way
+const MAX_OFFSET: f64 = 120.0;
+/// How much the two have to overlap to be compared at all
+const MIN_OVERLAP: Seconds = Seconds(30.0);
+/// Correlation from which what was found is applied without asking
+pub const MIN_SIMILARITY: f64 = 0.6;
+/// Stretches further from 1 aren't tried; two releases of one programme
+/// don't differ by more
+const MAX_STRETCH: f64 = 0.1;
+/// Stretches nearer 1 than this are none, as in `drift`
+const MIN_STRETCH: f64 = 1e-4;
+/// Offsets smaller than one loudness value are none
+const MIN_OFFSET: f64 = 0.02;
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MediaFacts {
+    pub duration: Seconds,
+    /// of the default video stream, on average
+    pub framerate: Option<f64>,
+    pub audio_index: Option<usize>,
+    pub video_index: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ConformReport {
+    pub old: MediaFacts,
+    pub new: MediaFacts,
+    /// what takes times in the old file to the same moment in the new;
+    /// absent if they already line up
+    pub retime: Option<Retime>,
+    /// the rate conversion the stretch was taken for, like `25/23.976`
+    pub known: Option<String>,
+    /// how alike the loudness of the two is once retimed, as a correlation;
+    /// absent unless both have audio
+    pub similarity: Option<f64>,
+    /// whether `similarity` is at least `MIN_SIMILARITY`, for `retime` to be
+    /// applied without asking
+    pub confident: bool,
+}
+
+fn facts(path: &Path, audio: Option<usize>) -> Result<MediaFacts, MediaError> {
+    let demuxer = demux::Demuxer::open(path)?;
+    let video = demuxer.get_stream_from_kind(StreamKind::Video).ok()
+        .map(|(info, stream)| (info.index(), f64::from(stream.avg_frame_rate())));
+    let audio_index = match audio {
+        Some(x) => Some(x),
+        None => demuxer.get_stream_from_kind(StreamKind::Audio).ok().map(|x| x.0.index()),
+    };
+    Ok(MediaFacts {
+        duration: demuxer.duration(),
+        framerate: video.map(|x| x.1).filter(|x| x.is_finite() && *x > 0.0),
+        audio_index,
+        video_index: video.map(|x| x.0),
+    })
+}
+
+/// Loudness over time, on a log scale so that quiet passages count too
+struct Envelope {
+    start: f64,
+    rate: f64,
+    values: Vec<f32>,
+}
+
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+impl Envelope {
+    fn read(
+        path: &Path, stream: usize, progress: impl FnMut(f64) -> bool,
+    ) -> Result<Self, MediaError> {
+        let waveform = audio::classified_waveform(path, Some(stream), RATE, progress)?;
+        Ok(Envelope {
+            start: waveform.start_time.0,
+            rate: waveform.sample_per_second as f64,
+            values: waveform.peaks.iter().map(|x| (x.abs() + 1e-4).ln()).collect(),
+        })
+    }
+
+    /// With every `n` values averaged into one
+    fn coarse(&self, n: usize) -> Self {
+        Envelope {
+            start: self.start,
+            rate: self.rate / n as f64,
+            values: self.values.chunks(n)
+                .map(|x| x.iter().sum::<f32>() / x.len() as f32)
+                .collect(),
+        }
+    }
+
+    fn at(&self, time: f64) -> Option<f32> {
+        let i = ((time - self.start) * self.rate).round();
+        if i < 0.0 {
+            return None;
+        }
+        self.values.get(i as usize).copied()
+    }
+}
+
+/// The correlation of `new` with `old` read through `retime`, over where
+/// both are; `None` if that is less than `MIN_OVERLAP`
+#[allow(clippy::cast_precision_loss)]
+fn similarity(old: &Envelope, new: &Envelope, retime: Retime) -> Option<f64> {
+    let (mut n, mut sx, mut sy, mut sxx, mut syy, mut sxy) = (0usize, 0.0, 0.0, 0.0, 0.0, 0.0);
+    for (i, &y) in new.values.iter().enumerate() {
+        let time = new.start + i as f64 / new.rate;
+        let Some(x) = old.at((time - retime.offset.0) / retime.factor) else { continue };
+        let (x, y) = (f64::from(x), f64::from(y));
+        n += 1;
+        sx += x;
+        sy += y;
+        sxx += x * x;
+        syy += y * y;
+        sxy += x * y;
+    }
+    let n = n as f64;
+    if n < MIN_OVERLAP.0 * new.rate {
+        return None;
+    }
+    let spread = ((n * sxx - sx * sx) * (n * syy - sy * sy)).sqrt();
+    (spread > 0.0).then(|| (n * sxy - sx * sy) / spread)
+}
+
+/// The offset from `from` to `to`, in steps of `step`, that lines `new` up
+/// best with `old` stretched by `factor`, and how well
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn best_offset(
+    old: &Envelope, new: &Envelope, factor: f64, (from, to): (f64, f64), step: f64,
+) -> Option<(f64, f64)> {
+    let steps = ((to - from) / step).round() as i64;
+    (0..=steps)
+        .filter_map(|i| {
+            let offset = from + i as f64 * step;
+            similarity(old, new, Retime { factor, offset: Seconds(offset) })
+                .map(|x| (offset, x))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Compares the file at `new_path` with the one at `old_path`, whose audio
+/// stream `old_audio` or default one is taken, for how to move times from
+/// one to the other. Without audio in both, only a known rate conversion
+/// between their framerates is looked for. `progress` is called with the
+/// fraction read so far, and returns `false` to give up.
+#[allow(clippy::cast_precision_loss)]
+pub fn compare(
+    old_path: &Path, old_audio: Option<usize>, new_path: &Path,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<ConformReport, MediaError> {
+    let old = facts(old_path, old_audio)?;
+    let new = facts(new_path, None)?;
+    let framerates = old.framerate.zip(new.framerate).map(|(a, b)| a / b);
+
+    let (Some(old_audio), Some(new_audio)) = (old.audio_index, new.audio_index) else {
+        let known = framerates.and_then(drift::known_ratio);
+        return Ok(ConformReport {
+            retime: known.map(|x| Retime { factor: x.0, offset: Seconds(0.0) }),
+            known: known.map(|x| x.1.to_owned()),
+            similarity: None,
+            confident: false,
+            old, new,
+        });
+    };
+
+    let mut candidates = vec![1.0];
+    candidates.extend(drift::KNOWN.iter().map(|x| x.0));
+    if old.duration.0 > 0.0 && new.duration.0 > 0.0 {
+        candidates.push(new.duration.0 / old.duration.0);
+    }
+    candidates.extend(framerates);
+    // those near one another are one, known ones first
+    let mut factors: Vec<f64> = Vec::new();
+    for x in candidates {
+        let x = drift::known_ratio(x).map_or(x, |known| known.0);
+        if (x - 1.0).abs() <= MAX_STRETCH
+            && !factors.iter().any(|y| (x / y - 1.0).abs() < MIN_STRETCH)
+        {
+            factors.push(x);
+        }
+    }
+
+    let old_envelope = Envelope::read(old_path, old_audio, |x| progress(x / 2.0))?;
+    let new_envelope = Envelope::read(new_path, new_audio, |x| progress(0.5 + x / 2.0))?;
+    let (old_coarse, new_coarse) = (old_envelope.coarse(COARSE), new_envelope.coarse(COARSE));
+    let coarse_step = COARSE as f64 / RATE as f64;
+    let too_little = || MediaError::InternalError("the two overlap too little to compare".to_owned());
+    let (factor, coarse_offset, _) = factors.iter()
+        .filter_map(|&factor| {
+            best_offset(&old_coarse, &new_coarse, factor, (-MAX_OFFSET, MAX_OFFSET), coarse_step)
+                .map(|(offset, x)| (factor, offset, x))
+        })
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+        .ok_or_else(too_little)?;
+    let (offset, similarity) = best_offset(&old_envelope, &new_envelope, factor,
+        (coarse_offset - coarse_step, coarse_offset + coarse_step), 1.0 / RATE as f64)
+        .ok_or_else(too_little)?;
+    progress(1.0);
+
+    let stretched = (factor - 1.0).abs() >= MIN_STRETCH;
+    let retime = (stretched || offset.abs() >= MIN_OFFSET)
+        .then_some(Retime { factor, offset: Seconds(offset) });
+    log::debug!("conform: {retime:?}, similarity {similarity:.3}");
+    Ok(ConformReport {
+        retime,
+        known: drift::known_ratio(factor).filter(|_| stretched).map(|x| x.1.to_owned()),
+        similarity: Some(similarity),
+        confident: similarity >= MIN_SIMILARITY,
+        old, new,
+    })
+}
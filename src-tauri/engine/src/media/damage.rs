@@ -0,0 +1,94 @@
+//! Damage in the source: packets a decoder refused as invalid and frames it
+//! flagged as corrupt. Both are counted and decoded past, rather than
+//! stopping playback or analysis, so that artifacts can be shown to be in
+//! the file and not of our making.
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+
+/// Damage closer together than this is reported as one span
+const SPAN_GAP: f64 = 2.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct DamageSpan {
+    pub start: Seconds,
+    /// of the last damaged packet or frame in it
+    pub end: Seconds,
+    pub errors: usize,
+    pub corrupt_frames: usize,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct StreamDamage {
+    pub index: usize,
+    /// packets refused
+    pub errors: usize,
+    pub corrupt_frames: usize,
+    pub spans: Vec<DamageSpan>,
+}
+
+/// Kept by a decoder. Times are kept to the millisecond, so that what is
+/// decoded again after seeking back isn't counted twice.
+#[derive(Default)]
+pub struct Damage {
+    errors: BTreeSet<i64>,
+    corrupt: BTreeSet<i64>,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn key(time: Seconds) -> i64 {
+    (time.0 * 1000.0).round() as i64
+}
+
+impl Damage {
+    pub fn record_error(&mut self, time: Seconds) {
+        self.errors.insert(key(time));
+    }
+
+    pub fn record_corrupt(&mut self, time: Seconds) {
+        self.corrupt.insert(key(time));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty() && self.corrupt.is_empty()
+    }
+
+    /// What has been seen so far, for stream `index`
+    pub fn report(&self, index: usize) -> StreamDamage {
+        let mut all: Vec<(i64, bool)> = self.errors.iter().map(|&x| (x, true))
+            .chain(self.corrupt.iter().map(|&x| (x, false)))
+            .collect();
+        all.sort_unstable();
+        let mut spans: Vec<DamageSpan> = Vec::new();
+        for (time, error) in all {
+            #[allow(clippy::cast_precision_loss)]
+            let time = Seconds(time as f64 / 1000.0);
+            let span = match spans.last_mut() {
+                Some(x) if time.0 - x.end.0 <= SPAN_GAP => x,
+                _ => {
+                    spans.push(DamageSpan { start: time, end: time, errors: 0, corrupt_frames: 0 });
+                    spans.last_mut().unwrap()
+                }
+            };
+            span.end = time;
+            if error {
+                span.errors += 1;
+            } else {
+                span.corrupt_frames += 1;
+            }
+        }
+        StreamDamage {
+            index,
+            errors: self.errors.len(),
+            corrupt_frames: self.corrupt.len(),
+            spans,
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Which parts of a frame changed since the last one sent. While paused the
+//! same picture is often rendered again with little or nothing different,
+//! and sending whole frames each time is most of the IPC traffic.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::media::frame;
+
+/// Width and height of a tile, in pixels
+pub const TILE_SIZE: u32 = 64;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A hash of every tile of an RGBA frame, row of tiles by row of tiles
+pub struct TileHashes {
+    size: (u32, u32),
+    hashes: Vec<u64>,
+}
+
+fn tiles(size: (u32, u32)) -> impl Iterator<Item = Tile> {
+    let (width, height) = size;
+    (0..height.div_ceil(TILE_SIZE)).flat_map(move |row| {
+        (0..width.div_ceil(TILE_SIZE)).map(move |column| {
+            let (x, y) = (column * TILE_SIZE, row * TILE_SIZE);
+            Tile {
+                x, y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+            }
+        })
+    })
+}
+
+/// The bytes of each line of `tile`, without the frame's padding
+pub fn tile_rows<'a>(frame: &'a frame::Video, tile: Tile) -> impl Iterator<Item = &'a [u8]> {
+    let data = frame.decoded.data(0);
+    let stride = frame.decoded.stride(0);
+    let (start, length) = (tile.x as usize * 4, tile.width as usize * 4);
+    (tile.y..tile.y + tile.height).map(move |y| {
+        let offset = y as usize * stride + start;
+        &data[offset..offset + length]
+    })
+}
+
+impl TileHashes {
+    pub fn compute(frame: &frame::Video) -> Self {
+        let size = (frame.decoded.width(), frame.decoded.height());
+        let hashes = tiles(size)
+            .map(|tile| {
+                let mut hasher = DefaultHasher::new();
+                for row in tile_rows(frame, tile) {
+                    row.hash(&mut hasher);
+                }
+                hasher.finish()
+            })
+            .collect();
+        Self { size, hashes }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// The tiles that differ from `previous`; all of them if there is no
+    /// previous frame or it had another size
+    pub fn changed_since(&self, previous: Option<&TileHashes>) -> Vec<Tile> {
+        let previous = previous.filter(|x| x.size == self.size);
+        tiles(self.size)
+            .zip(&self.hashes)
+            .enumerate()
+            .filter(|&(i, (_, hash))| previous.is_none_or(|x| x.hashes[i] != *hash))
+            .map(|(_, (tile, _))| tile)
+            .collect()
+    }
+}
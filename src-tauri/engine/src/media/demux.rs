@@ -4,7 +4,7 @@ use ffmpeg_sys_next::AV_NOPTS_VALUE;
 use getset::{CopyGetters};
 use log::{trace, warn};
 
-use crate::media::{demux, internal::{check, MediaError}, units::{self, Seconds}};
+use crate::media::{demux, images, internal::{check, MediaError}, units::{self, Seconds}};
 
 pub use ffmpeg_next::packet::Packet;
 pub use ffmpeg_next::media::Type as StreamKind;
@@ -30,6 +30,15 @@ pub struct StreamDescription {
     codec_id: Option<String>
 }
 
+impl StreamDescription {
+    pub fn new(
+        r#type: SerializableStreamKind, index: usize,
+        language_code: String, codec_id: Option<String>
+    ) -> Self {
+        Self { r#type, index, language_code, codec_id }
+    }
+}
+
 #[derive(Clone, Copy, CopyGetters)]
 pub struct StreamInfo {
     #[getset(get_copy = "pub")]
@@ -52,17 +61,73 @@ impl StreamInfo {
 }
 
 pub struct Demuxer {
-    input: Box<format::context::Input>
+    input: Box<format::context::Input>,
+    /// where a repeated still image stops
+    end: Option<Seconds>,
 }
 
 impl Demuxer {
     pub fn open(path: &std::path::Path) -> Result<Demuxer, MediaError> {
         let input = Box::new(check!(format::input(&path))?);
-        Ok(Demuxer { input })
+        Ok(Demuxer { input, end: None })
     }
 
+    pub fn open_images(
+        path: &std::path::Path, options: &images::ImageOptions
+    ) -> Result<Demuxer, MediaError> {
+        let input = Box::new(images::open(path, options)?);
+        let end = (!options.sequence).then_some(options.duration);
+        Ok(Demuxer { input, end })
+    }
+
+    /// Falls back on the longest stream when the container doesn't say, as
+    /// with some raw audio formats
     pub fn duration(&self) -> units::Seconds {
-        units::Timestamp(self.input.duration()).to_seconds(units::DEFAULT_TIMEBASE)
+        if let Some(end) = self.end {
+            return end;
+        }
+        let duration = self.input.duration();
+        if duration != AV_NOPTS_VALUE && duration > 0 {
+            return units::Timestamp(duration).to_seconds(units::DEFAULT_TIMEBASE);
+        }
+        let longest = self.input.streams()
+            .filter(|x| x.duration() != AV_NOPTS_VALUE && x.duration() > 0)
+            .map(|x| units::Timestamp(x.duration()).to_seconds(x.time_base()).0)
+            .fold(0.0, f64::max);
+        units::Seconds(longest)
+    }
+
+    /// The timecode of the first frame as the file gives it, such as
+    /// `01:00:00:00`, from the container or else the first stream that
+    /// has one, like the timecode track of a QuickTime file
+    pub fn start_timecode(&self) -> Option<String> {
+        if let Some(x) = self.input.metadata().get("timecode") {
+            return Some(x.to_owned());
+        }
+        self.input.streams().find_map(|x| x.metadata().get("timecode").map(str::to_owned))
+    }
+
+    /// Cover art in audio files comes as a video stream of one picture
+    fn is_video(stream: &ffmpeg_next::Stream) -> bool {
+        stream.parameters().medium() == StreamKind::Video
+            && !stream.disposition().contains(format::stream::Disposition::ATTACHED_PIC)
+    }
+
+    /// Whether there is any video besides cover art
+    pub fn has_video(&self) -> bool {
+        self.input.streams().any(|x| Self::is_video(&x))
+    }
+
+    /// Whether it holds audio or video of a media format, rather than text
+    /// or a lone picture, which ffmpeg also reads as video
+    pub fn is_media(&self) -> bool {
+        let format = self.input.format();
+        let name = format.name();
+        if matches!(name, "tty" | "image2" | "image2pipe") || name.ends_with("_pipe") {
+            return false;
+        }
+        self.has_video()
+            || self.input.streams().any(|x| x.parameters().medium() == StreamKind::Audio)
     }
 
     pub fn describe_streams(&self) -> Vec<StreamDescription> {
@@ -126,9 +191,18 @@ impl Demuxer {
         ))
     }
 
+    /// For video, `MediaError::NoVideo` if there is only cover art or nothing
     pub fn get_stream_from_kind(
         &self, kind: StreamKind
     ) -> Result<(demux::StreamInfo, ffmpeg_next::Stream<'_>), MediaError> {
+        if kind == StreamKind::Video {
+            let index = self.input.streams().best(kind)
+                .filter(Self::is_video)
+                .or_else(|| self.input.streams().find(Self::is_video))
+                .ok_or(MediaError::NoVideo)?
+                .index();
+            return self.get_stream_from_index(index);
+        }
         let index = self
             .input
             .streams()
@@ -141,11 +215,14 @@ impl Demuxer {
     }
 
     pub fn next_packet(&mut self) -> Option<(usize, Packet)> {
-        if let Some((s, p)) = self.input.packets().next() {
-            Some((s.index(), p))
-        } else {
-            None
+        let (s, p) = self.input.packets().next()?;
+        if let Some(end) = self.end
+            && let Some(pts) = p.pts()
+            && units::Timestamp(pts).to_seconds(s.time_base()) >= end
+        {
+            return None;
         }
+        Some((s.index(), p))
     }
 
     pub fn seek(&mut self, time: units::Seconds) -> Result<(), MediaError> {
@@ -157,6 +234,19 @@ impl Demuxer {
     }
 
 
+    /// Forgets what was read ahead of the packets given out so far, so that
+    /// the next reads go to the file: for files still being written, where
+    /// that may have been zeros that are data by now
+    pub fn discard_buffer(&mut self) {
+        unsafe {
+            let pb = (*self.input.as_mut_ptr()).pb;
+            if !pb.is_null() {
+                // for reading, this drops the buffer
+                ffmpeg_sys_next::avio_flush(pb);
+            }
+        }
+    }
+
     pub fn seek_byte_pos(&mut self, pos: i64) -> Result<(), MediaError> {
         trace!("seek_byte_pos: pos={pos}");
 
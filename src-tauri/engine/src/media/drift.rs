@@ -0,0 +1,125 @@
+//! Audio that drifts against the picture, as in some remuxes: the audio of
+//! one release laid under the video of another that runs at a different
+//! speed. Only the packets' timestamps are read, with nothing decoded, and
+//! the lengths of the two streams compared; the two are taken to have
+//! started together, so that how far apart they end says how much one is
+//! stretched against the other. Streams also end apart for duller reasons,
+//! like padding at the end or a cut, so a stretch is only taken for one
+//! when it is one of the rate conversions that cause it, `KNOWN`, and
+//! makes a difference of `MIN_DRIFT` at least; anything else is left to
+//! `conform`, which compares what is heard.
+
+use serde::Serialize;
+
+use crate::media::demux::{self, StreamKind};
+use crate::media::internal::MediaError;
+use crate::media::units::{self, Seconds};
+use crate::subtitle::retime::Retime;
+
+/// Stretches that move the end by less than this are none; it is more than
+/// a packet or two, which streams often end apart by
+const MIN_DRIFT: f64 = 0.5;
+/// Offsets smaller than this are none: muxers leave streams this far apart,
+/// for an encoder's delay and the like, and players make up for it
+const MIN_OFFSET: f64 = 0.1;
+/// How near a stretch has to be to one of `KNOWN` to be taken for it
+const KNOWN_TOLERANCE: f64 = 5e-4;
+/// The speed changes of converting between film, NTSC and PAL rates, as the
+/// audio's length over the video's
+pub const KNOWN: [(f64, &str); 6] = [
+    (25.0 / (24000.0 / 1001.0), "25/23.976"),
+    ((24000.0 / 1001.0) / 25.0, "23.976/25"),
+    (25.0 / 24.0, "25/24"),
+    (24.0 / 25.0, "24/25"),
+    (1001.0 / 1000.0, "24/23.976"),
+    (1000.0 / 1001.0, "23.976/24"),
+];
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct DriftReport {
+    pub audio_index: usize,
+    pub video_index: usize,
+    /// from the first packet to the end of the last, by their timestamps
+    pub audio_span: (Seconds, Seconds),
+    pub video_span: (Seconds, Seconds),
+    /// what takes times on the picture to where the same moment is in the
+    /// audio, for `subtitle::retime`; absent if they already match
+    pub correction: Option<Retime>,
+    /// the rate conversion the stretch was taken for, like `25/23.976`
+    pub known: Option<String>,
+}
+
+/// The one of `KNOWN` that `factor` is near enough to be taken for, with
+/// its name
+pub fn known_ratio(factor: f64) -> Option<(f64, &'static str)> {
+    KNOWN.iter().copied().find(|x| (factor / x.0 - 1.0).abs() <= KNOWN_TOLERANCE)
+}
+
+/// Reads the timestamps of audio stream `audio` and video stream `video` of
+/// `path`, or of the default ones, and compares their lengths. `progress`
+/// is called with the fraction read so far, and returns `false` to give up.
+pub fn measure(
+    path: &std::path::Path, audio: Option<usize>, video: Option<usize>,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<DriftReport, MediaError> {
+    let mut demuxer = demux::Demuxer::open(path)?;
+    let stream = |index: Option<usize>, kind| match index {
+        Some(i) => demuxer.get_stream_from_index(i),
+        None => demuxer.get_stream_from_kind(kind),
+    }.map(|x| x.0);
+    let audio = stream(audio, StreamKind::Audio)?;
+    let video = stream(video, StreamKind::Video)?;
+    let duration = demuxer.duration().0;
+
+    // of the audio and the video, in seconds
+    let mut spans: [Option<(f64, f64)>; 2] = [None, None];
+    let mut reported = 0.0;
+    while let Some((i, packet)) = demuxer.next_packet() {
+        let (span, info) = match i {
+            _ if i == audio.index() => (&mut spans[0], &audio),
+            _ if i == video.index() => (&mut spans[1], &video),
+            _ => continue,
+        };
+        let Some(pts) = packet.pts() else { continue };
+        let start = units::Timestamp(pts).to_seconds(info.timebase()).0;
+        let end = units::Timestamp(pts + packet.duration()).to_seconds(info.timebase()).0;
+        let span = span.get_or_insert((start, end));
+        span.0 = span.0.min(start);
+        span.1 = span.1.max(end);
+
+        if duration > 0.0 && start - reported >= duration / 100.0 {
+            reported = start;
+            if !progress((start / duration).min(1.0)) {
+                return Err(MediaError::Cancelled);
+            }
+        }
+    }
+    progress(1.0);
+
+    let [Some(audio_span), Some(video_span)] = spans else {
+        return Err(MediaError::InternalError("no timestamps to compare".to_owned()));
+    };
+    let (audio_length, video_length) =
+        (audio_span.1 - audio_span.0, video_span.1 - video_span.0);
+    if !(audio_length > 0.0 && video_length > 0.0) {
+        return Err(MediaError::InternalError("too short to compare".to_owned()));
+    }
+    let known = known_ratio(audio_length / video_length)
+        .filter(|x| (video_length * (x.0 - 1.0)).abs() >= MIN_DRIFT);
+    let stretched = known.is_some();
+    let factor = known.map_or(1.0, |x| x.0);
+    let offset = audio_span.0 - video_span.0 * factor;
+    let correction = (stretched || offset.abs() >= MIN_OFFSET)
+        .then_some(Retime { factor, offset: Seconds(offset) });
+    log::debug!("drift: audio {audio_span:?}, video {video_span:?}, {correction:?}");
+    Ok(DriftReport {
+        audio_index: audio.index(),
+        video_index: video.index(),
+        audio_span: (Seconds(audio_span.0), Seconds(audio_span.1)),
+        video_span: (Seconds(video_span.0), Seconds(video_span.1)),
+        correction,
+        known: known.map(|x| x.1.to_owned()),
+    })
+}
@@ -0,0 +1,75 @@
+pub use ffmpeg_next::frame::{Audio as AudioData, Video as VideoData};
+
+use crate::media::units;
+
+pub enum Frame {
+    Audio(Audio),
+    Video(Video),
+    Subpicture(Subpicture)
+}
+
+#[derive(Clone)]
+pub struct FrameMetadata {
+    pub byte_pos: isize,
+    pub pkt_pos: i64,
+    pub time: units::Seconds,
+}
+
+/// Cloning copies the samples, which is still far cheaper than decoding
+#[derive(Clone)]
+pub struct Audio {
+    pub meta: FrameMetadata,
+    pub decoded: AudioData,
+}
+
+pub struct Video {
+    pub meta: FrameMetadata,
+    pub decoded: VideoData,
+}
+
+/// A positioned, already palette-resolved region of a bitmap subtitle page
+pub struct BitmapRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// packed RGBA, `width * height * 4` bytes
+    pub rgba: Vec<u8>,
+}
+
+/// One composed page of a bitmap subtitle stream. An empty `rects` means
+/// the page clears whatever was on screen before.
+pub struct Subpicture {
+    pub meta: FrameMetadata,
+    pub end_time: Option<units::Seconds>,
+    pub rects: Vec<BitmapRect>,
+}
+
+impl From<Audio> for Frame {
+    fn from(value: Audio) -> Self {
+        Frame::Audio(value)
+    }
+}
+
+impl From<Video> for Frame {
+    fn from(value: Video) -> Self {
+        Frame::Video(value)
+    }
+}
+
+impl From<Subpicture> for Frame {
+    fn from(value: Subpicture) -> Self {
+        Frame::Subpicture(value)
+    }
+}
+
+impl Frame {
+    #[expect(unused)]
+    pub fn meta(&self) -> &FrameMetadata {
+        match self {
+            Frame::Audio(f) => &f.meta,
+            Frame::Video(f) => &f.meta,
+            Frame::Subpicture(f) => &f.meta,
+        }
+    }
+}
\ No newline at end of file
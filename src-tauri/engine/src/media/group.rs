@@ -0,0 +1,317 @@
+//! Several files played as one, for a film delivered in reels or an episode
+//! in segments that is subtitled as a whole. The files follow each other on
+//! one timeline, each starting where the one before ends, so that times on
+//! it are those of the subtitles; seeking goes to the file the time falls
+//! in and decoding goes on into the next at the end of each. Files are
+//! taken to start at 0, as reels cut for delivery do, and to have the same
+//! streams, in the same order.
+//!
+//! Only playing and sampling go through the group. Whatever works on a file
+//! through `MediaBackend::session_mut` has its own times, so a group has no
+//! session to give, and what reads a session's file again on its own, like
+//! waveforms, transcription or drift, refuses groups; see
+//! `MediaBackend::is_group`.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use num_traits::ToPrimitive;
+
+use crate::media::audio;
+use crate::media::backend::{self, AudioBlock, AudioStatus, BackendKind, MediaBackend, MediaStatus, VideoStatus};
+use crate::media::damage::StreamDamage;
+use crate::media::frame;
+use crate::media::internal::MediaError;
+use crate::media::session::Snapshot;
+use crate::media::units::Seconds;
+
+struct Member {
+    backend: Box<dyn MediaBackend>,
+    /// where it starts on the group's timeline
+    offset: Seconds,
+}
+
+pub struct MediaGroup {
+    members: Vec<Member>,
+    duration: Seconds,
+    /// the member being decoded
+    current: usize,
+    /// the current member has been decoded to its end; what it had left is
+    /// taken before going on to the next
+    ended: bool,
+    /// of the audio samplers, once they are opened
+    per_second: Option<f64>,
+}
+
+impl MediaGroup {
+    /// Opens `paths`, which the caller has vetted, in that order
+    pub fn create(kind: BackendKind, paths: &[PathBuf]) -> Result<Self, MediaError> {
+        if paths.is_empty() {
+            return Err(MediaError::InternalError("a group needs at least one file".to_owned()));
+        }
+        let mut members = Vec::with_capacity(paths.len());
+        let mut offset = Seconds(0.0);
+        for path in paths {
+            let backend = backend::open(kind, path)?;
+            let duration = backend.status().duration;
+            if !(duration.0.is_finite() && duration.0 > 0.0) {
+                return Err(MediaError::InternalError(
+                    format!("can't tell how long {} is", path.display())));
+            }
+            members.push(Member { backend, offset });
+            offset = Seconds(offset.0 + duration.0);
+        }
+        Ok(Self { members, duration: offset, current: 0, ended: false, per_second: None })
+    }
+
+    /// Opens the files of `snapshot.members` and the players it lists,
+    /// then seeks to where it was; see `Session::restore`
+    pub fn restore(snapshot: &Snapshot) -> Result<Self, MediaError> {
+        let paths: Vec<PathBuf> = snapshot.members.iter().map(PathBuf::from).collect();
+        let mut group = Self::create(snapshot.backend, &paths)?;
+        if snapshot.audio_index.is_some() {
+            group.open_audio_player(snapshot.audio_index)?;
+        }
+        if snapshot.video_index.is_some() {
+            group.open_video_player(snapshot.video_index, snapshot.accel)?;
+            if let Some(size) = snapshot.output_size {
+                group.set_video_size(size)?;
+            }
+        }
+        group.seek(snapshot.position)?;
+        Ok(group)
+    }
+
+    /// Where each file starts on the timeline
+    pub fn offsets(&self) -> Vec<Seconds> {
+        self.members.iter().map(|x| x.offset).collect()
+    }
+
+    /// Does `f` to every member, giving what it did to each
+    fn for_all<T>(
+        &mut self, mut f: impl FnMut(&mut dyn MediaBackend) -> Result<T, MediaError>,
+    ) -> Result<Vec<T>, MediaError> {
+        self.members.iter_mut().map(|x| f(x.backend.as_mut())).collect()
+    }
+
+    /// An audio status for the whole group from those of its members
+    fn audio_status(statuses: Vec<AudioStatus>) -> Result<AudioStatus, MediaError> {
+        let length = statuses.iter().map(|x| x.length).sum();
+        let mut statuses = statuses.into_iter();
+        let first = statuses.next().unwrap();
+        if statuses.any(|x| x.sample_rate != first.sample_rate) {
+            return Err(MediaError::InternalError(
+                "the files of the group have different sample rates".to_owned()));
+        }
+        Ok(AudioStatus { length, ..first })
+    }
+
+    /// The member being decoded and where it starts
+    fn playing(&mut self) -> (&mut dyn MediaBackend, Seconds) {
+        let member = &mut self.members[self.current];
+        (member.backend.as_mut(), member.offset)
+    }
+}
+
+impl MediaBackend for MediaGroup {
+    /// the first file's
+    fn path(&self) -> &Path {
+        self.members[0].backend.path()
+    }
+
+    fn is_group(&self) -> bool {
+        true
+    }
+
+    fn status(&self) -> MediaStatus {
+        MediaStatus {
+            audio_only: self.members.iter().all(|x| x.backend.status().audio_only),
+            duration: self.duration,
+            ..self.members[0].backend.status()
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let current = &self.members[self.current];
+        let snapshot = current.backend.snapshot();
+        Snapshot {
+            path: self.members[0].backend.path().to_string_lossy().into_owned(),
+            members: self.members.iter()
+                .map(|x| x.backend.path().to_string_lossy().into_owned())
+                .collect(),
+            position: Seconds(snapshot.position.0 + current.offset.0),
+            ..snapshot
+        }
+    }
+
+    /// the members', on the group's timeline
+    fn damage(&self) -> Vec<StreamDamage> {
+        let mut all: Vec<StreamDamage> = Vec::new();
+        for member in &self.members {
+            for mut damage in member.backend.damage() {
+                for span in &mut damage.spans {
+                    span.start = Seconds(span.start.0 + member.offset.0);
+                    span.end = Seconds(span.end.0 + member.offset.0);
+                }
+                match all.iter_mut().find(|x| x.index == damage.index) {
+                    Some(x) => {
+                        x.errors += damage.errors;
+                        x.corrupt_frames += damage.corrupt_frames;
+                        x.spans.append(&mut damage.spans);
+                    }
+                    None => all.push(damage),
+                }
+            }
+        }
+        all
+    }
+
+    fn seek(&mut self, time: Seconds) -> Result<(), MediaError> {
+        self.current = self.members.iter().rposition(|x| x.offset.0 <= time.0).unwrap_or(0);
+        self.ended = false;
+        let (backend, offset) = self.playing();
+        backend.seek(Seconds((time.0 - offset.0).max(0.0)))
+    }
+
+    fn open_audio_player(&mut self, index: Option<usize>) -> Result<AudioStatus, MediaError> {
+        let statuses = self.for_all(|x| x.open_audio_player(index))?;
+        Self::audio_status(statuses)
+    }
+
+    fn open_audio_sampler(
+        &mut self, index: Option<usize>, per_second: f64
+    ) -> Result<AudioStatus, MediaError> {
+        let statuses = self.for_all(|x| x.open_audio_sampler(index, per_second))?;
+        self.per_second = Some(per_second);
+        Self::audio_status(statuses)
+    }
+
+    fn open_video_player(
+        &mut self, index: Option<usize>, accel: bool
+    ) -> Result<VideoStatus, MediaError> {
+        let mut statuses = self.for_all(|x| x.open_video_player(index, accel))?;
+        Ok(statuses.swap_remove(0))
+    }
+
+    fn set_video_size(&mut self, size: (u32, u32)) -> Result<bool, MediaError> {
+        let results = self.for_all(|x| x.set_video_size(size))?;
+        Ok(results.into_iter().all(|x| x))
+    }
+
+    fn decode(&mut self, budget: Duration) -> Result<bool, MediaError> {
+        if self.ended {
+            if self.current + 1 == self.members.len() {
+                return Ok(false);
+            }
+            self.current += 1;
+            self.ended = false;
+            self.playing().0.seek(Seconds(0.0))?;
+        }
+        if self.playing().0.decode(budget)? {
+            return Ok(true);
+        }
+        self.ended = true;
+        Ok(self.current + 1 < self.members.len())
+    }
+
+    fn take_audio(&mut self) -> VecDeque<AudioBlock> {
+        let (backend, offset) = self.playing();
+        let mut blocks = backend.take_audio();
+        for block in &mut blocks {
+            block.time = Seconds(block.time.0 + offset.0);
+        }
+        blocks
+    }
+
+    fn take_audio_samples(&mut self) -> Option<audio::SamplerDeltaData> {
+        let per_second = self.per_second;
+        let (backend, offset) = self.playing();
+        let mut delta = backend.take_audio_samples()?;
+        // each member's sampler counts from its own start
+        if let Some(shift) = per_second.and_then(|x| (offset.0 * x).round().to_usize()) {
+            delta.start_index += shift;
+        }
+        delta.start_time = Seconds(delta.start_time.0 + offset.0);
+        delta.end_time = Seconds(delta.end_time.0 + offset.0);
+        Some(delta)
+    }
+
+    fn take_video(&mut self) -> VecDeque<frame::Video> {
+        let (backend, offset) = self.playing();
+        let mut frames = backend.take_video();
+        for frame in &mut frames {
+            frame.meta.time = Seconds(frame.meta.time.0 + offset.0);
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::tests::{fixture, DURATION};
+
+    /// The fixture twice over
+    fn reels() -> MediaGroup {
+        let path = fixture().to_owned();
+        MediaGroup::create(BackendKind::Ffmpeg, &[path.clone(), path]).unwrap()
+    }
+
+    /// The next frame the video player gets, decoding as far as needed
+    fn next_frame_time(group: &mut MediaGroup) -> Option<f64> {
+        loop {
+            if let Some(frame) = group.take_video().pop_front() {
+                return Some(frame.meta.time.0);
+            }
+            if !group.decode(Duration::from_millis(10)).unwrap() {
+                return None;
+            }
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn files_follow_each_other() {
+        let group = reels();
+        assert!(group.is_group());
+        let offsets = group.offsets();
+        assert_eq!(offsets.len(), 2);
+        assert_close(offsets[0].0, 0.0);
+        assert_close(offsets[1].0, DURATION);
+        assert_close(group.status().duration.0, 2.0 * DURATION);
+        assert_eq!(group.path(), fixture());
+        assert_eq!(group.snapshot().members.len(), 2);
+    }
+
+    #[test]
+    fn seeks_into_the_file_the_time_falls_in() {
+        let mut group = reels();
+        group.open_video_player(None, false).unwrap();
+        group.seek(Seconds(DURATION + 0.5)).unwrap();
+        assert_eq!(group.current, 1);
+        // on the group's timeline, not the second file's
+        let time = next_frame_time(&mut group).unwrap();
+        assert!(time >= DURATION && time <= DURATION + 0.5 + 1e-3, "{time}");
+        assert!(group.snapshot().position.0 >= DURATION);
+    }
+
+    #[test]
+    fn decodes_on_into_the_next_file() {
+        let mut group = reels();
+        group.open_video_player(None, false).unwrap();
+        group.seek(Seconds(DURATION - 0.2)).unwrap();
+        assert_eq!(group.current, 0);
+        let mut last = 0.0;
+        while let Some(time) = next_frame_time(&mut group) {
+            assert!(time >= last, "{time} after {last}");
+            last = time;
+        }
+        // less the frames left in the decoder at the end
+        assert_eq!(group.current, 1);
+        assert!(last > 1.5 * DURATION, "{last}");
+    }
+}
@@ -0,0 +1,84 @@
+//! Still images and numbered image sequences opened as video, for
+//! typesetting against screenshots before the video itself is at hand.
+
+use std::path::Path;
+
+use ffmpeg::{format, Dictionary, Format};
+use serde::{Deserialize, Serialize};
+
+use crate::media::{internal::{check, MediaError}, units::Seconds};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ImageOptions {
+    pub framerate: f64,
+    /// the file is the first of a numbered sequence, such as `shot0001.png`,
+    /// rather than a single picture
+    pub sequence: bool,
+    /// how long a single picture is shown for; a sequence lasts as long as
+    /// it has pictures
+    pub duration: Seconds,
+}
+
+/// `shot0012.png` becomes `shot%04d.png` starting at 12: the last run of
+/// digits in the file name is what counts up
+fn sequence_pattern(path: &Path) -> Result<(String, u64), MediaError> {
+    let not_numbered = || MediaError::InternalError(
+        format!("{}: not a numbered image", path.display()));
+    let stem = path.file_stem().and_then(|x| x.to_str()).ok_or_else(not_numbered)?;
+    let end = stem.rfind(|x: char| x.is_ascii_digit()).ok_or_else(not_numbered)? + 1;
+    let start = stem[..end]
+        .rfind(|x: char| !x.is_ascii_digit())
+        .map_or(0, |x| x + 1);
+    let digits = &stem[start..end];
+    let number = digits.parse().map_err(|_| not_numbered())?;
+
+    // `%` is special in the pattern everywhere else
+    let escape = |x: &str| x.replace('%', "%%");
+    let counter = if digits.starts_with('0') {
+        format!("%0{}d", digits.len())
+    } else {
+        "%d".to_owned()
+    };
+    let mut name = format!("{}{counter}{}", escape(&stem[..start]), escape(&stem[end..]));
+    if let Some(extension) = path.extension().and_then(|x| x.to_str()) {
+        name = format!("{name}.{}", escape(extension));
+    }
+    let dir = path.parent().map(|x| escape(&x.to_string_lossy())).unwrap_or_default();
+    let separator = if dir.is_empty() { "" } else { std::path::MAIN_SEPARATOR_STR };
+    Ok((format!("{dir}{separator}{name}"), number))
+}
+
+/// Opens `path` through ffmpeg's image2 demuxer. A single picture is
+/// repeated endlessly, and it is up to the caller to stop at
+/// `options.duration`.
+pub fn open(path: &Path, options: &ImageOptions) -> Result<format::context::Input, MediaError> {
+    if !(options.framerate.is_finite() && options.framerate > 0.0) {
+        return Err(MediaError::InternalError(
+            format!("invalid framerate: {}", options.framerate)));
+    }
+
+    let mut dictionary = Dictionary::new();
+    dictionary.set("framerate", &options.framerate.to_string());
+    let target = if options.sequence {
+        let (pattern, start_number) = sequence_pattern(path)?;
+        dictionary.set("pattern_type", "sequence");
+        dictionary.set("start_number", &start_number.to_string());
+        pattern
+    } else {
+        dictionary.set("pattern_type", "none");
+        dictionary.set("loop", "1");
+        path.to_string_lossy().into_owned()
+    };
+
+    let image2 = unsafe { ffmpeg_sys_next::av_find_input_format(c"image2".as_ptr()) };
+    if image2.is_null() {
+        return Err(MediaError::InternalError("image2 demuxer not available".to_owned()));
+    }
+    let image2 = Format::Input(unsafe { format::Input::wrap(image2.cast_mut()) });
+    match check!(format::open_with(&target, &image2, dictionary))? {
+        format::context::Context::Input(x) => Ok(x),
+        format::context::Context::Output(_) => unreachable!(),
+    }
+}
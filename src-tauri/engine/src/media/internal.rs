@@ -2,6 +2,8 @@ extern crate ffmpeg_next as ffmpeg;
 
 use core::fmt;
 
+use crate::media::units::Seconds;
+
 #[derive(Debug)]
 pub enum MediaError {
     FFMpegError {
@@ -10,6 +12,13 @@ pub enum MediaError {
         line: u32,
     },
     InternalError(String),
+    /// in a file still being written, past the part that is there
+    DataNotYetAvailable { playable_until: Seconds },
+    /// the file has no video stream, as with plain audio files
+    NoVideo,
+    /// the job was given up because nobody is waiting for it any more,
+    /// such as when its progress can't be delivered
+    Cancelled,
 }
 
 impl fmt::Display for MediaError {
@@ -19,6 +28,12 @@ impl fmt::Display for MediaError {
                 => write!(f, "at {line}: {func}: {e}"),
             MediaError::InternalError(msg) 
                 => write!(f, "internal error: {msg}"),
+            MediaError::DataNotYetAvailable { playable_until }
+                => write!(f, "data not yet available; playable until {playable_until}"),
+            MediaError::NoVideo
+                => write!(f, "no video stream"),
+            MediaError::Cancelled
+                => write!(f, "cancelled"),
         }
     }
 }
@@ -0,0 +1,110 @@
+//! Integrated loudness after ITU-R BS.1770 (K-weighting, 400 ms blocks,
+//! absolute and relative gating), measured incrementally over mono samples
+//! as they are played.
+
+/// Blocks quieter than this don't count at all
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks this much quieter than the ungated loudness don't count
+const RELATIVE_GATE: f64 = -10.0;
+const BLOCK_SECONDS: f64 = 0.4;
+
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[1] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[2] * y;
+        y
+    }
+}
+
+pub struct LoudnessMeter {
+    shelf: Biquad,
+    highpass: Biquad,
+    block_length: usize,
+    block_sum: f64,
+    block_count: usize,
+    /// mean squares of the blocks above the absolute gate
+    blocks: Vec<f64>,
+    integrated: Option<f64>,
+}
+
+fn to_lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+impl LoudnessMeter {
+    /// Filter coefficients as derived for arbitrary sample rates by libebur128
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn new(sample_rate: u32) -> LoudnessMeter {
+        let rate = f64::from(sample_rate);
+
+        let (f0, gain, q) = (1_681.974_450_955_533, 3.999_843_853_973_347, 0.707_175_236_955_419_6);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad {
+            b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+            a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        };
+
+        let (f0, q) = (38.135_470_876_024_44, 0.500_327_037_323_877_3);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let highpass = Biquad {
+            b: [1.0, -2.0, 1.0],
+            a: [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            z: [0.0; 2],
+        };
+
+        LoudnessMeter {
+            shelf, highpass,
+            block_length: (rate * BLOCK_SECONDS).round().max(1.0) as usize,
+            block_sum: 0.0,
+            block_count: 0,
+            blocks: Vec::new(),
+            integrated: None,
+        }
+    }
+
+    pub fn feed(&mut self, samples: &[f32]) {
+        for &x in samples {
+            let y = self.highpass.process(self.shelf.process(f64::from(x)));
+            self.block_sum += y * y;
+            self.block_count += 1;
+            if self.block_count == self.block_length {
+                #[allow(clippy::cast_precision_loss)]
+                let mean_square = self.block_sum / self.block_length as f64;
+                if to_lufs(mean_square) > ABSOLUTE_GATE {
+                    self.blocks.push(mean_square);
+                    self.integrated = self.compute_integrated();
+                }
+                self.block_sum = 0.0;
+                self.block_count = 0;
+            }
+        }
+    }
+
+    /// `None` until something louder than silence has been heard
+    pub fn integrated(&self) -> Option<f64> {
+        self.integrated
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn compute_integrated(&self) -> Option<f64> {
+        let ungated = self.blocks.iter().sum::<f64>() / self.blocks.len() as f64;
+        let threshold = to_lufs(ungated) + RELATIVE_GATE;
+        let (sum, count) = self.blocks.iter()
+            .filter(|&&x| to_lufs(x) > threshold)
+            .fold((0.0, 0usize), |(s, c), x| (s + x, c + 1));
+        (count > 0).then(|| to_lufs(sum / count as f64))
+    }
+}
@@ -0,0 +1,49 @@
+//! The audio last handed to the player, kept so that the last few seconds
+//! can be heard again at once, like the rewind pedal of a transcription
+//! machine, without seeking and decoding them again. It is counted back
+//! from the newest audio given out, which the player may still have a
+//! little of to play.
+
+use std::collections::VecDeque;
+
+use crate::media::backend::AudioBlock;
+use crate::media::units::Seconds;
+
+/// How far back the buffer goes
+pub const CAPACITY: Seconds = Seconds(30.0);
+/// A jump forward larger than this is a seek rather than a gap in the
+/// stream
+const MAX_GAP: Seconds = Seconds(1.0);
+
+#[derive(Default)]
+pub struct Monitor {
+    blocks: VecDeque<AudioBlock>,
+}
+
+impl Monitor {
+    /// Keeps copies of `blocks`, in the order they are played. After a seek
+    /// what was kept from before it is dropped, since it wasn't just heard.
+    pub fn record(&mut self, blocks: &VecDeque<AudioBlock>) {
+        for block in blocks {
+            if let Some(last) = self.blocks.back()
+                && (block.time.0 < last.time.0 || block.time.0 - last.time.0 > MAX_GAP.0)
+            {
+                self.blocks.clear();
+            }
+            self.blocks.push_back(block.clone());
+        }
+        let Some(newest) = self.blocks.back().map(|x| x.time.0) else { return };
+        while self.blocks.front().is_some_and(|x| x.time.0 < newest - CAPACITY.0) {
+            self.blocks.pop_front();
+        }
+    }
+
+    /// The blocks of about the last `length`, oldest first
+    pub fn last(&self, length: Seconds) -> VecDeque<AudioBlock> {
+        let Some(newest) = self.blocks.back().map(|x| x.time.0) else { return VecDeque::new() };
+        let from = self.blocks.iter()
+            .rposition(|x| x.time.0 <= newest - length.0)
+            .unwrap_or(0);
+        self.blocks.range(from..).cloned().collect()
+    }
+}
@@ -0,0 +1,485 @@
+//! Matroska muxing by stream copy, or MP4 for where Matroska won't play.
+//! Nothing is re-encoded: video and audio come from the source file as they
+//! are, subtitle tracks from files ffmpeg can demux (ASS, SRT...), fonts
+//! become attachments and chapters are written natively. Tags can be set on
+//! the file and its tracks, and those that would travel along unasked can
+//! be left out.
+//!
+//! A plan can be gone through without writing anything, for how long and
+//! large the file will be and what in it the container can't hold; see
+//! `estimate`.
+
+use std::ffi::CStr;
+use std::path::Path;
+
+use ffmpeg::{codec, format::{self, stream::Disposition}, Dictionary, Packet, Rational};
+use ffmpeg_sys_next as ffi;
+use serde::{Deserialize, Serialize};
+
+use crate::media::{demux::StreamKind, internal::{check, MediaError}, units::{self, Seconds}, verify::{read_cues, Expectation}};
+use crate::subtitle::chapters::Chapter;
+
+/// What a file takes beyond its streams, for indexes, headers and the like,
+/// as a fraction of their size
+const OVERHEAD: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum Container {
+    #[default]
+    Matroska,
+    /// for players and sites that take nothing else; subtitles have to be
+    /// `mov_text`, and nothing can be attached
+    Mp4,
+}
+
+impl Container {
+    /// ffmpeg's name for the muxer
+    pub fn muxer(self) -> &'static CStr {
+        match self {
+            Self::Matroska => c"matroska",
+            Self::Mp4 => c"mp4",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SubtitleTrack {
+    pub path: String,
+    pub name: String,
+    /// ISO 639-2, like `eng`
+    pub language: String,
+    pub default: bool,
+    pub forced: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Attachment {
+    pub path: String,
+    /// shown by players that list attachments
+    pub description: Option<String>,
+}
+
+/// Tags to set on one of the streams copied from the source
+#[derive(Clone, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct TrackMetadata {
+    /// index in the source
+    pub stream: usize,
+    pub title: Option<String>,
+    /// ISO 639-2, like `eng`
+    pub language: Option<String>,
+}
+
+/// What to leave out, for sharing files without what they would give away
+/// about the source and the tools
+#[derive(Clone, Copy, Debug, Default, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct StripMetadata {
+    /// tags of the source's streams, such as encoder, handler names,
+    /// creation times and statistics; only those the plan sets are written
+    pub source_tags: bool,
+    /// the version of the muxing library, which is written by default
+    pub muxer_version: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MuxPlan {
+    pub source: String,
+    /// streams of `source` to copy; empty for all of its video and audio
+    pub streams: Vec<usize>,
+    pub subtitles: Vec<SubtitleTrack>,
+    /// font files to attach
+    pub fonts: Vec<Attachment>,
+    pub chapters: Vec<Chapter>,
+    /// of the whole file
+    #[serde(default)]
+    pub title: Option<String>,
+    /// the application named as having written the file
+    #[serde(default)]
+    pub encoder: Option<String>,
+    #[serde(default)]
+    pub tracks: Vec<TrackMetadata>,
+    #[serde(default)]
+    pub strip: StripMetadata,
+    #[serde(default)]
+    pub container: Container,
+    pub output: String,
+}
+
+/// Why the plan would fail or lose something, found before it is executed
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+#[ts(export)]
+pub enum PlanProblem {
+    /// `track` is a stream of the source, as `stream 1`, or the name of a
+    /// subtitle track
+    #[serde(rename_all = "camelCase")]
+    UnsupportedCodec { track: String, codec: String },
+    /// fonts, or attachments of the source, in a container other than
+    /// Matroska
+    #[serde(rename_all = "camelCase")]
+    Attachments { count: usize },
+    /// a stream of `MuxPlan::streams` the source doesn't have
+    #[serde(rename_all = "camelCase")]
+    NoSuchStream { stream: usize },
+    /// a subtitle track's file, which holds no subtitles ffmpeg can read
+    #[serde(rename_all = "camelCase")]
+    NoSubtitles { path: String },
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ExportEstimate {
+    pub duration: Seconds,
+    /// in bytes, from the bitrates of the streams copied; video and audio
+    /// that give none share what the source's overall bitrate leaves
+    pub size: u64,
+    pub problems: Vec<PlanProblem>,
+}
+
+/// A packet waiting for its turn, with its timestamps still in `timebase`
+struct Pending {
+    time: f64,
+    output_index: usize,
+    timebase: Rational,
+    packet: Packet,
+}
+
+fn to_seconds(ts: i64, timebase: Rational) -> f64 {
+    units::Timestamp(ts).to_seconds(timebase).0
+}
+
+fn write_packet(
+    output: &mut format::context::Output, mut pending: Pending,
+) -> Result<(), MediaError> {
+    let timebase = output.stream(pending.output_index).unwrap().time_base();
+    pending.packet.rescale_ts(pending.timebase, timebase);
+    pending.packet.set_stream(pending.output_index);
+    pending.packet.set_position(-1);
+    check!(pending.packet.write_interleaved(output))
+}
+
+/// Marks a copied stream as not tied to the source container's codec tags
+fn clear_codec_tag(stream: &mut ffmpeg::StreamMut) {
+    unsafe {
+        (*(*stream.as_mut_ptr()).codecpar).codec_tag = 0;
+    }
+}
+
+fn set_disposition(stream: &mut ffmpeg::StreamMut, disposition: Disposition) {
+    unsafe {
+        (*stream.as_mut_ptr()).disposition = disposition.bits();
+    }
+}
+
+fn add_font(output: &mut format::context::Output, font: &Attachment) -> Result<(), MediaError> {
+    let path = Path::new(&font.path);
+    let data = std::fs::read(path)
+        .map_err(|e| MediaError::InternalError(format!("{}: {e}", path.display())))?;
+    let is_otf = path.extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("otf"));
+    let (codec_id, mimetype) = if is_otf {
+        (ffi::AVCodecID::AV_CODEC_ID_OTF, "application/vnd.ms-opentype")
+    } else {
+        (ffi::AVCodecID::AV_CODEC_ID_TTF, "application/x-truetype-font")
+    };
+
+    let mut stream = check!(output.add_stream(None::<ffmpeg::Codec>))?;
+    unsafe {
+        let parameters = (*stream.as_mut_ptr()).codecpar;
+        let padding = usize::try_from(ffi::AV_INPUT_BUFFER_PADDING_SIZE).unwrap();
+        let extradata = ffi::av_mallocz(data.len() + padding).cast::<u8>();
+        if extradata.is_null() {
+            return Err(MediaError::InternalError("add_font: out of memory".to_owned()));
+        }
+        std::ptr::copy_nonoverlapping(data.as_ptr(), extradata, data.len());
+        (*parameters).codec_type = ffi::AVMediaType::AVMEDIA_TYPE_ATTACHMENT;
+        (*parameters).codec_id = codec_id;
+        (*parameters).extradata = extradata;
+        (*parameters).extradata_size = i32::try_from(data.len())
+            .map_err(|_| MediaError::InternalError("add_font: font too large".to_owned()))?;
+    }
+
+    let mut metadata = Dictionary::new();
+    let name = path.file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or_default();
+    metadata.set("filename", &name);
+    metadata.set("mimetype", mimetype);
+    if let Some(description) = &font.description {
+        // the Matroska muxer writes this as the file's description
+        metadata.set("title", description);
+    }
+    stream.set_metadata(metadata);
+    Ok(())
+}
+
+/// Reads every packet of the first subtitle stream in `path`. Subtitle
+/// files are small, so holding them lets them be interleaved with the
+/// source as it is copied.
+fn read_subtitle(
+    output: &mut format::context::Output, container: Container, track: &SubtitleTrack,
+) -> Result<Vec<Pending>, MediaError> {
+    let mut input = check!(format::input(&track.path))?;
+    let Some(source) = input.streams()
+        .find(|x| x.parameters().medium() == StreamKind::Subtitle) else
+    {
+        return Err(MediaError::InternalError(
+            format!("{}: no subtitle stream", track.path)));
+    };
+    let id = source.parameters().id();
+    if !holds_subtitles(container, id) {
+        return Err(MediaError::InternalError(
+            format!("{}: {} subtitles can't go in {container:?}", track.path, id.name())));
+    }
+    let (input_index, timebase) = (source.index(), source.time_base());
+
+    let mut stream = check!(output.add_stream(None::<ffmpeg::Codec>))?;
+    stream.set_parameters(source.parameters());
+    clear_codec_tag(&mut stream);
+    let mut disposition = Disposition::empty();
+    disposition.set(Disposition::DEFAULT, track.default);
+    disposition.set(Disposition::FORCED, track.forced);
+    set_disposition(&mut stream, disposition);
+    let mut metadata = Dictionary::new();
+    metadata.set("title", &track.name);
+    metadata.set("language", &track.language);
+    stream.set_metadata(metadata);
+    let output_index = stream.index();
+
+    Ok(input.packets()
+        .filter(|(s, _)| s.index() == input_index)
+        .map(|(_, packet)| Pending {
+            time: to_seconds(packet.pts().or(packet.dts()).unwrap_or(0), timebase),
+            output_index, timebase, packet,
+        })
+        .collect())
+}
+
+/// Whether `plan` copies `source`
+fn copies(plan: &MuxPlan, source: &format::stream::Stream) -> bool {
+    if plan.streams.is_empty() {
+        matches!(source.parameters().medium(), StreamKind::Video | StreamKind::Audio)
+    } else {
+        plan.streams.contains(&source.index())
+    }
+}
+
+/// Whether `container` can hold `id`; taken that it can when ffmpeg can't
+/// tell
+fn can_hold(container: Container, id: codec::Id) -> bool {
+    unsafe {
+        let format = ffi::av_guess_format(container.muxer().as_ptr(), std::ptr::null(), std::ptr::null());
+        // with FF_COMPLIANCE_NORMAL
+        format.is_null() || ffi::avformat_query_codec(format, id.into(), 0) != 0
+    }
+}
+
+/// Whether `container` can hold subtitles in `id`: MP4 only `mov_text`,
+/// which is all its players read, though ffmpeg would write others
+fn holds_subtitles(container: Container, id: codec::Id) -> bool {
+    match container {
+        Container::Matroska => can_hold(container, id),
+        Container::Mp4 => id == codec::Id::MOV_TEXT,
+    }
+}
+
+/// Goes through `plan` without writing anything: how long the file will be,
+/// about how large, and what would fail or be lost
+pub fn estimate(plan: &MuxPlan) -> Result<ExportEstimate, MediaError> {
+    let input = check!(format::input(&plan.source))?;
+    let duration = units::Timestamp(input.duration()).to_seconds(units::DEFAULT_TIMEBASE);
+    let mut problems: Vec<PlanProblem> = plan.streams.iter()
+        .filter(|&&x| x >= input.nb_streams() as usize)
+        .map(|&stream| PlanProblem::NoSuchStream { stream })
+        .collect();
+
+    let bitrate = |x: &format::stream::Stream| unsafe { (*x.parameters().as_ptr()).bit_rate };
+    let playable = |x: &format::stream::Stream|
+        matches!(x.parameters().medium(), StreamKind::Video | StreamKind::Audio);
+    let unknown = input.streams().filter(|x| playable(x) && bitrate(x) <= 0).count();
+    let known: i64 = input.streams().map(|x| bitrate(&x).max(0)).sum();
+    let share = if unknown == 0 {
+        0
+    } else {
+        (input.bit_rate() - known).max(0) / i64::try_from(unknown).unwrap()
+    };
+    let mut bits_per_second = 0;
+    let mut attachments = plan.fonts.len();
+    for source in input.streams().filter(|x| copies(plan, x)) {
+        let rate = bitrate(&source);
+        if rate > 0 {
+            bits_per_second += rate;
+        } else if playable(&source) {
+            bits_per_second += share;
+        }
+        let id = source.parameters().id();
+        if source.parameters().medium() == StreamKind::Attachment {
+            attachments += 1;
+        } else if !can_hold(plan.container, id) {
+            problems.push(PlanProblem::UnsupportedCodec {
+                track: format!("stream {}", source.index()),
+                codec: id.name().to_owned(),
+            });
+        }
+    }
+    if attachments > 0 && plan.container != Container::Matroska {
+        problems.push(PlanProblem::Attachments { count: attachments });
+    }
+
+    let file_size = |path: &str| std::fs::metadata(path)
+        .map(|x| x.len())
+        .map_err(|e| MediaError::InternalError(format!("{path}: {e}")));
+    // subtitles and fonts go in about as they are
+    let mut carried = 0;
+    for track in &plan.subtitles {
+        let subtitles = check!(format::input(&track.path))?;
+        match subtitles.streams().find(|x| x.parameters().medium() == StreamKind::Subtitle) {
+            Some(x) if !holds_subtitles(plan.container, x.parameters().id()) =>
+                problems.push(PlanProblem::UnsupportedCodec {
+                    track: track.name.clone(),
+                    codec: x.parameters().id().name().to_owned(),
+                }),
+            Some(_) => (),
+            None => problems.push(PlanProblem::NoSubtitles { path: track.path.clone() }),
+        }
+        carried += file_size(&track.path)?;
+    }
+    for font in &plan.fonts {
+        carried += file_size(&font.path)?;
+    }
+
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let size = ((bits_per_second as f64 / 8.0 * duration.0 + carried as f64)
+        * (1.0 + OVERHEAD)).round() as u64;
+    Ok(ExportEstimate { duration, size, problems })
+}
+
+/// Executes `plan`, calling `progress` with the fraction of the source
+/// copied so far; it returns `false` to give up
+pub fn mux(plan: &MuxPlan, mut progress: impl FnMut(f64) -> bool) -> Result<(), MediaError> {
+    if plan.container != Container::Matroska && !plan.fonts.is_empty() {
+        return Err(MediaError::InternalError("fonts can only be attached in Matroska".to_owned()));
+    }
+    let mut input = check!(format::input(&plan.source))?;
+    let muxer = plan.container.muxer().to_str().unwrap();
+    let mut output = check!(format::output_as(&plan.output, muxer))?;
+    let duration = units::Timestamp(input.duration()).to_seconds(units::DEFAULT_TIMEBASE).0;
+
+    // input stream index -> output stream index
+    let mut mapping = vec![None; input.nb_streams() as usize];
+    for source in input.streams() {
+        if !copies(plan, &source) {
+            continue;
+        }
+        let mut metadata = if plan.strip.source_tags {
+            Dictionary::new()
+        } else {
+            source.metadata().to_owned()
+        };
+        if let Some(track) = plan.tracks.iter().find(|x| x.stream == source.index()) {
+            if let Some(title) = &track.title {
+                metadata.set("title", title);
+            }
+            if let Some(language) = &track.language {
+                metadata.set("language", language);
+            }
+        }
+        let mut stream = check!(output.add_stream(None::<ffmpeg::Codec>))?;
+        stream.set_parameters(source.parameters());
+        stream.set_metadata(metadata);
+        clear_codec_tag(&mut stream);
+        set_disposition(&mut stream, source.disposition());
+        mapping[source.index()] = Some(stream.index());
+    }
+
+    let mut subtitles = Vec::new();
+    for track in &plan.subtitles {
+        subtitles.append(&mut read_subtitle(&mut output, plan.container, track)?);
+    }
+    subtitles.sort_by(|a, b| b.time.total_cmp(&a.time));
+
+    for font in &plan.fonts {
+        add_font(&mut output, font)?;
+    }
+
+    let mut metadata = Dictionary::new();
+    if let Some(title) = &plan.title {
+        metadata.set("title", title);
+    }
+    // `encoder` is libavformat's own, set over whatever is given
+    if let Some(encoder) = &plan.encoder {
+        metadata.set("encoding_tool", encoder);
+    }
+    output.set_metadata(metadata);
+    if plan.strip.muxer_version {
+        unsafe {
+            (*output.as_mut_ptr()).flags |= ffi::AVFMT_FLAG_BITEXACT;
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    let to_ms = |x: f64| (x * 1000.0).round() as i64;
+    for (i, chapter) in plan.chapters.iter().enumerate() {
+        let end = plan.chapters.get(i + 1).map_or(duration, |x| x.start.0);
+        check!(output.add_chapter(
+            i64::try_from(i).unwrap(), Rational(1, 1000),
+            to_ms(chapter.start.0), to_ms(end.max(chapter.start.0)), &chapter.title))?;
+    }
+
+    check!(output.write_header())?;
+
+    let mut reported = 0.0;
+    for (source, packet) in input.packets() {
+        let Some(output_index) = mapping[source.index()] else { continue };
+        let timebase = source.time_base();
+        let time = to_seconds(packet.dts().or(packet.pts()).unwrap_or(0), timebase);
+        while subtitles.last().is_some_and(|x| x.time <= time) {
+            write_packet(&mut output, subtitles.pop().unwrap())?;
+        }
+        write_packet(&mut output, Pending { time, output_index, timebase, packet })?;
+
+        if duration > 0.0 && time - reported >= duration / 100.0 {
+            reported = time;
+            if !progress((time / duration).min(1.0)) {
+                return Err(MediaError::Cancelled);
+            }
+        }
+    }
+    while let Some(pending) = subtitles.pop() {
+        write_packet(&mut output, pending)?;
+    }
+    check!(output.write_trailer())?;
+    progress(1.0);
+    Ok(())
+}
+
+/// What the output of `plan` should contain, for `verify::verify_media`
+pub fn expectation(plan: &MuxPlan) -> Result<Expectation, MediaError> {
+    let source = check!(format::input(&plan.source))?;
+    let copied = if plan.streams.is_empty() {
+        source.streams()
+            .filter(|x| matches!(x.parameters().medium(), StreamKind::Video | StreamKind::Audio))
+            .count()
+    } else {
+        plan.streams.len()
+    };
+    let mut subtitles = Vec::new();
+    for track in &plan.subtitles {
+        subtitles.push(read_cues(&track.path)?.into_iter().next().unwrap_or_default());
+    }
+    Ok(Expectation {
+        tracks: copied + plan.subtitles.len() + plan.fonts.len(),
+        duration: units::Timestamp(source.duration()).to_seconds(units::DEFAULT_TIMEBASE),
+        subtitles,
+    })
+}
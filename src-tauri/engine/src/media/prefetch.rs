@@ -0,0 +1,266 @@
+//! Decoding ahead at the cues around where playback is, so that jumping to
+//! the next or previous one plays at once instead of after decoding from
+//! the keyframe before it. A second backend on the same file, restored from
+//! the playback's snapshot and run on a thread of its own, decodes the
+//! first `PrefetchPolicy::window` of each. A jump that finds one is given
+//! that, and the playback's own decoder goes on from where it ends, which
+//! it then has the window's length to get to. Decoded video is large, so
+//! all that is kept is held to `MAX_BYTES`, and a window that doesn't fit
+//! is cut short.
+
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::media::backend::{self, AudioBlock, MediaBackend};
+use crate::media::frame;
+use crate::media::internal::MediaError;
+use crate::media::session::Snapshot;
+use crate::media::units::Seconds;
+
+/// How far a jump may land from a cue's start to be given what was decoded
+/// for it
+const TOLERANCE: f64 = 0.001;
+/// Decoding slice between checks for having been stopped
+const BUDGET: Duration = Duration::from_millis(20);
+pub const MAX_WINDOW: Seconds = Seconds(2.0);
+/// Of `ahead` and `behind` together; each is kept decoded in memory
+pub const MAX_CUES: usize = 8;
+/// What is kept decoded for one playback, all cues together; a second of
+/// 1080p video at the player's size is about half of it
+pub const MAX_BYTES: usize = 512 << 20;
+
+#[derive(Clone, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct PrefetchPolicy {
+    /// the starts of the cues that can be jumped to, as listed
+    pub cues: Vec<Seconds>,
+    /// how many cues after the position to keep decoded
+    pub ahead: usize,
+    /// and before it
+    pub behind: usize,
+    /// how much is decoded from each start, as far as `MAX_BYTES` allows
+    pub window: Seconds,
+}
+
+/// What was decoded from the start of a cue up to `until`, not including it
+pub struct Prefetched {
+    pub time: Seconds,
+    pub until: Seconds,
+    pub audio: VecDeque<AudioBlock>,
+    pub video: VecDeque<frame::Video>,
+}
+
+struct Shared {
+    policy: PrefetchPolicy,
+    position: Seconds,
+    cache: Vec<Prefetched>,
+    /// starts that couldn't be decoded, not to be tried again until the
+    /// policy changes
+    failed: Vec<Seconds>,
+}
+
+impl Prefetched {
+    fn bytes(&self) -> usize {
+        self.audio.iter().map(|x| x.samples.len() * size_of::<f32>()).sum::<usize>()
+            + self.video.iter().map(|x| x.decoded.data(0).len()).sum::<usize>()
+    }
+}
+
+fn same(a: Seconds, b: Seconds) -> bool {
+    (a.0 - b.0).abs() < TOLERANCE
+}
+
+impl Shared {
+    /// The starts to keep decoded, nearest first
+    fn targets(&self) -> Vec<Seconds> {
+        let mut cues = self.policy.cues.clone();
+        cues.sort_by(|a, b| a.0.total_cmp(&b.0));
+        cues.dedup_by(|a, b| same(*a, *b));
+        let split = cues.partition_point(|x| x.0 < self.position.0 + TOLERANCE);
+        let (before, after) = cues.split_at(split);
+        // the cue jumped to is where playback is, not one to jump to
+        let mut before = before.iter().rev()
+            .filter(|&&x| !same(x, self.position))
+            .take(self.policy.behind);
+        let mut after = after.iter().take(self.policy.ahead);
+        let mut result = Vec::new();
+        loop {
+            let (a, b) = (after.next(), before.next());
+            if a.is_none() && b.is_none() {
+                return result;
+            }
+            result.extend(a.into_iter().chain(b).copied());
+        }
+    }
+}
+
+/// Keeps what starts from the last item at or before `time` on
+fn trim<T>(items: &mut VecDeque<T>, time: Seconds, at: impl Fn(&T) -> Seconds) {
+    let first = items.iter().rposition(|x| at(x).0 <= time.0).unwrap_or(0);
+    items.drain(..first);
+}
+
+/// What was decoded from `time` for `window`, or until the video would
+/// take more than `budget` bytes
+fn prefetch(
+    backend: &mut dyn MediaBackend, time: Seconds, window: Seconds,
+    (has_audio, has_video): (bool, bool), budget: usize,
+) -> Result<Prefetched, MediaError> {
+    let mut until = Seconds(time.0 + window.0);
+    let mut bytes = 0;
+    backend.seek(time)?;
+    let mut result = Prefetched {
+        time, until, audio: VecDeque::new(), video: VecDeque::new()
+    };
+    let (mut audio_done, mut video_done) = (!has_audio, !has_video);
+    loop {
+        let more = backend.decode(BUDGET)?;
+        for block in backend.take_audio() {
+            if block.time.0 < until.0 {
+                result.audio.push_back(block);
+            } else {
+                audio_done = true;
+            }
+        }
+        for frame in backend.take_video() {
+            let size = frame.decoded.data(0).len();
+            if frame.meta.time.0 >= until.0 {
+                video_done = true;
+            } else if frame.meta.time.0 <= time.0 {
+                // only the last before `time` is kept; see `trim`
+                result.video.clear();
+                bytes = size;
+                result.video.push_back(frame);
+            } else if bytes + size > budget {
+                until = frame.meta.time;
+                video_done = true;
+            } else {
+                bytes += size;
+                result.video.push_back(frame);
+            }
+        }
+        if !more || (audio_done && video_done) {
+            break;
+        }
+    }
+    result.until = until;
+    result.audio.retain(|x| x.time.0 < until.0);
+    trim(&mut result.audio, time, |x| x.time);
+    trim(&mut result.video, time, |x| x.meta.time);
+    Ok(result)
+}
+
+fn run(mut backend: Box<dyn MediaBackend>, shared: &Mutex<Shared>, wake: &mpsc::Receiver<()>) {
+    let snapshot = backend.snapshot();
+    let streams = (snapshot.audio_index.is_some(), snapshot.video_index.is_some());
+    loop {
+        let next = {
+            let mut shared = shared.lock().unwrap();
+            let targets = shared.targets();
+            shared.cache.retain(|x| targets.iter().any(|&t| same(t, x.time)));
+            let budget = MAX_BYTES.saturating_sub(shared.cache.iter().map(Prefetched::bytes).sum());
+            let missing = targets.into_iter().find(|&t|
+                !shared.cache.iter().any(|x| same(x.time, t))
+                && !shared.failed.iter().any(|&x| same(x, t)));
+            missing.filter(|_| budget > 0).map(|t| (t, shared.policy.window, budget))
+        };
+        let Some((time, window, budget)) = next else {
+            if wake.recv().is_err() {
+                return;
+            }
+            continue;
+        };
+        match prefetch(backend.as_mut(), time, window, streams, budget) {
+            Ok(x) => shared.lock().unwrap().cache.push(x),
+            Err(e) => {
+                log::debug!("prefetch: {time}: {e}");
+                shared.lock().unwrap().failed.push(time);
+            }
+        }
+        if let Err(mpsc::TryRecvError::Disconnected) = wake.try_recv() {
+            return;
+        }
+    }
+}
+
+/// Prefetching for one playback; its thread stops when this is dropped
+pub struct Prefetcher {
+    shared: Arc<Mutex<Shared>>,
+    wake: mpsc::Sender<()>,
+    /// after a jump was given what was prefetched, where the playback's
+    /// decoder goes on from; what it decodes before is left out
+    resume_at: Option<Seconds>,
+}
+
+impl Prefetcher {
+    /// Opens the file of `snapshot` again, with its players, and starts
+    /// decoding around its position by `policy`
+    pub fn start(snapshot: &Snapshot, policy: PrefetchPolicy) -> Result<Self, MediaError> {
+        let backend = backend::restore(snapshot)?;
+        let shared = Arc::new(Mutex::new(Shared {
+            policy,
+            position: snapshot.position,
+            cache: Vec::new(),
+            failed: Vec::new(),
+        }));
+        let (wake, receiver) = mpsc::channel();
+        let theirs = Arc::clone(&shared);
+        std::thread::Builder::new()
+            .name("prefetch".to_owned())
+            .spawn(move || run(backend, &theirs, &receiver))
+            .map_err(|e| MediaError::InternalError(e.to_string()))?;
+        Ok(Prefetcher { shared, wake, resume_at: None })
+    }
+
+    pub fn policy(&self) -> PrefetchPolicy {
+        self.shared.lock().unwrap().policy.clone()
+    }
+
+    pub fn set_policy(&mut self, policy: PrefetchPolicy) {
+        let mut shared = self.shared.lock().unwrap();
+        if !same(shared.policy.window, policy.window) {
+            shared.cache.clear();
+        }
+        shared.policy = policy;
+        shared.failed.clear();
+        drop(shared);
+        let _ = self.wake.send(());
+    }
+
+    /// Playback is at `position` now; what is kept decoded follows it
+    pub fn moved(&mut self, position: Seconds) {
+        let mut shared = self.shared.lock().unwrap();
+        let before = shared.targets();
+        shared.position = position;
+        let after = shared.targets();
+        let changed = after.len() != before.len()
+            || after.iter().zip(&before).any(|(&a, &b)| !same(a, b));
+        drop(shared);
+        if changed {
+            let _ = self.wake.send(());
+        }
+    }
+
+    /// What was decoded from `time`, if it is one of the cues and is ready,
+    /// for a jump there. The decoder must then be sought to its `until`
+    /// and `resume_at` be set to it. A playback drawing to a surface can't
+    /// be given decoded frames, and mustn't take it.
+    pub fn take(&mut self, time: Seconds) -> Option<Prefetched> {
+        let mut shared = self.shared.lock().unwrap();
+        let i = shared.cache.iter().position(|x| same(x.time, time))?;
+        Some(shared.cache.swap_remove(i))
+    }
+
+    pub fn resume_at(&self) -> Option<Seconds> {
+        self.resume_at
+    }
+
+    /// See `take`; `None` after any other seek
+    pub fn set_resume_at(&mut self, time: Option<Seconds>) {
+        self.resume_at = time;
+    }
+}
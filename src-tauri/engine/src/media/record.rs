@@ -0,0 +1,534 @@
+//! Recording from a microphone through ffmpeg's capture devices (PulseAudio
+//! or ALSA, AVFoundation, DirectShow), and mixing recordings into one
+//! track. Recordings are plain 16-bit mono WAV at `RECORD_RATE`, written
+//! and read here without ffmpeg, so that takes can be opened anywhere.
+
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use ffmpeg::{format, software, ChannelLayout, Dictionary, Format};
+use ffmpeg_sys_next as ffi;
+use log::{debug, warn};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::media::{audio, frame::AudioData, internal::{check, MediaError}, units::Seconds};
+
+pub const RECORD_RATE: u32 = 48000;
+const WAV_HEADER_LENGTH: usize = 44;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct InputDevice {
+    /// the capture format, like `pulse` or `dshow`
+    pub format: String,
+    pub name: String,
+    pub description: String,
+}
+
+fn io_error(path: &Path, e: &std::io::Error) -> MediaError {
+    MediaError::InternalError(format!("{}: {e}", path.display()))
+}
+
+/// The devices each capture format reports. Formats that can't list
+/// theirs, like AVFoundation, are offered with their default device.
+pub fn input_devices() -> Vec<InputDevice> {
+    ffmpeg::device::register_all();
+    let text = |x: *const std::ffi::c_char| if x.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(x) }.to_string_lossy().into_owned()
+    };
+    let mut result = Vec::new();
+    let mut input: *const ffi::AVInputFormat = std::ptr::null();
+    loop {
+        input = unsafe { ffi::av_input_audio_device_next(input) };
+        if input.is_null() {
+            break;
+        }
+        let format = text(unsafe { (*input).name });
+        let mut list: *mut ffi::AVDeviceInfoList = std::ptr::null_mut();
+        let status = unsafe {
+            ffi::avdevice_list_input_sources(
+                input, std::ptr::null(), std::ptr::null_mut(), &raw mut list)
+        };
+        if status < 0 || list.is_null() {
+            debug!("input_devices: {format} can't list its devices ({status})");
+            result.push(InputDevice {
+                description: format!("{} (default)", text(unsafe { (*input).long_name })),
+                format,
+                name: "default".to_owned(),
+            });
+            continue;
+        }
+        unsafe {
+            let count = usize::try_from((*list).nb_devices).unwrap_or(0);
+            for i in 0..count {
+                let info = *(*list).devices.add(i);
+                result.push(InputDevice {
+                    format: format.clone(),
+                    name: text((*info).device_name),
+                    description: text((*info).device_description),
+                });
+            }
+            ffi::avdevice_free_list_devices(&raw mut list);
+        }
+    }
+    result
+}
+
+fn open_device(device: &InputDevice) -> Result<format::context::Input, MediaError> {
+    ffmpeg::device::register_all();
+    let name = CString::new(device.format.as_str())
+        .map_err(|_| MediaError::InternalError("invalid device format".to_owned()))?;
+    let input = unsafe { ffi::av_find_input_format(name.as_ptr()) };
+    if input.is_null() {
+        return Err(MediaError::InternalError(
+            format!("capture format {} not available", device.format)));
+    }
+    let input = Format::Input(unsafe { format::Input::wrap(input.cast_mut()) });
+    // DirectShow takes the kind of device along with its name
+    let url = if device.format == "dshow" {
+        format!("audio={}", device.name)
+    } else {
+        device.name.clone()
+    };
+    match check!(format::open_with(&url, &input, Dictionary::new()))? {
+        format::context::Context::Input(x) => Ok(x),
+        format::context::Context::Output(_) => unreachable!(),
+    }
+}
+
+/// 16-bit WAV; the sizes in the header are filled in by `finish`
+struct WavWriter {
+    file: BufWriter<File>,
+    /// of all channels
+    samples: u32,
+}
+
+impl WavWriter {
+    fn create(path: &Path, channels: u16, sample_rate: u32) -> std::io::Result<Self> {
+        let block_align = channels * 2;
+        let mut header = Vec::with_capacity(WAV_HEADER_LENGTH);
+        header.extend_from_slice(b"RIFF\0\0\0\0WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        // PCM
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&channels.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data\0\0\0\0");
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&header)?;
+        Ok(Self { file, samples: 0 })
+    }
+
+    fn write(&mut self, samples: &[i16]) -> std::io::Result<()> {
+        for x in samples {
+            self.file.write_all(&x.to_le_bytes())?;
+        }
+        self.samples = self.samples.saturating_add(u32::try_from(samples.len()).unwrap_or(u32::MAX));
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        let data = self.samples.saturating_mul(2);
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&data.saturating_add(36).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&data.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// The samples of a WAV written by `record`
+fn read_wav(path: &Path) -> Result<Vec<i16>, MediaError> {
+    let mut data = Vec::new();
+    File::open(path).and_then(|mut x| x.read_to_end(&mut data))
+        .map_err(|e| io_error(path, &e))?;
+    let is_ours = data.len() >= WAV_HEADER_LENGTH
+        && &data[0..4] == b"RIFF" && &data[8..16] == b"WAVEfmt "
+        && data[20..24] == [1, 0, 1, 0]
+        && data[24..28] == RECORD_RATE.to_le_bytes()
+        && data[34..36] == [16, 0];
+    if !is_ours {
+        return Err(MediaError::InternalError(
+            format!("{}: not a recording of ours", path.display())));
+    }
+    Ok(data[WAV_HEADER_LENGTH..].chunks_exact(2)
+        .map(|x| i16::from_le_bytes([x[0], x[1]]))
+        .collect())
+}
+
+/// Records `duration` from `device` into a WAV at `path`. `started` is
+/// called once the device delivers, which is when whatever the recording
+/// goes along with should start playing. `progress` gets the fraction done
+/// now and then, and returns `false` to give up, in which case nothing is
+/// kept.
+pub fn record(
+    device: &InputDevice, path: &Path, duration: Seconds,
+    started: impl FnOnce(), mut progress: impl FnMut(f64) -> bool,
+) -> Result<(), MediaError> {
+    let mut input = open_device(device)?;
+    let stream = input.streams().best(ffmpeg::media::Type::Audio)
+        .ok_or(MediaError::InternalError(format!("{}: no audio", device.name)))?;
+    let index = stream.index();
+    let context = check!(ffmpeg::codec::Context::from_parameters(stream.parameters()))?;
+    let mut decoder = check!(context.decoder().audio())?;
+    let mut resampler = check!(software::resampler(
+        (decoder.format(), decoder.channel_layout(), decoder.rate()),
+        (format::Sample::I16(format::sample::Type::Packed), ChannelLayout::MONO, RECORD_RATE)
+    ))?;
+
+    let target = (duration.0 * f64::from(RECORD_RATE)).to_usize().unwrap_or(0);
+    let mut writer = WavWriter::create(path, 1, RECORD_RATE).map_err(|e| io_error(path, &e))?;
+    let mut written = 0;
+    let mut started = Some(started);
+    let mut reported = 0.0;
+    let result = (|| {
+        for (stream, packet) in input.packets() {
+            if stream.index() != index {
+                continue;
+            }
+            if let Some(f) = started.take() {
+                f();
+            }
+            check!(decoder.send_packet(&packet))?;
+            let mut decoded = AudioData::empty();
+            while decoder.receive_frame(&mut decoded).is_ok() {
+                let mut processed = AudioData::empty();
+                check!(resampler.run(&decoded, &mut processed))?;
+                let data: &[i16] = processed.plane(0);
+                let take = data.len().min(target - written);
+                writer.write(&data[..take]).map_err(|e| io_error(path, &e))?;
+                written += take;
+            }
+            if written >= target {
+                return Ok(());
+            }
+            let done = written.to_f64().unwrap() / target.to_f64().unwrap();
+            if done - reported >= 0.01 {
+                reported = done;
+                if !progress(done) {
+                    return Err(MediaError::Cancelled);
+                }
+            }
+        }
+        warn!("record: {} stopped delivering", device.name);
+        Ok(())
+    })();
+    let result = result.and_then(|()| writer.finish().map_err(|e| io_error(path, &e)));
+    if result.is_err() {
+        let _ = std::fs::remove_file(path);
+    }
+    progress(1.0);
+    result
+}
+
+/// How deep the original goes under the voice-over when bouncing, in dB
+const DUCK_DEPTH: f64 = -12.0;
+/// and how long it takes to go down and to come back up
+const DUCK_ATTACK: f64 = 0.1;
+const DUCK_RELEASE: f64 = 0.3;
+const BLOCK: usize = 4096;
+
+/// Part of a recording, placed on the timeline
+pub struct Clip {
+    pub path: PathBuf,
+    /// where the part kept starts
+    pub start: Seconds,
+    /// how much of the recording is cut from its start
+    pub skip: Seconds,
+    /// of the part kept
+    pub length: Seconds,
+    /// in dB
+    pub gain: f64,
+}
+
+fn to_samples(time: Seconds) -> usize {
+    (time.0.max(0.0) * f64::from(RECORD_RATE)).round().to_usize().unwrap_or(0)
+}
+
+fn to_sample(x: f32) -> i16 {
+    #[allow(clippy::cast_possible_truncation)]
+    let x = (x * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+    x
+}
+
+struct Placed {
+    path: PathBuf,
+    start: usize,
+    end: usize,
+    skip: usize,
+    gain: f32,
+}
+
+/// Renders clips block by block, in order, reading each only once the
+/// blocks reach it
+struct ClipMixer {
+    /// by start
+    clips: Vec<Placed>,
+    next: usize,
+    /// start and samples of the clips being played, gain applied
+    playing: Vec<(usize, Vec<f32>)>,
+    /// where the clips are, merged, for ducking
+    covered: Vec<(usize, usize)>,
+    covered_from: usize,
+}
+
+impl ClipMixer {
+    fn new(clips: &[Clip]) -> Self {
+        let mut placed: Vec<Placed> = clips.iter()
+            .map(|x| {
+                let start = to_samples(x.start);
+                #[allow(clippy::cast_possible_truncation)]
+                let gain = 10f64.powf(x.gain / 20.0) as f32;
+                Placed {
+                    path: x.path.clone(), start,
+                    end: start + to_samples(x.length),
+                    skip: to_samples(x.skip), gain,
+                }
+            })
+            .filter(|x| x.end > x.start)
+            .collect();
+        placed.sort_by_key(|x| x.start);
+        let mut covered: Vec<(usize, usize)> = Vec::new();
+        for clip in &placed {
+            match covered.last_mut() {
+                Some(last) if clip.start <= last.1 => last.1 = last.1.max(clip.end),
+                _ => covered.push((clip.start, clip.end)),
+            }
+        }
+        Self { clips: placed, next: 0, playing: Vec::new(), covered, covered_from: 0 }
+    }
+
+    /// Where the last clip ends
+    fn end(&self) -> usize {
+        self.covered.last().map_or(0, |x| x.1)
+    }
+
+    /// Fraction of the clips started
+    fn done(&self) -> f64 {
+        self.next.to_f64().unwrap() / self.clips.len().max(1).to_f64().unwrap()
+    }
+
+    /// Adds the clips' samples from `from` on to `out`; each call goes on
+    /// from where the last ended
+    fn render(&mut self, from: usize, out: &mut [f32]) -> Result<(), MediaError> {
+        let to = from + out.len();
+        while let Some(clip) = self.clips.get(self.next).filter(|x| x.start < to) {
+            let samples = read_wav(&clip.path)?;
+            let kept = samples.get(clip.skip..).unwrap_or(&[]);
+            let kept = &kept[..kept.len().min(clip.end - clip.start)];
+            self.playing.push((clip.start, kept.iter()
+                .map(|&x| f32::from(x) / 32768.0 * clip.gain)
+                .collect()));
+            self.next += 1;
+        }
+        for (start, samples) in &self.playing {
+            let begin = from.max(*start);
+            let end = to.min(start + samples.len());
+            for i in begin..end {
+                out[i - from] += samples[i - start];
+            }
+        }
+        self.playing.retain(|(start, samples)| start + samples.len() > to);
+        Ok(())
+    }
+
+    /// Gain of the original at every sample from `from` on, for `count` of
+    /// them: `depth` under the clips, with ramps either side
+    fn ducking(&mut self, from: usize, count: usize, depth: f32) -> Vec<f32> {
+        let attack = to_samples(Seconds(DUCK_ATTACK)).max(1);
+        let release = to_samples(Seconds(DUCK_RELEASE)).max(1);
+        while self.covered.get(self.covered_from)
+            .is_some_and(|x| x.1 + release <= from)
+        {
+            self.covered_from += 1;
+        }
+        let mut result = vec![1.0_f32; count];
+        for &(start, end) in &self.covered[self.covered_from..] {
+            if start >= from + count + attack {
+                break;
+            }
+            for (i, gain) in result.iter_mut().enumerate() {
+                let t = from + i;
+                // 1 under the clip, falling to 0 over the ramps
+                let amount = if t < start {
+                    1.0 - (start - t).to_f32().unwrap() / attack.to_f32().unwrap()
+                } else if t < end {
+                    1.0
+                } else {
+                    1.0 - (t - end).to_f32().unwrap() / release.to_f32().unwrap()
+                };
+                *gain = gain.min(1.0 - amount.max(0.0) * (1.0 - depth));
+            }
+        }
+        result
+    }
+}
+
+/// Mixes clips into one mono WAV at `output`; where they overlap they are
+/// summed. `progress` gets the fraction done now and then, and returns
+/// `false` to give up.
+pub fn mix(
+    clips: &[Clip], output: &Path, mut progress: impl FnMut(f64) -> bool,
+) -> Result<(), MediaError> {
+    let mut mixer = ClipMixer::new(clips);
+    let mut writer = WavWriter::create(output, 1, RECORD_RATE).map_err(|e| io_error(output, &e))?;
+    let mut position = 0;
+    while position < mixer.end() {
+        let mut block = vec![0.0; BLOCK.min(mixer.end() - position)];
+        mixer.render(position, &mut block)?;
+        let out: Vec<i16> = block.into_iter().map(to_sample).collect();
+        writer.write(&out).map_err(|e| io_error(output, &e))?;
+        position += out.len();
+        if !progress(mixer.done()) {
+            drop(writer);
+            let _ = std::fs::remove_file(output);
+            return Err(MediaError::Cancelled);
+        }
+    }
+    writer.finish().map_err(|e| io_error(output, &e))?;
+    progress(1.0);
+    Ok(())
+}
+
+/// Mixes clips over an audio stream of the file at `source`, or its default
+/// one, into a stereo WAV at `output`, turning the original down by
+/// `DUCK_DEPTH` wherever there is a clip. Whatever is longer sets the
+/// length. `progress` works as in `mix`.
+pub fn bounce(
+    clips: &[Clip], source: &Path, stream: Option<usize>, output: &Path,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<(), MediaError> {
+    #[allow(clippy::cast_possible_truncation)]
+    let depth = 10f64.powf(DUCK_DEPTH / 20.0) as f32;
+    let mut mixer = ClipMixer::new(clips);
+    let mut writer = WavWriter::create(output, 2, RECORD_RATE).map_err(|e| io_error(output, &e))?;
+    let mut position: Option<usize> = None;
+
+    // clips only, up to `to`
+    let voice_only = |mixer: &mut ClipMixer, writer: &mut WavWriter, from: usize, to: usize| {
+        let mut at = from;
+        while at < to {
+            let mut block = vec![0.0; BLOCK.min(to - at)];
+            mixer.render(at, &mut block)?;
+            let out: Vec<i16> = block.iter()
+                .flat_map(|&x| [to_sample(x); 2])
+                .collect();
+            writer.write(&out).map_err(|e| io_error(output, &e))?;
+            at += block.len();
+        }
+        Ok::<(), MediaError>(())
+    };
+
+    let result = audio::decode_stereo(source, stream, RECORD_RATE, |time, data| {
+        let from = match position {
+            Some(x) => x,
+            None => {
+                // the original may start late; the timeline starts at zero
+                let first = to_samples(Seconds(time));
+                voice_only(&mut mixer, &mut writer, 0, first)?;
+                first
+            }
+        };
+        let count = data.len();
+        let mut voice = vec![0.0; count];
+        mixer.render(from, &mut voice)?;
+        let ducking = mixer.ducking(from, count, depth);
+        let out: Vec<i16> = data.iter().zip(voice.iter().zip(&ducking))
+            .flat_map(|(&(left, right), (&voice, &gain))|
+                [to_sample(left * gain + voice), to_sample(right * gain + voice)])
+            .collect();
+        writer.write(&out).map_err(|e| io_error(output, &e))?;
+        position = Some(from + count);
+        Ok(())
+    }, &mut progress);
+    let result = result.and_then(|()| {
+        let from = position.unwrap_or(0);
+        let to = mixer.end().max(from);
+        voice_only(&mut mixer, &mut writer, from, to)?;
+        writer.finish().map_err(|e| io_error(output, &e))
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    progress(1.0);
+    result
+}
+
+/// Decodes an audio stream of the file at `source`, or its default one,
+/// into a mono WAV at `output` at `sample_rate`, as speech recognizers take
+/// it; silence is put before it if it starts late, so that times in it are
+/// times in the file. Returns its length; `progress` works as in `mix`.
+pub fn export_mono(
+    source: &Path, stream: Option<usize>, output: &Path, sample_rate: u32,
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<Seconds, MediaError> {
+    let mut writer = WavWriter::create(output, 1, sample_rate).map_err(|e| io_error(output, &e))?;
+    let mut position: Option<usize> = None;
+    let result = audio::decode_stereo(source, stream, sample_rate, |time, data| {
+        let from = match position {
+            Some(x) => x,
+            None => {
+                let first = (time.max(0.0) * f64::from(sample_rate)).round().to_usize().unwrap_or(0);
+                writer.write(&vec![0; first]).map_err(|e| io_error(output, &e))?;
+                first
+            }
+        };
+        let out: Vec<i16> = data.iter().map(|&(left, right)| to_sample((left + right) / 2.0)).collect();
+        writer.write(&out).map_err(|e| io_error(output, &e))?;
+        position = Some(from + out.len());
+        Ok(())
+    }, &mut progress);
+    let result = result.and_then(|()| writer.finish().map_err(|e| io_error(output, &e)));
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    progress(1.0);
+    result?;
+    Ok(Seconds(position.unwrap_or(0).to_f64().unwrap() / f64::from(sample_rate)))
+}
+
+/// `export_mono` of just `from` to `to`, as for a recognizer run on one
+/// event; times in it count from `from`, and it's silence where the file
+/// has no audio
+pub fn export_mono_window(
+    source: &Path, stream: Option<usize>, (from, to): (Seconds, Seconds), output: &Path,
+    sample_rate: u32,
+) -> Result<(), MediaError> {
+    let rate = f64::from(sample_rate);
+    let length = ((to.0 - from.0).max(0.0) * rate).round().to_usize().unwrap_or(0);
+    let mut writer = WavWriter::create(output, 1, sample_rate).map_err(|e| io_error(output, &e))?;
+    let mut position = 0;
+    let result = audio::decode_stereo_window(source, stream, sample_rate, (from, to), |time, data| {
+        // where `data` goes in the window; before it, the part is dropped
+        let at = ((time - from.0) * rate).round().to_isize().unwrap_or(0);
+        let skip = usize::try_from(-at).unwrap_or(0).min(data.len());
+        let at = usize::try_from(at).unwrap_or(0).max(position);
+        if at > position {
+            writer.write(&vec![0; at.min(length) - position]).map_err(|e| io_error(output, &e))?;
+            position = at.min(length);
+        }
+        let take = (length - position).min(data.len() - skip);
+        let out: Vec<i16> = data[skip..skip + take].iter()
+            .map(|&(left, right)| to_sample((left + right) / 2.0))
+            .collect();
+        writer.write(&out).map_err(|e| io_error(output, &e))?;
+        position += take;
+        Ok(())
+    });
+    let result = result
+        .and_then(|()| writer.write(&vec![0; length - position]).map_err(|e| io_error(output, &e)))
+        .and_then(|()| writer.finish().map_err(|e| io_error(output, &e)));
+    if result.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    result
+}
@@ -0,0 +1,131 @@
+//! Our own seek index, for files whose container index is missing or wrong
+//! (cut transport streams, half-remuxed AVI) and which therefore seek to
+//! the wrong place. One pass over the file notes where the keyframes are;
+//! seeking then goes by byte offset to the last one before the target and
+//! decodes forward from there. Indexes are kept in the cache directory,
+//! and one that no longer matches the file's size and modification time
+//! is not used.
+
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use log::{debug, warn};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::media::{demux, internal::MediaError, units::{Seconds, Timestamp}};
+
+/// Keyframes closer than this to the last one kept are left out; with
+/// audio, where every packet is one, this keeps the index small
+const MIN_INTERVAL: f64 = 0.5;
+
+#[derive(Serialize, Deserialize)]
+pub struct SeekIndex {
+    /// of the file indexed
+    size: u64,
+    modified_ms: u64,
+    /// time in seconds and byte offset of keyframes, by time
+    entries: Vec<(f64, i64)>,
+}
+
+/// Size and modification time, which tell an index is stale
+fn file_stamp(path: &Path) -> std::io::Result<(u64, u64)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata.modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| u64::try_from(x.as_millis()).unwrap_or(u64::MAX));
+    Ok((metadata.len(), modified))
+}
+
+/// FNV-1a, so that names stay the same across builds
+fn cache_name(path: &Path) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in path.to_string_lossy().bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}.json")
+}
+
+impl SeekIndex {
+    /// Reads the whole file once. The video stream is indexed if there is
+    /// one, the audio otherwise. `progress` gets the fraction done now and
+    /// then, and returns `false` to give up.
+    pub fn build(path: &Path, mut progress: impl FnMut(f64) -> bool) -> Result<Self, MediaError> {
+        let (size, modified_ms) = file_stamp(path)
+            .map_err(|e| MediaError::InternalError(e.to_string()))?;
+        let mut demuxer = demux::Demuxer::open(path)?;
+        let kind = if demuxer.has_video() { demux::StreamKind::Video } else { demux::StreamKind::Audio };
+        let (info, _) = demuxer.get_stream_from_kind(kind)?;
+        let stream = info.index();
+
+        let mut entries: Vec<(f64, i64)> = Vec::new();
+        let mut reported = 0.0;
+        while let Some((i, packet)) = demuxer.next_packet() {
+            let position = packet.position().to_i64().unwrap_or(-1);
+            if i != stream || !packet.is_key() || position < 0 {
+                continue;
+            }
+            let Some(pts) = packet.pts().or(packet.dts()) else { continue };
+            let time = Timestamp(pts).to_seconds(info.timebase()).0;
+            if entries.last().is_some_and(|&(t, _)| time < t + MIN_INTERVAL) {
+                continue;
+            }
+            entries.push((time, position));
+
+            let done = position.to_f64().unwrap() / size.max(1).to_f64().unwrap();
+            if done - reported >= 0.01 {
+                reported = done;
+                if !progress(done.min(1.0)) {
+                    return Err(MediaError::Cancelled);
+                }
+            }
+        }
+        // packets can come out of order around broken parts
+        entries.sort_by(|a, b| a.0.total_cmp(&b.0));
+        progress(1.0);
+        debug!("seek_index: {} entries for stream {stream}", entries.len());
+        Ok(Self { size, modified_ms, entries })
+    }
+
+    pub fn keyframes(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Times of the keyframes indexed, in order, less those within
+    /// `MIN_INTERVAL` of the one before
+    pub fn times(&self) -> impl Iterator<Item = Seconds> + '_ {
+        self.entries.iter().map(|&(t, _)| Seconds(t))
+    }
+
+    /// Byte offset of the last keyframe at or before `time`
+    pub fn position_before(&self, time: Seconds) -> Option<i64> {
+        let i = self.entries.partition_point(|&(t, _)| t <= time.0);
+        i.checked_sub(1).map(|i| self.entries[i].1)
+    }
+
+    fn file(dir: &Path, path: &Path) -> PathBuf {
+        dir.join(cache_name(path))
+    }
+
+    pub fn save(&self, dir: &Path, path: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let json = serde_json::to_vec(self).map_err(std::io::Error::other)?;
+        std::fs::write(Self::file(dir, path), json)
+    }
+
+    /// The index saved for `path`, unless there is none or the file has
+    /// changed since
+    pub fn load(dir: &Path, path: &Path) -> Option<Self> {
+        let data = std::fs::read(Self::file(dir, path)).ok()?;
+        let index: Self = match serde_json::from_slice(&data) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("seek_index: unreadable index for {}: {e}", path.display());
+                return None;
+            }
+        };
+        let stamp = file_stamp(path).ok()?;
+        (stamp == (index.size, index.modified_ms)).then_some(index)
+    }
+}
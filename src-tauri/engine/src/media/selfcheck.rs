@@ -0,0 +1,200 @@
+//! Turning "nothing happens when I open a video" into something to act on:
+//! whether ffmpeg starts, the versions of its libraries and those it was
+//! built for, then a round trip through a clip made by `test_media` — written,
+//! opened, and a frame and some audio decoded. Each step is reported, and
+//! later steps are skipped once one fails.
+
+use std::path::Path;
+
+use ffmpeg::sys;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::engine::Engine;
+use crate::media::{backend::MediaBackend, session::Session, test_media, units::Seconds, verify::Check};
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LinkedLibrary {
+    pub name: String,
+    /// as reported by the library at runtime
+    pub linked: String,
+    /// what the bindings were generated against
+    pub expected: String,
+    /// same major version, and at least the minor version expected
+    pub compatible: bool,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+#[ts(rename = "SelfCheckReport")]
+pub struct Report {
+    /// whether every check passed
+    pub passed: bool,
+    /// `av_version_info`, such as `7.1` or a git describe
+    pub version: String,
+    pub libraries: Vec<LinkedLibrary>,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, result: Result<String, String>) -> bool {
+        let passed = result.is_ok();
+        let detail = result.unwrap_or_else(|e| e);
+        if !passed {
+            warn!("selfcheck: {name} failed: {detail}");
+        }
+        self.passed &= passed;
+        self.checks.push(Check { name: name.to_owned(), passed, detail });
+        passed
+    }
+
+    fn skip(&mut self, names: &[&str]) {
+        for name in names {
+            self.passed = false;
+            self.checks.push(Check {
+                name: (*name).to_owned(),
+                passed: false,
+                detail: "skipped".to_owned(),
+            });
+        }
+    }
+}
+
+fn format_version(version: u32) -> String {
+    format!("{}.{}.{}", version >> 16, (version >> 8) & 0xff, version & 0xff)
+}
+
+fn library(name: &str, linked: u32, major: u32, minor: u32, micro: u32) -> LinkedLibrary {
+    let expected = (major << 16) | (minor << 8) | micro;
+    LinkedLibrary {
+        name: name.to_owned(),
+        linked: format_version(linked),
+        expected: format_version(expected),
+        compatible: linked >> 16 == major && (linked >> 8) & 0xff >= minor,
+    }
+}
+
+fn libraries() -> Vec<LinkedLibrary> {
+    unsafe {
+        vec![
+            library("avutil", sys::avutil_version(),
+                sys::LIBAVUTIL_VERSION_MAJOR, sys::LIBAVUTIL_VERSION_MINOR,
+                sys::LIBAVUTIL_VERSION_MICRO),
+            library("avcodec", sys::avcodec_version(),
+                sys::LIBAVCODEC_VERSION_MAJOR, sys::LIBAVCODEC_VERSION_MINOR,
+                sys::LIBAVCODEC_VERSION_MICRO),
+            library("avformat", sys::avformat_version(),
+                sys::LIBAVFORMAT_VERSION_MAJOR, sys::LIBAVFORMAT_VERSION_MINOR,
+                sys::LIBAVFORMAT_VERSION_MICRO),
+            library("avfilter", sys::avfilter_version(),
+                sys::LIBAVFILTER_VERSION_MAJOR, sys::LIBAVFILTER_VERSION_MINOR,
+                sys::LIBAVFILTER_VERSION_MICRO),
+            library("swscale", sys::swscale_version(),
+                sys::LIBSWSCALE_VERSION_MAJOR, sys::LIBSWSCALE_VERSION_MINOR,
+                sys::LIBSWSCALE_VERSION_MICRO),
+            library("swresample", sys::swresample_version(),
+                sys::LIBSWRESAMPLE_VERSION_MAJOR, sys::LIBSWRESAMPLE_VERSION_MINOR,
+                sys::LIBSWRESAMPLE_VERSION_MICRO),
+        ]
+    }
+}
+
+fn generate(path: &Path) -> Result<String, String> {
+    let spec = test_media::TestMediaSpec {
+        duration: Seconds(1.0),
+        size: (320, 240),
+        framerate: 25,
+        tone: Some(440.0),
+    };
+    test_media::generate(&spec, path, |_| true).map_err(|e| e.to_string())?;
+    let size = std::fs::metadata(path).map_err(|e| e.to_string())?.len();
+    Ok(format!("{size} bytes"))
+}
+
+fn open(path: &Path) -> Result<(Session, String), String> {
+    let session = Session::create(path).map_err(|e| e.to_string())?;
+    let streams = session.demuxer().describe_streams().len();
+    let duration = session.demuxer().duration();
+    Ok((session, format!("{streams} streams, {duration}")))
+}
+
+fn decode_video(session: &mut Session) -> Result<String, String> {
+    session.open_video_player(None, false).map_err(|e| e.to_string())?;
+    let frame = session.render_frame_at(Seconds(0.5)).map_err(|e| e.to_string())?
+        .ok_or("no frame at 0.5s")?;
+    Ok(format!("{}x{} at {}", frame.decoded.width(), frame.decoded.height(), frame.meta.time))
+}
+
+fn decode_audio(session: &mut Session) -> Result<String, String> {
+    MediaBackend::open_audio_player(session, None).map_err(|e| e.to_string())?;
+    MediaBackend::seek(session, Seconds(0.0)).map_err(|e| e.to_string())?;
+    let mut samples = 0;
+    while samples == 0 {
+        let more = session.decode(std::time::Duration::ZERO).map_err(|e| e.to_string())?;
+        samples += session.take_audio().iter().map(|x| x.samples.len()).sum::<usize>();
+        if !more {
+            break;
+        }
+    }
+    if samples == 0 {
+        return Err("no samples before the end".to_owned());
+    }
+    Ok(format!("{samples} samples"))
+}
+
+/// Runs every step, starting with setting up ffmpeg if that hasn't been
+/// done or failed
+pub fn run() -> Report {
+    let version = unsafe { std::ffi::CStr::from_ptr(sys::av_version_info()) };
+    let mut report = Report {
+        passed: true,
+        version: version.to_string_lossy().into_owned(),
+        libraries: libraries(),
+        checks: Vec::new(),
+    };
+
+    if !report.check("init", Engine::new().map(|_| "ffmpeg::init".to_owned())
+        .map_err(|e| e.to_string()))
+    {
+        report.skip(&["libraries", "generate", "open", "decodeVideo", "decodeAudio"]);
+        info!("selfcheck: passed = false");
+        return report;
+    }
+
+    let incompatible: Vec<_> = report.libraries.iter()
+        .filter(|x| !x.compatible)
+        .map(|x| format!("{} {} (built for {})", x.name, x.linked, x.expected))
+        .collect();
+    report.check("libraries", if incompatible.is_empty() {
+        Ok(format!("ffmpeg {}", report.version))
+    } else {
+        Err(incompatible.join(", "))
+    });
+
+    let path = std::env::temp_dir()
+        .join(format!("subtle-selfcheck-{}.mkv", std::process::id()));
+    if report.check("generate", generate(&path)) {
+        match open(&path) {
+            Ok((mut session, detail)) => {
+                report.check("open", Ok(detail));
+                report.check("decodeVideo", decode_video(&mut session));
+                report.check("decodeAudio", decode_audio(&mut session));
+            }
+            Err(e) => {
+                report.check("open", Err(e));
+                report.skip(&["decodeVideo", "decodeAudio"]);
+            }
+        }
+    } else {
+        report.skip(&["open", "decodeVideo", "decodeAudio"]);
+    }
+    if path.exists() && let Err(e) = std::fs::remove_file(&path) {
+        warn!("selfcheck: cannot remove {}: {e}", path.display());
+    }
+
+    info!("selfcheck: passed = {}", report.passed);
+    report
+}
@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::{audio::{self, AudioSink}, availability::Availability, backend::BackendKind, color, delta::TileHashes, demux, frame, images, internal::MediaError, seek_index::SeekIndex, subpicture, surface::Surface, units, video::{self, VideoSink}};
+
+/// How far past a time video packets are read while looking for the
+/// subtitle packets of it, which muxers may put a little later
+const SUBPICTURE_MUX_SLACK: f64 = 1.0;
+
+/// What is needed to open a session again as it was. Only the players are
+/// described: analysis sinks belong to one-off commands that start them
+/// afresh.
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export, rename = "PlaybackSnapshot")]
+pub struct Snapshot {
+    pub path: String,
+    /// what decoded it; see `backend`
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// opened with `create_tolerant`
+    pub tolerant: bool,
+    pub audio_index: Option<usize>,
+    /// target of the audio player's auto gain
+    pub auto_gain: Option<f64>,
+    pub video_index: Option<usize>,
+    pub accel: bool,
+    pub output_size: Option<(u32, u32)>,
+    /// see `set_color_management`; the display's profile is read again
+    #[serde(default)]
+    pub color_managed: bool,
+    /// the display it was managed for; `None` for the main one
+    #[serde(default)]
+    pub display: Option<color::DisplayAt>,
+    pub subpicture_index: Option<usize>,
+    /// opened with `create_images`
+    #[serde(default)]
+    pub images: Option<images::ImageOptions>,
+    /// the files of a group, in order, `path` being the first; see `group`
+    #[serde(default)]
+    pub members: Vec<String>,
+    /// where decoding had got to; a little past what was on screen, as the
+    /// frontend buffers ahead
+    pub position: units::Seconds,
+}
+
+pub struct Session {
+    path: PathBuf,
+    /// the last seek target, or the time of the last frame decoded since
+    position: units::Seconds,
+    demuxer: demux::Demuxer,
+    audio: Option<(audio::Decoder, audio::AudioSinkKind)>,
+    /// more consumers of the audio decoder's frames, each given its own
+    /// copy, so that one decoding pass serves them all
+    audio_taps: Vec<audio::AudioSinkKind>,
+    video: Option<(video::Decoder, video::VideoSinkKind)>,
+    subpicture: Option<(subpicture::Decoder, subpicture::Compositor)>,
+    /// only for sessions opened with `create_tolerant`
+    availability: Option<Availability>,
+    /// where the packet is, at which reading stopped because its data
+    /// wasn't all there; see `resume`
+    stalled: Option<(isize, usize)>,
+    /// the decoding timestamp of the last packet fed from each stream, of
+    /// sessions opened with `create_tolerant`
+    fed: HashMap<usize, i64>,
+    /// of the last frame `send_frame_delta` sent
+    sent_tiles: Option<TileHashes>,
+    /// where the video player's frames are drawn instead of being sent
+    surface: Option<Surface>,
+    /// only for sessions opened with `create_images`
+    images: Option<images::ImageOptions>,
+    /// used instead of the container's for seeking; see `seek_index`
+    seek_index: Option<SeekIndex>,
+}
+
+impl Session {
+    pub fn demuxer(&self) -> &demux::Demuxer {
+        &self.demuxer
+    }
+    pub fn audio(&self) -> Option<&(audio::Decoder, audio::AudioSinkKind)> {
+        self.audio.as_ref()
+    }
+    pub fn video(&self) -> Option<&(video::Decoder, video::VideoSinkKind)> {
+        self.video.as_ref()
+    }
+    pub fn audio_mut(&mut self) -> Option<&mut (audio::Decoder, audio::AudioSinkKind)> {
+        self.audio.as_mut()
+    }
+    pub fn video_mut(&mut self) -> Option<&mut (video::Decoder, video::VideoSinkKind)> {
+        self.video.as_mut()
+    }
+    pub fn audio_taps_mut(&mut self) -> &mut [audio::AudioSinkKind] {
+        &mut self.audio_taps
+    }
+    pub fn subpicture(&self) -> Option<&(subpicture::Decoder, subpicture::Compositor)> {
+        self.subpicture.as_ref()
+    }
+    pub fn subpicture_mut(&mut self) -> Option<&mut (subpicture::Decoder, subpicture::Compositor)> {
+        self.subpicture.as_mut()
+    }
+    pub fn availability_mut(&mut self) -> Option<&mut Availability> {
+        self.availability.as_mut()
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn position(&self) -> units::Seconds {
+        self.position
+    }
+    /// No video besides cover art; see `demux::Demuxer::has_video`
+    pub fn is_audio_only(&self) -> bool {
+        !self.demuxer.has_video()
+    }
+    pub fn sent_tiles_mut(&mut self) -> &mut Option<TileHashes> {
+        &mut self.sent_tiles
+    }
+    pub fn surface_mut(&mut self) -> Option<&mut Surface> {
+        self.surface.as_mut()
+    }
+    pub fn set_surface(&mut self, surface: Option<Surface>) {
+        self.surface = surface;
+    }
+    pub fn has_surface(&self) -> bool {
+        self.surface.is_some()
+    }
+    pub fn set_seek_index(&mut self, index: Option<SeekIndex>) {
+        self.seek_index = index;
+    }
+}
+
+unsafe impl Send for Session {}
+
+impl Session {
+    fn with_demuxer(path: &Path, demuxer: demux::Demuxer) -> Self {
+        Self {
+            path: path.to_owned(),
+            position: units::Seconds(0.0),
+            demuxer,
+            audio: None,
+            audio_taps: Vec::new(),
+            video: None,
+            subpicture: None,
+            availability: None,
+            stalled: None,
+            fed: HashMap::new(),
+            sent_tiles: None,
+            surface: None,
+            images: None,
+            seek_index: None,
+        }
+    }
+
+    #[tracing::instrument(name = "session::open", skip_all)]
+    pub fn create(path: &Path) -> Result<Self, MediaError> {
+        Ok(Self::with_demuxer(path, demux::Demuxer::open(path)?))
+    }
+
+    /// A still image, or the first of a numbered sequence, as a video
+    /// stream of its own; see `images`
+    pub fn create_images(path: &Path, options: images::ImageOptions) -> Result<Self, MediaError> {
+        let mut session = Self::with_demuxer(path, demux::Demuxer::open_images(path, &options)?);
+        session.images = Some(options);
+        Ok(session)
+    }
+
+    /// For files that are still being written: seeking or reading past what
+    /// is there fails with `MediaError::DataNotYetAvailable` rather than
+    /// running into garbage, and succeeds once the data has arrived
+    pub fn create_tolerant(path: &Path) -> Result<Self, MediaError> {
+        let availability = Availability::scan(path)
+            .map_err(|e| MediaError::InternalError(e.to_string()))?;
+        let demuxer = demux::Demuxer::open(path).map_err(|e| {
+            if availability.is_complete() { e } else { availability.unavailable() }
+        })?;
+        let mut session = Self::with_demuxer(path, demuxer);
+        session.availability = Some(availability);
+        Ok(session)
+    }
+
+    /// Rechecks the file if `time` looks out of reach
+    fn check_available(&mut self, time: units::Seconds) -> Result<(), MediaError> {
+        let Some(a) = self.availability.as_mut() else { return Ok(()) };
+        let reachable = |a: &Availability| a.is_complete() || time <= a.playable_until();
+        if reachable(a) {
+            return Ok(());
+        }
+        a.refresh_if_due().map_err(|e| MediaError::InternalError(e.to_string()))?;
+        if reachable(a) { Ok(()) } else { Err(a.unavailable()) }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let video_player = match &self.video {
+            Some((d, video::VideoSinkKind::Player(p))) => Some((d, p)),
+            _ => None,
+        };
+        let audio_player = match &self.audio {
+            Some((d, audio::AudioSinkKind::Player(p))) => Some((d, p)),
+            _ => None,
+        };
+        Snapshot {
+            path: self.path.to_string_lossy().into_owned(),
+            backend: BackendKind::Ffmpeg,
+            tolerant: self.availability.is_some(),
+            audio_index: audio_player.map(|(d, _)| d.stream_info().index()),
+            auto_gain: audio_player.and_then(|(_, p)| p.auto_gain()),
+            video_index: video_player.map(|(d, _)| d.stream_info().index()),
+            accel: video_player.is_some_and(|(d, _)| d.is_accelerated()),
+            output_size: video_player.map(|(_, p)| p.output_size()),
+            color_managed: video_player.is_some_and(|(_, p)| p.is_color_managed()),
+            display: video_player.and_then(|(_, p)| p.color_display()),
+            subpicture_index: self.subpicture.as_ref().map(|(d, _)| d.stream_info().index()),
+            images: self.images,
+            members: Vec::new(),
+            position: self.position,
+        }
+    }
+
+    /// Opens `snapshot.path`, which the caller has vetted, and the players
+    /// the snapshot lists, then seeks to where it was. A tolerant session
+    /// whose file doesn't reach that far yet stays at the start.
+    pub fn restore(snapshot: &Snapshot) -> Result<Self, MediaError> {
+        let path = Path::new(&snapshot.path);
+        let mut session = if let Some(options) = snapshot.images {
+            Self::create_images(path, options)?
+        } else if snapshot.tolerant {
+            Self::create_tolerant(path)?
+        } else {
+            Self::create(path)?
+        };
+        if let Some(index) = snapshot.audio_index {
+            session.open_audio_player(Some(index))?;
+            if let Some((_, audio::AudioSinkKind::Player(p))) = session.audio.as_mut() {
+                p.set_auto_gain(snapshot.auto_gain);
+            }
+        }
+        if let Some(index) = snapshot.video_index {
+            session.open_video_player(Some(index), snapshot.accel)?;
+            if let Some(size) = snapshot.output_size
+                && let Some((_, video::VideoSinkKind::Player(p))) = session.video.as_mut()
+            {
+                p.set_output_size(size)?;
+            }
+            if snapshot.color_managed
+                && let Some((_, video::VideoSinkKind::Player(p))) = session.video.as_mut()
+            {
+                let display = color::display_profile(snapshot.display)
+                    .unwrap_or_else(color::DisplayProfile::srgb);
+                p.set_color_management(Some(display))?;
+            }
+        }
+        if let Some(index) = snapshot.subpicture_index {
+            session.open_subpicture(Some(index))?;
+        }
+        match session.seek(snapshot.position) {
+            Ok(()) | Err(MediaError::DataNotYetAvailable { .. }) => Ok(session),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn flush(&mut self) {
+        self.stalled = None;
+        self.fed.clear();
+        if let Some((d, s)) = self.audio.as_mut() {
+            d.flush();
+            s.clear();
+        }
+        for tap in &mut self.audio_taps {
+            tap.clear();
+        }
+        if let Some((d, s)) = self.video.as_mut() {
+            d.flush();
+            s.clear();
+        }
+        if let Some((d, c)) = self.subpicture.as_mut() {
+            d.flush();
+            c.clear();
+        }
+    }
+
+    /// The video keyframes known so far, by time: those of the seek index,
+    /// which is of the video when there is one, and those the video sampler
+    /// has decoded
+    pub fn known_keyframes(&self) -> Vec<units::Seconds> {
+        let mut result: Vec<_> = self.seek_index.iter().flat_map(|x| x.times()).collect();
+        if let Some((_, video::VideoSinkKind::Sampler(s))) = &self.video {
+            result.extend(s.keyframes());
+        }
+        result.sort_by(|a, b| a.0.total_cmp(&b.0));
+        result.dedup_by(|a, b| a.0 == b.0);
+        result
+    }
+
+    /// Seeks the demuxer by the rebuilt index, if there is one and it has
+    /// a keyframe early enough
+    fn seek_indexed(&mut self, time: units::Seconds) -> Result<bool, MediaError> {
+        let Some(pos) = self.seek_index.as_ref().and_then(|x| x.position_before(time)) else {
+            return Ok(false);
+        };
+        self.demuxer.seek_byte_pos(pos)?;
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "session::seek", skip_all)]
+    pub fn seek(&mut self, time: units::Seconds) -> Result<(), MediaError> {
+        self.check_available(time)?;
+        if !self.seek_indexed(time)? {
+            match &self.audio {
+                // seeking by the audio stream's own timebase is exact for
+                // formats like WAV, where the default one may round to a
+                // whole packet
+                Some((d, _)) if self.is_audio_only() =>
+                    self.demuxer.seek_stream(time, d.stream_info())?,
+                _ => self.demuxer.seek(time)?,
+            }
+        }
+        self.flush();
+        self.position = time;
+        Ok(())
+    }
+
+    pub fn seek_byte_pos(&mut self, pos: i64) -> Result<(), MediaError> {
+        self.demuxer.seek_byte_pos(pos)?;
+        self.flush();
+        Ok(())
+    }
+
+    pub fn seek_audio(&mut self, time: units::Seconds) -> Result<(), MediaError> {
+        self.check_available(time)?;
+        let (d, _c) = self.audio.as_ref().unwrap();
+        let stream = *d.stream_info();
+        if !self.seek_indexed(time)? {
+            self.demuxer.seek_stream(time, &stream)?;
+        }
+        self.flush();
+        self.position = time;
+        Ok(())
+    }
+
+    pub fn seek_video(&mut self, time: units::Seconds) -> Result<(), MediaError> {
+        self.check_available(time)?;
+        let (d, _c) = self.video.as_ref().unwrap();
+        let stream = *d.stream_info();
+        if !self.seek_indexed(time)? {
+            self.demuxer.seek_stream(time, &stream)?;
+        }
+        self.flush();
+        self.position = time;
+        Ok(())
+    }
+
+    /// Taps were made for the decoder being replaced, so they go with it
+    fn set_audio(&mut self, decoder: audio::Decoder, sink: audio::AudioSinkKind) {
+        self.audio = Some((decoder, sink));
+        self.audio_taps.clear();
+    }
+
+    pub fn open_audio_player(&mut self, index: Option<usize>) -> Result<(), MediaError> {
+        let decoder = audio::Decoder::create(&self.demuxer, index)?;
+        let sink = audio::Player::create(&decoder)?;
+        self.set_audio(decoder, sink.into());
+        Ok(())
+    }
+
+    pub fn open_video_player(
+        &mut self, index: Option<usize>, accel: bool
+    ) -> Result<(), MediaError> {
+        let decoder = video::Decoder::create(&self.demuxer, index, accel)?;
+        let sink = video::Player::create(&decoder)?;
+        self.video = Some((decoder, sink.into()));
+        Ok(())
+    }
+
+    pub fn open_audio_sampler(
+        &mut self, index: Option<usize>, per_second: f64
+    ) -> Result<(), MediaError> {
+        let decoder = audio::Decoder::create(&self.demuxer, index)?;
+        let sink = audio::Sampler::create(&decoder, per_second)?;
+        self.set_audio(decoder, sink.into());
+        Ok(())
+    }
+
+    /// Computes intensities from the audio that is decoded anyway, such as
+    /// for playback, instead of decoding it all again elsewhere
+    pub fn add_audio_sampler_tap(&mut self, per_second: f64) -> Result<(), MediaError> {
+        let Some((decoder, _)) = self.audio.as_ref() else {
+            return Err(MediaError::InternalError("no audio stream".to_owned()));
+        };
+        let tap = audio::Sampler::create(decoder, per_second)?;
+        self.audio_taps.push(tap.into());
+        Ok(())
+    }
+
+    pub fn open_video_sampler(&mut self, index: Option<usize>) -> Result<(), MediaError> {
+        let decoder = video::Decoder::create(&self.demuxer, index, false)?;
+        let sink = video::Sampler::create(&decoder)?;
+        self.video = Some((decoder, sink.into()));
+        Ok(())
+    }
+
+    pub fn open_text_detector(&mut self, index: Option<usize>) -> Result<(), MediaError> {
+        let decoder = video::Decoder::create(&self.demuxer, index, false)?;
+        let sink = video::TextDetector::create(&decoder)?;
+        self.video = Some((decoder, sink.into()));
+        Ok(())
+    }
+
+    pub fn open_crop_detector(&mut self, index: Option<usize>) -> Result<(), MediaError> {
+        let decoder = video::Decoder::create(&self.demuxer, index, false)?;
+        let sink = video::CropDetector::create(&decoder)?;
+        self.video = Some((decoder, sink.into()));
+        Ok(())
+    }
+
+    pub fn open_scene_detector(&mut self, index: Option<usize>) -> Result<(), MediaError> {
+        let decoder = video::Decoder::create(&self.demuxer, index, false)?;
+        let sink = video::SceneDetector::create(&decoder)?;
+        self.video = Some((decoder, sink.into()));
+        Ok(())
+    }
+
+    pub fn open_silence_detector(
+        &mut self, index: Option<usize>, min_duration: units::Seconds
+    ) -> Result<(), MediaError> {
+        let decoder = audio::Decoder::create(&self.demuxer, index)?;
+        let sink = audio::SilenceDetector::create(&decoder, min_duration)?;
+        self.set_audio(decoder, sink.into());
+        Ok(())
+    }
+
+    pub fn open_subpicture(&mut self, index: Option<usize>) -> Result<(), MediaError> {
+        let decoder = subpicture::Decoder::create(&self.demuxer, index)?;
+        let compositor = subpicture::Compositor::create(&decoder)?;
+        self.subpicture = Some((decoder, compositor));
+        Ok(())
+    }
+
+    /// Once the packet reading stopped at has arrived, goes back to where
+    /// the streams were and reads again from there: the demuxer read zeros
+    /// in place of that packet and perhaps others before it, which are
+    /// data by now. What was fed already is skipped by `try_feed`.
+    fn resume(&mut self, (position, size): (isize, usize)) -> Result<(), MediaError> {
+        let a = self.availability.as_mut().unwrap();
+        if !a.covers(position, size) {
+            a.refresh_if_due().map_err(|e| MediaError::InternalError(e.to_string()))?;
+            if !a.covers(position, size) {
+                return Err(a.unavailable());
+            }
+        }
+        let time = self.fed.iter()
+            .filter_map(|(&i, &dts)| {
+                let (stream, _) = self.demuxer.get_stream_from_index(i).ok()?;
+                Some(units::Timestamp(dts).to_seconds(stream.timebase()))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap_or(units::Seconds(0.0));
+        self.demuxer.discard_buffer();
+        self.demuxer.seek(time)?;
+        self.stalled = None;
+        Ok(())
+    }
+
+    /// returns `Ok(false)` on EOF
+    #[tracing::instrument(name = "session::feed", skip_all)]
+    pub fn try_feed(&mut self) -> Result<bool, MediaError> {
+        if let Some(stalled) = self.stalled {
+            self.resume(stalled)?;
+        }
+        let Some((i, packet)) = self.demuxer.next_packet() else {
+            if let Some(a) = self.availability.as_mut()
+                && !a.is_complete()
+            {
+                // the end of what has been written, not of the file
+                return Err(a.unavailable());
+            }
+            if let Some((_, c)) = self.subpicture_mut() {
+                c.finish();
+            }
+            return Ok(false);
+        };
+        if let Some(a) = self.availability.as_mut() {
+            let range = (packet.position(), packet.size());
+            if !a.covers(range.0, range.1) {
+                a.refresh_if_due().map_err(|e| MediaError::InternalError(e.to_string()))?;
+                if !a.covers(range.0, range.1) {
+                    self.stalled = Some(range);
+                    return Err(a.unavailable());
+                }
+            }
+            if let Some(dts) = packet.dts() {
+                // read again after a stall
+                if self.fed.get(&i).is_some_and(|&x| dts <= x) {
+                    return Ok(true);
+                }
+                self.fed.insert(i, dts);
+            }
+        }
+        if let Some((d, _)) = self.audio_mut()
+            && d.stream_info().index() == i
+        {
+            d.feed(&packet)?;
+        }
+        if let Some((d, _)) = self.video_mut()
+            && d.stream_info().index() == i
+        {
+            d.feed(&packet)?;
+        }
+        if let Some((d, _)) = self.subpicture_mut()
+            && d.stream_info().index() == i
+        {
+            d.feed(&packet)?;
+        }
+        Ok(true)
+    }
+
+    /// Decodes the video frame on screen at `time` through the video player,
+    /// at the player's current output size. Leaves the session positioned
+    /// after it; `None` if `time` is past the end.
+    #[tracing::instrument(name = "session::render_frame_at", skip_all)]
+    pub fn render_frame_at(
+        &mut self, time: units::Seconds
+    ) -> Result<Option<frame::Video>, MediaError> {
+        let Some((d, _)) = self.video.as_ref() else {
+            return Err(MediaError::InternalError("no video stream".to_owned()));
+        };
+        // frames are displayed from their timestamp until the next one's
+        let half_frame = 0.5 / f64::from(d.framerate());
+        let from = units::Seconds(time.0 - half_frame);
+        self.seek_video(time)?;
+        loop {
+            self.try_process_skipping_before(from)?;
+            if let Some((_, video::VideoSinkKind::Player(p))) = self.video_mut()
+                && let Some(frame) = p.get_delta().pop_front()
+            {
+                return Ok(Some(frame));
+            }
+            if !self.try_feed()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// The bitmap subtitle page on screen at `time`. A seek to `time` may
+    /// land after that page started, so this seeks the subtitle stream
+    /// itself back to the last page start and reads its packets on from
+    /// there, skipping the others. The session has to be sought again
+    /// afterwards.
+    pub fn subpicture_at(
+        &mut self, time: units::Seconds
+    ) -> Result<Option<&frame::Subpicture>, MediaError> {
+        let Some((d, _)) = self.subpicture.as_ref() else { return Ok(None) };
+        let stream = *d.stream_info();
+        let video = self.video.as_ref().map(|(d, _)| *d.stream_info());
+        self.check_available(time)?;
+        self.demuxer.seek_stream(time, &stream)?;
+        self.flush();
+
+        let (d, c) = self.subpicture.as_mut().unwrap();
+        let past = |packet: &demux::Packet, stream: &demux::StreamInfo, slack: f64| {
+            packet.pts().is_some_and(|pts|
+                units::Timestamp(pts).to_seconds(stream.timebase()).0 > time.0 + slack)
+        };
+        while let Some((i, packet)) = self.demuxer.next_packet() {
+            if i == stream.index() {
+                if past(&packet, &stream, 0.0) {
+                    break;
+                }
+                d.feed(&packet)?;
+                while let Some(page) = d.try_receive() {
+                    c.process(page)?;
+                }
+            } else if let Some(video) = &video
+                && i == video.index()
+                && past(&packet, video, SUBPICTURE_MUX_SLACK)
+            {
+                break;
+            }
+        }
+        Ok(c.visible_at(time))
+    }
+
+    pub fn try_process(&mut self) -> Result<i32, MediaError> {
+        self.try_process_skipping_before(units::Seconds(f64::NEG_INFINITY))
+    }
+
+    #[tracing::instrument(name = "session::process", skip_all)]
+    pub fn try_process_skipping_before(&mut self, when: units::Seconds) -> Result<i32, MediaError> {
+        let mut count = 0;
+        loop {
+            if let Some((d, c)) = self.audio.as_mut()
+                && let Some(f) = d.try_receive()?
+                && f.meta.time >= when
+            {
+                self.position = f.meta.time;
+                for tap in &mut self.audio_taps {
+                    tap.process(f.clone())?;
+                }
+                c.process(f)?;
+                count += 1;
+                continue;
+            }
+            if let Some((d, c)) = self.video.as_mut()
+                && let Some(f) = d.try_receive()?
+                && f.meta.time >= when
+            {
+                self.position = f.meta.time;
+                c.process(f)?;
+                count += 1;
+                continue;
+            }
+            // pages starting before `when` may still be on screen, so they
+            // are never skipped
+            if let Some((d, c)) = self.subpicture_mut()
+                && let Some(f) = d.try_receive()
+            {
+                c.process(f)?;
+                count += 1;
+                continue;
+            }
+            break;
+        }
+        Ok(count)
+    }
+}
\ No newline at end of file
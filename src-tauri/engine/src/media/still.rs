@@ -0,0 +1,95 @@
+//! Single frames as image files, for screenshot export.
+
+use ffmpeg::{codec, encoder, format, software::scaling, Packet};
+use serde::Deserialize;
+
+use crate::media::{frame, internal::{check, MediaError}};
+
+#[derive(Clone, Copy, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// Encodes an RGBA frame as a complete image file
+pub fn encode(rgba: &frame::VideoData, format: ImageFormat) -> Result<Vec<u8>, MediaError> {
+    let (width, height) = (rgba.width(), rgba.height());
+    let (codec_id, pixel) = match format {
+        ImageFormat::Png => (codec::Id::PNG, format::Pixel::RGBA),
+        ImageFormat::Jpeg => (codec::Id::MJPEG, format::Pixel::YUVJ420P),
+    };
+
+    let converted;
+    let input = if pixel == format::Pixel::RGBA {
+        rgba
+    } else {
+        let mut scaler = check!(scaling::Context::get(
+            format::Pixel::RGBA, width, height,
+            pixel, width, height,
+            scaling::Flags::BICUBIC,
+        ))?;
+        let mut output = frame::VideoData::empty();
+        check!(scaler.run(rgba, &mut output))?;
+        converted = output;
+        &converted
+    };
+
+    let codec = encoder::find(codec_id).ok_or(
+        MediaError::InternalError(format!("encoder not found: {codec_id:?}")))?;
+    let mut context = check!(codec::Context::new_with_codec(codec).encoder().video())?;
+    context.set_width(width);
+    context.set_height(height);
+    context.set_format(pixel);
+    context.set_time_base((1, 1));
+    let mut encoder = check!(context.open_as(codec))?;
+
+    check!(encoder.send_frame(input))?;
+    check!(encoder.send_eof())?;
+    let mut packet = Packet::empty();
+    check!(encoder.receive_packet(&mut packet))?;
+    Ok(packet.data().unwrap_or(&[]).to_vec())
+}
+
+/// Draws a bitmap subtitle page onto an RGBA frame, scaling it from the
+/// stream's canvas to the frame
+pub fn overlay(target: &mut frame::VideoData, page: &frame::Subpicture, canvas: (u32, u32)) {
+    let (width, height) = (target.width(), target.height());
+    let stride = target.stride(0);
+    let data = target.data_mut(0);
+    let scale = |v: u32, from: u32, to: u32|
+        u32::try_from(u64::from(v) * u64::from(to) / u64::from(from.max(1))).unwrap();
+
+    for rect in &page.rects {
+        let x0 = scale(rect.x, canvas.0, width);
+        let y0 = scale(rect.y, canvas.1, height);
+        let x1 = scale(rect.x + rect.width, canvas.0, width).min(width);
+        let y1 = scale(rect.y + rect.height, canvas.1, height).min(height);
+        for y in y0..y1 {
+            // nearest source pixel
+            let sy = (scale(y, height, canvas.1).saturating_sub(rect.y)).min(rect.height - 1);
+            for x in x0..x1 {
+                let sx = (scale(x, width, canvas.0).saturating_sub(rect.x)).min(rect.width - 1);
+                let src = &rect.rgba[(sy * rect.width + sx) as usize * 4..][..4];
+                let dst = &mut data[y as usize * stride + x as usize * 4..][..4];
+                let alpha = u32::from(src[3]);
+                for c in 0..3 {
+                    let blended = (u32::from(src[c]) * alpha
+                        + u32::from(dst[c]) * (255 - alpha)) / 255;
+                    dst[c] = u8::try_from(blended).unwrap();
+                }
+                dst[3] = 255;
+            }
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+
+use ffmpeg::codec;
+use getset::{CopyGetters, Getters};
+use log::{debug, trace};
+
+use crate::media::{demux, frame, internal::{check, MediaError}, units};
+
+/// DVB doesn't require a display definition segment, in which case the
+/// page is assumed to be SD-sized
+const DEFAULT_CANVAS_SIZE: (u32, u32) = (720, 576);
+
+/// `ffmpeg::Subtitle` doesn't free its rects on drop
+pub(crate) struct OwnedSubtitle(pub(crate) ffmpeg::Subtitle);
+
+impl Drop for OwnedSubtitle {
+    fn drop(&mut self) {
+        unsafe {
+            ffmpeg_sys_next::avsubtitle_free(self.0.as_mut_ptr());
+        }
+    }
+}
+
+#[derive(Getters, CopyGetters)]
+pub struct Decoder {
+    inner: codec::decoder::Subtitle,
+
+    #[getset(get = "pub")]
+    stream_info: demux::StreamInfo,
+
+    #[getset(get_copy = "pub")]
+    canvas_size: (u32, u32),
+
+    #[getset(get = "pub")]
+    codec_name: String,
+
+    decoded: VecDeque<frame::Subpicture>,
+}
+
+impl Decoder {
+    pub fn create(
+        demuxer: &demux::Demuxer, index: Option<usize>
+    ) -> Result<Decoder, MediaError> {
+        let (stream_info, stream) = match index {
+            Some(i) => demuxer.get_stream_from_index(i),
+            None => demuxer.get_stream_from_kind(demux::StreamKind::Subtitle)
+        }?;
+
+        let codecxt = check!(codec::Context::from_parameters(stream.parameters()))?;
+        let codec_name = stream.parameters().id().name().to_owned();
+        let decoder = check!(codecxt.decoder().subtitle())?;
+
+        let canvas_size = unsafe {
+            let ptr = decoder.as_ptr();
+            match ((*ptr).width, (*ptr).height) {
+                (w, h) if w > 0 && h > 0 => (w.unsigned_abs(), h.unsigned_abs()),
+                _ => DEFAULT_CANVAS_SIZE
+            }
+        };
+
+        debug!(
+            "subpicture::Decoder::create: [{}] codec={codec_name}, canvas={canvas_size:?}",
+            stream_info.index()
+        );
+
+        Ok(Decoder {
+            stream_info,
+            canvas_size,
+            codec_name,
+            inner: decoder,
+            decoded: VecDeque::new(),
+        })
+    }
+
+    pub fn flush(&mut self) {
+        self.inner.flush();
+        self.decoded.clear();
+        self.stream_info.byte_pos_can_update = true;
+        self.stream_info.byte_pos = -1;
+    }
+
+    /// Subtitle decoding is synchronous in ffmpeg: every packet either yields
+    /// a complete display set or nothing, so we decode right away and queue
+    /// the result for `try_receive`.
+    pub fn feed(&mut self, packet: &demux::Packet) -> Result<(), MediaError> {
+        let mut decoded = OwnedSubtitle(ffmpeg::Subtitle::new());
+        if !check!(self.inner.decode(packet, &mut decoded.0))? {
+            return Ok(());
+        }
+
+        let base_time = match decoded.0.pts() {
+            Some(pts) => units::Timestamp(pts).to_seconds(units::DEFAULT_TIMEBASE),
+            None => units::Timestamp(
+                packet.pts()
+                .ok_or(MediaError::InternalError(
+                    "subtitle packet has no pts".to_owned(),
+                ))?
+            ).to_seconds(self.stream_info.timebase()),
+        };
+        let time = units::Seconds(base_time.0 + f64::from(decoded.0.start()) / 1000.0);
+        // an end time of 0 means undetermined; the page then lasts until
+        // the next one arrives
+        let end_time = match decoded.0.end() {
+            0 | u32::MAX => None,
+            x => Some(units::Seconds(base_time.0 + f64::from(x) / 1000.0)),
+        };
+
+        let mut rects = Vec::new();
+        for rect in decoded.0.rects() {
+            let codec::subtitle::Rect::Bitmap(bitmap) = rect else {
+                trace!("subpicture::Decoder::feed: ignoring non-bitmap rect");
+                continue;
+            };
+            match Self::resolve_bitmap(&bitmap) {
+                Some(x) => rects.push(x),
+                None => trace!("subpicture::Decoder::feed: ignoring rect off the picture"),
+            }
+        }
+
+        self.decoded.push_back(frame::Subpicture {
+            meta: frame::FrameMetadata {
+                time,
+                byte_pos: packet.position(),
+                pkt_pos: packet.position() as i64,
+            },
+            end_time,
+            rects,
+        });
+        Ok(())
+    }
+
+    pub fn try_receive(&mut self) -> Option<frame::Subpicture> {
+        self.decoded.pop_front()
+    }
+
+    /// Paletted bitmaps carry 8-bit indices in `data[0]` and an ARGB palette
+    /// of `nb_colors` native-endian u32 in `data[1]`. Nothing stops a
+    /// stream from placing a rect partly left of or above the picture; that
+    /// part is cut off, and `None` if nothing is left.
+    fn resolve_bitmap(bitmap: &codec::subtitle::Bitmap) -> Option<frame::BitmapRect> {
+        // `Bitmap::x` and `y` cast to usize, which negative positions don't
+        // survive
+        let (ptr, left, top) = unsafe {
+            let ptr = bitmap.as_ptr();
+            (ptr, (*ptr).x, (*ptr).y)
+        };
+        let (skip_x, skip_y) = (left.min(0).unsigned_abs(), top.min(0).unsigned_abs());
+        let width = bitmap.width().checked_sub(skip_x)?;
+        let height = bitmap.height().checked_sub(skip_y)?;
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+        unsafe {
+            let indices = (*ptr).data[0];
+            let palette = (*ptr).data[1].cast::<u32>();
+            let linesize = (*ptr).linesize[0].unsigned_abs() as usize;
+            let colors = bitmap.colors();
+            for y in skip_y as usize..(skip_y + height) as usize {
+                for x in skip_x as usize..(skip_x + width) as usize {
+                    let index = *indices.add(y * linesize + x) as usize;
+                    let argb = if index < colors { palette.add(index).read_unaligned() } else { 0 };
+                    rgba.extend_from_slice(&[
+                        (argb >> 16) as u8,
+                        (argb >> 8) as u8,
+                        argb as u8,
+                        (argb >> 24) as u8,
+                    ]);
+                }
+            }
+        }
+        Some(frame::BitmapRect {
+            x: left.max(0).unsigned_abs(),
+            y: top.max(0).unsigned_abs(),
+            width, height, rgba,
+        })
+    }
+}
+
+/// Turns the stream of display sets into pages with definite durations:
+/// a page stays on screen until its own timeout or until the next display
+/// set replaces it, whichever comes first.
+pub struct Compositor {
+    current: Option<frame::Subpicture>,
+    pages: VecDeque<frame::Subpicture>,
+}
+
+impl Compositor {
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn create(_decoder: &Decoder) -> Result<Self, MediaError> {
+        Ok(Self {
+            current: None,
+            pages: VecDeque::new(),
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.pages.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn process(&mut self, page: frame::Subpicture) -> Result<(), MediaError> {
+        if let Some(mut previous) = self.current.take() {
+            let replaced_at = page.meta.time;
+            previous.end_time = Some(match previous.end_time {
+                Some(end) if end < replaced_at => end,
+                _ => replaced_at,
+            });
+            self.pages.push_back(previous);
+        }
+        // a display set without regions just clears the screen
+        if !page.rects.is_empty() {
+            self.current = Some(page);
+        }
+        Ok(())
+    }
+
+    /// Emit the page still on screen, e.g. at EOF
+    pub fn finish(&mut self) {
+        if let Some(page) = self.current.take() {
+            self.pages.push_back(page);
+        }
+    }
+
+    /// The page on screen at `time` among those not yet taken by `get_delta`
+    pub fn visible_at(&self, time: units::Seconds) -> Option<&frame::Subpicture> {
+        self.pages.iter().chain(self.current.iter())
+            .rev()
+            .find(|x| x.meta.time <= time && x.end_time.is_none_or(|end| time < end))
+    }
+
+    pub fn get_delta(&mut self) -> VecDeque<frame::Subpicture> {
+        std::mem::take(&mut self.pages)
+    }
+}
@@ -0,0 +1,49 @@
+//! Drawing video straight into the window with wgpu, so that frames never
+//! cross IPC. The surface belongs to the whole window and sits below the
+//! webview: the frontend leaves the preview area transparent and tells us
+//! where it is, and on top of the video we draw the overlay it renders its
+//! subtitles into. Whether the video shows through a transparent webview is
+//! up to the platform's webview; where it doesn't, stay with IPC frames.
+//!
+//! Only built with the `surface` feature, which also brings in tauri for
+//! the window; without it `Surface::create` always fails.
+
+use serde::Deserialize;
+
+#[cfg(feature = "surface")]
+mod renderer;
+#[cfg(feature = "surface")]
+pub use renderer::Surface;
+
+/// The preview area, in physical pixels of the window
+#[derive(Clone, Copy, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[cfg(not(feature = "surface"))]
+pub enum Surface {}
+
+#[cfg(not(feature = "surface"))]
+impl Surface {
+    pub fn create<W>(_window: W, _viewport: Viewport) -> Result<Self, String> {
+        Err("surface: built without the `surface` feature".to_owned())
+    }
+    pub fn resize(&mut self, _size: (u32, u32), _viewport: Viewport) {
+        match *self {}
+    }
+    pub fn set_video(&mut self, _frame: &crate::media::frame::Video) -> Result<(), String> {
+        match *self {}
+    }
+    pub fn set_overlay(&mut self, _overlay: Option<((u32, u32), &[u8])>) -> Result<(), String> {
+        match *self {}
+    }
+    pub fn draw(&self) -> Result<(), String> {
+        match *self {}
+    }
+}
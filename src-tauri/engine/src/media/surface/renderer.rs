@@ -0,0 +1,298 @@
+//! The wgpu side of `surface`
+
+use std::borrow::Cow;
+
+use crate::media::{frame, surface::Viewport};
+
+const SHADER: &str = r"
+struct Output {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32) -> Output {
+    // one triangle covering the viewport
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    var out: Output;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// the part of the picture that is drawn, when the rect it goes into
+// sticks out of the window
+struct Crop {
+    offset: vec2<f32>,
+    scale: vec2<f32>,
+};
+
+@group(0) @binding(0) var picture: texture_2d<f32>;
+@group(0) @binding(1) var picture_sampler: sampler;
+@group(0) @binding(2) var<uniform> crop: Crop;
+
+@fragment
+fn fs_main(in: Output) -> @location(0) vec4<f32> {
+    return textureSample(picture, picture_sampler, crop.offset + in.uv * crop.scale);
+}
+";
+
+/// A texture and what the shader needs to draw it
+struct Layer {
+    texture: wgpu::Texture,
+    /// `Crop` in the shader
+    crop: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    size: (u32, u32),
+}
+
+pub struct Surface {
+    surface: wgpu::Surface<'static>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    config: wgpu::SurfaceConfiguration,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    viewport: Viewport,
+    video: Option<Layer>,
+    overlay: Option<Layer>,
+}
+
+fn error(what: &str, e: impl std::fmt::Display) -> String {
+    format!("surface: {what}: {e}")
+}
+
+impl Surface {
+    pub fn create(window: tauri::WebviewWindow, viewport: Viewport) -> Result<Self, String> {
+        let size = window.inner_size().map_err(|e| error("inner_size", e))?;
+        let size = (size.width, size.height);
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let surface = instance.create_surface(window)
+            .map_err(|e| error("create_surface", e))?;
+        let adapter = tauri::async_runtime::block_on(
+            instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: Some(&surface),
+                force_fallback_adapter: false,
+            }))
+            .map_err(|e| error("request_adapter", e))?;
+        let (device, queue) = tauri::async_runtime::block_on(
+            adapter.request_device(&wgpu::DeviceDescriptor::default()))
+            .map_err(|e| error("request_device", e))?;
+        let config = surface.get_default_config(&adapter, size.0.max(1), size.1.max(1))
+            .ok_or_else(|| "surface: not supported by the adapter".to_owned())?;
+        surface.configure(&device, &config);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("surface"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("surface"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            surface, device, queue, config, pipeline, sampler, viewport,
+            video: None,
+            overlay: None,
+        })
+    }
+
+    /// For when the window or the preview area in it has changed; `size` is
+    /// that of the window, in physical pixels
+    pub fn resize(&mut self, size: (u32, u32), viewport: Viewport) {
+        self.viewport = viewport;
+        let size = (size.0.max(1), size.1.max(1));
+        if (self.config.width, self.config.height) != size {
+            (self.config.width, self.config.height) = size;
+            self.surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// Writes `rgba` into `layer`, first making a new one if the size changed;
+    /// an error if the device can't hold a texture that size
+    fn upload(
+        &self, layer: &mut Option<Layer>, size: (u32, u32), stride: usize, rgba: &[u8]
+    ) -> Result<(), String> {
+        let max = self.device.limits().max_texture_dimension_2d;
+        if size.0 == 0 || size.1 == 0 || size.0 > max || size.1 > max {
+            return Err(format!("surface: can't take a {}x{} picture; the device allows \
+                up to {max}x{max}", size.0, size.1));
+        }
+        if layer.as_ref().is_none_or(|x| x.size != size) {
+            let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("surface layer"),
+                size: wgpu::Extent3d { width: size.0, height: size.1, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let crop = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("surface layer crop"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("surface layer"),
+                layout: &self.pipeline.get_bind_group_layout(0),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: crop.as_entire_binding(),
+                    },
+                ],
+            });
+            *layer = Some(Layer { texture, crop, bind_group, size });
+        }
+        let layer = layer.as_ref().unwrap();
+        self.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &layer.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(u32::try_from(stride).unwrap()),
+                rows_per_image: Some(size.1),
+            },
+            wgpu::Extent3d { width: size.0, height: size.1, depth_or_array_layers: 1 },
+        );
+        Ok(())
+    }
+
+    pub fn set_video(&mut self, frame: &frame::Video) -> Result<(), String> {
+        let size = (frame.decoded.width(), frame.decoded.height());
+        let mut layer = self.video.take();
+        let result = self.upload(&mut layer, size, frame.decoded.stride(0), frame.decoded.data(0));
+        self.video = layer;
+        result
+    }
+
+    /// `rgba` is packed, `width * height * 4` bytes; `None` removes the overlay
+    pub fn set_overlay(&mut self, overlay: Option<((u32, u32), &[u8])>) -> Result<(), String> {
+        let Some((size, rgba)) = overlay else {
+            self.overlay = None;
+            return Ok(());
+        };
+        let mut layer = self.overlay.take();
+        let result = self.upload(&mut layer, size, size.0 as usize * 4, rgba);
+        self.overlay = layer;
+        result
+    }
+
+    /// The video fitted into the viewport, keeping its aspect ratio
+    #[allow(clippy::cast_precision_loss)]
+    fn video_rect(&self, size: (u32, u32)) -> (f32, f32, f32, f32) {
+        let v = self.viewport;
+        let (vw, vh) = (v.width as f32, v.height as f32);
+        let scale = (vw / size.0 as f32).min(vh / size.1 as f32);
+        let (w, h) = (size.0 as f32 * scale, size.1 as f32 * scale);
+        (v.x as f32 + (vw - w) / 2.0, v.y as f32 + (vh - h) / 2.0, w, h)
+    }
+
+    /// `rect` cut down to the surface, which wgpu insists viewports stay
+    /// within, and the part of the picture that goes into what is left, as
+    /// `Crop` in the shader; `None` if nothing is left
+    #[allow(clippy::cast_precision_loss)]
+    fn clip(&self, (x, y, w, h): (f32, f32, f32, f32)) -> Option<((f32, f32, f32, f32), [f32; 4])> {
+        let (width, height) = (self.config.width as f32, self.config.height as f32);
+        let (left, top) = (x.clamp(0.0, width), y.clamp(0.0, height));
+        let (right, bottom) = ((x + w).clamp(0.0, width), (y + h).clamp(0.0, height));
+        let crop = [(left - x) / w, (top - y) / h, (right - left) / w, (bottom - top) / h];
+        (right > left && bottom > top).then_some(((left, top, right - left, bottom - top), crop))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn draw(&self) -> Result<(), String> {
+        let output = match self.surface.get_current_texture() {
+            Ok(x) => x,
+            // the window was resized or moved between screens; the next
+            // `resize` sorts it out
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => return Ok(()),
+            Err(e) => return Err(error("get_current_texture", e)),
+        };
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("surface"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+
+            let v = self.viewport;
+            let whole = (v.x as f32, v.y as f32, v.width as f32, v.height as f32);
+            let layers = [
+                self.video.as_ref().map(|x| (x, self.video_rect(x.size))),
+                self.overlay.as_ref().map(|x| (x, whole)),
+            ];
+            for (layer, rect) in layers.into_iter().flatten() {
+                let Some(((x, y, w, h), crop)) = self.clip(rect) else { continue };
+                // written before the submit, so each layer's is in place
+                // for its draw
+                let crop: Vec<u8> = crop.iter().flat_map(|x| x.to_ne_bytes()).collect();
+                self.queue.write_buffer(&layer.crop, 0, &crop);
+                pass.set_viewport(x, y, w, h, 0.0, 1.0);
+                pass.set_bind_group(0, &layer.bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        }
+        self.queue.submit([encoder.finish()]);
+        output.present();
+        Ok(())
+    }
+}
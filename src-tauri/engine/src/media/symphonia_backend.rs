@@ -0,0 +1,421 @@
+//! Audio files decoded by symphonia instead of ffmpeg, for audio-only work
+//! on machines where the bundled ffmpeg won't load. The output is what the
+//! ffmpeg audio sinks give: mono `f32` at the track's rate for the player,
+//! and `audio::Intensities` for the sampler. Should the rate change midway,
+//! as it can in chained Ogg streams, rubato brings it back to the rate the
+//! track was opened with.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use num_traits::ToPrimitive;
+use rubato::{FftFixedIn, Resampler};
+use symphonia::core::audio::{AudioBufferRef, SampleBuffer};
+use symphonia::core::codecs::{CodecParameters, Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
+
+use crate::media::audio::{Intensities, SamplerDeltaData};
+use crate::media::backend::{AudioBlock, AudioStatus, BackendKind, MediaBackend, MediaStatus};
+use crate::media::demux::{SerializableStreamKind, StreamDescription};
+use crate::media::{internal::MediaError, session::Snapshot, units::Seconds};
+
+/// Input samples per resampler run
+const RESAMPLE_CHUNK: usize = 1024;
+
+fn error(e: &Error) -> MediaError {
+    MediaError::InternalError(format!("symphonia: {e}"))
+}
+
+fn is_audio(params: &CodecParameters) -> bool {
+    params.codec != CODEC_TYPE_NULL && params.sample_rate.is_some()
+}
+
+fn timebase(params: &CodecParameters) -> TimeBase {
+    params.time_base.unwrap_or_else(|| TimeBase::new(1, params.sample_rate.unwrap_or(1)))
+}
+
+fn seconds(timebase: TimeBase, ts: u64) -> f64 {
+    let time = timebase.calc_time(ts);
+    time.seconds.to_f64().unwrap() + time.frac
+}
+
+/// Mono audio at some other rate, brought back to the track's
+struct Resampling {
+    from: u32,
+    resampler: FftFixedIn<f32>,
+    /// less than a chunk, left over from last time
+    pending: Vec<f32>,
+}
+
+impl Resampling {
+    fn create(from: u32, to: u32) -> Result<Self, MediaError> {
+        let resampler = FftFixedIn::new(
+            from.to_usize().unwrap(), to.to_usize().unwrap(), RESAMPLE_CHUNK, 2, 1)
+            .map_err(|e| MediaError::InternalError(format!("rubato: {e}")))?;
+        Ok(Self { from, resampler, pending: Vec::new() })
+    }
+
+    /// Returns what whole chunks there are, and how long before `data` the
+    /// first of them starts
+    fn run(&mut self, data: &[f32]) -> Result<(Vec<f32>, f64), MediaError> {
+        let lead = self.pending.len().to_f64().unwrap() / f64::from(self.from);
+        self.pending.extend_from_slice(data);
+        let mut output = Vec::new();
+        let mut consumed = 0;
+        loop {
+            let needed = self.resampler.input_frames_next();
+            if self.pending.len() - consumed < needed {
+                break;
+            }
+            let chunk = [&self.pending[consumed..consumed + needed]];
+            let resampled = self.resampler.process(&chunk, None)
+                .map_err(|e| MediaError::InternalError(format!("rubato: {e}")))?;
+            output.extend_from_slice(&resampled[0]);
+            consumed += needed;
+        }
+        self.pending.drain(..consumed);
+        Ok((output, lead))
+    }
+}
+
+/// The channels averaged, and the rate
+fn mix_down(decoded: AudioBufferRef) -> (Vec<f32>, u32) {
+    let spec = *decoded.spec();
+    let channels = spec.channels.count().max(1);
+    let mut interleaved = SampleBuffer::<f32>::new(decoded.capacity().to_u64().unwrap(), spec);
+    interleaved.copy_interleaved_ref(decoded);
+    #[allow(clippy::cast_precision_loss)]
+    let scale = 1.0 / channels as f32;
+    let mono = interleaved.samples()
+        .chunks_exact(channels)
+        .map(|x| x.iter().sum::<f32>() * scale)
+        .collect();
+    (mono, spec.rate)
+}
+
+enum Sink {
+    Player(VecDeque<AudioBlock>),
+    Sampler(Intensities),
+}
+
+struct Track {
+    id: u32,
+    /// among the file's tracks, as `describe_streams` counts them
+    index: usize,
+    decoder: Box<dyn Decoder>,
+    timebase: TimeBase,
+    sample_rate: u32,
+    start_time: Seconds,
+    /// number of samples, estimated
+    length: usize,
+    resampling: Option<Resampling>,
+    /// where the last seek went; what decodes before it is dropped
+    skip_before: Option<f64>,
+    sink: Sink,
+}
+
+impl Track {
+    fn status(&self) -> AudioStatus {
+        AudioStatus {
+            index: self.index,
+            length: self.length,
+            start_time: self.start_time,
+            sample_rate: self.sample_rate,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match &self.sink {
+            Sink::Player(x) => x.is_empty(),
+            Sink::Sampler(x) => x.is_empty(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.decoder.reset();
+        if let Some(x) = &mut self.resampling {
+            x.pending.clear();
+        }
+        match &mut self.sink {
+            Sink::Player(x) => x.clear(),
+            Sink::Sampler(x) => x.clear(),
+        }
+    }
+
+    /// Hands `mono`, at `rate` and starting at `time`, to the sink
+    fn process(&mut self, mut mono: Vec<f32>, rate: u32, mut time: f64) -> Result<(), MediaError> {
+        if let Some(target) = self.skip_before {
+            let rate = f64::from(rate);
+            let skip = ((target - time) * rate).round().to_usize().unwrap_or(0);
+            if skip >= mono.len() {
+                return Ok(());
+            }
+            mono.drain(..skip);
+            time += skip.to_f64().unwrap() / rate;
+            self.skip_before = None;
+        }
+
+        if rate != self.sample_rate {
+            if self.resampling.as_ref().is_none_or(|x| x.from != rate) {
+                debug!("symphonia: resampling {rate} to {}", self.sample_rate);
+                self.resampling = Some(Resampling::create(rate, self.sample_rate)?);
+            }
+            let (resampled, lead) = self.resampling.as_mut().unwrap().run(&mono)?;
+            mono = resampled;
+            time -= lead;
+        }
+        if mono.is_empty() {
+            return Ok(());
+        }
+
+        match &mut self.sink {
+            Sink::Player(x) => x.push_back(AudioBlock {
+                time: Seconds(time),
+                pkt_pos: -1,
+                samples: mono,
+            }),
+            Sink::Sampler(x) => x.add(time, f64::from(self.sample_rate), &mono),
+        }
+        Ok(())
+    }
+}
+
+pub struct SymphoniaBackend {
+    path: PathBuf,
+    reader: Box<dyn FormatReader>,
+    track: Option<Track>,
+    /// the last seek target, or the time of the last audio decoded since
+    position: Seconds,
+}
+
+impl SymphoniaBackend {
+    #[tracing::instrument(name = "symphonia::open", skip_all)]
+    pub fn create(path: &Path) -> Result<Self, MediaError> {
+        let file = File::open(path).map_err(|e| MediaError::InternalError(e.to_string()))?;
+        let source = MediaSourceStream::new(Box::new(file), MediaSourceStreamOptions::default());
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|x| x.to_str()) {
+            hint.with_extension(extension);
+        }
+        let options = FormatOptions { enable_gapless: true, ..Default::default() };
+        let probed = symphonia::default::get_probe()
+            .format(&hint, source, &options, &MetadataOptions::default())
+            .map_err(|e| error(&e))?;
+        Ok(Self {
+            path: path.to_owned(),
+            reader: probed.format,
+            track: None,
+            position: Seconds(0.0),
+        })
+    }
+
+    /// The first audio track if `index` is `None`; `sink` is given the
+    /// track's start time
+    fn open_track(
+        &self, index: Option<usize>, sink: impl FnOnce(Seconds) -> Sink
+    ) -> Result<Track, MediaError> {
+        let tracks = self.reader.tracks();
+        let (index, track) = match index {
+            Some(i) => (i, tracks.get(i).ok_or_else(|| MediaError::InternalError(
+                format!("open_track: [{i}] invalid stream index")))?),
+            None => tracks.iter().enumerate()
+                .find(|(_, x)| is_audio(&x.codec_params))
+                .ok_or_else(|| MediaError::InternalError("no audio stream".to_owned()))?,
+        };
+        let params = &track.codec_params;
+        if !is_audio(params) {
+            return Err(MediaError::InternalError(format!("[{index}] not an audio stream")));
+        }
+        let decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .map_err(|e| error(&e))?;
+        let timebase = timebase(params);
+        let sample_rate = params.sample_rate.unwrap();
+        let length = params.n_frames
+            .map_or(0, |x| x.to_usize().unwrap_or(usize::MAX));
+        let start_time = Seconds(seconds(timebase, params.start_ts));
+        Ok(Track {
+            id: track.id,
+            index,
+            decoder,
+            timebase,
+            sample_rate,
+            start_time,
+            length,
+            resampling: None,
+            skip_before: None,
+            sink: sink(start_time),
+        })
+    }
+
+    fn duration(&self) -> Seconds {
+        let longest = self.reader.tracks().iter()
+            .filter_map(|x| x.codec_params.n_frames
+                .map(|n| seconds(timebase(&x.codec_params), x.codec_params.start_ts + n)))
+            .fold(0.0, f64::max);
+        Seconds(longest)
+    }
+
+    /// Decodes one more packet; `Ok(false)` at the end of the file
+    #[tracing::instrument(name = "symphonia::decode", skip_all)]
+    fn step(&mut self) -> Result<bool, MediaError> {
+        let Some(track) = self.track.as_mut() else {
+            return Ok(false);
+        };
+        let packet = match self.reader.next_packet() {
+            Ok(x) => x,
+            Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                return Ok(false),
+            Err(Error::ResetRequired) => {
+                track.clear();
+                return Ok(true);
+            }
+            Err(e) => return Err(error(&e)),
+        };
+        if packet.track_id() != track.id {
+            return Ok(true);
+        }
+        let time = seconds(track.timebase, packet.ts());
+        match track.decoder.decode(&packet) {
+            Ok(decoded) => {
+                let (mono, rate) = mix_down(decoded);
+                track.process(mono, rate, time)?;
+                self.position = Seconds(time);
+            }
+            // a damaged packet; the next may well be fine
+            Err(Error::DecodeError(e)) => warn!("symphonia: at {time:.3}s: {e}"),
+            Err(e) => return Err(error(&e)),
+        }
+        Ok(true)
+    }
+
+    /// Opens `snapshot.path`, which the caller has vetted, with the player
+    /// it had, and seeks to where it was
+    pub fn restore(snapshot: &Snapshot) -> Result<Self, MediaError> {
+        let mut backend = Self::create(Path::new(&snapshot.path))?;
+        if snapshot.audio_index.is_some() {
+            backend.open_audio_player(snapshot.audio_index)?;
+        }
+        backend.seek(snapshot.position)?;
+        Ok(backend)
+    }
+}
+
+impl MediaBackend for SymphoniaBackend {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn status(&self) -> MediaStatus {
+        let codecs = symphonia::default::get_codecs();
+        let streams = self.reader.tracks().iter().enumerate()
+            .map(|(i, x)| StreamDescription::new(
+                if is_audio(&x.codec_params) {
+                    SerializableStreamKind::Audio
+                } else {
+                    SerializableStreamKind::Unknown
+                },
+                i,
+                x.language.clone().unwrap_or_else(|| "--".to_owned()),
+                codecs.get_codec(x.codec_params.codec).map(|c| c.short_name.to_owned()),
+            ))
+            .collect();
+        MediaStatus {
+            audio_index: self.track.as_ref().map(|x| x.index),
+            video_index: None,
+            audio_only: true,
+            duration: self.duration(),
+            streams,
+            start_timecode: None,
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let player = self.track.as_ref().filter(|x| matches!(x.sink, Sink::Player(_)));
+        Snapshot {
+            path: self.path.to_string_lossy().into_owned(),
+            backend: BackendKind::Symphonia,
+            tolerant: false,
+            audio_index: player.map(|x| x.index),
+            auto_gain: None,
+            video_index: None,
+            accel: false,
+            output_size: None,
+            color_managed: false,
+            display: None,
+            subpicture_index: None,
+            images: None,
+            members: Vec::new(),
+            position: self.position,
+        }
+    }
+
+    #[tracing::instrument(name = "symphonia::seek", skip_all)]
+    fn seek(&mut self, time: Seconds) -> Result<(), MediaError> {
+        let to = SeekTo::Time {
+            time: Time::from(time.0.max(0.0)),
+            track_id: self.track.as_ref().map(|x| x.id),
+        };
+        let seeked = self.reader.seek(SeekMode::Accurate, to).map_err(|e| error(&e))?;
+        if let Some(track) = self.track.as_mut() {
+            track.clear();
+            track.skip_before = Some(seconds(track.timebase, seeked.required_ts));
+        }
+        self.position = time;
+        Ok(())
+    }
+
+    fn open_audio_player(&mut self, index: Option<usize>) -> Result<AudioStatus, MediaError> {
+        let track = self.open_track(index, |_| Sink::Player(VecDeque::new()))?;
+        let status = track.status();
+        self.track = Some(track);
+        Ok(status)
+    }
+
+    fn open_audio_sampler(
+        &mut self, index: Option<usize>, per_second: f64
+    ) -> Result<AudioStatus, MediaError> {
+        let duration = self.duration();
+        let track = self.open_track(index, |start_time|
+            Sink::Sampler(Intensities::new(start_time, duration, per_second)))?;
+        let status = track.status();
+        self.track = Some(track);
+        Ok(status)
+    }
+
+    fn decode(&mut self, budget: Duration) -> Result<bool, MediaError> {
+        let start_time = Instant::now();
+        loop {
+            if start_time.elapsed() >= budget
+                && self.track.as_ref().is_none_or(|x| !x.is_empty())
+            {
+                return Ok(true);
+            }
+            if !self.step()? {
+                return Ok(false);
+            }
+        }
+    }
+
+    fn take_audio(&mut self) -> VecDeque<AudioBlock> {
+        match self.track.as_mut().map(|x| &mut x.sink) {
+            Some(Sink::Player(x)) => std::mem::take(x),
+            _ => VecDeque::new(),
+        }
+    }
+
+    fn take_audio_samples(&mut self) -> Option<SamplerDeltaData> {
+        match self.track.as_mut().map(|x| &mut x.sink) {
+            Some(Sink::Sampler(x)) => x.get_delta(),
+            _ => None,
+        }
+    }
+}
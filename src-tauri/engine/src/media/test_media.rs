@@ -0,0 +1,198 @@
+//! Synthetic clips for tutorials, tests and bug reports, so that none of
+//! them has to depend on someone's copyrighted video: ffmpeg's test pattern
+//! with the timecode burnt in, and a steady tone.
+
+use std::path::Path;
+
+use ffmpeg::{codec, encoder, filter, format, ChannelLayout, Frame, Packet};
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::media::{capabilities, frame::VideoData, internal::{check, MediaError}, units::Seconds};
+
+const SAMPLE_RATE: u32 = 48000;
+
+#[derive(Clone, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct TestMediaSpec {
+    pub duration: Seconds,
+    pub size: (u32, u32),
+    pub framerate: u32,
+    /// frequency of the tone in Hz; no audio stream if absent
+    pub tone: Option<f64>,
+}
+
+/// A filter graph made of a source, ending in a sink named `out`
+fn source_graph(spec: &str, sink: &str) -> Result<filter::Graph, MediaError> {
+    let mut graph = filter::Graph::new();
+    let sink = filter::find(sink).ok_or(
+        MediaError::InternalError(format!("filter not found: {sink}")))?;
+    check!(graph.add(&sink, "out", ""))?;
+    check!(check!(graph.input("out", 0))?.parse(spec))?;
+    check!(graph.validate())?;
+    Ok(graph)
+}
+
+/// The timecode needs `drawtext`, which needs a build with freetype and
+/// fontconfig; without it `testsrc`, which draws a frame counter of its
+/// own, has to do
+fn video_graph(spec: &TestMediaSpec) -> Result<filter::Graph, MediaError> {
+    let (width, height) = spec.size;
+    let (rate, duration) = (spec.framerate, spec.duration.0);
+    let source = format!("size={width}x{height}:rate={rate}:duration={duration}");
+    if capabilities::get().burnt_in_timecode {
+        let timecode = format!(
+            "testsrc2={source},drawtext=timecode='00\\:00\\:00\\:00':rate={rate}\
+             :fontsize={}:fontcolor=white:box=1:boxcolor=black@0.6:boxborderw=8\
+             :x=(w-tw)/2:y=h-th-{},format=yuv420p",
+            height / 10, height / 20);
+        match source_graph(&timecode, "buffersink") {
+            Ok(x) => return Ok(x),
+            Err(e) => warn!("test_media: drawtext unusable, falling back: {e}"),
+        }
+    }
+    source_graph(&format!("testsrc={source},format=yuv420p"), "buffersink")
+}
+
+fn audio_graph(spec: &TestMediaSpec, frequency: f64) -> Result<filter::Graph, MediaError> {
+    source_graph(&format!(
+        "sine=frequency={frequency}:sample_rate={SAMPLE_RATE}:duration={},\
+         aformat=sample_fmts=fltp:channel_layouts=mono",
+        spec.duration.0), "abuffersink")
+}
+
+struct Track {
+    graph: filter::Graph,
+    encoder: encoder::Encoder,
+    stream: usize,
+    /// of the last frame taken from the graph, in seconds
+    time: f64,
+    done: bool,
+}
+
+impl Track {
+    /// Encodes one more frame, or flushes the encoder at the end
+    fn step(&mut self, output: &mut format::context::Output) -> Result<(), MediaError> {
+        let mut sink = self.graph.get("out").unwrap();
+        let mut frame = Frame::empty();
+        match sink.sink().frame(&mut frame) {
+            Ok(()) => {
+                #[allow(clippy::cast_precision_loss)]
+                let pts = frame.pts().unwrap_or(0) as f64;
+                self.time = pts * f64::from(sink.sink().time_base());
+                check!(self.encoder.send_frame(&frame))?;
+            }
+            Err(ffmpeg::Error::Eof) => {
+                check!(self.encoder.send_eof())?;
+                self.done = true;
+            }
+            Err(e) => return check!(Err(e)),
+        }
+
+        let mut packet = Packet::empty();
+        while self.encoder.receive_packet(&mut packet).is_ok() {
+            let timebase = output.stream(self.stream).unwrap().time_base();
+            packet.set_stream(self.stream);
+            packet.rescale_ts(self.encoder.time_base(), timebase);
+            check!(packet.write_interleaved(output))?;
+        }
+        Ok(())
+    }
+}
+
+fn video_track(
+    spec: &TestMediaSpec, output: &mut format::context::Output
+) -> Result<Track, MediaError> {
+    let mut graph = video_graph(spec)?;
+    let timebase = graph.get("out").unwrap().sink().time_base();
+
+    let codec = encoder::find(codec::Id::MPEG4).ok_or(
+        MediaError::InternalError("encoder not found: MPEG4".to_owned()))?;
+    let mut context = check!(codec::Context::new_with_codec(codec).encoder().video())?;
+    context.set_width(spec.size.0);
+    context.set_height(spec.size.1);
+    context.set_format(format::Pixel::YUV420P);
+    context.set_time_base(timebase);
+    context.set_frame_rate(Some((i32::try_from(spec.framerate).unwrap_or(i32::MAX), 1)));
+    context.set_bit_rate(4_000_000);
+    if output.format().flags().contains(format::Flags::GLOBAL_HEADER) {
+        context.set_flags(codec::Flags::GLOBAL_HEADER);
+    }
+    let encoder = check!(context.open_as(codec))?;
+
+    let mut stream = check!(output.add_stream(codec))?;
+    stream.set_parameters(&encoder);
+    stream.set_time_base(timebase);
+    Ok(Track { graph, encoder: encoder.0.0, stream: stream.index(), time: 0.0, done: false })
+}
+
+fn audio_track(
+    spec: &TestMediaSpec, frequency: f64, output: &mut format::context::Output
+) -> Result<Track, MediaError> {
+    let mut graph = audio_graph(spec, frequency)?;
+    let timebase = graph.get("out").unwrap().sink().time_base();
+
+    let codec = encoder::find(codec::Id::AAC).ok_or(
+        MediaError::InternalError("encoder not found: AAC".to_owned()))?;
+    let mut context = check!(codec::Context::new_with_codec(codec).encoder().audio())?;
+    context.set_rate(i32::try_from(SAMPLE_RATE).unwrap());
+    context.set_channel_layout(ChannelLayout::MONO);
+    context.set_format(format::Sample::F32(format::sample::Type::Planar));
+    context.set_time_base(timebase);
+    context.set_bit_rate(128_000);
+    if output.format().flags().contains(format::Flags::GLOBAL_HEADER) {
+        context.set_flags(codec::Flags::GLOBAL_HEADER);
+    }
+    let encoder = check!(context.open_as(codec))?;
+    // the encoder takes whole frames of a fixed size
+    graph.get("out").unwrap().sink().set_frame_size(encoder.frame_size());
+
+    let mut stream = check!(output.add_stream(codec))?;
+    stream.set_parameters(&encoder);
+    stream.set_time_base(timebase);
+    Ok(Track { graph, encoder: encoder.0.0, stream: stream.index(), time: 0.0, done: false })
+}
+
+/// Writes the clip to `path`, in the container its extension calls for;
+/// `progress` returns `false` to give up
+pub fn generate(
+    spec: &TestMediaSpec, path: &Path, mut progress: impl FnMut(f64) -> bool
+) -> Result<(), MediaError> {
+    if !(spec.duration.0.is_finite() && spec.duration.0 > 0.0)
+        || spec.size.0 == 0 || spec.size.1 == 0 || spec.framerate == 0
+    {
+        return Err(MediaError::InternalError(format!("invalid spec: {spec:?}")));
+    }
+
+    let mut output = check!(format::output(&path))?;
+    let mut tracks = vec![video_track(spec, &mut output)?];
+    if let Some(frequency) = spec.tone {
+        tracks.push(audio_track(spec, frequency, &mut output)?);
+    }
+    check!(output.write_header())?;
+
+    // whichever is behind goes next, so that the streams come interleaved
+    while let Some(track) = tracks.iter_mut()
+        .filter(|x| !x.done)
+        .min_by(|a, b| a.time.total_cmp(&b.time))
+    {
+        track.step(&mut output)?;
+        if !progress((track.time / spec.duration.0).min(1.0)) {
+            return Err(MediaError::Cancelled);
+        }
+    }
+    check!(output.write_trailer())?;
+    debug!("test_media::generate: wrote {}", path.display());
+    Ok(())
+}
+
+/// The first frame of a source filter chain, such as
+/// `color=c=gray:size=320x240`, in gray; for tests of the analysing sinks
+/// on pictures made to order
+pub fn still(source: &str) -> Result<VideoData, MediaError> {
+    let mut graph = source_graph(&format!("{source},format=gray"), "buffersink")?;
+    let mut frame = VideoData::empty();
+    check!(graph.get("out").unwrap().sink().frame(&mut frame))?;
+    Ok(frame)
+}
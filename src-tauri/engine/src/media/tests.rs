@@ -0,0 +1,159 @@
+//! Sessions run against a clip made by `test_media`, so that positions can
+//! be checked against known values: frame `k` of the 25 fps fixture is at
+//! exactly `k / 25` seconds, and the tone has a constant amplitude.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::media::{audio::AudioSinkKind, session::Session, test_media, units::Seconds, video::{self, VideoSinkKind}};
+
+const FRAMERATE: u32 = 25;
+pub(super) const DURATION: f64 = 2.0;
+/// of ffmpeg's `sine` source
+const TONE_AMPLITUDE: f32 = 0.125;
+
+pub(super) fn fixture() -> &'static Path {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+    PATH.get_or_init(|| {
+        ffmpeg::init().unwrap();
+        let path = std::env::temp_dir()
+            .join(format!("subtle-fixture-{}.mkv", std::process::id()));
+        let spec = test_media::TestMediaSpec {
+            duration: Seconds(DURATION),
+            size: (320, 240),
+            framerate: FRAMERATE,
+            tone: Some(440.0),
+        };
+        test_media::generate(&spec, &path, |_| true).unwrap();
+        path
+    })
+}
+
+fn frame_time(k: u32) -> f64 {
+    f64::from(k) / f64::from(FRAMERATE)
+}
+
+fn video_session() -> Session {
+    let mut session = Session::create(fixture()).unwrap();
+    session.open_video_player(None, false).unwrap();
+    session
+}
+
+/// The next frame the video player gets, decoding as far as needed
+fn next_frame_time(session: &mut Session) -> Option<f64> {
+    loop {
+        session.try_process().unwrap();
+        if let Some((_, VideoSinkKind::Player(p))) = session.video_mut()
+            && let Some(frame) = p.get_delta().pop_front()
+        {
+            return Some(frame.meta.time.0);
+        }
+        if !session.try_feed().unwrap() {
+            return None;
+        }
+    }
+}
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 1e-3, "{actual} != {expected}");
+}
+
+#[test]
+fn status() {
+    let session = video_session();
+    assert_close(session.demuxer().duration().0, DURATION);
+    assert!(!session.is_audio_only());
+    assert_eq!(session.demuxer().describe_streams().len(), 2);
+}
+
+#[test]
+fn render_frame_at_exact_times() {
+    let mut session = video_session();
+    // the last few frames stay in the decoder at the end of the file
+    for k in [0, 1, 12, 25, 37] {
+        let frame = session.render_frame_at(Seconds(frame_time(k))).unwrap().unwrap();
+        assert_close(frame.meta.time.0, frame_time(k));
+    }
+}
+
+#[test]
+fn render_frame_at_rounds_to_nearest() {
+    let mut session = video_session();
+    for k in [3, 24, 30] {
+        let before = (f64::from(k) + 0.4) / f64::from(FRAMERATE);
+        let frame = session.render_frame_at(Seconds(before)).unwrap().unwrap();
+        assert_close(frame.meta.time.0, frame_time(k));
+
+        let after = (f64::from(k) + 0.6) / f64::from(FRAMERATE);
+        let frame = session.render_frame_at(Seconds(after)).unwrap().unwrap();
+        assert_close(frame.meta.time.0, frame_time(k + 1));
+    }
+}
+
+#[test]
+fn seek_then_step() {
+    let mut session = video_session();
+    // in either direction, and to where it already is
+    for k in [30, 10, 10, 0] {
+        let frame = session.render_frame_at(Seconds(frame_time(k))).unwrap().unwrap();
+        assert_close(frame.meta.time.0, frame_time(k));
+        for step in 1..=5 {
+            let time = next_frame_time(&mut session).unwrap();
+            assert_close(time, frame_time(k + step));
+        }
+    }
+}
+
+#[test]
+fn intensities_of_tone() {
+    let mut session = Session::create(fixture()).unwrap();
+    session.open_audio_sampler(None, 10.0).unwrap();
+    while session.try_feed().unwrap() {
+        session.try_process().unwrap();
+    }
+    let Some((_, AudioSinkKind::Sampler(s))) = session.audio_mut() else { unreachable!() };
+    let data = s.get_delta().unwrap();
+    assert_eq!(data.start_index, 0);
+    // AAC is lossy, and its priming and the end are left out
+    for (i, x) in data.intensity.iter().enumerate().take(15).skip(2) {
+        assert!((x - TONE_AMPLITUDE).abs() < 0.03, "[{i}] {x}");
+    }
+}
+
+#[test]
+fn audio_taps_match_sampler() {
+    let mut session = Session::create(fixture()).unwrap();
+    session.open_audio_player(None).unwrap();
+    session.add_audio_sampler_tap(10.0).unwrap();
+    while session.try_feed().unwrap() {
+        session.try_process().unwrap();
+    }
+    let Some(AudioSinkKind::Sampler(s)) = session.audio_taps_mut().first_mut() else {
+        unreachable!()
+    };
+    let tapped = s.get_delta().unwrap();
+
+    let mut session = Session::create(fixture()).unwrap();
+    session.open_audio_sampler(None, 10.0).unwrap();
+    while session.try_feed().unwrap() {
+        session.try_process().unwrap();
+    }
+    let Some((_, AudioSinkKind::Sampler(s))) = session.audio_mut() else { unreachable!() };
+    let sampled = s.get_delta().unwrap();
+    assert_eq!(tapped.intensity, sampled.intensity);
+}
+
+#[test]
+fn text_found_in_a_band_but_not_in_noise_or_flat() {
+    ffmpeg::init().unwrap();
+    let still = |source: &str| test_media::still(source).unwrap();
+    // strokes every 8 pixels across 16 rows near the bottom, like a line of
+    // white captions on black
+    let band = still("nullsrc=size=320x240,format=gray,\
+        geq=lum='if(between(Y,196,211)*lt(mod(X,8),3),235,16)'");
+    let noise = still("nullsrc=size=320x240,format=gray,geq=lum='random(1)*255'");
+    let flat = still("color=c=gray:size=320x240");
+    assert!(video::has_text(&band));
+    assert!(!video::has_text(&noise));
+    assert!(!video::has_text(&flat));
+}
@@ -0,0 +1,255 @@
+//! Milliseconds, frame numbers and SMPTE timecodes. Times are whole
+//! milliseconds and rates exact fractions, so conversions are done in
+//! integers: off-by-one frames come from floats rounding the wrong way.
+//!
+//! Frame `n` is on screen from the first millisecond at or after its exact
+//! start until the next frame's, so `frame_at(time_of_frame(n)) == n` at
+//! any rate up to 1000 fps.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Framerate {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl Framerate {
+    pub fn new(numerator: u32, denominator: u32) -> Result<Self, String> {
+        if numerator == 0 || denominator == 0 {
+            return Err(format!("invalid framerate: {numerator}/{denominator}"));
+        }
+        if u64::from(numerator) > 1000 * u64::from(denominator) {
+            return Err(format!("framerate above 1000 fps: {numerator}/{denominator}"));
+        }
+        Ok(Self { numerator, denominator })
+    }
+
+    /// The whole number of frames a timecode counts per second: 30 for
+    /// 29.97 fps
+    pub fn nominal(self) -> u32 {
+        ((self.numerator + self.denominator / 2) / self.denominator).max(1)
+    }
+
+    /// Drop-frame timecode is only defined for the NTSC rates, 30000/1001
+    /// and its multiples
+    pub fn supports_drop_frame(self) -> bool {
+        let nominal = self.nominal();
+        nominal.is_multiple_of(30)
+            && u64::from(self.numerator) * 1001 == u64::from(nominal) * 1000 * u64::from(self.denominator)
+    }
+
+    /// Frame numbers skipped at the start of each minute but every tenth
+    fn dropped_per_minute(self) -> u64 {
+        u64::from(self.nominal() / 15)
+    }
+}
+
+/// The frame on screen at `ms`; negative before the first
+pub fn frame_at(ms: i64, rate: Framerate) -> i64 {
+    let numerator = i128::from(ms) * i128::from(rate.numerator);
+    let denominator = 1000 * i128::from(rate.denominator);
+    i64::try_from(numerator.div_euclid(denominator)).unwrap()
+}
+
+/// The first whole millisecond at which `frame` is on screen
+pub fn time_of_frame(frame: i64, rate: Framerate) -> i64 {
+    let numerator = i128::from(frame) * 1000 * i128::from(rate.denominator);
+    let denominator = i128::from(rate.numerator);
+    // rounded up
+    i64::try_from(-(-numerator).div_euclid(denominator)).unwrap()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timecode {
+    /// not wrapped at 24
+    pub hours: u32,
+    pub minutes: u32,
+    pub seconds: u32,
+    pub frames: u32,
+    pub drop_frame: bool,
+}
+
+impl Timecode {
+    pub fn from_frame(frame: u64, rate: Framerate, drop_frame: bool) -> Result<Self, String> {
+        if drop_frame && !rate.supports_drop_frame() {
+            return Err(format!("no drop-frame timecode at {}/{}", rate.numerator, rate.denominator));
+        }
+        let nominal = u64::from(rate.nominal());
+        let mut label = frame;
+        if drop_frame {
+            let dropped = rate.dropped_per_minute();
+            let per_minute = nominal * 60 - dropped;
+            let per_ten_minutes = nominal * 600 - dropped * 9;
+            let (tens, rest) = (frame / per_ten_minutes, frame % per_ten_minutes);
+            // the first minute of ten keeps all its frame numbers
+            let minutes = if rest < nominal * 60 { 0 } else { (rest - dropped) / per_minute };
+            label += dropped * (9 * tens + minutes);
+        }
+        let narrow = |x: u64| u32::try_from(x).map_err(|_| format!("frame {frame} out of range"));
+        Ok(Self {
+            hours: narrow(label / (nominal * 3600))?,
+            minutes: narrow(label / (nominal * 60) % 60)?,
+            seconds: narrow(label / nominal % 60)?,
+            frames: narrow(label % nominal)?,
+            drop_frame,
+        })
+    }
+
+    pub fn to_frame(self, rate: Framerate) -> Result<u64, String> {
+        let nominal = rate.nominal();
+        if self.minutes >= 60 || self.seconds >= 60 || self.frames >= nominal {
+            return Err(format!("{self}: out of range at {nominal} fps"));
+        }
+        if self.drop_frame {
+            if !rate.supports_drop_frame() {
+                return Err(format!("no drop-frame timecode at {}/{}", rate.numerator, rate.denominator));
+            }
+            if self.seconds == 0 && !self.minutes.is_multiple_of(10)
+                && u64::from(self.frames) < rate.dropped_per_minute()
+            {
+                return Err(format!("{self}: dropped frame number"));
+            }
+        }
+        let nominal = u64::from(nominal);
+        let minutes = u64::from(self.hours) * 60 + u64::from(self.minutes);
+        let mut frame = (minutes * 60 + u64::from(self.seconds)) * nominal + u64::from(self.frames);
+        if self.drop_frame {
+            frame -= rate.dropped_per_minute() * (minutes - minutes / 10);
+        }
+        Ok(frame)
+    }
+}
+
+/// `HH:MM:SS:FF`, with `;` before the frames for drop-frame
+impl Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let separator = if self.drop_frame { ';' } else { ':' };
+        write!(f, "{:02}:{:02}:{:02}{separator}{:02}",
+            self.hours, self.minutes, self.seconds, self.frames)
+    }
+}
+
+impl FromStr for Timecode {
+    type Err = String;
+
+    /// Also takes `.` or `,` before the frames, as some tools write for
+    /// drop-frame
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid timecode: {s}");
+        let split = s.rfind([':', ';', '.', ',']).ok_or_else(invalid)?;
+        let drop_frame = !s[split..].starts_with(':');
+        let fields: Vec<u32> = s[..split].split(':')
+            .chain([&s[split + 1..]])
+            .map(|x| x.trim().parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        let [hours, minutes, seconds, frames] = fields[..] else { return Err(invalid()) };
+        Ok(Self { hours, minutes, seconds, frames, drop_frame })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// The common rates, and arbitrary ones
+    fn framerate() -> impl Strategy<Value = Framerate> {
+        prop_oneof![
+            Just((24000, 1001)), Just((24, 1)), Just((25, 1)), Just((30000, 1001)),
+            Just((30, 1)), Just((50, 1)), Just((60000, 1001)), Just((60, 1)),
+            (1..=1000u32, 1..=1001u32),
+        ]
+        .prop_filter_map("above 1000 fps", |(n, d)| Framerate::new(n, d).ok())
+    }
+
+    fn drop_framerate() -> impl Strategy<Value = Framerate> {
+        prop_oneof![Just(30000), Just(60000), Just(120_000)]
+            .prop_map(|n| Framerate::new(n, 1001).unwrap())
+    }
+
+    /// up to about 30 years in either direction
+    const EXTREME_MS: i64 = 1_000_000_000_000;
+
+    proptest! {
+        #[test]
+        fn frame_round_trip(frame in -EXTREME_MS..EXTREME_MS, rate in framerate()) {
+            prop_assert_eq!(frame_at(time_of_frame(frame, rate), rate), frame);
+        }
+
+        #[test]
+        fn time_is_within_frame(ms in -EXTREME_MS..EXTREME_MS, rate in framerate()) {
+            let frame = frame_at(ms, rate);
+            prop_assert!(time_of_frame(frame, rate) <= ms);
+            prop_assert!(time_of_frame(frame + 1, rate) > ms);
+        }
+
+        #[test]
+        fn frame_at_is_monotonic(ms in -EXTREME_MS..EXTREME_MS, rate in framerate()) {
+            prop_assert!(frame_at(ms + 1, rate) - frame_at(ms, rate) <= 1);
+            prop_assert!(frame_at(ms + 1, rate) >= frame_at(ms, rate));
+        }
+
+        #[test]
+        fn non_drop_round_trip(frame in 0..u64::from(u32::MAX), rate in framerate()) {
+            let timecode = Timecode::from_frame(frame, rate, false).unwrap();
+            prop_assert_eq!(timecode.to_frame(rate).unwrap(), frame);
+            prop_assert_eq!(timecode.to_string().parse::<Timecode>().unwrap(), timecode);
+        }
+
+        #[test]
+        fn drop_round_trip(frame in 0..u64::from(u32::MAX), rate in drop_framerate()) {
+            let timecode = Timecode::from_frame(frame, rate, true).unwrap();
+            prop_assert_eq!(timecode.to_frame(rate).unwrap(), frame);
+            prop_assert_eq!(timecode.to_string().parse::<Timecode>().unwrap(), timecode);
+        }
+
+        #[test]
+        fn drop_frame_skips_labels(frame in 0..u64::from(u32::MAX), rate in drop_framerate()) {
+            let timecode = Timecode::from_frame(frame, rate, true).unwrap();
+            let dropped = u32::try_from(rate.dropped_per_minute()).unwrap();
+            prop_assert!(!(timecode.seconds == 0 && !timecode.minutes.is_multiple_of(10)
+                && timecode.frames < dropped));
+        }
+
+        /// Drop-frame timecode follows the clock to within a frame or two
+        /// over a day; it only drifts by about 86 ms a day
+        #[test]
+        fn drop_frame_tracks_clock(frame in 0..2_500_000u64, rate in drop_framerate()) {
+            let timecode = Timecode::from_frame(frame, rate, true).unwrap();
+            let labelled = (u64::from(timecode.hours) * 3600 + u64::from(timecode.minutes) * 60
+                + u64::from(timecode.seconds)) * 1000
+                + u64::from(timecode.frames) * 1000 / u64::from(rate.nominal());
+            let actual = time_of_frame(i64::try_from(frame).unwrap(), rate);
+            prop_assert!((i64::try_from(labelled).unwrap() - actual).abs() < 200);
+        }
+    }
+
+    #[test]
+    fn drop_frame_known_values() {
+        let rate = Framerate::new(30000, 1001).unwrap();
+        let at = |frame| Timecode::from_frame(frame, rate, true).unwrap().to_string();
+        assert_eq!(at(0), "00:00:00;00");
+        assert_eq!(at(1799), "00:00:59;29");
+        assert_eq!(at(1800), "00:01:00;02");
+        assert_eq!(at(17982), "00:10:00;00");
+        assert_eq!(at(107_892), "01:00:00;00");
+        assert!("00:01:00;01".parse::<Timecode>().unwrap().to_frame(rate).is_err());
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert!(Framerate::new(0, 1).is_err());
+        assert!(Framerate::new(1001, 1).is_err());
+        assert!(!Framerate::new(25, 1).unwrap().supports_drop_frame());
+        assert!(Timecode::from_frame(0, Framerate::new(25, 1).unwrap(), true).is_err());
+        assert!("01:02:03".parse::<Timecode>().is_err());
+        assert!("00:00:00:25".parse::<Timecode>().unwrap()
+            .to_frame(Framerate::new(25, 1).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Reading exported files back, so that a bad deliverable is caught before
+//! it leaves the machine rather than after it's been uploaded.
+
+use ffmpeg::{codec, format};
+use serde::Serialize;
+
+use crate::media::{demux::StreamKind, internal::{check, MediaError}, subpicture::OwnedSubtitle, units::{self, Seconds}};
+
+/// How far the output's duration may be from what was expected
+const DURATION_TOLERANCE: f64 = 0.5;
+/// How far a cue may move; Matroska stores milliseconds
+const CUE_TOLERANCE: f64 = 0.01;
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    /// what was expected and what was found
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+#[ts(rename = "VerificationReport")]
+pub struct Report {
+    /// whether every check passed
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn new() -> Self {
+        Self { passed: true, checks: Vec::new() }
+    }
+
+    fn check(&mut self, name: impl Into<String>, passed: bool, detail: String) {
+        self.passed &= passed;
+        self.checks.push(Check { name: name.into(), passed, detail });
+    }
+}
+
+/// The cues of one subtitle track as found in a file
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cues {
+    pub count: usize,
+    /// packets the decoder refused
+    pub failed: usize,
+    pub first: Option<Seconds>,
+    pub last: Option<Seconds>,
+}
+
+/// What an exported media file should contain
+pub struct Expectation {
+    pub tracks: usize,
+    pub duration: Seconds,
+    /// in the order of the subtitle tracks in the file
+    pub subtitles: Vec<Cues>,
+}
+
+/// Decodes every subtitle track in `path`, noting when its first and last
+/// cues start
+pub fn read_cues(path: &str) -> Result<Vec<Cues>, MediaError> {
+    let mut input = check!(format::input(&path))?;
+    let mut tracks = Vec::new();
+    for stream in input.streams()
+        .filter(|x| x.parameters().medium() == StreamKind::Subtitle)
+    {
+        let context = check!(codec::Context::from_parameters(stream.parameters()))?;
+        let decoder = check!(context.decoder().subtitle())?;
+        tracks.push((stream.index(), stream.time_base(), decoder, Cues::default()));
+    }
+
+    for (stream, packet) in input.packets() {
+        let Some((_, timebase, decoder, cues)) =
+            tracks.iter_mut().find(|x| x.0 == stream.index()) else { continue };
+        let mut decoded = OwnedSubtitle(ffmpeg::Subtitle::new());
+        match decoder.decode(&packet, &mut decoded.0) {
+            Ok(_) => {
+                let Some(pts) = packet.pts() else { continue };
+                let time = units::Timestamp(pts).to_seconds(*timebase);
+                cues.count += 1;
+                if cues.first.is_none_or(|x| time < x) {
+                    cues.first = Some(time);
+                }
+                if cues.last.is_none_or(|x| time > x) {
+                    cues.last = Some(time);
+                }
+            }
+            Err(_) => cues.failed += 1,
+        }
+    }
+    Ok(tracks.into_iter().map(|x| x.3).collect())
+}
+
+fn same_time(a: Option<Seconds>, b: Option<Seconds>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => (a.0 - b.0).abs() <= CUE_TOLERANCE,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn describe(time: Option<Seconds>) -> String {
+    time.map_or("none".to_owned(), |x| x.to_string())
+}
+
+/// Checks that the streams are all there, that the duration is right, and
+/// that every subtitle track decodes and still starts and ends where it did
+pub fn verify_media(path: &str, expected: &Expectation) -> Result<Report, MediaError> {
+    let input = check!(format::input(&path))?;
+    let mut report = Report::new();
+
+    let tracks = input.nb_streams() as usize;
+    report.check("tracks", tracks == expected.tracks,
+        format!("expected {}, found {tracks}", expected.tracks));
+
+    let duration = units::Timestamp(input.duration()).to_seconds(units::DEFAULT_TIMEBASE);
+    report.check("duration",
+        (duration.0 - expected.duration.0).abs() <= DURATION_TOLERANCE,
+        format!("expected {}, found {duration}", expected.duration));
+    drop(input);
+
+    let found = read_cues(path)?;
+    report.check("subtitle tracks", found.len() == expected.subtitles.len(),
+        format!("expected {}, found {}", expected.subtitles.len(), found.len()));
+    for (i, (found, expected)) in found.iter().zip(&expected.subtitles).enumerate() {
+        let n = i + 1;
+        report.check(format!("subtitle track {n} decodable"), found.failed == 0,
+            format!("{} of {} cues failed to decode", found.failed, found.count + found.failed));
+        report.check(format!("subtitle track {n} cue count"), found.count == expected.count,
+            format!("expected {}, found {}", expected.count, found.count));
+        report.check(format!("subtitle track {n} first cue"),
+            same_time(found.first, expected.first),
+            format!("expected at {}, found at {}", describe(expected.first), describe(found.first)));
+        report.check(format!("subtitle track {n} last cue"),
+            same_time(found.last, expected.last),
+            format!("expected at {}, found at {}", describe(expected.last), describe(found.last)));
+    }
+    Ok(report)
+}
+
+/// Checks that each image opens and holds a picture
+pub fn verify_images(paths: &[String]) -> Report {
+    let mut report = Report::new();
+    for path in paths {
+        let result = format::input(path).ok().and_then(|input| {
+            let stream = input.streams().best(StreamKind::Video)?;
+            let context = codec::Context::from_parameters(stream.parameters()).ok()?;
+            let decoder = context.decoder().video().ok()?;
+            Some((decoder.width(), decoder.height()))
+        });
+        match result {
+            Some((w, h)) => report.check(path.clone(), w > 0 && h > 0, format!("{w}x{h}")),
+            None => report.check(path.clone(), false, "not a readable image".to_owned()),
+        }
+    }
+    report
+}
@@ -0,0 +1,770 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use enum_dispatch::enum_dispatch;
+use ffmpeg::{codec, decoder, error::EAGAIN, format, software::scaling, Rescale};
+use getset::{CopyGetters, Getters};
+use log::{debug, warn};
+
+use crate::media::{accel, color, damage::Damage, demux, disjoint_interval_set::DisjointIntervalSet, frame, internal::{check, MediaError}, units::{self, Seconds}};
+
+use ordered_float::OrderedFloat;
+type Of64 = OrderedFloat<f64>;
+
+#[derive(Getters, CopyGetters)]
+pub struct Decoder {
+    inner: codec::decoder::Video,
+    accelerator: Option<accel::HardwareDecoder>,
+
+    #[getset(get = "pub")]
+    stream_info: demux::StreamInfo,
+
+    #[getset(get_copy = "pub")]
+    sample_aspect_ratio: units::Rational,
+
+    #[getset(get_copy = "pub")]
+    original_size: (u32, u32),
+
+    #[getset(get_copy = "pub")]
+    is_vfr: bool,
+
+    /// will be inaccurate in case of VFR
+    #[getset(get_copy = "pub")]
+    framerate: units::Rational,
+
+    /// since the decoder was opened
+    #[getset(get = "pub")]
+    damage: Damage,
+}
+
+impl Decoder {
+    #[tracing::instrument(name = "video::open", skip_all)]
+    pub fn create(
+        demuxer: &demux::Demuxer, index: Option<usize>, accel: bool
+    ) -> Result<Decoder, MediaError> {
+        let (stream_info, stream) = match index {
+            Some(i) => demuxer.get_stream_from_index(i),
+            None => demuxer.get_stream_from_kind(demux::StreamKind::Video)
+        }?;
+        let index = stream_info.index();
+
+        let codec = decoder::find(stream.parameters().id()).ok_or(
+            MediaError::InternalError(
+                format!("codec not found: {:?}", stream.parameters().id()),
+        ))?;
+
+        // create decoder
+        let mut decoder_ctx = codec::Context::new_with_codec(codec).decoder();
+
+        decoder_ctx.set_threading(codec::threading::Config { 
+            kind: codec::threading::Type::Frame, 
+            count: num_cpus::get()
+        });
+
+        debug!(
+            "video::Decoder::create: codec = {:?}, using {} threads", 
+            decoder_ctx.codec().map(|x| x.id()),
+            num_cpus::get()
+        );
+
+        let accelerator_name = 
+            if !accel { "" }
+            else if cfg!(windows) { "d3d11va" }
+            else if cfg!(target_os = "macos") { "videotoolbox" }
+            else { "" };
+        let accelerator = 
+            if accel::HardwareDecoder::available_types()
+                .iter().any(|x| x == accelerator_name)
+            {
+                match accel::HardwareDecoder::create(accelerator_name, &mut decoder_ctx) {
+                    Ok(x) => {
+                        debug!("video::Decoder::create: using accelerator: {}", x.name());
+                        Some(x)
+                    },
+                    Err(e) => {
+                        debug!("video::Decoder::create: error creating accelerator: {e}, falling back");
+                        None
+                    }
+                }
+            } else { None };
+        
+        check!(decoder_ctx.set_parameters(stream.parameters()))?; // avcodec_parameters_to_context
+        let decoder = check!(decoder_ctx.video())?;            // avcodec_open2
+
+        let sample_aspect_ratio = match decoder.aspect_ratio() {
+            units::Rational(0, _) => units::Rational(1, 1),
+            x => if f64::from(x) <= 0.0 { units::Rational(1, 1) } else { x  }
+        };
+
+        if sample_aspect_ratio != units::Rational(1, 1) {
+            debug!("video::Decoder::create: [{index}] note: video has an SAR of {sample_aspect_ratio}");
+        }
+
+        let avg_framerate = stream.avg_frame_rate(); // avg_frame_rate
+        let framerate = stream.rate();               // r_frame_rate
+        let is_vfr = framerate != avg_framerate;
+        if is_vfr {
+            warn!("video::Decoder::create: [{index}] note: stream is VFR, avg={avg_framerate}, rate={framerate}");
+        }
+
+        debug!(
+            "video::Decoder::create: [{}] {:?}; decoder_fr={:?}",
+            stream_info.index(),
+            decoder.format(), decoder.frame_rate()
+        );
+
+        Ok(Decoder {
+            stream_info,
+            framerate,
+            is_vfr,
+            original_size: (decoder.width(), decoder.height()),
+            sample_aspect_ratio,
+            inner: decoder, accelerator,
+            damage: Damage::default(),
+        })
+    }
+
+    pub fn is_accelerated(&self) -> bool {
+        self.accelerator.is_some()
+    }
+
+    pub fn flush(&mut self) {
+        self.inner.flush();
+        self.stream_info.byte_pos_can_update = true;
+        self.stream_info.byte_pos = -1;
+    }
+
+    #[tracing::instrument(name = "video::feed", skip_all)]
+    pub fn feed(&mut self, packet: &demux::Packet) -> Result<(), MediaError> {
+        if self.stream_info.byte_pos_can_update {
+            self.stream_info.byte_pos = packet.position();
+        }
+        match self.inner.send_packet(packet) {
+            Ok(()) => Ok(()),
+            Err(ffmpeg_next::Error::Other { errno: EAGAIN }) => {
+                warn!("video::Decoder::feed: some frames haven't been read! flushing.");
+                self.flush();
+                // resend packet
+                self.feed(packet)
+            }
+            // damaged in the file; decoding goes on with the next
+            Err(ffmpeg_next::Error::InvalidData) => {
+                let time = units::Timestamp(packet.pts().or(packet.dts()).unwrap_or(0))
+                    .to_seconds(self.stream_info.timebase());
+                debug!("video::Decoder::feed: [{}] invalid packet at {time}", self.stream_info.index());
+                self.damage.record_error(time);
+                Ok(())
+            }
+            send_packet_error => check!(send_packet_error),
+        }
+    }
+
+    #[tracing::instrument(name = "video::receive", skip_all)]
+    pub fn try_receive(&mut self) -> Result<Option<frame::Video>, MediaError> {
+        let mut decoded = frame::VideoData::empty();
+        let mut byte_pos: isize = -1;
+        match self.inner.receive_frame(&mut decoded) {
+            Ok(()) => {
+                byte_pos = self.stream_info.byte_pos;
+                self.stream_info.byte_pos_can_update = true;
+            }
+            Err(ffmpeg_next::Error::Other { errno: EAGAIN }) => {
+                // trace!("receive: EAGAIN");
+                return Ok(None);
+            }
+            receive_frame_error => check!(receive_frame_error)?,
+        }
+
+        let time = units::Timestamp(
+            decoded
+            .pts()
+            // fall back to packet's DTS if no pts available (as in AVI)
+            .unwrap_or(decoded.packet().dts)
+        ).to_seconds(self.stream_info.timebase());
+        if decoded.flags().contains(ffmpeg_next::frame::Flags::CORRUPT) {
+            self.damage.record_corrupt(time);
+        }
+
+        if self.accelerator.is_some() {
+            let mut sw_frame = frame::VideoData::empty();
+            check!(accel::HardwareDecoder::transfer_frame(&decoded, &mut sw_frame))?;
+            decoded = sw_frame;
+        }
+
+        Ok(Some(frame::Video {
+            meta: frame::FrameMetadata {
+                time, byte_pos,
+                pkt_pos: decoded.packet().position,
+            },
+            decoded,
+        }))
+    }
+}
+
+#[enum_dispatch]
+pub enum VideoSinkKind {
+    Player,
+    Sampler,
+    TextDetector,
+    CropDetector,
+    SceneDetector
+}
+
+impl VideoSinkKind {
+    /// Time of the last frame an analysing sink has seen
+    pub fn analysed_until(&self) -> Option<Seconds> {
+        match self {
+            VideoSinkKind::TextDetector(x) => x.last_time(),
+            VideoSinkKind::CropDetector(x) => x.last_time(),
+            VideoSinkKind::SceneDetector(x) => x.last_time(),
+            VideoSinkKind::Player(_) | VideoSinkKind::Sampler(_) => None,
+        }
+    }
+}
+
+#[enum_dispatch(VideoSinkKind)]
+pub trait VideoSink {
+    fn clear(&mut self);
+    fn is_empty(&self) -> bool;
+    fn process(&mut self, frame: frame::Video) -> Result<(), MediaError>;
+}
+
+pub struct Player {
+    original_format: format::Pixel,
+    original_size: (u32, u32),
+    output_size: (u32, u32),
+    scaling_method: scaling::Flags,
+    scaler: scaling::Context,
+    /// set by `set_color_management`
+    display: Option<color::DisplayProfile>,
+    /// for the frames coming in; `None` in the transform when it would
+    /// change nothing
+    managed: Option<(color::SourceColor, Option<color::Transform>)>,
+
+    frames: VecDeque<frame::Video>
+}
+
+impl VideoSink for Player {
+    fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    #[tracing::instrument(name = "video::scale", skip_all)]
+    fn process(&mut self, mut frame: frame::Video) -> Result<(), MediaError> {
+        if frame.decoded.format() != self.original_format {
+            warn!("decoded format is actually {:?}", frame.decoded.format());
+            self.original_format = frame.decoded.format();
+            self.create_scaler()?;
+        }
+        if let Some(display) = &self.display {
+            let source = color::SourceColor::of(&frame.decoded);
+            if self.managed.as_ref().is_none_or(|(x, _)| *x != source) {
+                debug!("color management: {source:?}");
+                self.managed = Some((source, color::Transform::new(source.primaries, display)));
+                self.create_scaler()?;
+            }
+        }
+
+        // av_frame_alloc
+        let mut processed = frame::VideoData::empty();
+        // sws_scale
+        check!(self.scaler.run(&frame.decoded, &mut processed))?;
+        if let Some((_, Some(transform))) = &self.managed {
+            transform.apply(&mut processed);
+        }
+        frame.decoded = processed;
+        self.frames.push_back(frame);
+        Ok(())
+    }
+}
+
+impl Player {
+    pub fn create(decoder: &Decoder) -> Result<Self, MediaError> {
+        let format = if decoder.accelerator.as_ref().is_some() {
+            format::Pixel::NV12 // TODO: I just guessed one
+        } else {
+            decoder.inner.format()
+        };
+
+        let (w, h) = (decoder.inner.width(), decoder.inner.height());
+        let scaling_method = scaling::Flags::FAST_BILINEAR;
+
+        Ok(Self {
+            original_format: format,
+            original_size: (w, h),
+            output_size: (w, h),
+            scaling_method,
+            scaler: check!(scaling::Context::get(
+                format, w, h,
+                format::Pixel::RGBA,
+                decoder.inner
+                    .width()
+                    .rescale(units::Rational(1, 1), decoder.sample_aspect_ratio())
+                    .try_into()
+                    .unwrap(),
+                h,
+                scaling_method,
+            ))?,
+            display: None,
+            managed: None,
+            frames: VecDeque::new()
+        })
+    }
+
+    pub fn get_delta(&mut self) -> VecDeque<frame::Video> {
+        std::mem::take(&mut self.frames)
+    }
+
+    /// Removes the frames due by `time` and returns the newest of them
+    pub fn take_due(&mut self, time: Seconds) -> Option<frame::Video> {
+        let mut due = None;
+        while self.frames.front().is_some_and(|x| x.meta.time <= time) {
+            due = self.frames.pop_front();
+        }
+        due
+    }
+
+    pub fn output_size(&self) -> (u32, u32) {
+        self.output_size
+    }
+
+    pub fn is_color_managed(&self) -> bool {
+        self.display.is_some()
+    }
+
+    /// The display the frames are converted for, unless the main one
+    pub fn color_display(&self) -> Option<color::DisplayAt> {
+        self.display.as_ref().and_then(|x| x.at)
+    }
+
+    /// Converts the frames from here on for `display`; see `color`. `None`
+    /// goes back to swscale's defaults.
+    pub fn set_color_management(
+        &mut self, display: Option<color::DisplayProfile>
+    ) -> Result<(), MediaError> {
+        self.display = display;
+        self.managed = None;
+        self.create_scaler()
+    }
+
+    pub fn set_output_size(&mut self, size: (u32, u32)) -> Result<(), MediaError> {
+        if self.output_size == size {
+            return Ok(());
+        }
+
+        self.output_size = size;
+        self.create_scaler()?;
+        debug!("set_output_size: {:?}, format {:?}", size, self.original_format);
+        Ok(())
+    }
+
+    fn create_scaler(&mut self) -> Result<(), MediaError> {
+        self.scaler = check!(scaling::Context::get(
+            self.original_format,
+            self.original_size.0,
+            self.original_size.1,
+            format::Pixel::RGBA,
+            self.output_size.0,
+            self.output_size.1,
+            self.scaling_method,
+        ))?;
+        if let Some((source, _)) = &self.managed {
+            color::set_colorspace(&mut self.scaler, *source);
+        }
+        Ok(())
+    }
+}
+
+pub struct Sampler {
+    keyframes: BTreeMap<Of64, isize>,
+    known_range: DisjointIntervalSet,
+    data: Option<SamplerDeltaData>
+}
+
+#[derive(Clone, serde::Serialize, Debug, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+#[ts(rename = "VideoSamplerDeltaData")]
+pub struct SamplerDeltaData {
+    pub keyframes: Vec<(Seconds, isize)>,
+    pub start_time: Seconds,
+    pub end_time: Seconds
+}
+
+impl VideoSink for Sampler {
+    fn clear(&mut self) {
+        self.data = None;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_none()
+    }
+
+    fn process(&mut self, frame: frame::Video) -> Result<(), MediaError> {
+        if self.data.is_none() {
+            self.data = Some(SamplerDeltaData {
+                keyframes: Vec::new(),
+                start_time: frame.meta.time,
+                end_time: frame.meta.time
+            });
+        }
+
+        let sd = self.data.as_mut().unwrap();
+        if frame.decoded.is_key() {
+            sd.keyframes.push((frame.meta.time, frame.meta.byte_pos));
+            self.keyframes.insert(OrderedFloat(frame.meta.time.0), frame.meta.byte_pos);
+        }
+        if frame.meta.time > sd.end_time {
+            sd.end_time = frame.meta.time;
+            self.known_range.add(sd.start_time.0, sd.end_time.0);
+        }
+        Ok(())
+    }
+}
+
+impl Sampler {
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn create(_decoder: &Decoder) -> Result<Self, MediaError> {
+        Ok(Self {
+            keyframes: BTreeMap::new(),
+            known_range: DisjointIntervalSet::new(),
+            data: None
+        })
+    }
+
+    pub fn get_delta(&mut self) -> Option<SamplerDeltaData> {
+        std::mem::take(&mut self.data)
+    }
+
+    /// Times of the keyframes decoded so far, in order
+    pub fn keyframes(&self) -> impl Iterator<Item = Seconds> + '_ {
+        self.keyframes.keys().map(|x| Seconds(x.0))
+    }
+
+    pub fn get_keyframe_before(&self, time: Seconds) -> Option<(Seconds, isize)> {
+        let time = OrderedFloat(time.0);
+        if let Some((&OrderedFloat(k), &pos)) = 
+            self.keyframes.range(..=time).next_back()
+        {
+            if self.known_range.covers_range(k, *time) {
+                Some((Seconds(k), pos))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+}
+/// Width frames are scaled down to before being analysed
+const ANALYSIS_WIDTH: u32 = 320;
+
+/// Converts frames to small grayscale pictures for the analysing sinks,
+/// keeping the display aspect ratio
+struct GrayScaler {
+    original_format: format::Pixel,
+    original_size: (u32, u32),
+    size: (u32, u32),
+    context: scaling::Context,
+}
+
+impl GrayScaler {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn create(decoder: &Decoder) -> Result<Self, MediaError> {
+        let (w, h) = decoder.original_size();
+        let display_width = f64::from(w) * f64::from(decoder.sample_aspect_ratio());
+        let height = (f64::from(ANALYSIS_WIDTH) * f64::from(h) / display_width)
+            .round().max(4.0) as u32;
+        let size = (ANALYSIS_WIDTH, height);
+        let format = decoder.inner.format();
+        Ok(Self {
+            original_format: format,
+            original_size: (w, h),
+            size,
+            context: check!(scaling::Context::get(
+                format, w, h,
+                format::Pixel::GRAY8,
+                size.0, size.1,
+                scaling::Flags::FAST_BILINEAR,
+            ))?,
+        })
+    }
+
+    fn run(&mut self, decoded: &frame::VideoData) -> Result<frame::VideoData, MediaError> {
+        if decoded.format() != self.original_format {
+            self.original_format = decoded.format();
+            self.context = check!(scaling::Context::get(
+                self.original_format,
+                self.original_size.0,
+                self.original_size.1,
+                format::Pixel::GRAY8,
+                self.size.0,
+                self.size.1,
+                scaling::Flags::FAST_BILINEAR,
+            ))?;
+        }
+        let mut gray = frame::VideoData::empty();
+        check!(self.context.run(decoded, &mut gray))?;
+        Ok(gray)
+    }
+}
+
+/// Luma difference between neighbouring pixels that counts as an edge
+const EDGE_THRESHOLD: u8 = 48;
+/// Fraction of edge pixels above which a row is considered part of text
+const ROW_EDGE_DENSITY: f64 = 0.08;
+/// Detections closer than this are merged into one interval
+const MERGE_GAP: f64 = 0.5;
+/// Intervals shorter than this are considered noise
+const MIN_DURATION: f64 = 0.5;
+
+/// Finds when burned-in text (credits, signs) occupies the bottom quarter of
+/// the picture. This is a heuristic standing in for a text-detection model,
+/// not a model: rendered text shows up as a horizontal band of rows dense
+/// with sharp luma edges, which natural footage rarely produces across
+/// several rows in a row. Fine regular texture, like a fence or a striped
+/// shirt, can pass for text, and soft or faint text can be missed.
+pub struct TextDetector {
+    scaler: GrayScaler,
+
+    /// ongoing detection, `(start, last seen)`
+    current: Option<(Seconds, Seconds)>,
+    intervals: Vec<(Seconds, Seconds)>,
+    last_time: Option<Seconds>,
+}
+
+impl VideoSink for TextDetector {
+    fn clear(&mut self) {
+        self.current = None;
+        self.intervals.clear();
+        self.last_time = None;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.last_time.is_none()
+    }
+
+    fn process(&mut self, frame: frame::Video) -> Result<(), MediaError> {
+        let gray = self.scaler.run(&frame.decoded)?;
+        let time = frame.meta.time;
+        self.last_time = Some(time);
+        if !has_text(&gray) {
+            return Ok(());
+        }
+        match &mut self.current {
+            Some((_, last)) if time.0 - last.0 <= MERGE_GAP => *last = time,
+            _ => {
+                self.end_current();
+                self.current = Some((time, time));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TextDetector {
+    pub fn create(decoder: &Decoder) -> Result<Self, MediaError> {
+        Ok(Self {
+            scaler: GrayScaler::create(decoder)?,
+            current: None,
+            intervals: Vec::new(),
+            last_time: None,
+        })
+    }
+
+    fn end_current(&mut self) {
+        if let Some((start, last)) = self.current.take()
+            && last.0 - start.0 >= MIN_DURATION
+        {
+            self.intervals.push((start, last));
+        }
+    }
+
+    pub fn last_time(&self) -> Option<Seconds> {
+        self.last_time
+    }
+
+    /// Takes the intervals found so far, including one still ongoing
+    pub fn get_delta(&mut self) -> Vec<(Seconds, Seconds)> {
+        self.end_current();
+        std::mem::take(&mut self.intervals)
+    }
+}
+
+/// Looks for at least 3 consecutive edge-dense rows in the bottom quarter of
+/// a gray frame, but not so many that the whole area is just busy texture
+pub(super) fn has_text(gray: &frame::VideoData) -> bool {
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    let (data, stride) = (gray.data(0), gray.stride(0));
+    let band = (height * 3 / 4)..height;
+    let band_height = band.len();
+
+    let mut dense_rows = 0;
+    let mut run = 0;
+    let mut longest_run = 0;
+    for y in band {
+        let row = &data[y * stride..y * stride + width];
+        let edges = row.windows(2)
+            .filter(|x| x[0].abs_diff(x[1]) > EDGE_THRESHOLD)
+            .count();
+        #[allow(clippy::cast_precision_loss)]
+        let density = edges as f64 / width as f64;
+        if density > ROW_EDGE_DENSITY {
+            dense_rows += 1;
+            run += 1;
+            longest_run = longest_run.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    longest_run >= 3 && dense_rows * 10 < band_height * 8
+}
+
+/// Luma at or below which a row counts as black
+const BLACK_THRESHOLD: u8 = 32;
+
+/// Measures letterbox bars: the black rows at the top and bottom that every
+/// analysed frame has. Frames that are black as a whole tell nothing and are
+/// ignored.
+pub struct CropDetector {
+    scaler: GrayScaler,
+    /// in analysis rows; `None` before the first useful frame
+    bars: Option<(u32, u32)>,
+    last_time: Option<Seconds>,
+}
+
+impl VideoSink for CropDetector {
+    fn clear(&mut self) {
+        self.bars = None;
+        self.last_time = None;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.last_time.is_none()
+    }
+
+    fn process(&mut self, frame: frame::Video) -> Result<(), MediaError> {
+        let gray = self.scaler.run(&frame.decoded)?;
+        self.last_time = Some(frame.meta.time);
+
+        let (width, height) = (self.scaler.size.0 as usize, self.scaler.size.1);
+        let (data, stride) = (gray.data(0), gray.stride(0));
+        let is_black = |y: u32| {
+            let start = y as usize * stride;
+            data[start..start + width].iter().all(|&x| x <= BLACK_THRESHOLD)
+        };
+        let top = (0..height).take_while(|&y| is_black(y)).count();
+        if top == height as usize {
+            return Ok(());
+        }
+        let bottom = (0..height).rev().take_while(|&y| is_black(y)).count();
+        let (top, bottom) = (u32::try_from(top).unwrap(), u32::try_from(bottom).unwrap());
+        self.bars = Some(match self.bars {
+            Some((t, b)) => (t.min(top), b.min(bottom)),
+            None => (top, bottom),
+        });
+        Ok(())
+    }
+}
+
+impl CropDetector {
+    pub fn create(decoder: &Decoder) -> Result<Self, MediaError> {
+        Ok(Self {
+            scaler: GrayScaler::create(decoder)?,
+            bars: None,
+            last_time: None,
+        })
+    }
+
+    pub fn last_time(&self) -> Option<Seconds> {
+        self.last_time
+    }
+
+    /// `(top, bottom)` bar heights in pixels of the original frame
+    pub fn bars(&self) -> Option<(u32, u32)> {
+        let (top, bottom) = self.bars?;
+        let (h, analysis_h) = (self.scaler.original_size.1, self.scaler.size.1);
+        // round inwards, so that a bar never covers picture
+        Some((top * h / analysis_h, bottom * h / analysis_h))
+    }
+}
+
+/// Bins of the luma histograms compared between frames
+const HISTOGRAM_BINS: usize = 64;
+/// Histogram difference, from 0 to 1, above which two frames belong to
+/// different shots
+const SCENE_THRESHOLD: f64 = 0.4;
+
+/// Finds shot changes by comparing the luma histograms of consecutive
+/// frames. Histograms ignore motion within a shot, so only cuts and the
+/// end of fast fades stand out.
+pub struct SceneDetector {
+    scaler: GrayScaler,
+    previous: Option<[u32; HISTOGRAM_BINS]>,
+    cuts: Vec<Seconds>,
+    last_time: Option<Seconds>,
+}
+
+impl VideoSink for SceneDetector {
+    fn clear(&mut self) {
+        self.previous = None;
+        self.cuts.clear();
+        self.last_time = None;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.last_time.is_none()
+    }
+
+    fn process(&mut self, frame: frame::Video) -> Result<(), MediaError> {
+        let gray = self.scaler.run(&frame.decoded)?;
+        self.last_time = Some(frame.meta.time);
+
+        let (width, height) = (self.scaler.size.0 as usize, self.scaler.size.1 as usize);
+        let (data, stride) = (gray.data(0), gray.stride(0));
+        let mut histogram = [0u32; HISTOGRAM_BINS];
+        for y in 0..height {
+            for &x in &data[y * stride..y * stride + width] {
+                histogram[x as usize * HISTOGRAM_BINS / 256] += 1;
+            }
+        }
+
+        if let Some(previous) = &self.previous {
+            let difference: u32 = histogram.iter().zip(previous)
+                .map(|(a, b)| a.abs_diff(*b))
+                .sum();
+            // each moved pixel is counted once leaving a bin and once entering
+            #[allow(clippy::cast_precision_loss)]
+            let fraction = f64::from(difference) / (2 * width * height) as f64;
+            if fraction > SCENE_THRESHOLD {
+                self.cuts.push(frame.meta.time);
+            }
+        }
+        self.previous = Some(histogram);
+        Ok(())
+    }
+}
+
+impl SceneDetector {
+    pub fn create(decoder: &Decoder) -> Result<Self, MediaError> {
+        Ok(Self {
+            scaler: GrayScaler::create(decoder)?,
+            previous: None,
+            cuts: Vec::new(),
+            last_time: None,
+        })
+    }
+
+    pub fn last_time(&self) -> Option<Seconds> {
+        self.last_time
+    }
+
+    /// Takes the shot changes found so far, as the times of the first frame
+    /// of each new shot
+    pub fn get_delta(&mut self) -> Vec<Seconds> {
+        std::mem::take(&mut self.cuts)
+    }
+}
@@ -0,0 +1,81 @@
+//! Waveform peaks in the formats of BBC's audiowaveform, which peaks.js and
+//! other web players read, so that the analysis doesn't have to be done
+//! again elsewhere. Both are version 2 with a single channel and 16-bit
+//! values: the binary `.dat`, a little-endian header followed by a min/max
+//! pair per pixel, and the same as JSON.
+
+use serde::Deserialize;
+
+use crate::media::internal::MediaError;
+
+#[derive(Clone, Copy, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum WaveformFormat {
+    Dat,
+    Json,
+}
+
+pub struct Waveform {
+    pub sample_rate: u32,
+    pub samples_per_pixel: u32,
+    /// minimum and maximum of each pixel, in -1..1
+    pub peaks: Vec<(f32, f32)>,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn quantize(x: f32) -> i16 {
+    (x.clamp(-1.0, 1.0) * f32::from(i16::MAX)).round() as i16
+}
+
+impl Waveform {
+    fn length(&self) -> Result<u32, MediaError> {
+        u32::try_from(self.peaks.len()).map_err(|_|
+            MediaError::InternalError("waveform too long".to_owned()))
+    }
+
+    pub fn to_dat(&self) -> Result<Vec<u8>, MediaError> {
+        let header = [
+            2,  // version
+            0,  // flags; 16-bit values
+            self.sample_rate,
+            self.samples_per_pixel,
+            self.length()?,
+            1,  // channels
+        ];
+        let mut data = Vec::with_capacity(header.len() * 4 + self.peaks.len() * 4);
+        for x in header {
+            data.extend_from_slice(&x.to_le_bytes());
+        }
+        for &(min, max) in &self.peaks {
+            data.extend_from_slice(&quantize(min).to_le_bytes());
+            data.extend_from_slice(&quantize(max).to_le_bytes());
+        }
+        Ok(data)
+    }
+
+    pub fn to_json(&self) -> Result<String, MediaError> {
+        let data: Vec<i16> = self.peaks.iter()
+            .flat_map(|&(min, max)| [quantize(min), quantize(max)])
+            .collect();
+        let json = serde_json::json!({
+            "version": 2,
+            "channels": 1,
+            "sample_rate": self.sample_rate,
+            "samples_per_pixel": self.samples_per_pixel,
+            "bits": 16,
+            "length": self.length()?,
+            "data": data,
+        });
+        Ok(json.to_string())
+    }
+
+    pub fn write(&self, path: &std::path::Path, format: WaveformFormat) -> Result<(), MediaError> {
+        let data = match format {
+            WaveformFormat::Dat => self.to_dat()?,
+            WaveformFormat::Json => self.to_json()?.into_bytes(),
+        };
+        std::fs::write(path, data)
+            .map_err(|e| MediaError::InternalError(e.to_string()))
+    }
+}
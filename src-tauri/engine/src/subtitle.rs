@@ -0,0 +1,27 @@
+pub mod document;
+pub mod query;
+pub mod search;
+pub mod interval;
+pub mod edit;
+pub mod split;
+pub mod split_preview;
+pub mod merge;
+pub mod lead;
+pub mod retime;
+pub mod journal;
+pub mod safe_area;
+pub mod positioning;
+pub mod markers;
+pub mod regions;
+pub mod takes;
+pub mod words;
+pub mod align;
+pub mod chapters;
+pub mod qc;
+pub mod ass;
+pub mod sami;
+pub mod microdvd;
+pub mod srt;
+pub mod repair;
+pub mod convert;
+pub mod parse;
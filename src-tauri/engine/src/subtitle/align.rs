@@ -0,0 +1,168 @@
+//! Word timing for one event, for when its text has been edited and the
+//! words it kept from a transcript no longer match it. The recognizer is
+//! run again on the event's audio, and the words of the new text it heard
+//! take their times and confidence from it. The ones it didn't hear, or
+//! heard differently, are laid over the stretches of speech between them in
+//! proportion to their length, so that pauses fall between words rather
+//! than inside them, and keep the confidence they had before if they were
+//! there.
+
+use crate::media::units::Seconds;
+use crate::subtitle::ass;
+use crate::subtitle::document::Event;
+use crate::subtitle::words::Word;
+
+/// Pauses shorter than this are inside a word, not between two
+pub const MIN_PAUSE: Seconds = Seconds(0.05);
+
+/// The words of `text` as they read: override blocks dropped and line
+/// breaks and hard spaces taken as spaces
+pub fn split_words(text: &str) -> Vec<String> {
+    ass::plain_text(text).split_whitespace().map(str::to_owned).collect()
+}
+
+/// `word` in lower case without its punctuation, as it's compared
+fn bare(word: &str) -> String {
+    word.chars().filter(|x| x.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// For each of `new`, the one of `old` it is, if it stayed: the longest run
+/// of words the two have in common, ignoring case and punctuation
+fn matching(old: &[&Word], new: &[String]) -> Vec<Option<usize>> {
+    let (old_bare, new_bare): (Vec<String>, Vec<String>) =
+        (old.iter().map(|x| bare(&x.text)).collect(), new.iter().map(|x| bare(x)).collect());
+    let same = |i: usize, j: usize| old_bare[i] == new_bare[j];
+    // common[i][j]: the most words `old[i..]` and `new[j..]` have in common
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if same(i, j) {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut result = vec![None; new.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if same(i, j) {
+            result[j] = Some(i);
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    result
+}
+
+/// The time `position` seconds into the speech of `stretches`; at the
+/// border of two, the start of the later with `later`, else the end of
+/// the earlier
+fn time_at(stretches: &[(f64, f64)], mut position: f64, later: bool) -> f64 {
+    for (i, &(from, to)) in stretches.iter().enumerate() {
+        let length = to - from;
+        let last = i + 1 == stretches.len();
+        if position < length || (!later && position <= length) || last {
+            return (from + position).min(to);
+        }
+        position -= length;
+    }
+    unreachable!()
+}
+
+/// The index into `stretches` of the one `time` is in, or the last before it
+fn stretch_of(stretches: &[(f64, f64)], time: f64) -> usize {
+    stretches.iter().rposition(|x| x.0 <= time).unwrap_or(0)
+}
+
+/// Where `texts` go in `speech` between `from` and `to`, in proportion to
+/// their length
+fn spread(
+    texts: &[String], (from, to): (f64, f64), speech: &[(Seconds, Seconds)],
+) -> Vec<(f64, f64)> {
+    let mut stretches: Vec<(f64, f64)> = speech.iter()
+        .map(|x| (x.0.0.max(from), x.1.0.min(to)))
+        .filter(|x| x.1 > x.0)
+        .collect();
+    if stretches.is_empty() {
+        stretches.push((from, to));
+    }
+    let spoken: f64 = stretches.iter().map(|x| x.1 - x.0).sum();
+    let weights: Vec<usize> = texts.iter().map(|x| x.chars().count()).collect();
+    let total: usize = weights.iter().sum();
+
+    let mut times = Vec::with_capacity(texts.len());
+    let mut before = 0;
+    for weight in weights {
+        #[allow(clippy::cast_precision_loss)]
+        let position = |x: usize| spoken * x as f64 / total as f64;
+        let mut start = time_at(&stretches, position(before), true);
+        let mut end = time_at(&stretches, position(before + weight), false);
+        before += weight;
+        // across a pause; it goes to the side that has more of it
+        let (first, second) = (stretch_of(&stretches, start), stretch_of(&stretches, end));
+        if second > first {
+            let head = stretches[first].1 - start;
+            let tail = end - stretches[second].0;
+            if head >= tail {
+                end = stretches[first].1;
+            } else {
+                start = stretches[second].0;
+            }
+        }
+        times.push((start, end));
+    }
+    times
+}
+
+/// Words for the text of `event`, timed by `heard`, the words the
+/// recognizer heard in its audio, or else by `speech`, the stretches of
+/// speech in it; those not heard keep the confidence of the ones of `old`,
+/// its words from before, that are still there
+pub fn align(
+    event: &Event, heard: &[Word], speech: &[(Seconds, Seconds)], old: &[&Word],
+) -> Vec<Word> {
+    let texts = split_words(&event.text);
+    let heard: Vec<&Word> = heard.iter().collect();
+    let recognized = matching(&heard, &texts);
+    let kept = matching(old, &texts);
+    let (start, end) = (event.start.0, event.end.0);
+
+    let mut times: Vec<Option<(f64, f64)>> = recognized.iter()
+        .map(|x| x.map(|i| {
+            let from = heard[i].start.0.clamp(start, end);
+            (from, heard[i].end.0.clamp(from, end))
+        }))
+        .collect();
+    // the ones between two heard are spread over the speech between them
+    let mut j = 0;
+    while j < texts.len() {
+        if times[j].is_some() {
+            j += 1;
+            continue;
+        }
+        let next = (j..texts.len()).find(|&k| times[k].is_some()).unwrap_or(texts.len());
+        let from = if j == 0 { start } else { times[j - 1].unwrap().1 };
+        let to = times.get(next).copied().flatten().map_or(end, |x| x.0);
+        let spread = spread(&texts[j..next], (from, to.max(from)), speech);
+        for (k, x) in spread.into_iter().enumerate() {
+            times[j + k] = Some(x);
+        }
+        j = next;
+    }
+
+    texts.into_iter().zip(times).zip(recognized.into_iter().zip(kept))
+        .map(|((text, time), (recognized, kept))| {
+            let (start, end) = time.unwrap();
+            let confidence = match recognized {
+                Some(i) => heard[i].confidence,
+                None => kept.and_then(|i| old[i].confidence),
+            };
+            Word { event_id: event.id, start: Seconds(start), end: Seconds(end), text, confidence }
+        })
+        .collect()
+}
@@ -0,0 +1,619 @@
+//! Advanced SubStation Alpha (.ass, and mostly .ssa). The parser is fed one
+//! line at a time so that callers can report progress and never need more
+//! than the current line besides the document being built.
+
+use std::collections::BTreeMap;
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::{Document, Event, ParseIssue, ParseResult, Style, SubtitleFormat};
+use crate::subtitle::positioning::{Letterbox, PositioningPolicy};
+use crate::subtitle::takes::Take;
+use crate::subtitle::words::Word;
+
+/// V4+ style fields after `Name`, in the order we store them
+const STYLE_FIELDS: [&str; 22] = [
+    "fontname", "fontsize", "primarycolour", "secondarycolour", "outlinecolour",
+    "backcolour", "bold", "italic", "underline", "strikeout", "scalex", "scaley",
+    "spacing", "angle", "borderstyle", "outline", "shadow", "alignment",
+    "marginl", "marginr", "marginv", "encoding",
+];
+
+const DEFAULT_STYLE_FIELDS: [&str; 22] = [
+    "Arial", "48", "&H00FFFFFF", "&H000000FF", "&H00000000", "&H00000000",
+    "0", "0", "0", "0", "100", "100", "0", "0", "1", "2", "2", "2",
+    "10", "10", "10", "1",
+];
+
+const DEFAULT_EVENT_FORMAT: [&str; 10] = [
+    "layer", "start", "end", "style", "name",
+    "marginl", "marginr", "marginv", "effect", "text",
+];
+
+const SCRIPT_INFO_SECTION: &str = "[Script Info]";
+/// as we write it, whether the file had V4 or V4+ styles
+const STYLES_SECTION: &str = "[V4+ Styles]";
+const EVENTS_SECTION: &str = "[Events]";
+/// Where our markers are stored; other programs keep it as an unknown section
+const MARKERS_SECTION: &str = "[Subtle Markers]";
+/// Labelled audio, likewise
+const REGIONS_SECTION: &str = "[Subtle Regions]";
+/// Voice-over takes, likewise
+const TAKES_SECTION: &str = "[Subtle Takes]";
+/// Recognized words, likewise
+const WORDS_SECTION: &str = "[Subtle Words]";
+/// The positioning policy and letterbox, likewise
+const POSITIONING_SECTION: &str = "[Subtle Positioning]";
+
+enum Section {
+    None,
+    ScriptInfo,
+    Styles,
+    Events,
+    Markers,
+    Regions,
+    Takes,
+    Words,
+    Positioning,
+    /// index into `Document::extra_sections`
+    Other(usize),
+}
+
+pub struct Parser {
+    document: Document,
+    issues: Vec<ParseIssue>,
+    section: Section,
+    style_format: Vec<String>,
+    event_format: Vec<String>,
+    /// by the position of their event among the events, which may not have
+    /// been read yet
+    takes: Vec<(usize, Take)>,
+    words: Vec<(usize, Word)>,
+    line: usize,
+}
+
+/// Positioning given by override tags in the text; the first one wins, like
+/// in libass. The first font size is taken too, for the text as a whole.
+#[derive(Default)]
+pub struct Overrides {
+    pub pos: Option<(f64, f64)>,
+    /// numpad style, as in `\an`
+    pub alignment: Option<u8>,
+    /// as in `\fs`
+    pub font_size: Option<f64>,
+}
+
+/// A style field by its lowercase V4+ name, or the default if the style
+/// didn't come from ASS
+pub fn style_field<'a>(style: Option<&'a Style>, name: &str) -> &'a str {
+    let i = STYLE_FIELDS.iter().position(|&x| x == name)
+        .expect("not a style field");
+    style.and_then(|x| x.ass_fields.get(i))
+        .map_or(DEFAULT_STYLE_FIELDS[i], String::as_str)
+}
+
+/// Sets a style field by its lowercase V4+ name, filling in the defaults
+/// first if the style didn't come from ASS
+pub fn set_style_field(style: &mut Style, name: &str, value: String) {
+    let i = STYLE_FIELDS.iter().position(|&x| x == name)
+        .expect("not a style field");
+    if style.ass_fields.len() != STYLE_FIELDS.len() {
+        style.ass_fields = DEFAULT_STYLE_FIELDS.iter().map(|&x| x.to_owned()).collect();
+    }
+    style.ass_fields[i] = value;
+}
+
+/// The coordinate space of positions and margins (`PlayResX`, `PlayResY`)
+pub fn play_res(document: &Document) -> (f64, f64) {
+    let get = |key: &str| document.script_info.iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .and_then(|(_, v)| v.trim().parse::<f64>().ok())
+        .filter(|x| *x > 0.0);
+    // the defaults of VSFilter when neither is given
+    match (get("playresx"), get("playresy")) {
+        (Some(x), Some(y)) => (x, y),
+        (Some(x), None) => (x, if (x - 1280.0).abs() < f64::EPSILON { 1024.0 } else { x * 3.0 / 4.0 }),
+        (None, Some(y)) => (if (y - 1024.0).abs() < f64::EPSILON { 1280.0 } else { y * 4.0 / 3.0 }, y),
+        (None, None) => (384.0, 288.0),
+    }
+}
+
+/// The effective margins of an event: its own where non-zero, otherwise
+/// its style's
+pub fn margins(event: &Event, style: Option<&Style>) -> (f64, f64, f64) {
+    let pick = |own: i32, name: &str| if own != 0 {
+        f64::from(own)
+    } else {
+        style_field(style, name).parse().unwrap_or(0.0)
+    };
+    (pick(event.margins.0, "marginl"),
+     pick(event.margins.1, "marginr"),
+     pick(event.margins.2, "marginv"))
+}
+
+/// Text as it reads: override blocks dropped, and line breaks and hard
+/// spaces made plain spaces
+pub fn plain_text(text: &str) -> String {
+    let mut rest = text.replace("\\N", " ").replace("\\n", " ").replace("\\h", " ");
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else { break };
+        rest.replace_range(open..=open + close, "");
+    }
+    rest
+}
+
+pub fn parse_overrides(text: &str) -> Overrides {
+    let mut result = Overrides::default();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else { break };
+        for tag in rest[open + 1..open + close].split('\\').skip(1) {
+            if let Some(args) = tag.strip_prefix("pos(") {
+                let mut numbers = args.trim_end_matches(')').split(',')
+                    .map(|x| x.trim().parse::<f64>());
+                if result.pos.is_none()
+                    && let (Some(Ok(x)), Some(Ok(y))) = (numbers.next(), numbers.next())
+                {
+                    result.pos = Some((x, y));
+                }
+            } else if let Some(n) = tag.strip_prefix("fs") {
+                // not `\fscx`, `\fscy` or `\fsp`, which don't parse
+                if result.font_size.is_none()
+                    && let Ok(size) = n.trim().parse::<f64>()
+                    && size > 0.0
+                {
+                    result.font_size = Some(size);
+                }
+            } else if let Some(n) = tag.strip_prefix("an") {
+                if result.alignment.is_none()
+                    && let Ok(n @ 1..=9) = n.trim().parse::<u8>()
+                {
+                    result.alignment = Some(n);
+                }
+            } else if let Some(n) = tag.strip_prefix('a')
+                && result.alignment.is_none()
+                && let Ok(n @ 1..=11) = n.trim().parse::<u8>()
+                && n & 3 != 0
+            {
+                // legacy SSA alignment: +4 for top, +8 for middle
+                let horizontal = n & 3;
+                let vertical = if n & 4 != 0 { 6 } else if n & 8 != 0 { 3 } else { 0 };
+                result.alignment = Some(horizontal + vertical);
+            }
+        }
+        rest = &rest[open + close + 1..];
+    }
+    result
+}
+
+pub fn detect(source: &str) -> bool {
+    source.lines()
+        .map(|x| x.trim_start_matches('\u{feff}').trim())
+        .find(|x| !x.is_empty())
+        .is_some_and(|x| x.eq_ignore_ascii_case("[script info]"))
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser {
+            document: Document::new(SubtitleFormat::Ass),
+            issues: Vec::new(),
+            section: Section::None,
+            style_format: Vec::new(),
+            event_format: DEFAULT_EVENT_FORMAT.iter().map(|&x| x.to_owned()).collect(),
+            takes: Vec::new(),
+            words: Vec::new(),
+            line: 0,
+        }
+    }
+
+    fn issue(&mut self, message: impl Into<String>) {
+        self.issues.push(ParseIssue { line: self.line, message: message.into() });
+    }
+
+    pub fn feed_line(&mut self, line: &str) {
+        self.line += 1;
+        let line = line.trim_start_matches('\u{feff}').trim_end();
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            return;
+        }
+
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            let (section, header) = match trimmed.to_ascii_lowercase().as_str() {
+                "[script info]" => (Section::ScriptInfo, SCRIPT_INFO_SECTION),
+                "[v4+ styles]" | "[v4 styles]" => (Section::Styles, STYLES_SECTION),
+                "[events]" => (Section::Events, EVENTS_SECTION),
+                "[subtle markers]" => (Section::Markers, MARKERS_SECTION),
+                "[subtle regions]" => (Section::Regions, REGIONS_SECTION),
+                "[subtle takes]" => (Section::Takes, TAKES_SECTION),
+                "[subtle words]" => (Section::Words, WORDS_SECTION),
+                "[subtle positioning]" => (Section::Positioning, POSITIONING_SECTION),
+                _ => {
+                    self.document.extra_sections.push((trimmed.to_owned(), Vec::new()));
+                    (Section::Other(self.document.extra_sections.len() - 1), trimmed)
+                }
+            };
+            self.section = section;
+            self.document.section_order.push(header.to_owned());
+            return;
+        }
+
+        if let Section::Other(i) = self.section {
+            self.document.extra_sections[i].1.push(line.to_owned());
+            return;
+        }
+        if trimmed.starts_with(';') || trimmed.starts_with("!:") {
+            return;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            self.issue("line without a key; skipped");
+            return;
+        };
+        let (key, value) = (key.trim(), value.trim_start());
+
+        match self.section {
+            Section::ScriptInfo => {
+                self.document.script_info.push((key.to_owned(), value.to_owned()));
+            }
+            Section::Styles if key.eq_ignore_ascii_case("format") => {
+                self.style_format = parse_format(value);
+            }
+            Section::Styles if key.eq_ignore_ascii_case("style") => {
+                self.parse_style(value);
+            }
+            Section::Events if key.eq_ignore_ascii_case("format") => {
+                self.event_format = parse_format(value);
+            }
+            Section::Events if key.eq_ignore_ascii_case("dialogue") => {
+                self.parse_event(value, false);
+            }
+            Section::Events if key.eq_ignore_ascii_case("comment") => {
+                self.parse_event(value, true);
+            }
+            Section::Markers if key.eq_ignore_ascii_case("marker") => {
+                self.parse_marker(value);
+            }
+            Section::Regions if key.eq_ignore_ascii_case("region") => {
+                self.parse_region(value);
+            }
+            Section::Takes if key.eq_ignore_ascii_case("take") => {
+                self.parse_take(value);
+            }
+            Section::Words if key.eq_ignore_ascii_case("word") => {
+                self.parse_word(value);
+            }
+            Section::Positioning if key.eq_ignore_ascii_case("policy") => {
+                self.parse_policy(value);
+            }
+            Section::Positioning if key.eq_ignore_ascii_case("letterbox") => {
+                self.parse_letterbox(value);
+            }
+            Section::None => self.issue("line outside of any section; skipped"),
+            _ => self.issue(format!("unknown line type '{key}'; skipped")),
+        }
+    }
+
+    fn parse_style(&mut self, value: &str) {
+        let format: Vec<String> = if self.style_format.is_empty() {
+            std::iter::once("name").chain(STYLE_FIELDS).map(str::to_owned).collect()
+        } else {
+            self.style_format.clone()
+        };
+        let values: Vec<&str> = value.splitn(format.len(), ',').map(str::trim).collect();
+        let get = |name: &str| format.iter()
+            .position(|x| x == name)
+            .and_then(|i| values.get(i).copied());
+
+        let Some(name) = get("name") else {
+            return self.issue("style without a name; skipped");
+        };
+        let ass_fields = STYLE_FIELDS.iter().zip(DEFAULT_STYLE_FIELDS)
+            .map(|(field, default)| get(field).unwrap_or(default).to_owned())
+            .collect();
+        if self.document.styles.iter().any(|x| x.name == name) {
+            return self.issue(format!("duplicate style '{name}'; skipped"));
+        }
+        self.document.styles.push(Style { name: name.to_owned(), ass_fields });
+    }
+
+    fn parse_event(&mut self, value: &str, is_comment: bool) {
+        let values: Vec<&str> = value.splitn(self.event_format.len(), ',').collect();
+        let get = |name: &str| self.event_format.iter()
+            .position(|x| x == name)
+            .and_then(|i| values.get(i).copied());
+        let number = |name: &str| get(name).and_then(|x| x.trim().parse::<i32>().ok()).unwrap_or(0);
+
+        let (Some(start), Some(end)) = (
+            get("start").and_then(parse_time),
+            get("end").and_then(parse_time)
+        ) else {
+            return self.issue("event with invalid times; skipped");
+        };
+        let style = get("style").map_or("Default", str::trim);
+        let text = get("text").unwrap_or("").replace("\\N", "\n");
+        let layer = number("layer");
+        let margins = (number("marginl"), number("marginr"), number("marginv"));
+        let actor = self.document.intern(get("name").map_or("", str::trim));
+        let effect = self.document.intern(get("effect").map_or("", str::trim));
+
+        let style = style.to_owned();
+        if !self.document.styles.iter().any(|x| x.name == style) {
+            self.issue(format!("event uses undefined style '{style}'"));
+        }
+        self.document.push_event(start, end, &style, text);
+        let event = self.document.events.last_mut().unwrap();
+        event.actor = actor;
+        event.effect = effect;
+        event.layer = layer;
+        event.margins = margins;
+        event.is_comment = is_comment;
+    }
+
+    /// `Marker: time,color,label`, the time in seconds
+    fn parse_marker(&mut self, value: &str) {
+        let mut fields = value.splitn(3, ',');
+        let time = fields.next().and_then(|x| x.trim().parse::<f64>().ok()).map(Seconds);
+        let (Some(time), Some(color), Some(label)) = (time, fields.next(), fields.next()) else {
+            return self.issue("invalid marker; skipped");
+        };
+        if self.document.add_marker(time, label.to_owned(), color.trim().to_owned()).is_err() {
+            self.issue("invalid marker; skipped");
+        }
+    }
+
+    /// `Region: start,end,label`, the times in seconds
+    fn parse_region(&mut self, value: &str) {
+        let mut fields = value.splitn(3, ',');
+        let mut time = || fields.next().and_then(|x| x.trim().parse::<f64>().ok()).map(Seconds);
+        let (Some(start), Some(end)) = (time(), time()) else {
+            return self.issue("invalid region; skipped");
+        };
+        let label = fields.next().unwrap_or("").to_owned();
+        if self.document.add_region(start, end, label).is_err() {
+            self.issue("invalid region; skipped");
+        }
+    }
+
+    /// `Take: event,offset,length,selected,trim start,trim end,gain,path`,
+    /// the event counted from 0 and the times in seconds
+    fn parse_take(&mut self, value: &str) {
+        let fields: Vec<&str> = value.splitn(8, ',').map(str::trim).collect();
+        let [event, offset, length, selected, trim_start, trim_end, gain, path] = fields[..] else {
+            return self.issue("invalid take; skipped");
+        };
+        let number = |x: &str| x.parse::<f64>().ok();
+        let (Ok(event), Some(offset), Some(length), Some(selected),
+            Some(trim_start), Some(trim_end), Some(gain)) = (
+            event.parse::<usize>(), number(offset), number(length),
+            match selected { "0" => Some(false), "1" => Some(true), _ => None },
+            number(trim_start), number(trim_end), number(gain),
+        ) else {
+            return self.issue("invalid take; skipped");
+        };
+        self.takes.push((event, Take {
+            id: 0, event_id: 0, path: path.to_owned(),
+            offset: Seconds(offset), length: Seconds(length), selected,
+            trim_start: Seconds(trim_start), trim_end: Seconds(trim_end), gain,
+        }));
+    }
+
+    /// `Word: event,start,end,confidence,text`, the event counted from 0,
+    /// the times in seconds and the confidence empty if unknown
+    fn parse_word(&mut self, value: &str) {
+        let fields: Vec<&str> = value.splitn(5, ',').map(str::trim).collect();
+        let [event, start, end, confidence, text] = fields[..] else {
+            return self.issue("invalid word; skipped");
+        };
+        let confidence = match confidence {
+            "" => Ok(None),
+            x => x.parse::<f32>().map(Some),
+        };
+        let (Ok(event), Ok(start), Ok(end), Ok(confidence)) =
+            (event.parse::<usize>(), start.parse::<f64>(), end.parse::<f64>(), confidence) else
+        {
+            return self.issue("invalid word; skipped");
+        };
+        self.words.push((event, Word {
+            event_id: 0, start: Seconds(start), end: Seconds(end),
+            text: text.to_owned(), confidence,
+        }));
+    }
+
+    /// `Policy: free`, `insidePicture` or `insideBars`
+    fn parse_policy(&mut self, value: &str) {
+        self.document.positioning = match value.trim() {
+            "free" => PositioningPolicy::Free,
+            "insidePicture" => PositioningPolicy::InsidePicture,
+            "insideBars" => PositioningPolicy::InsideBars,
+            _ => return self.issue("invalid positioning policy; skipped"),
+        };
+    }
+
+    /// `Letterbox: top,bottom,height`, in pixels of the video
+    fn parse_letterbox(&mut self, value: &str) {
+        let fields: Vec<&str> = value.split(',').map(str::trim).collect();
+        let [top, bottom, height] = fields[..] else {
+            return self.issue("invalid letterbox; skipped");
+        };
+        let (Ok(top), Ok(bottom), Ok(height)) =
+            (top.parse::<u32>(), bottom.parse::<u32>(), height.parse::<u32>()) else
+        {
+            return self.issue("invalid letterbox; skipped");
+        };
+        self.document.letterbox = Some(Letterbox { top, bottom, height });
+    }
+
+    pub fn finish(mut self) -> ParseResult {
+        for (event, take) in std::mem::take(&mut self.takes) {
+            let Some(event_id) = self.document.events.get(event).map(|x| x.id) else {
+                self.issues.push(ParseIssue {
+                    line: 0,
+                    message: format!("take for missing event {event}; skipped"),
+                });
+                continue;
+            };
+            let id = self.document
+                .add_take(event_id, take.path, take.offset, take.length, take.selected)
+                .unwrap();
+            if let Err(e) = self.document.update_take(
+                id, Some(take.trim_start), Some(take.trim_end), Some(take.gain))
+            {
+                self.issues.push(ParseIssue { line: 0, message: format!("{e}; ignored") });
+            }
+        }
+        let mut missing = 0;
+        for (event, word) in std::mem::take(&mut self.words) {
+            match self.document.events.get(event).map(|x| x.id) {
+                Some(event_id) => self.document.add_words(event_id, [word]),
+                None => missing += 1,
+            }
+        }
+        if missing > 0 {
+            self.issues.push(ParseIssue {
+                line: 0,
+                message: format!("{missing} words for missing events; skipped"),
+            });
+        }
+        ParseResult { document: self.document, issues: self.issues }
+    }
+}
+
+fn parse_format(value: &str) -> Vec<String> {
+    value.split(',').map(|x| x.trim().to_ascii_lowercase()).collect()
+}
+
+/// `H:MM:SS.CC`, though we also take more or fewer fractional digits
+fn parse_time(s: &str) -> Option<Seconds> {
+    let mut parts = s.trim().split(':');
+    let h: f64 = parts.next()?.parse().ok()?;
+    let m: f64 = parts.next()?.parse().ok()?;
+    let s: f64 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Seconds(h * 3600.0 + m * 60.0 + s))
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_time(time: Seconds) -> String {
+    let cs = (time.0.max(0.0) * 100.0).round() as u64;
+    format!("{}:{:02}:{:02}.{:02}", cs / 360_000, cs / 6000 % 60, cs / 100 % 60, cs % 100)
+}
+
+/// Sections come out in the order the file had them; any it didn't have
+/// follow in ours: script info, styles, those we keep as they are, our
+/// own, and events last
+pub fn write(document: &Document) -> String {
+    let mut sections: Vec<(&str, String)> = Vec::new();
+
+    let mut text = format!("{SCRIPT_INFO_SECTION}\n");
+    if !document.script_info.iter().any(|(k, _)| k.eq_ignore_ascii_case("scripttype")) {
+        text.push_str("ScriptType: v4.00+\n");
+    }
+    for (key, value) in &document.script_info {
+        text.push_str(&format!("{key}: {value}\n"));
+    }
+    sections.push((SCRIPT_INFO_SECTION, text));
+
+    let mut text = format!("{STYLES_SECTION}\nFormat: Name, Fontname, Fontsize, PrimaryColour, \
+        SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, \
+        ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, \
+        MarginL, MarginR, MarginV, Encoding\n");
+    for style in &document.styles {
+        let fields = if style.ass_fields.len() == STYLE_FIELDS.len() {
+            style.ass_fields.join(",")
+        } else {
+            DEFAULT_STYLE_FIELDS.join(",")
+        };
+        text.push_str(&format!("Style: {},{fields}\n", style.name));
+    }
+    sections.push((STYLES_SECTION, text));
+
+    for (header, lines) in &document.extra_sections {
+        let mut text = format!("{header}\n");
+        for line in lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+        sections.push((header.as_str(), text));
+    }
+
+    if !document.markers.is_empty() {
+        let mut text = format!("{MARKERS_SECTION}\n");
+        for marker in &document.markers {
+            text.push_str(&format!("Marker: {:.3},{},{}\n",
+                marker.time.0, marker.color, marker.label.replace('\n', " ")));
+        }
+        sections.push((MARKERS_SECTION, text));
+    }
+
+    if !document.regions.is_empty() {
+        let mut text = format!("{REGIONS_SECTION}\n");
+        for region in &document.regions {
+            text.push_str(&format!("Region: {:.3},{:.3},{}\n",
+                region.start.0, region.end.0, region.label.replace('\n', " ")));
+        }
+        sections.push((REGIONS_SECTION, text));
+    }
+
+    if !document.takes.is_empty() {
+        let mut text = format!("{TAKES_SECTION}\n");
+        for take in &document.takes {
+            let Some(event) = document.events.iter().position(|x| x.id == take.event_id) else {
+                continue;
+            };
+            text.push_str(&format!("Take: {event},{:.3},{:.3},{},{:.3},{:.3},{:.1},{}\n",
+                take.offset.0, take.length.0, u8::from(take.selected),
+                take.trim_start.0, take.trim_end.0, take.gain, take.path));
+        }
+        sections.push((TAKES_SECTION, text));
+    }
+
+    if !document.words.is_empty() {
+        let mut text = format!("{WORDS_SECTION}\n");
+        let positions: BTreeMap<u32, usize> = document.events.iter().enumerate()
+            .map(|(i, x)| (x.id, i))
+            .collect();
+        for word in &document.words {
+            let Some(event) = positions.get(&word.event_id) else { continue };
+            let confidence = word.confidence.map_or(String::new(), |x| format!("{x:.3}"));
+            text.push_str(&format!("Word: {event},{:.3},{:.3},{confidence},{}\n",
+                word.start.0, word.end.0, word.text.replace('\n', " ")));
+        }
+        sections.push((WORDS_SECTION, text));
+    }
+
+    if document.positioning != PositioningPolicy::Free || document.letterbox.is_some() {
+        let policy = match document.positioning {
+            PositioningPolicy::Free => "free",
+            PositioningPolicy::InsidePicture => "insidePicture",
+            PositioningPolicy::InsideBars => "insideBars",
+        };
+        let mut text = format!("{POSITIONING_SECTION}\nPolicy: {policy}\n");
+        if let Some(Letterbox { top, bottom, height }) = document.letterbox {
+            text.push_str(&format!("Letterbox: {top},{bottom},{height}\n"));
+        }
+        sections.push((POSITIONING_SECTION, text));
+    }
+
+    let mut text = format!("{EVENTS_SECTION}\nFormat: Layer, Start, End, Style, Name, \
+        MarginL, MarginR, MarginV, Effect, Text\n");
+    for event in &document.events {
+        let (l, r, v) = event.margins;
+        text.push_str(&format!("{}: {},{},{},{},{},{l},{r},{v},{},{}\n",
+            if event.is_comment { "Comment" } else { "Dialogue" },
+            event.layer, format_time(event.start), format_time(event.end),
+            event.style, event.actor, event.effect,
+            event.text.replace('\n', "\\N")));
+    }
+    sections.push((EVENTS_SECTION, text));
+
+    let mut ordered = Vec::with_capacity(sections.len());
+    for header in &document.section_order {
+        if let Some(i) = sections.iter().position(|x| x.0 == *header) {
+            ordered.push(sections.remove(i).1);
+        }
+    }
+    ordered.extend(sections.into_iter().map(|x| x.1));
+    ordered.join("\n")
+}
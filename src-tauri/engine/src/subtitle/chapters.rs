@@ -0,0 +1,134 @@
+//! Matroska chapter XML, as read and written by mkvmerge and mkvextract.
+//! Only one edition with flat chapters is produced; nested chapters are
+//! flattened on import.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+
+/// Shot changes closer together than this are one transition
+const CUT_CLUSTER_GAP: f64 = 1.0;
+/// How far outside a silence a shot change may be to mark its boundary
+const SNAP_DISTANCE: f64 = 2.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Chapter {
+    pub start: Seconds,
+    pub title: String,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"")
+        .replace("&apos;", "'").replace("&amp;", "&")
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_time(time: Seconds) -> String {
+    let ns = (time.0.max(0.0) * 1e9).round() as u64;
+    let s = ns / 1_000_000_000;
+    format!("{:02}:{:02}:{:02}.{:09}", s / 3600, s / 60 % 60, s % 60, ns % 1_000_000_000)
+}
+
+/// `HH:MM:SS.nnnnnnnnn`, with any number of fractional digits
+fn parse_time(s: &str) -> Option<Seconds> {
+    let mut parts = s.trim().split(':');
+    let h: f64 = parts.next()?.parse().ok()?;
+    let m: f64 = parts.next()?.parse().ok()?;
+    let s: f64 = parts.next()?.parse().ok()?;
+    Some(Seconds(h * 3600.0 + m * 60.0 + s))
+}
+
+pub fn to_xml(chapters: &[Chapter], language: &str) -> String {
+    let mut result = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <!DOCTYPE Chapters SYSTEM \"matroskachapters.dtd\">\n\
+        <Chapters>\n  <EditionEntry>\n");
+    for chapter in chapters {
+        result.push_str(&format!(
+            "    <ChapterAtom>\n\
+            \x20     <ChapterTimeStart>{}</ChapterTimeStart>\n\
+            \x20     <ChapterDisplay>\n\
+            \x20       <ChapterString>{}</ChapterString>\n\
+            \x20       <ChapterLanguage>{language}</ChapterLanguage>\n\
+            \x20     </ChapterDisplay>\n\
+            \x20   </ChapterAtom>\n",
+            format_time(chapter.start), escape(&chapter.title)));
+    }
+    result.push_str("  </EditionEntry>\n</Chapters>\n");
+    result
+}
+
+/// The text of the first `<tag>` in `source`
+fn element<'a>(source: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let start = source.find(&open)? + open.len();
+    let end = source[start..].find(&format!("</{tag}>"))?;
+    Some(&source[start..start + end])
+}
+
+pub fn from_xml(source: &str) -> Result<Vec<Chapter>, String> {
+    if !source.contains("<Chapters") {
+        return Err("not a Matroska chapter file".to_owned());
+    }
+    let mut chapters = Vec::new();
+    // every atom's own fields come before any nested atom
+    for atom in source.split("<ChapterAtom>").skip(1) {
+        let start = element(atom, "ChapterTimeStart")
+            .and_then(parse_time)
+            .ok_or("chapter without a valid ChapterTimeStart")?;
+        let title = element(atom, "ChapterString").map(unescape).unwrap_or_default();
+        chapters.push(Chapter { start, title });
+    }
+    chapters.sort_by(|a, b| a.start.0.total_cmp(&b.start.0));
+    Ok(chapters)
+}
+
+/// Proposes chapters where a long silence meets a shot change, which is how
+/// most programmes separate their parts. Silences with no shot change nearby
+/// still qualify but rank lower; shot changes alone are too frequent to mean
+/// anything. Chapters are kept at least `min_length` apart, and the first
+/// one always starts at 0.
+pub fn suggest(
+    cuts: &[Seconds], silences: &[(Seconds, Seconds)],
+    duration: Seconds, min_length: Seconds,
+) -> Vec<Chapter> {
+    // a transition's new shot starts at its last cut
+    let mut transitions: Vec<f64> = Vec::new();
+    for cut in cuts {
+        match transitions.last_mut() {
+            Some(last) if cut.0 - *last <= CUT_CLUSTER_GAP => *last = cut.0,
+            _ => transitions.push(cut.0),
+        }
+    }
+
+    let mut candidates: Vec<(f64, f64)> = silences.iter()
+        .map(|(start, end)| {
+            let length = end.0 - start.0;
+            let middle = (start.0 + end.0) / 2.0;
+            transitions.iter()
+                .filter(|&&x| x >= start.0 - SNAP_DISTANCE && x <= end.0 + SNAP_DISTANCE)
+                .min_by(|a, b| (*a - middle).abs().total_cmp(&(*b - middle).abs()))
+                .map_or((end.0, length), |&x| (x, length * 2.0))
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut starts = vec![0.0];
+    for (time, _) in candidates {
+        if time <= duration.0 - min_length.0
+            && starts.iter().all(|x| (time - x).abs() >= min_length.0)
+        {
+            starts.push(time);
+        }
+    }
+    starts.sort_by(f64::total_cmp);
+    starts.into_iter().enumerate()
+        .map(|(i, x)| Chapter { start: Seconds(x), title: format!("Chapter {}", i + 1) })
+        .collect()
+}
@@ -0,0 +1,448 @@
+//! Between ASS and SubRip. Most of what ASS can do has no SRT equivalent,
+//! so going down follows `SrtRules`, and whatever is lost is reported by
+//! kind and event rather than left to be found by watching. Going up gives
+//! a plain script on a template style, with italic and top-placed cues
+//! moved into styles of their own so that they can be restyled at once.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::subtitle::ass;
+use crate::subtitle::document::{Document, Event, Style, SubtitleFormat};
+use crate::subtitle::srt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum PositionRule {
+    /// everything goes to the bottom center
+    Drop,
+    /// events at the top get `{\an8}`, which most players honour
+    KeepTop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum FormattingRule {
+    /// italic, bold, underline and strikeout become `<i>`, `<b>`, `<u>`, `<s>`
+    Tags,
+    Strip,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SrtRules {
+    pub positioning: PositionRule,
+    pub formatting: FormattingRule,
+    /// events with the same times and placement become one cue, and lines
+    /// repeated on several layers, as signs often are, are kept once
+    pub merge_layers: bool,
+    /// added to every time, as to write in a program's timecode; see
+    /// `media_api::set_timecode_offset`
+    #[serde(default)]
+    pub time_offset_ms: i64,
+}
+
+impl SrtRules {
+    /// Everything a document read from SRT could have held to begin with
+    pub const ROUND_TRIP: SrtRules = SrtRules {
+        positioning: PositionRule::KeepTop,
+        formatting: FormattingRule::Tags,
+        merge_layers: false,
+        time_offset_ms: 0,
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum LossKind {
+    /// `\pos`, `\move`, `\org`, or an alignment SRT can't express
+    Positioning,
+    /// italic, bold, underline or strikeout, under `FormattingRule::Strip`
+    Formatting,
+    /// fonts, sizes, colours, borders, shadows, rotation, clipping
+    Styling,
+    /// fades, transforms and karaoke
+    Animation,
+    /// vector drawings, which are left out
+    Drawing,
+    /// lines repeated on another layer, merged into one
+    Layer,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Loss {
+    pub kind: LossKind,
+    pub event_ids: Vec<u32>,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct DowngradeReport {
+    pub cues: usize,
+    /// only the kinds that happened
+    pub losses: Vec<Loss>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Formatting {
+    italic: bool,
+    bold: bool,
+    underline: bool,
+    strikeout: bool,
+}
+
+impl Formatting {
+    fn of(style: Option<&Style>) -> Formatting {
+        let flag = |name| ass::style_field(style, name).trim().parse::<i32>()
+            .is_ok_and(|x| x != 0);
+        Formatting {
+            italic: flag("italic"),
+            bold: flag("bold"),
+            underline: flag("underline"),
+            strikeout: flag("strikeout"),
+        }
+    }
+
+    fn get(self, tag: &str) -> bool {
+        match tag {
+            "i" => self.italic,
+            "b" => self.bold,
+            "u" => self.underline,
+            _ => self.strikeout,
+        }
+    }
+}
+
+enum Tag {
+    Lost(LossKind),
+    Italic,
+    Bold,
+    Underline,
+    Strikeout,
+    Reset,
+    Drawing,
+    /// read by `ass::parse_overrides` instead
+    Alignment,
+}
+
+/// Longer names first, where one is a prefix of another
+const TAGS: &[(&str, Tag)] = &[
+    ("iclip", Tag::Lost(LossKind::Styling)), ("clip", Tag::Lost(LossKind::Styling)),
+    ("xbord", Tag::Lost(LossKind::Styling)), ("ybord", Tag::Lost(LossKind::Styling)),
+    ("bord", Tag::Lost(LossKind::Styling)), ("xshad", Tag::Lost(LossKind::Styling)),
+    ("yshad", Tag::Lost(LossKind::Styling)), ("shad", Tag::Lost(LossKind::Styling)),
+    ("blur", Tag::Lost(LossKind::Styling)), ("be", Tag::Lost(LossKind::Styling)),
+    ("fscx", Tag::Lost(LossKind::Styling)), ("fscy", Tag::Lost(LossKind::Styling)),
+    ("fsp", Tag::Lost(LossKind::Styling)), ("fs", Tag::Lost(LossKind::Styling)),
+    ("fn", Tag::Lost(LossKind::Styling)), ("fe", Tag::Lost(LossKind::Styling)),
+    ("frx", Tag::Lost(LossKind::Styling)), ("fry", Tag::Lost(LossKind::Styling)),
+    ("frz", Tag::Lost(LossKind::Styling)), ("fr", Tag::Lost(LossKind::Styling)),
+    ("fax", Tag::Lost(LossKind::Styling)), ("fay", Tag::Lost(LossKind::Styling)),
+    ("alpha", Tag::Lost(LossKind::Styling)),
+    ("1c", Tag::Lost(LossKind::Styling)), ("2c", Tag::Lost(LossKind::Styling)),
+    ("3c", Tag::Lost(LossKind::Styling)), ("4c", Tag::Lost(LossKind::Styling)),
+    ("1a", Tag::Lost(LossKind::Styling)), ("2a", Tag::Lost(LossKind::Styling)),
+    ("3a", Tag::Lost(LossKind::Styling)), ("4a", Tag::Lost(LossKind::Styling)),
+    ("c", Tag::Lost(LossKind::Styling)), ("q", Tag::Lost(LossKind::Styling)),
+    ("pbo", Tag::Lost(LossKind::Styling)),
+    ("fade", Tag::Lost(LossKind::Animation)), ("fad", Tag::Lost(LossKind::Animation)),
+    ("kf", Tag::Lost(LossKind::Animation)), ("ko", Tag::Lost(LossKind::Animation)),
+    ("k", Tag::Lost(LossKind::Animation)), ("K", Tag::Lost(LossKind::Animation)),
+    ("t", Tag::Lost(LossKind::Animation)),
+    ("pos", Tag::Lost(LossKind::Positioning)), ("move", Tag::Lost(LossKind::Positioning)),
+    ("org", Tag::Lost(LossKind::Positioning)),
+    ("p", Tag::Drawing),
+    ("i", Tag::Italic), ("b", Tag::Bold), ("u", Tag::Underline), ("s", Tag::Strikeout),
+    ("r", Tag::Reset),
+    ("a", Tag::Alignment),
+];
+
+/// Closes the open tags that are no longer wanted, with the ones opened
+/// after them so that tags stay nested, then opens what is missing
+fn sync_tags(text: &mut String, open: &mut Vec<&'static str>, want: Formatting) {
+    if let Some(first) = open.iter().position(|x| !want.get(x)) {
+        for tag in open.drain(first..).rev() {
+            text.push_str(&format!("</{tag}>"));
+        }
+    }
+    for tag in ["i", "b", "u", "s"] {
+        if want.get(tag) && !open.contains(&tag) {
+            text.push_str(&format!("<{tag}>"));
+            open.push(tag);
+        }
+    }
+}
+
+struct Converted {
+    text: String,
+    top: bool,
+}
+
+fn convert_event(
+    document: &Document, event: &Event, rules: SrtRules,
+    losses: &mut BTreeMap<LossKind, Vec<u32>>,
+) -> Option<Converted> {
+    let mut lost = Vec::new();
+    let find_style = |name: &str| document.styles.iter().find(|x| x.name == name);
+    let style = find_style(&event.style);
+    let base = Formatting::of(style);
+
+    let overrides = ass::parse_overrides(&event.text);
+    let alignment = overrides.alignment
+        .or_else(|| ass::style_field(style, "alignment").parse().ok())
+        .unwrap_or(2);
+    let top = rules.positioning == PositionRule::KeepTop && (7..=9).contains(&alignment);
+    if alignment != 2 && !(top && alignment == 8) {
+        lost.push(LossKind::Positioning);
+    }
+
+    let mut text = String::new();
+    let mut open = Vec::new();
+    let mut state = base;
+    let mut drawing = false;
+    let mut visible = false;
+    let mut rest = event.text.as_str();
+    while !rest.is_empty() {
+        if rest.starts_with('{') && let Some(close) = rest.find('}') {
+            for tag in rest[1..close].split('\\').skip(1).map(str::trim) {
+                let Some((name, kind)) = TAGS.iter().find(|(x, _)| tag.starts_with(x)) else {
+                    continue;
+                };
+                let arg = tag[name.len()..].trim();
+                let on = |current: bool, default: bool| match arg {
+                    "" => default,
+                    // `\b` also takes a weight
+                    x => x.parse::<i32>().map_or(current, |x| x == 1 || x >= 600),
+                };
+                match kind {
+                    Tag::Lost(x) => lost.push(*x),
+                    Tag::Italic => state.italic = on(state.italic, base.italic),
+                    Tag::Bold => state.bold = on(state.bold, base.bold),
+                    Tag::Underline => state.underline = on(state.underline, base.underline),
+                    Tag::Strikeout => state.strikeout = on(state.strikeout, base.strikeout),
+                    Tag::Reset if arg.is_empty() => state = base,
+                    Tag::Reset => state = Formatting::of(find_style(arg).or(style)),
+                    Tag::Drawing => drawing = arg.parse::<i32>().is_ok_and(|x| x > 0),
+                    Tag::Alignment => (),
+                }
+            }
+            rest = &rest[close + 1..];
+            continue;
+        }
+
+        let first = rest.chars().next().map_or(0, char::len_utf8);
+        let end = rest[first..].find('{').map_or(rest.len(), |x| x + first);
+        let run = rest[..end].replace("\\n", " ").replace("\\h", "\u{a0}");
+        rest = &rest[end..];
+        if drawing {
+            lost.push(LossKind::Drawing);
+            continue;
+        }
+        if run.trim().is_empty() {
+            text.push_str(&run);
+            continue;
+        }
+        visible = true;
+        match rules.formatting {
+            FormattingRule::Tags => sync_tags(&mut text, &mut open, state),
+            FormattingRule::Strip if state != Formatting::default() =>
+                lost.push(LossKind::Formatting),
+            FormattingRule::Strip => (),
+        }
+        text.push_str(&run);
+    }
+    sync_tags(&mut text, &mut open, Formatting::default());
+
+    for kind in lost {
+        let ids = losses.entry(kind).or_default();
+        if ids.last() != Some(&event.id) {
+            ids.push(event.id);
+        }
+    }
+    let text = text.lines().map(str::trim).collect::<Vec<_>>().join("\n");
+    visible.then_some(Converted { text: text.trim_matches('\n').to_owned(), top })
+}
+
+/// Cues for every event that isn't a comment, in order of start time
+pub fn to_srt(document: &Document, rules: SrtRules) -> (Vec<srt::Cue>, DowngradeReport) {
+    let mut losses = BTreeMap::new();
+    let mut events: Vec<&Event> = document.events.iter()
+        .filter(|x| !x.is_comment)
+        .collect();
+    events.sort_by(|a, b| a.start.0.total_cmp(&b.start.0));
+
+    let mut cues: Vec<(srt::Cue, bool)> = Vec::new();
+    let mut by_time: HashMap<(u64, u64, bool), usize> = HashMap::new();
+    for event in events {
+        let Some(converted) = convert_event(document, event, rules, &mut losses) else {
+            continue;
+        };
+        let key = (event.start.0.to_bits(), event.end.0.to_bits(), converted.top);
+        if rules.merge_layers && let Some(&i) = by_time.get(&key) {
+            let cue = &mut cues[i].0;
+            if cue.text.split('\n').any(|x| x == converted.text) {
+                losses.entry(LossKind::Layer).or_insert_with(Vec::new).push(event.id);
+            } else {
+                cue.text.push('\n');
+                cue.text.push_str(&converted.text);
+            }
+            continue;
+        }
+        by_time.insert(key, cues.len());
+        cues.push((srt::Cue {
+            start: event.start,
+            end: event.end,
+            text: converted.text,
+        }, converted.top));
+    }
+
+    let cues: Vec<srt::Cue> = cues.into_iter()
+        .map(|(mut cue, top)| {
+            if top {
+                cue.text.insert_str(0, "{\\an8}");
+            }
+            cue
+        })
+        .collect();
+    let report = DowngradeReport {
+        cues: cues.len(),
+        losses: losses.into_iter()
+            .map(|(kind, event_ids)| Loss { kind, event_ids })
+            .collect(),
+    };
+    (cues, report)
+}
+
+/// The text of a cue that is italic throughout, line by line or as a
+/// whole, without its `<i>` tags
+fn strip_full_italics(text: &str) -> Option<String> {
+    fn unwrap(text: &str) -> Option<&str> {
+        let text = text.trim();
+        let lower = text.to_ascii_lowercase();
+        let inner = lower.strip_prefix("<i>")?.strip_suffix("</i>")?;
+        if inner.contains("<i>") || inner.contains("</i>") {
+            return None;
+        }
+        Some(&text[3..text.len() - 4])
+    }
+    if let Some(inner) = unwrap(text) {
+        return Some(inner.to_owned());
+    }
+    text.split('\n')
+        .map(unwrap)
+        .collect::<Option<Vec<_>>>()
+        .map(|x| x.join("\n"))
+}
+
+/// `#RRGGBB` as an ASS colour, `&HBBGGRR&`
+fn ass_colour(tag: &str) -> Option<String> {
+    let start = tag.find('#')? + 1;
+    let hex = tag.get(start..start + 6)?;
+    if !hex.bytes().all(|x| x.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("&H{}{}{}&", &hex[4..6], &hex[2..4], &hex[0..2]).to_ascii_uppercase())
+}
+
+/// Formatting tags as override tags; anything else that looks like a tag
+/// is left as text, since SRT has no escaping and `<` may be meant
+fn tags_to_ass(text: &str) -> String {
+    const SIMPLE: [(&str, &str); 8] = [
+        ("<i>", "{\\i1}"), ("</i>", "{\\i0}"), ("<b>", "{\\b1}"), ("</b>", "{\\b0}"),
+        ("<u>", "{\\u1}"), ("</u>", "{\\u0}"), ("<s>", "{\\s1}"), ("</s>", "{\\s0}"),
+    ];
+    // ASCII lowercasing keeps byte offsets identical between the two
+    let lower = text.to_ascii_lowercase();
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while let Some(offset) = lower[i..].find('<') {
+        let at = i + offset;
+        result.push_str(&text[i..at]);
+        let rest = &lower[at..];
+        if let Some((tag, ass)) = SIMPLE.iter().find(|(x, _)| rest.starts_with(x)) {
+            result.push_str(ass);
+            i = at + tag.len();
+        } else if rest.starts_with("<font") && let Some(end) = rest.find('>') {
+            if let Some(colour) = ass_colour(&rest[..end]) {
+                result.push_str(&format!("{{\\c{colour}}}"));
+            }
+            i = at + end + 1;
+        } else if rest.starts_with("</font>") {
+            result.push_str("{\\c}");
+            i = at + "</font>".len();
+        } else {
+            result.push('<');
+            i = at + 1;
+        }
+    }
+    result.push_str(&text[i..]);
+    result
+}
+
+/// A document read from SRT, `SubtitleFormat::Srt`, as SRT again
+pub fn write_srt(document: &Document) -> String {
+    srt::write(&to_srt(document, SrtRules::ROUND_TRIP).0)
+}
+
+/// Play resolution for converted files without a template, which the
+/// default style's sizes suit
+pub const DEFAULT_PLAY_RES: (&str, &str) = ("1920", "1080");
+
+/// `from_srt` on a default style
+pub fn from_srt_plain(cues: Vec<srt::Cue>) -> Document {
+    let template = Style { name: "Default".to_owned(), ass_fields: Vec::new() };
+    from_srt(cues, &template, vec![
+        ("PlayResX".to_owned(), DEFAULT_PLAY_RES.0.to_owned()),
+        ("PlayResY".to_owned(), DEFAULT_PLAY_RES.1.to_owned()),
+    ])
+}
+
+/// A document in ASS whose events use `template`, or variants of it for
+/// the italic and top-placed cues. `script_info` should give the play
+/// resolution that the template's sizes are meant for.
+pub fn from_srt(
+    cues: Vec<srt::Cue>, template: &Style, script_info: Vec<(String, String)>,
+) -> Document {
+    let mut document = Document::new(SubtitleFormat::Ass);
+    document.script_info = script_info;
+    document.styles.push(template.clone());
+    for cue in cues {
+        let (text, top) = match cue.text.strip_prefix("{\\an8}") {
+            Some(x) => (x, true),
+            None => (cue.text.as_str(), false),
+        };
+        let (text, italic) = match strip_full_italics(text) {
+            Some(x) => (x, true),
+            None => (text.to_owned(), false),
+        };
+
+        let mut name = template.name.clone();
+        if top {
+            name.push_str(" Top");
+        }
+        if italic {
+            name.push_str(" Italic");
+        }
+        if !document.styles.iter().any(|x| x.name == name) {
+            let mut style = template.clone();
+            style.name.clone_from(&name);
+            if top {
+                ass::set_style_field(&mut style, "alignment", "8".to_owned());
+            }
+            if italic {
+                ass::set_style_field(&mut style, "italic", "-1".to_owned());
+            }
+            document.styles.push(style);
+        }
+        document.push_event(cue.start, cue.end, &name, tags_to_ass(&text));
+    }
+    document
+}
@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::TextFormat;
+use crate::media::units::Seconds;
+use crate::subtitle::interval::IntervalIndex;
+use crate::subtitle::markers::Marker;
+use crate::subtitle::regions::Region;
+use crate::subtitle::takes::Take;
+use crate::subtitle::words::Word;
+use crate::subtitle::positioning::{Letterbox, PositioningPolicy};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum SubtitleFormat {
+    Ass,
+    Sami,
+    MicroDvd,
+    /// read from SubRip and held as ASS, and written back as SubRip; see
+    /// `convert::write_srt`
+    Srt,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+#[ts(rename = "SubtitleStyleInfo")]
+pub struct Style {
+    pub name: String,
+    /// the remaining fields of an ASS style line in canonical V4+ order;
+    /// empty for styles that didn't come from ASS
+    pub ass_fields: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+#[ts(rename = "SubtitleCue")]
+pub struct Event {
+    pub id: u32,
+    pub start: Seconds,
+    pub end: Seconds,
+    pub style: Arc<str>,
+    pub actor: Arc<str>,
+    pub effect: Arc<str>,
+    pub layer: i32,
+    /// left, right, vertical; 0 means using the style's
+    pub margins: (i32, i32, i32),
+    pub is_comment: bool,
+    /// lines are separated by `\n`
+    pub text: String,
+}
+
+/// Something the parser had to skip or guess. Parsing never fails because
+/// of these; they are reported so the user knows what was lost.
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ParseIssue {
+    /// 1-based; 0 if the issue isn't tied to a line
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    pub format: SubtitleFormat,
+    /// how the file was stored; used again when saving
+    pub text_format: TextFormat,
+    /// for frame-based formats
+    pub framerate: Option<f64>,
+    /// whether the framerate was written in the file itself
+    pub framerate_declared: bool,
+    /// `[Script Info]` of ASS files, in order
+    pub script_info: Vec<(String, String)>,
+    /// sections we don't interpret (fonts, graphics...), kept verbatim
+    pub extra_sections: Vec<(String, Vec<String>)>,
+    /// the headers of an ASS file's sections in the order they came, so
+    /// that they are written back in it; see `ass::write`
+    #[serde(default)]
+    pub section_order: Vec<String>,
+    pub styles: Vec<Style>,
+    pub events: Vec<Event>,
+    #[serde(default)]
+    pub positioning: PositioningPolicy,
+    /// of the video the document is timed against, once detected
+    #[serde(default)]
+    pub letterbox: Option<Letterbox>,
+    /// sorted by time
+    #[serde(default)]
+    pub markers: Vec<Marker>,
+    /// labelled audio, sorted by start
+    #[serde(default)]
+    pub regions: Vec<Region>,
+    /// recorded voice-over, by when they were recorded
+    #[serde(default)]
+    pub takes: Vec<Take>,
+    /// as recognized, for documents made from a transcript; by event, in
+    /// the order they were said
+    #[serde(default)]
+    pub words: Vec<Word>,
+    pub(super) next_event_id: u32,
+    #[serde(default)]
+    pub(super) next_marker_id: u32,
+    #[serde(default)]
+    pub(super) next_region_id: u32,
+    #[serde(default)]
+    pub(super) next_take_id: u32,
+    /// Style, actor and effect strings repeat across most events; sharing
+    /// them keeps huge karaoke scripts from multiplying their memory use
+    #[serde(skip)]
+    interned: HashSet<Arc<str>>,
+    /// built on first query; must be reset whenever `events` changes
+    #[serde(skip)]
+    index: Option<IntervalIndex>,
+}
+
+/// By hand, as the index is left to be built again
+impl Clone for Document {
+    fn clone(&self) -> Document {
+        Document {
+            format: self.format,
+            text_format: self.text_format.clone(),
+            framerate: self.framerate,
+            framerate_declared: self.framerate_declared,
+            script_info: self.script_info.clone(),
+            extra_sections: self.extra_sections.clone(),
+            section_order: self.section_order.clone(),
+            styles: self.styles.clone(),
+            events: self.events.clone(),
+            positioning: self.positioning,
+            letterbox: self.letterbox,
+            markers: self.markers.clone(),
+            regions: self.regions.clone(),
+            takes: self.takes.clone(),
+            words: self.words.clone(),
+            next_event_id: self.next_event_id,
+            next_marker_id: self.next_marker_id,
+            next_region_id: self.next_region_id,
+            next_take_id: self.next_take_id,
+            interned: self.interned.clone(),
+            index: None,
+        }
+    }
+}
+
+impl Document {
+    pub fn new(format: SubtitleFormat) -> Document {
+        Document {
+            format,
+            text_format: TextFormat::default(),
+            framerate: None,
+            framerate_declared: false,
+            script_info: Vec::new(),
+            extra_sections: Vec::new(),
+            section_order: Vec::new(),
+            styles: Vec::new(),
+            events: Vec::new(),
+            positioning: PositioningPolicy::Free,
+            letterbox: None,
+            markers: Vec::new(),
+            regions: Vec::new(),
+            takes: Vec::new(),
+            words: Vec::new(),
+            next_event_id: 0,
+            next_marker_id: 0,
+            next_region_id: 0,
+            next_take_id: 0,
+            interned: HashSet::new(),
+            index: None,
+        }
+    }
+
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(x) = self.interned.get(s) {
+            return x.clone();
+        }
+        let x: Arc<str> = Arc::from(s);
+        self.interned.insert(x.clone());
+        x
+    }
+
+    pub fn events_changed(&mut self) {
+        self.index = None;
+    }
+
+    /// A copy with everything timed moved `by` later, for writing the
+    /// document in another time, such as a program's timecode
+    pub fn shifted(&self, by: Seconds) -> Document {
+        let at = |x: Seconds| Seconds(x.0 + by.0);
+        let mut d = self.clone();
+        for x in &mut d.events {
+            (x.start, x.end) = (at(x.start), at(x.end));
+        }
+        for x in &mut d.markers {
+            x.time = at(x.time);
+        }
+        for x in &mut d.regions {
+            (x.start, x.end) = (at(x.start), at(x.end));
+        }
+        // takes are timed from their events
+        for x in &mut d.words {
+            (x.start, x.end) = (at(x.start), at(x.end));
+        }
+        d
+    }
+
+    /// Shares the strings of a deserialized document again
+    pub fn reintern(&mut self) {
+        let mut events = std::mem::take(&mut self.events);
+        for event in &mut events {
+            event.style = self.intern(&event.style);
+            event.actor = self.intern(&event.actor);
+            event.effect = self.intern(&event.effect);
+        }
+        self.events = events;
+    }
+
+    /// Events overlapping `[start, end)`, in document order; with `start ==
+    /// end`, the events visible at that instant
+    pub fn events_in(&mut self, start: Seconds, end: Seconds) -> Vec<&Event> {
+        let events = &self.events;
+        let index = self.index.get_or_insert_with(|| IntervalIndex::build(events));
+        index.query(start.0, end.0).into_iter()
+            .map(|i| &self.events[i])
+            .collect()
+    }
+
+    /// Creates the style if it doesn't exist yet
+    pub fn ensure_style(&mut self, name: &str) {
+        if !self.styles.iter().any(|x| x.name == name) {
+            self.styles.push(Style { name: name.to_owned(), ass_fields: Vec::new() });
+        }
+    }
+
+    pub fn push_event(&mut self, start: Seconds, end: Seconds, style: &str, text: String) -> u32 {
+        self.ensure_style(style);
+        let id = self.next_event_id;
+        self.next_event_id += 1;
+        self.events_changed();
+        let style = self.intern(style);
+        let empty = self.intern("");
+        self.events.push(Event {
+            id, start, end, style,
+            actor: empty.clone(),
+            effect: empty,
+            layer: 0,
+            margins: (0, 0, 0),
+            is_comment: false,
+            text,
+        });
+        id
+    }
+}
+
+pub struct ParseResult {
+    pub document: Document,
+    pub issues: Vec<ParseIssue>,
+}
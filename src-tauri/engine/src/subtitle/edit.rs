@@ -0,0 +1,254 @@
+//! Changes to a document, as small serializable operations. Autosave journals
+//! these instead of rewriting the whole document.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::{Document, Event};
+use crate::subtitle::markers::Marker;
+use crate::subtitle::positioning::{Letterbox, PositioningPolicy};
+use crate::subtitle::regions::Region;
+use crate::subtitle::takes::Take;
+use crate::subtitle::words::Word;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "op")]
+#[ts(export)]
+pub enum Edit {
+    /// The new event gets the next free id
+    #[serde(rename_all = "camelCase")]
+    Insert {
+        /// position among the events; clamped to the end
+        at: usize,
+        start: Seconds,
+        end: Seconds,
+        style: String,
+        text: String,
+    },
+    /// Fields that are absent are left as they are
+    #[serde(rename_all = "camelCase")]
+    Update {
+        id: u32,
+        start: Option<Seconds>,
+        end: Option<Seconds>,
+        style: Option<String>,
+        actor: Option<String>,
+        /// left, right, vertical; 0 means using the style's
+        margins: Option<(i32, i32, i32)>,
+        text: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Remove { id: u32 },
+    /// The event becomes the first piece; the others are copies of it, with
+    /// the next free ids, placed right after it
+    #[serde(rename_all = "camelCase")]
+    Split { id: u32, pieces: Vec<SplitPiece> },
+    /// The first of the events in document order becomes one spanning them
+    /// all, with `text`; the others are removed
+    #[serde(rename_all = "camelCase")]
+    Merge { ids: Vec<u32>, text: String },
+    /// Puts back an event exactly as it was, id and all; for undoing
+    #[serde(rename_all = "camelCase")]
+    Restore {
+        /// position among the events; clamped to the end
+        at: usize,
+        event: Event,
+    },
+    /// Puts `words` in place of the words of event `event_id`
+    #[serde(rename_all = "camelCase")]
+    SetWords { event_id: u32, words: Vec<Word> },
+    /// Adds the marker, or puts it in place of the one with its id. Markers
+    /// are changed by the methods in `markers`, and journaled as this
+    /// afterwards.
+    #[serde(rename_all = "camelCase")]
+    SetMarker { marker: Marker },
+    #[serde(rename_all = "camelCase")]
+    RemoveMarker { id: u32 },
+    /// Adds the region, or puts it in place of the one with its id. Regions
+    /// are changed by the methods in `regions`, and journaled as this
+    /// afterwards.
+    #[serde(rename_all = "camelCase")]
+    SetRegion { region: Region },
+    #[serde(rename_all = "camelCase")]
+    RemoveRegion { id: u32 },
+    /// Adds the take, or puts it in place of the one with its id. Takes are
+    /// changed by the methods in `takes`, and journaled as this afterwards.
+    #[serde(rename_all = "camelCase")]
+    SetTake { take: Take },
+    #[serde(rename_all = "camelCase")]
+    RemoveTake { id: u32 },
+    /// Sets where dialogue goes on letterboxed video, and the letterbox if
+    /// one is given; see `positioning`
+    #[serde(rename_all = "camelCase")]
+    SetPositioning { policy: PositioningPolicy, letterbox: Option<Letterbox> },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SplitPiece {
+    pub start: Seconds,
+    pub end: Seconds,
+    pub text: String,
+}
+
+impl Document {
+    pub fn apply(&mut self, edit: &Edit) -> Result<(), String> {
+        match edit {
+            Edit::Insert { at, start, end, style, text } => {
+                self.push_event(*start, *end, style, text.clone());
+                let event = self.events.pop().unwrap();
+                let at = (*at).min(self.events.len());
+                self.events.insert(at, event);
+            }
+            Edit::Update { id, start, end, style, actor, margins, text } => {
+                if let Some(style) = style {
+                    self.ensure_style(style);
+                }
+                let style = style.as_deref().map(|x| self.intern(x));
+                let actor = actor.as_deref().map(|x| self.intern(x));
+                let event = self.events.iter_mut()
+                    .find(|x| x.id == *id)
+                    .ok_or(format!("no event with id {id}"))?;
+                if let Some(x) = start { event.start = *x; }
+                if let Some(x) = end { event.end = *x; }
+                if let Some(x) = style { event.style = x; }
+                if let Some(x) = actor { event.actor = x; }
+                if let Some(x) = margins { event.margins = *x; }
+                if let Some(x) = text { event.text.clone_from(x); }
+            }
+            Edit::Remove { id } => {
+                let position = self.events.iter()
+                    .position(|x| x.id == *id)
+                    .ok_or(format!("no event with id {id}"))?;
+                self.events.remove(position);
+                self.words.retain(|x| x.event_id != *id);
+            }
+            Edit::Split { id, pieces } => {
+                let position = self.events.iter()
+                    .position(|x| x.id == *id)
+                    .ok_or(format!("no event with id {id}"))?;
+                let Some((first, rest)) = pieces.split_first() else {
+                    return Err("no pieces to split into".to_owned());
+                };
+                for (i, piece) in rest.iter().enumerate() {
+                    let mut event = self.events[position].clone();
+                    event.id = self.next_event_id;
+                    self.next_event_id += 1;
+                    event.start = piece.start;
+                    event.end = piece.end;
+                    event.text.clone_from(&piece.text);
+                    self.events.insert(position + 1 + i, event);
+                }
+                let event = &mut self.events[position];
+                event.start = first.start;
+                event.end = first.end;
+                event.text.clone_from(&first.text);
+                // each word goes to the piece it was said in
+                let pieces = &self.events[position..=position + rest.len()];
+                for word in self.words.iter_mut().filter(|x| x.event_id == *id) {
+                    if let Some(piece) = pieces.iter().rev().find(|x| x.start.0 <= word.start.0) {
+                        word.event_id = piece.id;
+                    }
+                }
+            }
+            Edit::Merge { ids, text } => {
+                let mut positions = ids.iter()
+                    .map(|id| self.events.iter()
+                        .position(|x| x.id == *id)
+                        .ok_or(format!("no event with id {id}")))
+                    .collect::<Result<Vec<_>, _>>()?;
+                positions.sort_unstable();
+                positions.dedup();
+                if positions.len() < 2 {
+                    return Err("nothing to merge".to_owned());
+                }
+                let merged = &self.events;
+                let start = positions.iter().map(|&i| merged[i].start)
+                    .min_by(|a, b| a.0.total_cmp(&b.0)).unwrap();
+                let end = positions.iter().map(|&i| merged[i].end)
+                    .max_by(|a, b| a.0.total_cmp(&b.0)).unwrap();
+                let event = &mut self.events[positions[0]];
+                event.start = start;
+                event.end = end;
+                event.text.clone_from(text);
+                let kept = event.id;
+                let mut removed = Vec::with_capacity(positions.len() - 1);
+                for &i in positions[1..].iter().rev() {
+                    removed.push(self.events.remove(i).id);
+                }
+                for word in self.words.iter_mut().filter(|x| removed.contains(&x.event_id)) {
+                    word.event_id = kept;
+                }
+            }
+            Edit::Restore { at, event } => {
+                if self.events.iter().any(|x| x.id == event.id) {
+                    return Err(format!("there is already an event with id {}", event.id));
+                }
+                let next = event.id.checked_add(1)
+                    .ok_or(format!("event id {} out of range", event.id))?;
+                self.ensure_style(&event.style);
+                let mut event = event.clone();
+                event.style = self.intern(&event.style);
+                event.actor = self.intern(&event.actor);
+                event.effect = self.intern(&event.effect);
+                self.next_event_id = self.next_event_id.max(next);
+                let at = (*at).min(self.events.len());
+                self.events.insert(at, event);
+            }
+            Edit::SetWords { event_id, words } => {
+                self.replace_words(*event_id, words.clone());
+                return Ok(());
+            }
+            Edit::SetMarker { marker } => {
+                let next = marker.id.checked_add(1)
+                    .ok_or(format!("marker id {} out of range", marker.id))?;
+                self.next_marker_id = self.next_marker_id.max(next);
+                self.markers.retain(|x| x.id != marker.id);
+                let at = self.markers.partition_point(|x| x.time.0 <= marker.time.0);
+                self.markers.insert(at, marker.clone());
+                return Ok(());
+            }
+            Edit::RemoveMarker { id } => {
+                self.remove_marker(*id)?;
+                return Ok(());
+            }
+            Edit::SetRegion { region } => {
+                let next = region.id.checked_add(1)
+                    .ok_or(format!("region id {} out of range", region.id))?;
+                self.next_region_id = self.next_region_id.max(next);
+                self.regions.retain(|x| x.id != region.id);
+                let at = self.regions.partition_point(|x| x.start.0 <= region.start.0);
+                self.regions.insert(at, region.clone());
+                return Ok(());
+            }
+            Edit::RemoveRegion { id } => {
+                self.remove_region(*id)?;
+                return Ok(());
+            }
+            Edit::SetTake { take } => {
+                let next = take.id.checked_add(1)
+                    .ok_or(format!("take id {} out of range", take.id))?;
+                self.next_take_id = self.next_take_id.max(next);
+                match self.takes.iter_mut().find(|x| x.id == take.id) {
+                    Some(x) => x.clone_from(take),
+                    None => self.takes.push(take.clone()),
+                }
+                return Ok(());
+            }
+            Edit::RemoveTake { id } => {
+                self.remove_take(*id)?;
+                return Ok(());
+            }
+            Edit::SetPositioning { policy, letterbox } => {
+                self.positioning = *policy;
+                if letterbox.is_some() {
+                    self.letterbox = *letterbox;
+                }
+                return Ok(());
+            }
+        }
+        self.events_changed();
+        Ok(())
+    }
+}
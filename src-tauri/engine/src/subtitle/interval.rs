@@ -0,0 +1,76 @@
+//! An implicit interval tree: events sorted by start time, laid out as a
+//! balanced binary tree over the sorted array, with every node knowing the
+//! latest end time below it. Queries only descend into subtrees that can
+//! contain a hit, which makes them O(log n + k).
+
+use crate::subtitle::document::Event;
+
+struct Entry {
+    start: f64,
+    end: f64,
+    /// index into `Document::events`
+    position: usize,
+    /// latest `end` in the subtree rooted here
+    max_end: f64,
+}
+
+pub struct IntervalIndex {
+    entries: Vec<Entry>,
+}
+
+impl IntervalIndex {
+    pub fn build(events: &[Event]) -> IntervalIndex {
+        let mut entries: Vec<Entry> = events.iter().enumerate()
+            .map(|(position, x)| Entry {
+                start: x.start.0, end: x.end.0, position,
+                max_end: x.end.0,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.start.total_cmp(&b.start));
+        let mut index = IntervalIndex { entries };
+        index.compute_max_end(0, index.entries.len());
+        index
+    }
+
+    fn compute_max_end(&mut self, lo: usize, hi: usize) -> f64 {
+        if lo >= hi {
+            return f64::NEG_INFINITY;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = self.compute_max_end(lo, mid);
+        let right = self.compute_max_end(mid + 1, hi);
+        let entry = &mut self.entries[mid];
+        entry.max_end = entry.end.max(left).max(right);
+        entry.max_end
+    }
+
+    /// Positions of events overlapping `[start, end)`, in document order. An
+    /// event is visible at `t` if `event.start <= t < event.end`, so use
+    /// `end == start` for a point query.
+    pub fn query(&self, start: f64, end: f64) -> Vec<usize> {
+        let mut result = Vec::new();
+        self.collect(0, self.entries.len(), start, end, &mut result);
+        result.sort_unstable();
+        result
+    }
+
+    fn collect(&self, lo: usize, hi: usize, start: f64, end: f64, result: &mut Vec<usize>) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let entry = &self.entries[mid];
+        if entry.max_end <= start {
+            return;
+        }
+        self.collect(lo, mid, start, end, result);
+        // everything to the right starts no earlier than this one
+        if entry.start > end || (entry.start == end && end > start) {
+            return;
+        }
+        if entry.end > start {
+            result.push(entry.position);
+        }
+        self.collect(mid + 1, hi, start, end, result);
+    }
+}
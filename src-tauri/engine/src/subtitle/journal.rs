@@ -0,0 +1,293 @@
+//! Crash-safe autosave. A full snapshot of the document is written once;
+//! after that every edit is appended to a journal next to it, and recovery
+//! replays the journal onto the snapshot. The journal is folded into a new
+//! snapshot once it grows long.
+//!
+//! Both files carry a generation number. A snapshot is replaced before its
+//! journal is restarted, so a crash in between leaves a journal from an older
+//! generation, whose edits are already in the snapshot and are ignored. A
+//! journal created over old files goes on from their generation, never
+//! back to one an old journal might still carry.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::subtitle::document::Document;
+use crate::subtitle::edit::Edit;
+
+/// Journal entries after which a new snapshot is written
+const COMPACT_THRESHOLD: usize = 5000;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    generation: u64,
+}
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    generation: u64,
+    document: &'a Document,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    generation: u64,
+    document: Document,
+}
+
+/// Of a snapshot, without the document
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    generation: u64,
+}
+
+pub struct Journal {
+    snapshot_path: PathBuf,
+    journal_path: PathBuf,
+    file: File,
+    generation: u64,
+    entries: usize,
+}
+
+fn journal_path(snapshot_path: &Path) -> PathBuf {
+    let mut path = snapshot_path.as_os_str().to_owned();
+    path.push(".journal");
+    PathBuf::from(path)
+}
+
+fn write_snapshot(path: &Path, generation: u64, document: &Document) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    serde_json::to_writer(&mut writer, &SnapshotRef { generation, document })?;
+    writer.into_inner()?.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+fn start_journal(path: &Path, generation: u64) -> io::Result<File> {
+    let mut file = File::create(path)?;
+    serde_json::to_writer(&mut file, &Header { generation })?;
+    file.write_all(b"\n")?;
+    Ok(file)
+}
+
+/// The newest generation of the files already at `snapshot_path` and its
+/// journal, if any can be read
+fn previous_generation(snapshot_path: &Path, journal_path: &Path) -> Option<u64> {
+    let snapshot = File::open(snapshot_path).ok()
+        .and_then(|x| serde_json::from_reader::<_, SnapshotHeader>(BufReader::new(x)).ok())
+        .map(|x| x.generation);
+    let journal = File::open(journal_path).ok()
+        .and_then(|x| BufReader::new(x).lines().next()?.ok())
+        .and_then(|x| serde_json::from_str::<Header>(&x).ok())
+        .map(|x| x.generation);
+    snapshot.max(journal)
+}
+
+impl Journal {
+    /// Writes a snapshot of `document` to `path` and starts an empty journal,
+    /// a generation after any already there
+    pub fn create(path: &Path, document: &Document) -> io::Result<Journal> {
+        let snapshot_path = path.to_owned();
+        let journal_path = journal_path(path);
+        let generation = previous_generation(&snapshot_path, &journal_path)
+            .map_or(0, |x| x + 1);
+        write_snapshot(&snapshot_path, generation, document)?;
+        let file = start_journal(&journal_path, generation)?;
+        Ok(Journal { snapshot_path, journal_path, file, generation, entries: 0 })
+    }
+
+    /// Records edits that have already been applied to `document`
+    pub fn append(&mut self, edits: &[Edit], document: &Document) -> io::Result<()> {
+        if self.entries + edits.len() > COMPACT_THRESHOLD {
+            return self.compact(document);
+        }
+        let mut buf = Vec::new();
+        for edit in edits {
+            serde_json::to_writer(&mut buf, edit)?;
+            buf.push(b'\n');
+        }
+        self.file.write_all(&buf)?;
+        self.entries += edits.len();
+        Ok(())
+    }
+
+    fn compact(&mut self, document: &Document) -> io::Result<()> {
+        let generation = self.generation + 1;
+        write_snapshot(&self.snapshot_path, generation, document)?;
+        self.file = start_journal(&self.journal_path, generation)?;
+        self.generation = generation;
+        self.entries = 0;
+        Ok(())
+    }
+
+    /// Where the snapshot is; the journal is next to it
+    pub fn path(&self) -> &Path {
+        &self.snapshot_path
+    }
+
+    /// Removes both files, for when the document has been saved properly
+    pub fn discard(self) -> io::Result<()> {
+        drop(self.file);
+        fs::remove_file(&self.journal_path)?;
+        fs::remove_file(&self.snapshot_path)
+    }
+}
+
+pub struct Recovered {
+    pub document: Document,
+    /// edits replayed from the journal
+    pub replayed: usize,
+    /// the last entry was cut off by the crash and was dropped
+    pub truncated: bool,
+}
+
+pub fn recover(path: &Path) -> io::Result<Recovered> {
+    let Snapshot { generation, mut document } =
+        serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    document.reintern();
+    let mut result = Recovered { document, replayed: 0, truncated: false };
+
+    let file = match File::open(journal_path(path)) {
+        Ok(x) => x,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(result),
+        Err(e) => return Err(e),
+    };
+    // as bytes, since a cut-off entry can end inside a character
+    let mut lines = BufReader::new(file).split(b'\n');
+    let current = lines.next().transpose()?
+        .and_then(|x| serde_json::from_slice::<Header>(&x).ok())
+        .is_some_and(|x| x.generation == generation);
+    if !current {
+        return Ok(result);
+    }
+    for line in lines {
+        let Ok(edit) = serde_json::from_slice::<Edit>(&line?) else {
+            // only the last write can be incomplete
+            result.truncated = true;
+            break;
+        };
+        result.document.apply(&edit).map_err(io::Error::other)?;
+        result.replayed += 1;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::units::Seconds;
+    use crate::subtitle::document::SubtitleFormat;
+
+    fn insert(text: &str) -> Edit {
+        Edit::Insert {
+            at: usize::MAX,
+            start: Seconds(1.0),
+            end: Seconds(2.0),
+            style: "Default".to_owned(),
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn recovers_after_resnapshot() {
+        let path = std::env::temp_dir()
+            .join(format!("subtle-journal-{}.json", std::process::id()));
+        let mut document = Document::new(SubtitleFormat::Ass);
+        let mut journal = Journal::create(&path, &document).unwrap();
+        let edit = insert("one");
+        document.apply(&edit).unwrap();
+        journal.append(&[edit], &document).unwrap();
+        let old_journal = fs::read(journal_path(&path)).unwrap();
+        drop(journal);
+
+        // recovering starts a new snapshot with the edit in it...
+        let recovered = recover(&path).unwrap();
+        assert_eq!(recovered.replayed, 1);
+        let journal = Journal::create(&path, &recovered.document).unwrap();
+        drop(journal);
+        // ...and a crash before its journal was restarted leaves the old one
+        fs::write(journal_path(&path), old_journal).unwrap();
+
+        let recovered = recover(&path).unwrap();
+        assert_eq!(recovered.replayed, 0);
+        assert_eq!(recovered.document.events.len(), 1);
+        let _ = fs::remove_file(journal_path(&path));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn recovers_from_an_entry_cut_inside_a_character() {
+        let path = std::env::temp_dir()
+            .join(format!("subtle-journal-cut-{}.json", std::process::id()));
+        let mut document = Document::new(SubtitleFormat::Ass);
+        let mut journal = Journal::create(&path, &document).unwrap();
+        for text in ["一", "二"] {
+            let edit = insert(text);
+            document.apply(&edit).unwrap();
+            journal.append(&[edit], &document).unwrap();
+        }
+        drop(journal);
+        // the crash cuts the last entry in the middle of `二`
+        let mut data = fs::read(journal_path(&path)).unwrap();
+        let cut = data.windows(3).rposition(|x| x == "二".as_bytes()).unwrap() + 1;
+        data.truncate(cut);
+        fs::write(journal_path(&path), data).unwrap();
+
+        let recovered = recover(&path).unwrap();
+        assert_eq!(recovered.replayed, 1);
+        assert!(recovered.truncated);
+        assert_eq!(recovered.document.events[0].text, "一");
+        let _ = fs::remove_file(journal_path(&path));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn restore_rejects_the_last_id() {
+        let mut document = Document::new(SubtitleFormat::Ass);
+        document.apply(&insert("one")).unwrap();
+        // as a tampered journal could have it
+        let mut event = document.events[0].clone();
+        event.id = u32::MAX;
+        assert!(document.apply(&Edit::Restore { at: 1, event }).is_err());
+        assert_eq!(document.events.len(), 1);
+        assert_eq!(document.next_event_id, 1);
+    }
+
+    #[test]
+    fn replays_takes() {
+        let path = std::env::temp_dir()
+            .join(format!("subtle-journal-takes-{}.json", std::process::id()));
+        let mut document = Document::new(SubtitleFormat::Ass);
+        document.apply(&insert("one")).unwrap();
+        let mut journal = Journal::create(&path, &document).unwrap();
+        // as the commands journal them
+        let set = |document: &Document| document.takes.iter()
+            .map(|x| Edit::SetTake { take: x.clone() })
+            .collect::<Vec<_>>();
+        let first = document.add_take(0, "a.wav".to_owned(), Seconds(-1.0), Seconds(3.0), true)
+            .unwrap();
+        journal.append(&set(&document), &document).unwrap();
+        let second = document.add_take(0, "b.wav".to_owned(), Seconds(-1.0), Seconds(3.0), true)
+            .unwrap();
+        journal.append(&set(&document), &document).unwrap();
+        document.update_take(second, None, None, Some(-6.0)).unwrap();
+        journal.append(&set(&document), &document).unwrap();
+        document.remove_take(first).unwrap();
+        journal.append(&[Edit::RemoveTake { id: first }], &document).unwrap();
+        drop(journal);
+
+        let recovered = recover(&path).unwrap().document;
+        assert_eq!(recovered.takes.len(), 1);
+        assert_eq!(recovered.takes[0].id, second);
+        assert!(recovered.takes[0].selected);
+        assert!((recovered.takes[0].gain + 6.0).abs() < 1e-9);
+        assert_eq!(recovered.next_take_id, second + 1);
+        let _ = fs::remove_file(journal_path(&path));
+        let _ = fs::remove_file(&path);
+    }
+}
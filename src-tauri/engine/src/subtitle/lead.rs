@@ -0,0 +1,93 @@
+//! Lead-in and lead-out from the audio: each cue starts a little before
+//! the speech it carries begins and ends a little after it stops, rather
+//! than being moved by a fixed offset, without running across a cut or
+//! into the cues next to it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::Document;
+use crate::subtitle::edit::Edit;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LeadConfig {
+    /// before speech starts
+    pub lead_in: Seconds,
+    /// after it stops
+    pub lead_out: Seconds,
+    /// how far from a cue's edge speech starting or stopping still belongs
+    /// to it
+    pub reach: Seconds,
+    /// kept between a cue and the ones before and after it
+    pub min_gap: Seconds,
+}
+
+/// The speech edge in `edges`, which is sorted, nearest to `time` and
+/// within `reach`
+fn nearest(edges: &[f64], time: f64, reach: f64) -> Option<f64> {
+    let i = edges.partition_point(|&x| x < time);
+    [i.checked_sub(1), (i < edges.len()).then_some(i)].into_iter()
+        .flatten()
+        .map(|i| edges[i])
+        .filter(|x| (x - time).abs() <= reach)
+        .min_by(|a, b| (a - time).abs().total_cmp(&(b - time).abs()))
+}
+
+/// Edits retiming the cues of `document` to `speech`, the stretches of it
+/// in the audio; an edge with no speech starting or stopping near it stays
+/// where it is. `cuts` are scene changes, which a lead never crosses.
+pub fn suggest(
+    document: &Document, speech: &[(Seconds, Seconds)], cuts: &[Seconds], config: &LeadConfig,
+) -> Vec<Edit> {
+    let onsets: Vec<f64> = speech.iter().map(|x| x.0.0).collect();
+    let offsets: Vec<f64> = speech.iter().map(|x| x.1.0).collect();
+    let mut cuts: Vec<f64> = cuts.iter().map(|x| x.0).collect();
+    cuts.sort_by(f64::total_cmp);
+
+    let mut events: Vec<_> = document.events.iter().filter(|x| !x.is_comment).collect();
+    events.sort_by(|a, b| a.start.0.total_cmp(&b.start.0));
+
+    let mut edits = Vec::new();
+    // where the cue before ends, after its own edit
+    let mut previous_end = f64::NEG_INFINITY;
+    for (i, event) in events.iter().enumerate() {
+        let next_start = events.get(i + 1).map_or(f64::INFINITY, |x| x.start.0);
+
+        let mut start = event.start.0;
+        if let Some(onset) = nearest(&onsets, start, config.reach.0) {
+            start = onset - config.lead_in.0;
+            if let Some(&cut) = cuts.iter().rev().find(|&&x| start < x && x < onset) {
+                start = cut;
+            }
+            start = start.max(previous_end + config.min_gap.0);
+        }
+
+        let mut end = event.end.0;
+        if let Some(offset) = nearest(&offsets, end, config.reach.0) {
+            end = offset + config.lead_out.0;
+            if let Some(&cut) = cuts.iter().find(|&&x| offset < x && x < end) {
+                end = cut;
+            }
+            end = end.min(next_start - config.min_gap.0);
+        }
+
+        if end <= start {
+            // nothing sensible fits; leave the cue alone
+            previous_end = event.end.0;
+            continue;
+        }
+        previous_end = end;
+        let moved = |a: f64, b: f64| (a - b).abs() >= 0.001;
+        if moved(start, event.start.0) || moved(end, event.end.0) {
+            edits.push(Edit::Update {
+                id: event.id,
+                start: moved(start, event.start.0).then_some(Seconds(start)),
+                end: moved(end, event.end.0).then_some(Seconds(end)),
+                style: None, actor: None, margins: None, text: None,
+            });
+        }
+    }
+    edits
+}
@@ -0,0 +1,72 @@
+//! Markers on the timeline: notes like "check this sign" attached to a time,
+//! kept with the document.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::Document;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Marker {
+    pub id: u32,
+    pub time: Seconds,
+    pub label: String,
+    /// a CSS color, without commas; see `check_color`
+    pub color: String,
+}
+
+/// A color goes between commas in ASS, see `ass::write`, so it can't hold
+/// any itself; `rgb(255 136 0)` says what `rgb(255, 136, 0)` does
+fn check_color(color: &str) -> Result<(), String> {
+    if color.trim().is_empty() || color.contains(',') || color.contains(char::is_control) {
+        return Err(format!("invalid marker color {color:?}; write it without commas"));
+    }
+    Ok(())
+}
+
+impl Document {
+    /// Markers are kept sorted by time
+    pub fn add_marker(
+        &mut self, time: Seconds, label: String, color: String,
+    ) -> Result<u32, String> {
+        check_color(&color)?;
+        let id = self.next_marker_id;
+        self.next_marker_id += 1;
+        let at = self.markers.partition_point(|x| x.time.0 <= time.0);
+        self.markers.insert(at, Marker { id, time, label, color });
+        Ok(id)
+    }
+
+    pub fn update_marker(
+        &mut self, id: u32,
+        time: Option<Seconds>, label: Option<String>, color: Option<String>,
+    ) -> Result<(), String> {
+        let position = self.markers.iter().position(|x| x.id == id)
+            .ok_or(format!("no marker with id {id}"))?;
+        if let Some(x) = &color {
+            check_color(x)?;
+        }
+        let mut marker = self.markers.remove(position);
+        if let Some(x) = time { marker.time = x; }
+        if let Some(x) = label { marker.label = x; }
+        if let Some(x) = color { marker.color = x; }
+        let at = self.markers.partition_point(|x| x.time.0 <= marker.time.0);
+        self.markers.insert(at, marker);
+        Ok(())
+    }
+
+    pub fn remove_marker(&mut self, id: u32) -> Result<(), String> {
+        let position = self.markers.iter().position(|x| x.id == id)
+            .ok_or(format!("no marker with id {id}"))?;
+        self.markers.remove(position);
+        Ok(())
+    }
+
+    /// The first marker strictly after `from`
+    pub fn next_marker(&self, from: Seconds) -> Option<&Marker> {
+        let at = self.markers.partition_point(|x| x.time.0 <= from.0);
+        self.markers.get(at)
+    }
+}
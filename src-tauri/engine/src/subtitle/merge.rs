@@ -0,0 +1,152 @@
+//! Merging events into one, the inverse of `split`: their texts are joined
+//! the way the policy says, and the merged event spans them all, gaps
+//! between them included.
+
+use serde::{Deserialize, Serialize};
+
+use crate::subtitle::document::Document;
+use crate::subtitle::edit::Edit;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum Separator {
+    Space,
+    LineBreak,
+    /// each event a line of its own, starting with a dash unless it has one
+    Dialogue,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MergePolicy {
+    pub separator: Separator,
+    /// Drops the marks that carry a sentence over from one event to the
+    /// next, an ellipsis or a dash at the end of one and the start of the
+    /// other, where they meet; only if the next goes on with the sentence,
+    /// by starting with such a mark or in lower case. Not for dialogue.
+    pub remove_continuation: bool,
+    /// Drops an `{\i0}` ending one event and an `{\i1}` starting the next,
+    /// so that the italics run on
+    pub join_italics: bool,
+}
+
+const TRAILING_MARKS: [&str; 5] = ["...", "…", "--", "—", "–"];
+const LEADING_MARKS: [&str; 4] = ["...", "…", "—", "–"];
+
+/// `text` cut before the override blocks it ends with
+fn trailing_tags(text: &str) -> (&str, &str) {
+    let mut body = text;
+    while body.ends_with('}')
+        && let Some(open) = body.rfind('{')
+    {
+        body = &body[..open];
+    }
+    text.split_at(body.len())
+}
+
+/// `text` cut after the override blocks it starts with
+fn leading_tags(text: &str) -> (&str, &str) {
+    let mut body = text;
+    while body.starts_with('{')
+        && let Some(close) = body.find('}')
+    {
+        body = &body[close + 1..];
+    }
+    text.split_at(text.len() - body.len())
+}
+
+fn strip_trailing_mark(text: &str) -> Option<&str> {
+    let text = text.trim_end();
+    TRAILING_MARKS.iter()
+        .find_map(|x| text.strip_suffix(x))
+        // a hyphen only counts on its own, not ending a word
+        .or_else(|| text.strip_suffix(" -"))
+        .map(str::trim_end)
+}
+
+fn strip_leading_mark(text: &str) -> Option<&str> {
+    LEADING_MARKS.iter()
+        .find_map(|x| text.strip_prefix(x))
+        .map(str::trim_start)
+}
+
+fn join(left: &str, right: &str, policy: &MergePolicy) -> String {
+    let (left_body, left_tags) = trailing_tags(left.trim_end());
+    let (right_tags, right_body) = leading_tags(right.trim_start());
+    let (mut left_body, mut right_body) = (left_body.trim_end(), right_body.trim_start());
+    let (left_tags, right_tags) =
+        if policy.join_italics && left_tags == "{\\i0}" && right_tags == "{\\i1}" {
+            ("", "")
+        } else {
+            (left_tags, right_tags)
+        };
+    if policy.remove_continuation && !matches!(policy.separator, Separator::Dialogue) {
+        let leading = strip_leading_mark(right_body);
+        let goes_on = leading.is_some()
+            || right_body.chars().next().is_some_and(char::is_lowercase);
+        if goes_on {
+            left_body = strip_trailing_mark(left_body).unwrap_or(left_body);
+            right_body = leading.unwrap_or(right_body);
+        }
+    }
+    let separator = match policy.separator {
+        Separator::Space => " ",
+        Separator::LineBreak | Separator::Dialogue => "\n",
+    };
+    format!("{left_body}{left_tags}{separator}{right_tags}{right_body}")
+}
+
+/// A dialogue line: a dash after the leading tags, if it has none
+fn dialogue_line(text: &str) -> String {
+    let (tags, body) = leading_tags(text.trim());
+    if body.starts_with(['-', '–', '—']) {
+        text.trim().to_owned()
+    } else {
+        format!("{tags}- {body}")
+    }
+}
+
+/// The edit that merges events `ids` of `document` by `policy`, and the
+/// edits that undo it
+pub fn merge(
+    document: &Document, ids: &[u32], policy: &MergePolicy,
+) -> Result<(Edit, Vec<Edit>), String> {
+    let mut events = Vec::with_capacity(ids.len());
+    for (position, event) in document.events.iter().enumerate() {
+        if ids.contains(&event.id) {
+            events.push((position, event));
+        }
+    }
+    if let Some(id) = ids.iter().find(|id| !events.iter().any(|(_, x)| x.id == **id)) {
+        return Err(format!("no event with id {id}"));
+    }
+    if events.len() < 2 {
+        return Err("nothing to merge".to_owned());
+    }
+    let (first, rest) = (events[0].1, &events[1..]);
+
+    let texts: Vec<String> = events.iter()
+        .map(|(_, x)| match policy.separator {
+            Separator::Dialogue => dialogue_line(&x.text),
+            _ => x.text.clone(),
+        })
+        .collect();
+    let text = texts[1..].iter()
+        .fold(texts[0].clone(), |merged, x| join(&merged, x, policy));
+
+    let mut undo = vec![Edit::Update {
+        id: first.id,
+        start: Some(first.start),
+        end: Some(first.end),
+        style: None,
+        actor: None,
+        margins: None,
+        text: Some(first.text.clone()),
+    }];
+    // in order, each goes back where it was once those before it are back
+    undo.extend(rest.iter()
+        .map(|(position, event)| Edit::Restore { at: *position, event: (*event).clone() }));
+    Ok((Edit::Merge { ids: ids.to_vec(), text }, undo))
+}
@@ -0,0 +1,142 @@
+//! MicroDVD (.sub): `{start}{end}text`, with times given as frame numbers.
+//! Converting to seconds needs the framerate of the video the file was made
+//! for; many files declare it in a first line like `{1}{1}23.976`.
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::{Document, ParseIssue, ParseResult, SubtitleFormat};
+
+const DEFAULT_STYLE: &str = "default";
+
+struct Line<'a> {
+    start: u64,
+    end: Option<u64>,
+    text: &'a str,
+}
+
+fn parse_line(line: &str) -> Option<Line<'_>> {
+    fn frame_number(s: &str) -> Option<(Option<u64>, &str)> {
+        let s = s.strip_prefix('{')?;
+        let close = s.find('}')?;
+        let number = s[..close].trim();
+        let value = if number.is_empty() { None } else { Some(number.parse().ok()?) };
+        Some((value, &s[close + 1..]))
+    }
+
+    let (start, rest) = frame_number(line.trim_start_matches('\u{feff}').trim_start())?;
+    let (end, text) = frame_number(rest)?;
+    Some(Line { start: start?, end, text })
+}
+
+pub fn detect(source: &str) -> bool {
+    source.lines()
+        .find(|x| !x.trim().is_empty())
+        .is_some_and(|x| parse_line(x).is_some())
+}
+
+/// Looks for the framerate declaration that conventionally occupies the
+/// first cue
+pub fn detect_framerate(source: &str) -> Option<f64> {
+    let line = parse_line(source.lines().find(|x| !x.trim().is_empty())?)?;
+    if line.start > 1 || line.end.is_some_and(|x| x > 1) {
+        return None;
+    }
+    line.text.trim().parse::<f64>().ok().filter(|x| *x > 0.0 && *x < 1000.0)
+}
+
+pub fn parse(source: &str, framerate: f64) -> ParseResult {
+    let mut document = Document::new(SubtitleFormat::MicroDvd);
+    let mut issues = Vec::new();
+    let mut ignored_codes = 0;
+    let mut open_ended = Vec::<usize>::new();
+    let declared = detect_framerate(source);
+    document.framerate = Some(framerate);
+    document.framerate_declared = declared.is_some();
+
+    #[allow(clippy::cast_precision_loss)]
+    let to_seconds = |frame: u64| Seconds(frame as f64 / framerate);
+
+    for (i, raw) in source.lines().enumerate() {
+        if raw.trim().is_empty() {
+            continue;
+        }
+        let Some(line) = parse_line(raw) else {
+            issues.push(ParseIssue {
+                line: i + 1,
+                message: "not a MicroDVD line; skipped".to_owned(),
+            });
+            continue;
+        };
+        if declared.is_some() && document.events.is_empty() && open_ended.is_empty()
+            && line.start <= 1 && line.text.trim().parse::<f64>().is_ok()
+        {
+            // the framerate declaration isn't a cue
+            continue;
+        }
+
+        let text = line.text
+            .split('|')
+            .map(|x| strip_control_codes(x, &mut ignored_codes))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let (start, end) = match line.end {
+            Some(end) if end >= line.start => (to_seconds(line.start), to_seconds(end)),
+            Some(end) => {
+                issues.push(ParseIssue {
+                    line: i + 1,
+                    message: "end frame is before start frame; swapped".to_owned(),
+                });
+                (to_seconds(end), to_seconds(line.start))
+            }
+            None => {
+                open_ended.push(document.events.len());
+                (to_seconds(line.start), to_seconds(line.start))
+            }
+        };
+        document.push_event(start, end, DEFAULT_STYLE, text);
+    }
+
+    // `{start}{}` lasts until the next line starts
+    for index in open_ended {
+        let next = document.events.get(index + 1).map(|x| x.start);
+        let event = &mut document.events[index];
+        event.end = next.unwrap_or(Seconds(event.start.0 + 2.0));
+    }
+
+    if ignored_codes > 0 {
+        issues.push(ParseIssue {
+            line: 0,
+            message: format!("ignored {ignored_codes} formatting codes like {{y:i}}"),
+        });
+    }
+    ParseResult { document, issues }
+}
+
+/// Removes leading `{x:value}` codes from a line of text
+fn strip_control_codes(line: &str, count: &mut usize) -> String {
+    let mut rest = line.trim();
+    while rest.starts_with('{')
+        && let Some(close) = rest.find('}')
+        && rest[..close].contains(':')
+    {
+        *count += 1;
+        rest = rest[close + 1..].trim_start();
+    }
+    rest.to_owned()
+}
+
+pub fn write(document: &Document, framerate: f64) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let to_frame = |time: Seconds| (time.0 * framerate).round().max(0.0) as u64;
+
+    let mut result = String::new();
+    if document.framerate_declared {
+        result.push_str(&format!("{{1}}{{1}}{framerate}\n"));
+    }
+    for event in &document.events {
+        result.push_str(&format!("{{{}}}{{{}}}{}\n",
+            to_frame(event.start), to_frame(event.end),
+            event.text.replace('\n', "|")));
+    }
+    result
+}
@@ -0,0 +1,397 @@
+//! Text in, document out: format detection and the parsers behind it, with
+//! no files, IPC or ffmpeg involved. Files come from anywhere, so whatever
+//! is given here must not panic; the fuzz targets in `fuzz/` hold it to
+//! that.
+
+use serde::Serialize;
+
+use crate::subtitle::document::{ParseIssue, ParseResult, SubtitleFormat};
+use crate::subtitle::{ass, convert, microdvd, sami, srt};
+
+/// How many lines ASS is parsed by between two progress reports
+const PROGRESS_INTERVAL: usize = 10000;
+/// How many lines from the start are looked at to tell the format
+const SNIFF_LINES: usize = 200;
+/// Below this, a file is taken to be in no format we know
+const MIN_CONFIDENCE: f32 = 0.2;
+
+/// What a file holds, by its text rather than its name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum SourceFormat {
+    Ass,
+    Sami,
+    MicroDvd,
+    /// opened as ASS and saved as SRT; see `SubtitleFormat::Srt`
+    Srt,
+}
+
+impl SourceFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ass => "ASS",
+            Self::Sami => "SAMI",
+            Self::MicroDvd => "MicroDVD",
+            Self::Srt => "SubRip",
+        }
+    }
+
+    /// The extensions files in this format usually have
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Ass => &["ass", "ssa"],
+            Self::Sami => &["smi", "sami"],
+            Self::MicroDvd => &["sub", "txt"],
+            Self::Srt => &["srt"],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Detection {
+    pub format: SourceFormat,
+    /// from 0 to 1: 1 when the file starts as the format says it must,
+    /// else how much of its start looks like it
+    pub confidence: f32,
+}
+
+/// How much of `lines` looks like ASS: section headers and the lines
+/// of the sections
+fn ass_score(lines: &[&str]) -> f32 {
+    const HEADERS: [&str; 4] = ["[script info]", "[v4+ styles]", "[v4 styles]", "[events]"];
+    const KEYS: [&str; 4] = ["dialogue:", "comment:", "style:", "format:"];
+    let lower: Vec<String> = lines.iter().map(|x| x.to_ascii_lowercase()).collect();
+    if !lower.iter().any(|x| x == "[events]" || x.starts_with("dialogue:")) {
+        return 0.0;
+    }
+    fraction(lower.iter()
+        .filter(|x| HEADERS.contains(&x.as_str()) || KEYS.iter().any(|k| x.starts_with(k)))
+        .count(), lines.len())
+}
+
+/// SAMI wraps everything in tags, so the tags are looked for anywhere
+fn sami_score(lines: &[&str]) -> f32 {
+    let lower = lines.join("\n").to_ascii_lowercase();
+    if lower.contains("<sami") {
+        0.9
+    } else if lower.contains("<sync start") {
+        0.6
+    } else {
+        0.0
+    }
+}
+
+/// How many of the blocks between blank lines in `source` have a timing
+/// line, as every SRT cue does
+fn srt_score(source: &str) -> f32 {
+    let (mut blocks, mut timed) = (0, 0);
+    let mut in_block = false;
+    let mut block_timed = false;
+    for line in source.lines().take(SNIFF_LINES) {
+        if line.trim().is_empty() {
+            in_block = false;
+            continue;
+        }
+        if !in_block {
+            in_block = true;
+            block_timed = false;
+            blocks += 1;
+        }
+        if !block_timed && srt::parse_timing(line).is_some() {
+            block_timed = true;
+            timed += 1;
+        }
+    }
+    fraction(timed, blocks)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn fraction(part: usize, whole: usize) -> f32 {
+    if whole == 0 { 0.0 } else { part as f32 / whole as f32 }
+}
+
+/// The format `source` is most likely in, if it looks like any: a file
+/// that starts as a format must is in it, and otherwise the start of the
+/// file is scored against each, for files that were misnamed or have
+/// something else before the subtitles
+pub fn detect(source: &str) -> Option<Detection> {
+    let certain = |format| Some(Detection { format, confidence: 1.0 });
+    if ass::detect(source) {
+        return certain(SourceFormat::Ass);
+    }
+    if sami::detect(source) {
+        return certain(SourceFormat::Sami);
+    }
+    let lines: Vec<&str> = source.lines()
+        .map(|x| x.trim_start_matches('\u{feff}').trim())
+        .filter(|x| !x.is_empty())
+        .take(SNIFF_LINES)
+        .collect();
+    let microdvd = fraction(lines.iter().filter(|x| microdvd::detect(x)).count(), lines.len());
+    [
+        (SourceFormat::Ass, ass_score(&lines)),
+        (SourceFormat::Sami, sami_score(&lines)),
+        (SourceFormat::MicroDvd, microdvd),
+        (SourceFormat::Srt, srt_score(source)),
+    ]
+    .into_iter()
+    .filter(|x| x.1 >= MIN_CONFIDENCE)
+    .max_by(|a, b| a.1.total_cmp(&b.1))
+    .map(|(format, confidence)| Detection { format, confidence })
+}
+
+/// Why nothing was parsed
+#[derive(Debug)]
+pub enum Unparsed {
+    /// a MicroDVD file that doesn't say its framerate, and none was given
+    FramerateRequired,
+    UnknownFormat,
+}
+
+fn parse_ass(source: &str, mut progress: impl FnMut(f64)) -> ParseResult {
+    let mut parser = ass::Parser::new();
+    let mut consumed = 0;
+    for (i, line) in source.lines().enumerate() {
+        if i > 0 && i % PROGRESS_INTERVAL == 0 {
+            #[allow(clippy::cast_precision_loss)]
+            let fraction = consumed as f64 / source.len() as f64;
+            progress(fraction);
+        }
+        consumed += line.len() + 1;
+        parser.feed_line(line);
+    }
+    parser.finish()
+}
+
+/// Parses text fed to it a line at a time, as `parse` does all at once.
+/// ASS, which karaoke effects can make hundreds of thousands of lines
+/// long, is parsed as it comes, so the file is never held whole; other
+/// formats are gathered and parsed by `finish`.
+pub struct LineParser<P> {
+    framerate: Option<f64>,
+    progress: P,
+    lines: usize,
+    state: Fed,
+}
+
+enum Fed {
+    /// as many blank lines, and nothing else yet
+    Blank(usize),
+    Ass(Box<ass::Parser>),
+    Other(String),
+}
+
+impl<P: FnMut(f64)> LineParser<P> {
+    /// `framerate` and `progress` as for `parse`
+    pub fn new(framerate: Option<f64>, progress: P) -> LineParser<P> {
+        LineParser { framerate, progress, lines: 0, state: Fed::Blank(0) }
+    }
+
+    /// A line without its ending; `fraction` is how much of the file has
+    /// been read with it, for reporting progress
+    pub fn feed_line(&mut self, line: &str, fraction: f64) {
+        self.lines += 1;
+        if let Fed::Blank(n) = self.state {
+            if line.trim_start_matches('\u{feff}').trim().is_empty() {
+                self.state = Fed::Blank(n + 1);
+                return;
+            }
+            // as `detect` tells it for certain, by the first line
+            self.state = if ass::detect(line) {
+                let mut parser = ass::Parser::new();
+                (0..n).for_each(|_| parser.feed_line(""));
+                Fed::Ass(Box::new(parser))
+            } else {
+                Fed::Other("\n".repeat(n))
+            };
+        }
+        match &mut self.state {
+            Fed::Ass(parser) => {
+                if self.lines.is_multiple_of(PROGRESS_INTERVAL) {
+                    (self.progress)(fraction);
+                }
+                parser.feed_line(line);
+            }
+            Fed::Other(source) => {
+                source.push_str(line);
+                source.push('\n');
+            }
+            Fed::Blank(_) => {}
+        }
+    }
+
+    pub fn finish(self) -> Result<(ParseResult, Detection), Unparsed> {
+        match self.state {
+            Fed::Blank(_) => Err(Unparsed::UnknownFormat),
+            Fed::Ass(parser) => Ok((parser.finish(), Detection {
+                format: SourceFormat::Ass,
+                confidence: 1.0,
+            })),
+            Fed::Other(source) => parse(&source, self.framerate, self.progress),
+        }
+    }
+}
+
+/// Detects the format and parses `source` accordingly; `framerate` is for
+/// MicroDVD files and takes precedence over what they declare. Only ASS
+/// reports `progress`. A format told with less than full confidence is
+/// noted among the issues, as reading the file that way may have been a
+/// guess.
+pub fn parse(
+    source: &str, framerate: Option<f64>, progress: impl FnMut(f64)
+) -> Result<(ParseResult, Detection), Unparsed> {
+    let detection = detect(source).ok_or(Unparsed::UnknownFormat)?;
+    let mut result = match detection.format {
+        SourceFormat::Ass => parse_ass(source, progress),
+        SourceFormat::Sami => sami::parse(source),
+        SourceFormat::MicroDvd => {
+            let framerate = framerate
+                .or_else(|| microdvd::detect_framerate(source))
+                .ok_or(Unparsed::FramerateRequired)?;
+            microdvd::parse(source, framerate)
+        }
+        SourceFormat::Srt => {
+            let (cues, issues) = srt::parse(source);
+            let mut document = convert::from_srt_plain(cues);
+            document.format = SubtitleFormat::Srt;
+            ParseResult { document, issues }
+        }
+    };
+    if detection.confidence < 1.0 {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let percent = (detection.confidence * 100.0).round() as u32;
+        result.issues.insert(0, ParseIssue {
+            line: 0,
+            message: format!("read as {}, which {percent}% of the start of the file looks like",
+                detection.format.name()),
+        });
+    }
+    Ok((result, detection))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::units::Seconds;
+    use crate::subtitle::positioning::{Letterbox, PositioningPolicy};
+
+    const SRT: &str = "1\n00:00:01,000 --> 00:00:02,500\n<i>Hello</i>\n\n\
+        2\n00:00:03,000 --> 00:00:04,000\n{\\an8}World\n\n";
+    const ASS: &str = "[Script Info]\nScriptType: v4.00+\n\n[Events]\n\
+        Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+        Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n";
+
+    fn detected(source: &str) -> (SourceFormat, f32) {
+        let x = detect(source).expect("a format");
+        (x.format, x.confidence)
+    }
+
+    #[test]
+    fn srt_in_a_txt() {
+        // what a .txt holds is told by its text alone
+        assert_eq!(detected(SRT), (SourceFormat::Srt, 1.0));
+    }
+
+    #[test]
+    fn ass_in_an_srt() {
+        assert_eq!(detected(ASS), (SourceFormat::Ass, 1.0));
+    }
+
+    #[test]
+    fn microdvd_in_a_txt() {
+        let source = "{1}{1}23.976\n{24}{48}Hello\n{72}{96}World|again\n";
+        assert_eq!(detected(source).0, SourceFormat::MicroDvd);
+    }
+
+    #[test]
+    fn srt_after_a_preamble() {
+        let source = format!("Subtitles ripped by someone\n\n{SRT}");
+        let (format, confidence) = detected(&source);
+        assert_eq!(format, SourceFormat::Srt);
+        assert!(confidence < 1.0);
+    }
+
+    #[test]
+    fn prose_is_nothing() {
+        assert!(detect("Dear diary,\nnothing happened today.\n").is_none());
+    }
+
+    #[test]
+    fn srt_saves_as_srt() {
+        let (result, _) = parse(SRT, None, |_| ()).unwrap();
+        assert_eq!(result.document.format, SubtitleFormat::Srt);
+        assert_eq!(convert::write_srt(&result.document), SRT);
+    }
+
+    #[test]
+    fn fed_by_line_as_all_at_once() {
+        for source in [ASS, SRT] {
+            let mut parser = LineParser::new(None, |_| ());
+            for line in format!("\n{source}").lines() {
+                parser.feed_line(line, 0.0);
+            }
+            let (fed, detection) = parser.finish().unwrap();
+            let (whole, _) = parse(source, None, |_| ()).unwrap();
+            assert_eq!(detection.format, detected(source).0);
+            assert_eq!(fed.document.events.len(), whole.document.events.len());
+        }
+    }
+
+    #[test]
+    fn ass_sections_keep_their_order() {
+        let source = "[Script Info]\nScriptType: v4.00+\n\n\
+            [Aegisub Project Garbage]\nVideo File: a.mkv\n\n\
+            [V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, \
+            SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, \
+            ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, \
+            MarginL, MarginR, MarginV, Encoding\n\
+            Style: Default,Arial,48,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,\
+            0,0,0,0,100,100,0,0,1,2,2,2,10,10,10,1\n\n\
+            [Events]\nFormat: Layer, Start, End, Style, Name, \
+            MarginL, MarginR, MarginV, Effect, Text\n\
+            Dialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n\n\
+            [Fonts]\nfontname: a.ttf\n";
+        let (result, _) = parse(source, None, |_| ()).unwrap();
+        assert!(result.issues.is_empty());
+        assert_eq!(ass::write(&result.document), source);
+    }
+
+    #[test]
+    fn regions_survive_ass() {
+        let (mut result, _) = parse(ASS, None, |_| ()).unwrap();
+        result.document.add_region(Seconds(1.5), Seconds(4.25), "song, then cut".to_owned())
+            .unwrap();
+        let (again, _) = parse(&ass::write(&result.document), None, |_| ()).unwrap();
+        let region = &again.document.regions[0];
+        assert_eq!((region.start, region.end), (Seconds(1.5), Seconds(4.25)));
+        assert_eq!(region.label, "song, then cut");
+    }
+
+    #[test]
+    fn positioning_survives_ass() {
+        let (mut result, _) = parse(ASS, None, |_| ()).unwrap();
+        let letterbox = Letterbox { top: 132, bottom: 140, height: 1080 };
+        result.document.positioning = PositioningPolicy::InsideBars;
+        result.document.letterbox = Some(letterbox);
+        let (again, _) = parse(&ass::write(&result.document), None, |_| ()).unwrap();
+        assert_eq!(again.document.positioning, PositioningPolicy::InsideBars);
+        assert_eq!(again.document.letterbox, Some(letterbox));
+    }
+
+    #[test]
+    fn marker_colors_survive_ass() {
+        let (mut result, _) = parse(ASS, None, |_| ()).unwrap();
+        let document = &mut result.document;
+        assert!(document.add_marker(Seconds(1.0), "sign".to_owned(), "rgb(255, 0, 0)".to_owned())
+            .is_err());
+        document.add_marker(Seconds(1.234), "sign, top".to_owned(), "rgb(255 0 0)".to_owned())
+            .unwrap();
+        let (again, _) = parse(&ass::write(document), None, |_| ()).unwrap();
+        let marker = &again.document.markers[0];
+        assert_eq!((marker.color.as_str(), marker.label.as_str()), ("rgb(255 0 0)", "sign, top"));
+        assert_eq!(marker.time.0, 1.234);
+    }
+}
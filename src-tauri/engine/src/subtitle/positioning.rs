@@ -0,0 +1,64 @@
+//! Where dialogue goes on letterboxed video: kept inside the picture, or
+//! placed in the black bars. A document has one policy, which the preview
+//! follows and which a QC check can enforce by adjusting margins.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum PositioningPolicy {
+    /// margins are left as the styles say
+    #[default]
+    Free,
+    InsidePicture,
+    InsideBars,
+}
+
+/// Black bars of a video, as found by `detect_letterbox`, in pixels of the
+/// original frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Letterbox {
+    pub top: u32,
+    pub bottom: u32,
+    pub height: u32,
+}
+
+/// Distance kept from the edge of the picture, as a fraction of its height
+const PICTURE_PADDING: f64 = 0.02;
+/// Where in a bar the text is anchored, as a fraction of the bar's height
+/// from the outer edge
+const BAR_ANCHOR: f64 = 0.15;
+/// Bars narrower than this fraction of the frame have no room for text
+const MIN_BAR: f64 = 0.05;
+
+impl PositioningPolicy {
+    /// The vertical margin, in script coordinates, that events aligned to
+    /// the top or bottom edge should have; `None` if the policy doesn't
+    /// constrain it
+    pub fn margin(self, letterbox: &Letterbox, play_res_y: f64, top: bool) -> Option<f64> {
+        let height = f64::from(letterbox.height);
+        let bar = f64::from(if top { letterbox.top } else { letterbox.bottom }) / height;
+        let margin = match self {
+            PositioningPolicy::Free => return None,
+            PositioningPolicy::InsidePicture => bar + PICTURE_PADDING,
+            PositioningPolicy::InsideBars if bar < MIN_BAR => return None,
+            PositioningPolicy::InsideBars => bar * BAR_ANCHOR,
+        };
+        Some((margin * play_res_y).round())
+    }
+
+    /// Whether an existing margin already satisfies the policy
+    pub fn accepts(self, letterbox: &Letterbox, play_res_y: f64, top: bool, margin: f64) -> bool {
+        let height = f64::from(letterbox.height);
+        let bar = f64::from(if top { letterbox.top } else { letterbox.bottom })
+            / height * play_res_y;
+        match self {
+            PositioningPolicy::Free => true,
+            PositioningPolicy::InsidePicture => margin >= bar,
+            PositioningPolicy::InsideBars => bar < MIN_BAR * play_res_y || margin < bar / 2.0,
+        }
+    }
+}
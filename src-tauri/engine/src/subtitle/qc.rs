@@ -0,0 +1,456 @@
+//! Quality checks over a document. Each check reports the events it finds a
+//! problem with, possibly with an edit that would fix it; none of them
+//! change anything themselves.
+
+use serde::Serialize;
+
+use crate::media::damage::DamageSpan;
+use crate::media::units::Seconds;
+use crate::subtitle::ass;
+use crate::subtitle::document::{Document, Event};
+use crate::subtitle::edit::Edit;
+use crate::subtitle::positioning::{Letterbox, PositioningPolicy};
+use crate::subtitle::safe_area::BroadcastStandard;
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct QcIssue {
+    pub event_id: u32,
+    pub message: String,
+    pub fix: Option<Edit>,
+}
+
+/// How wide a glyph is on average, for the font size
+const AVERAGE_ADVANCE: f64 = 0.5;
+
+/// Numpad alignment of an event, after overrides
+fn alignment(document: &Document, event: &Event, overrides: &ass::Overrides) -> u8 {
+    let style = document.styles.iter().find(|x| *x.name == *event.style);
+    overrides.alignment
+        .or_else(|| ass::style_field(style, "alignment").parse().ok())
+        .unwrap_or(2)
+}
+
+/// Where an event is anchored, in script coordinates
+fn anchor(document: &Document, event: &Event) -> (f64, f64) {
+    let overrides = ass::parse_overrides(&event.text);
+    if let Some(pos) = overrides.pos {
+        return pos;
+    }
+    let (res_x, res_y) = ass::play_res(document);
+    let style = document.styles.iter().find(|x| *x.name == *event.style);
+    let (left, right, vertical) = ass::margins(event, style);
+    let alignment = alignment(document, event, &overrides);
+    let x = match alignment % 3 {
+        1 => left,
+        0 => res_x - right,
+        _ => (left + res_x - right) / 2.0,
+    };
+    let y = match alignment {
+        1..=3 => res_y - vertical,
+        7..=9 => vertical,
+        _ => res_y / 2.0,
+    };
+    (x, y)
+}
+
+/// About the box an event's text takes, in script coordinates, as
+/// `(left, top, right, bottom)`: a line per `\N` and more where a line is
+/// too wide for the margins and gets wrapped, each as high as the font size
+/// and its glyphs `AVERAGE_ADVANCE` as wide
+#[allow(clippy::cast_precision_loss)]
+fn text_box(document: &Document, event: &Event) -> (f64, f64, f64, f64) {
+    let overrides = ass::parse_overrides(&event.text);
+    let style = document.styles.iter().find(|x| *x.name == *event.style);
+    let field = |name: &str, default: f64| ass::style_field(style, name).parse().unwrap_or(default);
+    let size = overrides.font_size.unwrap_or_else(|| field("fontsize", 48.0));
+    let (scale_x, scale_y) = (field("scalex", 100.0) / 100.0, field("scaley", 100.0) / 100.0);
+    let (res_x, _) = ass::play_res(document);
+    let (left, right, _) = ass::margins(event, style);
+    // `\pos` text isn't wrapped to the margins
+    let room = if overrides.pos.is_some() { f64::INFINITY } else { (res_x - left - right).max(1.0) };
+
+    let advance = size * scale_x * AVERAGE_ADVANCE;
+    let (mut width, mut lines) = (0.0f64, 0.0);
+    for line in ass::plain_text(&event.text.replace("\\N", "\n")).split('\n') {
+        let length = line.trim().chars().count() as f64 * advance;
+        width = width.max(length.min(room));
+        lines += (length / room).ceil().max(1.0);
+    }
+    let height = lines * size * scale_y;
+
+    let (x, y) = anchor(document, event);
+    let alignment = alignment(document, event, &overrides);
+    let left = match alignment % 3 {
+        1 => x,
+        0 => x - width,
+        _ => x - width / 2.0,
+    };
+    let top = match alignment {
+        1..=3 => y - height,
+        7..=9 => y,
+        _ => y - height / 2.0,
+    };
+    (left, top, left + width, top + height)
+}
+
+/// Flags events whose text would run outside the title-safe area of a
+/// `width`×`height` video. Glyph extents would need libass, which we don't
+/// link, so the box the text takes is estimated from its font size and
+/// lines; see `text_box`. Text close to the edge may be misjudged by a
+/// little either way.
+pub fn check_title_safe(
+    document: &Document, width: f64, height: f64, standard: BroadcastStandard,
+) -> Vec<QcIssue> {
+    let (res_x, res_y) = ass::play_res(document);
+    let title_safe = standard.areas(width, height).title_safe;
+    let (scale_x, scale_y) = (width / res_x, height / res_y);
+
+    document.events.iter()
+        .filter(|x| !x.is_comment && !ass::plain_text(&x.text).trim().is_empty())
+        .filter_map(|event| {
+            let (left, top, right, bottom) = text_box(document, event);
+            let inside = title_safe.contains(left * scale_x, top * scale_y)
+                && title_safe.contains(right * scale_x, bottom * scale_y);
+            (!inside).then(|| QcIssue {
+                event_id: event.id,
+                message: format!("runs outside the title-safe area, \
+                    from about ({left:.0}, {top:.0}) to ({right:.0}, {bottom:.0})"),
+                fix: None,
+            })
+        })
+        .collect()
+}
+
+/// Flags bottom-aligned events shown while burned-in text occupies the bottom
+/// of the picture, suggesting to move them to the top with `\an8`
+pub fn check_onscreen_text(
+    document: &Document, intervals: &[(Seconds, Seconds)],
+) -> Vec<QcIssue> {
+    document.events.iter()
+        .filter(|x| !x.is_comment)
+        .filter(|event| {
+            let overrides = ass::parse_overrides(&event.text);
+            overrides.pos.is_none()
+                && (1..=3).contains(&alignment(document, event, &overrides))
+        })
+        .filter(|event| intervals.iter()
+            .any(|(start, end)| event.start.0 < end.0 && start.0 < event.end.0))
+        .map(|event| QcIssue {
+            event_id: event.id,
+            message: "overlaps text burned into the bottom of the video".to_owned(),
+            fix: Some(Edit::Update {
+                id: event.id,
+                start: None, end: None, style: None, actor: None, margins: None,
+                text: Some(format!("{{\\an8}}{}", event.text)),
+            }),
+        })
+        .collect()
+}
+
+/// Flags events aligned to the top or bottom edge whose vertical margin
+/// doesn't follow `policy` on a video with `letterbox`, with a fix setting
+/// the margin. Events with `\pos` or centered vertically are left alone.
+pub fn check_positioning(
+    document: &Document, policy: PositioningPolicy, letterbox: &Letterbox,
+) -> Vec<QcIssue> {
+    let (_, res_y) = ass::play_res(document);
+    document.events.iter()
+        .filter(|x| !x.is_comment)
+        .filter_map(|event| {
+            let overrides = ass::parse_overrides(&event.text);
+            if overrides.pos.is_some() {
+                return None;
+            }
+            let top = match alignment(document, event, &overrides) {
+                1..=3 => false,
+                7..=9 => true,
+                _ => return None,
+            };
+            let style = document.styles.iter().find(|x| *x.name == *event.style);
+            let (left, right, vertical) = ass::margins(event, style);
+            if policy.accepts(letterbox, res_y, top, vertical) {
+                return None;
+            }
+            let margin = policy.margin(letterbox, res_y, top)?;
+            #[allow(clippy::cast_possible_truncation)]
+            let margins = (left as i32, right as i32, (margin as i32).max(1));
+            Some(QcIssue {
+                event_id: event.id,
+                message: match policy {
+                    PositioningPolicy::InsideBars => "not placed in the letterbox bar",
+                    _ => "overlaps the letterbox bar",
+                }.to_owned(),
+                fix: Some(Edit::Update {
+                    id: event.id,
+                    start: None, end: None, style: None, actor: None,
+                    margins: Some(margins),
+                    text: None,
+                }),
+            })
+        })
+        .collect()
+}
+
+/// Flags event starts and ends that are within `frames` frames of a
+/// keyframe or scene change in `keyframes` without being on it, which makes
+/// a cue flash up just before the cut or linger just after it, with a fix
+/// snapping the edge to it. An edge within half a frame is on it.
+pub fn check_keyframe_distance(
+    document: &Document, keyframes: &[Seconds], framerate: f64, frames: u32,
+) -> Vec<QcIssue> {
+    let mut keyframes: Vec<f64> = keyframes.iter().map(|x| x.0).collect();
+    keyframes.sort_by(f64::total_cmp);
+    // the nearest keyframe to `time`, and how many frames away it is
+    let nearest = |time: f64| {
+        let i = keyframes.partition_point(|&x| x < time);
+        [i.checked_sub(1), (i < keyframes.len()).then_some(i)].into_iter()
+            .flatten()
+            .map(|i| (keyframes[i], (keyframes[i] - time).abs() * framerate))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    };
+
+    let mut issues = Vec::new();
+    for event in document.events.iter().filter(|x| !x.is_comment) {
+        for is_start in [true, false] {
+            let edge = if is_start { event.start } else { event.end };
+            let Some((keyframe, distance)) = nearest(edge.0) else { continue };
+            if distance < 0.5 || distance.round() > f64::from(frames) {
+                continue;
+            }
+            let side = if keyframe > edge.0 { "before" } else { "after" };
+            let distance = distance.round();
+            let plural = if distance > 1.0 { "s" } else { "" };
+            let (start, end) = if is_start {
+                (Some(Seconds(keyframe)), None)
+            } else {
+                (None, Some(Seconds(keyframe)))
+            };
+            let snapped_valid = start.unwrap_or(event.start).0 < end.unwrap_or(event.end).0;
+            issues.push(QcIssue {
+                event_id: event.id,
+                message: format!("{} {distance} frame{plural} {side} the keyframe at {}",
+                    if is_start { "starts" } else { "ends" }, Seconds(keyframe)),
+                fix: snapped_valid.then_some(Edit::Update {
+                    id: event.id,
+                    start, end,
+                    style: None, actor: None, margins: None, text: None,
+                }),
+            });
+        }
+    }
+    issues
+}
+
+/// `time` as players show it, `m:ss`, or `h:mm:ss` from an hour on
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn clock(time: Seconds) -> String {
+    let s = time.0.max(0.0).floor() as u64;
+    if s >= 3600 {
+        format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+    } else {
+        format!("{}:{:02}", s / 60, s % 60)
+    }
+}
+
+/// Flags events shown over damage the source was found to have, as
+/// `media_api::get_decode_damage` reports it, so that artifacts there are
+/// known to be in the file. There is nothing to fix.
+pub fn check_source_damage(document: &Document, spans: &[DamageSpan]) -> Vec<QcIssue> {
+    let plural = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+    document.events.iter()
+        .filter(|x| !x.is_comment)
+        .flat_map(|event| spans.iter()
+            .filter(|x| event.start.0 <= x.end.0 && x.start.0 < event.end.0)
+            .map(|span| {
+                let mut what = Vec::new();
+                if span.corrupt_frames > 0 {
+                    what.push(plural(span.corrupt_frames, "corrupt frame"));
+                }
+                if span.errors > 0 {
+                    what.push(plural(span.errors, "unreadable packet"));
+                }
+                let (start, end) = (clock(span.start), clock(span.end));
+                let when = if start == end {
+                    format!("at {start}")
+                } else {
+                    format!("between {start}\u{2013}{end}")
+                };
+                QcIssue {
+                    event_id: event.id,
+                    message: format!("source has {} {when}", what.join(" and ")),
+                    fix: None,
+                }
+            }))
+        .collect()
+}
+
+/// Text as it reads: override blocks dropped, line breaks as spaces, case
+/// and punctuation ignored
+fn normalize(text: &str) -> Vec<char> {
+    let mut result = Vec::new();
+    for c in ass::plain_text(text).chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            result.push(c);
+        } else if c.is_whitespace() && result.last().is_some_and(|x| *x != ' ') {
+            result.push(' ');
+        }
+    }
+    if result.last() == Some(&' ') {
+        result.pop();
+    }
+    result
+}
+
+/// 1 minus the edit distance relative to the longer text
+#[allow(clippy::cast_precision_loss)]
+fn similarity(a: &[char], b: &[char]) -> f64 {
+    let longer = a.len().max(b.len());
+    if longer == 0 {
+        return 1.0;
+    }
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(x != y);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    1.0 - row[b.len()] as f64 / longer as f64
+}
+
+fn overlap(a: &Event, b: &Event) -> f64 {
+    (a.end.0.min(b.end.0) - a.start.0.max(b.start.0)).max(0.0)
+}
+
+/// Flags events that overlap another in time and read the same, with a
+/// `threshold` from 0 to 1 on how similar the text has to be, as bad merges
+/// leave behind. Of every group of copies the better timed one is kept:
+/// the one colliding least with the events around it, since a copy that
+/// came in shifted runs into its neighbours, then the longer one. The
+/// others are flagged with a fix removing them.
+pub fn find_duplicates(document: &Document, threshold: f64) -> Vec<QcIssue> {
+    let events: Vec<&Event> = document.events.iter()
+        .filter(|x| !x.is_comment)
+        .collect();
+    let texts: Vec<Vec<char>> = events.iter().map(|x| normalize(&x.text)).collect();
+    let mut order: Vec<usize> = (0..events.len()).collect();
+    order.sort_by(|&a, &b| events[a].start.0.total_cmp(&events[b].start.0));
+
+    // groups of copies, each of the events like its first; not of events
+    // alike only through others in between. Groups whose first has ended
+    // take no more.
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    for &i in &order {
+        open.retain(|&g| events[groups[g][0]].end.0 > events[i].start.0);
+        let like_first = |g: &usize| {
+            let first = groups[*g][0];
+            !texts[first].is_empty() && overlap(events[first], events[i]) > 0.0
+                && similarity(&texts[first], &texts[i]) >= threshold
+        };
+        match open.iter().copied().find(like_first) {
+            Some(g) => groups[g].push(i),
+            None => {
+                open.push(groups.len());
+                groups.push(vec![i]);
+            }
+        }
+    }
+
+    let mut issues = Vec::new();
+    for members in groups.iter().filter(|x| x.len() > 1) {
+        let collisions = |i: usize| events.iter().enumerate()
+            .filter(|(j, _)| !members.contains(j))
+            .map(|(_, x)| overlap(events[i], x))
+            .sum::<f64>();
+        let duration = |i: usize| events[i].end.0 - events[i].start.0;
+        let kept = *members.iter()
+            .min_by(|&&a, &&b| collisions(a).total_cmp(&collisions(b))
+                .then(duration(b).total_cmp(&duration(a)))
+                .then(a.cmp(&b)))
+            .unwrap();
+        for &i in members.iter().filter(|&&x| x != kept) {
+            let score = similarity(&texts[i], &texts[kept]);
+            issues.push(QcIssue {
+                event_id: events[i].id,
+                message: format!("duplicate of event {} ({:.0}% alike)",
+                    events[kept].id, score * 100.0),
+                fix: Some(Edit::Remove { id: events[i].id }),
+            });
+        }
+    }
+    issues.sort_by_key(|x| x.event_id);
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subtitle::document::SubtitleFormat;
+
+    fn document(events: &[(f64, f64, &str)]) -> Document {
+        let mut document = Document::new(SubtitleFormat::Ass);
+        for &(start, end, text) in events {
+            document.apply(&Edit::Insert {
+                at: usize::MAX,
+                start: Seconds(start),
+                end: Seconds(end),
+                style: "Default".to_owned(),
+                text: text.to_owned(),
+            }).unwrap();
+        }
+        document
+    }
+
+    fn flagged(issues: &[QcIssue]) -> Vec<u32> {
+        issues.iter().map(|x| x.event_id).collect()
+    }
+
+    #[test]
+    fn title_safe_takes_the_text_box() {
+        // 384×288, its title-safe area 19.2 in from the sides and 14.4 from
+        // the top and bottom; the default style is 48 high. Every anchor is
+        // inside.
+        let document = document(&[
+            (1.0, 2.0, "{\\pos(192,200)}Short"),
+            (2.0, 3.0, "{\\an8\\pos(192,240)}Two\\Nlines"),
+            (3.0, 4.0, "{\\pos(192,200)}A line far too long to stay inside unless wrapped"),
+            (4.0, 5.0, "{\\an8\\fs20\\pos(192,20)}Small, up top, inside"),
+        ]);
+        let issues = check_title_safe(&document, 1920.0, 1080.0, BroadcastStandard::Modern);
+        let ids: Vec<u32> = [1, 2].map(|i| document.events[i].id).into();
+        assert_eq!(flagged(&issues), ids);
+    }
+
+    #[test]
+    fn keeps_the_better_timed_copy() {
+        let document = document(&[
+            (1.0, 3.0, "Where are you going?"),
+            (1.2, 3.1, "{\\i1}Where are you going{\\i0}"),
+            (3.0, 5.0, "Home."),
+        ]);
+        // the second runs into the third
+        let issues = find_duplicates(&document, 0.9);
+        assert_eq!(flagged(&issues), [document.events[1].id]);
+        assert!(matches!(issues[0].fix, Some(Edit::Remove { id }) if id == document.events[1].id));
+    }
+
+    #[test]
+    fn alike_only_through_another_isnt_a_copy() {
+        // each next to the one before is a letter off, the last two from
+        // the first
+        let document = document(&[
+            (1.0, 3.0, "abcdefghij"),
+            (1.1, 3.0, "abcdefghiX"),
+            (1.2, 3.0, "abcdefghXX"),
+        ]);
+        let issues = find_duplicates(&document, 0.85);
+        assert_eq!(flagged(&issues).len(), 1);
+        assert!(!flagged(&issues).contains(&document.events[2].id));
+    }
+}
@@ -0,0 +1,203 @@
+//! Filtering, sorting and statistics over events, done here so that the
+//! frontend can work with a document without ever holding all of it.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::subtitle::document::Event;
+
+#[derive(Clone, Debug, Default, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct EventFilter {
+    /// exact style name
+    pub style: Option<String>,
+    /// exact actor name
+    pub actor: Option<String>,
+    /// case-insensitive substring of the text
+    pub text: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum EventColumn {
+    Start,
+    End,
+    Duration,
+    Style,
+    Actor,
+    Effect,
+    Layer,
+    Text,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct EventSort {
+    pub column: EventColumn,
+    pub descending: bool,
+}
+
+impl EventFilter {
+    /// `text` must already be lowercase; see `page`
+    fn matches(&self, event: &Event) -> bool {
+        self.style.as_ref().is_none_or(|x| *x == *event.style)
+            && self.actor.as_ref().is_none_or(|x| *x == *event.actor)
+            && self.text.as_ref().is_none_or(|x| event.text.to_lowercase().contains(x))
+    }
+}
+
+impl EventColumn {
+    pub fn compare(self, a: &Event, b: &Event) -> Ordering {
+        match self {
+            EventColumn::Start => a.start.0.total_cmp(&b.start.0),
+            EventColumn::End => a.end.0.total_cmp(&b.end.0),
+            EventColumn::Duration =>
+                (a.end.0 - a.start.0).total_cmp(&(b.end.0 - b.start.0)),
+            EventColumn::Style => a.style.cmp(&b.style),
+            EventColumn::Actor => a.actor.cmp(&b.actor),
+            EventColumn::Effect => a.effect.cmp(&b.effect),
+            EventColumn::Layer => a.layer.cmp(&b.layer),
+            EventColumn::Text => a.text.cmp(&b.text),
+        }
+    }
+}
+
+/// Returns the matching events in the requested order, together with how many
+/// matched in total. Sorting is stable, so ties keep document order.
+pub fn page<'a>(
+    events: &'a [Event], offset: usize, limit: usize,
+    filter: &EventFilter, sort: Option<EventSort>,
+) -> (usize, Vec<&'a Event>) {
+    let filter = EventFilter {
+        text: filter.text.as_ref().map(|x| x.to_lowercase()),
+        ..filter.clone()
+    };
+    let mut matching: Vec<&Event> = events.iter()
+        .filter(|x| filter.matches(x))
+        .collect();
+    if let Some(EventSort { column, descending }) = sort {
+        matching.sort_by(|a, b| {
+            let order = column.compare(a, b);
+            if descending { order.reverse() } else { order }
+        });
+    }
+    let total = matching.len();
+    (total, matching.into_iter().skip(offset).take(limit).collect())
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "by")]
+#[ts(export)]
+pub enum GroupBy {
+    Style,
+    Actor,
+    Effect,
+    Layer,
+    /// a histogram of characters per second
+    #[serde(rename_all = "camelCase")]
+    Cps { bucket_width: f64 },
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum Metric {
+    Count,
+    TotalDuration,
+    MeanDuration,
+    TotalCharacters,
+    MeanCps,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct EventGroup {
+    /// for CPS buckets, the lower bound
+    pub key: String,
+    /// one for each requested metric, in the same order
+    pub values: Vec<f64>,
+}
+
+#[derive(Default)]
+struct Accumulator {
+    count: usize,
+    duration: f64,
+    characters: usize,
+}
+
+/// Characters that end up on screen: ASS override blocks and line breaks
+/// don't count
+pub fn visible_characters(text: &str) -> usize {
+    let mut count = 0;
+    let mut in_block = false;
+    for c in text.chars() {
+        match c {
+            '{' => in_block = true,
+            '}' if in_block => in_block = false,
+            '\n' => (),
+            _ if !in_block => count += 1,
+            _ => (),
+        }
+    }
+    count
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn cps(characters: usize, duration: f64) -> f64 {
+    if duration > 0.0 { characters as f64 / duration } else { 0.0 }
+}
+
+/// Comments are left out. Named groups keep the order of first appearance;
+/// layers and CPS buckets are sorted numerically.
+pub fn aggregate(events: &[Event], group_by: GroupBy, metrics: &[Metric]) -> Vec<EventGroup> {
+    let mut groups: Vec<(String, f64, Accumulator)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for event in events.iter().filter(|x| !x.is_comment) {
+        let duration = event.end.0 - event.start.0;
+        let characters = visible_characters(&event.text);
+        let (key, order) = match group_by {
+            GroupBy::Style => (event.style.to_string(), 0.0),
+            GroupBy::Actor => (event.actor.to_string(), 0.0),
+            GroupBy::Effect => (event.effect.to_string(), 0.0),
+            GroupBy::Layer => (event.layer.to_string(), f64::from(event.layer)),
+            GroupBy::Cps { bucket_width } => {
+                let width = if bucket_width > 0.0 { bucket_width } else { 1.0 };
+                let bucket = (cps(characters, duration) / width).floor() * width;
+                (bucket.to_string(), bucket)
+            }
+        };
+        let i = *index.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, order, Accumulator::default()));
+            groups.len() - 1
+        });
+        let acc = &mut groups[i].2;
+        acc.count += 1;
+        acc.duration += duration;
+        acc.characters += characters;
+    }
+
+    if matches!(group_by, GroupBy::Layer | GroupBy::Cps { .. }) {
+        groups.sort_by(|a, b| a.1.total_cmp(&b.1));
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    groups.into_iter()
+        .map(|(key, _, acc)| EventGroup {
+            key,
+            values: metrics.iter().map(|metric| match metric {
+                Metric::Count => acc.count as f64,
+                Metric::TotalDuration => acc.duration,
+                Metric::MeanDuration => acc.duration / acc.count as f64,
+                Metric::TotalCharacters => acc.characters as f64,
+                Metric::MeanCps => cps(acc.characters, acc.duration),
+            }).collect(),
+        })
+        .collect()
+}
@@ -0,0 +1,98 @@
+//! Labelled stretches of audio, like "song starts" or "flashback", for
+//! working with whoever edits the audio. Unlike markers they span a time
+//! range, and they are exchanged as Audacity label tracks: one
+//! `start<TAB>end<TAB>label` line per region, times in seconds.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::Document;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Region {
+    pub id: u32,
+    pub start: Seconds,
+    /// equal to `start` for a point label
+    pub end: Seconds,
+    pub label: String,
+}
+
+fn check_range(start: Seconds, end: Seconds) -> Result<(), String> {
+    if !(start.0.is_finite() && end.0.is_finite() && start.0 <= end.0) {
+        return Err(format!("invalid region: {start} to {end}"));
+    }
+    Ok(())
+}
+
+impl Document {
+    /// Regions are kept sorted by start
+    pub fn add_region(
+        &mut self, start: Seconds, end: Seconds, label: String
+    ) -> Result<u32, String> {
+        check_range(start, end)?;
+        let id = self.next_region_id;
+        self.next_region_id += 1;
+        let at = self.regions.partition_point(|x| x.start.0 <= start.0);
+        self.regions.insert(at, Region { id, start, end, label });
+        Ok(id)
+    }
+
+    pub fn update_region(
+        &mut self, id: u32,
+        start: Option<Seconds>, end: Option<Seconds>, label: Option<String>,
+    ) -> Result<(), String> {
+        let position = self.regions.iter().position(|x| x.id == id)
+            .ok_or(format!("no region with id {id}"))?;
+        let region = &self.regions[position];
+        let (start, end) = (start.unwrap_or(region.start), end.unwrap_or(region.end));
+        check_range(start, end)?;
+        let mut region = self.regions.remove(position);
+        region.start = start;
+        region.end = end;
+        if let Some(x) = label { region.label = x; }
+        let at = self.regions.partition_point(|x| x.start.0 <= region.start.0);
+        self.regions.insert(at, region);
+        Ok(())
+    }
+
+    pub fn remove_region(&mut self, id: u32) -> Result<(), String> {
+        let position = self.regions.iter().position(|x| x.id == id)
+            .ok_or(format!("no region with id {id}"))?;
+        self.regions.remove(position);
+        Ok(())
+    }
+}
+
+/// As Audacity exports them, with microsecond precision
+pub fn to_audacity(regions: &[Region]) -> String {
+    regions.iter()
+        .map(|x| format!("{:.6}\t{:.6}\t{}\n",
+            x.start.0, x.end.0, x.label.replace(['\t', '\r', '\n'], " ")))
+        .collect()
+}
+
+/// Start, end and label of every region in a label track. Lines starting
+/// with a backslash hold the frequency range of the label before, which
+/// has no meaning here.
+pub fn from_audacity(source: &str) -> Result<Vec<(Seconds, Seconds, String)>, String> {
+    let mut result = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('\\') {
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let mut time = || fields.next()
+            .and_then(|x| x.trim().parse::<f64>().ok())
+            .filter(|x| x.is_finite())
+            .map(Seconds);
+        let (Some(start), Some(end)) = (time(), time()) else {
+            return Err(format!("line {}: expected start and end times", i + 1));
+        };
+        check_range(start, end).map_err(|e| format!("line {}: {e}", i + 1))?;
+        let label = fields.next().unwrap_or("").to_owned();
+        result.push((start, end, label));
+    }
+    Ok(result)
+}
@@ -0,0 +1,249 @@
+//! Reading SRT files that `srt::parse` would get wrong: files put together
+//! from several, with their numbering starting over and byte order marks
+//! left in the middle, hand-edited ones missing the blank line between two
+//! cues, and ones from tools that write times with a dot, or a colon, for
+//! the comma. What is done to the file is logged by kind, with the lines.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::media::units::Seconds;
+use crate::subtitle::srt::{self, Cue};
+
+const BOM: char = '\u{feff}';
+/// A UTF-8 byte order mark read as Windows-1252, as it ends up when files
+/// in different encodings are put together
+const BOM_MISREAD: &str = "\u{ef}\u{bb}\u{bf}";
+/// How long a cue that ends before it starts is made, unless the next one
+/// starts sooner
+const ASSUMED_DURATION: f64 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum RepairKind {
+    /// a cue's number repeats or skips one before it; all are numbered
+    /// again from 1 when written
+    Renumbered,
+    /// a cue starts right after the text of the one before
+    MissingBlankLine,
+    /// a cue ends before it starts; see `ASSUMED_DURATION`
+    NegativeDuration,
+    /// a time written with a dot or colon before the milliseconds
+    DecimalSeparator,
+    /// a byte order mark after the start of the file, removed
+    StrayBom,
+    /// text outside of any cue, left out
+    Skipped,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Repair {
+    pub kind: RepairKind,
+    /// 1-based
+    pub lines: Vec<usize>,
+}
+
+/// A time as `srt::parse_time` takes it, and whether its separator had to
+/// be changed for that
+fn parse_time(s: &str) -> Option<(Seconds, bool)> {
+    let s = s.trim();
+    if s.matches(':').count() == 3 {
+        let (hms, fraction) = s.rsplit_once(':')?;
+        return Some((srt::parse_time(&format!("{hms},{fraction}"))?, true));
+    }
+    Some((srt::parse_time(s)?, s.contains('.')))
+}
+
+/// `srt::parse_timing`, with the same leniency as `parse_time`
+fn parse_timing(line: &str) -> Option<(Seconds, Seconds, bool)> {
+    let (start, rest) = line.split_once("-->")?;
+    let (start, a) = parse_time(start)?;
+    let (end, b) = parse_time(rest.split_whitespace().next()?)?;
+    Some((start, end, a || b))
+}
+
+fn is_number(line: &str) -> bool {
+    !line.is_empty() && line.bytes().all(|x| x.is_ascii_digit())
+}
+
+/// The cues of `source` as far as they can be made out, in file order, and
+/// what was done for that
+pub fn repair(source: &str) -> (Vec<Cue>, Vec<Repair>) {
+    let mut log: BTreeMap<RepairKind, Vec<usize>> = BTreeMap::new();
+    let mut lines: Vec<String> = Vec::new();
+    for (i, line) in source.lines().enumerate() {
+        let mut line = line.trim_end().to_owned();
+        // one at the very start is only a byte order mark
+        if i == 0 && line.starts_with(BOM) {
+            line.remove(0);
+        }
+        if line.contains(BOM) || line.contains(BOM_MISREAD) {
+            line = line.replace(BOM_MISREAD, "").replace(BOM, "");
+            log.entry(RepairKind::StrayBom).or_default().push(i + 1);
+        }
+        lines.push(line);
+    }
+
+    let mut cues: Vec<Cue> = Vec::new();
+    // where each cue's timing is
+    let mut timing_lines: Vec<usize> = Vec::new();
+    let mut current: Option<Cue> = None;
+    let mut last_number: Option<u64> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let timing = parse_timing(line);
+        if let Some(cue) = current.as_mut() {
+            let next_is_timing = lines.get(i + 1).is_some_and(|x| parse_timing(x).is_some());
+            if line.trim().is_empty() {
+                cues.extend(current.take());
+                continue;
+            }
+            if timing.is_none() && !(is_number(line.trim()) && next_is_timing) {
+                if !cue.text.is_empty() {
+                    cue.text.push('\n');
+                }
+                cue.text.push_str(line);
+                continue;
+            }
+            log.entry(RepairKind::MissingBlankLine).or_default().push(i + 1);
+            cues.extend(current.take());
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if is_number(line) {
+            let number = line.parse().ok();
+            let expected = last_number.map_or(1, |x| x + 1);
+            if number.is_some_and(|x| x != expected) && last_number.is_some() {
+                log.entry(RepairKind::Renumbered).or_default().push(i + 1);
+            }
+            last_number = number;
+            continue;
+        }
+        match timing {
+            Some((start, end, separator)) => {
+                if separator {
+                    log.entry(RepairKind::DecimalSeparator).or_default().push(i + 1);
+                }
+                current = Some(Cue { start, end, text: String::new() });
+                timing_lines.push(i + 1);
+            }
+            None => log.entry(RepairKind::Skipped).or_default().push(i + 1),
+        }
+    }
+    cues.extend(current);
+
+    for i in 0..cues.len() {
+        let start = cues[i].start;
+        if cues[i].end.0 >= start.0 {
+            continue;
+        }
+        let next = cues.get(i + 1).map(|x| x.start.0).filter(|&x| x > start.0);
+        let end = next.map_or(start.0 + ASSUMED_DURATION, |x| x.min(start.0 + ASSUMED_DURATION));
+        cues[i].end = Seconds(end);
+        log.entry(RepairKind::NegativeDuration).or_default().push(timing_lines[i]);
+    }
+
+    let repairs = log.into_iter().map(|(kind, lines)| Repair { kind, lines }).collect();
+    (cues, repairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines_of(repairs: &[Repair], kind: RepairKind) -> Vec<usize> {
+        repairs.iter().find(|x| x.kind == kind).map(|x| x.lines.clone()).unwrap_or_default()
+    }
+
+    fn texts(cues: &[Cue]) -> Vec<&str> {
+        cues.iter().map(|x| x.text.as_str()).collect()
+    }
+
+    #[test]
+    fn clean_file_needs_nothing() {
+        let (cues, repairs) = repair(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n2\n00:00:03,000 --> 00:00:04,000\nTwo\n");
+        assert_eq!(texts(&cues), ["One", "Two"]);
+        assert!(repairs.is_empty(), "{repairs:?}");
+    }
+
+    #[test]
+    fn numbering_starting_over() {
+        let (cues, repairs) = repair(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+             1\n00:00:03,000 --> 00:00:04,000\nTwo\n\n\
+             2\n00:00:05,000 --> 00:00:06,000\nThree\n");
+        assert_eq!(texts(&cues), ["One", "Two", "Three"]);
+        assert_eq!(lines_of(&repairs, RepairKind::Renumbered), [5]);
+    }
+
+    #[test]
+    fn missing_blank_line() {
+        let (cues, repairs) = repair(
+            "1\n00:00:01,000 --> 00:00:02,000\nOne\n\
+             2\n00:00:03,000 --> 00:00:04,000\nTwo\n");
+        assert_eq!(texts(&cues), ["One", "Two"]);
+        assert_eq!(lines_of(&repairs, RepairKind::MissingBlankLine), [4]);
+
+        // without its number either
+        let (cues, repairs) = repair(
+            "00:00:01,000 --> 00:00:02,000\nOne\n00:00:03,000 --> 00:00:04,000\nTwo\n");
+        assert_eq!(texts(&cues), ["One", "Two"]);
+        assert_eq!(lines_of(&repairs, RepairKind::MissingBlankLine), [3]);
+    }
+
+    #[test]
+    fn numbers_in_the_text_stay() {
+        let (cues, repairs) = repair("1\n00:00:01,000 --> 00:00:02,000\n1984\n");
+        assert_eq!(texts(&cues), ["1984"]);
+        assert!(repairs.is_empty(), "{repairs:?}");
+    }
+
+    #[test]
+    fn negative_duration() {
+        let (cues, repairs) = repair(
+            "1\n00:00:05,000 --> 00:00:04,000\nOne\n\n\
+             2\n00:00:06,000 --> 00:00:07,000\nTwo\n\n\
+             3\n00:00:10,000 --> 00:00:01,000\nThree\n");
+        // up to the next cue, or for `ASSUMED_DURATION`
+        assert_eq!(cues[0].end, Seconds(6.0));
+        assert_eq!(cues[1].end, Seconds(7.0));
+        assert_eq!(cues[2].end, Seconds(10.0 + ASSUMED_DURATION));
+        assert_eq!(lines_of(&repairs, RepairKind::NegativeDuration), [2, 10]);
+    }
+
+    #[test]
+    fn dot_and_colon_separators() {
+        let (cues, repairs) = repair(
+            "1\n00:00:01.500 --> 00:00:02,000\nOne\n\n\
+             2\n00:00:03:250 --> 00:00:04:000\nTwo\n");
+        assert_eq!(cues[0].start, Seconds(1.5));
+        assert_eq!(cues[1].start, Seconds(3.25));
+        assert_eq!(cues[1].end, Seconds(4.0));
+        assert_eq!(lines_of(&repairs, RepairKind::DecimalSeparator), [2, 6]);
+    }
+
+    #[test]
+    fn stray_bom() {
+        let (cues, repairs) = repair(
+            "\u{feff}1\n00:00:01,000 --> 00:00:02,000\nOne\n\n\
+             \u{feff}1\n00:00:03,000 --> 00:00:04,000\nTwo\n\n\
+             \u{ef}\u{bb}\u{bf}2\n00:00:05,000 --> 00:00:06,000\nThree\n");
+        assert_eq!(texts(&cues), ["One", "Two", "Three"]);
+        // the one at the very start is no repair
+        assert_eq!(lines_of(&repairs, RepairKind::StrayBom), [5, 9]);
+        assert_eq!(lines_of(&repairs, RepairKind::Renumbered), [5]);
+    }
+
+    #[test]
+    fn text_outside_cues_is_skipped() {
+        let (cues, repairs) = repair("WEBVTT?\n\n1\n00:00:01,000 --> 00:00:02,000\nOne\n");
+        assert_eq!(texts(&cues), ["One"]);
+        assert_eq!(lines_of(&repairs, RepairKind::Skipped), [1]);
+    }
+}
@@ -0,0 +1,100 @@
+//! Moving and stretching every event at once, as for subtitles timed to a
+//! release whose audio ran at another speed or started elsewhere; see
+//! `media::drift` for finding by how much. Whatever else is timed to the
+//! programme goes along: markers, regions and recognized words. Takes are
+//! timed from their event's start, and go with it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::Document;
+use crate::subtitle::edit::Edit;
+use crate::subtitle::markers::Marker;
+use crate::subtitle::regions::Region;
+use crate::subtitle::words::Word;
+
+/// `time * factor + offset`
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Retime {
+    pub factor: f64,
+    pub offset: Seconds,
+}
+
+impl Retime {
+    pub fn apply(self, time: Seconds) -> Seconds {
+        Seconds(time.0 * self.factor + self.offset.0)
+    }
+}
+
+/// Edits retiming every event of `document` by `retime`, and its markers,
+/// regions and words, leaving out those it would move by less than a
+/// millisecond. Times that would come before the start are put at it.
+pub fn suggest(document: &Document, retime: Retime) -> Vec<Edit> {
+    let moved = |a: Seconds, b: Seconds| (a.0 - b.0).abs() >= 0.001;
+    let at = |x: Seconds| Seconds(retime.apply(x).0.max(0.0));
+    let events = document.events.iter()
+        .filter_map(|event| {
+            let start = at(event.start);
+            let end = Seconds(at(event.end).0.max(start.0));
+            (moved(start, event.start) || moved(end, event.end)).then(|| Edit::Update {
+                id: event.id,
+                start: moved(start, event.start).then_some(start),
+                end: moved(end, event.end).then_some(end),
+                style: None, actor: None, margins: None, text: None,
+            })
+        });
+    let markers = document.markers.iter()
+        .filter(|x| moved(at(x.time), x.time))
+        .map(|x| Edit::SetMarker { marker: Marker { time: at(x.time), ..x.clone() } });
+    let regions = document.regions.iter()
+        .filter(|x| moved(at(x.start), x.start) || moved(at(x.end), x.end))
+        .map(|x| {
+            let start = at(x.start);
+            let end = Seconds(at(x.end).0.max(start.0));
+            Edit::SetRegion { region: Region { start, end, ..x.clone() } }
+        });
+    // words are replaced an event's at a time
+    let mut words: Vec<(u32, Vec<Word>, bool)> = Vec::new();
+    for word in &document.words {
+        let start = at(word.start);
+        let end = Seconds(at(word.end).0.max(start.0));
+        let retimed = Word { start, end, ..word.clone() };
+        let changed = moved(start, word.start) || moved(end, word.end);
+        match words.iter_mut().find(|x| x.0 == word.event_id) {
+            Some(x) => {
+                x.1.push(retimed);
+                x.2 |= changed;
+            }
+            None => words.push((word.event_id, vec![retimed], changed)),
+        }
+    }
+    let words = words.into_iter()
+        .filter(|x| x.2)
+        .map(|(event_id, words, _)| Edit::SetWords { event_id, words });
+    events.chain(markers).chain(regions).chain(words).collect()
+}
+
+/// Edits putting back what `edits`, made by `suggest` from `document`, are
+/// going to change
+pub fn undo(document: &Document, edits: &[Edit]) -> Vec<Edit> {
+    edits.iter()
+        .filter_map(|edit| match edit {
+            Edit::Update { id, .. } => document.events.iter().find(|x| x.id == *id)
+                .map(|x| Edit::Update {
+                    id: x.id, start: Some(x.start), end: Some(x.end),
+                    style: None, actor: None, margins: None, text: None,
+                }),
+            Edit::SetMarker { marker } => document.markers.iter().find(|x| x.id == marker.id)
+                .map(|x| Edit::SetMarker { marker: x.clone() }),
+            Edit::SetRegion { region } => document.regions.iter().find(|x| x.id == region.id)
+                .map(|x| Edit::SetRegion { region: x.clone() }),
+            Edit::SetWords { event_id, .. } => Some(Edit::SetWords {
+                event_id: *event_id,
+                words: document.words_of(*event_id).into_iter().cloned().collect(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
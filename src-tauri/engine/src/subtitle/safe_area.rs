@@ -0,0 +1,62 @@
+//! Action-safe and title-safe areas, as broadcast standards define them
+//! relative to the full picture.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum BroadcastStandard {
+    /// EBU R 95 and SMPTE ST 2046-1, for 16:9 HD: 93% action, 90% title
+    Modern,
+    /// the old 4:3 SD convention: 90% action, 80% title
+    Legacy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SafeAreas {
+    pub action_safe: Rect,
+    pub title_safe: Rect,
+}
+
+impl Rect {
+    /// The centered rectangle covering `fraction` of the width and height
+    fn centered(width: f64, height: f64, fraction: f64) -> Rect {
+        Rect {
+            x: width * (1.0 - fraction) / 2.0,
+            y: height * (1.0 - fraction) / 2.0,
+            width: width * fraction,
+            height: height * fraction,
+        }
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width
+            && y >= self.y && y <= self.y + self.height
+    }
+}
+
+impl BroadcastStandard {
+    pub fn areas(self, width: f64, height: f64) -> SafeAreas {
+        let (action, title) = match self {
+            BroadcastStandard::Modern => (0.93, 0.90),
+            BroadcastStandard::Legacy => (0.90, 0.80),
+        };
+        SafeAreas {
+            action_safe: Rect::centered(width, height, action),
+            title_safe: Rect::centered(width, height, title),
+        }
+    }
+}
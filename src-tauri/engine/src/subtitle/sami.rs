@@ -0,0 +1,266 @@
+//! SAMI (.smi). Markup is HTML-like and often not well-formed, so this is a
+//! lenient scanner rather than a real parser: every `<SYNC>` replaces what
+//! is on screen, and every `<P Class=...>` inside it is one language track.
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::{Document, ParseIssue, ParseResult, SubtitleFormat};
+
+const DEFAULT_CLASS: &str = "default";
+
+/// Used for the last sync point, which has nothing after it to end it
+const LAST_CUE_DURATION: f64 = 2.0;
+
+const HEADER: &str = "<SAMI>\n<HEAD>\n<STYLE TYPE=\"text/css\">\n<!--\n\
+    P { margin-left:8pt; margin-right:8pt; margin-bottom:2pt; margin-top:2pt; \
+    text-align:center; }\n";
+
+pub fn detect(source: &str) -> bool {
+    source.trim_start_matches('\u{feff}').trim_start()
+        .get(..5)
+        .is_some_and(|x| x.eq_ignore_ascii_case("<sami"))
+}
+
+pub fn parse(source: &str) -> ParseResult {
+    // ASCII lowercasing keeps byte offsets identical between the two
+    let lower = source.to_ascii_lowercase();
+    let mut document = Document::new(SubtitleFormat::Sami);
+    let mut issues = Vec::new();
+
+    let mut cursor = lower.find("<body").unwrap_or(0);
+    // a stray `</body>` before the body mustn't end it before it begins
+    let body_end = lower[cursor..].find("</body").map_or(lower.len(), |x| cursor + x);
+    let mut open: Vec<(String, Seconds, String)> = Vec::new();
+
+    while let Some(offset) = lower[cursor..body_end].find("<sync") {
+        let tag_start = cursor + offset;
+        let Some(tag_len) = lower[tag_start..body_end].find('>') else {
+            issues.push(ParseIssue {
+                line: line_of(source, tag_start),
+                message: "unterminated SYNC tag".to_owned(),
+            });
+            break;
+        };
+        let tag_end = tag_start + tag_len + 1;
+        let content_end = lower[tag_end..body_end].find("<sync")
+            .map_or(body_end, |x| tag_end + x);
+        cursor = content_end;
+
+        let Some(start) = attribute(&source[tag_start..tag_end], "start")
+            .and_then(|x| x.trim().parse::<i64>().ok()) else
+        {
+            issues.push(ParseIssue {
+                line: line_of(source, tag_start),
+                message: "SYNC tag without a valid Start; skipped".to_owned(),
+            });
+            continue;
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let time = Seconds(start as f64 / 1000.0);
+
+        // a new sync point replaces everything on screen
+        for (class, start, text) in open.drain(..) {
+            if time.0 < start.0 {
+                issues.push(ParseIssue {
+                    line: line_of(source, tag_start),
+                    message: format!("SYNC at {time} goes back in time; cue dropped"),
+                });
+                continue;
+            }
+            document.push_event(start, time, &class, text);
+        }
+
+        for (class, text) in paragraphs(&source[tag_end..content_end]) {
+            let text = to_plain_text(&text);
+            if !text.is_empty() {
+                open.push((class, time, text));
+            }
+        }
+    }
+
+    if !open.is_empty() {
+        issues.push(ParseIssue {
+            line: 0,
+            message: format!("the last cue has no end time; assumed {LAST_CUE_DURATION}s"),
+        });
+    }
+    for (class, start, text) in open {
+        document.push_event(start, Seconds(start.0 + LAST_CUE_DURATION), &class, text);
+    }
+
+    ParseResult { document, issues }
+}
+
+pub fn write(document: &Document) -> String {
+    let mut result = HEADER.to_owned();
+    for style in &document.styles {
+        result.push_str(&format!(".{} {{ Name:{}; }}\n", class_name(&style.name), style.name));
+    }
+    result.push_str("-->\n</STYLE>\n</HEAD>\n<BODY>\n");
+
+    // every time something appears or disappears needs a sync point
+    let mut times: Vec<f64> = document.events.iter()
+        .flat_map(|x| [x.start.0, x.end.0])
+        .collect();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+
+    let fallback_class = document.styles.first()
+        .map_or(DEFAULT_CLASS.to_owned(), |x| class_name(&x.name));
+    for time in times {
+        let mut paragraphs: Vec<(String, Vec<&str>)> = Vec::new();
+        for event in document.events.iter()
+            .filter(|x| x.start.0 <= time && time < x.end.0)
+        {
+            let class = class_name(&event.style);
+            match paragraphs.iter_mut().find(|(c, _)| *c == class) {
+                Some((_, texts)) => texts.push(&event.text),
+                None => paragraphs.push((class, vec![&event.text])),
+            }
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        let ms = (time * 1000.0).round() as i64;
+        if paragraphs.is_empty() {
+            result.push_str(&format!("<SYNC Start={ms}><P Class={fallback_class}>&nbsp;\n"));
+            continue;
+        }
+        result.push_str(&format!("<SYNC Start={ms}>"));
+        for (class, texts) in paragraphs {
+            let text = texts.iter()
+                .map(|x| escape(x).replace('\n', "<br>"))
+                .collect::<Vec<_>>()
+                .join("<br>");
+            result.push_str(&format!("<P Class={class}>{text}\n"));
+        }
+    }
+    result.push_str("</BODY>\n</SAMI>\n");
+    result
+}
+
+fn class_name(style: &str) -> String {
+    let name: String = style.chars().filter(char::is_ascii_alphanumeric).collect();
+    if name.is_empty() { DEFAULT_CLASS.to_owned() } else { name }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Splits the content of a sync point into `(class, markup)` pairs
+fn paragraphs(content: &str) -> Vec<(String, String)> {
+    let lower = content.to_ascii_lowercase();
+    let mut result = Vec::new();
+    let mut starts: Vec<usize> = lower.match_indices("<p")
+        .map(|(i, _)| i)
+        .filter(|&i| lower[i + 2..].starts_with(['>', ' ', '\t', '\r', '\n']))
+        .collect();
+    if starts.is_empty() {
+        result.push((DEFAULT_CLASS.to_owned(), content.to_owned()));
+        return result;
+    }
+    starts.push(content.len());
+    for pair in starts.windows(2) {
+        let (begin, end) = (pair[0], pair[1]);
+        let Some(tag_len) = content[begin..end].find('>') else { continue };
+        let class = attribute(&content[begin..=begin + tag_len], "class")
+            .unwrap_or_else(|| DEFAULT_CLASS.to_owned());
+        result.push((class, content[begin + tag_len + 1..end].to_owned()));
+    }
+    result
+}
+
+/// Reads `name=value`, `name="value"` or `name='value'` from inside a tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(i) = lower[from..].find(name).map(|x| from + x) {
+        from = i + name.len();
+        let preceded_by_space = lower[..i].ends_with(|c: char| c.is_ascii_whitespace());
+        let rest = lower[from..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        return Some(match value.chars().next() {
+            Some(q @ ('"' | '\'')) => value[1..].split(q).next().unwrap_or("").to_owned(),
+            _ => value
+                .split(|c: char| c.is_ascii_whitespace() || c == '>')
+                .next().unwrap_or("").to_owned(),
+        });
+    }
+    None
+}
+
+/// HTML whitespace rules, `<br>` as line breaks, all other tags dropped
+pub(crate) fn to_plain_text(markup: &str) -> String {
+    let mut collapsed = String::new();
+    let mut rest = markup;
+    while !rest.is_empty() {
+        if rest.starts_with('<') {
+            let end = rest.find('>').map_or(rest.len(), |x| x + 1);
+            let tag = rest[..end].to_ascii_lowercase();
+            if tag.starts_with("<br") {
+                collapsed.push('\n');
+            }
+            rest = &rest[end..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            for c in rest[..end].chars() {
+                if c.is_whitespace() {
+                    if !collapsed.ends_with([' ', '\n']) {
+                        collapsed.push(' ');
+                    }
+                } else {
+                    collapsed.push(c);
+                }
+            }
+            rest = &rest[end..];
+        }
+    }
+    decode_entities(&collapsed)
+        .split('\n')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_owned()
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let Some(end) = rest.find(';').filter(|&x| x <= 10) else {
+            result.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity.to_ascii_lowercase().as_str() {
+            "nbsp" => Some(' '),
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            x if x.starts_with("#x") => u32::from_str_radix(&x[2..], 16).ok().and_then(char::from_u32),
+            x if x.starts_with('#') => x[1..].parse::<u32>().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        if let Some(c) = decoded {
+            result.push(c);
+            rest = &rest[end + 1..];
+        } else {
+            result.push('&');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+pub(crate) fn line_of(source: &str, offset: usize) -> usize {
+    source.as_bytes()[..offset].iter().filter(|&&x| x == b'\n').count() + 1
+}
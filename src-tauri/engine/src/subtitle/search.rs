@@ -0,0 +1,71 @@
+//! Finding text anywhere in a document: event texts and actors, marker
+//! labels and region labels, for a palette that jumps to whatever matches
+//! across all open documents.
+
+use serde::Serialize;
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::Document;
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+#[ts(export)]
+pub enum SearchHit {
+    #[serde(rename_all = "camelCase")]
+    Event { id: u32, start: Seconds, end: Seconds, actor: String, text: String },
+    #[serde(rename_all = "camelCase")]
+    Marker { id: u32, time: Seconds, label: String },
+    #[serde(rename_all = "camelCase")]
+    Region { id: u32, start: Seconds, end: Seconds, label: String },
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SearchGroup {
+    pub document_id: i32,
+    /// how many hits there are, of which `hits` has the first few
+    pub total: usize,
+    pub hits: Vec<SearchHit>,
+}
+
+/// The words of a query, lowercased; a hit has to contain all of them
+pub fn terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(str::to_lowercase).collect()
+}
+
+fn matches(terms: &[String], fields: &[&str]) -> bool {
+    let fields: Vec<String> = fields.iter().map(|x| x.to_lowercase()).collect();
+    terms.iter().all(|term| fields.iter().any(|x| x.contains(term)))
+}
+
+/// Events first, in document order, then markers and regions, which are
+/// sorted by time already; no more than `limit` of them
+pub fn search(document_id: i32, document: &Document, terms: &[String], limit: usize) -> SearchGroup {
+    let events = document.events.iter()
+        .filter(|x| matches(terms, &[&x.text, &x.actor]))
+        .map(|x| SearchHit::Event {
+            id: x.id,
+            start: x.start,
+            end: x.end,
+            actor: x.actor.to_string(),
+            text: x.text.clone(),
+        });
+    let markers = document.markers.iter()
+        .filter(|x| matches(terms, &[&x.label]))
+        .map(|x| SearchHit::Marker { id: x.id, time: x.time, label: x.label.clone() });
+    let regions = document.regions.iter()
+        .filter(|x| matches(terms, &[&x.label]))
+        .map(|x| SearchHit::Region {
+            id: x.id, start: x.start, end: x.end, label: x.label.clone(),
+        });
+    let mut total = 0;
+    let mut hits = Vec::new();
+    for hit in events.chain(markers).chain(regions) {
+        total += 1;
+        if hits.len() < limit {
+            hits.push(hit);
+        }
+    }
+    SearchGroup { document_id, total, hits }
+}
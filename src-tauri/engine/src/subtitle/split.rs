@@ -0,0 +1,227 @@
+//! Splitting a cue that is too long to read into several, at the ends of
+//! sentences where it can and of clauses where it must. Words are what is
+//! between white space, so text written without spaces is only split where
+//! it has some. Each piece gets the time its words take: from word timings
+//! if an aligner gave them, from karaoke tags if the event has them, and in
+//! proportion to its length otherwise.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::Event;
+use crate::subtitle::edit::SplitPiece;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SplitPolicy {
+    /// longest a piece may last
+    pub max_duration: Seconds,
+    /// most characters a piece may have, not counting tags and line breaks
+    pub max_chars: usize,
+    /// shortest a piece should last; shorter ones are avoided, not ruled out
+    pub min_duration: Seconds,
+}
+
+/// What breaking after a word costs, by how it ends
+const SENTENCE_BREAK: f64 = 0.0;
+const LINE_BREAK: f64 = 0.5;
+const CLAUSE_BREAK: f64 = 1.0;
+const WORD_BREAK: f64 = 3.0;
+/// Every piece costs something, so that there are no more than needed
+const PIECE_COST: f64 = 1.0;
+/// Going over a limit costs more than any break could, plus more the
+/// further over it goes
+const OVER_LIMIT: f64 = 20.0;
+/// Cost of a piece shorter than the minimum, per minimum it falls short
+const TOO_SHORT: f64 = 5.0;
+
+struct Word {
+    /// byte offset in the text, including the tags right before the word
+    from: usize,
+    chars: usize,
+    /// what breaking after it costs
+    break_cost: f64,
+    /// start of the karaoke syllable it starts in, from the event's start
+    karaoke: Option<f64>,
+}
+
+fn break_cost(last: char) -> f64 {
+    match last {
+        '.' | '!' | '?' | '…' | '。' | '！' | '？' => SENTENCE_BREAK,
+        ',' | ';' | ':' | '—' | '–' | '、' | '，' | '；' | '：' => CLAUSE_BREAK,
+        _ => WORD_BREAK,
+    }
+}
+
+/// Duration of a `\k`, `\K`, `\kf` or `\ko` tag, in seconds
+fn karaoke(tag: &str) -> Option<f64> {
+    let arg = tag.strip_prefix("kf").or_else(|| tag.strip_prefix("ko"))
+        .or_else(|| tag.strip_prefix('k'))
+        .or_else(|| tag.strip_prefix('K'))?;
+    arg.trim().parse::<f64>().ok().map(|x| x / 100.0)
+}
+
+fn words(text: &str) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    let mut in_word = false;
+    // start of the tags since the last word, which go with the next one
+    let mut tags_from = None;
+    let mut elapsed = 0.0;
+    let mut syllable = None;
+    let mut at = 0;
+    while at < text.len() {
+        let rest = &text[at..];
+        if rest.starts_with('{')
+            && let Some(close) = rest.find('}')
+        {
+            if !in_word {
+                tags_from.get_or_insert(at);
+            }
+            for tag in rest[1..close].split('\\').skip(1) {
+                if let Some(duration) = karaoke(tag) {
+                    syllable = Some(elapsed);
+                    elapsed += duration;
+                }
+            }
+            at += close + 1;
+            continue;
+        }
+        let c = rest.chars().next().unwrap();
+        if c.is_whitespace() {
+            in_word = false;
+            if c == '\n' && let Some(word) = words.last_mut() {
+                word.break_cost = word.break_cost.min(LINE_BREAK);
+            }
+        } else {
+            if !in_word {
+                in_word = true;
+                words.push(Word {
+                    from: tags_from.take().unwrap_or(at),
+                    chars: 0,
+                    break_cost: WORD_BREAK,
+                    karaoke: syllable,
+                });
+            }
+            let word = words.last_mut().unwrap();
+            word.chars += 1;
+            // closing quotes and brackets don't hide the punctuation before
+            if !matches!(c, '"' | '\'' | '”' | '’' | '»' | ')' | ']') {
+                word.break_cost = break_cost(c);
+            }
+        }
+        at += c.len_utf8();
+    }
+    words
+}
+
+/// The leading override block of `text` without its karaoke tags, to be
+/// repeated at the start of every piece; empty if there is none
+fn leading_tags(text: &str) -> String {
+    let Some(close) = text.strip_prefix('{').and_then(|x| x.find('}')) else {
+        return String::new();
+    };
+    let tags: Vec<&str> = text[1..=close].split('\\').skip(1)
+        .filter(|x| karaoke(x).is_none())
+        .collect();
+    if tags.is_empty() {
+        String::new()
+    } else {
+        format!("{{\\{}}}", tags.join("\\"))
+    }
+}
+
+/// Pieces of `event` that keep to `policy` as well as its words allow;
+/// just the one if it already does. `word_starts`, if given, says when
+/// each word is spoken, and has to have as many as the event has words.
+/// Overrides in the middle of the text stay where they are, so one that
+/// is switched on in a piece isn't carried over to the next.
+pub fn split(
+    event: &Event, policy: &SplitPolicy, word_starts: Option<&[Seconds]>,
+) -> Result<Vec<SplitPiece>, String> {
+    let text = &event.text;
+    let words = words(text);
+    let duration = event.end.0 - event.start.0;
+    if words.is_empty() || duration <= 0.0 {
+        return Ok(vec![SplitPiece { start: event.start, end: event.end, text: text.clone() }]);
+    }
+    if !(policy.max_duration.0 > 0.0 && policy.max_chars > 0) {
+        return Err("invalid split policy".to_owned());
+    }
+
+    // when each word starts, from the event's start, and when the last ends
+    let total_chars: usize = words.iter().map(|x| x.chars).sum();
+    let mut times: Vec<f64> = match word_starts {
+        Some(starts) if starts.len() != words.len() => {
+            return Err(format!("{} word timings for {} words", starts.len(), words.len()));
+        }
+        Some(starts) => starts.iter().map(|x| x.0 - event.start.0).collect(),
+        None if words.iter().any(|x| x.karaoke.is_some()) => {
+            words.iter().map(|x| x.karaoke.unwrap_or(0.0)).collect()
+        }
+        None => words.iter()
+            .scan(0, |before, x| {
+                let time = fraction(*before, total_chars) * duration;
+                *before += x.chars;
+                Some(time)
+            })
+            .collect(),
+    };
+    times[0] = 0.0;
+    times.push(duration);
+    for i in 1..times.len() {
+        times[i] = times[i].clamp(times[i - 1], duration);
+    }
+
+    // best[i]: the cheapest way to split the first i words, and where its
+    // last piece starts
+    let mut best = vec![(f64::INFINITY, 0); words.len() + 1];
+    best[0].0 = 0.0;
+    for to in 1..=words.len() {
+        for from in 0..to {
+            let chars = words[from..to].iter().map(|x| x.chars).sum::<usize>() + (to - from - 1);
+            let length = times[to] - times[from];
+            let mut cost = best[from].0 + PIECE_COST + (length / policy.max_duration.0).powi(2);
+            if to < words.len() {
+                cost += words[to - 1].break_cost;
+            }
+            if chars > policy.max_chars {
+                cost += OVER_LIMIT * fraction(chars, policy.max_chars);
+            }
+            if length > policy.max_duration.0 {
+                cost += OVER_LIMIT * length / policy.max_duration.0;
+            }
+            if policy.min_duration.0 > 0.0 && length < policy.min_duration.0 {
+                cost += TOO_SHORT * (1.0 - length / policy.min_duration.0);
+            }
+            if cost < best[to].0 {
+                best[to] = (cost, from);
+            }
+        }
+    }
+    let mut bounds = vec![words.len()];
+    while let Some(&to) = bounds.last() && to > 0 {
+        bounds.push(best[to].1);
+    }
+    bounds.reverse();
+
+    let tags = leading_tags(text);
+    Ok(bounds.windows(2)
+        .map(|x| {
+            let (from, to) = (x[0], x[1]);
+            let start = if from == 0 { 0 } else { words[from].from };
+            let end = words.get(to).map_or(text.len(), |x| x.from);
+            let piece = text[start..end].trim();
+            SplitPiece {
+                start: Seconds(event.start.0 + times[from]),
+                end: Seconds(event.start.0 + times[to]),
+                text: if from == 0 { piece.to_owned() } else { format!("{tags}{piece}") },
+            }
+        })
+        .collect())
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn fraction(count: usize, total: usize) -> f64 {
+    count as f64 / total.max(1) as f64
+}
@@ -0,0 +1,110 @@
+//! What the split tool shows while it is dragged through a cue: the audio
+//! level around the proposed time, and the pause in speech nearest to it to
+//! snap to. A drag asks far more often than audio can be read, so requests
+//! go through a `Throttle` that lets the latest one through at a time.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::media::audio::ClassifiedWaveform;
+use crate::media::audio_class;
+use crate::media::units::Seconds;
+
+/// Least time between two previews run
+pub const PREVIEW_INTERVAL: Duration = Duration::from_millis(50);
+/// How far either side of the proposed time to look, within the cue
+pub const REACH: Seconds = Seconds(1.5);
+/// Pauses shorter than this, as inside a word, are no place to split
+const MIN_GAP: Seconds = Seconds(0.06);
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SplitPreview {
+    pub event_id: u32,
+    /// the proposed split, as asked
+    pub time: Seconds,
+    /// time of the first value of `peaks`
+    pub start_time: Seconds,
+    pub sample_per_second: usize,
+    pub peaks: Vec<f32>,
+    /// the pause in speech nearest to `time`, inside the cue; absent if
+    /// there is none in reach
+    pub gap: Option<(Seconds, Seconds)>,
+    /// the middle of `gap`, where the split would snap to
+    pub snap: Option<Seconds>,
+}
+
+/// The preview for splitting the cue `bounds` at `time`, from `waveform`,
+/// which covers the part of it in reach. A pause running into the cue's
+/// start or end is the silence around its speech, not a gap in it.
+pub fn preview(
+    event_id: u32, time: Seconds, bounds: (Seconds, Seconds), waveform: ClassifiedWaveform,
+) -> SplitPreview {
+    #[allow(clippy::cast_precision_loss)]
+    let per_second = waveform.sample_per_second as f64;
+    #[allow(clippy::cast_precision_loss)]
+    let window_end = waveform.start_time.0 + waveform.classes.len() as f64 / per_second;
+    let speech = audio_class::speech_segments(
+        &waveform.classes, waveform.start_time, per_second, MIN_GAP);
+
+    let mut gaps: Vec<(f64, f64)> = speech.windows(2).map(|x| (x[0].1.0, x[1].0.0)).collect();
+    let touches = |edge: f64, bound: Seconds| (edge - bound.0).abs() < 0.5 / per_second;
+    if let (Some(first), Some(last)) = (speech.first(), speech.last()) {
+        if !touches(waveform.start_time.0, bounds.0) && first.0.0 - waveform.start_time.0 >= MIN_GAP.0 {
+            gaps.insert(0, (waveform.start_time.0, first.0.0));
+        }
+        if !touches(window_end, bounds.1) && window_end - last.1.0 >= MIN_GAP.0 {
+            gaps.push((last.1.0, window_end));
+        }
+    }
+    let distance = |(from, to): (f64, f64)| (from - time.0).max(time.0 - to).max(0.0);
+    let gap = gaps.into_iter().min_by(|a, b| distance(*a).total_cmp(&distance(*b)));
+
+    SplitPreview {
+        event_id,
+        time,
+        start_time: waveform.start_time,
+        sample_per_second: waveform.sample_per_second,
+        peaks: waveform.peaks,
+        gap: gap.map(|(from, to)| (Seconds(from), Seconds(to))),
+        snap: gap.map(|(from, to)| Seconds((from + to) / 2.0)),
+    }
+}
+
+/// Lets requests through no more often than `PREVIEW_INTERVAL`; one
+/// overtaken by a newer request while it waits is dropped
+pub struct Throttle {
+    latest: u64,
+    last_run: Option<Instant>,
+}
+
+impl Throttle {
+    pub fn new() -> Self {
+        Self { latest: 0, last_run: None }
+    }
+
+    /// A ticket for a new request, which overtakes every one before it
+    pub fn enter(&mut self) -> u64 {
+        self.latest += 1;
+        self.latest
+    }
+
+    /// How much longer the request with `ticket` has to wait, or `None` if
+    /// it has been overtaken. Once it comes out as zero the request counts
+    /// as run.
+    pub fn wait(&mut self, ticket: u64) -> Option<Duration> {
+        if ticket != self.latest {
+            return None;
+        }
+        let now = Instant::now();
+        let ready = self.last_run.map_or(now, |x| x + PREVIEW_INTERVAL);
+        if ready <= now {
+            self.last_run = Some(now);
+            Some(Duration::ZERO)
+        } else {
+            Some(ready - now)
+        }
+    }
+}
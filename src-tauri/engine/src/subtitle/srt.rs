@@ -0,0 +1,95 @@
+//! SubRip (.srt): numbered cues with a time range and a few lines of text,
+//! which may carry `<i>`, `<b>`, `<u>`, `<s>` and `<font>` tags and a
+//! leading `{\an8}`. Nothing is kept as a document of its own; see `convert`.
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::ParseIssue;
+
+pub struct Cue {
+    pub start: Seconds,
+    pub end: Seconds,
+    /// lines are separated by `\n`
+    pub text: String,
+}
+
+/// `HH:MM:SS,mmm`; a dot for the comma and any number of digits are
+/// also taken, as written by some tools
+pub fn parse_time(s: &str) -> Option<Seconds> {
+    let (hms, fraction) = s.trim().split_once([',', '.']).unwrap_or((s.trim(), "0"));
+    let mut parts = hms.split(':');
+    let h: u32 = parts.next()?.trim().parse().ok()?;
+    let m: u32 = parts.next()?.parse().ok()?;
+    let s: u32 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() || !fraction.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    let fraction: f64 = format!("0.{fraction}").parse().ok()?;
+    Some(Seconds(f64::from(h) * 3600.0 + f64::from(m) * 60.0 + f64::from(s) + fraction))
+}
+
+/// `start --> end`, possibly followed by coordinates, which are ignored
+pub fn parse_timing(line: &str) -> Option<(Seconds, Seconds)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+    Some((parse_time(start)?, parse_time(end)?))
+}
+
+/// Cues in file order. The number before each timing line is not checked,
+/// and text runs until a blank line.
+pub fn parse(source: &str) -> (Vec<Cue>, Vec<ParseIssue>) {
+    let mut cues = Vec::new();
+    let mut issues = Vec::new();
+    let mut current: Option<Cue> = None;
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim_start_matches('\u{feff}').trim_end();
+        if let Some(cue) = current.as_mut() {
+            if line.trim().is_empty() {
+                cues.extend(current.take());
+            } else {
+                if !cue.text.is_empty() {
+                    cue.text.push('\n');
+                }
+                cue.text.push_str(line);
+            }
+            continue;
+        }
+        if line.trim().is_empty() || line.trim().bytes().all(|x| x.is_ascii_digit()) {
+            continue;
+        }
+        match parse_timing(line) {
+            Some((start, mut end)) => {
+                if end.0 < start.0 {
+                    issues.push(ParseIssue {
+                        line: i + 1,
+                        message: "cue ends before it starts; made instant".to_owned(),
+                    });
+                    end = start;
+                }
+                current = Some(Cue { start, end, text: String::new() });
+            }
+            None => issues.push(ParseIssue {
+                line: i + 1,
+                message: "text outside of any cue; skipped".to_owned(),
+            }),
+        }
+    }
+    cues.extend(current);
+    (cues, issues)
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format_time(time: Seconds) -> String {
+    let ms = (time.0.max(0.0) * 1000.0).round() as u64;
+    format!("{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
+
+/// Numbers the cues from 1 in the order given
+pub fn write(cues: &[Cue]) -> String {
+    cues.iter().enumerate().map(|(i, cue)| write_cue(i + 1, cue)).collect()
+}
+
+/// One cue, numbered `number`, with the blank line that ends it
+pub fn write_cue(number: usize, cue: &Cue) -> String {
+    format!("{number}\n{} --> {}\n{}\n\n", format_time(cue.start), format_time(cue.end), cue.text)
+}
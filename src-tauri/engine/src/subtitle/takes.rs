@@ -0,0 +1,126 @@
+//! Voice-over takes: recordings made against an event, for table reads and
+//! rough dubs. Each event can have several; the selected one goes into the
+//! voice-over track, trimmed and turned up or down as set. The recordings
+//! are files in a directory the user chose, and only their paths are kept
+//! here.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::Document;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Take {
+    pub id: u32,
+    pub event_id: u32,
+    pub path: String,
+    /// where the recording starts relative to the event's start; negative
+    /// with pre-roll
+    pub offset: Seconds,
+    pub length: Seconds,
+    pub selected: bool,
+    /// cut from the start of the recording when it is played
+    #[serde(default = "no_trim")]
+    pub trim_start: Seconds,
+    /// and from its end
+    #[serde(default = "no_trim")]
+    pub trim_end: Seconds,
+    /// in dB
+    #[serde(default)]
+    pub gain: f64,
+}
+
+fn no_trim() -> Seconds {
+    Seconds(0.0)
+}
+
+/// Quieter than this is as good as muted, and louder can only clip
+const GAIN_RANGE: std::ops::RangeInclusive<f64> = -60.0..=24.0;
+
+impl Take {
+    /// How long the part played is
+    pub fn kept(&self) -> Seconds {
+        Seconds(self.length.0 - self.trim_start.0 - self.trim_end.0)
+    }
+}
+
+impl Document {
+    /// A selected take replaces the one selected for its event before
+    pub fn add_take(
+        &mut self, event_id: u32, path: String, offset: Seconds, length: Seconds,
+        selected: bool,
+    ) -> Result<u32, String> {
+        if !self.events.iter().any(|x| x.id == event_id) {
+            return Err(format!("no event with id {event_id}"));
+        }
+        let id = self.next_take_id;
+        self.next_take_id += 1;
+        if selected {
+            for take in self.takes.iter_mut().filter(|x| x.event_id == event_id) {
+                take.selected = false;
+            }
+        }
+        self.takes.push(Take {
+            id, event_id, path, offset, length, selected,
+            trim_start: no_trim(), trim_end: no_trim(), gain: 0.0,
+        });
+        Ok(id)
+    }
+
+    /// Makes `id` the one take of its event that is selected
+    pub fn select_take(&mut self, id: u32) -> Result<(), String> {
+        let event_id = self.takes.iter().find(|x| x.id == id)
+            .ok_or(format!("no take with id {id}"))?
+            .event_id;
+        for take in self.takes.iter_mut().filter(|x| x.event_id == event_id) {
+            take.selected = take.id == id;
+        }
+        Ok(())
+    }
+
+    /// Fields that are absent are left as they are
+    pub fn update_take(
+        &mut self, id: u32,
+        trim_start: Option<Seconds>, trim_end: Option<Seconds>, gain: Option<f64>,
+    ) -> Result<(), String> {
+        let take = self.takes.iter_mut().find(|x| x.id == id)
+            .ok_or(format!("no take with id {id}"))?;
+        let mut updated = take.clone();
+        if let Some(x) = trim_start { updated.trim_start = x; }
+        if let Some(x) = trim_end { updated.trim_end = x; }
+        if let Some(x) = gain { updated.gain = x; }
+        if !(updated.trim_start.0 >= 0.0 && updated.trim_end.0 >= 0.0 && updated.kept().0 > 0.0) {
+            return Err(format!("invalid trim: {} and {} of {}",
+                updated.trim_start, updated.trim_end, updated.length));
+        }
+        if !GAIN_RANGE.contains(&updated.gain) {
+            return Err(format!("gain out of range: {} dB", updated.gain));
+        }
+        *take = updated;
+        Ok(())
+    }
+
+    /// The take removed, whose file is left to the caller
+    pub fn remove_take(&mut self, id: u32) -> Result<Take, String> {
+        let position = self.takes.iter().position(|x| x.id == id)
+            .ok_or(format!("no take with id {id}"))?;
+        Ok(self.takes.remove(position))
+    }
+
+    /// The selected takes, and where on the timeline the part of each that
+    /// is played starts; takes of events that have since been removed are
+    /// left out
+    pub fn voice_over(&self) -> Vec<(Seconds, &Take)> {
+        self.takes.iter()
+            .filter(|x| x.selected)
+            .filter_map(|take| self.events.iter()
+                .find(|x| x.id == take.event_id)
+                .map(|event| (
+                    Seconds(event.start.0 + take.offset.0 + take.trim_start.0),
+                    take,
+                )))
+            .collect()
+    }
+}
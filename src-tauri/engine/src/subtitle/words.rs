@@ -0,0 +1,90 @@
+//! Words as speech recognition heard them, kept with the events of a
+//! document made from a transcript: when each was said and how sure the
+//! recognizer was of it. Going through the least certain first is quicker
+//! than proofing hours of transcript line by line. They are what was
+//! heard, so editing an event's text leaves them as they are; removing it
+//! removes them, and splitting or merging events takes them along.
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::document::Document;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Word {
+    pub event_id: u32,
+    pub start: Seconds,
+    pub end: Seconds,
+    pub text: String,
+    /// from 0 to 1; absent if the recognizer didn't say
+    pub confidence: Option<f32>,
+}
+
+/// Words in a row of one event that all fall below a threshold
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct LowConfidenceSpan {
+    pub event_id: u32,
+    pub start: Seconds,
+    pub end: Seconds,
+    pub text: String,
+    /// of the least certain word in it
+    pub confidence: f32,
+}
+
+impl Document {
+    /// Adds the words heard in event `event_id`
+    pub fn add_words(&mut self, event_id: u32, words: impl IntoIterator<Item = Word>) {
+        self.words.extend(words.into_iter().map(|x| Word { event_id, ..x }));
+    }
+
+    /// The words of event `event_id`, in order
+    pub fn words_of(&self, event_id: u32) -> Vec<&Word> {
+        self.words.iter().filter(|x| x.event_id == event_id).collect()
+    }
+
+    /// Puts `words` in place of the words of event `event_id`
+    pub fn replace_words(&mut self, event_id: u32, words: Vec<Word>) {
+        let at = self.words.iter().position(|x| x.event_id == event_id)
+            .unwrap_or(self.words.len());
+        self.words.retain(|x| x.event_id != event_id);
+        self.words.splice(at..at, words);
+    }
+
+    /// Spans of words with a confidence below `threshold`, least certain
+    /// first; words of events that have since been removed are left out
+    pub fn low_confidence_spans(&self, threshold: f32) -> Vec<LowConfidenceSpan> {
+        let mut spans: Vec<LowConfidenceSpan> = Vec::new();
+        let mut previous: Option<u32> = None;
+        for word in &self.words {
+            let low = word.confidence.filter(|&x| x < threshold);
+            let Some(confidence) = low else {
+                previous = None;
+                continue;
+            };
+            match spans.last_mut() {
+                Some(span) if previous == Some(word.event_id) => {
+                    span.end = Seconds(span.end.0.max(word.end.0));
+                    span.text.push(' ');
+                    span.text.push_str(&word.text);
+                    span.confidence = span.confidence.min(confidence);
+                }
+                _ => spans.push(LowConfidenceSpan {
+                    event_id: word.event_id,
+                    start: word.start,
+                    end: word.end,
+                    text: word.text.clone(),
+                    confidence,
+                }),
+            }
+            previous = Some(word.event_id);
+        }
+        spans.retain(|span| self.events.iter().any(|x| x.id == span.event_id));
+        spans.sort_by(|a, b| a.confidence.total_cmp(&b.confidence)
+            .then(a.start.0.total_cmp(&b.start.0)));
+        spans
+    }
+}
@@ -0,0 +1,25 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use subtle_fuzz::subtitle::ass;
+
+// Fed line by line as `open_subtitle` does, detected as ASS or not
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let mut parser = ass::Parser::new();
+    for line in source.lines() {
+        parser.feed_line(line);
+    }
+    let result = parser.finish();
+    let written = ass::write(&result.document);
+    for event in &result.document.events {
+        ass::parse_overrides(&event.text);
+    }
+
+    // what was written must read again
+    let mut parser = ass::Parser::new();
+    for line in written.lines() {
+        parser.feed_line(line);
+    }
+    parser.finish();
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use subtle_fuzz::subtitle::microdvd;
+
+// The first eight bytes are the framerate, which may well be zero, negative
+// or NaN; the rest is the file
+fuzz_target!(|data: &[u8]| {
+    let Some((framerate, rest)) = data.split_first_chunk::<8>() else { return };
+    let framerate = f64::from_le_bytes(*framerate);
+    let source = String::from_utf8_lossy(rest);
+    microdvd::detect_framerate(&source);
+    let result = microdvd::parse(&source, framerate);
+    microdvd::write(&result.document, framerate);
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use subtle_fuzz::subtitle::document::SubtitleFormat;
+use subtle_fuzz::subtitle::{ass, convert, microdvd, parse, sami};
+
+// Whatever a downloaded file holds, detecting, parsing and writing it back
+// must not panic
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let Ok((result, _)) = parse::parse(&source, None, |_| {}) else { return };
+    let document = &result.document;
+    match document.format {
+        SubtitleFormat::Ass => { ass::write(document); }
+        SubtitleFormat::Sami => { sami::write(document); }
+        SubtitleFormat::MicroDvd => {
+            microdvd::write(document, document.framerate.unwrap_or(23.976));
+        }
+        SubtitleFormat::Srt => { convert::write_srt(document); }
+    }
+});
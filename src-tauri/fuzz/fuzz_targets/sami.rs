@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use subtle_fuzz::subtitle::sami;
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let result = sami::parse(&source);
+    sami::parse(&sami::write(&result.document));
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use subtle_fuzz::subtitle::{repair, srt};
+
+// Repairing whatever a file holds, and writing what comes of it out and
+// reading it again, must not panic
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let (cues, _) = repair::repair(&source);
+    let written = srt::write(&cues);
+    srt::parse(&written);
+});
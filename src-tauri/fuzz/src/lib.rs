@@ -0,0 +1,51 @@
+//! The subtitle parsers, built on their own for fuzzing. The engine crate
+//! needs ffmpeg to build, so instead of depending on it the sources are
+//! pulled in as modules. What they use from the rest of the engine comes
+//! from modules that need ffmpeg or more crates, so those two types are
+//! stood in for here.
+//!
+//! Run with `cargo fuzz run <target>` from `src-tauri/fuzz`.
+
+pub mod media {
+    pub mod units {
+        /// as in `media::units`
+        #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize, ts_rs::TS)]
+        pub struct Seconds(pub f64);
+
+        impl std::fmt::Display for Seconds {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{:.3}s", self.0)
+            }
+        }
+    }
+}
+
+pub mod encoding {
+    use serde::{Deserialize, Serialize};
+
+    /// as in `encoding`
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+    pub struct TextFormat {
+        pub encoding: String,
+        pub bom: bool,
+    }
+}
+
+#[allow(dead_code, clippy::new_without_default)]
+#[path = "../../engine/src/subtitle"]
+pub mod subtitle {
+    pub mod document;
+    pub mod interval;
+    pub mod markers;
+    pub mod positioning;
+    pub mod regions;
+    pub mod takes;
+    pub mod words;
+    pub mod srt;
+    pub mod repair;
+    pub mod convert;
+    pub mod ass;
+    pub mod sami;
+    pub mod microdvd;
+    pub mod parse;
+}
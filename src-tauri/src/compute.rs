@@ -0,0 +1,175 @@
+//! Which device an ML job like `transcribe` runs on. A model that doesn't
+//! fit in what is left of a GPU's memory fails halfway or crawls, and the
+//! video decoder may be using the same GPU for hardware decoding. So a job
+//! estimates what its model needs, reserves that on a GPU with room for it,
+//! counting what our other jobs have reserved and leaving some over for the
+//! decoder, and runs on the CPU if no GPU has the room. GPUs are found with
+//! `nvidia-smi`: CUDA is what GPU builds of whisper.cpp mostly use, and
+//! other GPUs aren't listed. They are numbered as `nvidia-smi` numbers
+//! them, by PCI bus, while CUDA puts the fastest first, so a job is told
+//! its GPU by UUID.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+const MIB: u64 = 1024 * 1024;
+/// Left free on every GPU, as hardware decoding takes the system's default
+/// device, which can be any of them
+const DECODE_HEADROOM: u64 = 512 * MIB;
+/// What a model needs besides its weights, for compute buffers and the
+/// like: this much of their size, and a fixed amount on top
+const MODEL_OVERHEAD: f64 = 0.25;
+const MODEL_WORKSPACE: u64 = 256 * MIB;
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ComputeDevice {
+    /// of the GPU, for `DevicePreference::Gpu`; absent for the CPU
+    pub index: Option<u32>,
+    pub name: String,
+    /// in bytes; absent for the CPU
+    pub total_memory: Option<u64>,
+    pub free_memory: Option<u64>,
+    /// by jobs of ours running on it
+    pub reserved: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+#[ts(export)]
+pub enum DevicePreference {
+    /// the GPU with the most room, if any has enough
+    #[default]
+    Auto,
+    Cpu,
+    /// this GPU if it has enough room, the CPU otherwise
+    #[serde(rename_all = "camelCase")]
+    Gpu { index: u32 },
+}
+
+struct Gpu {
+    index: u32,
+    uuid: String,
+    name: String,
+    total: u64,
+    free: u64,
+}
+
+/// `nvidia-smi`'s view of the GPUs; none if it isn't there
+fn gpus() -> Vec<Gpu> {
+    let output = Command::new("nvidia-smi")
+        .arg("--query-gpu=index,uuid,name,memory.total,memory.free")
+        .arg("--format=csv,noheader,nounits")
+        .output();
+    let Ok(output) = output.inspect_err(|e| log::debug!("nvidia-smi: {e}")) else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+    String::from_utf8_lossy(&output.stdout).lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [index, uuid, name, total, free] = fields[..] else { return None };
+            Some(Gpu {
+                index: index.parse().ok()?,
+                uuid: uuid.to_owned(),
+                name: name.to_owned(),
+                total: total.parse::<u64>().ok()? * MIB,
+                free: free.parse::<u64>().ok()? * MIB,
+            })
+        })
+        .collect()
+}
+
+/// Bytes reserved by running jobs, by id and GPU
+static RESERVED: Mutex<Vec<(u64, u32, u64)>> = Mutex::new(Vec::new());
+static NEXT_RESERVATION: Mutex<u64> = Mutex::new(0);
+
+fn reserved_on(reserved: &[(u64, u32, u64)], index: u32) -> u64 {
+    reserved.iter().filter(|x| x.1 == index).map(|x| x.2).sum()
+}
+
+/// The CPU, then every GPU found
+pub fn list_devices() -> Vec<ComputeDevice> {
+    let reserved = RESERVED.lock().unwrap();
+    let cpu = ComputeDevice {
+        index: None,
+        name: format!("CPU ({} threads)", num_cpus::get()),
+        total_memory: None,
+        free_memory: None,
+        reserved: 0,
+    };
+    std::iter::once(cpu)
+        .chain(gpus().into_iter().map(|x| ComputeDevice {
+            index: Some(x.index),
+            reserved: reserved_on(&reserved, x.index),
+            name: x.name,
+            total_memory: Some(x.total),
+            free_memory: Some(x.free),
+        }))
+        .collect()
+}
+
+/// Bytes a job with the model at `model` needs on a GPU, from its size
+pub fn estimate_memory(model: &Path) -> Result<u64, String> {
+    let size = std::fs::metadata(model).map_err(|e| format!("{}: {e}", model.display()))?.len();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss, clippy::cast_precision_loss)]
+    let overhead = (size as f64 * MODEL_OVERHEAD) as u64;
+    Ok(size + overhead + MODEL_WORKSPACE)
+}
+
+/// Memory held on a GPU for a job, given back when dropped; or the CPU
+pub struct Reservation {
+    id: Option<u64>,
+    /// the GPU to run on, or `None` for the CPU
+    pub device: Option<u32>,
+    /// of the GPU, as CUDA's `CUDA_VISIBLE_DEVICES` takes it
+    pub uuid: Option<String>,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            RESERVED.lock().unwrap().retain(|x| x.0 != id);
+        }
+    }
+}
+
+/// Finds a device for a job needing `need` bytes by `preference` and
+/// reserves them on it. Also says why, if it runs on the CPU when a GPU
+/// was wanted or could have been.
+pub fn reserve(preference: DevicePreference, need: u64) -> (Reservation, Option<String>) {
+    let cpu = |why| (Reservation { id: None, device: None, uuid: None }, why);
+    if matches!(preference, DevicePreference::Cpu) {
+        return cpu(None);
+    }
+    let gpus = gpus();
+    let mut reserved = RESERVED.lock().unwrap();
+    let room = |gpu: &Gpu| {
+        gpu.free.saturating_sub(reserved_on(&reserved, gpu.index) + DECODE_HEADROOM)
+    };
+    let chosen = match preference {
+        DevicePreference::Cpu => unreachable!(),
+        DevicePreference::Auto => gpus.iter().max_by_key(|x| room(x)),
+        DevicePreference::Gpu { index } => match gpus.iter().find(|x| x.index == index) {
+            Some(x) => Some(x),
+            None => return cpu(Some(format!("no GPU {index}; running on the CPU"))),
+        },
+    };
+    let Some(gpu) = chosen else { return cpu(None) };
+    let room = room(gpu);
+    if room < need {
+        return cpu(Some(format!("{} has {} MiB to spare and the job needs {} MiB; running on the CPU",
+            gpu.name, room / MIB, need / MIB)));
+    }
+    let mut next = NEXT_RESERVATION.lock().unwrap();
+    *next += 1;
+    reserved.push((*next, gpu.index, need));
+    let uuid = Some(gpu.uuid.clone());
+    (Reservation { id: Some(*next), device: Some(gpu.index), uuid }, None)
+}
@@ -0,0 +1,240 @@
+//! Jog/shuttle controllers, the USB wheels transcriptionists and timers
+//! work with: the spring-loaded shuttle ring sets how fast to play, forwards
+//! or backwards, the jog wheel inside it steps, and the buttons do what they
+//! are mapped to. They are read here over HID rather than through keyboard
+//! emulation, which needs a driver and loses the wheels' positions, on a
+//! thread of their own that blocks on the device, so that a turn reaches
+//! the frontend as soon as it is made.
+//!
+//! Contour's ShuttleXpress and ShuttlePRO are supported, which is most of
+//! what is around. Their reports are five bytes: the shuttle's position
+//! from -7 to 7, the jog wheel's count of notches, wrapping around, a byte
+//! we don't use, and the buttons as bits.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hidapi::{HidApi, HidDevice};
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+
+const CONTOUR: u16 = 0x0b33;
+/// Product ids and names of the controllers we know
+const SUPPORTED: [(u16, u16, &str); 3] = [
+    (CONTOUR, 0x0020, "ShuttleXpress"),
+    (CONTOUR, 0x0010, "ShuttlePRO"),
+    (CONTOUR, 0x0030, "ShuttlePRO v2"),
+];
+const REPORT_LENGTH: usize = 5;
+/// How long a read waits before the thread checks whether it should stop
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+#[ts(export)]
+pub enum ControllerAction {
+    #[serde(rename_all = "camelCase")]
+    Seek { by: Seconds },
+    #[serde(rename_all = "camelCase")]
+    SeekTo { time: Seconds },
+    /// by video frames
+    #[serde(rename_all = "camelCase")]
+    Step { frames: i32 },
+    /// adds a marker where playback is
+    Mark,
+    /// sets where the selected cue starts to where playback is
+    MarkIn,
+    /// sets where the selected cue ends to where playback is
+    MarkOut,
+    PlayPause,
+    Play,
+    Pause,
+    /// see `media_api::replay_last`
+    #[serde(rename_all = "camelCase")]
+    Replay { seconds: f64 },
+}
+
+impl ControllerAction {
+    /// The same action the other way, for turning a wheel back
+    fn reversed(&self) -> Self {
+        match self {
+            Self::Seek { by } => Self::Seek { by: Seconds(-by.0) },
+            Self::Step { frames } => Self::Step { frames: -frames },
+            x => x.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ButtonBinding {
+    /// counted from 0, as the device numbers its bits
+    pub button: u8,
+    pub action: ControllerAction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ControllerMapping {
+    /// buttons not bound do nothing
+    pub buttons: Vec<ButtonBinding>,
+    /// for each notch the jog wheel is turned clockwise; turned the other
+    /// way, the same backwards
+    pub jog: ControllerAction,
+    /// playback rates with the shuttle turned right from 1 to 7, the last
+    /// standing for any left out; turned left, the same in reverse
+    pub shuttle_rates: Vec<f64>,
+}
+
+/// What the controller asks for, by `ControllerMapping`
+#[derive(Clone, Debug)]
+pub enum ControllerInput {
+    Action(ControllerAction),
+    /// 0 with the shuttle let go
+    Shuttle(f64),
+    /// unplugged, or failing; the reader has stopped
+    Disconnected(String),
+    /// the monitoring gain, in dB, from a MIDI fader
+    Gain(f64),
+    /// a MIDI message as it is, in learn mode
+    Message(crate::midi::MidiMessage),
+}
+
+pub type Output = Box<dyn FnMut(ControllerInput) + Send>;
+
+struct Reader {
+    /// tells a reader that has been replaced from the current one
+    generation: u64,
+    name: String,
+    mapping: ControllerMapping,
+    output: Output,
+}
+
+/// The reader running, if any; its thread stops once this no longer has
+/// its generation
+static READER: Mutex<Option<Reader>> = Mutex::new(None);
+static NEXT_GENERATION: Mutex<u64> = Mutex::new(0);
+
+/// The first supported controller plugged in, opened, and its name
+fn open() -> Result<(HidDevice, String), String> {
+    let api = HidApi::new().map_err(|e| e.to_string())?;
+    for info in api.device_list() {
+        let Some(&(_, _, model)) = SUPPORTED.iter()
+            .find(|x| x.0 == info.vendor_id() && x.1 == info.product_id()) else { continue };
+        let name = info.product_string().unwrap_or(model).to_owned();
+        let device = info.open_device(&api).map_err(|e| format!("{name}: {e}"))?;
+        return Ok((device, name));
+    }
+    Err("no jog/shuttle controller found".to_owned())
+}
+
+/// The rate for shuttle `position` by `rates`
+fn shuttle_rate(rates: &[f64], position: i8) -> f64 {
+    let Some(&last) = rates.last() else { return 0.0 };
+    let rate = rates.get(usize::from(position.unsigned_abs()).saturating_sub(1))
+        .copied()
+        .unwrap_or(last);
+    match position {
+        0 => 0.0,
+        x if x < 0 => -rate,
+        _ => rate,
+    }
+}
+
+/// What is kept of the last report, to tell what changed
+#[derive(Default)]
+struct State {
+    shuttle: i8,
+    jog: Option<u8>,
+    buttons: u16,
+}
+
+/// What `report` asks for after `state`, which it updates
+fn interpret(report: &[u8; REPORT_LENGTH], state: &mut State, mapping: &ControllerMapping)
+    -> Vec<ControllerInput>
+{
+    let mut inputs = Vec::new();
+    let shuttle = i8::from_le_bytes([report[0]]);
+    if shuttle != state.shuttle {
+        state.shuttle = shuttle;
+        inputs.push(ControllerInput::Shuttle(shuttle_rate(&mapping.shuttle_rates, shuttle)));
+    }
+    let jog = report[1];
+    // the first report only says where the wheel is
+    if let Some(last) = state.jog.replace(jog) {
+        let turned = i8::from_le_bytes([jog.wrapping_sub(last)]);
+        let action = if turned < 0 { mapping.jog.reversed() } else { mapping.jog.clone() };
+        for _ in 0..turned.unsigned_abs() {
+            inputs.push(ControllerInput::Action(action.clone()));
+        }
+    }
+    let buttons = u16::from_le_bytes([report[3], report[4]]);
+    let pressed = buttons & !state.buttons;
+    state.buttons = buttons;
+    for binding in &mapping.buttons {
+        if binding.button < 16 && pressed & (1 << binding.button) != 0 {
+            inputs.push(ControllerInput::Action(binding.action.clone()));
+        }
+    }
+    inputs
+}
+
+fn run(device: &HidDevice, generation: u64) {
+    let mut state = State::default();
+    let mut report = [0; REPORT_LENGTH];
+    let timeout = i32::try_from(READ_TIMEOUT.as_millis()).unwrap();
+    loop {
+        let read = device.read_timeout(&mut report, timeout);
+        let mut reader = READER.lock().unwrap();
+        let Some(current) = reader.as_mut().filter(|x| x.generation == generation) else {
+            return;
+        };
+        match read {
+            Ok(REPORT_LENGTH) => {
+                for input in interpret(&report, &mut state, &current.mapping) {
+                    (current.output)(input);
+                }
+            }
+            Ok(_) => (),
+            Err(e) => {
+                log::warn!("controller: {}: {e}", current.name);
+                (current.output)(ControllerInput::Disconnected(format!("{}: {e}", current.name)));
+                *reader = None;
+                return;
+            }
+        }
+    }
+}
+
+/// Has the controller plugged in send what it asks for by `mapping` to
+/// `output` from now on, and gives its name. With one already being read
+/// only the mapping and where it goes change, with nothing reopened.
+pub fn configure(mapping: ControllerMapping, output: Output) -> Result<String, String> {
+    let mut reader = READER.lock().unwrap();
+    if let Some(current) = reader.as_mut() {
+        current.mapping = mapping;
+        current.output = output;
+        return Ok(current.name.clone());
+    }
+    let (device, name) = open()?;
+    let mut next = NEXT_GENERATION.lock().unwrap();
+    *next += 1;
+    let generation = *next;
+    *reader = Some(Reader { generation, name: name.clone(), mapping, output });
+    let spawned = std::thread::Builder::new()
+        .name("controller".to_owned())
+        .spawn(move || run(&device, generation));
+    if let Err(e) = spawned {
+        *reader = None;
+        return Err(e.to_string());
+    }
+    Ok(name)
+}
+
+/// Stops reading the controller, if one is being read
+pub fn release() -> bool {
+    READER.lock().unwrap().take().is_some()
+}
@@ -0,0 +1,131 @@
+#![allow(clippy::needless_pass_by_value)]
+
+//! Commands for `controller`, `midi` and `remote`: mapping a jog/shuttle
+//! controller or a MIDI input, or listening for remote control, and letting
+//! it go.
+
+use crate::controller::{self, ControllerAction, ControllerInput, ControllerMapping, Output};
+use crate::midi::{self, MidiMapping, MidiMessage};
+use crate::remote::{self, RemoteConfig};
+use crate::timing;
+
+use serde::Serialize;
+use tauri::ipc::Channel;
+
+#[derive(Clone, Serialize, Debug, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "event", content = "data")]
+#[ts(export)]
+pub enum ControllerEvent {
+    #[serde(rename_all = "camelCase")]
+    Connected { name: String },
+    /// To be done where playback is, as if from the keyboard
+    #[serde(rename_all = "camelCase")]
+    Action { action: ControllerAction },
+    /// The playback rate the shuttle asks for; 0 once it's let go
+    #[serde(rename_all = "camelCase")]
+    Shuttle { rate: f64 },
+    /// The controller stopped answering; it has to be configured again
+    #[serde(rename_all = "camelCase")]
+    Disconnected { reason: String },
+    /// The monitoring gain a MIDI fader asks for, in dB
+    #[serde(rename_all = "camelCase")]
+    Gain { db: f64 },
+    /// A message from a MIDI input in learn mode, to map
+    #[serde(rename_all = "camelCase")]
+    MidiMessage { message: MidiMessage },
+    #[serde(rename_all = "camelCase")]
+    MidiPorts { ports: Vec<String> },
+    /// Remote control is on, at `address`
+    #[serde(rename_all = "camelCase")]
+    Listening { address: String },
+    #[serde(rename_all = "camelCase")]
+    Done {},
+    #[serde(rename_all = "camelCase")]
+    RuntimeError { what: String },
+}
+
+fn send(channel: &Channel<ControllerEvent>, what: ControllerEvent) {
+    if let Err(e) = channel.send(what) {
+        log::warn!("ChannelClosed: channel {}: {e}", channel.id());
+    }
+}
+
+/// Sends what an input asks for on `channel`
+fn output(channel: &Channel<ControllerEvent>) -> Output {
+    let channel = channel.clone();
+    Box::new(move |input| send(&channel, match input {
+        ControllerInput::Action(action) => ControllerEvent::Action { action },
+        ControllerInput::Shuttle(rate) => ControllerEvent::Shuttle { rate },
+        ControllerInput::Disconnected(reason) => ControllerEvent::Disconnected { reason },
+        ControllerInput::Gain(db) => ControllerEvent::Gain { db },
+        ControllerInput::Message(message) => ControllerEvent::MidiMessage { message },
+    }))
+}
+
+/// Reads the jog/shuttle controller plugged in by `mapping`, sending what
+/// it asks for on `channel` until released or configured again; see
+/// `controller`. Configuring again only changes the mapping and the channel.
+#[tauri::command]
+pub fn configure_input_device(mapping: ControllerMapping, channel: Channel<ControllerEvent>) {
+    let _timing = timing::Command::start("configure_input_device", |_| {});
+    let result = controller::configure(mapping, output(&channel));
+    match result {
+        Ok(name) => send(&channel, ControllerEvent::Connected { name }),
+        Err(what) => send(&channel, ControllerEvent::RuntimeError { what }),
+    }
+}
+
+#[tauri::command]
+pub fn release_input_device(channel: Channel<ControllerEvent>) {
+    let _timing = timing::Command::start("release_input_device", |_| {});
+    controller::release();
+    send(&channel, ControllerEvent::Done {});
+}
+
+#[tauri::command]
+pub fn list_midi_ports(channel: Channel<ControllerEvent>) {
+    let _timing = timing::Command::start("list_midi_ports", |_| {});
+    match midi::ports() {
+        Ok(ports) => send(&channel, ControllerEvent::MidiPorts { ports }),
+        Err(what) => send(&channel, ControllerEvent::RuntimeError { what }),
+    }
+}
+
+/// Reads MIDI input `port` by `mapping`, sending what it asks for on
+/// `channel` until released or configured again; with `learn`, every note
+/// and control change is sent as it is instead. See `midi`.
+#[tauri::command]
+pub fn configure_midi_input(
+    port: String, mapping: MidiMapping, learn: bool, channel: Channel<ControllerEvent>,
+) {
+    let _timing = timing::Command::start("configure_midi_input", |_| {});
+    match midi::configure(&port, mapping, learn, output(&channel)) {
+        Ok(()) => send(&channel, ControllerEvent::Connected { name: port }),
+        Err(what) => send(&channel, ControllerEvent::RuntimeError { what }),
+    }
+}
+
+#[tauri::command]
+pub fn release_midi_input(channel: Channel<ControllerEvent>) {
+    let _timing = timing::Command::start("release_midi_input", |_| {});
+    midi::release();
+    send(&channel, ControllerEvent::Done {});
+}
+
+/// Turns on remote control by `config`, sending what it asks for on
+/// `channel` until stopped or started again; see `remote`
+#[tauri::command]
+pub fn start_remote_control(config: RemoteConfig, channel: Channel<ControllerEvent>) {
+    let _timing = timing::Command::start("start_remote_control", |_| {});
+    match remote::start(config, output(&channel)) {
+        Ok(address) => send(&channel, ControllerEvent::Listening { address: address.to_string() }),
+        Err(what) => send(&channel, ControllerEvent::RuntimeError { what }),
+    }
+}
+
+#[tauri::command]
+pub fn stop_remote_control(channel: Channel<ControllerEvent>) {
+    let _timing = timing::Command::start("stop_remote_control", |_| {});
+    remote::stop();
+    send(&channel, ControllerEvent::Done {});
+}
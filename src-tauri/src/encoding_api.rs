@@ -1,5 +1,8 @@
 use std::{fs, io::Read};
 use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::sandbox;
 
 #[derive(Clone, Serialize, Debug, ts_rs::TS)]
 #[serde(rename_all = "camelCase", tag = "type", content = "data")]
@@ -19,7 +22,11 @@ pub enum DetectResult {
 }
 
 #[tauri::command]
-pub fn decode_file_as(path: String, encoding: Option<String>) -> DecodeResult {
+pub fn decode_file_as(app: AppHandle, path: String, encoding: Option<String>) -> DecodeResult {
+    let path = match sandbox::check_read(&app, &path) {
+        Ok(x) => x,
+        Err(e) => return DecodeResult::Error(e)
+    };
     let mut file = match fs::OpenOptions::new().read(true).open(path) {
         Ok(x) => x,
         Err(e) => return DecodeResult::Error(e.to_string())
@@ -40,7 +47,11 @@ pub fn decode_file_as(path: String, encoding: Option<String>) -> DecodeResult {
 }
 
 #[tauri::command]
-pub fn decode_or_detect_file(path: String) -> DetectResult {
+pub fn decode_or_detect_file(app: AppHandle, path: String) -> DetectResult {
+    let path = match sandbox::check_read(&app, &path) {
+        Ok(x) => x,
+        Err(e) => return DetectResult::Error(e)
+    };
     let mut file = match fs::OpenOptions::new().read(true).open(path) {
         Ok(x) => x,
         Err(e) => return DetectResult::Error(e.to_string())
@@ -67,4 +78,4 @@ pub fn decode_or_detect_file(path: String) -> DetectResult {
     }
     let (cow, _, _) = encoding_rs::UTF_8.decode(buf.as_slice());
     DetectResult::Normal(cow.to_string())
-}
\ No newline at end of file
+}
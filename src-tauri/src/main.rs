@@ -4,12 +4,27 @@
 #![allow(clippy::used_underscore_binding)]
 
 extern crate ffmpeg_next as ffmpeg;
-mod encoding;
-mod media;
+mod compute;
+mod controller;
+mod controller_api;
+mod encoding_api;
 mod media_api;
+mod midi;
+mod model_api;
+mod models;
 mod redirect_log;
+mod remote;
+mod sandbox;
+mod snapshot_api;
+mod subtitle_api;
+mod timing;
+mod tools;
+mod transcribe;
+mod tts;
 
 use std::sync::{Arc, Mutex};
+use media::backend::BackendKind;
+use subtle_engine::{encoding, engine, media, subtitle, Engine};
 use tauri::AppHandle;
 use tauri::Manager;
 use tauri::State;
@@ -21,8 +36,15 @@ struct SetupState {
 }
 
 fn main() {
-    ffmpeg::init().unwrap();
+    // without ffmpeg there's still symphonia for audio, and
+    // `run_media_selfcheck` to find out what went wrong
+    let ffmpeg_error = Engine::new().err().map(|e| e.to_string());
+    let default_backend = match ffmpeg_error {
+        None => BackendKind::Ffmpeg,
+        Some(_) => BackendKind::Symphonia,
+    };
     redirect_log::init_ffmpeg_logging();
+    timing::init();
 
     let time_format = time::format_description::parse(
         "[year]-[month]-[day]@[hour]:[minute]:[second].[subsecond digits:3]",
@@ -71,36 +93,162 @@ fn main() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_fs::init())
+        // what the user picked stays allowed by `sandbox` in the next run
+        .plugin(tauri_plugin_persisted_scope::init())
         .plugin(tauri_plugin_os::init())
         .manage(Mutex::new(SetupState {
             frontend_task: false,
             backend_task: true,
         }))
-        .manage(Arc::new(Mutex::new(media_api::PlaybackRegistry::new())))
+        .manage(Arc::new(Mutex::new(media_api::PlaybackRegistry::new(default_backend))))
+        .manage(Arc::new(Mutex::new(subtitle_api::SubtitleRegistry::new())))
+        .setup(move |_| {
+            if let Some(e) = ffmpeg_error {
+                log::error!("ffmpeg failed to start, opening media with symphonia: {e}");
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             init_complete,
             media_api::media_version,
             media_api::media_status,
+            media_api::get_decode_damage,
+            media_api::set_timecode_offset,
+            media_api::set_prefetch_policy,
+            media_api::set_color_management,
             media_api::open_media,
+            media_api::open_media_tolerant,
+            media_api::open_media_group,
+            media_api::open_images,
+            media_api::check_availability,
+            media_api::rebuild_index,
             media_api::close_media,
+            media_api::set_event_filter,
             media_api::open_audio,
             media_api::open_video,
             media_api::open_audio_sampler,
+            media_api::open_audio_sampler_tap,
             media_api::open_video_sampler,
+            media_api::open_text_detector,
+            media_api::detect_onscreen_text,
+            media_api::open_crop_detector,
+            media_api::detect_letterbox,
+            media_api::suggest_chapters,
+            media_api::get_intensity_pair,
+            media_api::get_waveform,
+            media_api::measure_drift,
+            media_api::export_waveform,
+            media_api::mux_matroska,
+            media_api::estimate_export,
+            media_api::generate_test_media,
+            media_api::export_frame_sequence,
+            media_api::compare_with_render,
+            media_api::open_subpicture,
+            media_api::get_subpictures,
             media_api::seek_media,
             media_api::seek_media_byte,
             media_api::seek_audio,
             media_api::seek_video,
             media_api::skip_until,
+            media_api::replay_last,
+            media_api::send_frame_delta,
+            media_api::attach_surface,
+            media_api::update_surface,
+            media_api::present_surface,
+            media_api::set_surface_overlay,
+            media_api::detach_surface,
             media_api::sample_automatic3,
             media_api::get_frames_automatic,
             media_api::video_set_size,
+            media_api::get_safe_areas,
+            media_api::enable_auto_gain,
+            media_api::disable_auto_gain,
             media_api::get_keyframe_before,
             media_api::test_performance,
             media_api::media_config,
+            media_api::get_backend_capabilities,
+            media_api::run_media_selfcheck,
+            media_api::list_input_devices,
+            media_api::set_performance_budget,
+            media_api::format_timecode,
+            media_api::parse_timecode,
+            subtitle_api::open_subtitle,
+            subtitle_api::convert_from_srt,
+            subtitle_api::repair_subtitle,
+            subtitle_api::import_transcript,
+            subtitle_api::save_subtitle,
+            subtitle_api::convert_to_srt,
+            subtitle_api::close_subtitle,
+            subtitle_api::get_events_page,
+            subtitle_api::aggregate_events,
+            subtitle_api::query_events_at,
+            subtitle_api::query_events_in,
+            subtitle_api::global_search,
+            subtitle_api::edit_subtitle,
+            subtitle_api::enable_autosave,
+            subtitle_api::recover_subtitle,
+            subtitle_api::check_title_safe,
+            subtitle_api::check_onscreen_text,
+            subtitle_api::check_keyframe_distance,
+            subtitle_api::check_source_damage,
+            subtitle_api::find_duplicate_events,
+            subtitle_api::dedupe_events,
+            subtitle_api::auto_split_event,
+            subtitle_api::merge_events,
+            subtitle_api::set_positioning_policy,
+            subtitle_api::get_positioning,
+            subtitle_api::check_positioning,
+            subtitle_api::get_markers,
+            subtitle_api::add_marker,
+            subtitle_api::update_marker,
+            subtitle_api::remove_marker,
+            subtitle_api::next_marker,
+            subtitle_api::export_markers,
+            subtitle_api::import_markers,
+            subtitle_api::get_regions,
+            subtitle_api::add_region,
+            subtitle_api::update_region,
+            subtitle_api::remove_region,
+            subtitle_api::export_regions,
+            subtitle_api::import_regions,
+            subtitle_api::get_takes,
+            subtitle_api::record_take,
+            subtitle_api::select_take,
+            subtitle_api::update_take,
+            subtitle_api::remove_take,
+            subtitle_api::export_voice_over,
+            subtitle_api::bounce_voiceover,
+            subtitle_api::speak_cue,
+            subtitle_api::check_spoken_lengths,
+            subtitle_api::list_compute_devices,
+            subtitle_api::transcribe_media,
+            subtitle_api::set_asr_key,
+            subtitle_api::clear_asr_key,
+            subtitle_api::get_low_confidence_spans,
+            subtitle_api::realign_event,
+            subtitle_api::suggest_lead,
+            subtitle_api::suggest_retime,
+            subtitle_api::conform_to_new_media,
+            subtitle_api::preview_split,
+            subtitle_api::export_chapters,
+            snapshot_api::snapshot_session,
+            snapshot_api::restore_session,
+            model_api::list_models,
+            model_api::download_model,
+            model_api::remove_model,
+            model_api::list_tools,
+            model_api::choose_tool,
+            model_api::clear_tool,
+            controller_api::configure_input_device,
+            controller_api::release_input_device,
+            controller_api::list_midi_ports,
+            controller_api::configure_midi_input,
+            controller_api::release_midi_input,
+            controller_api::start_remote_control,
+            controller_api::stop_remote_control,
             redirect_log::set_log_filter_level,
-            encoding::decode_file_as,
-            encoding::decode_or_detect_file,
+            encoding_api::decode_file_as,
+            encoding_api::decode_or_detect_file,
             open_devtools,
         ])
         .run(ctx)
@@ -1,11 +0,0 @@
-pub mod internal;
-pub mod accel;
-pub mod frame;
-pub mod demux;
-pub mod units;
-pub mod audio;
-pub mod video;
-pub mod session;
-
-mod aggregation_tree;
-mod disjoint_interval_set;
@@ -5,29 +5,163 @@ extern crate ffmpeg_next as ffmpeg;
 use crate::media::audio::{AudioSink, AudioSinkKind};
 use crate::media::internal::MediaError;
 use crate::media::video::{VideoSink, VideoSinkKind};
-use crate::media::{accel, audio, demux, frame, session, units, video};
+use crate::media::timecode::{Framerate, Timecode};
+use crate::media::{accel, audio, backend::{self, MediaBackend}, capabilities, color, compare, damage, delta, demux, drift, frame, group::MediaGroup, images, monitor::{self, Monitor}, mux, prefetch::{self, PrefetchPolicy, Prefetcher}, record, seek_index::SeekIndex, selfcheck, session, still, surface, test_media, timecode, units, verify, video, waveform};
+use crate::sandbox;
+use crate::timing;
+use crate::subtitle::chapters::{self, Chapter};
+use crate::subtitle::safe_area::{BroadcastStandard, SafeAreas};
+use crate::subtitle_api::SubtitleRegistry;
 
 use num_traits::ToPrimitive;
-use serde::Serialize;
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
 use std::{collections::HashMap};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tauri::ipc::{self, Channel};
-use tauri::{async_runtime, State};
+use tauri::{async_runtime, AppHandle, Manager, State};
+
+/// Why there is no file of a session to read again on its own
+pub enum NoFile {
+    InvalidId,
+    /// the session is a group; see `group`
+    Group,
+}
 
 pub struct PlaybackRegistry {
     next_id: i32,
-    table: HashMap<i32, session::Session>,
+    table: HashMap<i32, Box<dyn MediaBackend>>,
+    /// set by `set_event_filter`
+    filters: HashMap<i32, HashSet<EventKind>>,
+    /// what each has played lately; see `replay_last`
+    monitors: HashMap<i32, Monitor>,
+    /// in milliseconds; see `set_timecode_offset`
+    timecode_offsets: HashMap<i32, i64>,
+    /// set by `set_prefetch_policy`
+    prefetchers: HashMap<i32, Prefetcher>,
+    /// what files are opened with unless the frontend says; symphonia if
+    /// ffmpeg failed to start
+    default_backend: backend::BackendKind,
+}
+
+/// Events that commands send besides their answer, which the frontend may
+/// not care for; the answer itself is always sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum EventKind {
+    /// off unless asked for
+    Debug,
+    Progress,
 }
 
 impl PlaybackRegistry {
-    pub fn new() -> PlaybackRegistry {
+    pub fn new(default_backend: backend::BackendKind) -> PlaybackRegistry {
         PlaybackRegistry {
             next_id: 0,
             table: HashMap::new(),
+            filters: HashMap::new(),
+            monitors: HashMap::new(),
+            timecode_offsets: HashMap::new(),
+            prefetchers: HashMap::new(),
+            default_backend,
+        }
+    }
+
+    /// Whether commands on `id` should send events of `kind`
+    fn wants(&self, id: i32, kind: EventKind) -> bool {
+        self.filters.get(&id)
+            .map_or(kind != EventKind::Debug, |x| x.contains(&kind))
+    }
+
+    /// Tells the prefetcher of `id`, if any, of a seek to `time`, or to a
+    /// byte position for `None`
+    fn sought(&mut self, id: i32, time: Option<units::Seconds>) {
+        let Some(prefetcher) = self.prefetchers.get_mut(&id) else { return };
+        prefetcher.set_resume_at(None);
+        if let Some(time) = time {
+            prefetcher.moved(time);
+        }
+    }
+
+    /// Starts the prefetcher of `id`, if any, over from the session as it
+    /// is now, after its players have changed
+    fn restart_prefetcher(&mut self, id: i32) {
+        let Some(policy) = self.prefetchers.remove(&id).map(|x| x.policy()) else { return };
+        let Some(backend) = self.table.get(&id) else { return };
+        match Prefetcher::start(&backend.snapshot(), policy) {
+            Ok(x) => { self.prefetchers.insert(id, x); }
+            Err(e) => log::warn!("prefetch: {id}: {e}"),
+        }
+    }
+
+    /// Every open session, by id
+    pub fn snapshot(&self) -> Vec<(i32, session::Snapshot)> {
+        let mut result: Vec<_> = self.table.iter()
+            .map(|(&id, backend)| (id, backend.snapshot()))
+            .collect();
+        result.sort_by_key(|(id, _)| *id);
+        result
+    }
+
+    /// Registers a backend opened outside of `open_media`
+    pub fn insert(&mut self, backend: Box<dyn MediaBackend>) -> i32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.table.insert(id, backend);
+        id
+    }
+
+    /// The backend of session `id`, for reading its file again outside of
+    /// the session; not a group's, as times in its files aren't the
+    /// session's
+    pub fn file_backend(&self, id: i32) -> Result<&dyn MediaBackend, NoFile> {
+        match self.table.get(&id) {
+            None => Err(NoFile::InvalidId),
+            Some(backend) if backend.is_group() => Err(NoFile::Group),
+            Some(backend) => Ok(backend.as_ref()),
         }
     }
+
+    /// The video keyframes known of session `id`, and its framerate; see
+    /// `Session::known_keyframes`. `Ok(None)` if it has no video that ffmpeg
+    /// decodes.
+    pub fn keyframes(&mut self, id: i32) -> Result<Option<(Vec<units::Seconds>, f64)>, NoFile> {
+        self.file_backend(id)?;
+        let session = self.table.get_mut(&id).and_then(|x| x.session_mut());
+        Ok(session.and_then(|x| {
+            let framerate = f64::from(x.video()?.0.framerate());
+            Some((x.known_keyframes(), framerate))
+        }))
+    }
+
+    /// The file of session `id` and its open audio stream; see
+    /// `file_backend`
+    pub fn audio_source(&self, id: i32) -> Result<(std::path::PathBuf, Option<usize>), NoFile> {
+        self.file_backend(id)
+            .map(|backend| (backend.path().to_owned(), backend.status().audio_index))
+    }
+
+    pub fn contains(&self, id: i32) -> bool {
+        self.table.contains_key(&id)
+    }
+
+    pub fn snapshot_of(&self, id: i32) -> Option<session::Snapshot> {
+        self.table.get(&id).map(|backend| backend.snapshot())
+    }
+
+    /// Puts `backend` in the place of session `id`, keeping its id and event
+    /// filter; what was kept for the old file goes. Returns `false` if
+    /// there is no such session.
+    pub fn replace(&mut self, id: i32, backend: Box<dyn MediaBackend>) -> bool {
+        let Some(old) = self.table.get_mut(&id) else { return false };
+        *old = backend;
+        self.monitors.remove(&id);
+        self.timecode_offsets.remove(&id);
+        self.prefetchers.remove(&id);
+        true
+    }
 }
 
 #[derive(Clone, Serialize, Debug, ts_rs::TS)]
@@ -40,8 +174,15 @@ pub enum MediaEvent<'a> {
     MediaStatus {
         audio_index: i32,
         video_index: i32,
+        /// no video besides cover art, as with podcasts and music
+        audio_only: bool,
         duration: units::Seconds,
         streams: Vec<demux::StreamDescription>,
+        /// of the first frame, as the file gives it
+        start_timecode: Option<String>,
+        /// set by `set_timecode_offset`; 0 for times from the start of
+        /// the file
+        timecode_offset_ms: i64,
     },
     #[serde(rename_all = "camelCase")]
     AudioStatus { 
@@ -60,13 +201,80 @@ pub enum MediaEvent<'a> {
         size: (u32, u32),
     },
     #[serde(rename_all = "camelCase")]
+    SubpictureStatus {
+        index: usize,
+        codec: String,
+        canvas_size: (u32, u32),
+    },
+    #[serde(rename_all = "camelCase")]
+    SafeAreas { areas: SafeAreas },
+    #[serde(rename_all = "camelCase")]
+    OnscreenText { intervals: Vec<(units::Seconds, units::Seconds)> },
+    /// Heights of the black bars above and below the picture, in pixels of
+    /// the original frame; absent if every frame analysed was black
+    #[serde(rename_all = "camelCase")]
+    Letterbox { bars: Option<(u32, u32)>, height: u32 },
+    #[serde(rename_all = "camelCase")]
+    Progress { fraction: f64 },
+    #[serde(rename_all = "camelCase")]
+    FramesExported { paths: Vec<String> },
+    #[serde(rename_all = "camelCase")]
+    FramesCompared { diffs: Vec<compare::FrameDiff> },
+    #[serde(rename_all = "camelCase")]
+    Chapters { chapters: Vec<Chapter> },
+    #[serde(rename_all = "camelCase")]
+    IntensityPair { pair: audio::IntensityPair },
+    #[serde(rename_all = "camelCase")]
+    Verified { report: verify::Report },
+    #[serde(rename_all = "camelCase")]
+    ExportEstimated { estimate: mux::ExportEstimate },
+    /// Only streams with some damage are listed
+    #[serde(rename_all = "camelCase")]
+    DecodeDamage { streams: Vec<damage::StreamDamage> },
+    #[serde(rename_all = "camelCase")]
+    Drift { report: drift::DriftReport },
+    /// `display` is the name of the display's profile; absent when off, or
+    /// when none was found and sRGB is taken
+    #[serde(rename_all = "camelCase")]
+    ColorManagement { on: bool, display: Option<String> },
+    #[serde(rename_all = "camelCase")]
+    Capabilities { capabilities: capabilities::Capabilities },
+    #[serde(rename_all = "camelCase")]
+    SelfCheck { report: selfcheck::Report },
+    /// The command took longer than its budget; see `timing`
+    #[serde(rename_all = "camelCase")]
+    PerformanceWarning { warning: timing::PerformanceWarning },
+    /// The linked ffmpeg lacks what the job needs; see `capabilities`
+    #[serde(rename_all = "camelCase")]
+    Unsupported { feature: &'a str },
+    /// A path given was refused by `sandbox`
+    #[serde(rename_all = "camelCase")]
+    PathRejected { reason: String },
+    /// The file is still being written and doesn't reach that far yet
+    #[serde(rename_all = "camelCase")]
+    DataNotYetAvailable { playable_until: units::Seconds },
+    #[serde(rename_all = "camelCase")]
+    Availability { playable_until: units::Seconds, complete: bool },
+    #[serde(rename_all = "camelCase")]
+    SeekIndexBuilt { keyframes: usize },
+    #[serde(rename_all = "camelCase")]
+    Waveform { waveform: audio::ClassifiedWaveform },
+    #[serde(rename_all = "camelCase")]
+    InputDevices { devices: Vec<record::InputDevice> },
+    #[serde(rename_all = "camelCase")]
     Debug { message: &'a str },
     #[serde(rename_all = "camelCase")]
     RuntimeError { what: &'a str },
     #[serde(rename_all = "camelCase")]
     Opened { id: i32 },
+    /// For `open_media_group`: where each of its files starts
+    #[serde(rename_all = "camelCase")]
+    GroupOpened { id: i32, offsets: Vec<units::Seconds> },
     #[serde(rename_all = "camelCase")]
     NoStream {},
+    /// A video stream was asked for in a file that has none
+    #[serde(rename_all = "camelCase")]
+    NoVideo {},
     #[serde(rename_all = "camelCase")]
     InvalidId {},
     #[serde(rename_all = "camelCase")]
@@ -86,32 +294,158 @@ pub enum MediaEvent<'a> {
     },
 }
 
+/// Sends `what`, and tells whether the frontend is still listening. A
+/// closed channel, as after a reload, only gets logged: whatever is left of
+/// the command has nobody to report to, and jobs use this to stop early.
+fn try_send(channel: &Channel<MediaEvent>, what: MediaEvent) -> bool {
+    match channel.send(what) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("ChannelClosed: channel {}: {e}", channel.id());
+            false
+        }
+    }
+}
+
 fn send(channel: &Channel<MediaEvent>, what: MediaEvent) {
-    channel.send(what).expect("Error sending event");
+    try_send(channel, what);
+}
+
+/// For the `progress` of long jobs: gives up once nobody is listening
+fn send_progress(channel: &Channel<MediaEvent>, fraction: f64) -> bool {
+    try_send(channel, MediaEvent::Progress { fraction })
+}
+
+/// `send_progress` for a job on a playback, unless filtered out; see
+/// `set_event_filter`
+fn progress_of<'c, 'e>(
+    ap: &PlaybackRegistry, id: i32, channel: &'c Channel<MediaEvent<'e>>
+) -> impl FnMut(f64) -> bool + use<'c, 'e> {
+    let wanted = ap.wants(id, EventKind::Progress);
+    move |fraction| !wanted || send_progress(channel, fraction)
 }
 
 macro_rules! send_error {
     ($channel:expr, $what:expr) => {
-        $channel
-            .send(MediaEvent::RuntimeError {
-                what: format!("{} (at line {})", AsRef::<str>::as_ref(&$what), line!()).as_str(),
-            })
-            .expect("Error sending event")
+        send($channel, MediaEvent::RuntimeError {
+            what: format!("{} (at line {})", AsRef::<str>::as_ref(&$what), line!()).as_str(),
+        })
+    };
+}
+
+/// Like `send_error!`, except that missing data in a file still being
+/// written is reported as such, since the caller can simply retry later,
+/// and so is asking for video in a file without any. A job cancelled for
+/// lack of a listener has nobody to tell.
+macro_rules! send_media_error {
+    ($channel:expr, $e:expr) => {
+        match $e {
+            MediaError::DataNotYetAvailable { playable_until } =>
+                send($channel, MediaEvent::DataNotYetAvailable { playable_until }),
+            MediaError::NoVideo => send($channel, MediaEvent::NoVideo {}),
+            MediaError::Cancelled => log::debug!("cancelled at line {}", line!()),
+            e => send_error!($channel, e.to_string()),
+        }
+    };
+}
+
+/// Times the rest of the command against its budget, warning the frontend
+/// through `channel` if there is one; see `timing`
+macro_rules! timed {
+    ($name:literal, $channel:expr) => {
+        timing::Command::start($name, |warning|
+            send($channel, MediaEvent::PerformanceWarning { warning }))
+    };
+    ($name:literal) => {
+        timing::Command::start($name, |_| {})
     };
 }
 
+/// Logs the message, and sends it too if the frontend has asked for debug
+/// events of playback `id`
+macro_rules! send_debug {
+    ($ap:expr, $id:expr, $channel:expr, $($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        log::debug!("{message}");
+        if $ap.wants($id, EventKind::Debug) {
+            send($channel, MediaEvent::Debug { message: &message });
+        }
+    }};
+}
+
 fn send_invalid_id(channel: &Channel<MediaEvent>) {
-    channel
-        .send(MediaEvent::InvalidId {})
-        .expect("Error sending event");
+    send(channel, MediaEvent::InvalidId {});
+}
+
+/// The ffmpeg session behind `id`, for what only that backend does; tells
+/// the frontend why not otherwise
+fn ffmpeg_session<'a>(
+    ap: &'a mut PlaybackRegistry, id: i32, channel: &Channel<MediaEvent>
+) -> Option<&'a mut session::Session> {
+    let Some(backend) = ap.table.get_mut(&id) else {
+        send_invalid_id(channel);
+        return None;
+    };
+    let session = backend.session_mut();
+    if session.is_none() {
+        send(channel, MediaEvent::Unsupported { feature: "ffmpegSession" });
+    }
+    session
+}
+
+/// `PlaybackRegistry::file_backend`; tells the frontend why not otherwise
+fn file_backend<'a>(
+    ap: &'a PlaybackRegistry, id: i32, channel: &Channel<MediaEvent>
+) -> Option<&'a dyn MediaBackend> {
+    match ap.file_backend(id) {
+        Ok(x) => Some(x),
+        Err(NoFile::InvalidId) => {
+            send_invalid_id(channel);
+            None
+        }
+        Err(NoFile::Group) => {
+            send(channel, MediaEvent::Unsupported { feature: "mediaGroup" });
+            None
+        }
+    }
+}
+
+/// A session of its own on the file of session `id`, with no sinks yet,
+/// for reading much of it without moving playback or holding the registry;
+/// along with the snapshot of `id`, to open the same streams
+fn own_session(
+    state: &Mutex<PlaybackRegistry>, id: i32, channel: &Channel<MediaEvent>,
+) -> Option<(session::Session, session::Snapshot)> {
+    let Some(snapshot) = state.lock().unwrap().snapshot_of(id) else {
+        send_invalid_id(channel);
+        return None;
+    };
+    if !snapshot.members.is_empty() {
+        send(channel, MediaEvent::Unsupported { feature: "mediaGroup" });
+        return None;
+    }
+    let path = std::path::Path::new(&snapshot.path);
+    let session = if snapshot.tolerant {
+        session::Session::create_tolerant(path)
+    } else {
+        session::Session::create(path)
+    };
+    match session {
+        Ok(x) => Some((x, snapshot)),
+        Err(e) => {
+            send_media_error!(channel, e);
+            None
+        }
+    }
 }
 
 fn send_done(channel: &Channel<MediaEvent>) {
-    channel.send(MediaEvent::Done {}).expect("Error sending event");
+    send(channel, MediaEvent::Done {});
 }
 
 #[tauri::command]
 pub fn media_version(channel: Channel<MediaEvent>) {
+    let _timing = timed!("media_version", &channel);
     let c_buf = unsafe { ffmpeg::sys::av_version_info() };
     let c_str = unsafe { std::ffi::CStr::from_ptr(c_buf) };
     send(
@@ -122,336 +456,1880 @@ pub fn media_version(channel: Channel<MediaEvent>) {
     );
 }
 
+/// What the linked ffmpeg supports, so that the frontend can disable what
+/// it doesn't
+#[tauri::command]
+pub fn get_backend_capabilities(channel: Channel<MediaEvent>) {
+    let _timing = timed!("get_backend_capabilities", &channel);
+    send(&channel, MediaEvent::Capabilities {
+        capabilities: capabilities::get().clone(),
+    });
+}
+
+/// Checks that ffmpeg works end to end, for when opening files fails with
+/// nothing to show for it; see `selfcheck`
+#[tauri::command]
+pub async fn run_media_selfcheck(channel: Channel<MediaEvent<'static>>) -> Result<(), ()> {
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("run_media_selfcheck", &channel);
+        send(&channel, MediaEvent::SelfCheck { report: selfcheck::run() });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Microphones and other inputs to record takes from; asking the sound
+/// server can take a moment
+#[tauri::command]
+pub async fn list_input_devices(channel: Channel<MediaEvent<'static>>) -> Result<(), ()> {
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("list_input_devices", &channel);
+        send(&channel, MediaEvent::InputDevices { devices: record::input_devices() });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// How long `command` may take before a `PerformanceWarning`, in
+/// milliseconds; `None` to never warn about it
+#[tauri::command]
+pub fn set_performance_budget(command: String, budget_ms: Option<u64>) {
+    timing::set_budget(&command, budget_ms.map(Duration::from_millis));
+}
+
 #[tauri::command]
 pub fn media_config() -> String {
+    let _timing = timed!("media_config");
     ffmpeg_next::util::configuration().to_owned()
 }
 
+/// `time_ms` as the timecode of the frame on screen then
+#[tauri::command]
+pub fn format_timecode(time_ms: i64, rate: Framerate, drop_frame: bool) -> Result<String, String> {
+    let _timing = timed!("format_timecode");
+    let rate = Framerate::new(rate.numerator, rate.denominator)?;
+    let frame = u64::try_from(timecode::frame_at(time_ms, rate))
+        .map_err(|_| format!("negative time: {time_ms}"))?;
+    Ok(Timecode::from_frame(frame, rate, drop_frame)?.to_string())
+}
+
+/// The first millisecond at which the frame labelled `text` is on screen
+#[tauri::command]
+pub fn parse_timecode(text: String, rate: Framerate) -> Result<i64, String> {
+    let _timing = timed!("parse_timecode");
+    let rate = Framerate::new(rate.numerator, rate.denominator)?;
+    let frame = text.parse::<Timecode>()?.to_frame(rate)?;
+    let frame = i64::try_from(frame).map_err(|_| format!("{text}: out of range"))?;
+    Ok(timecode::time_of_frame(frame, rate))
+}
+
 #[tauri::command]
 pub fn media_status(id: i32, state: State<Arc<Mutex<PlaybackRegistry>>>, channel: Channel<MediaEvent>) {
-    let mut ap = state.lock().unwrap();
-    let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-    let audio_index = match session.audio() {
-        Some((d, _)) => d.stream_info().index().to_i32().unwrap(),
-        None => -1,
-    };
-    let video_index = match session.video() {
-        Some((d, _)) => d.stream_info().index().to_i32().unwrap(),
-        None => -1,
-    };
+    let _timing = timed!("media_status", &channel);
+    let ap = state.lock().unwrap();
+    let Some(backend) = 
+        ap.table.get(&id) else { return send_invalid_id(&channel) };
+    let status = backend.status();
+    let index = |x: Option<usize>| x.map_or(-1, |x| x.to_i32().unwrap());
     send(
         &channel,
         MediaEvent::MediaStatus {
-            audio_index,
-            video_index,
-            duration: session.demuxer().duration(),
-            streams: session.demuxer().describe_streams(),
+            audio_index: index(status.audio_index),
+            video_index: index(status.video_index),
+            audio_only: status.audio_only,
+            duration: status.duration,
+            streams: status.streams,
+            start_timecode: status.start_timecode,
+            timecode_offset_ms: ap.timecode_offsets.get(&id).copied().unwrap_or(0),
         },
     );
 }
 
+/// Sends the damage found in the source of playback `id` by what has been
+/// decoded of it so far; see `media::damage`
 #[tauri::command]
-pub fn video_set_size(
-    id: i32, width: u32, height: u32,
+pub fn get_decode_damage(id: i32, state: State<Arc<Mutex<PlaybackRegistry>>>, channel: Channel<MediaEvent>) {
+    let _timing = timed!("get_decode_damage", &channel);
+    let ap = state.lock().unwrap();
+    let Some(backend) =
+        ap.table.get(&id) else { return send_invalid_id(&channel) };
+    send(&channel, MediaEvent::DecodeDamage { streams: backend.damage() });
+}
+
+/// Counts the times of playback `id` from `timecode` at `rate`, usually
+/// the file's own start timecode, as when the program starts at 01:00:00:00;
+/// without one, from the start of the file again. Only recorded here and
+/// reported by `media_status`: the frontend shows times with it, and adds
+/// it when exporting subtitles in program time.
+#[tauri::command]
+pub fn set_timecode_offset(
+    id: i32, timecode: Option<String>, rate: Framerate,
     state: State<Arc<Mutex<PlaybackRegistry>>>,
     channel: Channel<MediaEvent>,
 ) {
+    let _timing = timed!("set_timecode_offset", &channel);
     let mut ap = state.lock().unwrap();
-    let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-    let Some((_, VideoSinkKind::Player(c))) = 
-        session.video_mut() else { return send(&channel, MediaEvent::NoStream {}) };
-
-    match c.set_output_size((width, height)) {
-        Ok(()) => send_done(&channel),
-        Err(e) => send_error!(&channel, e.to_string()),
+    if !ap.table.contains_key(&id) {
+        return send_invalid_id(&channel);
+    }
+    let Some(text) = timecode else {
+        ap.timecode_offsets.remove(&id);
+        return send_done(&channel);
+    };
+    let offset = Framerate::new(rate.numerator, rate.denominator)
+        .and_then(|rate| {
+            let frame = text.parse::<Timecode>()?.to_frame(rate)?;
+            let frame = i64::try_from(frame).map_err(|_| format!("{text}: out of range"))?;
+            Ok(timecode::time_of_frame(frame, rate))
+        });
+    match offset {
+        Ok(x) => {
+            ap.timecode_offsets.insert(id, x);
+            send_done(&channel);
+        }
+        Err(e) => send_error!(&channel, e),
     }
 }
 
+/// Keeps the first `policy.window` of the cues around where playback `id`
+/// is decoded ahead, for `skip_until` to give at once when jumped to; with
+/// neither `ahead` nor `behind`, stops. Given again when the cues change.
 #[tauri::command]
-pub fn close_media(id: i32, state: State<Arc<Mutex<PlaybackRegistry>>>, channel: Channel<MediaEvent>) {
+pub fn set_prefetch_policy(
+    id: i32, policy: PrefetchPolicy,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("set_prefetch_policy", &channel);
     let mut ap = state.lock().unwrap();
-    if ap.table.remove(&id).is_none() {
+    if !ap.table.contains_key(&id) {
         return send_invalid_id(&channel);
     }
-    send_done(&channel);
-}
-
-#[tauri::command]
-pub fn open_media(state: State<Arc<Mutex<PlaybackRegistry>>>, path: &str, channel: Channel<MediaEvent>) {
-    let mut ap = state.lock().unwrap();
-    send(&channel, MediaEvent::Debug { message: path });
-
-    let session = match session::Session::create(std::path::Path::new(path)) {
-        Ok(x) => x,
-        Err(e) => return send_error!(&channel, e.to_string()),
-    };
-
-    let id = ap.next_id;
-    ap.next_id += 1;
-    ap.table.insert(id, session);
-    send(&channel, MediaEvent::Opened { id });
+    if policy.ahead + policy.behind == 0 {
+        ap.prefetchers.remove(&id);
+        return send_done(&channel);
+    }
+    if !(policy.window.0 > 0.0 && policy.window.0 <= prefetch::MAX_WINDOW.0) {
+        return send_error!(&channel,
+            format!("the window must be more than 0 and at most {}", prefetch::MAX_WINDOW));
+    }
+    if policy.ahead + policy.behind > prefetch::MAX_CUES {
+        return send_error!(&channel,
+            format!("at most {} cues can be prefetched", prefetch::MAX_CUES));
+    }
+    if let Some(x) = policy.cues.iter().find(|x| !x.0.is_finite()) {
+        return send_error!(&channel, format!("invalid cue time: {}", x.0));
+    }
+    if let Some(x) = ap.prefetchers.get_mut(&id) {
+        x.set_policy(policy);
+        return send_done(&channel);
+    }
+    let snapshot = ap.table[&id].snapshot();
+    match Prefetcher::start(&snapshot, policy) {
+        Ok(x) => {
+            ap.prefetchers.insert(id, x);
+            send_done(&channel);
+        }
+        Err(e) => send_media_error!(&channel, e),
+    }
 }
 
 #[tauri::command]
-#[allow(clippy::cast_sign_loss)]
-pub fn open_video(
-    id: i32, video_id: i32, accel: bool,
+pub fn video_set_size(
+    id: i32, width: u32, height: u32,
     state: State<Arc<Mutex<PlaybackRegistry>>>,
     channel: Channel<MediaEvent>,
 ) {
+    let _timing = timed!("video_set_size", &channel);
     let mut ap = state.lock().unwrap();
-    let Some(session) = 
+    let Some(backend) = 
         ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
 
-    let index = (video_id > 0).then_some(video_id as usize);
-    let (d, _) = match session.open_video_player(index, accel) {
-        Ok(()) => session.video().unwrap(),
-        Err(e) => return send_error!(&channel, e.to_string()),
-    };
-
-    log::debug!("open_video: {id} {video_id}");
-
-    send(&channel, MediaEvent::VideoStatus {
-        index: d.stream_info().index(),
-        framerate: d.framerate().into(),
-        is_vfr: d.is_vfr(),
-        start_time: d.stream_info().start_time_seconds(),
-        sample_aspect_ratio: d.sample_aspect_ratio().into(),
-        size: d.original_size()
-    });
-    send_done(&channel);
+    match backend.set_video_size((width, height)) {
+        Ok(true) => {
+            ap.restart_prefetcher(id);
+            send_done(&channel);
+        }
+        Ok(false) => send(&channel, MediaEvent::NoStream {}),
+        Err(e) => send_error!(&channel, e.to_string()),
+    }
 }
 
+/// Safe areas of the open video stream, in pixels of its original size
 #[tauri::command]
-#[allow(clippy::cast_sign_loss)]
-pub fn open_video_sampler(
-    id: i32, video_id: i32, _accel: bool,
+pub fn get_safe_areas(
+    id: i32, standard: BroadcastStandard,
     state: State<Arc<Mutex<PlaybackRegistry>>>,
     channel: Channel<MediaEvent>,
 ) {
+    let _timing = timed!("get_safe_areas", &channel);
     let mut ap = state.lock().unwrap();
     let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-
-    let index = (video_id > 0).then_some(video_id as usize);
-    let (d, _) = match session.open_video_sampler(index) {
-        Ok(()) => session.video().unwrap(),
-        Err(e) => return send_error!(&channel, e.to_string()),
-    };
-
-    log::debug!("open_video_sampler: {id} {video_id}");
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    let Some((d, _)) = 
+        session.video() else { return send(&channel, MediaEvent::NoStream {}) };
 
-    send(&channel, MediaEvent::VideoStatus {
-        index: d.stream_info().index(),
-        framerate: d.framerate().into(),
-        is_vfr: d.is_vfr(),
-        start_time: d.stream_info().start_time_seconds(),
-        sample_aspect_ratio: d.sample_aspect_ratio().into(),
-        size: d.original_size()
+    let (width, height) = d.original_size();
+    send(&channel, MediaEvent::SafeAreas {
+        areas: standard.areas(f64::from(width), f64::from(height)),
     });
-    send_done(&channel);
 }
 
+/// Applies makeup gain to the audio player so that playback approaches
+/// `target_lufs`, as measured over what has been played so far
 #[tauri::command]
-#[allow(clippy::cast_sign_loss)]
-pub fn open_audio(
-    id: i32, audio_id: i32,
+pub fn enable_auto_gain(
+    id: i32, target_lufs: f64,
     state: State<Arc<Mutex<PlaybackRegistry>>>,
     channel: Channel<MediaEvent>,
 ) {
+    let _timing = timed!("enable_auto_gain", &channel);
     let mut ap = state.lock().unwrap();
     let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-
-    let index = (audio_id > 0).then_some(audio_id as usize);
-    let (d, _) = match session.open_audio_player(index) {
-        Ok(()) => session.audio().unwrap(),
-        Err(e) => return send_error!(&channel, e.to_string()),
-    };
-
-    log::debug!("open_audio: {id} {audio_id}");
-
-    send(&channel, MediaEvent::AudioStatus {
-        index: d.stream_info().index(),
-        start_time: d.stream_info().start_time_seconds(),
-        length: d.estimated_length(),
-        sample_rate: d.sample_rate(),
-    });
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    let Some((_, AudioSinkKind::Player(c))) = 
+        session.audio_mut() else { return send(&channel, MediaEvent::NoStream {}) };
+    c.set_auto_gain(Some(target_lufs));
+    send_done(&channel);
 }
 
 #[tauri::command]
-#[allow(clippy::cast_sign_loss)]
-pub fn open_audio_sampler(
-    id: i32, audio_id: i32,
-    sample_per_second: usize,
+pub fn disable_auto_gain(
+    id: i32,
     state: State<Arc<Mutex<PlaybackRegistry>>>,
     channel: Channel<MediaEvent>,
 ) {
+    let _timing = timed!("disable_auto_gain", &channel);
     let mut ap = state.lock().unwrap();
     let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-
-    let index = (audio_id > 0).then_some(audio_id as usize);
-    let (d, _) = match session.open_audio_sampler(index, sample_per_second) {
-        Ok(()) => session.audio().unwrap(),
-        Err(e) => return send_error!(&channel, e.to_string()),
-    };
-
-    log::debug!("open_audio_sampler: {id} [{audio_id}] {sample_per_second}");
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    let Some((_, AudioSinkKind::Player(c))) = 
+        session.audio_mut() else { return send(&channel, MediaEvent::NoStream {}) };
+    c.set_auto_gain(None);
+    send_done(&channel);
+}
 
-    send(&channel, MediaEvent::AudioStatus {
-        index: d.stream_info().index(),
-        start_time: d.stream_info().start_time_seconds(),
-        length: d.estimated_length(),
-        sample_rate: d.sample_rate(),
-    });
+/// The middle of the display `window` is mostly on, for `color`
+fn display_of(window: &tauri::Window) -> Option<color::DisplayAt> {
+    let monitor = window.current_monitor().ok()??;
+    let (position, size) = (monitor.position(), monitor.size());
+    Some(color::DisplayAt {
+        x: position.x.saturating_add(i32::try_from(size.width / 2).ok()?),
+        y: position.y.saturating_add(i32::try_from(size.height / 2).ok()?),
+        scale: monitor.scale_factor(),
+    })
 }
 
+/// Converts the frames of the video player of `id` for the display the
+/// calling window is on, by the profile the system has for it; see
+/// `color`. Asked again after the window moves to another display.
 #[tauri::command]
-pub fn seek_media(
-    id: i32,
-    time: units::Seconds,
+pub fn set_color_management(
+    window: tauri::Window,
+    id: i32, on: bool,
     state: State<Arc<Mutex<PlaybackRegistry>>>,
     channel: Channel<MediaEvent>,
 ) {
+    let _timing = timed!("set_color_management", &channel);
+    // may run a program; not under the lock
+    let display = on.then(|| color::display_profile(display_of(&window))
+        .unwrap_or_else(color::DisplayProfile::srgb));
+    let name = display.as_ref().and_then(|x| x.description.clone());
     let mut ap = state.lock().unwrap();
     let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-    if let Err(e) = session.seek(time) {
-        return send_error!(&channel, e.to_string());
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    let Some((_, VideoSinkKind::Player(p))) = 
+        session.video_mut() else { return send(&channel, MediaEvent::NoStream {}) };
+    if let Err(e) = p.set_color_management(display) {
+        return send_media_error!(&channel, e);
     }
-    send_done(&channel);
+    ap.restart_prefetcher(id);
+    send(&channel, MediaEvent::ColorManagement { on, display: name });
 }
 
 #[tauri::command]
-pub fn seek_media_byte(
+pub fn close_media(id: i32, state: State<Arc<Mutex<PlaybackRegistry>>>, channel: Channel<MediaEvent>) {
+    let _timing = timed!("close_media", &channel);
+    let mut ap = state.lock().unwrap();
+    if ap.table.remove(&id).is_none() {
+        return send_invalid_id(&channel);
+    }
+    ap.filters.remove(&id);
+    ap.monitors.remove(&id);
+    ap.timecode_offsets.remove(&id);
+    ap.prefetchers.remove(&id);
+    send_done(&channel);
+}
+
+/// Which of the optional events the commands on playback `id` send, such as
+/// progress during long jobs; without a filter, all but `Debug`
+#[tauri::command]
+pub fn set_event_filter(
+    id: i32, kinds: Vec<EventKind>,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("set_event_filter", &channel);
+    let mut ap = state.lock().unwrap();
+    if !ap.table.contains_key(&id) {
+        return send_invalid_id(&channel);
+    }
+    ap.filters.insert(id, kinds.into_iter().collect());
+    send_done(&channel);
+}
+
+#[tauri::command]
+pub fn open_media(
+    app: AppHandle, state: State<Arc<Mutex<PlaybackRegistry>>>, path: &str,
+    backend: Option<backend::BackendKind>, channel: Channel<MediaEvent>
+) {
+    let _timing = timed!("open_media", &channel);
+    let mut ap = state.lock().unwrap();
+    log::debug!("open_media: {path}");
+
+    let path = match sandbox::check_media(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, MediaEvent::PathRejected { reason }),
+    };
+    let mut opened = match backend::open(backend.unwrap_or(ap.default_backend), &path) {
+        Ok(x) => x,
+        Err(e) => return send_error!(&channel, e.to_string()),
+    };
+    if let Some(session) = opened.session_mut()
+        && let Some(dir) = seek_index_dir(&app)
+        && let Some(index) = SeekIndex::load(&dir, &path)
+    {
+        log::debug!("open_media: using the rebuilt index of {} keyframes", index.keyframes());
+        session.set_seek_index(Some(index));
+    }
+
+    let id = ap.insert(opened);
+    send(&channel, MediaEvent::Opened { id });
+}
+
+/// Opens a screenshot, or a numbered sequence of them, as video to
+/// typeset against; see `Session::create_images`
+#[tauri::command]
+pub fn open_images(
+    app: AppHandle, state: State<Arc<Mutex<PlaybackRegistry>>>,
+    path: &str, options: images::ImageOptions, channel: Channel<MediaEvent>
+) {
+    let _timing = timed!("open_images", &channel);
+    let mut ap = state.lock().unwrap();
+    let path = match sandbox::check_read(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, MediaEvent::PathRejected { reason }),
+    };
+    let session = match session::Session::create_images(&path, options) {
+        Ok(x) => x,
+        Err(e) => return send_error!(&channel, e.to_string()),
+    };
+    let id = ap.insert(Box::new(session));
+    send(&channel, MediaEvent::Opened { id });
+}
+
+/// Opens a file that may still be downloading; see `Session::create_tolerant`
+#[tauri::command]
+pub fn open_media_tolerant(
+    app: AppHandle, state: State<Arc<Mutex<PlaybackRegistry>>>, path: &str, channel: Channel<MediaEvent>
+) {
+    let _timing = timed!("open_media_tolerant", &channel);
+    let path = match sandbox::check_media(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, MediaEvent::PathRejected { reason }),
+    };
+    // reads the whole file, so the registry isn't locked before
+    let mut session = match session::Session::create_tolerant(&path) {
+        Ok(x) => x,
+        Err(e) => return send_media_error!(&channel, e),
+    };
+    let availability = session.availability_mut().unwrap();
+    let (playable_until, complete) = (availability.playable_until(), availability.is_complete());
+
+    let id = state.lock().unwrap().insert(Box::new(session));
+    send(&channel, MediaEvent::Opened { id });
+    send(&channel, MediaEvent::Availability { playable_until, complete });
+}
+
+/// Opens `paths` as one file, played one after another on a single
+/// timeline; see `group`
+#[tauri::command]
+pub fn open_media_group(
+    app: AppHandle, state: State<Arc<Mutex<PlaybackRegistry>>>, paths: Vec<String>,
+    backend: Option<backend::BackendKind>, channel: Channel<MediaEvent>
+) {
+    let _timing = timed!("open_media_group", &channel);
+    let mut ap = state.lock().unwrap();
+    log::debug!("open_media_group: {paths:?}");
+
+    let mut checked = Vec::with_capacity(paths.len());
+    for path in &paths {
+        match sandbox::check_read(&app, path) {
+            Ok(x) => checked.push(x),
+            Err(reason) => return send(&channel, MediaEvent::PathRejected { reason }),
+        }
+    }
+    let group = match MediaGroup::create(backend.unwrap_or(ap.default_backend), &checked) {
+        Ok(x) => x,
+        Err(e) => return send_error!(&channel, e.to_string()),
+    };
+    let offsets = group.offsets();
+    let id = ap.insert(Box::new(group));
+    send(&channel, MediaEvent::GroupOpened { id, offsets });
+}
+
+/// Where `rebuild_index` keeps the indexes it builds
+fn seek_index_dir(app: &AppHandle) -> Option<std::path::PathBuf> {
+    app.path().app_cache_dir().ok().map(|x| x.join("seek-index"))
+}
+
+/// Scans the whole file of session `id` for keyframes and seeks by that
+/// from then on, for files whose own index is broken or missing. The index
+/// is kept in the cache directory and used again whenever the file is
+/// opened, as long as it hasn't changed; see `seek_index`.
+#[tauri::command]
+pub async fn rebuild_index(
+    id: i32,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+    let (path, wants_progress) = {
+        let mut ap = state.lock().unwrap();
+        let wants_progress = ap.wants(id, EventKind::Progress);
+        let Some(session) = ffmpeg_session(&mut ap, id, &channel) else {
+            return Ok(());
+        };
+        (session.path().to_owned(), wants_progress)
+    };
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("rebuild_index", &channel);
+        let progress = |fraction| !wants_progress || send_progress(&channel, fraction);
+        let index = match SeekIndex::build(&path, progress) {
+            Ok(x) => x,
+            Err(e) => return send_media_error!(&channel, e),
+        };
+        match seek_index_dir(&app) {
+            Some(dir) => {
+                if let Err(e) = index.save(&dir, &path) {
+                    log::warn!("rebuild_index: cannot save the index: {e}");
+                }
+            }
+            None => log::warn!("rebuild_index: no cache directory; the index won't be kept"),
+        }
+        let keyframes = index.keyframes();
+        let mut ap = state.lock().unwrap();
+        // unless it was closed in the meantime
+        let Some(session) = ap.table.get_mut(&id).and_then(|x| x.session_mut())
+            .filter(|x| x.path() == path) else
+        {
+            return send_invalid_id(&channel);
+        };
+        session.set_seek_index(Some(index));
+        send(&channel, MediaEvent::SeekIndexBuilt { keyframes });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Looks at the file again, as much of it as `Availability::refresh` does
+/// at a time, and reports how far it can be played. Files not opened with
+/// `open_media_tolerant` are always complete.
+#[tauri::command]
+pub fn check_availability(
     id: i32,
-    pos: i64,
     state: State<Arc<Mutex<PlaybackRegistry>>>,
     channel: Channel<MediaEvent>,
 ) {
+    let _timing = timed!("check_availability", &channel);
     let mut ap = state.lock().unwrap();
     let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-    if let Err(e) = session.seek_byte_pos(pos) {
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    let duration = session.demuxer().duration();
+    let Some(availability) = session.availability_mut() else {
+        return send(&channel, MediaEvent::Availability { playable_until: duration, complete: true });
+    };
+    if let Err(e) = availability.refresh() {
         return send_error!(&channel, e.to_string());
     }
+    send(&channel, MediaEvent::Availability {
+        playable_until: availability.playable_until(),
+        complete: availability.is_complete(),
+    });
+}
+
+#[tauri::command]
+#[allow(clippy::cast_sign_loss)]
+pub fn open_video(
+    id: i32, video_id: i32, accel: bool,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("open_video", &channel);
+    let mut ap = state.lock().unwrap();
+    send_debug!(ap, id, &channel, "open_video: {id} {video_id}, accel = {accel}");
+    let Some(backend) = 
+        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
+
+    let index = (video_id > 0).then_some(video_id as usize);
+    let status = match backend.open_video_player(index, accel) {
+        Ok(x) => x,
+        Err(e) => return send_media_error!(&channel, e),
+    };
+    ap.restart_prefetcher(id);
+
+    send(&channel, MediaEvent::VideoStatus {
+        index: status.index,
+        framerate: status.framerate,
+        is_vfr: status.is_vfr,
+        start_time: status.start_time,
+        sample_aspect_ratio: status.sample_aspect_ratio,
+        size: status.size,
+    });
+    send_done(&channel);
+}
+
+#[tauri::command]
+#[allow(clippy::cast_sign_loss)]
+pub fn open_video_sampler(
+    id: i32, video_id: i32, _accel: bool,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("open_video_sampler", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+
+    let index = (video_id > 0).then_some(video_id as usize);
+    let (d, _) = match session.open_video_sampler(index) {
+        Ok(()) => session.video().unwrap(),
+        Err(e) => return send_media_error!(&channel, e),
+    };
+
+    log::debug!("open_video_sampler: {id} {video_id}");
+
+    send(&channel, MediaEvent::VideoStatus {
+        index: d.stream_info().index(),
+        framerate: d.framerate().into(),
+        is_vfr: d.is_vfr(),
+        start_time: d.stream_info().start_time_seconds(),
+        sample_aspect_ratio: d.sample_aspect_ratio().into(),
+        size: d.original_size()
+    });
+    send_done(&channel);
+}
+
+#[tauri::command]
+#[allow(clippy::cast_sign_loss)]
+pub fn open_text_detector(
+    id: i32, video_id: i32,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("open_text_detector", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+
+    let index = (video_id > 0).then_some(video_id as usize);
+    let (d, _) = match session.open_text_detector(index) {
+        Ok(()) => session.video().unwrap(),
+        Err(e) => return send_media_error!(&channel, e),
+    };
+
+    log::debug!("open_text_detector: {id} {video_id}");
+
+    send(&channel, MediaEvent::VideoStatus {
+        index: d.stream_info().index(),
+        framerate: d.framerate().into(),
+        is_vfr: d.is_vfr(),
+        start_time: d.stream_info().start_time_seconds(),
+        sample_aspect_ratio: d.sample_aspect_ratio().into(),
+        size: d.original_size()
+    });
+    send_done(&channel);
+}
+
+#[tauri::command]
+#[allow(clippy::cast_sign_loss)]
+pub fn open_crop_detector(
+    id: i32, video_id: i32,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("open_crop_detector", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+
+    let index = (video_id > 0).then_some(video_id as usize);
+    let (d, _) = match session.open_crop_detector(index) {
+        Ok(()) => session.video().unwrap(),
+        Err(e) => return send_media_error!(&channel, e),
+    };
+
+    log::debug!("open_crop_detector: {id} {video_id}");
+
+    send(&channel, MediaEvent::VideoStatus {
+        index: d.stream_info().index(),
+        framerate: d.framerate().into(),
+        is_vfr: d.is_vfr(),
+        start_time: d.stream_info().start_time_seconds(),
+        sample_aspect_ratio: d.sample_aspect_ratio().into(),
+        size: d.original_size()
+    });
     send_done(&channel);
 }
 
 #[tauri::command]
-pub fn seek_audio(
-    id: i32,
-    time: units::Seconds,
-    state: State<Arc<Mutex<PlaybackRegistry>>>,
-    channel: Channel<MediaEvent>,
-) {
-    let mut ap = state.lock().unwrap();
-    let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-    if session.audio().is_none() {
-        return send(&channel, MediaEvent::NoStream {});
+#[allow(clippy::cast_sign_loss)]
+pub fn open_audio(
+    id: i32, audio_id: i32,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("open_audio", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(backend) = 
+        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
+
+    let index = (audio_id > 0).then_some(audio_id as usize);
+    let status = match backend.open_audio_player(index) {
+        Ok(x) => x,
+        Err(e) => return send_error!(&channel, e.to_string()),
+    };
+    ap.restart_prefetcher(id);
+
+    send_debug!(ap, id, &channel, "open_audio: {id} {audio_id}, {} Hz", status.sample_rate);
+
+    send(&channel, MediaEvent::AudioStatus {
+        index: status.index,
+        start_time: status.start_time,
+        length: status.length,
+        sample_rate: status.sample_rate,
+    });
+}
+
+/// Values per second for a sampler, given either as such or as the length
+/// of the step between values in seconds, which needn't divide a second
+fn sampler_rate(sample_per_second: Option<usize>, step: Option<f64>) -> Result<f64, String> {
+    match (sample_per_second, step) {
+        (Some(x), None) if x > 0 => Ok(x.to_f64().unwrap()),
+        (None, Some(x)) if x.is_finite() && x > 0.0 => Ok(1.0 / x),
+        (Some(_), Some(_)) => Err("give either sample_per_second or step, not both".to_owned()),
+        _ => Err("invalid sampler resolution".to_owned()),
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::cast_sign_loss)]
+pub fn open_audio_sampler(
+    id: i32, audio_id: i32,
+    sample_per_second: Option<usize>, step: Option<f64>,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("open_audio_sampler", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(backend) = 
+        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
+
+    let per_second = match sampler_rate(sample_per_second, step) {
+        Ok(x) => x,
+        Err(e) => return send_error!(&channel, e),
+    };
+    let index = (audio_id > 0).then_some(audio_id as usize);
+    let status = match backend.open_audio_sampler(index, per_second) {
+        Ok(x) => x,
+        Err(e) => return send_error!(&channel, e.to_string()),
+    };
+
+    log::debug!("open_audio_sampler: {id} [{audio_id}] {per_second}/s");
+
+    send(&channel, MediaEvent::AudioStatus {
+        index: status.index,
+        start_time: status.start_time,
+        length: status.length,
+        sample_rate: status.sample_rate,
+    });
+}
+
+/// Adds a sampler fed by the session's open audio decoder, typically the
+/// player's, so that the waveform doesn't need a second decoding pass. See
+/// `sample_automatic3` for getting at the results.
+#[tauri::command]
+pub fn open_audio_sampler_tap(
+    id: i32, sample_per_second: Option<usize>, step: Option<f64>,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("open_audio_sampler_tap", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    if session.audio().is_none() {
+        return send(&channel, MediaEvent::NoStream {});
+    }
+    let per_second = match sampler_rate(sample_per_second, step) {
+        Ok(x) => x,
+        Err(e) => return send_error!(&channel, e),
+    };
+    if let Err(e) = session.add_audio_sampler_tap(per_second) {
+        return send_error!(&channel, e.to_string());
+    }
+
+    log::debug!("open_audio_sampler_tap: {id} {per_second}/s");
+
+    let (d, _) = session.audio().unwrap();
+    send(&channel, MediaEvent::AudioStatus {
+        index: d.stream_info().index(),
+        start_time: d.stream_info().start_time_seconds(),
+        length: d.estimated_length(),
+        sample_rate: d.sample_rate(),
+    });
+}
+
+#[tauri::command]
+#[allow(clippy::cast_sign_loss)]
+pub fn open_subpicture(
+    id: i32, stream_id: i32,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("open_subpicture", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+
+    let index = (stream_id >= 0).then_some(stream_id as usize);
+    let (d, _) = match session.open_subpicture(index) {
+        Ok(()) => session.subpicture().unwrap(),
+        Err(e) => return send_error!(&channel, e.to_string()),
+    };
+
+    log::debug!("open_subpicture: {id} {stream_id}");
+
+    send(&channel, MediaEvent::SubpictureStatus {
+        index: d.stream_info().index(),
+        codec: d.codec_name().clone(),
+        canvas_size: d.canvas_size(),
+    });
+    send_done(&channel);
+}
+
+#[tauri::command]
+pub fn get_subpictures(
+    id: i32,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) -> Result<ipc::Response, ()> {
+    let _timing = timed!("get_subpictures", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = ffmpeg_session(&mut ap, id, &channel) else {
+        return Err(());
+    };
+    let Some((_, c)) = session.subpicture_mut() else {
+        send(&channel, MediaEvent::NoStream {});
+        return Err(());
+    };
+
+    let mut buf: Vec<u8> = Vec::new();
+    pack_subpictures(&c.get_delta(), &mut buf);
+    Ok(ipc::Response::new(buf))
+}
+
+#[tauri::command]
+pub fn seek_media(
+    id: i32,
+    time: units::Seconds,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("seek_media", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(backend) = 
+        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    if let Err(e) = backend.seek(time) {
+        return send_media_error!(&channel, e);
+    }
+    ap.sought(id, Some(time));
+    send_done(&channel);
+}
+
+#[tauri::command]
+pub fn seek_media_byte(
+    id: i32,
+    pos: i64,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("seek_media_byte", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    if let Err(e) = session.seek_byte_pos(pos) {
+        return send_error!(&channel, e.to_string());
+    }
+    ap.sought(id, None);
+    send_done(&channel);
+}
+
+#[tauri::command]
+pub fn seek_audio(
+    id: i32,
+    time: units::Seconds,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("seek_audio", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    if session.audio().is_none() {
+        return send(&channel, MediaEvent::NoStream {});
+    }
+    if let Err(e) = session.seek_audio(time) {
+        return send_media_error!(&channel, e);
+    }
+    ap.sought(id, Some(time));
+    send_done(&channel);
+}
+
+#[tauri::command]
+pub fn seek_video(
+    id: i32,
+    time: units::Seconds,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("seek_video", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    if session.video().is_none() {
+        return send(&channel, MediaEvent::NoStream {});
+    }
+    if let Err(e) = session.seek_video(time) {
+        return send_media_error!(&channel, e);
+    }
+    ap.sought(id, Some(time));
+    send_done(&channel);
+}
+
+#[tauri::command]
+pub fn skip_until(
+    id: i32,
+    time: units::Seconds,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) -> Result<ipc::Response, ()> {
+    let _timing = timed!("skip_until", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(surface) = ffmpeg_session(&mut ap, id, &channel).map(|x| x.has_surface()) else {
+        return Err(());
+    };
+    let mut monitor = ap.monitors.remove(&id).unwrap_or_default();
+    // its frames would be dropped, and the jump play without them
+    let prefetched = if surface {
+        None
+    } else {
+        ap.prefetchers.get_mut(&id).and_then(|x| x.take(time))
+    };
+    let Some(session) = ffmpeg_session(&mut ap, id, &channel) else {
+        return Err(());
+    };
+
+    // the session decodes what comes after while this plays
+    if let Some(hit) = prefetched {
+        if let Err(e) = session.seek(hit.until) {
+            send_media_error!(&channel, e);
+            return Err(());
+        }
+        monitor.record(&hit.audio);
+        ap.monitors.insert(id, monitor);
+        if let Some(x) = ap.prefetchers.get_mut(&id) {
+            x.set_resume_at(Some(hit.until));
+        }
+        let mut buf = Vec::new();
+        pack_audio_frames(&hit.audio, &mut buf);
+        pack_video_frames(&hit.video, &mut buf);
+        return Ok(ipc::Response::new(buf));
+    }
+
+    if let Some((_, s)) = session.audio_mut() {
+        s.clear();
+    }
+    if let Some((_, s)) = session.video_mut() {
+        s.clear();
+    }
+
+    loop {
+        if let Err(e) = session.try_process_skipping_before(time) {
+            send_media_error!(&channel, e);
+            return Err(());
+        }
+
+        if session.audio().as_ref().is_none_or(|(_, s)| !s.is_empty())
+            && session.video().as_ref().is_none_or(|(_, s)| !s.is_empty())
+        {
+            break;
+        }
+
+        match session.try_feed() {
+            Ok(false) => break,
+            Ok(true) => {},
+            Err(e) => {
+                send_media_error!(&channel, e);
+                return Err(());
+            }
+        }
+    };
+    let response = send_frames(session, &mut monitor, None);
+    ap.monitors.insert(id, monitor);
+    Ok(response)
+}
+
+/// The last `seconds` of audio given to the player of session `id`, up to
+/// `monitor::CAPACITY`, packed as by `get_frames_automatic` without any
+/// video, for the player to play again. Nothing is sought or decoded, so
+/// playback goes on from where it was afterwards.
+#[tauri::command]
+pub fn replay_last(
+    id: i32, seconds: f64,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) -> Result<ipc::Response, ()> {
+    let _timing = timed!("replay_last", &channel);
+    let ap = state.lock().unwrap();
+    if !ap.table.contains_key(&id) {
+        send_invalid_id(&channel);
+        return Err(());
+    }
+    if !(seconds.is_finite() && seconds > 0.0) {
+        send_error!(&channel, format!("invalid length: {seconds}"));
+        return Err(());
+    }
+    let length = units::Seconds(seconds.min(monitor::CAPACITY.0));
+    let audio = ap.monitors.get(&id).map(|x| x.last(length)).unwrap_or_default();
+    let mut buf = Vec::new();
+    pack_audio_frames(&audio, &mut buf);
+    pack_video_frames(&VecDeque::new(), &mut buf);
+    Ok(ipc::Response::new(buf))
+}
+
+/// For a paused picture that is being rendered again: takes the newest
+/// frame the video player has, or renders the one at the current position,
+/// and sends only the tiles that changed since the last call. See
+/// `pack_frame_delta`.
+#[tauri::command]
+pub fn send_frame_delta(
+    id: i32,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) -> Result<ipc::Response, ()> {
+    let _timing = timed!("send_frame_delta", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = ffmpeg_session(&mut ap, id, &channel) else {
+        return Err(());
+    };
+    let Some((_, VideoSinkKind::Player(p))) = session.video_mut() else {
+        send(&channel, MediaEvent::NoStream {});
+        return Err(());
+    };
+
+    let frame = match p.get_delta().pop_back() {
+        Some(x) => x,
+        None => match session.render_frame_at(session.position()) {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                send_error!(&channel, "send_frame_delta: past the end");
+                return Err(());
+            }
+            Err(e) => {
+                send_media_error!(&channel, e);
+                return Err(());
+            }
+        },
+    };
+    let hashes = delta::TileHashes::compute(&frame);
+    let changed = hashes.changed_since(session.sent_tiles_mut().as_ref());
+    let mut buf = Vec::new();
+    pack_frame_delta(&frame, &hashes, &changed, &mut buf);
+    *session.sent_tiles_mut() = Some(hashes);
+    Ok(ipc::Response::new(buf))
+}
+
+fn window_size(app: &AppHandle) -> Result<(u32, u32), String> {
+    let window = app.get_webview_window("main").ok_or("no main window")?;
+    let size = window.inner_size().map_err(|e| e.to_string())?;
+    Ok((size.width, size.height))
+}
+
+/// Starts drawing the session's video into the main window, below the
+/// webview; see `media::surface`. From then on video frames aren't sent
+/// with the audio, and `present_surface` shows them instead.
+#[tauri::command]
+pub fn attach_surface(
+    app: AppHandle, id: i32, viewport: surface::Viewport,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("attach_surface", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    let Some(window) = app.get_webview_window("main") else {
+        return send_error!(&channel, "attach_surface: no main window");
+    };
+    match surface::Surface::create(window, viewport) {
+        Ok(x) => session.set_surface(Some(x)),
+        Err(e) => return send_error!(&channel, e),
+    }
+    send_done(&channel);
+}
+
+/// For when the window has been resized or the preview area has moved
+#[tauri::command]
+pub fn update_surface(
+    app: AppHandle, id: i32, viewport: surface::Viewport,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("update_surface", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    let Some(surface) = 
+        session.surface_mut() else { return send_error!(&channel, "no surface attached") };
+    let size = match window_size(&app) {
+        Ok(x) => x,
+        Err(e) => return send_error!(&channel, e),
+    };
+    surface.resize(size, viewport);
+    if let Err(e) = surface.draw() {
+        return send_error!(&channel, e);
+    }
+    send_done(&channel);
+}
+
+/// Draws the newest video frame due by `time`, dropping the older ones;
+/// meant to be called on every animation frame while playing
+#[tauri::command]
+pub fn present_surface(
+    id: i32, time: units::Seconds,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("present_surface", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    let Some((_, VideoSinkKind::Player(p))) = 
+        session.video_mut() else { return send(&channel, MediaEvent::NoStream {}) };
+    let due = p.take_due(time);
+    let Some(surface) = 
+        session.surface_mut() else { return send_error!(&channel, "no surface attached") };
+    if let Some(frame) = &due
+        && let Err(e) = surface.set_video(frame)
+    {
+        return send_error!(&channel, e);
+    }
+    if let Err(e) = surface.draw() {
+        return send_error!(&channel, e);
+    }
+    send_done(&channel);
+}
+
+/**
+ * The body is raw, so there is no channel: errors reject the call instead.
+ * request := [
+ *  id          : [i32]
+ *  width       : [u32]
+ *  height      : [u32]
+ *  rgba data   : \[[u8]] (none to remove the overlay)
+ * ]
+ * */
+#[tauri::command]
+pub fn set_surface_overlay(
+    request: ipc::Request,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+) -> Result<(), String> {
+    let _timing = timed!("set_surface_overlay");
+    let ipc::InvokeBody::Raw(body) = request.body() else {
+        return Err("set_surface_overlay: expected raw bytes".to_owned());
+    };
+    let field = |i: usize| body.get(i * 4..i * 4 + 4)
+        .map(|x| <[u8; 4]>::try_from(x).unwrap());
+    let (Some(id), Some(width), Some(height)) = (
+        field(0).map(i32::from_le_bytes),
+        field(1).map(u32::from_le_bytes),
+        field(2).map(u32::from_le_bytes),
+    ) else {
+        return Err("set_surface_overlay: request too short".to_owned());
+    };
+    let rgba = &body[12..];
+    if !rgba.is_empty() && rgba.len() != width as usize * height as usize * 4 {
+        return Err(format!("set_surface_overlay: {} bytes for {width}x{height}", rgba.len()));
+    }
+
+    let mut ap = state.lock().unwrap();
+    let session = ap.table.get_mut(&id)
+        .and_then(|x| x.session_mut())
+        .ok_or("invalid id, or not an ffmpeg session")?;
+    let surface = session.surface_mut().ok_or("no surface attached")?;
+    surface.set_overlay((!rgba.is_empty()).then_some(((width, height), rgba)))?;
+    surface.draw()
+}
+
+/// Goes back to sending video frames
+#[tauri::command]
+pub fn detach_surface(
+    id: i32,
+    state: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent>,
+) {
+    let _timing = timed!("detach_surface", &channel);
+    let mut ap = state.lock().unwrap();
+    let Some(session) = 
+        ffmpeg_session(&mut ap, id, &channel) else { return };
+    session.set_surface(None);
+    send_done(&channel);
+}
+
+#[tauri::command]
+pub async fn get_frames_automatic(
+    id: i32, target_working_time_ms: u64,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<ipc::Response, ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("get_frames_automatic", &channel);
+        let mut ap = state.lock().unwrap();
+        let PlaybackRegistry { table, monitors, prefetchers, .. } = &mut *ap;
+        let Some(backend) = table.get_mut(&id) else {
+            send_invalid_id(&channel);
+            return Err(());
+        };
+        
+        match backend.decode(Duration::from_millis(target_working_time_ms)) {
+            Ok(_) => {
+                let prefetcher = prefetchers.get_mut(&id);
+                let resume_at = prefetcher.as_ref().and_then(|x| x.resume_at());
+                let response =
+                    send_frames(backend.as_mut(), monitors.entry(id).or_default(), resume_at);
+                if let Some(x) = prefetcher {
+                    x.moved(backend.snapshot().position);
+                }
+                Ok(response)
+            }
+            Err(e) => {
+                send_media_error!(&channel, e);
+                Err(())
+            }
+        }
+    })
+    .await
+    .map_err(|_| ())
+    .flatten()
+}
+
+/// Feeds the frames in `[start, end]` to an analysing video sink
+fn analyse_range(
+    session: &mut session::Session, start: units::Seconds, end: units::Seconds
+) -> Result<(), MediaError> {
+    session.seek_video(start)?;
+    loop {
+        session.try_process_skipping_before(start)?;
+        if let Some((_, s)) = session.video()
+            && s.analysed_until().is_some_and(|x| x > end)
+        {
+            return Ok(());
+        }
+        if !session.try_feed()? {
+            return Ok(());
+        }
+    }
+}
+
+/// Runs the text detector opened by `open_text_detector` over `[start, end]`
+/// and sends the intervals where the bottom of the picture holds text
+#[tauri::command]
+pub async fn detect_onscreen_text(
+    id: i32, start: units::Seconds, end: units::Seconds,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("detect_onscreen_text", &channel);
+        let mut ap = state.lock().unwrap();
+        let Some(session) = ffmpeg_session(&mut ap, id, &channel) else {
+            return;
+        };
+        if !matches!(session.video(), Some((_, VideoSinkKind::TextDetector(_)))) {
+            return send(&channel, MediaEvent::NoStream {});
+        }
+
+        if let Err(e) = analyse_range(session, start, end) {
+            return send_error!(&channel, e.to_string());
+        }
+
+        let Some((_, VideoSinkKind::TextDetector(s))) = session.video_mut() else {
+            unreachable!()
+        };
+        let intervals = s.get_delta().into_iter()
+            .filter(|(a, _)| *a <= end)
+            .map(|(a, b)| (a, if b > end { end } else { b }))
+            .collect();
+        send(&channel, MediaEvent::OnscreenText { intervals });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Runs the crop detector opened by `open_crop_detector` over `[start, end]`
+/// and sends the letterbox bars common to all those frames
+#[tauri::command]
+pub async fn detect_letterbox(
+    id: i32, start: units::Seconds, end: units::Seconds,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("detect_letterbox", &channel);
+        let mut ap = state.lock().unwrap();
+        let Some(session) = ffmpeg_session(&mut ap, id, &channel) else {
+            return;
+        };
+        if !matches!(session.video(), Some((_, VideoSinkKind::CropDetector(_)))) {
+            return send(&channel, MediaEvent::NoStream {});
+        }
+        if let Err(e) = analyse_range(session, start, end) {
+            return send_error!(&channel, e.to_string());
+        }
+
+        let Some((d, VideoSinkKind::CropDetector(s))) = session.video() else {
+            unreachable!()
+        };
+        send(&channel, MediaEvent::Letterbox {
+            bars: s.bars(),
+            height: d.original_size().1,
+        });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Silences shorter than this are pauses, not breaks between parts
+const CHAPTER_SILENCE: units::Seconds = units::Seconds(1.0);
+
+/// Reads the file of session `id` again on its own, so playback isn't
+/// disturbed; see `audio::intensity_pair`
+#[tauri::command]
+pub async fn get_intensity_pair(
+    id: i32, stream_a: usize, stream_b: usize, sample_per_second: usize,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let (path, wants_progress) = {
+        let ap = state.lock().unwrap();
+        let Some(backend) = file_backend(&ap, id, &channel) else { return Ok(()) };
+        (backend.path().to_owned(), ap.wants(id, EventKind::Progress))
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("get_intensity_pair", &channel);
+        let progress = |fraction| !wants_progress || send_progress(&channel, fraction);
+        match audio::intensity_pair(&path, (stream_a, stream_b), sample_per_second, progress) {
+            Ok(pair) => send(&channel, MediaEvent::IntensityPair { pair }),
+            Err(e) => send_media_error!(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Peaks of the open audio stream of session `id`, or of the default one,
+/// each labelled speech, music or silence; see `audio_class`. Reads the file
+/// again on its own like `get_intensity_pair`.
+#[tauri::command]
+pub async fn get_waveform(
+    id: i32, sample_per_second: usize,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let (path, stream, wants_progress) = {
+        let ap = state.lock().unwrap();
+        let Some(backend) = file_backend(&ap, id, &channel) else { return Ok(()) };
+        (backend.path().to_owned(), backend.status().audio_index,
+            ap.wants(id, EventKind::Progress))
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("get_waveform", &channel);
+        let progress = |fraction| !wants_progress || send_progress(&channel, fraction);
+        match audio::classified_waveform(&path, stream, sample_per_second, progress) {
+            Ok(waveform) => send(&channel, MediaEvent::Waveform { waveform }),
+            Err(e) => send_media_error!(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Compares the lengths of the open audio and video streams of playback
+/// `id`, or of its default ones, for audio drifting against the picture,
+/// and sends the correction for the subtitles; see `drift`. Reads the file
+/// again on its own like `get_waveform`.
+#[tauri::command]
+pub async fn measure_drift(
+    id: i32,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let (path, status, wants_progress) = {
+        let ap = state.lock().unwrap();
+        let Some(backend) = file_backend(&ap, id, &channel) else { return Ok(()) };
+        (backend.path().to_owned(), backend.status(), ap.wants(id, EventKind::Progress))
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("measure_drift", &channel);
+        let progress = |fraction| !wants_progress || send_progress(&channel, fraction);
+        match drift::measure(&path, status.audio_index, status.video_index, progress) {
+            Ok(report) => send(&channel, MediaEvent::Drift { report }),
+            Err(e) => send_media_error!(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// audiowaveform's default, about 5ms per pixel at 48kHz
+const WAVEFORM_SAMPLES_PER_PIXEL: u32 = 256;
+
+/// Writes the peaks of the open audio stream of session `id`, or of the
+/// default one, as an audiowaveform file; see `waveform`. Reads the file
+/// again on its own like `get_intensity_pair`.
+#[tauri::command]
+pub async fn export_waveform(
+    id: i32, path: String, format: waveform::WaveformFormat,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let out_path = match sandbox::check_write(&app, &path) {
+        Ok(x) => x,
+        Err(reason) => {
+            send(&channel, MediaEvent::PathRejected { reason });
+            return Ok(());
+        }
+    };
+    let (path, stream, wants_progress) = {
+        let ap = state.lock().unwrap();
+        let Some(backend) = file_backend(&ap, id, &channel) else { return Ok(()) };
+        (backend.path().to_owned(), backend.status().audio_index,
+            ap.wants(id, EventKind::Progress))
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("export_waveform", &channel);
+        let progress = |fraction| !wants_progress || send_progress(&channel, fraction);
+        let samples_per_pixel = WAVEFORM_SAMPLES_PER_PIXEL;
+        let result = audio::min_max_peaks(
+                &path, stream, samples_per_pixel as usize, progress)
+            .and_then(|(sample_rate, peaks)| waveform::Waveform {
+                sample_rate, samples_per_pixel, peaks
+            }.write(&out_path, format));
+        match result {
+            Ok(()) => send_done(&channel),
+            Err(e) => send_media_error!(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Reads the whole file, reporting progress, and returns the shot changes
+/// and silences found
+fn find_breaks(
+    session: &mut session::Session, mut progress: impl FnMut(f64) -> bool,
+) -> Result<(Vec<units::Seconds>, Vec<(units::Seconds, units::Seconds)>), MediaError> {
+    let duration = session.demuxer().duration();
+    let mut cuts = Vec::new();
+    let mut silences = Vec::new();
+    let mut reported = 0.0;
+    session.seek(units::Seconds(0.0))?;
+    while session.try_feed()? {
+        session.try_process()?;
+        if let Some((_, VideoSinkKind::SceneDetector(s))) = session.video_mut() {
+            cuts.append(&mut s.get_delta());
+        }
+        if let Some((_, AudioSinkKind::SilenceDetector(s))) = session.audio_mut() {
+            if let Some(time) = s.last_time()
+                && duration.0 > 0.0 && time.0 - reported >= duration.0 / 100.0
+            {
+                reported = time.0;
+                if !progress((time.0 / duration.0).min(1.0)) {
+                    return Err(MediaError::Cancelled);
+                }
+            }
+            silences.append(&mut s.get_delta());
+        }
     }
-    if let Err(e) = session.seek_audio(time) {
-        return send_error!(&channel, e.to_string());
+    session.try_process()?;
+    if let Some((_, AudioSinkKind::SilenceDetector(s))) = session.audio_mut() {
+        silences.append(&mut s.get_delta());
     }
-    send_done(&channel);
+    // an ongoing silence is reported again, longer, as it continues
+    silences.dedup_by(|b, a| {
+        let same = a.0 == b.0;
+        if same { a.1 = b.1; }
+        same
+    });
+    Ok((cuts, silences))
 }
 
+/// Proposes chapters where long silences meet shot changes in the file of
+/// session `id`, read in a session of its own; see `own_session`. Streams
+/// 0 are the file's default ones, and either may be missing, but not both.
 #[tauri::command]
-pub fn seek_video(
-    id: i32,
-    time: units::Seconds,
-    state: State<Arc<Mutex<PlaybackRegistry>>>,
-    channel: Channel<MediaEvent>,
-) {
-    let mut ap = state.lock().unwrap();
-    let Some(session) = 
-        ap.table.get_mut(&id) else { return send_invalid_id(&channel) };
-    if session.video().is_none() {
-        return send(&channel, MediaEvent::NoStream {});
+#[allow(clippy::cast_sign_loss)]
+pub async fn suggest_chapters(
+    id: i32, video_id: i32, audio_id: i32, min_length: units::Seconds,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("suggest_chapters", &channel);
+        let progress = progress_of(&state.lock().unwrap(), id, &channel);
+        let Some((mut session, _)) = own_session(&state, id, &channel) else { return };
+        let video = (video_id > 0).then_some(video_id as usize);
+        let audio = (audio_id > 0).then_some(audio_id as usize);
+        let has_video = video.is_some() || session.demuxer().has_video();
+        let has_audio = audio.is_some() || session.demuxer().describe_streams().iter()
+            .any(|x| matches!(x.r#type, demux::SerializableStreamKind::Audio));
+        if !has_video && !has_audio {
+            return send(&channel, MediaEvent::NoStream {});
+        }
+        if has_video && let Err(e) = session.open_scene_detector(video) {
+            return send_media_error!(&channel, e);
+        }
+        if has_audio && let Err(e) = session.open_silence_detector(audio, CHAPTER_SILENCE) {
+            return send_media_error!(&channel, e);
+        }
+
+        let (cuts, silences) = match find_breaks(&mut session, progress) {
+            Ok(x) => x,
+            Err(e) => return send_media_error!(&channel, e),
+        };
+        log::debug!("suggest_chapters: {} cuts, {} silences", cuts.len(), silences.len());
+        let chapters = chapters::suggest(
+            &cuts, &silences, session.demuxer().duration(), min_length);
+        send(&channel, MediaEvent::Chapters { chapters });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+fn check_plan(app: &AppHandle, plan: &mux::MuxPlan) -> Result<(), String> {
+    sandbox::check_read(app, &plan.source)?;
+    for track in &plan.subtitles {
+        sandbox::check_read(app, &track.path)?;
     }
-    if let Err(e) = session.seek_video(time) {
-        return send_error!(&channel, e.to_string());
+    for font in &plan.fonts {
+        sandbox::check_read(app, &font.path)?;
     }
-    send_done(&channel);
+    sandbox::check_write(app, &plan.output)?;
+    Ok(())
 }
 
-#[tauri::command]
-pub fn skip_until(
-    id: i32,
-    time: units::Seconds,
-    state: State<Arc<Mutex<PlaybackRegistry>>>,
-    channel: Channel<MediaEvent>,
-) -> Result<ipc::Response, ()> {
-    let mut ap = state.lock().unwrap();
-    let Some(session) = ap.table.get_mut(&id) else {
-        send_invalid_id(&channel);
-        return Err(());
+/// Whether the linked ffmpeg can write `container`, sending `Unsupported`
+/// if not
+fn check_container(container: mux::Container, channel: &Channel<MediaEvent>) -> bool {
+    let (supported, feature) = match container {
+        mux::Container::Matroska => (capabilities::get().matroska_muxing, "matroskaMuxing"),
+        mux::Container::Mp4 => (capabilities::get().mp4_muxing, "mp4Muxing"),
     };
+    if !supported {
+        send(channel, MediaEvent::Unsupported { feature });
+    }
+    supported
+}
 
-    if let Some((_, s)) = session.audio_mut() {
-        s.clear();
+/// Writes a Matroska file, or MP4 by `plan.container`, as described by
+/// `plan`, sending progress, then reads it back and sends the verification
+/// report
+#[tauri::command]
+pub async fn mux_matroska(
+    plan: mux::MuxPlan,
+    app: AppHandle,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let channel = channel.clone();
+    if !check_container(plan.container, &channel) {
+        return Ok(());
     }
-    if let Some((_, s)) = session.video_mut() {
-        s.clear();
+    if let Err(reason) = check_plan(&app, &plan) {
+        send(&channel, MediaEvent::PathRejected { reason });
+        return Ok(());
     }
 
-    loop {
-        if let Err(e) = session.try_process_skipping_before(time) {
-            send_error!(&channel, e.to_string());
-            return Err(());
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("mux_matroska", &channel);
+        let progress = |fraction| send_progress(&channel, fraction);
+        if let Err(e) = mux::mux(&plan, progress) {
+            return send_media_error!(&channel, e);
         }
-
-        if session.audio().as_ref().is_none_or(|(_, s)| !s.is_empty())
-            && session.video().as_ref().is_none_or(|(_, s)| !s.is_empty())
+        match mux::expectation(&plan)
+            .and_then(|x| verify::verify_media(&plan.output, &x))
         {
-            break;
+            Ok(report) => send(&channel, MediaEvent::Verified { report }),
+            Err(e) => send_error!(&channel, e.to_string()),
         }
+    })
+    .await
+    .map_err(|_| ())
+}
 
-        match session.try_feed() {
-            Ok(false) => break,
-            Ok(true) => {},
-            Err(e) => {
-                send_error!(&channel, e.to_string());
-                return Err(());
+/// Goes through `plan` as `mux_matroska` would without writing anything,
+/// and sends how long and large the file will be and what in the plan
+/// would fail; see `mux::estimate`
+#[tauri::command]
+pub async fn estimate_export(
+    plan: mux::MuxPlan,
+    app: AppHandle,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let channel = channel.clone();
+    if !check_container(plan.container, &channel) {
+        return Ok(());
+    }
+    if let Err(reason) = check_plan(&app, &plan) {
+        send(&channel, MediaEvent::PathRejected { reason });
+        return Ok(());
+    }
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("estimate_export", &channel);
+        match mux::estimate(&plan) {
+            Ok(estimate) => {
+                log::debug!("estimate_export: {} bytes, {} problems",
+                    estimate.size, estimate.problems.len());
+                send(&channel, MediaEvent::ExportEstimated { estimate });
             }
+            Err(e) => send_media_error!(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Writes a synthetic clip as described by `spec`; see `test_media`
+#[tauri::command]
+pub async fn generate_test_media(
+    spec: test_media::TestMediaSpec, out_path: String,
+    app: AppHandle,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let channel = channel.clone();
+    let path = match sandbox::check_write(&app, &out_path) {
+        Ok(x) => x,
+        Err(reason) => {
+            send(&channel, MediaEvent::PathRejected { reason });
+            return Ok(());
         }
     };
-    Ok(send_frames(session))
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("generate_test_media", &channel);
+        let progress = |fraction| send_progress(&channel, fraction);
+        match test_media::generate(&spec, &path, progress) {
+            Ok(()) => send_done(&channel),
+            Err(e) => send_media_error!(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+fn export_frames(
+    session: &mut session::Session, positions: &[units::Seconds],
+    dir: &std::path::Path, format: still::ImageFormat, with_subtitles: bool,
+    mut burner: Option<compare::Burner>, mut progress: impl FnMut(f64) -> bool,
+) -> Result<Vec<String>, MediaError> {
+    let mut paths = Vec::new();
+    for (i, &time) in positions.iter().enumerate() {
+        let Some(mut frame) = session.render_frame_at(time)? else {
+            log::warn!("export_frames: {time} is past the end");
+            continue;
+        };
+        if with_subtitles
+            && let Some(canvas) = session.subpicture().map(|(d, _)| d.canvas_size())
+            && let Some(page) = session.subpicture_at(time)?
+        {
+            still::overlay(&mut frame.decoded, page, canvas);
+        }
+        if let Some(burner) = burner.as_mut() {
+            frame.decoded = burner.burn(&frame.decoded, time)?;
+        }
+        let data = still::encode(&frame.decoded, format)?;
+        let path = dir.join(format!("{:05}.{}", i + 1, format.extension()));
+        // frames from before are overwritten, but not through a link
+        if path.is_symlink() {
+            return Err(MediaError::InternalError(
+                format!("{}: a link to somewhere else", path.display())));
+        }
+        std::fs::write(&path, data)
+            .map_err(|e| MediaError::InternalError(e.to_string()))?;
+        paths.push(path.to_string_lossy().into_owned());
+
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = (i + 1) as f64 / positions.len() as f64;
+        if !progress(fraction) {
+            return Err(MediaError::Cancelled);
+        }
+    }
+    Ok(paths)
 }
 
+/// Writes the frames at `positions` as numbered images into `dir`, at the
+/// video's display size, then reads them back for a verification report.
+/// `with_subtitles` draws the subtitles on top: the open bitmap subtitle
+/// stream, if any, and document `subtitle_id`, if given, burnt in as in a
+/// hardsub export. Needs a video player; the session has to be sought
+/// again afterwards.
 #[tauri::command]
-pub async fn get_frames_automatic(
-    id: i32, target_working_time_ms: u64,
+pub async fn export_frame_sequence(
+    id: i32, positions: Vec<units::Seconds>, dir: String,
+    format: still::ImageFormat, with_subtitles: bool, subtitle_id: Option<i32>,
+    app: AppHandle,
     state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    subtitles: State<'_, Arc<Mutex<SubtitleRegistry>>>,
     channel: Channel<MediaEvent<'static>>,
-) -> Result<ipc::Response, ()> {
+) -> Result<(), ()> {
     let state = Arc::clone(&state);
     let channel = channel.clone();
+    let supported = match format {
+        still::ImageFormat::Png => capabilities::get().png_export,
+        still::ImageFormat::Jpeg => capabilities::get().jpeg_export,
+    };
+    if !supported {
+        let feature = match format {
+            still::ImageFormat::Png => "pngExport",
+            still::ImageFormat::Jpeg => "jpegExport",
+        };
+        send(&channel, MediaEvent::Unsupported { feature });
+        return Ok(());
+    }
+    // the frames are written into it
+    let dir = match sandbox::check_write(&app, &dir) {
+        Ok(x) => x,
+        Err(reason) => {
+            send(&channel, MediaEvent::PathRejected { reason });
+            return Ok(());
+        }
+    };
+    let script_path = match subtitle_id.filter(|_| with_subtitles) {
+        Some(subtitle_id) => {
+            let Some(script) = subtitles.lock().unwrap().ass_script(subtitle_id) else {
+                send_invalid_id(&channel);
+                return Ok(());
+            };
+            match cached_script(&app, "frames", subtitle_id, &script) {
+                Ok(x) => Some(x),
+                Err(e) => {
+                    send_error!(&channel, e);
+                    return Ok(());
+                }
+            }
+        }
+        None => None,
+    };
 
     async_runtime::spawn_blocking(move || {
+        let _timing = timed!("export_frame_sequence", &channel);
         let mut ap = state.lock().unwrap();
-        let Some(session) = ap.table.get_mut(&id) else {
-            send_invalid_id(&channel);
-            return Err(());
+        let progress = progress_of(&ap, id, &channel);
+        let Some(session) = ffmpeg_session(&mut ap, id, &channel) else {
+            return;
         };
-        
-        match work(session, target_working_time_ms) {
-            Ok(_) => {
-                Ok(send_frames(session))
-            }
-            Err(e) => {
-                send_error!(&channel, e.to_string());
-                Err(())
+        let Some((d, VideoSinkKind::Player(p))) = session.video_mut() else {
+            return send(&channel, MediaEvent::NoStream {});
+        };
+
+        let previous_size = p.output_size();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let display_size = (
+            (f64::from(d.original_size().0) * f64::from(d.sample_aspect_ratio())).round() as u32,
+            d.original_size().1,
+        );
+        if let Err(e) = p.set_output_size(display_size) {
+            return send_error!(&channel, e.to_string());
+        }
+        let burner = match script_path.map(|x| compare::Burner::create(display_size, &x)) {
+            Some(Ok(x)) => Some(x),
+            Some(Err(e)) => return send_media_error!(&channel, e),
+            None => None,
+        };
+
+        let result = export_frames(
+            session, &positions, &dir, format, with_subtitles, burner, progress);
+
+        if let Some((_, VideoSinkKind::Player(p))) = session.video_mut()
+            && let Err(e) = p.set_output_size(previous_size)
+        {
+            log::warn!("export_frame_sequence: failed to restore output size: {e}");
+        }
+        match result {
+            Ok(paths) => {
+                let report = verify::verify_images(&paths);
+                send(&channel, MediaEvent::FramesExported { paths });
+                send(&channel, MediaEvent::Verified { report });
             }
+            Err(e) => send_media_error!(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Writes `script`, made from document `subtitle_id`, into `dir` of the
+/// app's cache, for `compare::Burner`, which reads it from a file
+fn cached_script(
+    app: &AppHandle, dir: &str, subtitle_id: i32, script: &str
+) -> Result<std::path::PathBuf, String> {
+    let dir = app.path().app_cache_dir().map_err(|e| e.to_string())?.join(dir);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{subtitle_id}.ass"));
+    std::fs::write(&path, script).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn compare_frames(
+    session: &mut session::Session, rendered: &mut session::Session,
+    burner: &mut compare::Burner, positions: &[units::Seconds],
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<Vec<compare::FrameDiff>, MediaError> {
+    let mut diffs = Vec::new();
+    for (i, &time) in positions.iter().enumerate() {
+        let (Some(source), Some(old)) =
+            (session.render_frame_at(time)?, rendered.render_frame_at(time)?) else {
+            log::warn!("compare_frames: {time} is past the end");
+            continue;
+        };
+        let new = burner.burn(&source.decoded, time)?;
+        diffs.push(compare::compare(&old.decoded, &new, time));
+
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = (i + 1) as f64 / positions.len() as f64;
+        if !progress(fraction) {
+            return Err(MediaError::Cancelled);
+        }
+    }
+    Ok(diffs)
+}
+
+/// Compares the frames at `positions` of `rendered_path`, a hardsub export
+/// made earlier, with the video of session `id` with document
+/// `subtitle_id` burnt in as it is now; see `compare`. The frames are
+/// compared at the export's display size, and the session's video is read
+/// in a session of its own, so playback is left where it was.
+#[tauri::command]
+pub async fn compare_with_render(
+    id: i32, subtitle_id: i32, rendered_path: String, positions: Vec<units::Seconds>,
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    subtitles: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<MediaEvent<'static>>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+    let rendered_path = match sandbox::check_read(&app, &rendered_path) {
+        Ok(x) => x,
+        Err(reason) => {
+            send(&channel, MediaEvent::PathRejected { reason });
+            return Ok(());
+        }
+    };
+    let Some(script) = subtitles.lock().unwrap().ass_script(subtitle_id) else {
+        send_invalid_id(&channel);
+        return Ok(());
+    };
+    let script_path = match cached_script(&app, "compare", subtitle_id, &script) {
+        Ok(x) => x,
+        Err(e) => {
+            send_error!(&channel, e);
+            return Ok(());
+        }
+    };
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("compare_with_render", &channel);
+        let mut rendered = match session::Session::create(&rendered_path)
+            .and_then(|mut x| x.open_video_player(None, false).map(|()| x))
+        {
+            Ok(x) => x,
+            Err(e) => return send_media_error!(&channel, e),
+        };
+        let Some((d, VideoSinkKind::Player(p))) = rendered.video_mut() else {
+            return send(&channel, MediaEvent::NoStream {});
+        };
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let size = (
+            (f64::from(d.original_size().0) * f64::from(d.sample_aspect_ratio())).round() as u32,
+            d.original_size().1,
+        );
+        if let Err(e) = p.set_output_size(size) {
+            return send_error!(&channel, e.to_string());
+        }
+        let mut burner = match compare::Burner::create(size, &script_path) {
+            Ok(x) => x,
+            Err(e) => return send_media_error!(&channel, e),
+        };
+
+        let progress = progress_of(&state.lock().unwrap(), id, &channel);
+        let Some((mut session, snapshot)) = own_session(&state, id, &channel) else { return };
+        if snapshot.video_index.is_none() {
+            return send(&channel, MediaEvent::NoStream {});
+        }
+        if let Err(e) = session.open_video_player(snapshot.video_index, false) {
+            return send_media_error!(&channel, e);
+        }
+        let Some((_, VideoSinkKind::Player(p))) = session.video_mut() else {
+            return send(&channel, MediaEvent::NoStream {});
+        };
+        if let Err(e) = p.set_output_size(size) {
+            return send_error!(&channel, e.to_string());
+        }
+
+        let result = compare_frames(&mut session, &mut rendered, &mut burner, &positions, progress);
+        match result {
+            Ok(diffs) => send(&channel, MediaEvent::FramesCompared { diffs }),
+            Err(e) => send_media_error!(&channel, e),
         }
     })
     .await
     .map_err(|_| ())
-    .flatten()
 }
 
 #[tauri::command]
@@ -464,26 +2342,23 @@ pub async fn sample_automatic3(
     let channel = channel.clone();
 
     async_runtime::spawn_blocking(move || {
+        let _timing = timed!("sample_automatic3", &channel);
         let mut ap = state.lock().unwrap();
-        let Some(session) = ap.table.get_mut(&id) else {
+        let Some(backend) = ap.table.get_mut(&id) else {
             send_invalid_id(&channel);
             return Err(());
         };
 
-        match work(session, target_working_time_ms) {
+        match backend.decode(Duration::from_millis(target_working_time_ms)) {
             Ok(has_next) => {
-                let audio = 
-                    if let Some((_, AudioSinkKind::Sampler(s))) = session.audio_mut() {
-                        s.get_delta()
-                    } else {
-                        None
-                    };
-                let video = 
-                    if let Some((_, VideoSinkKind::Sampler(s))) = session.video_mut() {
-                        s.get_delta()
-                    } else {
-                        None
-                    };
+                let audio = backend.take_audio_samples();
+                let video = if let Some(session) = backend.session_mut()
+                    && let Some((_, VideoSinkKind::Sampler(s))) = session.video_mut()
+                {
+                    s.get_delta()
+                } else {
+                    None
+                };
                 send(&channel, MediaEvent::SampleDone2 {
                     audio, video,
                     is_eof: !has_next
@@ -491,7 +2366,7 @@ pub async fn sample_automatic3(
                 Ok(())
             }
             Err(e) => {
-                send_error!(&channel, e.to_string());
+                send_media_error!(&channel, e);
                 Err(())
             }
         }
@@ -501,39 +2376,19 @@ pub async fn sample_automatic3(
     .flatten()
 }
 
-fn work(
-    session: &mut session::Session, target_working_time_ms: u64
-) -> Result<bool, MediaError> {
-    let start_time = Instant::now();
-    let target_working_time = Duration::from_millis(target_working_time_ms);
-    loop {
-        session.try_process()?;
-        if start_time.elapsed() >= target_working_time
-            && (session.audio().is_none_or(|(_, s)| !s.is_empty())
-            || session.video().is_none_or(|(_, s)| !s.is_empty()))
-        {
-            return Ok(true);
-        }
-        if !session.try_feed()? {
-            return Ok(false);
-        }
-    }
-}
-
-fn send_frames(session: &mut session::Session) -> tauri::ipc::Response {
+/// Packs what the players have got since last time, but for what comes
+/// before `resume_at`, keeping the audio in `monitor` too
+fn send_frames(
+    backend: &mut dyn MediaBackend, monitor: &mut Monitor, resume_at: Option<units::Seconds>,
+) -> tauri::ipc::Response {
     let mut buf: Vec<u8> = Vec::new();
-    let audio = 
-        if let Some((_, AudioSinkKind::Player(s))) = session.audio_mut() {
-            s.get_delta()
-        } else {
-            VecDeque::new()
-        };
-    let video = 
-        if let Some((_, VideoSinkKind::Player(s))) = session.video_mut() {
-            s.get_delta()
-        } else {
-            VecDeque::new()
-        };
+    let mut audio = backend.take_audio();
+    let mut video = backend.take_video();
+    if let Some(time) = resume_at {
+        audio.retain(|x| x.time.0 >= time.0);
+        video.retain(|x| x.meta.time.0 >= time.0);
+    }
+    monitor.record(&audio);
     pack_audio_frames(&audio, &mut buf);
     pack_video_frames(&video, &mut buf);
     // log::trace!("sent frames: {} audio, {} video", audio.len(), video.len());
@@ -571,6 +2426,45 @@ pub fn pack_video_frames(frames: &VecDeque<frame::Video>, buf: &mut Vec<u8>) {
     }
 }
 
+/**
+ * tile := [
+ *  x, y        : [u32; 2]
+ *  width       : [u32]
+ *  height      : [u32]
+ *  rgba data   : \[[u8]]
+ * ]
+ * response := [
+ *  time        : [f64]
+ *  width       : [u32]
+ *  height      : [u32]
+ *  full        : [u32] (1 if every tile is sent)
+ *  count       : [u32]
+ *  tiles       : tile[]
+ * ]
+ * */
+pub fn pack_frame_delta(
+    frame: &frame::Video, hashes: &delta::TileHashes, changed: &[delta::Tile], buf: &mut Vec<u8>,
+) {
+    let (width, height) = hashes.size();
+    let total = width.div_ceil(delta::TILE_SIZE) * height.div_ceil(delta::TILE_SIZE);
+    let count = u32::try_from(changed.len()).unwrap();
+
+    buf.extend(frame.meta.time.0.to_le_bytes().iter());
+    buf.extend(width.to_le_bytes().iter());
+    buf.extend(height.to_le_bytes().iter());
+    buf.extend(u32::from(count == total).to_le_bytes().iter());
+    buf.extend(count.to_le_bytes().iter());
+    for &tile in changed {
+        buf.extend(tile.x.to_le_bytes().iter());
+        buf.extend(tile.y.to_le_bytes().iter());
+        buf.extend(tile.width.to_le_bytes().iter());
+        buf.extend(tile.height.to_le_bytes().iter());
+        for row in delta::tile_rows(frame, tile) {
+            buf.extend_from_slice(row);
+        }
+    }
+}
+
 /**
  * frame: [
  *  time        : [f64]
@@ -583,7 +2477,7 @@ pub fn pack_video_frames(frames: &VecDeque<frame::Video>, buf: &mut Vec<u8>) {
  *  frames      : frame[]
  * ]
  * */
-pub fn pack_audio_frames(frames: &VecDeque<frame::Audio>, buf: &mut Vec<u8>) {
+pub fn pack_audio_frames(frames: &VecDeque<backend::AudioBlock>, buf: &mut Vec<u8>) {
     // FIXME: support multiple channels
     fn to_byte_slice(floats: &[f32]) -> &[u8] {
         unsafe { 
@@ -593,25 +2487,62 @@ pub fn pack_audio_frames(frames: &VecDeque<frame::Audio>, buf: &mut Vec<u8>) {
     
     buf.extend(u32::try_from(frames.len()).unwrap().to_le_bytes().iter());
     for frame in frames {
-        let time = frame.meta.time.0;
-        let data: &[f32] = frame.decoded.plane(0);
+        let time = frame.time.0;
+        let data = &frame.samples;
 
         buf.extend(time.to_le_bytes().iter());
-        buf.extend(i32::try_from(frame.meta.pkt_pos).unwrap().to_le_bytes().iter());
+        buf.extend(i32::try_from(frame.pkt_pos).unwrap().to_le_bytes().iter());
         buf.extend(i32::try_from(data.len()).unwrap().to_le_bytes().iter());
         buf.extend_from_slice(to_byte_slice(data));
     };
 }
 
+/**
+ * rect := [
+ *  x, y        : [u32; 2]
+ *  width       : [u32]
+ *  height      : [u32]
+ *  rgba data   : \[[u8]]
+ * ]
+ * page := [
+ *  start       : [f64]
+ *  end         : [f64] (NaN if unknown)
+ *  count       : [u32]
+ *  rects       : rect[]
+ * ]
+ * response := [
+ *  size        : [u32]
+ *  pages       : page[]
+ * ]
+ * */
+pub fn pack_subpictures(pages: &VecDeque<frame::Subpicture>, buf: &mut Vec<u8>) {
+    buf.extend(u32::try_from(pages.len()).unwrap().to_le_bytes().iter());
+    for page in pages {
+        let end = page.end_time.map_or(f64::NAN, |x| x.0);
+
+        buf.extend(page.meta.time.0.to_le_bytes().iter());
+        buf.extend(end.to_le_bytes().iter());
+        buf.extend(u32::try_from(page.rects.len()).unwrap().to_le_bytes().iter());
+        for rect in &page.rects {
+            buf.extend(rect.x.to_le_bytes().iter());
+            buf.extend(rect.y.to_le_bytes().iter());
+            buf.extend(rect.width.to_le_bytes().iter());
+            buf.extend(rect.height.to_le_bytes().iter());
+            buf.extend_from_slice(&rect.rgba);
+        }
+    }
+}
+
 #[tauri::command]
 pub fn get_keyframe_before(
     id: i32, time: units::Seconds,
     state: State<Arc<Mutex<PlaybackRegistry>>>,
     channel: Channel<MediaEvent>
 ) {
-    let ap = state.lock().unwrap();
+    let _timing = timed!("get_keyframe_before", &channel);
+    let mut ap = state.lock().unwrap();
     let Some(session) = 
-        ap.table.get(&id) else { return send_invalid_id(&channel) };
+        ffmpeg_session(&mut ap, id, &channel) else { return };
     let Some((_, VideoSinkKind::Sampler(s))) = 
         session.video() else { return send(&channel, MediaEvent::NoStream {}) };
 
@@ -624,15 +2555,22 @@ pub fn get_keyframe_before(
 
 #[tauri::command]
 pub fn test_performance(
+    app: AppHandle,
     path: String, _postprocess: bool, hwaccel: bool, channel: Channel<MediaEvent>
 ) {
+    let _timing = timed!("test_performance", &channel);
+    let path = match sandbox::check_read(&app, &path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, MediaEvent::PathRejected { reason }),
+    };
+
     log::info!("list of available accelerators:");
     for t in accel::HardwareDecoder::available_types() {
         log::info!("-- {t}");
     }
 
     let mut session = 
-        session::Session::create(std::path::Path::new(&path)).unwrap();
+        session::Session::create(&path).unwrap();
     session.open_video_player(None, hwaccel).unwrap();
     session.open_audio_player(None).unwrap();
     if let Some((_, VideoSinkKind::Player(x))) = session.video_mut() {
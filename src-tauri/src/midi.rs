@@ -0,0 +1,166 @@
+//! MIDI controllers for timing: pads and keys to mark where a cue starts
+//! and ends, or to play and pause, and a fader or knob for the monitoring
+//! gain, mapped like the jog/shuttle controllers of `controller` and read
+//! on midir's own thread as messages come. Which pad sends which note
+//! differs from one controller to the next, so in learn mode messages are
+//! only reported, for the frontend to build the mapping from.
+
+use std::sync::{mpsc, Mutex};
+
+use midir::{MidiInput, MidiInputConnection};
+use serde::{Deserialize, Serialize};
+
+use crate::controller::{ControllerAction, ControllerInput, Output};
+
+const CLIENT_NAME: &str = "subtle";
+/// A control change counts as pressed from this value up, as pads sending
+/// 127 and 0 do
+const CONTROL_PRESSED: u8 = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+#[ts(export)]
+pub enum MidiControl {
+    /// a key or pad; `channel` from 1 to 16, as MIDI software shows it
+    #[serde(rename_all = "camelCase")]
+    Note { channel: u8, number: u8 },
+    /// a fader, knob or button sending control changes
+    #[serde(rename_all = "camelCase")]
+    Control { channel: u8, number: u8 },
+}
+
+/// A message as reported in learn mode
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MidiMessage {
+    pub control: MidiControl,
+    /// velocity or value, from 0 to 127; 0 for a note released
+    pub value: u8,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MidiBinding {
+    pub control: MidiControl,
+    pub action: ControllerAction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct MidiMapping {
+    /// done when a note is struck or a control goes up past its middle
+    pub bindings: Vec<MidiBinding>,
+    /// a control change for the monitoring gain
+    pub gain: Option<MidiControl>,
+    /// the gain at the bottom and top of `gain`, in dB
+    pub gain_range: (f64, f64),
+}
+
+/// The names of the MIDI inputs there are
+pub fn ports() -> Result<Vec<String>, String> {
+    let input = MidiInput::new(CLIENT_NAME).map_err(|e| e.to_string())?;
+    Ok(input.ports().iter().filter_map(|x| input.port_name(x).ok()).collect())
+}
+
+/// The control of a note or control change `message` and its value
+fn parse(message: &[u8]) -> Option<MidiMessage> {
+    let [status, number, value] = *message else { return None };
+    let channel = (status & 0x0f) + 1;
+    let (control, value) = match status & 0xf0 {
+        0x80 => (MidiControl::Note { channel, number }, 0),
+        0x90 => (MidiControl::Note { channel, number }, value),
+        0xb0 => (MidiControl::Control { channel, number }, value),
+        _ => return None,
+    };
+    Some(MidiMessage { control, value })
+}
+
+/// What `message` asks for by `mapping`, or as it is when learning. The
+/// last value of each control is kept in `values`, to tell a press.
+fn interpret(
+    message: &[u8], mapping: &MidiMapping, learn: bool, values: &mut Vec<(MidiControl, u8)>,
+) -> Vec<ControllerInput> {
+    let Some(message) = parse(message) else { return Vec::new() };
+    if learn {
+        return vec![ControllerInput::Message(message)];
+    }
+    let last = match values.iter_mut().find(|x| x.0 == message.control) {
+        Some(x) => std::mem::replace(&mut x.1, message.value),
+        None => {
+            values.push((message.control, message.value));
+            0
+        }
+    };
+    let mut inputs = Vec::new();
+    if mapping.gain == Some(message.control) {
+        let (bottom, top) = mapping.gain_range;
+        inputs.push(ControllerInput::Gain(bottom + (top - bottom) * f64::from(message.value) / 127.0));
+    }
+    let pressed = match message.control {
+        MidiControl::Note { .. } => message.value > 0,
+        MidiControl::Control { .. } => last < CONTROL_PRESSED && message.value >= CONTROL_PRESSED,
+    };
+    if pressed {
+        inputs.extend(mapping.bindings.iter()
+            .filter(|x| x.control == message.control)
+            .map(|x| ControllerInput::Action(x.action.clone())));
+    }
+    inputs
+}
+
+fn connect(
+    port: &str, mapping: MidiMapping, learn: bool, mut output: Output,
+) -> Result<MidiInputConnection<Vec<(MidiControl, u8)>>, String> {
+    let input = MidiInput::new(CLIENT_NAME).map_err(|e| e.to_string())?;
+    let found = input.ports().into_iter()
+        .find(|x| input.port_name(x).is_ok_and(|name| name == port))
+        .ok_or(format!("no MIDI input {port}"))?;
+    input
+        .connect(&found, CLIENT_NAME, move |_, message, values| {
+            for x in interpret(message, &mapping, learn, values) {
+                output(x);
+            }
+        }, Vec::new())
+        .map_err(|e| format!("{port}: {e}"))
+}
+
+/// Tells the thread holding the connection to close it
+static STOP: Mutex<Option<mpsc::Sender<()>>> = Mutex::new(None);
+
+/// Has MIDI input `port` send what it asks for by `mapping` to `output`,
+/// or with `learn` every note and control change as it is, in place of
+/// any input read before
+pub fn configure(
+    port: &str, mapping: MidiMapping, learn: bool, output: Output,
+) -> Result<(), String> {
+    let mut stop = STOP.lock().unwrap();
+    stop.take();
+    let (sender, receiver) = mpsc::channel::<()>();
+    let (result_sender, result) = mpsc::sync_channel(1);
+    let port = port.to_owned();
+    // the connection may not be sent between threads, so it is held by one
+    std::thread::Builder::new()
+        .name("midi".to_owned())
+        .spawn(move || {
+            let connection = connect(&port, mapping, learn, output);
+            let connected = connection.is_ok();
+            let _ = result_sender.send(connection.as_ref().map(|_| ()).map_err(Clone::clone));
+            if connected {
+                // till the sender is dropped
+                let _ = receiver.recv();
+            }
+            drop(connection);
+        })
+        .map_err(|e| e.to_string())?;
+    result.recv().map_err(|e| e.to_string())??;
+    *stop = Some(sender);
+    Ok(())
+}
+
+/// Closes the MIDI input being read, if there is one
+pub fn release() -> bool {
+    STOP.lock().unwrap().take().is_some()
+}
@@ -0,0 +1,142 @@
+#![allow(clippy::needless_pass_by_value)]
+
+//! Commands for `models`: what is installed, downloading and removing;
+//! and for `tools`, the programs that run them.
+
+use crate::models::{self, InstalledModel, ModelSource};
+use crate::sandbox;
+use crate::timing;
+use crate::tools::{self, Tool, ToolPath};
+
+use serde::Serialize;
+use std::path::Path;
+use tauri::ipc::Channel;
+use tauri::{async_runtime, AppHandle};
+
+#[derive(Clone, Serialize, Debug, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "event", content = "data")]
+#[ts(export)]
+pub enum ModelEvent {
+    #[serde(rename_all = "camelCase")]
+    Models { models: Vec<InstalledModel> },
+    /// Bytes of the model there are so far, and its size if the server says
+    #[serde(rename_all = "camelCase")]
+    Progress { received: u64, total: Option<u64> },
+    #[serde(rename_all = "camelCase")]
+    Installed { model: InstalledModel },
+    #[serde(rename_all = "camelCase")]
+    Tools { tools: Vec<ToolPath> },
+    #[serde(rename_all = "camelCase")]
+    Done {},
+    #[serde(rename_all = "camelCase")]
+    RuntimeError { what: String },
+}
+
+fn send(channel: &Channel<ModelEvent>, what: ModelEvent) {
+    if let Err(e) = channel.send(what) {
+        log::warn!("ChannelClosed: channel {}: {e}", channel.id());
+    }
+}
+
+fn send_error(channel: &Channel<ModelEvent>, what: String) {
+    send(channel, ModelEvent::RuntimeError { what });
+}
+
+/// The installed models, whose paths are then allowed by `sandbox`
+#[tauri::command]
+pub fn list_models(app: AppHandle, channel: Channel<ModelEvent>) {
+    let _timing = timing::Command::start("list_models", |_| {});
+    let dir = match models::dir(&app) {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e),
+    };
+    let models = models::list(&dir);
+    for model in &models {
+        sandbox::grant_own(&app, Path::new(&model.path));
+    }
+    send(&channel, ModelEvent::Models { models });
+}
+
+/// Downloads and installs `source`; see `models::download`. Closing the
+/// channel stops the download, which goes on from there the next time.
+#[tauri::command]
+pub async fn download_model(
+    app: AppHandle, source: ModelSource, channel: Channel<ModelEvent>,
+) -> Result<(), ()> {
+    let dir = match models::dir(&app) {
+        Ok(x) => x,
+        Err(e) => {
+            send_error(&channel, e);
+            return Ok(());
+        }
+    };
+    let progress = |received, total| match channel.send(ModelEvent::Progress { received, total }) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("ChannelClosed: channel {}: {e}", channel.id());
+            false
+        }
+    };
+    match models::download(&dir, &source, progress).await {
+        Ok(model) => {
+            sandbox::grant_own(&app, Path::new(&model.path));
+            send(&channel, ModelEvent::Installed { model });
+        }
+        Err(e) => send_error(&channel, e),
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_model(
+    app: AppHandle, name: String, channel: Channel<ModelEvent>,
+) -> Result<(), ()> {
+    async_runtime::spawn_blocking(move || {
+        let _timing = timing::Command::start("remove_model", |_| {});
+        match models::dir(&app).and_then(|dir| models::remove(&dir, &name)) {
+            Ok(()) => send(&channel, ModelEvent::Done {}),
+            Err(e) => send_error(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+fn send_tools(app: &AppHandle, channel: &Channel<ModelEvent>) {
+    match tools::list(app) {
+        Ok(tools) => send(channel, ModelEvent::Tools { tools }),
+        Err(e) => send_error(channel, e),
+    }
+}
+
+/// The tools and where they are; see `tools`
+#[tauri::command]
+pub fn list_tools(app: AppHandle, channel: Channel<ModelEvent>) {
+    let _timing = timing::Command::start("list_tools", |_| {});
+    send_tools(&app, &channel);
+}
+
+/// Has the user pick the program for `tool` in a file dialog, then sends
+/// `Tools`, or `Done` if they cancelled
+#[tauri::command]
+pub async fn choose_tool(
+    app: AppHandle, tool: Tool, channel: Channel<ModelEvent>,
+) -> Result<(), ()> {
+    async_runtime::spawn_blocking(move || {
+        match tools::choose(&app, tool) {
+            Ok(true) => send_tools(&app, &channel),
+            Ok(false) => send(&channel, ModelEvent::Done {}),
+            Err(e) => send_error(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+#[tauri::command]
+pub fn clear_tool(app: AppHandle, tool: Tool, channel: Channel<ModelEvent>) {
+    match tools::clear(&app, tool) {
+        Ok(()) => send_tools(&app, &channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
@@ -0,0 +1,211 @@
+//! Models for the ML features, speech recognition, voice activity detection
+//! and OCR, downloaded into the app's data directory so that they work
+//! without the user finding and placing files. Each comes from a URL in the
+//! configuration along with the SHA-256 it must have, and nothing that
+//! doesn't match is installed. A download that stops halfway is kept as
+//! `<name>.part` and taken up with a range request the next time; beside
+//! each model, `<name>.json` says where it came from.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_http::reqwest::{header, Client, StatusCode};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum ModelKind {
+    /// speech recognition, like whisper.cpp's `ggml` models
+    Asr,
+    /// voice activity detection
+    Vad,
+    Ocr,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ModelSource {
+    /// the file it's kept as, like `ggml-base.en.bin`
+    pub name: String,
+    pub kind: ModelKind,
+    pub url: String,
+    /// in hex
+    pub sha256: String,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct InstalledModel {
+    pub source: ModelSource,
+    /// to hand to the feature that uses it
+    pub path: String,
+    pub size: u64,
+}
+
+/// Where models are kept, created if it isn't there yet
+pub fn dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("models");
+    fs::create_dir_all(&dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    Ok(dir)
+}
+
+/// A plain file name, which can't be taken for one of our own files
+fn check_name(name: &str) -> Result<(), String> {
+    let plain = Path::new(name).file_name().is_some_and(|x| x == name)
+        && !name.starts_with('.');
+    if !plain || name.ends_with(".part") || name.ends_with(".json") {
+        return Err(format!("invalid model name: {name}"));
+    }
+    Ok(())
+}
+
+fn manifest_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.json"))
+}
+
+fn part_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.part"))
+}
+
+/// The models installed in `dir`; any whose file has gone are left out
+pub fn list(dir: &Path) -> Vec<InstalledModel> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut models: Vec<InstalledModel> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            let source: ModelSource = fs::read_to_string(&path).ok()
+                .and_then(|x| serde_json::from_str(&x).ok())?;
+            let model = dir.join(&source.name);
+            let size = fs::metadata(&model).ok()?.len();
+            Some(InstalledModel { path: model.to_string_lossy().into_owned(), size, source })
+        })
+        .collect();
+    models.sort_by(|a, b| a.source.name.cmp(&b.source.name));
+    models
+}
+
+/// Deletes model `name` from `dir`, along with any part of it downloaded
+pub fn remove(dir: &Path, name: &str) -> Result<(), String> {
+    check_name(name)?;
+    let mut found = false;
+    for path in [dir.join(name), manifest_path(dir, name), part_path(dir, name)] {
+        match fs::remove_file(&path) {
+            Ok(()) => found = true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        }
+    }
+    if found { Ok(()) } else { Err(format!("no model named {name}")) }
+}
+
+/// Names of the models being downloaded, so that two downloads of one
+/// don't write into the same part
+static DOWNLOADING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Downloading(String);
+
+impl Drop for Downloading {
+    fn drop(&mut self) {
+        DOWNLOADING.lock().unwrap().retain(|x| *x != self.0);
+    }
+}
+
+/// Feeds what has been downloaded of a part already to `hasher`
+fn hash_file(path: &Path, hasher: &mut Sha256) -> std::io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; 1 << 20];
+    let mut length = 0;
+    loop {
+        let count = file.read(&mut buffer)?;
+        if count == 0 {
+            return Ok(length);
+        }
+        hasher.update(&buffer[..count]);
+        length += u64::try_from(count).unwrap();
+    }
+}
+
+/// Downloads `source` into `dir`, going on from a part left by an earlier
+/// try if the server allows, and installs it once its checksum is right.
+/// `progress` gets the bytes there are so far and the size, if known, and
+/// returns `false` to stop; the part is kept for next time.
+pub async fn download(
+    dir: &Path, source: &ModelSource, mut progress: impl FnMut(u64, Option<u64>) -> bool,
+) -> Result<InstalledModel, String> {
+    check_name(&source.name)?;
+    let _downloading = {
+        let mut downloading = DOWNLOADING.lock().unwrap();
+        if downloading.contains(&source.name) {
+            return Err(format!("{} is already being downloaded", source.name));
+        }
+        downloading.push(source.name.clone());
+        Downloading(source.name.clone())
+    };
+    let part = part_path(dir, &source.name);
+    let io_error = |e: std::io::Error| format!("{}: {e}", part.display());
+
+    let mut hasher = Sha256::new();
+    let mut received = match hash_file(&part, &mut hasher) {
+        Ok(x) => x,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(io_error(e)),
+    };
+    let mut request = Client::new().get(&source.url);
+    if received > 0 {
+        request = request.header(header::RANGE, format!("bytes={received}-"));
+    }
+    let mut response = request.send().await.map_err(|e| format!("{}: {e}", source.url))?;
+    let status = response.status();
+    let complete = received > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE;
+    if !complete && !status.is_success() {
+        return Err(format!("{}: {status}", source.url));
+    }
+    if status == StatusCode::OK && received > 0 {
+        log::info!("download: {} doesn't take ranges; starting over", source.url);
+        hasher = Sha256::new();
+        received = 0;
+    }
+    let total = response.content_length().map(|x| x + received).filter(|_| !complete);
+
+    let mut file = OpenOptions::new()
+        .create(true).write(true).append(received > 0).truncate(received == 0)
+        .open(&part).map_err(io_error)?;
+    if !complete {
+        while let Some(chunk) = response.chunk().await.map_err(|e| format!("{}: {e}", source.url))? {
+            file.write_all(&chunk).map_err(io_error)?;
+            hasher.update(&chunk);
+            received += u64::try_from(chunk.len()).unwrap();
+            if !progress(received, total) {
+                return Err("download cancelled".to_owned());
+            }
+        }
+    }
+    file.sync_all().map_err(io_error)?;
+    drop(file);
+
+    let digest = format!("{:x}", hasher.finalize());
+    if !digest.eq_ignore_ascii_case(source.sha256.trim()) {
+        let _ = fs::remove_file(&part);
+        return Err(format!("{}: checksum {digest} doesn't match {}", source.name, source.sha256));
+    }
+    let model = dir.join(&source.name);
+    fs::rename(&part, &model).map_err(io_error)?;
+    let manifest = manifest_path(dir, &source.name);
+    fs::write(&manifest, serde_json::to_string_pretty(source).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("{}: {e}", manifest.display()))?;
+    Ok(InstalledModel {
+        source: source.clone(),
+        path: model.to_string_lossy().into_owned(),
+        size: received,
+    })
+}
@@ -0,0 +1,244 @@
+//! Remote control over OSC, for stream decks, control surfaces and
+//! companion apps on a phone: play, pause, seek and mark, passed on like
+//! what a jog/shuttle controller asks for. OSC is the one these speak
+//! most, and over UDP it needs nothing beyond the standard library. It is
+//! only listened for once turned on, and every message has to carry the
+//! token set then as its first argument, since anyone able to reach the
+//! port could send one.
+//!
+//! The addresses are:
+//!
+//! | address              | arguments after the token   |
+//! |----------------------|-----------------------------|
+//! | `/subtle/play`       |                             |
+//! | `/subtle/pause`      |                             |
+//! | `/subtle/playpause`  |                             |
+//! | `/subtle/seek`       | the time to go to, seconds  |
+//! | `/subtle/seekby`     | how far, seconds            |
+//! | `/subtle/step`       | how many frames             |
+//! | `/subtle/mark`       |                             |
+//! | `/subtle/markin`     |                             |
+//! | `/subtle/markout`    |                             |
+//!
+//! Numbers can be sent as ints, floats or doubles. Bundles are taken apart
+//! and their messages done at once, whatever their time tag.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::controller::{ControllerAction, ControllerInput, Output};
+use crate::media::units::Seconds;
+
+/// How long a receive waits before the thread checks whether it should stop
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// The largest datagram read; OSC messages for this are far shorter
+const MAX_PACKET: usize = 1 << 16;
+/// The shortest token accepted, so that it can't be guessed by trying
+const MIN_TOKEN_LENGTH: usize = 16;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct RemoteConfig {
+    pub port: u16,
+    pub token: String,
+    /// listen on every network interface, for devices other than this
+    /// computer; otherwise only on loopback
+    pub all_interfaces: bool,
+}
+
+/// An argument of an OSC message
+#[derive(Debug, PartialEq)]
+enum Argument {
+    Int(i32),
+    Float(f64),
+    Str(String),
+    Other,
+}
+
+impl Argument {
+    fn number(&self) -> Option<f64> {
+        match *self {
+            Self::Int(x) => Some(f64::from(x)),
+            Self::Float(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// Takes a string padded to four bytes from the start of `data`
+fn take_string(data: &mut &[u8]) -> Option<String> {
+    let length = data.iter().position(|&x| x == 0)?;
+    let string = std::str::from_utf8(&data[..length]).ok()?.to_owned();
+    *data = data.get((length / 4 + 1) * 4..)?;
+    Some(string)
+}
+
+fn take_bytes<const N: usize>(data: &mut &[u8]) -> Option<[u8; N]> {
+    let (bytes, rest) = data.split_first_chunk::<N>()?;
+    *data = rest;
+    Some(*bytes)
+}
+
+/// The messages in `packet`, a message or a bundle, as addresses and
+/// arguments
+fn parse(mut packet: &[u8], messages: &mut Vec<(String, Vec<Argument>)>) -> Option<()> {
+    if packet.starts_with(b"#bundle\0") {
+        // and the time tag
+        packet = packet.get(16..)?;
+        while !packet.is_empty() {
+            let size = usize::try_from(i32::from_be_bytes(take_bytes(&mut packet)?)).ok()?;
+            let (element, rest) = packet.split_at_checked(size)?;
+            parse(element, messages)?;
+            packet = rest;
+        }
+        return Some(());
+    }
+    let address = take_string(&mut packet)?;
+    // the type tags may be left out by old senders, with no arguments then
+    let tags = if packet.is_empty() { String::new() } else { take_string(&mut packet)? };
+    let mut arguments = Vec::new();
+    for tag in tags.strip_prefix(',').unwrap_or("").chars() {
+        arguments.push(match tag {
+            'i' => Argument::Int(i32::from_be_bytes(take_bytes(&mut packet)?)),
+            'f' => Argument::Float(f64::from(f32::from_be_bytes(take_bytes(&mut packet)?))),
+            'd' => Argument::Float(f64::from_be_bytes(take_bytes(&mut packet)?)),
+            's' => Argument::Str(take_string(&mut packet)?),
+            'h' | 't' => {
+                take_bytes::<8>(&mut packet)?;
+                Argument::Other
+            }
+            'T' | 'F' | 'N' | 'I' => Argument::Other,
+            // anything with a length of its own we can't skip
+            _ => return None,
+        });
+    }
+    messages.push((address, arguments));
+    Some(())
+}
+
+/// Whether `a` and `b` are the same, taking as long whatever differs
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |all, (x, y)| all | (x ^ y)) == 0
+}
+
+/// The action for a message to `address` with `arguments`, the token left
+/// out
+fn action(address: &str, arguments: &[Argument]) -> Option<ControllerAction> {
+    let number = || arguments.first().and_then(Argument::number);
+    Some(match address.strip_prefix("/subtle/")? {
+        "play" => ControllerAction::Play,
+        "pause" => ControllerAction::Pause,
+        "playpause" => ControllerAction::PlayPause,
+        "seek" => ControllerAction::SeekTo { time: Seconds(number()?) },
+        "seekby" => ControllerAction::Seek { by: Seconds(number()?) },
+        #[allow(clippy::cast_possible_truncation)]
+        "step" => ControllerAction::Step { frames: number()?.round() as i32 },
+        "mark" => ControllerAction::Mark,
+        "markin" => ControllerAction::MarkIn,
+        "markout" => ControllerAction::MarkOut,
+        _ => return None,
+    })
+}
+
+struct Listener {
+    /// tells a listener that has been replaced from the current one
+    generation: u64,
+    address: SocketAddr,
+    token: String,
+    output: Output,
+}
+
+/// The listener running, if any; its thread stops once this no longer has
+/// its generation
+static LISTENER: Mutex<Option<Listener>> = Mutex::new(None);
+static NEXT_GENERATION: Mutex<u64> = Mutex::new(0);
+
+fn run(socket: &UdpSocket, generation: u64) {
+    let mut packet = vec![0; MAX_PACKET];
+    loop {
+        let received = socket.recv_from(&mut packet);
+        let mut listener = LISTENER.lock().unwrap();
+        let Some(current) = listener.as_mut().filter(|x| x.generation == generation) else {
+            return;
+        };
+        let (length, from) = match received {
+            Ok(x) => x,
+            Err(e) if matches!(e.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+            // what an earlier reply being refused gives on some systems
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => continue,
+            Err(e) => {
+                log::warn!("remote: {e}");
+                (current.output)(ControllerInput::Disconnected(e.to_string()));
+                *listener = None;
+                return;
+            }
+        };
+        let mut messages = Vec::new();
+        if parse(&packet[..length], &mut messages).is_none() {
+            log::debug!("remote: malformed packet from {from}");
+            continue;
+        }
+        for (address, arguments) in messages {
+            let Some((Argument::Str(token), arguments)) = arguments.split_first() else {
+                log::warn!("remote: {address} from {from} has no token");
+                continue;
+            };
+            if !same_token(token, &current.token) {
+                log::warn!("remote: {address} from {from} has a wrong token");
+                continue;
+            }
+            match action(&address, arguments) {
+                Some(x) => (current.output)(ControllerInput::Action(x)),
+                None => log::debug!("remote: unknown message {address} from {from}"),
+            }
+        }
+    }
+}
+
+/// Listens for OSC messages by `config` and sends what they ask for to
+/// `output` from now on, and gives the address listened on. Already
+/// listening on the same address, only the token and where it goes change.
+pub fn start(config: RemoteConfig, output: Output) -> Result<SocketAddr, String> {
+    if config.token.chars().count() < MIN_TOKEN_LENGTH {
+        return Err(format!("the token must be at least {MIN_TOKEN_LENGTH} characters"));
+    }
+    let host = if config.all_interfaces { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+    let address = SocketAddr::from((host, config.port));
+    let mut listener = LISTENER.lock().unwrap();
+    if let Some(current) = listener.as_mut().filter(|x| x.address == address) {
+        current.token = config.token;
+        current.output = output;
+        return Ok(address);
+    }
+    // the one before lets go of its port once its thread sees it's stopped
+    if listener.take().is_some_and(|x| x.address.port() == config.port) {
+        drop(listener);
+        std::thread::sleep(READ_TIMEOUT * 2);
+        listener = LISTENER.lock().unwrap();
+    }
+    let socket = UdpSocket::bind(address).map_err(|e| format!("{address}: {e}"))?;
+    socket.set_read_timeout(Some(READ_TIMEOUT)).map_err(|e| e.to_string())?;
+    let address = socket.local_addr().map_err(|e| e.to_string())?;
+    let mut next = NEXT_GENERATION.lock().unwrap();
+    *next += 1;
+    let generation = *next;
+    *listener = Some(Listener { generation, address, token: config.token, output });
+    let spawned = std::thread::Builder::new()
+        .name("remote".to_owned())
+        .spawn(move || run(&socket, generation));
+    if let Err(e) = spawned {
+        *listener = None;
+        return Err(e.to_string());
+    }
+    Ok(address)
+}
+
+/// Stops listening, if listening
+pub fn stop() -> bool {
+    LISTENER.lock().unwrap().take().is_some()
+}
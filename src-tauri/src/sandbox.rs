@@ -0,0 +1,193 @@
+//! Checks on paths that arrive from the frontend. Commands take paths as
+//! plain strings, so without these anything able to invoke them could read
+//! or overwrite whatever the user can.
+//!
+//! A path is accepted when it is absolute, has no `..` in it, doesn't lead
+//! into a system directory (even through symlinks), and is inside the asset
+//! protocol scope. The configuration puts only the app's cache in that
+//! scope; the dialog plugin adds every path the user picks to it, which is
+//! kept across runs, and `grant_own` files the backend made itself.
+//!
+//! Media a subtitle project points to is another matter: the project may
+//! come from anyone, so what it names is kept out of that scope, which the
+//! webview reads through and writes are checked against. `grant_referenced`
+//! notes it in a list of its own, which only `check_media` consults.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use tauri::{AppHandle, Manager};
+
+use crate::media::demux::Demuxer;
+use crate::subtitle::document::Document;
+
+/// Directories that are off limits, and those among them that aren't:
+/// fonts are attached when muxing
+struct SystemDirs {
+    system: Vec<PathBuf>,
+    fonts: Vec<PathBuf>,
+}
+
+#[cfg(unix)]
+fn system_dirs() -> SystemDirs {
+    let paths = |x: &[&str]| x.iter().map(PathBuf::from).collect();
+    SystemDirs {
+        system: paths(&[
+            "/bin", "/boot", "/dev", "/etc", "/lib", "/lib64", "/proc", "/sbin", "/sys",
+            "/usr/bin", "/usr/lib", "/usr/sbin", "/System", "/private/etc", "/private/var/db",
+        ]),
+        fonts: paths(&["/System/Library/Fonts"]),
+    }
+}
+
+/// Windows needn't be on `C:`, so these come from the environment
+#[cfg(windows)]
+fn system_dirs() -> SystemDirs {
+    let var = |name| std::env::var_os(name).map(PathBuf::from);
+    let windows = var("SystemRoot").or_else(|| var("windir"))
+        .unwrap_or_else(|| PathBuf::from("C:\\Windows"));
+    let mut system = vec![windows.clone()];
+    system.extend(var("ProgramData").map(|x| x.join("Microsoft")));
+    SystemDirs { system, fonts: vec![windows.join("Fonts")] }
+}
+
+/// Media that opened subtitle projects refer to, canonical; see
+/// `grant_referenced`
+static REFERENCED: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(Default::default);
+
+static SYSTEM_DIRS: LazyLock<SystemDirs> = LazyLock::new(|| {
+    let SystemDirs { system, fonts } = system_dirs();
+    // canonical, like the paths they're compared with
+    let resolve = |x: Vec<PathBuf>| x.into_iter()
+        .map(|x| comparable(&x.canonicalize().unwrap_or(x)))
+        .collect();
+    SystemDirs { system: resolve(system), fonts: resolve(fonts) }
+});
+
+/// Lowercased on Windows, without the `\\?\` that canonicalizing adds
+fn comparable(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from(path.to_string_lossy().trim_start_matches(r"\\?\").to_lowercase())
+    } else {
+        path.to_owned()
+    }
+}
+
+fn is_system(path: &Path) -> bool {
+    let path = comparable(path);
+    let under = |dirs: &[PathBuf]| dirs.iter().any(|x| path.starts_with(x));
+    under(&SYSTEM_DIRS.system) && !under(&SYSTEM_DIRS.fonts)
+}
+
+fn check_syntax(path: &str) -> Result<&Path, String> {
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return Err(format!("{}: not an absolute path", path.display()));
+    }
+    if path.components().any(|x| x == Component::ParentDir) {
+        return Err(format!("{}: contains `..`", path.display()));
+    }
+    Ok(path)
+}
+
+fn check_resolved(app: &AppHandle, original: &Path, resolved: &Path) -> Result<(), String> {
+    if is_system(resolved) {
+        return Err(format!("{}: inside a system directory", original.display()));
+    }
+    if !app.asset_protocol_scope().is_allowed(resolved) {
+        return Err(format!("{}: not opened by the user", original.display()));
+    }
+    Ok(())
+}
+
+/// For a file or directory to be read; it must exist
+pub fn check_read(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let path = check_syntax(path)?;
+    let resolved = path.canonicalize()
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    check_resolved(app, path, &resolved)?;
+    Ok(resolved)
+}
+
+/// For a media file to be opened for playback, which may also be one that
+/// a subtitle project refers to; see `grant_referenced`
+pub fn check_media(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let path = check_syntax(path)?;
+    let resolved = path.canonicalize()
+        .map_err(|e| format!("{}: {e}", path.display()))?;
+    if !is_system(&resolved) && REFERENCED.lock().unwrap().contains(&resolved) {
+        return Ok(resolved);
+    }
+    check_resolved(app, path, &resolved)?;
+    Ok(resolved)
+}
+
+/// For a file to be created or overwritten; its directory must exist
+pub fn check_write(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let path = check_syntax(path)?;
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(format!("{}: not a file path", path.display()));
+    };
+    let resolved = parent.canonicalize()
+        .map_err(|e| format!("{}: {e}", parent.display()))?
+        .join(name);
+    // an existing file may be a symlink to somewhere else
+    let resolved = resolved.canonicalize().unwrap_or(resolved);
+    check_resolved(app, path, &resolved)?;
+    Ok(resolved)
+}
+
+/// For an autosave at `path`, which also writes the journal and the
+/// temporary file beside it; those are allowed along with it, unless they
+/// lead elsewhere
+pub fn check_autosave(app: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let resolved = check_write(app, path)?;
+    for suffix in [".journal", ".tmp"] {
+        let mut sibling = resolved.as_os_str().to_owned();
+        sibling.push(suffix);
+        let sibling = PathBuf::from(sibling);
+        if sibling.canonicalize().is_ok_and(|x| x != sibling) {
+            return Err(format!("{}: a link to somewhere else", sibling.display()));
+        }
+        grant_own(app, &sibling);
+    }
+    Ok(resolved)
+}
+
+/// Allows the video and audio an Aegisub project refers to, which are
+/// given relative to the subtitle file, to be opened by `check_media`;
+/// only files ffmpeg reads as media are, and nothing else may read or
+/// write them
+pub fn grant_referenced(document_path: &Path, document: &Document) {
+    let Some(dir) = document_path.parent() else { return };
+    let referenced = document.extra_sections.iter()
+        .filter(|(header, _)| header.eq_ignore_ascii_case("[Aegisub Project Garbage]"))
+        .flat_map(|(_, lines)| lines)
+        .filter_map(|x| x.split_once(':'))
+        .filter(|(key, _)| matches!(key.trim(), "Video File" | "Audio File"))
+        .map(|(_, value)| value.trim())
+        // Aegisub writes `?video` and the like for dummy sources
+        .filter(|x| !x.is_empty() && !x.starts_with('?'));
+    for path in referenced {
+        let Ok(resolved) = dir.join(path).canonicalize() else { continue };
+        if is_system(&resolved) || !resolved.is_file() {
+            continue;
+        }
+        match Demuxer::open(&resolved) {
+            Ok(x) if x.is_media() => {
+                REFERENCED.lock().unwrap().insert(resolved);
+            }
+            Ok(_) => log::warn!("grant_referenced: {}: not media", resolved.display()),
+            Err(e) => log::warn!("grant_referenced: {}: {e}", resolved.display()),
+        }
+    }
+}
+
+/// Allows a file the backend put in the app's own directories, like a
+/// downloaded model, to be handed back to commands that check paths
+pub fn grant_own(app: &AppHandle, path: &Path) {
+    if let Err(e) = app.asset_protocol_scope().allow_file(path) {
+        log::warn!("grant_own: {}: {e}", path.display());
+    }
+}
@@ -0,0 +1,124 @@
+#![allow(clippy::needless_pass_by_value)]
+
+//! Everything open at once, so that the frontend can put it all back after
+//! an update or a crash with a single call.
+
+use crate::media::{backend, session};
+use crate::media_api::PlaybackRegistry;
+use crate::sandbox;
+use crate::timing;
+use crate::subtitle_api::{DocumentSnapshot, SubtitleRegistry};
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tauri::ipc::Channel;
+use tauri::{async_runtime, AppHandle, State};
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SessionSnapshot {
+    /// by the id they had
+    pub playbacks: Vec<(i32, session::Snapshot)>,
+    pub documents: Vec<(i32, DocumentSnapshot)>,
+}
+
+#[derive(Clone, Serialize, Debug, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "event", content = "data")]
+#[ts(export)]
+pub enum SnapshotEvent {
+    #[serde(rename_all = "camelCase")]
+    Snapshot { snapshot: SessionSnapshot },
+    #[serde(rename_all = "camelCase")]
+    Restored {
+        /// pairs of the id in the snapshot and the new one
+        playbacks: Vec<(i32, i32)>,
+        documents: Vec<(i32, i32)>,
+        /// what couldn't be reopened, and why
+        failures: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    RuntimeError { what: String },
+}
+
+fn send(channel: &Channel<SnapshotEvent>, what: SnapshotEvent) {
+    if let Err(e) = channel.send(what) {
+        log::warn!("ChannelClosed: channel {}: {e}", channel.id());
+    }
+}
+
+#[tauri::command]
+pub fn snapshot_session(
+    playbacks: State<Arc<Mutex<PlaybackRegistry>>>,
+    documents: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SnapshotEvent>,
+) {
+    let _timing = timing::Command::start("snapshot_session", |_| {});
+    let playbacks = playbacks.lock().unwrap().snapshot();
+    let documents = match documents.lock().unwrap().snapshot() {
+        Ok(x) => x,
+        Err(what) => return send(&channel, SnapshotEvent::RuntimeError { what }),
+    };
+    send(&channel, SnapshotEvent::Snapshot {
+        snapshot: SessionSnapshot { playbacks, documents },
+    });
+}
+
+/// Reopens what `snapshot_session` captured under new ids. Paths go through
+/// `sandbox` like any other; whatever fails is reported and the rest is
+/// restored anyway.
+#[tauri::command]
+pub async fn restore_session(
+    app: AppHandle, snapshot: SessionSnapshot,
+    playbacks: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    documents: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SnapshotEvent>,
+) -> Result<(), ()> {
+    let playback_state = Arc::clone(&playbacks);
+    let document_state = Arc::clone(&documents);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timing::Command::start("restore_session", |_| {});
+        let mut failures = Vec::new();
+
+        let mut playbacks = Vec::new();
+        for (previous, mut x) in snapshot.playbacks {
+            let opened = sandbox::check_media(&app, &x.path).and_then(|path| {
+                x.path = path.to_string_lossy().into_owned();
+                for member in &mut x.members {
+                    *member = sandbox::check_media(&app, member)?.to_string_lossy().into_owned();
+                }
+                backend::restore(&x).map_err(|e| format!("{}: {e}", x.path))
+            });
+            match opened {
+                Ok(backend) => playbacks.push(
+                    (previous, playback_state.lock().unwrap().insert(backend))),
+                Err(e) => failures.push(e),
+            }
+        }
+
+        let mut documents = Vec::new();
+        for (previous, mut x) in snapshot.documents {
+            if let Some(autosave) = &x.autosave {
+                match sandbox::check_autosave(&app, autosave) {
+                    Ok(path) => x.autosave = Some(path.to_string_lossy().into_owned()),
+                    Err(e) => {
+                        failures.push(e);
+                        continue;
+                    }
+                }
+            }
+            match document_state.lock().unwrap().restore(x) {
+                Ok(id) => documents.push((previous, id)),
+                Err(e) => failures.push(format!("document {previous}: {e}")),
+            }
+        }
+
+        log::debug!("restore_session: {} playbacks, {} documents, {} failures",
+            playbacks.len(), documents.len(), failures.len());
+        send(&channel, SnapshotEvent::Restored { playbacks, documents, failures });
+    })
+    .await
+    .map_err(|_| ())
+}
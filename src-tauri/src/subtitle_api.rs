@@ -0,0 +1,2251 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use crate::compute::{self, ComputeDevice};
+use crate::encoding::{self, TextFormat};
+use crate::engine::{Engine, EngineError, Opened};
+use crate::sandbox;
+use crate::timing;
+use crate::tools::{self, Tool};
+use crate::transcribe::cloud::{self, Cloud};
+use crate::transcribe::{self, AsrConfig, AsrProvider, TranscriptSegment, TranscriptWriter, Whisper};
+use crate::tts::{self, SpokenLength, Voice};
+use crate::media::{audio, audio_class, backend, session::Snapshot};
+use crate::media::conform::{self, ConformReport};
+use crate::media::damage::DamageSpan;
+use crate::media::record::{self, InputDevice};
+use crate::media::units::Seconds;
+use crate::media_api::{NoFile, PlaybackRegistry};
+use crate::subtitle::document::{Document, Event, ParseIssue, Style, SubtitleFormat};
+use crate::subtitle::edit::{Edit, SplitPiece};
+use crate::subtitle::journal::{self, Journal};
+use crate::subtitle::lead::{self, LeadConfig};
+use crate::subtitle::chapters::{self, Chapter};
+use crate::subtitle::convert::{self, DowngradeReport, SrtRules};
+use crate::subtitle::markers::Marker;
+use crate::subtitle::merge::{self, MergePolicy};
+use crate::subtitle::regions::{self, Region};
+use crate::subtitle::retime::{self, Retime};
+use crate::subtitle::repair::{self, Repair};
+use crate::subtitle::positioning::{Letterbox, PositioningPolicy};
+use crate::subtitle::qc::{self, QcIssue};
+use crate::subtitle::safe_area::BroadcastStandard;
+use crate::subtitle::search::{self, SearchGroup};
+use crate::subtitle::split::{self, SplitPolicy};
+use crate::subtitle::split_preview::{self, SplitPreview, Throttle};
+use crate::subtitle::takes::Take;
+use crate::subtitle::words::{LowConfidenceSpan, Word};
+use crate::subtitle::align;
+use crate::subtitle::query::{self, EventFilter, EventGroup, EventSort, GroupBy, Metric};
+use crate::subtitle::{ass, parse, srt};
+use crate::subtitle::parse::Detection;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::ipc::Channel;
+use tauri::{async_runtime, AppHandle, Manager, State};
+
+pub struct SubtitleRegistry {
+    next_id: i32,
+    table: HashMap<i32, Document>,
+    /// autosave journals of the documents that have one
+    journals: HashMap<i32, Journal>,
+    /// requests of `preview_split`
+    split_throttle: Throttle,
+}
+
+impl SubtitleRegistry {
+    pub fn new() -> SubtitleRegistry {
+        SubtitleRegistry {
+            next_id: 0,
+            table: HashMap::new(),
+            journals: HashMap::new(),
+            split_throttle: Throttle::new(),
+        }
+    }
+
+    /// Every open document, by id
+    pub fn snapshot(&self) -> Result<Vec<(i32, DocumentSnapshot)>, String> {
+        let mut result = Vec::new();
+        for (&id, document) in &self.table {
+            result.push((id, DocumentSnapshot {
+                document: serde_json::to_value(document).map_err(|e| e.to_string())?,
+                autosave: self.journals.get(&id)
+                    .map(|x| x.path().to_string_lossy().into_owned()),
+            }));
+        }
+        result.sort_by_key(|(id, _)| *id);
+        Ok(result)
+    }
+
+    /// Opens the document in `snapshot` under a new id; its autosave path,
+    /// if any, must have been vetted by the caller
+    pub fn restore(&mut self, snapshot: DocumentSnapshot) -> Result<i32, String> {
+        let document: Document = serde_json::from_value(snapshot.document)
+            .map_err(|e| e.to_string())?;
+        let journal = snapshot.autosave
+            .map(|x| Journal::create(Path::new(&x), &document))
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.table.insert(id, document);
+        if let Some(journal) = journal {
+            self.journals.insert(id, journal);
+        }
+        Ok(id)
+    }
+
+    /// Records `edits`, already made to document `id`, in its autosave
+    /// journal if it has one
+    fn journal(&mut self, id: i32, edits: &[Edit]) -> Result<(), String> {
+        let SubtitleRegistry { table, journals, .. } = self;
+        match (table.get(&id), journals.get_mut(&id)) {
+            (Some(document), Some(journal)) => journal.append(edits, document)
+                .map_err(|e| format!("autosave failed: {e}")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Document `id` written as ASS, for libass to render
+    pub fn ass_script(&self, id: i32) -> Option<String> {
+        self.table.get(&id).map(ass::write)
+    }
+}
+
+/// An open document as `SubtitleRegistry::snapshot` saw it, edits included
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct DocumentSnapshot {
+    /// the document itself, opaque to the frontend
+    #[ts(type = "unknown")]
+    pub document: serde_json::Value,
+    /// where it was being autosaved
+    pub autosave: Option<String>,
+}
+
+#[derive(Clone, Serialize, Debug, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "event", content = "data")]
+#[ts(export)]
+pub enum SubtitleEvent {
+    #[serde(rename_all = "camelCase")]
+    Done {},
+    #[serde(rename_all = "camelCase")]
+    Opened {
+        id: i32,
+        format: SubtitleFormat,
+        issues: Vec<ParseIssue>,
+        text_format: TextFormat,
+        /// some bytes couldn't be decoded and were replaced with U+FFFD
+        lossy_decoding: bool,
+    },
+    /// What `open_subtitle` took the file to be, before it is opened
+    #[serde(rename_all = "camelCase")]
+    FormatDetected { detection: Detection },
+    /// What `repair_subtitle` did to the file, before it is opened
+    #[serde(rename_all = "camelCase")]
+    Repaired { repairs: Vec<Repair> },
+    #[serde(rename_all = "camelCase")]
+    Recovered {
+        id: i32,
+        format: SubtitleFormat,
+        text_format: TextFormat,
+        /// edits replayed from the journal
+        replayed: usize,
+        /// the last edit was only partly written and has been lost
+        truncated: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    QcResult { issues: Vec<QcIssue> },
+    /// ids of the events removed by `dedupe_events`
+    #[serde(rename_all = "camelCase")]
+    Deduplicated { removed: Vec<u32> },
+    /// What `auto_split_event` would split an event into; an `Edit::Split`
+    /// with these pieces, as they are or after review, does it
+    #[serde(rename_all = "camelCase")]
+    SplitProposed { event_id: u32, pieces: Vec<SplitPiece> },
+    #[serde(rename_all = "camelCase")]
+    SplitPreviewed { preview: SplitPreview },
+    /// A newer `preview_split` came in before this one got to run
+    #[serde(rename_all = "camelCase")]
+    SplitPreviewSkipped {},
+    /// Retimings proposed by `suggest_lead`, for `edit_subtitle`
+    #[serde(rename_all = "camelCase")]
+    LeadSuggested { edits: Vec<Edit> },
+    /// Retimings proposed by `suggest_retime`, for `edit_subtitle`
+    #[serde(rename_all = "camelCase")]
+    RetimeSuggested { edits: Vec<Edit> },
+    /// What `conform_to_new_media` found, and whether its retiming was
+    /// applied and the media session moved to the new file. `undo`, given
+    /// to `edit_subtitle`, puts the document's times back as they were; the
+    /// session stays on the new file.
+    #[serde(rename_all = "camelCase")]
+    Conformed { report: ConformReport, applied: bool, undo: Vec<Edit> },
+    /// `undo`, given to `edit_subtitle`, puts the events back as they were
+    #[serde(rename_all = "camelCase")]
+    Merged { event_id: u32, undo: Vec<Edit> },
+    #[serde(rename_all = "camelCase")]
+    Positioning {
+        policy: PositioningPolicy,
+        letterbox: Option<Letterbox>,
+        /// vertical margins, in script coordinates, that the preview uses
+        /// for events aligned to the top or bottom edge; absent if the policy
+        /// doesn't constrain them
+        margin_top: Option<f64>,
+        margin_bottom: Option<f64>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Markers { markers: Vec<Marker> },
+    #[serde(rename_all = "camelCase")]
+    MarkerAdded { marker_id: u32 },
+    #[serde(rename_all = "camelCase")]
+    NextMarker { marker: Option<Marker> },
+    #[serde(rename_all = "camelCase")]
+    Regions { regions: Vec<Region> },
+    #[serde(rename_all = "camelCase")]
+    RegionAdded { region_id: u32 },
+    #[serde(rename_all = "camelCase")]
+    Takes { takes: Vec<Take> },
+    /// The microphone is live; playback from `from` should start now
+    #[serde(rename_all = "camelCase")]
+    RecordingStarted { from: Seconds },
+    #[serde(rename_all = "camelCase")]
+    TakeRecorded { take: Take },
+    /// A cue said out loud into the WAV at `path`, for the frontend to play
+    #[serde(rename_all = "camelCase")]
+    Spoken { path: String, length: SpokenLength },
+    #[serde(rename_all = "camelCase")]
+    SpokenLengths { lengths: Vec<SpokenLength> },
+    #[serde(rename_all = "camelCase")]
+    ComputeDevices { devices: Vec<ComputeDevice> },
+    /// Where an ML job is running: a GPU, or the CPU if `device` is absent,
+    /// in which case `fallback` says why if a GPU was wanted
+    #[serde(rename_all = "camelCase")]
+    ComputePlaced { device: Option<u32>, fallback: Option<String> },
+    /// A segment `transcribe_media` has written to the transcript
+    #[serde(rename_all = "camelCase")]
+    Transcribed { segment: TranscriptSegment },
+    /// Least certain first
+    #[serde(rename_all = "camelCase")]
+    LowConfidenceSpans { spans: Vec<LowConfidenceSpan> },
+    /// The words of an event, timed again by `realign_event`
+    #[serde(rename_all = "camelCase")]
+    Realigned { event_id: u32, words: Vec<Word> },
+    /// Sent during long jobs, like parsing a large file or recording
+    #[serde(rename_all = "camelCase")]
+    Progress { fraction: f64 },
+    #[serde(rename_all = "camelCase")]
+    Saved {
+        /// some characters couldn't be represented in the target encoding
+        lossy_encoding: bool,
+        /// what the format has no place for and was left out, of
+        /// `markers`, `regions`, `takes` and `words`; only ASS keeps them
+        unsaved: Vec<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    ConvertedToSrt {
+        report: DowngradeReport,
+        lossy_encoding: bool,
+    },
+    #[serde(rename_all = "camelCase")]
+    Events {
+        /// number of events that pass the filter
+        total: usize,
+        events: Vec<Event>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Aggregated { groups: Vec<EventGroup> },
+    /// by document id; documents without hits are left out
+    #[serde(rename_all = "camelCase")]
+    SearchResult { groups: Vec<SearchGroup> },
+    /// The format needs a framerate that the file doesn't declare; call
+    /// again with one
+    #[serde(rename_all = "camelCase")]
+    FramerateRequired {},
+    #[serde(rename_all = "camelCase")]
+    UnknownFormat {},
+    /// A path given was refused by `sandbox`
+    #[serde(rename_all = "camelCase")]
+    PathRejected { reason: String },
+    /// The command took longer than its budget; see `timing`
+    #[serde(rename_all = "camelCase")]
+    PerformanceWarning { warning: timing::PerformanceWarning },
+    #[serde(rename_all = "camelCase")]
+    InvalidId {},
+    #[serde(rename_all = "camelCase")]
+    RuntimeError { what: String },
+}
+
+/// Sends `what`, and tells whether the frontend is still listening; a
+/// closed channel, as after a reload, only gets logged
+fn try_send(channel: &Channel<SubtitleEvent>, what: SubtitleEvent) -> bool {
+    match channel.send(what) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("ChannelClosed: channel {}: {e}", channel.id());
+            false
+        }
+    }
+}
+
+fn send(channel: &Channel<SubtitleEvent>, what: SubtitleEvent) {
+    try_send(channel, what);
+}
+
+fn send_error(channel: &Channel<SubtitleEvent>, what: impl AsRef<str>) {
+    send(channel, SubtitleEvent::RuntimeError { what: what.as_ref().to_owned() });
+}
+
+/// Times the rest of the command against its budget; see `timing`
+macro_rules! timed {
+    ($name:literal, $channel:expr) => {
+        timing::Command::start($name, |warning|
+            send($channel, SubtitleEvent::PerformanceWarning { warning }))
+    };
+}
+
+fn send_invalid_id(channel: &Channel<SubtitleEvent>) {
+    send(channel, SubtitleEvent::InvalidId {});
+}
+
+fn send_done(channel: &Channel<SubtitleEvent>) {
+    send(channel, SubtitleEvent::Done {});
+}
+
+/// The file and audio stream of media session `media_id`; see
+/// `PlaybackRegistry::audio_source`. Tells the frontend why not otherwise.
+fn audio_source(
+    playbacks: &Mutex<PlaybackRegistry>, media_id: i32, channel: &Channel<SubtitleEvent>,
+) -> Option<(PathBuf, Option<usize>)> {
+    of_file(playbacks.lock().unwrap().audio_source(media_id), channel)
+}
+
+/// What was asked of a playback's file, if it has one of its own; tells the
+/// frontend why not otherwise
+fn of_file<T>(result: Result<T, NoFile>, channel: &Channel<SubtitleEvent>) -> Option<T> {
+    match result {
+        Ok(x) => Some(x),
+        Err(NoFile::InvalidId) => {
+            send_invalid_id(channel);
+            None
+        }
+        Err(NoFile::Group) => {
+            send_error(channel, "not for a group; open its files one by one");
+            None
+        }
+    }
+}
+
+/// The format is told from the text rather than the file's name, and
+/// sent as `FormatDetected`; see `parse::detect`. Parsing runs on a
+/// blocking thread, so huge files don't hold up the runtime; ASS files
+/// report `Progress` along the way.
+#[tauri::command]
+pub async fn open_subtitle(
+    app: AppHandle,
+    path: String, encoding: Option<String>, framerate: Option<f64>,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("open_subtitle", &channel);
+        let resolved = match sandbox::check_read(&app, &path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        // parsing can't be given up halfway, but stops reporting
+        let mut listening = true;
+        let progress = |fraction| if listening {
+            listening = try_send(&channel, SubtitleEvent::Progress { fraction });
+        };
+        let Opened { document, mut issues, detection, lossy_decoding } =
+            match Engine::open_subtitle(&resolved, encoding.as_deref(), framerate, progress) {
+                Ok(x) => x,
+                Err(EngineError::Unparsed(parse::Unparsed::FramerateRequired)) =>
+                    return send(&channel, SubtitleEvent::FramerateRequired {}),
+                Err(EngineError::Unparsed(parse::Unparsed::UnknownFormat)) =>
+                    return send(&channel, SubtitleEvent::UnknownFormat {}),
+                Err(e) => return send_error(&channel, e.to_string()),
+            };
+
+        let extension = resolved.extension()
+            .map(|x| x.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if !detection.format.extensions().contains(&extension.as_str()) {
+            issues.insert(0, ParseIssue {
+                line: 0,
+                message: format!("named .{extension}, but holds {}", detection.format.name()),
+            });
+        }
+        log::debug!("open_subtitle: {path}: {:?} ({}), {} events, {} issues, decoded as {}",
+            detection.format, detection.confidence, document.events.len(), issues.len(),
+            document.text_format.encoding);
+        send(&channel, SubtitleEvent::FormatDetected { detection });
+
+        sandbox::grant_referenced(&resolved, &document);
+        let mut registry = state.lock().unwrap();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        let (format, text_format) = (document.format, document.text_format.clone());
+        registry.table.insert(id, document);
+        send(&channel, SubtitleEvent::Opened { id, format, issues, text_format, lossy_decoding });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Opens an SRT file as a new ASS document; see `convert::from_srt`. The
+/// template is a style of another open document, by name or its first,
+/// and brings that document's play resolution along; without one, a
+/// default style is used.
+#[tauri::command]
+pub async fn convert_from_srt(
+    app: AppHandle,
+    path: String, encoding: Option<String>,
+    template_id: Option<i32>, template_style: Option<String>,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("convert_from_srt", &channel);
+        let resolved = match sandbox::check_read(&app, &path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        let encoding::Decoded { text: source, format: text_format, lossy } =
+            match encoding::read(&resolved, encoding.as_deref()) {
+                Ok(x) => x,
+                Err(e) => return send_error(&channel, e),
+            };
+        let (cues, issues) = srt::parse(&source);
+
+        let mut registry = state.lock().unwrap();
+        let (template, script_info) = match template_id {
+            Some(template_id) => {
+                let Some(other) =
+                    registry.table.get(&template_id) else { return send_invalid_id(&channel) };
+                let style = match &template_style {
+                    Some(name) => other.styles.iter().find(|x| x.name == *name),
+                    None => other.styles.first(),
+                };
+                let Some(style) = style else {
+                    return send_error(&channel, format!(
+                        "no style {} in document {template_id}",
+                        template_style.as_deref().unwrap_or("at all")));
+                };
+                let script_info = other.script_info.iter()
+                    .filter(|(k, _)| k.eq_ignore_ascii_case("playresx")
+                        || k.eq_ignore_ascii_case("playresy"))
+                    .cloned()
+                    .collect();
+                (style.clone(), script_info)
+            }
+            None => (
+                Style { name: "Default".to_owned(), ass_fields: Vec::new() },
+                vec![
+                    ("PlayResX".to_owned(), convert::DEFAULT_PLAY_RES.0.to_owned()),
+                    ("PlayResY".to_owned(), convert::DEFAULT_PLAY_RES.1.to_owned()),
+                ],
+            ),
+        };
+
+        let mut document = convert::from_srt(cues, &template, script_info);
+        log::debug!("convert_from_srt: {path}: {} events, {} styles, {} issues",
+            document.events.len(), document.styles.len(), issues.len());
+        document.text_format = text_format.clone();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.table.insert(id, document);
+        send(&channel, SubtitleEvent::Opened {
+            id, format: SubtitleFormat::Ass, issues, text_format,
+            lossy_decoding: lossy,
+        });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Opens an SRT file that doesn't parse cleanly, repairing what it can,
+/// and sends what was done as `Repaired`; see `subtitle::repair`. It's
+/// saved as SRT again, and comes out clean.
+#[tauri::command]
+pub async fn repair_subtitle(
+    app: AppHandle,
+    path: String, encoding: Option<String>,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("repair_subtitle", &channel);
+        let resolved = match sandbox::check_read(&app, &path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        let encoding::Decoded { text: source, format: text_format, lossy } =
+            match encoding::read(&resolved, encoding.as_deref()) {
+                Ok(x) => x,
+                Err(e) => return send_error(&channel, e),
+            };
+        let (cues, repairs) = repair::repair(&source);
+        let mut document = convert::from_srt_plain(cues);
+        document.format = SubtitleFormat::Srt;
+        log::debug!("repair_subtitle: {path}: {} events, {} kinds of repair",
+            document.events.len(), repairs.len());
+        send(&channel, SubtitleEvent::Repaired { repairs });
+
+        document.text_format = text_format.clone();
+        let mut registry = state.lock().unwrap();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.table.insert(id, document);
+        send(&channel, SubtitleEvent::Opened {
+            id, format: SubtitleFormat::Srt, issues: Vec::new(), text_format,
+            lossy_decoding: lossy,
+        });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Opens a transcript made by `transcribe_media` as a new ASS document with
+/// the default style, an event to a segment. The words of the segments and
+/// how sure the recognizer was of them are kept; see `subtitle::words`.
+#[tauri::command]
+pub async fn import_transcript(
+    app: AppHandle,
+    path: String,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("import_transcript", &channel);
+        let resolved = match sandbox::check_read(&app, &path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        let segments = match transcribe::read(&resolved) {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e),
+        };
+
+        let (cues, words): (Vec<_>, Vec<_>) = segments.into_iter()
+            .map(|x| (srt::Cue { start: x.start, end: x.end, text: x.text }, x.words))
+            .unzip();
+        let mut document = convert::from_srt_plain(cues);
+        let ids: Vec<u32> = document.events.iter().map(|x| x.id).collect();
+        for (event_id, words) in ids.into_iter().zip(words) {
+            document.add_words(event_id, words.into_iter().map(|x| Word {
+                event_id, start: x.start, end: x.end, text: x.text, confidence: x.confidence,
+            }));
+        }
+        log::debug!("import_transcript: {path}: {} events, {} words",
+            document.events.len(), document.words.len());
+
+        let text_format = document.text_format.clone();
+        let mut registry = state.lock().unwrap();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.table.insert(id, document);
+        send(&channel, SubtitleEvent::Opened {
+            id, format: SubtitleFormat::Ass, issues: Vec::new(), text_format,
+            lossy_decoding: false,
+        });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// `ms` as seconds, as timecodes are counted
+#[allow(clippy::cast_precision_loss)]
+fn milliseconds(ms: i64) -> Seconds {
+    Seconds(ms as f64 / 1000.0)
+}
+
+/// Writes the document in its own format, stored as it was read (encoding,
+/// BOM and line endings) unless `text_format` is given. The text is written
+/// anew from the document, so an unchanged file keeps what was read from
+/// it but not always its bytes: SAMI loses its own styling and markup, and
+/// ASS its comments. With `time_offset_ms`, times are written that much
+/// later, as in a program's timecode; see `media_api::set_timecode_offset`.
+#[tauri::command]
+pub fn save_subtitle(
+    app: AppHandle,
+    id: i32, path: &str, text_format: Option<TextFormat>, time_offset_ms: Option<i64>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("save_subtitle", &channel);
+    let path = match sandbox::check_write(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+    };
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let shifted = time_offset_ms.map(|x| document.shifted(milliseconds(x)));
+    let document = shifted.as_ref().unwrap_or(document);
+
+    let lossy_encoding = match Engine::save_subtitle(document, &path, text_format.as_ref()) {
+        Ok(x) => x,
+        Err(EngineError::FramerateRequired) =>
+            return send(&channel, SubtitleEvent::FramerateRequired {}),
+        Err(e) => return send_error(&channel, e.to_string()),
+    };
+    let unsaved = if document.format == SubtitleFormat::Ass {
+        Vec::new()
+    } else {
+        [
+            ("markers", document.markers.is_empty()),
+            ("regions", document.regions.is_empty()),
+            ("takes", document.takes.is_empty()),
+            ("words", document.words.is_empty()),
+        ].into_iter().filter(|x| !x.1).map(|x| x.0.to_owned()).collect()
+    };
+    send(&channel, SubtitleEvent::Saved { lossy_encoding, unsaved });
+}
+
+/// Writes the document as SubRip, downgraded as `rules` say, and reports
+/// what couldn't be carried over; the document itself is left as it is
+#[tauri::command]
+pub fn convert_to_srt(
+    app: AppHandle,
+    id: i32, path: &str, rules: SrtRules, text_format: Option<TextFormat>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("convert_to_srt", &channel);
+    let path = match sandbox::check_write(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+    };
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+
+    let shifted = (rules.time_offset_ms != 0)
+        .then(|| document.shifted(milliseconds(rules.time_offset_ms)));
+    let (cues, report) = convert::to_srt(shifted.as_ref().unwrap_or(document), rules);
+    let text_format = text_format.unwrap_or_else(|| document.text_format.clone());
+    let (buf, lossy_encoding) = match encoding::encode(&srt::write(&cues), &text_format) {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e),
+    };
+    if let Err(e) = fs::write(path, buf) {
+        return send_error(&channel, e.to_string());
+    }
+    send(&channel, SubtitleEvent::ConvertedToSrt { report, lossy_encoding });
+}
+
+#[tauri::command]
+pub fn close_subtitle(
+    id: i32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("close_subtitle", &channel);
+    let mut registry = state.lock().unwrap();
+    if registry.table.remove(&id).is_none() {
+        return send_invalid_id(&channel);
+    }
+    // closed on purpose, so there is nothing to recover
+    if let Some(journal) = registry.journals.remove(&id)
+        && let Err(e) = journal.discard()
+    {
+        log::warn!("close_subtitle: failed to remove autosave: {e}");
+    }
+    send_done(&channel);
+}
+
+/// Sends at most `limit` events starting from `offset` among those passing
+/// `filter`, in the order given by `sort` (document order if absent), so the
+/// frontend only holds the part of a large document it is showing
+#[tauri::command]
+pub fn get_events_page(
+    id: i32, offset: usize, limit: usize,
+    filter: Option<EventFilter>, sort: Option<EventSort>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("get_events_page", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let (total, events) = query::page(
+        &document.events, offset, limit, &filter.unwrap_or_default(), sort);
+    send(&channel, SubtitleEvent::Events {
+        total,
+        events: events.into_iter().cloned().collect(),
+    });
+}
+
+#[tauri::command]
+pub fn aggregate_events(
+    id: i32, group_by: GroupBy, metrics: Vec<Metric>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("aggregate_events", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let groups = query::aggregate(&document.events, group_by, &metrics);
+    send(&channel, SubtitleEvent::Aggregated { groups });
+}
+
+fn send_events_in(
+    id: i32, start: Seconds, end: Seconds,
+    state: &Mutex<SubtitleRegistry>,
+    channel: &Channel<SubtitleEvent>,
+) {
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(channel) };
+    let events: Vec<Event> = document.events_in(start, end).into_iter().cloned().collect();
+    send(channel, SubtitleEvent::Events { total: events.len(), events });
+}
+
+/// The events visible at `time`
+#[tauri::command]
+pub fn query_events_at(
+    id: i32, time: Seconds,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("query_events_at", &channel);
+    send_events_in(id, time, time, &state, &channel);
+}
+
+/// The events overlapping `[start, end)`
+#[tauri::command]
+pub fn query_events_in(
+    id: i32, start: Seconds, end: Seconds,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("query_events_in", &channel);
+    send_events_in(id, start, end, &state, &channel);
+}
+
+/// Looks for `query` in every open document; see `search::search`. Each
+/// group has no more than `limit` hits.
+#[tauri::command]
+pub fn global_search(
+    query: String, limit: usize,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("global_search", &channel);
+    let terms = search::terms(&query);
+    if terms.is_empty() {
+        return send(&channel, SubtitleEvent::SearchResult { groups: Vec::new() });
+    }
+    let registry = state.lock().unwrap();
+    let mut groups: Vec<SearchGroup> = registry.table.iter()
+        .map(|(id, document)| search::search(*id, document, &terms, limit))
+        .filter(|x| x.total > 0)
+        .collect();
+    groups.sort_by_key(|x| x.document_id);
+    send(&channel, SubtitleEvent::SearchResult { groups });
+}
+
+/// Applies the edits in order, stopping at the first one that fails. Those
+/// applied are recorded in the autosave journal if there is one.
+#[tauri::command]
+pub fn edit_subtitle(
+    id: i32, edits: Vec<Edit>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("edit_subtitle", &channel);
+    let mut registry = state.lock().unwrap();
+    let SubtitleRegistry { table, journals, .. } = &mut *registry;
+    let Some(document) = table.get_mut(&id) else { return send_invalid_id(&channel) };
+
+    let mut applied = 0;
+    let mut error = None;
+    for edit in &edits {
+        if let Err(e) = document.apply(edit) {
+            error = Some(e);
+            break;
+        }
+        applied += 1;
+    }
+    if let Some(journal) = journals.get_mut(&id)
+        && let Err(e) = journal.append(&edits[..applied], document)
+    {
+        return send_error(&channel, format!("autosave failed: {e}"));
+    }
+    match error {
+        Some(e) => send_error(&channel, e),
+        None => send_done(&channel),
+    }
+}
+
+/// Starts journaling the document's edits to `path` and `path.journal`
+#[tauri::command]
+pub fn enable_autosave(
+    app: AppHandle,
+    id: i32, path: &str,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("enable_autosave", &channel);
+    let path = match sandbox::check_autosave(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+    };
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let journal = match Journal::create(&path, document) {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e.to_string()),
+    };
+    registry.journals.insert(id, journal);
+    send_done(&channel);
+}
+
+/// Opens the document an autosave at `path` describes, and continues
+/// autosaving to the same place
+#[tauri::command]
+pub fn recover_subtitle(
+    app: AppHandle,
+    path: &str,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("recover_subtitle", &channel);
+    // autosaving goes on in the same place
+    let path = match sandbox::check_autosave(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+    };
+    let journal::Recovered { document, replayed, truncated } =
+        match journal::recover(&path) {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e.to_string()),
+        };
+    let journal = match Journal::create(&path, &document) {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e.to_string()),
+    };
+
+    let mut registry = state.lock().unwrap();
+    let id = registry.next_id;
+    registry.next_id += 1;
+    let format = document.format;
+    let text_format = document.text_format.clone();
+    registry.table.insert(id, document);
+    registry.journals.insert(id, journal);
+    send(&channel, SubtitleEvent::Recovered { id, format, text_format, replayed, truncated });
+}
+
+/// Checks that events are placed within the title-safe area of a video of
+/// the given size
+#[tauri::command]
+pub fn check_title_safe(
+    id: i32, width: u32, height: u32, standard: BroadcastStandard,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("check_title_safe", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let issues = qc::check_title_safe(
+        document, f64::from(width), f64::from(height), standard);
+    send(&channel, SubtitleEvent::QcResult { issues });
+}
+
+/// Checks events against intervals where `detect_onscreen_text` found
+/// burned-in text at the bottom of the video
+#[tauri::command]
+pub fn check_onscreen_text(
+    id: i32, intervals: Vec<(Seconds, Seconds)>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("check_onscreen_text", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let issues = qc::check_onscreen_text(document, &intervals);
+    send(&channel, SubtitleEvent::QcResult { issues });
+}
+
+/// Checks event edges against the keyframes of the video of playback
+/// `media_id`, as far as they are known: those of its seek index and those
+/// its video sampler has decoded; see `qc::check_keyframe_distance`
+#[tauri::command]
+pub fn check_keyframe_distance(
+    id: i32, media_id: i32, frames: u32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    playbacks: State<Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("check_keyframe_distance", &channel);
+    let keyframes = playbacks.lock().unwrap().keyframes(media_id);
+    let Some(keyframes) = of_file(keyframes, &channel) else { return };
+    let Some((keyframes, framerate)) = keyframes else {
+        return send_error(&channel, "no video to take keyframes from");
+    };
+    if keyframes.is_empty() {
+        return send_error(&channel,
+            "no keyframes known yet; build a seek index or sample the video first");
+    }
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let issues = qc::check_keyframe_distance(document, &keyframes, framerate, frames);
+    send(&channel, SubtitleEvent::QcResult { issues });
+}
+
+/// Checks events against damage found in the source of a playback, as
+/// sent by `get_decode_damage`; see `qc::check_source_damage`
+#[tauri::command]
+pub fn check_source_damage(
+    id: i32, spans: Vec<DamageSpan>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("check_source_damage", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let issues = qc::check_source_damage(document, &spans);
+    send(&channel, SubtitleEvent::QcResult { issues });
+}
+
+/// Flags near-duplicate events; see `qc::find_duplicates`
+#[tauri::command]
+pub fn find_duplicate_events(
+    id: i32, threshold: f64,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("find_duplicate_events", &channel);
+    if !(0.0..=1.0).contains(&threshold) {
+        return send_error(&channel, format!("invalid threshold: {threshold}"));
+    }
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let issues = qc::find_duplicates(document, threshold);
+    send(&channel, SubtitleEvent::QcResult { issues });
+}
+
+/// Removes the copies `find_duplicate_events` would flag, keeping the
+/// better-timed event of each group, as edits that go into the journal
+#[tauri::command]
+pub fn dedupe_events(
+    id: i32, threshold: f64,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("dedupe_events", &channel);
+    if !(0.0..=1.0).contains(&threshold) {
+        return send_error(&channel, format!("invalid threshold: {threshold}"));
+    }
+    let mut registry = state.lock().unwrap();
+    let SubtitleRegistry { table, journals, .. } = &mut *registry;
+    let Some(document) = table.get_mut(&id) else { return send_invalid_id(&channel) };
+
+    let edits: Vec<Edit> = qc::find_duplicates(document, threshold).into_iter()
+        .filter_map(|x| x.fix)
+        .collect();
+    for edit in &edits {
+        // the ids were just found in the document
+        let _ = document.apply(edit);
+    }
+    if let Some(journal) = journals.get_mut(&id)
+        && let Err(e) = journal.append(&edits, document)
+    {
+        return send_error(&channel, format!("autosave failed: {e}"));
+    }
+    let removed = edits.iter()
+        .filter_map(|x| match x {
+            Edit::Remove { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    send(&channel, SubtitleEvent::Deduplicated { removed });
+}
+
+/// Proposes how to split event `event_id` into pieces that keep to
+/// `policy`; see `split::split`. Nothing is changed until the pieces come
+/// back as an edit.
+#[tauri::command]
+pub fn auto_split_event(
+    id: i32, event_id: u32, policy: SplitPolicy, word_starts: Option<Vec<Seconds>>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("auto_split_event", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let Some(event) = document.events.iter().find(|x| x.id == event_id) else {
+        return send_error(&channel, format!("no event with id {event_id}"));
+    };
+    match split::split(event, &policy, word_starts.as_deref()) {
+        Ok(pieces) => send(&channel, SubtitleEvent::SplitProposed { event_id, pieces }),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// Merges events `ids` into the first of them, as one edit that goes into
+/// the journal; see `merge::merge`
+#[tauri::command]
+pub fn merge_events(
+    id: i32, ids: Vec<u32>, policy: MergePolicy,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("merge_events", &channel);
+    let mut registry = state.lock().unwrap();
+    let SubtitleRegistry { table, journals, .. } = &mut *registry;
+    let Some(document) = table.get_mut(&id) else { return send_invalid_id(&channel) };
+
+    let (edit, undo) = match merge::merge(document, &ids, &policy) {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e),
+    };
+    // what the merged event will be
+    let event_id = document.events.iter().find(|x| ids.contains(&x.id)).unwrap().id;
+    if let Err(e) = document.apply(&edit) {
+        return send_error(&channel, e);
+    }
+    if let Some(journal) = journals.get_mut(&id)
+        && let Err(e) = journal.append(std::slice::from_ref(&edit), document)
+    {
+        return send_error(&channel, format!("autosave failed: {e}"));
+    }
+    send(&channel, SubtitleEvent::Merged { event_id, undo });
+}
+
+#[tauri::command]
+pub fn set_positioning_policy(
+    id: i32, policy: PositioningPolicy, letterbox: Option<Letterbox>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("set_positioning_policy", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    let edit = Edit::SetPositioning { policy, letterbox };
+    if let Err(e) = document.apply(&edit) {
+        return send_error(&channel, e);
+    }
+    match registry.journal(id, &[edit]) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+#[tauri::command]
+pub fn get_positioning(
+    id: i32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("get_positioning", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let (_, res_y) = ass::play_res(document);
+    let policy = document.positioning;
+    let margin = |top| document.letterbox.as_ref()
+        .and_then(|x| policy.margin(x, res_y, top));
+    send(&channel, SubtitleEvent::Positioning {
+        policy,
+        letterbox: document.letterbox,
+        margin_top: margin(true),
+        margin_bottom: margin(false),
+    });
+}
+
+/// Checks margins against the document's positioning policy; fixes come with
+/// each issue. Nothing is reported until a letterbox has been set.
+#[tauri::command]
+pub fn check_positioning(
+    id: i32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("check_positioning", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let issues = match &document.letterbox {
+        Some(letterbox) => qc::check_positioning(document, document.positioning, letterbox),
+        None => Vec::new(),
+    };
+    send(&channel, SubtitleEvent::QcResult { issues });
+}
+
+/// Markers `ids` as they are now, for the journal
+fn marker_edits(document: &Document, ids: &[u32]) -> Vec<Edit> {
+    document.markers.iter()
+        .filter(|x| ids.contains(&x.id))
+        .map(|x| Edit::SetMarker { marker: x.clone() })
+        .collect()
+}
+
+#[tauri::command]
+pub fn get_markers(
+    id: i32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("get_markers", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    send(&channel, SubtitleEvent::Markers { markers: document.markers.clone() });
+}
+
+#[tauri::command]
+pub fn add_marker(
+    id: i32, time: Seconds, label: String, color: String,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("add_marker", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    let marker_id = match document.add_marker(time, label, color) {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e),
+    };
+    let edits = marker_edits(document, &[marker_id]);
+    match registry.journal(id, &edits) {
+        Ok(()) => send(&channel, SubtitleEvent::MarkerAdded { marker_id }),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// Fields that are absent are left as they are
+#[tauri::command]
+pub fn update_marker(
+    id: i32, marker_id: u32,
+    time: Option<Seconds>, label: Option<String>, color: Option<String>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("update_marker", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    if let Err(e) = document.update_marker(marker_id, time, label, color) {
+        return send_error(&channel, e);
+    }
+    let edits = marker_edits(document, &[marker_id]);
+    match registry.journal(id, &edits) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+#[tauri::command]
+pub fn remove_marker(
+    id: i32, marker_id: u32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("remove_marker", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    if let Err(e) = document.remove_marker(marker_id) {
+        return send_error(&channel, e);
+    }
+    match registry.journal(id, &[Edit::RemoveMarker { id: marker_id }]) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// The first marker after `from`, if any
+#[tauri::command]
+pub fn next_marker(
+    id: i32, from: Seconds,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("next_marker", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    send(&channel, SubtitleEvent::NextMarker { marker: document.next_marker(from).cloned() });
+}
+
+/// Writes the markers as Matroska chapters, labels becoming chapter titles
+#[tauri::command]
+pub fn export_markers(
+    app: AppHandle,
+    id: i32, path: &str, language: Option<String>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("export_markers", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let chapters: Vec<Chapter> = document.markers.iter()
+        .map(|x| Chapter { start: x.time, title: x.label.clone() })
+        .collect();
+    write_chapters(&app, path, &chapters, language.as_deref(), &channel);
+}
+
+/// Writes chapters that aren't markers, such as those from `suggest_chapters`
+#[tauri::command]
+pub fn export_chapters(
+    app: AppHandle,
+    chapters: Vec<Chapter>, path: &str, language: Option<String>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("export_chapters", &channel);
+    write_chapters(&app, path, &chapters, language.as_deref(), &channel);
+}
+
+fn write_chapters(
+    app: &AppHandle, path: &str, chapters: &[Chapter], language: Option<&str>,
+    channel: &Channel<SubtitleEvent>,
+) {
+    let path = match sandbox::check_write(app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(channel, SubtitleEvent::PathRejected { reason }),
+    };
+    let xml = chapters::to_xml(chapters, language.unwrap_or("und"));
+    if let Err(e) = fs::write(path, xml) {
+        return send_error(channel, e.to_string());
+    }
+    send_done(channel);
+}
+
+/// Adds a marker for every chapter in a Matroska chapter file
+#[tauri::command]
+pub fn import_markers(
+    app: AppHandle,
+    id: i32, path: &str, color: String,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("import_markers", &channel);
+    let path = match sandbox::check_read(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+    };
+    let chapters = match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|x| chapters::from_xml(&x))
+    {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e),
+    };
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    let added = chapters.into_iter()
+        .map(|x| document.add_marker(x.start, x.title, color.clone()))
+        .collect::<Result<Vec<u32>, _>>();
+    // the color is the same for all, so either all are added or none
+    let added = match added {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e),
+    };
+    let markers = document.markers.clone();
+    let edits = marker_edits(document, &added);
+    if let Err(e) = registry.journal(id, &edits) {
+        return send_error(&channel, e);
+    }
+    send(&channel, SubtitleEvent::Markers { markers });
+}
+
+#[tauri::command]
+pub fn get_regions(
+    id: i32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("get_regions", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    send(&channel, SubtitleEvent::Regions { regions: document.regions.clone() });
+}
+
+#[tauri::command]
+pub fn add_region(
+    id: i32, start: Seconds, end: Seconds, label: String,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("add_region", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    let region_id = match document.add_region(start, end, label) {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e),
+    };
+    let edits = region_edits(document, &[region_id]);
+    match registry.journal(id, &edits) {
+        Ok(()) => send(&channel, SubtitleEvent::RegionAdded { region_id }),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// Fields that are absent are left as they are
+#[tauri::command]
+pub fn update_region(
+    id: i32, region_id: u32,
+    start: Option<Seconds>, end: Option<Seconds>, label: Option<String>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("update_region", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    if let Err(e) = document.update_region(region_id, start, end, label) {
+        return send_error(&channel, e);
+    }
+    let edits = region_edits(document, &[region_id]);
+    match registry.journal(id, &edits) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+#[tauri::command]
+pub fn remove_region(
+    id: i32, region_id: u32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("remove_region", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    if let Err(e) = document.remove_region(region_id) {
+        return send_error(&channel, e);
+    }
+    match registry.journal(id, &[Edit::RemoveRegion { id: region_id }]) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// Regions `ids` as they are now, for the journal
+fn region_edits(document: &Document, ids: &[u32]) -> Vec<Edit> {
+    document.regions.iter()
+        .filter(|x| ids.contains(&x.id))
+        .map(|x| Edit::SetRegion { region: x.clone() })
+        .collect()
+}
+
+/// Writes the regions as an Audacity label track
+#[tauri::command]
+pub fn export_regions(
+    app: AppHandle,
+    id: i32, path: &str,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("export_regions", &channel);
+    let path = match sandbox::check_write(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+    };
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    if let Err(e) = fs::write(path, regions::to_audacity(&document.regions)) {
+        return send_error(&channel, e.to_string());
+    }
+    send_done(&channel);
+}
+
+/// Adds a region for every label in an Audacity label track
+#[tauri::command]
+pub fn import_regions(
+    app: AppHandle,
+    id: i32, path: &str,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("import_regions", &channel);
+    let path = match sandbox::check_read(&app, path) {
+        Ok(x) => x,
+        Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+    };
+    let labels = match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|x| regions::from_audacity(&x))
+    {
+        Ok(x) => x,
+        Err(e) => return send_error(&channel, e),
+    };
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    // already checked by `from_audacity`
+    let added: Vec<u32> = labels.into_iter()
+        .filter_map(|(start, end, label)| document.add_region(start, end, label).ok())
+        .collect();
+    let regions = document.regions.clone();
+    let edits = region_edits(document, &added);
+    if let Err(e) = registry.journal(id, &edits) {
+        return send_error(&channel, e);
+    }
+    send(&channel, SubtitleEvent::Regions { regions });
+}
+
+/// Recording starts this long before the event, so the actor hears the cue
+const TAKE_PRE_ROLL: f64 = 1.0;
+/// and runs on this long after it
+const TAKE_POST_ROLL: f64 = 0.5;
+
+/// Every take of event `event_id` as it is now, for the journal
+fn takes_of(document: &Document, event_id: u32) -> Vec<Edit> {
+    document.takes.iter()
+        .filter(|x| x.event_id == event_id)
+        .map(|x| Edit::SetTake { take: x.clone() })
+        .collect()
+}
+
+/// The takes of one event, or of all of them
+#[tauri::command]
+pub fn get_takes(
+    id: i32, event_id: Option<u32>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("get_takes", &channel);
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let takes = document.takes.iter()
+        .filter(|x| event_id.is_none_or(|id| x.event_id == id))
+        .cloned()
+        .collect();
+    send(&channel, SubtitleEvent::Takes { takes });
+}
+
+/// Records a take of event `event_id` from `device` into a new file in
+/// `dir`, from `TAKE_PRE_ROLL` before the event to `TAKE_POST_ROLL` after.
+/// `RecordingStarted` tells when to start playing, so that the actor speaks
+/// along with the picture; the take becomes the event's selected one.
+#[tauri::command]
+pub async fn record_take(
+    app: AppHandle,
+    id: i32, event_id: u32, device: InputDevice, dir: String,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("record_take", &channel);
+        let (start, end) = {
+            let registry = state.lock().unwrap();
+            let Some(document) =
+                registry.table.get(&id) else { return send_invalid_id(&channel) };
+            let Some(event) = document.events.iter().find(|x| x.id == event_id) else {
+                return send_error(&channel, format!("no event with id {event_id}"));
+            };
+            (event.start, event.end)
+        };
+        let from = Seconds((start.0 - TAKE_PRE_ROLL).max(0.0));
+        let length = Seconds(end.0 + TAKE_POST_ROLL - from.0);
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |x| x.as_millis());
+        let path = Path::new(&dir).join(format!("take-{event_id}-{stamp}.wav"));
+        let path = match sandbox::check_write(&app, &path.to_string_lossy()) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+
+        // the lock isn't held while recording, so the document may change
+        // meanwhile; the take is added to whatever the event has become
+        let result = record::record(&device, &path, length,
+            || send(&channel, SubtitleEvent::RecordingStarted { from }),
+            |fraction| try_send(&channel, SubtitleEvent::Progress { fraction }));
+        if let Err(e) = result {
+            return send_error(&channel, e.to_string());
+        }
+        let mut registry = state.lock().unwrap();
+        let Some(document) =
+            registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+        let path = path.to_string_lossy().into_owned();
+        let offset = Seconds(from.0 - start.0);
+        let take_id = match document.add_take(event_id, path, offset, length, true) {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e),
+        };
+        let take = document.takes.iter().find(|x| x.id == take_id).unwrap().clone();
+        // the others of the event were unselected
+        let edits = takes_of(document, event_id);
+        if let Err(e) = registry.journal(id, &edits) {
+            return send_error(&channel, e);
+        }
+        send(&channel, SubtitleEvent::TakeRecorded { take });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+#[tauri::command]
+pub fn select_take(
+    id: i32, take_id: u32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("select_take", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    if let Err(e) = document.select_take(take_id) {
+        return send_error(&channel, e);
+    }
+    let event_id = document.takes.iter().find(|x| x.id == take_id).unwrap().event_id;
+    let edits = takes_of(document, event_id);
+    match registry.journal(id, &edits) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// Forgets a take, and deletes its recording if `delete_file`
+#[tauri::command]
+pub fn remove_take(
+    app: AppHandle,
+    id: i32, take_id: u32, delete_file: bool,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("remove_take", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    let Some(take) = document.takes.iter().find(|x| x.id == take_id) else {
+        return send_error(&channel, format!("no take with id {take_id}"));
+    };
+    if delete_file {
+        let path = match sandbox::check_write(&app, &take.path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        if let Err(e) = fs::remove_file(path) {
+            return send_error(&channel, e.to_string());
+        }
+    }
+    if let Err(e) = document.remove_take(take_id) {
+        return send_error(&channel, e);
+    }
+    match registry.journal(id, &[Edit::RemoveTake { id: take_id }]) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// Trims and gain; fields that are absent are left as they are
+#[tauri::command]
+pub fn update_take(
+    id: i32, take_id: u32,
+    trim_start: Option<Seconds>, trim_end: Option<Seconds>, gain: Option<f64>,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("update_take", &channel);
+    let mut registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get_mut(&id) else { return send_invalid_id(&channel) };
+    if let Err(e) = document.update_take(take_id, trim_start, trim_end, gain) {
+        return send_error(&channel, e);
+    }
+    let take = document.takes.iter().find(|x| x.id == take_id).unwrap().clone();
+    match registry.journal(id, &[Edit::SetTake { take }]) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// The selected takes of document `id` as clips for `record`, their files
+/// checked by `sandbox`
+fn voice_over_clips(
+    app: &AppHandle, id: i32, state: &Mutex<SubtitleRegistry>,
+    channel: &Channel<SubtitleEvent>,
+) -> Option<Vec<record::Clip>> {
+    let registry = state.lock().unwrap();
+    let Some(document) = registry.table.get(&id) else {
+        send_invalid_id(channel);
+        return None;
+    };
+    let mut clips = Vec::new();
+    for (start, take) in document.voice_over() {
+        match sandbox::check_read(app, &take.path) {
+            Ok(path) => clips.push(record::Clip {
+                path, start,
+                skip: take.trim_start,
+                length: take.kept(),
+                gain: take.gain,
+            }),
+            Err(reason) => {
+                send(channel, SubtitleEvent::PathRejected { reason });
+                return None;
+            }
+        }
+    }
+    Some(clips)
+}
+
+/// Mixes the selected take of every event into one WAV at `path`, each at
+/// its place on the timeline, as a rough voice-over track for table reads
+#[tauri::command]
+pub async fn export_voice_over(
+    app: AppHandle,
+    id: i32, path: String,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("export_voice_over", &channel);
+        let output = match sandbox::check_write(&app, &path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        let Some(clips) = voice_over_clips(&app, id, &state, &channel) else { return };
+        match record::mix(&clips, &output,
+            |fraction| try_send(&channel, SubtitleEvent::Progress { fraction }))
+        {
+            Ok(()) => send_done(&channel),
+            Err(e) => send_error(&channel, e.to_string()),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Renders the selected takes of document `id` over the audio of media
+/// session `media_id`, which is turned down under them, into a stereo WAV
+/// at `out_path`
+#[tauri::command]
+pub async fn bounce_voiceover(
+    app: AppHandle,
+    id: i32, media_id: i32, out_path: String,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    playbacks: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let Some((source, stream)) = audio_source(&playbacks, media_id, &channel) else {
+        return Ok(());
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("bounce_voiceover", &channel);
+        let output = match sandbox::check_write(&app, &out_path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        let Some(clips) = voice_over_clips(&app, id, &state, &channel) else { return };
+        match record::bounce(&clips, &source, stream, &output,
+            |fraction| try_send(&channel, SubtitleEvent::Progress { fraction }))
+        {
+            Ok(()) => send_done(&channel),
+            Err(e) => send_error(&channel, e.to_string()),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Directory `name` in the cache, created if it isn't there yet
+fn cache_dir(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    app.path().app_cache_dir().map(|x| x.join(name))
+        .map_err(|e| e.to_string())
+        .and_then(|dir| fs::create_dir_all(&dir).map(|()| dir).map_err(|e| e.to_string()))
+}
+
+/// The program chosen for `tool`, its `model` as checked by `sandbox`, and
+/// the directory `cache` in the cache that their output is written to
+fn tool_paths(
+    app: &AppHandle, tool: Tool, model: &str, cache: &str, channel: &Channel<SubtitleEvent>,
+) -> Option<(PathBuf, PathBuf, PathBuf)> {
+    let program = match tools::path(app, tool) {
+        Ok(x) => x,
+        Err(e) => {
+            send_error(channel, e);
+            return None;
+        }
+    };
+    let model = match sandbox::check_read(app, model) {
+        Ok(x) => x,
+        Err(reason) => {
+            send(channel, SubtitleEvent::PathRejected { reason });
+            return None;
+        }
+    };
+    match cache_dir(app, cache) {
+        Ok(dir) => Some((program, model, dir)),
+        Err(e) => {
+            send_error(channel, e);
+            None
+        }
+    }
+}
+
+/// Says event `event_id` with `voice`, so that a translator can hear it
+/// against the original; `Spoken` gives the file and how the length
+/// compares with the event's
+#[tauri::command]
+pub async fn speak_cue(
+    app: AppHandle,
+    id: i32, event_id: u32, voice: Voice,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("speak_cue", &channel);
+        let (text, available) = {
+            let registry = state.lock().unwrap();
+            let Some(document) =
+                registry.table.get(&id) else { return send_invalid_id(&channel) };
+            let Some(event) = document.events.iter().find(|x| x.id == event_id) else {
+                return send_error(&channel, format!("no event with id {event_id}"));
+            };
+            (tts::spoken_text(&event.text), Seconds(event.end.0 - event.start.0))
+        };
+        if text.is_empty() {
+            return send_error(&channel, format!("event {event_id} has nothing to say"));
+        }
+        let Some((program, model, dir)) =
+            tool_paths(&app, Tool::Piper, &voice.model, "tts", &channel) else { return };
+        let path = dir.join(format!("{id}-{event_id}.wav"));
+        match tts::speak(&program, &model, &voice, &text, &path) {
+            Ok(spoken) => send(&channel, SubtitleEvent::Spoken {
+                path: path.to_string_lossy().into_owned(),
+                length: SpokenLength::new(event_id, spoken, available),
+            }),
+            Err(e) => send_error(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Says every event of document `id` that isn't a comment with `voice`, or
+/// those in `event_ids` if given, and reports in `SpokenLengths` how each
+/// compares with the time it's on screen. Nothing is kept of the speech.
+#[tauri::command]
+pub async fn check_spoken_lengths(
+    app: AppHandle,
+    id: i32, voice: Voice, event_ids: Option<Vec<u32>>,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("check_spoken_lengths", &channel);
+        let cues: Vec<(u32, String, Seconds)> = {
+            let registry = state.lock().unwrap();
+            let Some(document) =
+                registry.table.get(&id) else { return send_invalid_id(&channel) };
+            document.events.iter()
+                .filter(|x| !x.is_comment)
+                .filter(|x| event_ids.as_ref().is_none_or(|ids| ids.contains(&x.id)))
+                .map(|x| (x.id, tts::spoken_text(&x.text), Seconds(x.end.0 - x.start.0)))
+                .filter(|(_, text, _)| !text.is_empty())
+                .collect()
+        };
+        let Some((program, model, dir)) =
+            tool_paths(&app, Tool::Piper, &voice.model, "tts", &channel) else { return };
+        let lines: Vec<(String, PathBuf)> = cues.iter()
+            .map(|(event_id, text, _)|
+                (text.clone(), dir.join(format!("{id}-check-{event_id}.wav"))))
+            .collect();
+        let result = tts::speak_all(&program, &model, &voice, &lines,
+            |fraction| try_send(&channel, SubtitleEvent::Progress { fraction }));
+        for (_, path) in &lines {
+            let _ = fs::remove_file(path);
+        }
+        match result {
+            Ok(spoken) => send(&channel, SubtitleEvent::SpokenLengths {
+                lengths: cues.iter().zip(spoken)
+                    .map(|((event_id, _, available), spoken)|
+                        SpokenLength::new(*event_id, spoken, *available))
+                    .collect(),
+            }),
+            Err(e) => send_error(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// The devices ML jobs can run on, with how much GPU memory is free and
+/// reserved by jobs of ours; see `compute`
+#[tauri::command]
+pub async fn list_compute_devices(channel: Channel<SubtitleEvent>) -> Result<(), ()> {
+    async_runtime::spawn_blocking(move || {
+        send(&channel, SubtitleEvent::ComputeDevices { devices: compute::list_devices() });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Keeps the API key for a transcription service at `endpoint` in the
+/// system's keyring; see `transcribe::cloud`
+#[tauri::command]
+pub fn set_asr_key(endpoint: String, key: String, channel: Channel<SubtitleEvent>) {
+    match cloud::set_key(&endpoint, &key) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+#[tauri::command]
+pub fn clear_asr_key(endpoint: String, channel: Channel<SubtitleEvent>) {
+    match cloud::clear_key(&endpoint) {
+        Ok(()) => send_done(&channel),
+        Err(e) => send_error(&channel, e),
+    }
+}
+
+/// The stretches of words in a document made from a transcript that the
+/// recognizer was less sure of than `threshold`, from 0 to 1, least sure
+/// first, to be reviewed before the rest
+#[tauri::command]
+pub fn get_low_confidence_spans(
+    subtitle_id: i32, threshold: f32,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("get_low_confidence_spans", &channel);
+    if !(0.0..=1.0).contains(&threshold) {
+        return send_error(&channel, format!("threshold out of range: {threshold}"));
+    }
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&subtitle_id) else { return send_invalid_id(&channel) };
+    send(&channel, SubtitleEvent::LowConfidenceSpans {
+        spans: document.low_confidence_spans(threshold),
+    });
+}
+
+/// Times the words of event `event_id` of document `subtitle_id` again
+/// after its text has been edited: whisper, set up by `transcriber`, is run
+/// on the audio of media session `media_id` under just that event, and the
+/// new text matched to what it heard; see `subtitle::align`. The new words
+/// replace the event's old ones and come in `Realigned`.
+#[tauri::command]
+pub async fn realign_event(
+    app: AppHandle,
+    media_id: i32, subtitle_id: i32, event_id: u32, transcriber: transcribe::Transcriber,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    playbacks: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let Some((source, stream)) = audio_source(&playbacks, media_id, &channel) else {
+        return Ok(());
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("realign_event", &channel);
+        let (event, old) = {
+            let registry = state.lock().unwrap();
+            let Some(document) =
+                registry.table.get(&subtitle_id) else { return send_invalid_id(&channel) };
+            let Some(event) = document.events.iter().find(|x| x.id == event_id) else {
+                return send_error(&channel, format!("no event with id {event_id}"));
+            };
+            let old: Vec<Word> = document.words_of(event_id).into_iter().cloned().collect();
+            (event.clone(), old)
+        };
+        let Some((program, model, dir)) = tool_paths(&app, Tool::Whisper,
+            &transcriber.model, "realign", &channel) else { return };
+        let need = match compute::estimate_memory(&model) {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e),
+        };
+        let (reservation, fallback) = compute::reserve(transcriber.device, need);
+        send(&channel, SubtitleEvent::ComputePlaced { device: reservation.device, fallback });
+        let whisper = Whisper { program, model, transcriber, device: reservation.uuid.clone() };
+
+        let audio = dir.join(format!("{subtitle_id}-{event_id}.wav"));
+        if let Err(e) = record::export_mono_window(
+            &source, stream, (event.start, event.end), &audio, transcribe::SAMPLE_RATE)
+        {
+            return send_error(&channel, e.to_string());
+        }
+        let heard = whisper.words_in(&audio, &align::split_words(&event.text).join(" "));
+        let _ = fs::remove_file(&audio);
+        drop(reservation);
+        let heard: Vec<Word> = match heard {
+            Ok(x) => x.into_iter().map(|x| Word {
+                event_id,
+                start: Seconds(event.start.0 + x.start.0),
+                end: Seconds(event.start.0 + x.end.0),
+                text: x.text,
+                confidence: x.confidence,
+            }).collect(),
+            Err(e) => return send_error(&channel, e),
+        };
+        let waveform = match audio::classified_window(
+            &source, stream, (event.start, event.end), audio_class::FRAME_RATE)
+        {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e.to_string()),
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let speech = audio_class::speech_segments(&waveform.classes, waveform.start_time,
+            waveform.sample_per_second as f64, align::MIN_PAUSE);
+        let words = align::align(&event, &heard, &speech, &old.iter().collect::<Vec<_>>());
+
+        let mut registry = state.lock().unwrap();
+        let Some(document) =
+            registry.table.get_mut(&subtitle_id) else { return send_invalid_id(&channel) };
+        // edited again while the audio was read; that edit realigns it
+        let current = document.events.iter().find(|x| x.id == event_id);
+        if current.is_none_or(|x| x.text != event.text || x.start != event.start || x.end != event.end) {
+            return send_error(&channel, format!("event {event_id} changed while being realigned"));
+        }
+        let edit = Edit::SetWords { event_id, words: words.clone() };
+        if let Err(e) = document.apply(&edit) {
+            return send_error(&channel, e);
+        }
+        if let Err(e) = registry.journal(subtitle_id, &[edit]) {
+            return send_error(&channel, e);
+        }
+        send(&channel, SubtitleEvent::Realigned { event_id, words });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Part of the progress that goes to getting the audio out to transcribe
+const TRANSCRIBE_EXTRACT_SHARE: f64 = 0.1;
+
+/// Transcribes the audio of media session `media_id` with `asr` into a
+/// transcript at `out_path`, SubRip or JSON Lines by its extension; see
+/// `transcribe`. Each segment is written as soon as it's done and sent in
+/// `Transcribed`, so the start can be worked on while the rest is still
+/// coming. With `resume`, a transcript already there is gone on with from
+/// where it ends, as after a crash. Whisper runs on the device it asks for
+/// if the model fits, and `ComputePlaced` says where.
+#[tauri::command]
+pub async fn transcribe_media(
+    app: AppHandle,
+    media_id: i32, asr: AsrConfig, out_path: String, resume: bool,
+    playbacks: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let Some((source, stream)) = audio_source(&playbacks, media_id, &channel) else {
+        return Ok(());
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("transcribe_media", &channel);
+        let output = match sandbox::check_write(&app, &out_path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        let (provider, _reservation): (Box<dyn AsrProvider>, _) = match asr {
+            AsrConfig::Whisper(transcriber) => {
+                let Some((program, model, _)) = tool_paths(&app, Tool::Whisper,
+                    &transcriber.model, "transcribe", &channel) else { return };
+                let need = match compute::estimate_memory(&model) {
+                    Ok(x) => x,
+                    Err(e) => return send_error(&channel, e),
+                };
+                let (reservation, fallback) = compute::reserve(transcriber.device, need);
+                send(&channel, SubtitleEvent::ComputePlaced { device: reservation.device, fallback });
+                let device = reservation.uuid.clone();
+                (Box::new(Whisper { program, model, transcriber, device }), Some(reservation))
+            }
+            AsrConfig::Cloud(config) => match Cloud::new(config) {
+                Ok(x) => (Box::new(x), None),
+                Err(e) => return send_error(&channel, e),
+            },
+        };
+        let mut writer = match TranscriptWriter::open(&output, resume) {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e),
+        };
+
+        let dir = match cache_dir(&app, "transcribe") {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e),
+        };
+        let audio = dir.join(format!("{media_id}.wav"));
+        let duration = match record::export_mono(&source, stream, &audio, transcribe::SAMPLE_RATE,
+            |fraction| try_send(&channel, SubtitleEvent::Progress {
+                fraction: fraction * TRANSCRIBE_EXTRACT_SHARE,
+            }))
+        {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e.to_string()),
+        };
+        let mut written = Ok(());
+        let result = provider.transcribe(&audio, writer.end(), &mut |segment| {
+            written = writer.append(&segment);
+            if written.is_err() {
+                return false;
+            }
+            let fraction = TRANSCRIBE_EXTRACT_SHARE
+                + (1.0 - TRANSCRIBE_EXTRACT_SHARE) * (segment.end.0 / duration.0).min(1.0);
+            send(&channel, SubtitleEvent::Transcribed { segment });
+            try_send(&channel, SubtitleEvent::Progress { fraction })
+        });
+        let _ = fs::remove_file(&audio);
+        match written.and(result) {
+            Ok(()) => send_done(&channel),
+            Err(e) => send_error(&channel, e),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Proposes moving and stretching every event of document `id` by
+/// `retime`, and its markers, regions and words, such as the correction
+/// `measure_drift` found; see `retime::suggest`
+#[tauri::command]
+pub fn suggest_retime(
+    id: i32, retime: Retime,
+    state: State<Arc<Mutex<SubtitleRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) {
+    let _timing = timed!("suggest_retime", &channel);
+    if !(retime.factor.is_finite() && retime.factor > 0.0 && retime.offset.0.is_finite()) {
+        return send_error(&channel, format!("invalid retiming: {retime:?}"));
+    }
+    let registry = state.lock().unwrap();
+    let Some(document) =
+        registry.table.get(&id) else { return send_invalid_id(&channel) };
+    let edits = retime::suggest(document, retime);
+    send(&channel, SubtitleEvent::RetimeSuggested { edits });
+}
+
+/// Moves media session `media_id` over to the file at `path`, another
+/// release of the same programme, and document `id` along with it; see
+/// `conform::compare`. If the two are alike enough, the document is retimed
+/// as `retime::suggest` would, journaled, and the session is reopened on
+/// the new file as it was, under the same id; otherwise only the report is
+/// sent, for its retiming to be looked at and given to `suggest_retime` by
+/// hand.
+#[tauri::command]
+pub async fn conform_to_new_media(
+    app: AppHandle,
+    id: i32, media_id: i32, path: String,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    playbacks: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let playbacks = Arc::clone(&playbacks);
+    let old = playbacks.lock().unwrap().snapshot_of(media_id);
+    let Some(old) = old.filter(|_| state.lock().unwrap().table.contains_key(&id)) else {
+        send_invalid_id(&channel);
+        return Ok(());
+    };
+    if !old.members.is_empty() {
+        send_error(&channel, "a group can't be conformed");
+        return Ok(());
+    }
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("conform_to_new_media", &channel);
+        let resolved = match sandbox::check_read(&app, &path) {
+            Ok(x) => x,
+            Err(reason) => return send(&channel, SubtitleEvent::PathRejected { reason }),
+        };
+        let report = match conform::compare(Path::new(&old.path), old.audio_index, &resolved,
+            |fraction| try_send(&channel, SubtitleEvent::Progress { fraction }))
+        {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e.to_string()),
+        };
+        if !report.confident {
+            return send(&channel, SubtitleEvent::Conformed {
+                report, applied: false, undo: Vec::new(),
+            });
+        }
+
+        let position = report.retime.map_or(old.position, |x| x.apply(old.position));
+        let snapshot = Snapshot {
+            path: resolved.to_string_lossy().into_owned(),
+            tolerant: false,
+            audio_index: old.audio_index.and(report.new.audio_index),
+            video_index: old.video_index.and(report.new.video_index),
+            subpicture_index: None,
+            images: None,
+            position: Seconds(position.0.max(0.0)),
+            ..old
+        };
+        let backend = match backend::restore(&snapshot) {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e.to_string()),
+        };
+
+        // all or nothing: the edits are made on a copy, and the document
+        // and the session change only once they and the journal went well
+        let mut registry = state.lock().unwrap();
+        let mut playbacks = playbacks.lock().unwrap();
+        let SubtitleRegistry { table, journals, .. } = &mut *registry;
+        let Some(document) = table.get_mut(&id) else { return send_invalid_id(&channel) };
+        if !playbacks.contains(media_id) {
+            return send_invalid_id(&channel);
+        }
+        let edits = report.retime.map(|x| retime::suggest(document, x)).unwrap_or_default();
+        let undo = retime::undo(document, &edits);
+        let mut retimed = document.clone();
+        for edit in &edits {
+            if let Err(e) = retimed.apply(edit) {
+                return send_error(&channel, e);
+            }
+        }
+        if let Some(journal) = journals.get_mut(&id)
+            && let Err(e) = journal.append(&edits, &retimed)
+        {
+            return send_error(&channel, format!("autosave failed: {e}"));
+        }
+        *document = retimed;
+        playbacks.replace(media_id, backend);
+        send(&channel, SubtitleEvent::Conformed { report, applied: true, undo });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// Proposes lead-in and lead-out for the events of document `id` from where
+/// speech starts and stops in the audio of media session `media_id`; see
+/// `lead::suggest`. `cuts` are the video's scene changes, if known.
+#[tauri::command]
+pub async fn suggest_lead(
+    id: i32, media_id: i32, config: LeadConfig, cuts: Vec<Seconds>,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    playbacks: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let Some((source, stream)) = audio_source(&playbacks, media_id, &channel) else {
+        return Ok(());
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        let _timing = timed!("suggest_lead", &channel);
+        let waveform = match audio::classified_waveform(&source, stream,
+            audio_class::FRAME_RATE,
+            |fraction| try_send(&channel, SubtitleEvent::Progress { fraction }))
+        {
+            Ok(x) => x,
+            Err(e) => return send_error(&channel, e.to_string()),
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let speech = audio_class::speech_segments(&waveform.classes, waveform.start_time,
+            waveform.sample_per_second as f64, audio_class::SPEECH_BRIDGE);
+        let registry = state.lock().unwrap();
+        let Some(document) =
+            registry.table.get(&id) else { return send_invalid_id(&channel) };
+        let edits = lead::suggest(document, &speech, &cuts, &config);
+        send(&channel, SubtitleEvent::LeadSuggested { edits });
+    })
+    .await
+    .map_err(|_| ())
+}
+
+/// The audio level around `time` in event `event_id` of document `id` and
+/// the pause in speech nearest to it, from media session `media_id`; for
+/// the split tool to snap to as it's dragged. Only the part in reach, see
+/// `split_preview::REACH`, is read. Requests coming faster
+/// than `split_preview::PREVIEW_INTERVAL` get `SplitPreviewSkipped`, all but
+/// the latest.
+#[tauri::command]
+pub async fn preview_split(
+    id: i32, event_id: u32, media_id: i32, time: Seconds,
+    state: State<'_, Arc<Mutex<SubtitleRegistry>>>,
+    playbacks: State<'_, Arc<Mutex<PlaybackRegistry>>>,
+    channel: Channel<SubtitleEvent>,
+) -> Result<(), ()> {
+    let state = Arc::clone(&state);
+    let Some((source, stream)) = audio_source(&playbacks, media_id, &channel) else {
+        return Ok(());
+    };
+    let (bounds, ticket) = {
+        let mut registry = state.lock().unwrap();
+        let Some(document) = registry.table.get(&id) else {
+            send_invalid_id(&channel);
+            return Ok(());
+        };
+        let Some(event) = document.events.iter().find(|x| x.id == event_id) else {
+            send_error(&channel, format!("no event with id {event_id}"));
+            return Ok(());
+        };
+        if !(event.start.0 < time.0 && time.0 < event.end.0) {
+            send_error(&channel, "split time outside the event");
+            return Ok(());
+        }
+        ((event.start, event.end), registry.split_throttle.enter())
+    };
+    let channel = channel.clone();
+
+    async_runtime::spawn_blocking(move || {
+        loop {
+            let wait = state.lock().unwrap().split_throttle.wait(ticket);
+            match wait {
+                None => return send(&channel, SubtitleEvent::SplitPreviewSkipped {}),
+                Some(wait) if wait.is_zero() => break,
+                Some(wait) => std::thread::sleep(wait),
+            }
+        }
+        let _timing = timed!("preview_split", &channel);
+        let window = (
+            Seconds((time.0 - split_preview::REACH.0).max(bounds.0.0)),
+            Seconds((time.0 + split_preview::REACH.0).min(bounds.1.0)),
+        );
+        match audio::classified_window(&source, stream, window, audio_class::FRAME_RATE) {
+            Ok(waveform) => send(&channel, SubtitleEvent::SplitPreviewed {
+                preview: split_preview::preview(event_id, time, bounds, waveform),
+            }),
+            Err(e) => send_error(&channel, e.to_string()),
+        }
+    })
+    .await
+    .map_err(|_| ())
+}
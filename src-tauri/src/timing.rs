@@ -0,0 +1,191 @@
+//! Where the time goes when a command is slow. Commands run inside a
+//! `tracing` span, and so do the decoding steps underneath them; the
+//! `Timings` layer adds up how long each kind of step took within every
+//! command. A command that takes longer than its budget gets a
+//! `PerformanceWarning` with that breakdown, which is logged and sent to the
+//! frontend, so that a slow seek can be reported with the details of why.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+
+use log::warn;
+use serde::Serialize;
+use tracing::span::{Attributes, Id};
+use tracing::{Span, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+/// Commands without a budget are timed, but never warned about
+static BUDGETS: LazyLock<RwLock<HashMap<String, Duration>>> = LazyLock::new(|| {
+    let budgets = [
+        ("seek_media", 200),
+        ("seek_media_byte", 200),
+        ("seek_audio", 200),
+        ("seek_video", 200),
+        ("send_frame_delta", 100),
+        ("present_surface", 50),
+        ("update_surface", 50),
+        ("open_media", 2000),
+        ("open_video", 1000),
+        ("open_audio", 1000),
+        ("query_events_at", 50),
+        ("get_events_page", 100),
+    ];
+    RwLock::new(budgets.into_iter()
+        .map(|(name, ms)| (name.to_owned(), Duration::from_millis(ms)))
+        .collect())
+});
+
+/// Sets how long `command` may take before it's warned about, or removes
+/// its budget
+pub fn set_budget(command: &str, budget: Option<Duration>) {
+    let mut budgets = BUDGETS.write().unwrap();
+    match budget {
+        Some(x) => budgets.insert(command.to_owned(), x),
+        None => budgets.remove(command),
+    };
+}
+
+/// All the spans of one name within a command
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SpanTiming {
+    pub name: String,
+    pub count: u32,
+    /// time spent inside these spans, including their own children
+    pub total_ms: f64,
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct PerformanceWarning {
+    pub command: String,
+    pub elapsed_ms: f64,
+    pub budget_ms: f64,
+    /// slowest first
+    pub spans: Vec<SpanTiming>,
+}
+
+#[derive(Default)]
+struct Totals {
+    entered: Option<Instant>,
+    busy: Duration,
+    /// of the spans below, by name
+    below: HashMap<&'static str, (u32, Duration)>,
+}
+
+/// Keeps `Totals` on every span and folds each span into its parent's when
+/// it closes
+pub struct Timings;
+
+impl<S> Layer<S> for Timings
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Totals::default());
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(totals) = span.extensions_mut().get_mut::<Totals>()
+        {
+            totals.entered = Some(Instant::now());
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id)
+            && let Some(totals) = span.extensions_mut().get_mut::<Totals>()
+            && let Some(entered) = totals.entered.take()
+        {
+            totals.busy += entered.elapsed();
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(parent) = span.parent() else { return };
+        let Some(totals) = span.extensions_mut().remove::<Totals>() else { return };
+        let mut extensions = parent.extensions_mut();
+        let Some(into) = extensions.get_mut::<Totals>() else { return };
+        for (name, (count, time)) in totals.below {
+            let entry = into.below.entry(name).or_default();
+            entry.0 += count;
+            entry.1 += time;
+        }
+        let entry = into.below.entry(span.name()).or_default();
+        entry.0 += 1;
+        entry.1 += totals.busy;
+    }
+}
+
+/// Installs `Timings` as the global subscriber; logging stays with `log`
+pub fn init() {
+    use tracing_subscriber::layer::SubscriberExt;
+    let subscriber = Registry::default().with(Timings);
+    if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+        warn!("timing: cannot install subscriber: {e}");
+    }
+}
+
+/// Times a command for as long as it lives, and calls `on_exceeded` when
+/// dropped if the command went over its budget. Spans are tied to a
+/// thread, so in async commands create it inside the blocking task.
+pub struct Command<F: FnOnce(PerformanceWarning)> {
+    name: &'static str,
+    start: Instant,
+    on_exceeded: Option<F>,
+    span: tracing::span::EnteredSpan,
+}
+
+impl<F: FnOnce(PerformanceWarning)> Command<F> {
+    pub fn start(name: &'static str, on_exceeded: F) -> Self {
+        let span = tracing::info_span!("command", name).entered();
+        Self { name, start: Instant::now(), on_exceeded: Some(on_exceeded), span }
+    }
+}
+
+fn breakdown(span: &Span) -> Vec<SpanTiming> {
+    let spans = span.with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        let span = registry.span(id)?;
+        let extensions = span.extensions();
+        let totals = extensions.get::<Totals>()?;
+        Some(totals.below.iter()
+            .map(|(name, (count, time))| SpanTiming {
+                name: (*name).to_owned(),
+                count: *count,
+                total_ms: time.as_secs_f64() * 1000.0,
+            })
+            .collect::<Vec<_>>())
+    });
+    let mut spans = spans.flatten().unwrap_or_default();
+    spans.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    spans
+}
+
+impl<F: FnOnce(PerformanceWarning)> Drop for Command<F> {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let Some(&budget) = BUDGETS.read().unwrap().get(self.name) else { return };
+        if elapsed <= budget {
+            return;
+        }
+        let warning = PerformanceWarning {
+            command: self.name.to_owned(),
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+            budget_ms: budget.as_secs_f64() * 1000.0,
+            spans: breakdown(&self.span),
+        };
+        warn!("timing: {warning:?}");
+        if let Some(f) = self.on_exceeded.take() {
+            f(warning);
+        }
+    }
+}
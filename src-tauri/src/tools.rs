@@ -0,0 +1,108 @@
+//! The programs of others that the backend runs: piper for `tts` and
+//! whisper.cpp for `transcribe`. Where they are is the user's choice, made
+//! in a file dialog that the backend opens itself and kept in the app's
+//! config directory, so no command takes a program to run from the
+//! frontend; commands only say which tool they want.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::DialogExt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub enum Tool {
+    Piper,
+    /// whisper.cpp's command line program, `whisper-cli`
+    Whisper,
+}
+
+impl Tool {
+    const ALL: [Tool; 2] = [Tool::Piper, Tool::Whisper];
+
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Piper => "piper",
+            Tool::Whisper => "whisper-cli",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct ToolPath {
+    pub tool: Tool,
+    /// absent if it hasn't been chosen
+    pub path: Option<String>,
+}
+
+fn config(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path().app_config_dir().map(|x| x.join("tools.json")).map_err(|e| e.to_string())
+}
+
+fn load(app: &AppHandle) -> Result<BTreeMap<Tool, PathBuf>, String> {
+    let path = config(app)?;
+    match fs::read(&path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| format!("{}: {e}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    }
+}
+
+fn store(app: &AppHandle, tools: &BTreeMap<Tool, PathBuf>) -> Result<(), String> {
+    let path = config(app)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    }
+    let data = serde_json::to_vec_pretty(tools).map_err(|e| e.to_string())?;
+    fs::write(&path, data).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Every tool, with where it is if chosen
+pub fn list(app: &AppHandle) -> Result<Vec<ToolPath>, String> {
+    let chosen = load(app)?;
+    Ok(Tool::ALL.iter().map(|tool| ToolPath {
+        tool: *tool,
+        path: chosen.get(tool).map(|x| x.to_string_lossy().into_owned()),
+    }).collect())
+}
+
+/// The program chosen for `tool`, if it's still there
+pub fn path(app: &AppHandle, tool: Tool) -> Result<PathBuf, String> {
+    let Some(path) = load(app)?.remove(&tool) else {
+        return Err(format!("{} hasn't been set up; choose the program first", tool.name()));
+    };
+    path.canonicalize().map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Asks the user where the program for `tool` is, and keeps it. Returns
+/// `false` if they cancelled. Blocks until the dialog is closed, so it
+/// mustn't be called on the main thread.
+pub fn choose(app: &AppHandle, tool: Tool) -> Result<bool, String> {
+    let picked = app.dialog().file()
+        .set_title(format!("Where is {}?", tool.name()))
+        .blocking_pick_file();
+    let Some(picked) = picked else { return Ok(false) };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    let path = path.canonicalize().map_err(|e| format!("{}: {e}", path.display()))?;
+    if !path.is_file() {
+        return Err(format!("{}: not a file", path.display()));
+    }
+    let mut tools = load(app)?;
+    tools.insert(tool, path);
+    store(app, &tools)?;
+    Ok(true)
+}
+
+pub fn clear(app: &AppHandle, tool: Tool) -> Result<(), String> {
+    let mut tools = load(app)?;
+    if tools.remove(&tool).is_some() {
+        store(app, &tools)?;
+    }
+    Ok(())
+}
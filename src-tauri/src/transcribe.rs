@@ -0,0 +1,402 @@
+//! Speech recognition with whisper.cpp, run as a program of its own like
+//! piper in `tts`: we hand it a 16 kHz mono WAV and a model, a few minutes
+//! of it at a time, and read the segments it writes. Hours of audio take a
+//! long time to get through, so segments go into the transcript on disk as
+//! they come rather than at the end. Whatever was done survives a crash, the
+//! start can be opened and edited while the rest is still coming, and a
+//! transcription that stopped can be taken up again where it did.
+//!
+//! Whisper is one `AsrProvider`; on weak hardware a service can do the work
+//! instead, see `cloud`, with the transcript written the same way.
+//!
+//! Segments carry the words in them with how sure the recognizer was of
+//! each, where it says; JSON Lines keeps them and SubRip has no place for
+//! them. A document made from a transcript keeps them too, see
+//! `subtitle::words`.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::compute::DevicePreference;
+use crate::media::units::Seconds;
+use crate::subtitle::srt;
+use crate::tts;
+
+pub mod cloud;
+
+/// What the providers take
+pub const SAMPLE_RATE: u32 = 16000;
+
+/// Which provider transcribes, and how
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+#[ts(export)]
+pub enum AsrConfig {
+    Whisper(Transcriber),
+    Cloud(cloud::CloudConfig),
+}
+
+/// Something that turns speech into segments
+pub trait AsrProvider {
+    /// Transcribes the WAV at `audio`, 16-bit mono at `SAMPLE_RATE`, from
+    /// `from` on, handing each segment to `segment` as it's done, in order;
+    /// `segment` returns `false` to stop
+    fn transcribe(
+        &self, audio: &Path, from: Seconds, segment: &mut dyn FnMut(TranscriptSegment) -> bool,
+    ) -> Result<(), String>;
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Transcriber {
+    /// the `ggml` model
+    pub model: String,
+    /// like `en`; absent to have it detected
+    pub language: Option<String>,
+    pub threads: Option<u32>,
+    /// where to run it; see `compute`
+    #[serde(default)]
+    pub device: DevicePreference,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct TranscriptWord {
+    pub start: Seconds,
+    pub end: Seconds,
+    pub text: String,
+    /// from 0 to 1; absent if the provider didn't say
+    pub confidence: Option<f32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct TranscriptSegment {
+    pub start: Seconds,
+    pub end: Seconds,
+    pub text: String,
+    /// empty if the provider gave none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<TranscriptWord>,
+}
+
+/// How a transcript is written, by the extension of its path: `.srt` as
+/// SubRip, anything else as JSON Lines, a `TranscriptSegment` per line,
+/// which, unlike one JSON array, is whole after every segment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    Srt,
+    JsonLines,
+}
+
+impl TranscriptFormat {
+    pub fn of(path: &Path) -> Self {
+        match path.extension() {
+            Some(x) if x.eq_ignore_ascii_case("srt") => Self::Srt,
+            _ => Self::JsonLines,
+        }
+    }
+}
+
+/// The segments of JSON Lines `text` up to the first that is cut off or
+/// broken, and the length of the part they take up
+fn parse_json_lines(text: &str) -> (Vec<TranscriptSegment>, usize) {
+    let mut segments = Vec::new();
+    let mut length = 0;
+    for line in text.split_inclusive('\n') {
+        let Some(segment) = line.strip_suffix('\n')
+            .and_then(|x| serde_json::from_str::<TranscriptSegment>(x).ok())
+        else { break };
+        length += line.len();
+        segments.push(segment);
+    }
+    (segments, length)
+}
+
+/// The segments of the transcript at `path`, as far as it is whole
+pub fn read(path: &Path) -> Result<Vec<TranscriptSegment>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(match TranscriptFormat::of(path) {
+        TranscriptFormat::Srt => srt::parse(&text).0.into_iter()
+            .map(|x| TranscriptSegment { start: x.start, end: x.end, text: x.text, words: Vec::new() })
+            .collect(),
+        TranscriptFormat::JsonLines => parse_json_lines(&text).0,
+    })
+}
+
+/// A transcript on disk, written to a segment at a time
+pub struct TranscriptWriter {
+    file: File,
+    format: TranscriptFormat,
+    /// segments in it
+    count: usize,
+    /// where the last of them ends
+    end: Seconds,
+}
+
+impl TranscriptWriter {
+    /// Starts a transcript at `path`, or with `resume` goes on with the one
+    /// there, keeping its segments up to the first one cut off and dropping
+    /// the rest
+    pub fn open(path: &Path, resume: bool) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("{}: {e}", path.display());
+        let format = TranscriptFormat::of(path);
+        let mut file = OpenOptions::new()
+            .read(true).write(true).create(true).truncate(!resume)
+            .open(path).map_err(error)?;
+        let mut writer = Self {
+            file: file.try_clone().map_err(error)?,
+            format,
+            count: 0,
+            end: Seconds(0.0),
+        };
+        if !resume {
+            return Ok(writer);
+        }
+
+        let mut text = String::new();
+        file.read_to_string(&mut text).map_err(error)?;
+        let kept;
+        match format {
+            TranscriptFormat::Srt => {
+                kept = text.rfind("\n\n").map_or(0, |x| x + 2);
+                let (cues, _) = srt::parse(&text[..kept]);
+                writer.count = cues.len();
+                writer.end = cues.last().map_or(Seconds(0.0), |x| x.end);
+            }
+            TranscriptFormat::JsonLines => {
+                let segments;
+                (segments, kept) = parse_json_lines(&text);
+                writer.count = segments.len();
+                writer.end = segments.last().map_or(Seconds(0.0), |x| x.end);
+            }
+        }
+        let length = u64::try_from(kept).unwrap();
+        file.set_len(length).map_err(error)?;
+        writer.file.seek(SeekFrom::Start(length)).map_err(error)?;
+        Ok(writer)
+    }
+
+    /// Where the segments so far end, and a resumed transcription starts
+    pub fn end(&self) -> Seconds {
+        self.end
+    }
+
+    /// Writes `segment` through to the disk
+    pub fn append(&mut self, segment: &TranscriptSegment) -> Result<(), String> {
+        let record = match self.format {
+            TranscriptFormat::Srt => srt::write_cue(self.count + 1, &srt::Cue {
+                start: segment.start,
+                end: segment.end,
+                text: segment.text.clone(),
+            }),
+            TranscriptFormat::JsonLines => format!("{}\n",
+                serde_json::to_string(segment).map_err(|e| e.to_string())?),
+        };
+        self.file.write_all(record.as_bytes())
+            .and_then(|()| self.file.sync_data())
+            .map_err(|e| e.to_string())?;
+        self.count += 1;
+        self.end = segment.end;
+        Ok(())
+    }
+}
+
+/// How much of the audio one run of whisper is given. What it was sure of
+/// and when each word was said only come in the JSON it writes when it's
+/// done, so the audio goes through a piece at a time, and each piece's
+/// segments reach the transcript once it's through.
+const PIECE: Seconds = Seconds(300.0);
+
+/// What `--output-json-full` writes, as far as it's read
+#[derive(Deserialize)]
+struct WhisperOutput {
+    transcription: Vec<WhisperSegment>,
+}
+
+#[derive(Deserialize)]
+struct WhisperSegment {
+    offsets: WhisperSpan,
+    text: String,
+    #[serde(default)]
+    tokens: Vec<WhisperToken>,
+}
+
+#[derive(Deserialize)]
+struct WhisperToken {
+    text: String,
+    offsets: WhisperSpan,
+    /// the probability of the token
+    p: f32,
+}
+
+/// In milliseconds from the start of the file
+#[derive(Clone, Copy, Deserialize)]
+struct WhisperSpan {
+    from: i64,
+    to: i64,
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn milliseconds(ms: i64) -> Seconds {
+    Seconds(ms as f64 / 1000.0)
+}
+
+/// The words of a segment's tokens, each from its first token to its
+/// last, and as sure as its least sure one. A token that starts with a
+/// space starts a word; the bytes of a character may be split between
+/// tokens, so they're joined as bytes.
+fn words(tokens: &[WhisperToken]) -> Vec<TranscriptWord> {
+    let mut pieces: Vec<(Vec<u8>, WhisperSpan, f32)> = Vec::new();
+    // `[_BEG_]`, `[_TT_150]`, `<|endoftext|>` and the like
+    let text = tokens.iter().filter(|x| !x.text.starts_with("[_") && !x.text.starts_with("<|"));
+    for token in text {
+        match pieces.last_mut() {
+            Some((bytes, span, p)) if !token.text.starts_with(' ') => {
+                bytes.extend_from_slice(token.text.as_bytes());
+                span.to = token.offsets.to;
+                *p = p.min(token.p);
+            }
+            _ => pieces.push((token.text.as_bytes().to_vec(), token.offsets, token.p)),
+        }
+    }
+    pieces.into_iter()
+        .map(|(bytes, span, p)| TranscriptWord {
+            start: milliseconds(span.from),
+            end: milliseconds(span.to),
+            text: String::from_utf8_lossy(&bytes).trim().to_owned(),
+            confidence: Some(p.clamp(0.0, 1.0)),
+        })
+        .filter(|x| !x.text.is_empty())
+        .collect()
+}
+
+fn segment_of(x: WhisperSegment) -> Option<TranscriptSegment> {
+    let text = x.text.trim().to_owned();
+    (!text.is_empty()).then(|| TranscriptSegment {
+        start: milliseconds(x.offsets.from),
+        end: milliseconds(x.offsets.to),
+        text,
+        words: words(&x.tokens),
+    })
+}
+
+/// whisper.cpp's command line program, as chosen in `tools`, with the
+/// `model` of `transcriber` already checked, on the GPU of UUID `device`
+/// or the CPU; see `compute::Reservation`
+pub struct Whisper {
+    pub program: PathBuf,
+    pub model: PathBuf,
+    pub transcriber: Transcriber,
+    pub device: Option<String>,
+}
+
+impl Whisper {
+    /// The words whisper hears in all of the short WAV at `audio`, which is
+    /// meant to say `expected`; for timing the words of one event again,
+    /// see `subtitle::align`
+    pub fn words_in(&self, audio: &Path, expected: &str) -> Result<Vec<TranscriptWord>, String> {
+        let Self { program, model, transcriber, device } = self;
+        let length = tts::wav_duration(audio)?;
+        let segments = run_piece(program, model, transcriber, audio, (Seconds(0.0), length),
+            Some(expected), device.as_deref())?;
+        Ok(segments.into_iter().flat_map(|x| x.words).collect())
+    }
+}
+
+impl AsrProvider for Whisper {
+    fn transcribe(
+        &self, audio: &Path, from: Seconds, segment: &mut dyn FnMut(TranscriptSegment) -> bool,
+    ) -> Result<(), String> {
+        let Self { program, model, transcriber, device } = self;
+        run_whisper(program, model, transcriber, audio, from, device.as_deref(), segment)
+    }
+}
+
+/// Runs whisper on `audio` a `PIECE` at a time from `from`. A piece's
+/// last segment may have been cut off where the piece ends, so it's left
+/// for the next piece, which starts where it does.
+fn run_whisper(
+    program: &Path, model: &Path, transcriber: &Transcriber, audio: &Path, from: Seconds,
+    device: Option<&str>, segment: &mut dyn FnMut(TranscriptSegment) -> bool,
+) -> Result<(), String> {
+    let length = tts::wav_duration(audio)?;
+    let mut at = from;
+    while at.0 < length.0 {
+        let mut segments = run_piece(program, model, transcriber, audio, (at, PIECE), None, device)?;
+        let mut next = Seconds(at.0 + PIECE.0);
+        if next.0 < length.0
+            && segments.len() > 1
+            && let Some(last) = segments.pop_if(|x| x.start.0 > at.0)
+        {
+            next = last.start;
+        }
+        for x in segments {
+            if !segment(x) {
+                return Err("transcription cancelled".to_owned());
+            }
+        }
+        at = next;
+    }
+    Ok(())
+}
+
+/// The segments of `length` of `audio` from `at`, with `prompt` as the
+/// text that came before, which leans whisper towards its words
+fn run_piece(
+    program: &Path, model: &Path, transcriber: &Transcriber, audio: &Path,
+    (at, length): (Seconds, Seconds), prompt: Option<&str>, device: Option<&str>,
+) -> Result<Vec<TranscriptSegment>, String> {
+    // whisper adds `.json`
+    let output = audio.with_extension("whisper");
+    let mut command = Command::new(program);
+    command.arg("--model").arg(model).arg("--file").arg(audio)
+        .arg("--no-prints")
+        .arg("--output-json-full")
+        .arg("--output-file").arg(&output);
+    if let Some(language) = &transcriber.language {
+        command.arg("--language").arg(language);
+    }
+    if let Some(threads) = transcriber.threads {
+        command.arg("--threads").arg(threads.to_string());
+    }
+    if let Some(prompt) = prompt {
+        command.arg("--prompt").arg(prompt);
+    }
+    // the only GPU it sees is the one reserved
+    match device {
+        Some(uuid) => command.env("CUDA_VISIBLE_DEVICES", uuid).arg("--device").arg("0"),
+        None => command.arg("--no-gpu"),
+    };
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let (offset, duration) = ((at.0 * 1000.0).round() as u64, (length.0 * 1000.0).round() as u64);
+    command.arg("--offset-t").arg(offset.to_string())
+        .arg("--duration").arg(duration.to_string());
+    let result = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| format!("{}: {e}", program.display()))?;
+    if !result.status.success() {
+        let message = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("whisper failed ({}): {}",
+            result.status, message.lines().last().unwrap_or("").trim()));
+    }
+
+    let mut json = output.into_os_string();
+    json.push(".json");
+    let json = PathBuf::from(json);
+    let read = std::fs::read(&json).map_err(|e| format!("{}: {e}", json.display()));
+    let _ = std::fs::remove_file(&json);
+    let output: WhisperOutput = serde_json::from_slice(&read?)
+        .map_err(|e| format!("{}: {e}", json.display()))?;
+    Ok(output.transcription.into_iter().filter_map(segment_of).collect())
+}
@@ -0,0 +1,249 @@
+//! Transcription by a service with OpenAI's API for it: the audio is posted
+//! as a form to `{endpoint}/audio/transcriptions` and segments come back as
+//! `verbose_json`. OpenAI, Groq and servers like faster-whisper-server all
+//! take it. Uploads are limited in size, 25 MB at OpenAI, so the audio goes
+//! up `CHUNK` at a time, each piece transcribed and handed on before the
+//! next is sent; a word cut at the boundary may come out garbled. The API
+//! key is kept in the system's keyring under the endpoint rather than in
+//! the settings.
+//!
+//! Words come with their times where the service gives them. OpenAI gives
+//! no confidence for words, only the average log probability of a segment,
+//! which then stands for each word in it; servers running faster-whisper
+//! give each word's own.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime;
+use tauri_plugin_http::reqwest::{header, Client};
+
+use crate::media::units::Seconds;
+use crate::transcribe::{AsrProvider, TranscriptSegment, TranscriptWord, SAMPLE_RATE};
+
+const KEYRING_SERVICE: &str = "subtle-asr";
+/// Audio per upload; 10 minutes are about 19 MB as 16-bit mono
+const CHUNK: Seconds = Seconds(600.0);
+/// as `record::export_mono` writes it
+const WAV_HEADER_LENGTH: u64 = 44;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct CloudConfig {
+    /// the API's base URL, like `https://api.openai.com/v1`
+    pub endpoint: String,
+    /// like `whisper-1`
+    pub model: String,
+    /// like `en`; absent to have it detected
+    pub language: Option<String>,
+}
+
+fn entry(endpoint: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, endpoint).map_err(|e| e.to_string())
+}
+
+/// Keeps `key` in the keyring for `endpoint`
+pub fn set_key(endpoint: &str, key: &str) -> Result<(), String> {
+    entry(endpoint)?.set_password(key).map_err(|e| e.to_string())
+}
+
+/// Forgets the key for `endpoint`, if there is one
+pub fn clear_key(endpoint: &str) -> Result<(), String> {
+    match entry(endpoint)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    segments: Vec<ResponseSegment>,
+    /// OpenAI's words, apart from the segments
+    #[serde(default)]
+    words: Vec<ResponseWord>,
+}
+
+#[derive(Deserialize)]
+struct ResponseSegment {
+    start: f64,
+    end: f64,
+    text: String,
+    avg_logprob: Option<f64>,
+    /// faster-whisper's words, in their segment
+    #[serde(default)]
+    words: Vec<ResponseWord>,
+}
+
+#[derive(Deserialize)]
+struct ResponseWord {
+    word: String,
+    start: f64,
+    end: f64,
+    probability: Option<f32>,
+}
+
+/// A WAV of 16-bit mono `pcm` at `SAMPLE_RATE`
+fn wav(pcm: &[u8]) -> Vec<u8> {
+    let length = u32::try_from(pcm.len()).unwrap();
+    let mut result = Vec::with_capacity(pcm.len() + 44);
+    result.extend_from_slice(b"RIFF");
+    result.extend_from_slice(&(length + 36).to_le_bytes());
+    result.extend_from_slice(b"WAVEfmt ");
+    result.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, mono
+    result.extend_from_slice(&1u16.to_le_bytes());
+    result.extend_from_slice(&1u16.to_le_bytes());
+    result.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    result.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    result.extend_from_slice(&2u16.to_le_bytes());
+    result.extend_from_slice(&16u16.to_le_bytes());
+    result.extend_from_slice(b"data");
+    result.extend_from_slice(&length.to_le_bytes());
+    result.extend_from_slice(pcm);
+    result
+}
+
+/// A `multipart/form-data` body of `fields` and the WAV `audio` as `file`
+fn form(boundary: &str, fields: &[(&str, &str)], audio: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(audio.len() + 1024);
+    for (name, value) in fields {
+        body.extend_from_slice(format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+        ).as_bytes());
+    }
+    body.extend_from_slice(format!(
+        "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"audio.wav\"\r\n\
+        Content-Type: audio/wav\r\n\r\n"
+    ).as_bytes());
+    body.extend_from_slice(audio);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
+
+pub struct Cloud {
+    config: CloudConfig,
+    key: String,
+    client: Client,
+}
+
+impl Cloud {
+    /// Fails if no key has been set for the endpoint
+    pub fn new(config: CloudConfig) -> Result<Self, String> {
+        let key = match entry(&config.endpoint)?.get_password() {
+            Ok(x) => x,
+            Err(keyring::Error::NoEntry) =>
+                return Err(format!("no API key for {}", config.endpoint)),
+            Err(e) => return Err(e.to_string()),
+        };
+        Ok(Self { config, key, client: Client::new() })
+    }
+
+    fn request(&self, audio: &[u8]) -> Result<Response, String> {
+        let url = format!("{}/audio/transcriptions", self.config.endpoint.trim_end_matches('/'));
+        let boundary = format!("subtle-{:016x}", rand::random::<u64>());
+        let mut fields = vec![
+            ("model", self.config.model.as_str()),
+            ("response_format", "verbose_json"),
+            ("timestamp_granularities[]", "segment"),
+            ("timestamp_granularities[]", "word"),
+        ];
+        if let Some(language) = &self.config.language {
+            fields.push(("language", language));
+        }
+        let body = form(&boundary, &fields, audio);
+        async_runtime::block_on(async {
+            let response = self.client.post(&url)
+                .bearer_auth(&self.key)
+                .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={boundary}"))
+                .body(body)
+                .send().await
+                .map_err(|e| format!("{url}: {e}"))?;
+            let status = response.status();
+            let bytes = response.bytes().await.map_err(|e| format!("{url}: {e}"))?;
+            if !status.is_success() {
+                let message = String::from_utf8_lossy(&bytes);
+                return Err(format!("{url}: {status}: {}", message.chars().take(200).collect::<String>()));
+            }
+            serde_json::from_slice(&bytes).map_err(|e| format!("{url}: {e}"))
+        })
+    }
+}
+
+impl AsrProvider for Cloud {
+    fn transcribe(
+        &self, audio: &Path, from: Seconds, segment: &mut dyn FnMut(TranscriptSegment) -> bool,
+    ) -> Result<(), String> {
+        let io_error = |e: std::io::Error| format!("{}: {e}", audio.display());
+        let mut file = File::open(audio).map_err(io_error)?;
+        let data_length = file.metadata().map_err(io_error)?.len().saturating_sub(WAV_HEADER_LENGTH);
+        let bytes_per_second = u64::from(SAMPLE_RATE) * 2;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (mut start, chunk_length) = (
+            (from.0.max(0.0) * f64::from(SAMPLE_RATE)).round() as u64 * 2,
+            CHUNK.0 as u64 * bytes_per_second,
+        );
+
+        while start < data_length {
+            let length = chunk_length.min(data_length - start);
+            let mut pcm = vec![0; usize::try_from(length).unwrap()];
+            file.seek(SeekFrom::Start(WAV_HEADER_LENGTH + start))
+                .and_then(|_| file.read_exact(&mut pcm))
+                .map_err(io_error)?;
+            #[allow(clippy::cast_precision_loss)]
+            let (offset, duration) = (
+                start as f64 / bytes_per_second as f64,
+                length as f64 / bytes_per_second as f64,
+            );
+            let response = self.request(&wav(&pcm))?;
+
+            // some servers give only the text; it goes in as one segment
+            let pieces = if response.segments.is_empty() {
+                vec![ResponseSegment {
+                    start: 0.0, end: duration, text: response.text,
+                    avg_logprob: None, words: Vec::new(),
+                }]
+            } else {
+                response.segments
+            };
+            let mut loose = response.words.into_iter().peekable();
+            let time = |x: f64| Seconds(offset + x.clamp(0.0, duration));
+            for x in pieces {
+                let mut words = x.words;
+                while let Some(word) = loose.next_if(|w| (w.start + w.end) / 2.0 < x.end) {
+                    words.push(word);
+                }
+                let text = x.text.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                #[allow(clippy::cast_possible_truncation)]
+                let average = x.avg_logprob.map(|p| p.exp() as f32);
+                let piece = TranscriptSegment {
+                    start: time(x.start),
+                    end: time(x.end),
+                    text: text.to_owned(),
+                    words: words.into_iter()
+                        .filter(|w| !w.word.trim().is_empty())
+                        .map(|w| TranscriptWord {
+                            start: time(w.start),
+                            end: time(w.end),
+                            text: w.word.trim().to_owned(),
+                            confidence: w.probability.or(average),
+                        })
+                        .collect(),
+                };
+                if !segment(piece) {
+                    return Err("transcription cancelled".to_owned());
+                }
+            }
+            start += length;
+        }
+        Ok(())
+    }
+}
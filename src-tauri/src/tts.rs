@@ -0,0 +1,161 @@
+//! Text-to-speech with piper, a local neural engine, so that translators
+//! can hear whether a dubbed line fits in the time the original takes.
+//! Piper is run as a program of its own, which brings its phonemizer and
+//! ONNX runtime along; we only hand it lines of text and a voice model
+//! (an `.onnx` file with its `.onnx.json` beside it) and read back the
+//! length of the WAVs it writes. Where piper is is kept by `tools`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::media::units::Seconds;
+use crate::subtitle::ass;
+
+#[derive(Clone, Debug, Serialize, Deserialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct Voice {
+    /// the `.onnx` voice model
+    pub model: String,
+    /// for models with several speakers
+    pub speaker: Option<u32>,
+    /// speaking rate; above 1 is slower
+    pub length_scale: Option<f64>,
+}
+
+/// How a cue said out loud compares with the time it's on screen
+#[derive(Clone, Debug, Serialize, ts_rs::TS)]
+#[serde(rename_all = "camelCase")]
+#[ts(export)]
+pub struct SpokenLength {
+    pub event_id: u32,
+    pub spoken: Seconds,
+    /// how long the event lasts
+    pub available: Seconds,
+    /// `spoken` less `available`: positive if the line runs long
+    pub overrun: Seconds,
+}
+
+impl SpokenLength {
+    pub fn new(event_id: u32, spoken: Seconds, available: Seconds) -> Self {
+        Self { event_id, spoken, available, overrun: Seconds(spoken.0 - available.0) }
+    }
+}
+
+/// What an event says out loud: override blocks dropped, line breaks and
+/// hard spaces made plain spaces
+pub fn spoken_text(text: &str) -> String {
+    ass::plain_text(text).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Length of a PCM WAV file, from its header
+pub fn wav_duration(path: &Path) -> Result<Seconds, String> {
+    let data = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let invalid = || format!("{}: not a PCM WAV file", path.display());
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(invalid());
+    }
+    let mut byte_rate = None;
+    let mut at = 12;
+    while at + 8 <= data.len() {
+        let id = &data[at..at + 4];
+        let size = u32::from_le_bytes(data[at + 4..at + 8].try_into().unwrap());
+        let body = at + 8;
+        let available = u32::try_from(data.len() - body).unwrap_or(u32::MAX);
+        if id == b"fmt " && body + 12 <= data.len() {
+            byte_rate = Some(u32::from_le_bytes(data[body + 8..body + 12].try_into().unwrap()));
+        } else if id == b"data" {
+            let rate = byte_rate.filter(|x| *x > 0).ok_or_else(invalid)?;
+            // a writer that was cut off leaves the size unset; count what's there
+            return Ok(Seconds(f64::from(available.min(size)) / f64::from(rate)));
+        }
+        if size > available {
+            break;
+        }
+        // chunks are padded to an even length
+        at = body + usize::try_from(size.saturating_add(size & 1)).unwrap();
+    }
+    Err(invalid())
+}
+
+/// Says each of `lines` with `voice` into the WAV beside it and returns
+/// how long each takes. It's all one run of `program`, so the model is
+/// loaded once however many lines there are. `progress` is called with the
+/// fraction done so far, and returns `false` to give up.
+pub fn speak_all(
+    program: &Path, model: &Path, voice: &Voice, lines: &[(String, PathBuf)],
+    mut progress: impl FnMut(f64) -> bool,
+) -> Result<Vec<Seconds>, String> {
+    let failed = |e: std::io::Error| format!("{}: {e}", program.display());
+    let mut command = Command::new(program);
+    command.arg("--model").arg(model).arg("--json-input");
+    if let Some(speaker) = voice.speaker {
+        command.arg("--speaker").arg(speaker.to_string());
+    }
+    if let Some(scale) = voice.length_scale {
+        command.arg("--length_scale").arg(scale.to_string());
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(failed)?;
+
+    // one utterance per line, each saying where it goes; written alongside
+    // so that neither side waits on a full pipe
+    let input: Vec<String> = lines.iter()
+        .map(|(text, output)| serde_json::json!({
+            "text": text.replace('\n', " "),
+            "output_file": output.to_string_lossy(),
+        }).to_string())
+        .collect();
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || -> std::io::Result<()> {
+        for line in input {
+            writeln!(stdin, "{line}")?;
+        }
+        Ok(())
+    });
+    let mut stderr = child.stderr.take().unwrap();
+    let errors = std::thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+    // piper prints the path of each file as it finishes it
+    let mut done = 0;
+    for line in BufReader::new(child.stdout.take().unwrap()).lines() {
+        if line.map_err(failed)?.trim().is_empty() {
+            continue;
+        }
+        done += 1;
+        #[allow(clippy::cast_precision_loss)]
+        if !progress(done as f64 / lines.len() as f64) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("speech cancelled".to_owned());
+        }
+    }
+    let status = child.wait().map_err(failed)?;
+    if !status.success() {
+        let message = errors.join().unwrap_or_default();
+        return Err(format!("piper failed ({status}): {}",
+            message.lines().last().unwrap_or("").trim()));
+    }
+    writer.join().unwrap_or(Ok(())).map_err(failed)?;
+    lines.iter().map(|(_, output)| wav_duration(output)).collect()
+}
+
+/// Says `text` with `voice` into a WAV at `output` and returns how long it
+/// takes; see `speak_all`
+pub fn speak(
+    program: &Path, model: &Path, voice: &Voice, text: &str, output: &Path,
+) -> Result<Seconds, String> {
+    let lines = [(text.to_owned(), output.to_owned())];
+    let mut lengths = speak_all(program, model, voice, &lines, |_| true)?;
+    Ok(lengths.remove(0))
+}
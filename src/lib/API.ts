@@ -3,6 +3,11 @@ import { Debug } from './Debug';
 import { BinaryReader } from './details/BinaryReader';
 import type { MediaEvent } from './bindings/MediaEvent';
 import type { StreamDescription } from './bindings/StreamDescription';
+import type { Framerate } from './bindings/Framerate';
+import type { Capabilities } from './bindings/Capabilities';
+import type { BackendKind } from './bindings/BackendKind';
+import type { SelfCheckReport } from './bindings/SelfCheckReport';
+import type { EventKind } from './bindings/EventKind';
 
 export class MediaError extends Error {
     constructor(msg: string, public readonly from: string) {
@@ -42,6 +47,11 @@ function createChannel(
             return reject(new MediaError('runtimeError: ' + msg.data.what, from));
         case 'invalidId':
             return reject(new MediaError('invalid media ID referenced', from));
+        case 'unsupported':
+            return reject(new MediaError(`not supported: ${msg.data.feature}`, from));
+        case 'performanceWarning':
+            Debug.warn(`${from}: took ${msg.data.warning.elapsedMs.toFixed(0)}ms`, msg.data.warning);
+            break;
         default:
             return reject(new Error('unhandled event: ' + msg.event));
         }
@@ -148,16 +158,17 @@ export class MMedia {
         return { pktpos, time, stride, length, content, size: [...this.#outSize] };
     }
 
-    static async open(path: string) {
+    static async open(path: string, backend: BackendKind | null = null) {
         const id = await new Promise<number>((resolve, reject) => {
             const channel = createChannel('open', {
                 opened: (data) => resolve(data.id)
             }, reject);
-            invoke('open_media', {path, channel});
+            invoke('open_media', {path, backend, channel});
         });
         const status = await new Promise<{
             audioIndex: number,
             videoIndex: number,
+            audioOnly: boolean,
             duration: number,
             streams: StreamDescription[]
         }>((resolve, reject) => {
@@ -187,6 +198,17 @@ export class MMedia {
             invoke('close_media', {id: this.id, channel});
         });
     }
+
+    /** Optional events to receive for this media; all but `debug` by default */
+    async setEventFilter(kinds: EventKind[]) {
+        Debug.assert(!this.#destroyed);
+        await new Promise<void>((resolve, reject) => {
+            const channel = createChannel('setEventFilter', {
+                done: () => resolve()
+            }, reject);
+            invoke('set_event_filter', {id: this.id, kinds, channel});
+        });
+    }
     
     async waitUntilAvailable() {
         return await new Promise<void>((resolve, reject) => {
@@ -423,6 +445,36 @@ export const MAPI = {
         return await invoke<string>('media_config', {});
     },
 
+    async capabilities() {
+        return await new Promise<Capabilities>((resolve, reject) => {
+            const channel = createChannel('get_backend_capabilities', {
+                capabilities: (data) => resolve(data.capabilities)
+            }, reject);
+            invoke('get_backend_capabilities', {channel});
+        });
+    },
+
+    async selfcheck() {
+        return await new Promise<SelfCheckReport>((resolve, reject) => {
+            const channel = createChannel('run_media_selfcheck', {
+                selfCheck: (data) => resolve(data.report)
+            }, reject);
+            invoke('run_media_selfcheck', {channel});
+        });
+    },
+
+    async setPerformanceBudget(command: string, budgetMs: number | null) {
+        return await invoke<void>('set_performance_budget', { command, budgetMs });
+    },
+
+    async formatTimecode(timeMs: number, rate: Framerate, dropFrame: boolean) {
+        return await invoke<string>('format_timecode', { timeMs, rate, dropFrame });
+    },
+
+    async parseTimecode(text: string, rate: Framerate) {
+        return await invoke<number>('parse_timecode', { text, rate });
+    },
+
     async testPerformance(path: string, postprocess: boolean, hwaccel: boolean) {
         return await new Promise<void>((resolve, reject) => {
             const channel = createChannel('test_performance', {
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CloudConfig } from "./CloudConfig";
+import type { Transcriber } from "./Transcriber";
+
+/**
+ * Which provider transcribes, and how
+ */
+export type AsrConfig = { "kind": "whisper" } & Transcriber | { "kind": "cloud" } & CloudConfig;
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Attachment = { path: string, 
+/**
+ * shown by players that list attachments
+ */
+description: string | null, };
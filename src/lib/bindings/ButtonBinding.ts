@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ControllerAction } from "./ControllerAction";
+
+export type ButtonBinding = { 
+/**
+ * counted from 0, as the device numbers its bits
+ */
+button: number, action: ControllerAction, };
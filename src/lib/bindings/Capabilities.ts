@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Capabilities = { version: string, 
+/**
+ * burning ASS subtitles into the picture, with libass's `subtitles`
+ * filter
+ */
+hardsub: boolean, 
+/**
+ * HDR to SDR, with `zscale` and `tonemap` or with `libplacebo`
+ */
+hdrToneMapping: boolean, h264Encoding: boolean, hevcEncoding: boolean, pngExport: boolean, jpegExport: boolean, 
+/**
+ * the timecode in generated test clips, with `drawtext`
+ */
+burntInTimecode: boolean, imageSequences: boolean, matroskaMuxing: boolean, mp4Muxing: boolean, hardwareDecoders: Array<string>, 
+/**
+ * every component looked for and not found, such as `filter:subtitles`
+ * or `encoder:hevc`, for bug reports
+ */
+missing: Array<string>, };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AudioClass } from "./AudioClass";
+import type { Seconds } from "./Seconds";
+
+export type ClassifiedWaveform = { 
+/**
+ * time of the first value
+ */
+startTime: Seconds, samplePerSecond: number, peaks: Array<number>, 
+/**
+ * what each stretch of `peaks` sounds like, one for each
+ */
+classes: Array<AudioClass>, };
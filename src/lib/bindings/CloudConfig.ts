@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type CloudConfig = { 
+/**
+ * the API's base URL, like `https://api.openai.com/v1`
+ */
+endpoint: string, 
+/**
+ * like `whisper-1`
+ */
+model: string, 
+/**
+ * like `en`; absent to have it detected
+ */
+language: string | null, };
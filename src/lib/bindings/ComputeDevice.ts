@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ComputeDevice = { 
+/**
+ * of the GPU, for `DevicePreference::Gpu`; absent for the CPU
+ */
+index: number | null, name: string, 
+/**
+ * in bytes; absent for the CPU
+ */
+totalMemory: bigint | null, freeMemory: bigint | null, 
+/**
+ * by jobs of ours running on it
+ */
+reserved: bigint, };
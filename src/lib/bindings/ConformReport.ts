@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MediaFacts } from "./MediaFacts";
+import type { Retime } from "./Retime";
+
+export type ConformReport = { old: MediaFacts, new: MediaFacts, 
+/**
+ * what takes times in the old file to the same moment in the new;
+ * absent if they already line up
+ */
+retime: Retime | null, 
+/**
+ * the rate conversion the stretch was taken for, like `25/23.976`
+ */
+known: string | null, 
+/**
+ * how alike the loudness of the two is once retimed, as a correlation;
+ * absent unless both have audio
+ */
+similarity: number | null, 
+/**
+ * whether `similarity` is at least `MIN_SIMILARITY`, for `retime` to be
+ * applied without asking
+ */
+confident: boolean, };
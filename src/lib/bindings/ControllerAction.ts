@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type ControllerAction = { "kind": "seek", by: Seconds, } | { "kind": "seekTo", time: Seconds, } | { "kind": "step", frames: number, } | { "kind": "mark" } | { "kind": "markIn" } | { "kind": "markOut" } | { "kind": "playPause" } | { "kind": "play" } | { "kind": "pause" } | { "kind": "replay", seconds: number, };
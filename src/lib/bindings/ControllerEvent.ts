@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ControllerAction } from "./ControllerAction";
+import type { MidiMessage } from "./MidiMessage";
+
+export type ControllerEvent = { "event": "connected", "data": { name: string, } } | { "event": "action", "data": { action: ControllerAction, } } | { "event": "shuttle", "data": { rate: number, } } | { "event": "disconnected", "data": { reason: string, } } | { "event": "gain", "data": { db: number, } } | { "event": "midiMessage", "data": { message: MidiMessage, } } | { "event": "midiPorts", "data": { ports: Array<string>, } } | { "event": "listening", "data": { address: string, } } | { "event": "done", "data": Record<string, never> } | { "event": "runtimeError", "data": { what: string, } };
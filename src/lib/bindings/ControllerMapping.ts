@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ButtonBinding } from "./ButtonBinding";
+import type { ControllerAction } from "./ControllerAction";
+
+export type ControllerMapping = { 
+/**
+ * buttons not bound do nothing
+ */
+buttons: Array<ButtonBinding>, 
+/**
+ * for each notch the jog wheel is turned clockwise; turned the other
+ * way, the same backwards
+ */
+jog: ControllerAction, 
+/**
+ * playback rates with the shuttle turned right from 1 to 7, the last
+ * standing for any left out; turned left, the same in reverse
+ */
+shuttleRates: Array<number>, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type DamageSpan = { start: Seconds, 
+/**
+ * of the last damaged packet or frame in it
+ */
+end: Seconds, errors: number, corruptFrames: number, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SourceFormat } from "./SourceFormat";
+
+export type Detection = { format: SourceFormat, 
+/**
+ * from 0 to 1: 1 when the file starts as the format says it must,
+ * else how much of its start looks like it
+ */
+confidence: number, };
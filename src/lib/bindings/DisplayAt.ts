@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A display, by a point on it in the desktop's physical pixels and its
+ * scale factor, as the window system gives them
+ */
+export type DisplayAt = { x: number, y: number, scale: number, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * An open document as `SubtitleRegistry::snapshot` saw it, edits included
+ */
+export type DocumentSnapshot = { 
+/**
+ * the document itself, opaque to the frontend
+ */
+document: unknown, 
+/**
+ * where it was being autosaved
+ */
+autosave: string | null, };
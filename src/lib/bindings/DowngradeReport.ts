@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Loss } from "./Loss";
+
+export type DowngradeReport = { cues: number, 
+/**
+ * only the kinds that happened
+ */
+losses: Array<Loss>, };
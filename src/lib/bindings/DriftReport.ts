@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Retime } from "./Retime";
+import type { Seconds } from "./Seconds";
+
+export type DriftReport = { audioIndex: number, videoIndex: number, 
+/**
+ * from the first packet to the end of the last, by their timestamps
+ */
+audioSpan: [Seconds, Seconds], videoSpan: [Seconds, Seconds], 
+/**
+ * what takes times on the picture to where the same moment is in the
+ * audio, for `subtitle::retime`; absent if they already match
+ */
+correction: Retime | null, 
+/**
+ * the rate conversion the stretch was taken for, like `25/23.976`
+ */
+known: string | null, };
@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Letterbox } from "./Letterbox";
+import type { Marker } from "./Marker";
+import type { PositioningPolicy } from "./PositioningPolicy";
+import type { Region } from "./Region";
+import type { Seconds } from "./Seconds";
+import type { SplitPiece } from "./SplitPiece";
+import type { SubtitleCue } from "./SubtitleCue";
+import type { Take } from "./Take";
+import type { Word } from "./Word";
+
+export type Edit = { "op": "insert", 
+/**
+ * position among the events; clamped to the end
+ */
+at: number, start: Seconds, end: Seconds, style: string, text: string, } | { "op": "update", id: number, start: Seconds | null, end: Seconds | null, style: string | null, actor: string | null, 
+/**
+ * left, right, vertical; 0 means using the style's
+ */
+margins: [number, number, number] | null, text: string | null, } | { "op": "remove", id: number, } | { "op": "split", id: number, pieces: Array<SplitPiece>, } | { "op": "merge", ids: Array<number>, text: string, } | { "op": "restore", 
+/**
+ * position among the events; clamped to the end
+ */
+at: number, event: SubtitleCue, } | { "op": "setWords", eventId: number, words: Array<Word>, } | { "op": "setMarker", marker: Marker, } | { "op": "removeMarker", id: number, } | { "op": "setRegion", region: Region, } | { "op": "removeRegion", id: number, } | { "op": "setTake", take: Take, } | { "op": "removeTake", id: number, } | { "op": "setPositioning", policy: PositioningPolicy, letterbox: Letterbox | null, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type EventFilter = { 
+/**
+ * exact style name
+ */
+style: string | null, 
+/**
+ * exact actor name
+ */
+actor: string | null, 
+/**
+ * case-insensitive substring of the text
+ */
+text: string | null, };
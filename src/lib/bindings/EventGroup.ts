@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type EventGroup = { 
+/**
+ * for CPS buckets, the lower bound
+ */
+key: string, 
+/**
+ * one for each requested metric, in the same order
+ */
+values: Array<number>, };
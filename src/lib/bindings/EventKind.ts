@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Events that commands send besides their answer, which the frontend may
+ * not care for; the answer itself is always sent
+ */
+export type EventKind = "debug" | "progress";
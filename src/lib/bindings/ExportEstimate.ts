@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PlanProblem } from "./PlanProblem";
+import type { Seconds } from "./Seconds";
+
+export type ExportEstimate = { duration: Seconds, 
+/**
+ * in bytes, from the bitrates of the streams copied; video and audio
+ * that give none share what the source's overall bitrate leaves
+ */
+size: bigint, problems: Array<PlanProblem>, };
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type FrameDiff = { time: Seconds, 
+/**
+ * of all pixels, from 0 to 1
+ */
+meanDifference: number, 
+/**
+ * fraction of the blocks that changed
+ */
+changed: number, 
+/**
+ * `(x, y, width, height)` around the blocks that changed, in pixels of
+ * the export; absent if none did
+ */
+bounds: [number, number, number, number] | null, };
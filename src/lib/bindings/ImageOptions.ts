@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type ImageOptions = { framerate: number, 
+/**
+ * the file is the first of a numbered sequence, such as `shot0001.png`,
+ * rather than a single picture
+ */
+sequence: boolean, 
+/**
+ * how long a single picture is shown for; a sequence lasts as long as
+ * it has pictures
+ */
+duration: Seconds, };
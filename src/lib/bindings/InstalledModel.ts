@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ModelSource } from "./ModelSource";
+
+export type InstalledModel = { source: ModelSource, 
+/**
+ * to hand to the feature that uses it
+ */
+path: string, size: bigint, };
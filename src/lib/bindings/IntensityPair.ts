@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type IntensityPair = { 
+/**
+ * time of the first value of both
+ */
+startTime: Seconds, samplePerSecond: number, a: Array<number>, b: Array<number>, };
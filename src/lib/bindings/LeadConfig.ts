@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type LeadConfig = { 
+/**
+ * before speech starts
+ */
+leadIn: Seconds, 
+/**
+ * after it stops
+ */
+leadOut: Seconds, 
+/**
+ * how far from a cue's edge speech starting or stopping still belongs
+ * to it
+ */
+reach: Seconds, 
+/**
+ * kept between a cue and the ones before and after it
+ */
+minGap: Seconds, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Black bars of a video, as found by `detect_letterbox`, in pixels of the
+ * original frame
+ */
+export type Letterbox = { top: number, bottom: number, height: number, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type LinkedLibrary = { name: string, 
+/**
+ * as reported by the library at runtime
+ */
+linked: string, 
+/**
+ * what the bindings were generated against
+ */
+expected: string, 
+/**
+ * same major version, and at least the minor version expected
+ */
+compatible: boolean, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+/**
+ * Words in a row of one event that all fall below a threshold
+ */
+export type LowConfidenceSpan = { eventId: number, start: Seconds, end: Seconds, text: string, 
+/**
+ * of the least certain word in it
+ */
+confidence: number, };
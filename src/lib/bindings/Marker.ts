@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type Marker = { id: number, time: Seconds, label: string, 
+/**
+ * a CSS color, without commas; see `check_color`
+ */
+color: string, };
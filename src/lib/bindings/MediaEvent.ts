@@ -1,7 +1,20 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { AudioSamplerDeltaData } from "./AudioSamplerDeltaData";
+import type { Capabilities } from "./Capabilities";
+import type { Chapter } from "./Chapter";
+import type { ClassifiedWaveform } from "./ClassifiedWaveform";
+import type { DriftReport } from "./DriftReport";
+import type { ExportEstimate } from "./ExportEstimate";
+import type { FrameDiff } from "./FrameDiff";
+import type { InputDevice } from "./InputDevice";
+import type { IntensityPair } from "./IntensityPair";
+import type { PerformanceWarning } from "./PerformanceWarning";
+import type { SafeAreas } from "./SafeAreas";
 import type { Seconds } from "./Seconds";
+import type { SelfCheckReport } from "./SelfCheckReport";
+import type { StreamDamage } from "./StreamDamage";
 import type { StreamDescription } from "./StreamDescription";
+import type { VerificationReport } from "./VerificationReport";
 import type { VideoSamplerDeltaData } from "./VideoSamplerDeltaData";
 
-export type MediaEvent = { "event": "done", "data": Record<string, never> } | { "event": "mediaStatus", "data": { audioIndex: number, videoIndex: number, duration: Seconds, streams: Array<StreamDescription>, } } | { "event": "audioStatus", "data": { index: number, length: number, startTime: Seconds, sampleRate: number, } } | { "event": "videoStatus", "data": { index: number, framerate: number, isVfr: boolean, startTime: Seconds, sampleAspectRatio: number, size: [number, number], } } | { "event": "debug", "data": { message: string, } } | { "event": "runtimeError", "data": { what: string, } } | { "event": "opened", "data": { id: number, } } | { "event": "noStream", "data": Record<string, never> } | { "event": "invalidId", "data": Record<string, never> } | { "event": "ffmpegVersion", "data": { value: string, } } | { "event": "keyframeData", "data": { time: Seconds, bytePos: number, } } | { "event": "noKeyframeData", "data": Record<string, never> } | { "event": "sampleDone2", "data": { audio: AudioSamplerDeltaData | null, video: VideoSamplerDeltaData | null, isEof: boolean, } };
+export type MediaEvent = { "event": "done", "data": Record<string, never> } | { "event": "mediaStatus", "data": { audioIndex: number, videoIndex: number, audioOnly: boolean, duration: Seconds, streams: Array<StreamDescription>, startTimecode: string | null, timecodeOffsetMs: bigint, } } | { "event": "audioStatus", "data": { index: number, length: number, startTime: Seconds, sampleRate: number, } } | { "event": "videoStatus", "data": { index: number, framerate: number, isVfr: boolean, startTime: Seconds, sampleAspectRatio: number, size: [number, number], } } | { "event": "subpictureStatus", "data": { index: number, codec: string, canvasSize: [number, number], } } | { "event": "safeAreas", "data": { areas: SafeAreas, } } | { "event": "onscreenText", "data": { intervals: Array<[Seconds, Seconds]>, } } | { "event": "letterbox", "data": { bars: [number, number] | null, height: number, } } | { "event": "progress", "data": { fraction: number, } } | { "event": "framesExported", "data": { paths: Array<string>, } } | { "event": "framesCompared", "data": { diffs: Array<FrameDiff>, } } | { "event": "chapters", "data": { chapters: Array<Chapter>, } } | { "event": "intensityPair", "data": { pair: IntensityPair, } } | { "event": "verified", "data": { report: VerificationReport, } } | { "event": "exportEstimated", "data": { estimate: ExportEstimate, } } | { "event": "decodeDamage", "data": { streams: Array<StreamDamage>, } } | { "event": "drift", "data": { report: DriftReport, } } | { "event": "colorManagement", "data": { on: boolean, display: string | null, } } | { "event": "capabilities", "data": { capabilities: Capabilities, } } | { "event": "selfCheck", "data": { report: SelfCheckReport, } } | { "event": "performanceWarning", "data": { warning: PerformanceWarning, } } | { "event": "unsupported", "data": { feature: string, } } | { "event": "pathRejected", "data": { reason: string, } } | { "event": "dataNotYetAvailable", "data": { playableUntil: Seconds, } } | { "event": "availability", "data": { playableUntil: Seconds, complete: boolean, } } | { "event": "seekIndexBuilt", "data": { keyframes: number, } } | { "event": "waveform", "data": { waveform: ClassifiedWaveform, } } | { "event": "inputDevices", "data": { devices: Array<InputDevice>, } } | { "event": "debug", "data": { message: string, } } | { "event": "runtimeError", "data": { what: string, } } | { "event": "opened", "data": { id: number, } } | { "event": "groupOpened", "data": { id: number, offsets: Array<Seconds>, } } | { "event": "noStream", "data": Record<string, never> } | { "event": "noVideo", "data": Record<string, never> } | { "event": "invalidId", "data": Record<string, never> } | { "event": "ffmpegVersion", "data": { value: string, } } | { "event": "keyframeData", "data": { time: Seconds, bytePos: number, } } | { "event": "noKeyframeData", "data": Record<string, never> } | { "event": "sampleDone2", "data": { audio: AudioSamplerDeltaData | null, video: VideoSamplerDeltaData | null, isEof: boolean, } };
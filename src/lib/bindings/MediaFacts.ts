@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type MediaFacts = { duration: Seconds, 
+/**
+ * of the default video stream, on average
+ */
+framerate: number | null, audioIndex: number | null, videoIndex: number | null, };
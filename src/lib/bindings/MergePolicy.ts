@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Separator } from "./Separator";
+
+export type MergePolicy = { separator: Separator, 
+/**
+ * Drops the marks that carry a sentence over from one event to the
+ * next, an ellipsis or a dash at the end of one and the start of the
+ * other, where they meet; only if the next goes on with the sentence,
+ * by starting with such a mark or in lower case. Not for dialogue.
+ */
+removeContinuation: boolean, 
+/**
+ * Drops an `{\i0}` ending one event and an `{\i1}` starting the next,
+ * so that the italics run on
+ */
+joinItalics: boolean, };
@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ControllerAction } from "./ControllerAction";
+import type { MidiControl } from "./MidiControl";
+
+export type MidiBinding = { control: MidiControl, action: ControllerAction, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type MidiControl = { "kind": "note", channel: number, number: number, } | { "kind": "control", channel: number, number: number, };
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MidiBinding } from "./MidiBinding";
+import type { MidiControl } from "./MidiControl";
+
+export type MidiMapping = { 
+/**
+ * done when a note is struck or a control goes up past its middle
+ */
+bindings: Array<MidiBinding>, 
+/**
+ * a control change for the monitoring gain
+ */
+gain: MidiControl | null, 
+/**
+ * the gain at the bottom and top of `gain`, in dB
+ */
+gainRange: [number, number], };
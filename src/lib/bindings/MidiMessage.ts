@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MidiControl } from "./MidiControl";
+
+/**
+ * A message as reported in learn mode
+ */
+export type MidiMessage = { control: MidiControl, 
+/**
+ * velocity or value, from 0 to 127; 0 for a note released
+ */
+value: number, };
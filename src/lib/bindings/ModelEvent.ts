@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InstalledModel } from "./InstalledModel";
+import type { ToolPath } from "./ToolPath";
+
+export type ModelEvent = { "event": "models", "data": { models: Array<InstalledModel>, } } | { "event": "progress", "data": { received: bigint, total: bigint | null, } } | { "event": "installed", "data": { model: InstalledModel, } } | { "event": "tools", "data": { tools: Array<ToolPath>, } } | { "event": "done", "data": Record<string, never> } | { "event": "runtimeError", "data": { what: string, } };
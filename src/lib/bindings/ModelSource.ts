@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ModelKind } from "./ModelKind";
+
+export type ModelSource = { 
+/**
+ * the file it's kept as, like `ggml-base.en.bin`
+ */
+name: string, kind: ModelKind, url: string, 
+/**
+ * in hex
+ */
+sha256: string, };
@@ -0,0 +1,25 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Attachment } from "./Attachment";
+import type { Chapter } from "./Chapter";
+import type { Container } from "./Container";
+import type { StripMetadata } from "./StripMetadata";
+import type { SubtitleTrack } from "./SubtitleTrack";
+import type { TrackMetadata } from "./TrackMetadata";
+
+export type MuxPlan = { source: string, 
+/**
+ * streams of `source` to copy; empty for all of its video and audio
+ */
+streams: Array<number>, subtitles: Array<SubtitleTrack>, 
+/**
+ * font files to attach
+ */
+fonts: Array<Attachment>, chapters: Array<Chapter>, 
+/**
+ * of the whole file
+ */
+title: string | null, 
+/**
+ * the application named as having written the file
+ */
+encoder: string | null, tracks: Array<TrackMetadata>, strip: StripMetadata, container: Container, output: string, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Something the parser had to skip or guess. Parsing never fails because
+ * of these; they are reported so the user knows what was lost.
+ */
+export type ParseIssue = { 
+/**
+ * 1-based; 0 if the issue isn't tied to a line
+ */
+line: number, message: string, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SpanTiming } from "./SpanTiming";
+
+export type PerformanceWarning = { command: string, elapsedMs: number, budgetMs: number, 
+/**
+ * slowest first
+ */
+spans: Array<SpanTiming>, };
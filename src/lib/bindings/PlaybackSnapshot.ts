@@ -0,0 +1,45 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BackendKind } from "./BackendKind";
+import type { DisplayAt } from "./DisplayAt";
+import type { ImageOptions } from "./ImageOptions";
+import type { Seconds } from "./Seconds";
+
+/**
+ * What is needed to open a session again as it was. Only the players are
+ * described: analysis sinks belong to one-off commands that start them
+ * afresh.
+ */
+export type PlaybackSnapshot = { path: string, 
+/**
+ * what decoded it; see `backend`
+ */
+backend: BackendKind, 
+/**
+ * opened with `create_tolerant`
+ */
+tolerant: boolean, audioIndex: number | null, 
+/**
+ * target of the audio player's auto gain
+ */
+autoGain: number | null, videoIndex: number | null, accel: boolean, outputSize: [number, number] | null, 
+/**
+ * see `set_color_management`; the display's profile is read again
+ */
+colorManaged: boolean, 
+/**
+ * the display it was managed for; `None` for the main one
+ */
+display: DisplayAt | null, subpictureIndex: number | null, 
+/**
+ * opened with `create_images`
+ */
+images: ImageOptions | null, 
+/**
+ * the files of a group, in order, `path` being the first; see `group`
+ */
+members: Array<string>, 
+/**
+ * where decoding had got to; a little past what was on screen, as the
+ * frontend buffers ahead
+ */
+position: Seconds, };
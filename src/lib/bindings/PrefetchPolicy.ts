@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type PrefetchPolicy = { 
+/**
+ * the starts of the cues that can be jumped to, as listed
+ */
+cues: Array<Seconds>, 
+/**
+ * how many cues after the position to keep decoded
+ */
+ahead: number, 
+/**
+ * and before it
+ */
+behind: number, 
+/**
+ * how much is decoded from each start; video is kept at the player's
+ * size, so a second of it can take hundreds of megabytes
+ */
+window: Seconds, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type Region = { id: number, start: Seconds, 
+/**
+ * equal to `start` for a point label
+ */
+end: Seconds, label: string, };
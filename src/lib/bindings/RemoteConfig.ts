@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type RemoteConfig = { port: number, token: string, 
+/**
+ * listen on every network interface, for devices other than this
+ * computer; otherwise only on loopback
+ */
+allInterfaces: boolean, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RepairKind } from "./RepairKind";
+
+export type Repair = { kind: RepairKind, 
+/**
+ * 1-based
+ */
+lines: Array<number>, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SearchHit } from "./SearchHit";
+
+export type SearchGroup = { documentId: number, 
+/**
+ * how many hits there are, of which `hits` has the first few
+ */
+total: number, hits: Array<SearchHit>, };
@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type SearchHit = { "kind": "event", id: number, start: Seconds, end: Seconds, actor: string, text: string, } | { "kind": "marker", id: number, time: Seconds, label: string, } | { "kind": "region", id: number, start: Seconds, end: Seconds, label: string, };
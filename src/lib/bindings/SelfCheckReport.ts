@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Check } from "./Check";
+import type { LinkedLibrary } from "./LinkedLibrary";
+
+export type SelfCheckReport = { 
+/**
+ * whether every check passed
+ */
+passed: boolean, 
+/**
+ * `av_version_info`, such as `7.1` or a git describe
+ */
+version: string, libraries: Array<LinkedLibrary>, checks: Array<Check>, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DocumentSnapshot } from "./DocumentSnapshot";
+import type { PlaybackSnapshot } from "./PlaybackSnapshot";
+
+export type SessionSnapshot = { 
+/**
+ * by the id they had
+ */
+playbacks: Array<[number, PlaybackSnapshot]>, documents: Array<[number, DocumentSnapshot]>, };
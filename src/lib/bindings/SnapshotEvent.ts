@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SessionSnapshot } from "./SessionSnapshot";
+
+export type SnapshotEvent = { "event": "snapshot", "data": { snapshot: SessionSnapshot, } } | { "event": "restored", "data": { 
+/**
+ * pairs of the id in the snapshot and the new one
+ */
+playbacks: Array<[number, number]>, documents: Array<[number, number]>, 
+/**
+ * what couldn't be reopened, and why
+ */
+failures: Array<string>, } } | { "event": "runtimeError", "data": { what: string, } };
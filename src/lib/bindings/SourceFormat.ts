@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What a file holds, by its text rather than its name
+ */
+export type SourceFormat = "ass" | "sami" | "microDvd" | "srt";
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * All the spans of one name within a command
+ */
+export type SpanTiming = { name: string, count: number, 
+/**
+ * time spent inside these spans, including their own children
+ */
+totalMs: number, };
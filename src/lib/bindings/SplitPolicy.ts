@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type SplitPolicy = { 
+/**
+ * longest a piece may last
+ */
+maxDuration: Seconds, 
+/**
+ * most characters a piece may have, not counting tags and line breaks
+ */
+maxChars: number, 
+/**
+ * shortest a piece should last; shorter ones are avoided, not ruled out
+ */
+minDuration: Seconds, };
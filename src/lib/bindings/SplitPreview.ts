@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type SplitPreview = { eventId: number, 
+/**
+ * the proposed split, as asked
+ */
+time: Seconds, 
+/**
+ * time of the first value of `peaks`
+ */
+startTime: Seconds, samplePerSecond: number, peaks: Array<number>, 
+/**
+ * the pause in speech nearest to `time`, inside the cue; absent if
+ * there is none in reach
+ */
+gap: [Seconds, Seconds] | null, 
+/**
+ * the middle of `gap`, where the split would snap to
+ */
+snap: Seconds | null, };
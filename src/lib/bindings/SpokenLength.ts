@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+/**
+ * How a cue said out loud compares with the time it's on screen
+ */
+export type SpokenLength = { eventId: number, spoken: Seconds, 
+/**
+ * how long the event lasts
+ */
+available: Seconds, 
+/**
+ * `spoken` less `available`: positive if the line runs long
+ */
+overrun: Seconds, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FormattingRule } from "./FormattingRule";
+import type { PositionRule } from "./PositionRule";
+
+export type SrtRules = { positioning: PositionRule, formatting: FormattingRule, 
+/**
+ * events with the same times and placement become one cue, and lines
+ * repeated on several layers, as signs often are, are kept once
+ */
+mergeLayers: boolean, 
+/**
+ * added to every time, as to write in a program's timecode; see
+ * `media_api::set_timecode_offset`
+ */
+timeOffsetMs: bigint, };
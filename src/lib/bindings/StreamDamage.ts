@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DamageSpan } from "./DamageSpan";
+
+export type StreamDamage = { index: number, 
+/**
+ * packets refused
+ */
+errors: number, corruptFrames: number, spans: Array<DamageSpan>, };
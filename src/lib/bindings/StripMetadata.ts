@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What to leave out, for sharing files without what they would give away
+ * about the source and the tools
+ */
+export type StripMetadata = { 
+/**
+ * tags of the source's streams, such as encoder, handler names,
+ * creation times and statistics; only those the plan sets are written
+ */
+sourceTags: boolean, 
+/**
+ * the version of the muxing library, which is written by default
+ */
+muxerVersion: boolean, };
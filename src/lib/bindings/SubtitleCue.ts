@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type SubtitleCue = { id: number, start: Seconds, end: Seconds, style: string, actor: string, effect: string, layer: number, 
+/**
+ * left, right, vertical; 0 means using the style's
+ */
+margins: [number, number, number], isComment: boolean, 
+/**
+ * lines are separated by `\n`
+ */
+text: string, };
@@ -0,0 +1,60 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ComputeDevice } from "./ComputeDevice";
+import type { ConformReport } from "./ConformReport";
+import type { Detection } from "./Detection";
+import type { DowngradeReport } from "./DowngradeReport";
+import type { Edit } from "./Edit";
+import type { EventGroup } from "./EventGroup";
+import type { Letterbox } from "./Letterbox";
+import type { LowConfidenceSpan } from "./LowConfidenceSpan";
+import type { Marker } from "./Marker";
+import type { ParseIssue } from "./ParseIssue";
+import type { PerformanceWarning } from "./PerformanceWarning";
+import type { PositioningPolicy } from "./PositioningPolicy";
+import type { QcIssue } from "./QcIssue";
+import type { Region } from "./Region";
+import type { Repair } from "./Repair";
+import type { SearchGroup } from "./SearchGroup";
+import type { Seconds } from "./Seconds";
+import type { SplitPiece } from "./SplitPiece";
+import type { SplitPreview } from "./SplitPreview";
+import type { SpokenLength } from "./SpokenLength";
+import type { SubtitleCue } from "./SubtitleCue";
+import type { SubtitleFormat } from "./SubtitleFormat";
+import type { Take } from "./Take";
+import type { TextFormat } from "./TextFormat";
+import type { TranscriptSegment } from "./TranscriptSegment";
+import type { Word } from "./Word";
+
+export type SubtitleEvent = { "event": "done", "data": Record<string, never> } | { "event": "opened", "data": { id: number, format: SubtitleFormat, issues: Array<ParseIssue>, textFormat: TextFormat, 
+/**
+ * some bytes couldn't be decoded and were replaced with U+FFFD
+ */
+lossyDecoding: boolean, } } | { "event": "formatDetected", "data": { detection: Detection, } } | { "event": "repaired", "data": { repairs: Array<Repair>, } } | { "event": "recovered", "data": { id: number, format: SubtitleFormat, textFormat: TextFormat, 
+/**
+ * edits replayed from the journal
+ */
+replayed: number, 
+/**
+ * the last edit was only partly written and has been lost
+ */
+truncated: boolean, } } | { "event": "qcResult", "data": { issues: Array<QcIssue>, } } | { "event": "deduplicated", "data": { removed: Array<number>, } } | { "event": "splitProposed", "data": { eventId: number, pieces: Array<SplitPiece>, } } | { "event": "splitPreviewed", "data": { preview: SplitPreview, } } | { "event": "splitPreviewSkipped", "data": Record<string, never> } | { "event": "leadSuggested", "data": { edits: Array<Edit>, } } | { "event": "retimeSuggested", "data": { edits: Array<Edit>, } } | { "event": "conformed", "data": { report: ConformReport, applied: boolean, undo: Array<Edit>, } } | { "event": "merged", "data": { eventId: number, undo: Array<Edit>, } } | { "event": "positioning", "data": { policy: PositioningPolicy, letterbox: Letterbox | null, 
+/**
+ * vertical margins, in script coordinates, that the preview uses
+ * for events aligned to the top or bottom edge; absent if the policy
+ * doesn't constrain them
+ */
+marginTop: number | null, marginBottom: number | null, } } | { "event": "markers", "data": { markers: Array<Marker>, } } | { "event": "markerAdded", "data": { markerId: number, } } | { "event": "nextMarker", "data": { marker: Marker | null, } } | { "event": "regions", "data": { regions: Array<Region>, } } | { "event": "regionAdded", "data": { regionId: number, } } | { "event": "takes", "data": { takes: Array<Take>, } } | { "event": "recordingStarted", "data": { from: Seconds, } } | { "event": "takeRecorded", "data": { take: Take, } } | { "event": "spoken", "data": { path: string, length: SpokenLength, } } | { "event": "spokenLengths", "data": { lengths: Array<SpokenLength>, } } | { "event": "computeDevices", "data": { devices: Array<ComputeDevice>, } } | { "event": "computePlaced", "data": { device: number | null, fallback: string | null, } } | { "event": "transcribed", "data": { segment: TranscriptSegment, } } | { "event": "lowConfidenceSpans", "data": { spans: Array<LowConfidenceSpan>, } } | { "event": "realigned", "data": { eventId: number, words: Array<Word>, } } | { "event": "progress", "data": { fraction: number, } } | { "event": "saved", "data": { 
+/**
+ * some characters couldn't be represented in the target encoding
+ */
+lossyEncoding: boolean, 
+/**
+ * what the format has no place for and was left out, of
+ * `markers`, `regions`, `takes` and `words`; only ASS keeps them
+ */
+unsaved: Array<string>, } } | { "event": "convertedToSrt", "data": { report: DowngradeReport, lossyEncoding: boolean, } } | { "event": "events", "data": { 
+/**
+ * number of events that pass the filter
+ */
+total: number, events: Array<SubtitleCue>, } } | { "event": "aggregated", "data": { groups: Array<EventGroup>, } } | { "event": "searchResult", "data": { groups: Array<SearchGroup>, } } | { "event": "framerateRequired", "data": Record<string, never> } | { "event": "unknownFormat", "data": Record<string, never> } | { "event": "pathRejected", "data": { reason: string, } } | { "event": "performanceWarning", "data": { warning: PerformanceWarning, } } | { "event": "invalidId", "data": Record<string, never> } | { "event": "runtimeError", "data": { what: string, } };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SubtitleStyleInfo = { name: string, 
+/**
+ * the remaining fields of an ASS style line in canonical V4+ order;
+ * empty for styles that didn't come from ASS
+ */
+assFields: Array<string>, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type SubtitleTrack = { path: string, name: string, 
+/**
+ * ISO 639-2, like `eng`
+ */
+language: string, default: boolean, forced: boolean, };
@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type Take = { id: number, eventId: number, path: string, 
+/**
+ * where the recording starts relative to the event's start; negative
+ * with pre-roll
+ */
+offset: Seconds, length: Seconds, selected: boolean, 
+/**
+ * cut from the start of the recording when it is played
+ */
+trimStart: Seconds, 
+/**
+ * and from its end
+ */
+trimEnd: Seconds, 
+/**
+ * in dB
+ */
+gain: number, };
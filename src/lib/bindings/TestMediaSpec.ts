@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type TestMediaSpec = { duration: Seconds, size: [number, number], framerate: number, 
+/**
+ * frequency of the tone in Hz; no audio stream if absent
+ */
+tone: number | null, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LineEnding } from "./LineEnding";
+
+/**
+ * How a text file was stored on disk, so it can be written back the same
+ * way
+ */
+export type TextFormat = { 
+/**
+ * an encoding_rs label
+ */
+encoding: string, bom: boolean, lineEnding: LineEnding, };
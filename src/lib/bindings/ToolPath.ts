@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Tool } from "./Tool";
+
+export type ToolPath = { tool: Tool, 
+/**
+ * absent if it hasn't been chosen
+ */
+path: string | null, };
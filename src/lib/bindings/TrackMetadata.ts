@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Tags to set on one of the streams copied from the source
+ */
+export type TrackMetadata = { 
+/**
+ * index in the source
+ */
+stream: number, title: string | null, 
+/**
+ * ISO 639-2, like `eng`
+ */
+language: string | null, };
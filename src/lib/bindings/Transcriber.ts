@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DevicePreference } from "./DevicePreference";
+
+export type Transcriber = { 
+/**
+ * the `ggml` model
+ */
+model: string, 
+/**
+ * like `en`; absent to have it detected
+ */
+language: string | null, threads: number | null, 
+/**
+ * where to run it; see `compute`
+ */
+device: DevicePreference, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+import type { TranscriptWord } from "./TranscriptWord";
+
+export type TranscriptSegment = { start: Seconds, end: Seconds, text: string, 
+/**
+ * empty if the provider gave none
+ */
+words?: Array<TranscriptWord>, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Seconds } from "./Seconds";
+
+export type TranscriptWord = { start: Seconds, end: Seconds, text: string, 
+/**
+ * from 0 to 1; absent if the provider didn't say
+ */
+confidence: number | null, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Check } from "./Check";
+
+export type VerificationReport = { 
+/**
+ * whether every check passed
+ */
+passed: boolean, checks: Array<Check>, };
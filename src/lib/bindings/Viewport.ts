@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The preview area, in physical pixels of the window
+ */
+export type Viewport = { x: number, y: number, width: number, height: number, };
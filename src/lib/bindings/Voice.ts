@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Voice = { 
+/**
+ * the `.onnx` voice model
+ */
+model: string, 
+/**
+ * for models with several speakers
+ */
+speaker: number | null, 
+/**
+ * speaking rate; above 1 is slower
+ */
+lengthScale: number | null, };